@@ -0,0 +1,101 @@
+use crate::{error::Error, storage::StorageImpl, AppState};
+use futures::StreamExt;
+use pesde::{
+    names::PackageName,
+    source::{pesde::ArchiveCompression, version_id::VersionId},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// A file in the archive of a version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveFile {
+    /// The size of the file, in bytes
+    pub size: u64,
+    /// The SHA-256 checksum of the file's content
+    pub hash: String,
+}
+
+/// Lists the files of an archive by their paths, which always use forward slashes
+pub async fn list_files(archive: &[u8]) -> std::io::Result<BTreeMap<String, ArchiveFile>> {
+    // archives read from an HTTP storage may have already been decompressed
+    let reader: Box<dyn AsyncRead + Unpin + Send + '_> = match ArchiveCompression::detect(archive) {
+        Some(compression) => compression.decoder(archive),
+        None => Box::new(archive),
+    };
+    let mut archive = tokio_tar::Archive::new(reader);
+
+    let mut files = BTreeMap::new();
+    let mut entries = archive.entries()?;
+    let mut buf = vec![0; 8 * 1024];
+
+    while let Some(entry) = entries.next().await {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path()?.to_string_lossy().replace('\\', "/");
+        let path = path.trim_start_matches("./").to_string();
+
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+        loop {
+            let read = entry.read(&mut buf).await?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buf[..read]);
+            size += read as u64;
+        }
+
+        files.insert(
+            path,
+            ArchiveFile {
+                size,
+                hash: format!("{:x}", hasher.finalize()),
+            },
+        );
+    }
+
+    Ok(files)
+}
+
+/// The files of the archive of a version, from the listing cached at publish time. Versions
+/// published before listings were cached have theirs listed from their archive and cached.
+/// Returns `None` if the archive is missing or can't be read
+pub async fn read_file_list(
+    app_state: &AppState,
+    name: &PackageName,
+    v_id: &VersionId,
+) -> Result<Option<BTreeMap<String, ArchiveFile>>, Error> {
+    if let Some(contents) = app_state.storage.get().read_file_list(name, v_id).await? {
+        match serde_json::from_slice(&contents) {
+            Ok(files) => return Ok(Some(files)),
+            Err(e) => tracing::warn!("invalid file listing of {name}@{v_id}, relisting: {e}"),
+        }
+    }
+
+    let Some(archive) = app_state.storage.get().read_package(name, v_id).await? else {
+        tracing::error!("archive of {name}@{v_id} is missing from the storage");
+        return Ok(None);
+    };
+
+    let files = match list_files(&archive).await {
+        Ok(files) => files,
+        Err(e) => {
+            tracing::error!("failed to read archive of {name}@{v_id}: {e}");
+            return Ok(None);
+        }
+    };
+
+    app_state
+        .storage
+        .get()
+        .store_file_list(name, v_id, serde_json::to_vec(&files)?)
+        .await?;
+
+    Ok(Some(files))
+}
@@ -0,0 +1,373 @@
+use crate::{
+    auth::UserId,
+    owners::{Owner, Role},
+    scan::{Finding, ScanAction},
+    webhooks::Event,
+    AppState,
+};
+use actix_web::{web, HttpRequest};
+use chrono::{DateTime, Utc};
+use fs_err::tokio as fs;
+use pesde::{manifest::target::TargetKind, names::PackageName};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use utoipa::{
+    openapi::{
+        schema::{AdditionalProperties, Type},
+        Object, ObjectBuilder,
+    },
+    ToSchema,
+};
+
+/// A mutating action taken on the registry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    Publish {
+        package: PackageName,
+        version: Version,
+        target: TargetKind,
+    },
+    Yank {
+        package: PackageName,
+        version: Version,
+        target: String,
+    },
+    Unyank {
+        package: PackageName,
+        version: Version,
+        target: String,
+    },
+    Deprecate {
+        package: PackageName,
+        versions: String,
+        message: String,
+    },
+    Undeprecate {
+        package: PackageName,
+        versions: String,
+    },
+    CreateToken {
+        id: String,
+        name: String,
+    },
+    RevokeToken {
+        id: String,
+    },
+    AddTrustedPublisher {
+        package: PackageName,
+        id: String,
+        repository: String,
+        workflow: String,
+    },
+    RemoveTrustedPublisher {
+        package: PackageName,
+        id: String,
+    },
+    AddOwner {
+        scope: String,
+        /// The package the owner was added to, or `None` if they were added to the whole scope
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        package: Option<PackageName>,
+        owner: Owner,
+        /// Whether the owner was added as an owner or only as a publisher
+        #[serde(default, skip_serializing_if = "Role::is_owner")]
+        role: Role,
+    },
+    RemoveOwner {
+        scope: String,
+        /// The package the owner was removed from, or `None` if they were removed from the whole
+        /// scope
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        package: Option<PackageName>,
+        owner: Owner,
+        #[serde(default, skip_serializing_if = "Role::is_owner")]
+        role: Role,
+    },
+    Remove {
+        package: PackageName,
+        /// The version which was removed, or `None` if the whole package was
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        version: Option<Version>,
+        /// The target which was removed, or `None` if all of the version's were
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        target: Option<TargetKind>,
+        reason: String,
+    },
+    Ban {
+        user: u64,
+        reason: String,
+    },
+    /// A published version which the scanners found something in
+    Scan {
+        package: PackageName,
+        version: Version,
+        target: TargetKind,
+        /// Whether the version was rejected or quarantined
+        outcome: ScanAction,
+        findings: Vec<Finding>,
+    },
+    Unban {
+        user: u64,
+    },
+    /// A partially published version which was rolled back
+    Rollback {
+        package: PackageName,
+        version: Version,
+        target: TargetKind,
+    },
+    /// Versions which were imported from an index snapshot
+    Import {
+        packages: usize,
+        versions: usize,
+    },
+    CreateUser {
+        user: u64,
+        name: String,
+    },
+    DeleteUser {
+        user: u64,
+    },
+    CreateKey {
+        user: u64,
+        id: String,
+        name: String,
+    },
+    RevokeKey {
+        user: u64,
+        id: String,
+    },
+}
+
+impl Action {
+    /// The package the action affected, if any
+    pub fn package(&self) -> Option<&PackageName> {
+        match self {
+            Action::Publish { package, .. }
+            | Action::Yank { package, .. }
+            | Action::Unyank { package, .. }
+            | Action::Deprecate { package, .. }
+            | Action::Undeprecate { package, .. }
+            | Action::AddTrustedPublisher { package, .. }
+            | Action::RemoveTrustedPublisher { package, .. }
+            | Action::Remove { package, .. }
+            | Action::Scan { package, .. }
+            | Action::Rollback { package, .. } => Some(package),
+            Action::AddOwner { package, .. } | Action::RemoveOwner { package, .. } => {
+                package.as_ref()
+            }
+            Action::CreateToken { .. }
+            | Action::RevokeToken { .. }
+            | Action::Ban { .. }
+            | Action::Unban { .. }
+            | Action::Import { .. }
+            | Action::CreateUser { .. }
+            | Action::DeleteUser { .. }
+            | Action::CreateKey { .. }
+            | Action::RevokeKey { .. } => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Publish { .. } => "publish",
+            Action::Yank { .. } => "yank",
+            Action::Unyank { .. } => "unyank",
+            Action::Deprecate { .. } => "deprecate",
+            Action::Undeprecate { .. } => "undeprecate",
+            Action::CreateToken { .. } => "create_token",
+            Action::RevokeToken { .. } => "revoke_token",
+            Action::AddTrustedPublisher { .. } => "add_trusted_publisher",
+            Action::RemoveTrustedPublisher { .. } => "remove_trusted_publisher",
+            Action::AddOwner { .. } => "add_owner",
+            Action::RemoveOwner { .. } => "remove_owner",
+            Action::Remove { .. } => "remove",
+            Action::Ban { .. } => "ban",
+            Action::Unban { .. } => "unban",
+            Action::Scan { .. } => "scan",
+            Action::Rollback { .. } => "rollback",
+            Action::Import { .. } => "import",
+            Action::CreateUser { .. } => "create_user",
+            Action::DeleteUser { .. } => "delete_user",
+            Action::CreateKey { .. } => "create_key",
+            Action::RevokeKey { .. } => "revoke_key",
+        }
+    }
+}
+
+impl From<Event> for Action {
+    fn from(event: Event) -> Self {
+        match event {
+            Event::Publish {
+                package,
+                version,
+                target,
+            } => Action::Publish {
+                package,
+                version,
+                target,
+            },
+            Event::Yank {
+                package,
+                version,
+                target,
+            } => Action::Yank {
+                package,
+                version,
+                target,
+            },
+            Event::Unyank {
+                package,
+                version,
+                target,
+            } => Action::Unyank {
+                package,
+                version,
+                target,
+            },
+            Event::Deprecate {
+                package,
+                versions,
+                message,
+            } => Action::Deprecate {
+                package,
+                versions,
+                message,
+            },
+            Event::Undeprecate { package, versions } => Action::Undeprecate { package, versions },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    /// The user who took the action
+    pub actor: u64,
+    /// The IP address the action was taken from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    #[serde(flatten)]
+    #[schema(schema_with = action_schema)]
+    pub action: Action,
+}
+
+/// The action of an audit log entry, whose other fields depend on it
+fn action_schema() -> Object {
+    ObjectBuilder::new()
+        .schema_type(Type::Object)
+        .property(
+            "action",
+            ObjectBuilder::new()
+                .schema_type(Type::String)
+                .description(Some("What was done, such as `publish` or `yank`")),
+        )
+        .required("action")
+        .additional_properties(Some(AdditionalProperties::FreeForm(true)))
+        .build()
+}
+
+/// Filters for querying the audit log
+#[derive(Debug, Default)]
+pub struct AuditQuery {
+    pub actor: Option<UserId>,
+    pub package: Option<PackageName>,
+    pub action: Option<String>,
+    /// Only entries before this time are returned, to page through the log
+    pub before: Option<DateTime<Utc>>,
+    pub limit: usize,
+}
+
+/// An append-only log of the mutating actions taken on the registry, stored as JSON lines in a
+/// file of the data directory
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    file: tokio::sync::Mutex<fs::File>,
+}
+
+impl AuditLog {
+    pub async fn open(path: PathBuf) -> std::io::Result<Self> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+
+        Ok(Self {
+            path,
+            file: tokio::sync::Mutex::new(file),
+        })
+    }
+
+    /// Records an action taken by the user in the request. Failing to do so doesn't fail the
+    /// request, since the action has already been taken
+    pub async fn record(&self, request: &HttpRequest, actor: UserId, action: impl Into<Action>) {
+        let ip = request
+            .app_data::<web::Data<AppState>>()
+            .and_then(|app_state| app_state.rate_limits.get().client_ip(request))
+            .map(|ip| ip.to_string());
+
+        self.record_from(ip, actor, action).await
+    }
+
+    /// Records an action taken by the user from the IP address, for actions which are finished
+    /// after their request, such as queued publishes
+    pub async fn record_from(&self, ip: Option<String>, actor: UserId, action: impl Into<Action>) {
+        let entry = AuditEntry {
+            timestamp: Utc::now(),
+            actor: actor.0,
+            ip,
+            action: action.into(),
+        };
+
+        let mut line = serde_json::to_string(&entry).unwrap();
+        line.push('\n');
+
+        let mut file = self.file.lock().await;
+        if let Err(e) = async {
+            file.write_all(line.as_bytes()).await?;
+            file.flush().await
+        }
+        .await
+        {
+            tracing::error!("failed to write audit log entry {entry:?}: {e}");
+        }
+    }
+
+    /// The entries matching the query, newest first
+    pub async fn query(&self, query: &AuditQuery) -> std::io::Result<Vec<AuditEntry>> {
+        // read without the lock so that queries don't hold up actions being recorded. An entry
+        // which is being written may be read partially, so everything after the last complete
+        // line is skipped
+        let mut contents = fs::read_to_string(&self.path).await?;
+        contents.truncate(contents.rfind('\n').map_or(0, |i| i + 1));
+
+        Ok(contents
+            .lines()
+            .rev()
+            .filter_map(|line| match serde_json::from_str::<AuditEntry>(line) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    tracing::warn!("skipping invalid audit log entry: {e}");
+                    None
+                }
+            })
+            .filter(|entry| {
+                query.actor.is_none_or(|actor| entry.actor == actor.0)
+                    && query
+                        .package
+                        .as_ref()
+                        .is_none_or(|package| entry.action.package() == Some(package))
+                    && query
+                        .action
+                        .as_ref()
+                        .is_none_or(|action| entry.action.name() == action)
+                    && query.before.is_none_or(|before| entry.timestamp < before)
+            })
+            .take(query.limit)
+            .collect())
+    }
+}
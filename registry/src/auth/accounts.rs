@@ -0,0 +1,213 @@
+use crate::{
+    auth::{
+        api_tokens::{hash_token, random_hex},
+        get_token_from_req, AuthImpl, UserId,
+    },
+    write_json_atomically,
+};
+use actix_web::{dev::ServiceRequest, Error as ActixError};
+use chrono::{DateTime, Utc};
+use fs_err::tokio as fs;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt::Display, path::PathBuf};
+
+/// The prefix of the API keys of accounts, which tells them apart from API tokens
+pub const KEY_PREFIX: &str = "pesdekey_";
+
+/// A user managed by the registry itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    pub id: u64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A key authenticating requests as the account it belongs to, with all of its permissions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: String,
+    pub user_id: u64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+fn first_id() -> u64 {
+    1
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AccountsData {
+    /// The ID the next account is given. IDs aren't reused, as ownership records refer to them
+    #[serde(default = "first_id")]
+    next_id: u64,
+    #[serde(default)]
+    users: BTreeMap<u64, Account>,
+    /// The keys, by their hex-encoded hash
+    #[serde(default)]
+    keys: BTreeMap<String, ApiKey>,
+}
+
+impl Default for AccountsData {
+    fn default() -> Self {
+        Self {
+            next_id: first_id(),
+            users: BTreeMap::new(),
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+/// Users and their API keys, managed by the registry's administrators and persisted in a file of
+/// the data directory, for registries which don't rely on a third party for authentication. Only
+/// the SHA-256 hashes of the keys are stored
+#[derive(Debug)]
+pub struct Accounts {
+    path: PathBuf,
+    data: tokio::sync::RwLock<AccountsData>,
+}
+
+impl Accounts {
+    pub async fn load(path: PathBuf) -> std::io::Result<Self> {
+        let data = match fs::read_to_string(&path).await {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => AccountsData::default(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            path,
+            data: tokio::sync::RwLock::new(data),
+        })
+    }
+
+    async fn save(&self, data: &AccountsData) -> std::io::Result<()> {
+        write_json_atomically(&self.path, data).await
+    }
+
+    fn add_key(data: &mut AccountsData, user_id: u64, name: String) -> (ApiKey, String) {
+        let secret = format!("{KEY_PREFIX}{}", random_hex::<32>());
+        let key = ApiKey {
+            id: random_hex::<8>(),
+            user_id,
+            name,
+            created_at: Utc::now(),
+        };
+        data.keys.insert(hash_token(&secret), key.clone());
+
+        (key, secret)
+    }
+
+    /// Creates an account along with its first key, returning the key's secret, which isn't
+    /// stored. Returns `None` if an account with the name already exists
+    pub async fn create_user(
+        &self,
+        name: String,
+    ) -> std::io::Result<Option<(Account, ApiKey, String)>> {
+        let mut data = self.data.write().await;
+        if data.users.values().any(|account| account.name == name) {
+            return Ok(None);
+        }
+
+        let account = Account {
+            id: data.next_id,
+            name,
+            created_at: Utc::now(),
+        };
+        data.next_id += 1;
+        data.users.insert(account.id, account.clone());
+        let (key, secret) = Self::add_key(&mut data, account.id, "default".to_string());
+        self.save(&data).await?;
+
+        Ok(Some((account, key, secret)))
+    }
+
+    /// Deletes an account and its keys, returning whether it existed
+    pub async fn delete_user(&self, user_id: UserId) -> std::io::Result<bool> {
+        let mut data = self.data.write().await;
+        if data.users.remove(&user_id.0).is_none() {
+            return Ok(false);
+        }
+
+        data.keys.retain(|_, key| key.user_id != user_id.0);
+        self.save(&data).await?;
+
+        Ok(true)
+    }
+
+    /// Creates a key for the account, returning it along with its secret, which isn't stored.
+    /// Returns `None` if the account doesn't exist
+    pub async fn create_key(
+        &self,
+        user_id: UserId,
+        name: String,
+    ) -> std::io::Result<Option<(ApiKey, String)>> {
+        let mut data = self.data.write().await;
+        if !data.users.contains_key(&user_id.0) {
+            return Ok(None);
+        }
+
+        let created = Self::add_key(&mut data, user_id.0, name);
+        self.save(&data).await?;
+
+        Ok(Some(created))
+    }
+
+    /// Revokes one of the account's keys, returning whether it existed
+    pub async fn revoke_key(&self, user_id: UserId, id: &str) -> std::io::Result<bool> {
+        let mut data = self.data.write().await;
+
+        let len = data.keys.len();
+        data.keys
+            .retain(|_, key| !(key.user_id == user_id.0 && key.id == id));
+        if data.keys.len() == len {
+            return Ok(false);
+        }
+
+        self.save(&data).await?;
+
+        Ok(true)
+    }
+
+    /// The accounts, sorted by ID, along with their keys
+    pub async fn list(&self) -> Vec<(Account, Vec<ApiKey>)> {
+        let data = self.data.read().await;
+
+        data.users
+            .values()
+            .map(|account| {
+                let keys = data
+                    .keys
+                    .values()
+                    .filter(|key| key.user_id == account.id)
+                    .cloned()
+                    .collect();
+                (account.clone(), keys)
+            })
+            .collect()
+    }
+}
+
+impl AuthImpl for Accounts {
+    async fn for_write_request(&self, req: &ServiceRequest) -> Result<Option<UserId>, ActixError> {
+        let Some(token) = get_token_from_req(req) else {
+            return Ok(None);
+        };
+        if !token.starts_with(KEY_PREFIX) {
+            return Ok(None);
+        }
+
+        Ok(self
+            .data
+            .read()
+            .await
+            .keys
+            .get(&hash_token(&token))
+            .map(|key| UserId(key.user_id)))
+    }
+}
+
+impl Display for Accounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Accounts")
+    }
+}
@@ -0,0 +1,387 @@
+use crate::{
+    auth::{get_token_from_req, oidc::PublishGrant, UserId},
+    write_json_atomically,
+};
+use actix_web::dev::ServiceRequest;
+use chrono::{DateTime, Utc};
+use fs_err::tokio as fs;
+use pesde::names::PackageName;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, fmt::Display, path::PathBuf, str::FromStr};
+
+/// The prefix of API tokens, which tells them apart from the tokens of other authentication methods
+pub const TOKEN_PREFIX: &str = "pesde_";
+
+/// A permission granted to an API token
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum TokenScope {
+    /// Reading packages, if the registry requires authentication for it
+    Read,
+    /// Publishing packages matching the pattern, which is either `*`, `<scope>/*` or a package name
+    Publish(String),
+    /// Yanking and deprecating packages
+    Yank,
+    /// Everything, including managing API tokens
+    Admin,
+}
+
+impl TokenScope {
+    fn allows_publishing(&self, name: &PackageName) -> bool {
+        match self {
+            TokenScope::Publish(pattern) => match pattern.strip_suffix("/*") {
+                _ if pattern == "*" => true,
+                Some(scope) => name.as_str().0 == scope,
+                None => name.to_string() == *pattern,
+            },
+            TokenScope::Admin => true,
+            _ => false,
+        }
+    }
+}
+
+impl Display for TokenScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenScope::Read => write!(f, "read"),
+            TokenScope::Publish(pattern) => write!(f, "publish:{pattern}"),
+            TokenScope::Yank => write!(f, "yank"),
+            TokenScope::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl FromStr for TokenScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(TokenScope::Read),
+            "yank" => Ok(TokenScope::Yank),
+            "admin" => Ok(TokenScope::Admin),
+            s => match s.strip_prefix("publish:") {
+                Some(pattern)
+                    if pattern == "*"
+                        || pattern.strip_suffix("/*").is_some_and(|scope| {
+                            format!("{scope}/a").parse::<PackageName>().is_ok()
+                        })
+                        || pattern.parse::<PackageName>().is_ok() =>
+                {
+                    Ok(TokenScope::Publish(pattern.to_string()))
+                }
+                Some(pattern) => Err(format!("invalid publish pattern `{pattern}`")),
+                None => Err(format!("unknown token scope `{s}`")),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for TokenScope {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<TokenScope> for String {
+    fn from(scope: TokenScope) -> Self {
+        scope.to_string()
+    }
+}
+
+/// What an authenticated request is allowed to do
+#[derive(Debug, Clone)]
+pub enum Permissions {
+    /// The request was authenticated by the registry's authentication method, and can do anything
+    /// its user can
+    All,
+    /// The request was authenticated by an API token, and can only do what its scopes allow
+    Scoped(Vec<TokenScope>),
+}
+
+impl Permissions {
+    fn has(&self, scope: &TokenScope) -> bool {
+        match self {
+            Permissions::All => true,
+            Permissions::Scoped(scopes) => {
+                scopes.contains(scope) || scopes.contains(&TokenScope::Admin)
+            }
+        }
+    }
+
+    pub fn can_read(&self) -> bool {
+        self.has(&TokenScope::Read)
+    }
+
+    pub fn can_publish(&self, name: &PackageName) -> bool {
+        match self {
+            Permissions::All => true,
+            Permissions::Scoped(scopes) => scopes.iter().any(|scope| scope.allows_publishing(name)),
+        }
+    }
+
+    /// Whether any package can be published, for requests made before the package is known
+    pub fn can_publish_any(&self) -> bool {
+        match self {
+            Permissions::All => true,
+            Permissions::Scoped(scopes) => scopes
+                .iter()
+                .any(|scope| matches!(scope, TokenScope::Publish(_) | TokenScope::Admin)),
+        }
+    }
+
+    pub fn can_yank(&self) -> bool {
+        self.has(&TokenScope::Yank)
+    }
+
+    pub fn can_manage_tokens(&self) -> bool {
+        self.has(&TokenScope::Admin)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    pub user_id: u64,
+    pub scopes: Vec<TokenScope>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// The grant of the OIDC token the token was exchanged for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grant: Option<PublishGrant>,
+}
+
+impl ApiToken {
+    fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+}
+
+/// API tokens created by users, persisted in a file of the data directory. Only the SHA-256
+/// hashes of the tokens are stored
+#[derive(Debug)]
+pub struct ApiTokens {
+    path: PathBuf,
+    /// The tokens, keyed by their hex-encoded hash
+    tokens: tokio::sync::RwLock<BTreeMap<String, ApiToken>>,
+}
+
+/// Hashes a token for storage, encoded as hex
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Generates `N` random bytes, encoded as hex
+pub fn random_hex<const N: usize>() -> String {
+    let mut bytes = [0; N];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("failed to generate random bytes");
+
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+impl ApiTokens {
+    pub async fn load(path: PathBuf) -> std::io::Result<Self> {
+        let tokens = match fs::read_to_string(&path).await {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            path,
+            tokens: tokio::sync::RwLock::new(tokens),
+        })
+    }
+
+    async fn save(&self, tokens: &BTreeMap<String, ApiToken>) -> std::io::Result<()> {
+        write_json_atomically(&self.path, tokens).await
+    }
+
+    /// Authenticates a request by its API token. Returns `None` if the request doesn't carry an
+    /// API token, and `Some(None)` if the token is unknown or expired
+    pub async fn authenticate(
+        &self,
+        req: &ServiceRequest,
+    ) -> Option<Option<(UserId, Permissions, Option<PublishGrant>)>> {
+        let token = get_token_from_req(req)?;
+        if !token.starts_with(TOKEN_PREFIX) {
+            return None;
+        }
+
+        let tokens = self.tokens.read().await;
+        Some(
+            tokens
+                .get(&hash_token(&token))
+                .filter(|token| !token.is_expired())
+                .map(|token| {
+                    (
+                        UserId(token.user_id),
+                        Permissions::Scoped(token.scopes.clone()),
+                        token.grant.clone(),
+                    )
+                }),
+        )
+    }
+
+    /// Creates a token for the user, returning it along with its secret, which isn't stored
+    pub async fn create(
+        &self,
+        user_id: UserId,
+        name: String,
+        scopes: Vec<TokenScope>,
+        expires_at: Option<DateTime<Utc>>,
+        grant: Option<PublishGrant>,
+    ) -> std::io::Result<(ApiToken, String)> {
+        let secret = format!("{TOKEN_PREFIX}{}", random_hex::<32>());
+        let token = ApiToken {
+            id: random_hex::<8>(),
+            name,
+            user_id: user_id.0,
+            scopes,
+            created_at: Utc::now(),
+            expires_at,
+            grant,
+        };
+
+        let mut tokens = self.tokens.write().await;
+        tokens.retain(|_, token| !token.is_expired());
+        tokens.insert(hash_token(&secret), token.clone());
+        self.save(&tokens).await?;
+
+        Ok((token, secret))
+    }
+
+    /// The tokens of the user
+    pub async fn list(&self, user_id: UserId) -> Vec<ApiToken> {
+        self.tokens
+            .read()
+            .await
+            .values()
+            .filter(|token| token.user_id == user_id.0)
+            .cloned()
+            .collect()
+    }
+
+    /// Revokes one of the user's tokens, returning whether it existed
+    pub async fn revoke(&self, user_id: UserId, id: &str) -> std::io::Result<bool> {
+        let mut tokens = self.tokens.write().await;
+
+        let len = tokens.len();
+        tokens.retain(|_, token| !(token.user_id == user_id.0 && token.id == id));
+        if tokens.len() == len {
+            return Ok(false);
+        }
+
+        self.save(&tokens).await?;
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(name: &str) -> PackageName {
+        name.parse().unwrap()
+    }
+
+    fn token(expires_at: Option<DateTime<Utc>>) -> ApiToken {
+        ApiToken {
+            id: random_hex::<8>(),
+            name: "ci".to_string(),
+            user_id: 1,
+            scopes: vec![TokenScope::Read],
+            created_at: Utc::now(),
+            expires_at,
+            grant: None,
+        }
+    }
+
+    #[test]
+    fn parses_scopes() {
+        assert_eq!("read".parse(), Ok(TokenScope::Read));
+        assert_eq!("yank".parse(), Ok(TokenScope::Yank));
+        assert_eq!("admin".parse(), Ok(TokenScope::Admin));
+        assert_eq!(
+            "publish:*".parse(),
+            Ok(TokenScope::Publish("*".to_string()))
+        );
+        assert_eq!(
+            "publish:acme/*".parse(),
+            Ok(TokenScope::Publish("acme/*".to_string()))
+        );
+        assert_eq!(
+            "publish:acme/foo".parse(),
+            Ok(TokenScope::Publish("acme/foo".to_string()))
+        );
+
+        assert!("write".parse::<TokenScope>().is_err());
+        assert!("publish:".parse::<TokenScope>().is_err());
+        assert!("publish:acme".parse::<TokenScope>().is_err());
+        assert!("publish:Acme/*".parse::<TokenScope>().is_err());
+        assert!("publish:acme/foo/*".parse::<TokenScope>().is_err());
+
+        for scope in ["read", "publish:acme/*", "publish:acme/foo", "admin"] {
+            assert_eq!(scope.parse::<TokenScope>().unwrap().to_string(), scope);
+        }
+    }
+
+    #[test]
+    fn allows_publishing_matching_packages() {
+        let any = TokenScope::Publish("*".to_string());
+        assert!(any.allows_publishing(&name("acme/foo")));
+        assert!(any.allows_publishing(&name("other/bar")));
+
+        let scope = TokenScope::Publish("acme/*".to_string());
+        assert!(scope.allows_publishing(&name("acme/foo")));
+        assert!(!scope.allows_publishing(&name("acmes/foo")));
+        assert!(!scope.allows_publishing(&name("other/acme")));
+
+        let exact = TokenScope::Publish("acme/foo".to_string());
+        assert!(exact.allows_publishing(&name("acme/foo")));
+        assert!(!exact.allows_publishing(&name("acme/foobar")));
+        assert!(!exact.allows_publishing(&name("acme/bar")));
+
+        assert!(TokenScope::Admin.allows_publishing(&name("acme/foo")));
+        assert!(!TokenScope::Read.allows_publishing(&name("acme/foo")));
+        assert!(!TokenScope::Yank.allows_publishing(&name("acme/foo")));
+    }
+
+    #[test]
+    fn admin_implies_every_permission() {
+        let admin = Permissions::Scoped(vec![TokenScope::Admin]);
+        assert!(admin.can_read());
+        assert!(admin.can_yank());
+        assert!(admin.can_manage_tokens());
+        assert!(admin.can_publish(&name("acme/foo")));
+        assert!(admin.can_publish_any());
+
+        let read = Permissions::Scoped(vec![TokenScope::Read]);
+        assert!(read.can_read());
+        assert!(!read.can_yank());
+        assert!(!read.can_manage_tokens());
+        assert!(!read.can_publish(&name("acme/foo")));
+        assert!(!read.can_publish_any());
+
+        let publish = Permissions::Scoped(vec![TokenScope::Publish("acme/*".to_string())]);
+        assert!(publish.can_publish_any());
+        assert!(publish.can_publish(&name("acme/foo")));
+        assert!(!publish.can_publish(&name("other/foo")));
+        assert!(!publish.can_manage_tokens());
+    }
+
+    #[test]
+    fn expires_tokens() {
+        assert!(!token(None).is_expired());
+        assert!(!token(Some(Utc::now() + chrono::Duration::hours(1))).is_expired());
+        assert!(token(Some(Utc::now() - chrono::Duration::seconds(1))).is_expired());
+    }
+}
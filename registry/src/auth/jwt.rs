@@ -0,0 +1,167 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::signature::{RsaPublicKeyComponents, RSA_PKCS1_2048_8192_SHA256};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// The minimum time between two fetches of a provider's signing keys, so that tokens signed by
+/// unknown keys can't be used to flood the provider with requests
+const JWKS_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+/// How far off the clocks of a provider and the registry may be
+pub const CLOCK_LEEWAY: i64 = 60;
+
+#[derive(Debug, Deserialize)]
+struct JwtHeader {
+    alg: String,
+    kid: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    pub fn contains(&self, audience: &str) -> bool {
+        match self {
+            Audience::One(aud) => aud == audience,
+            Audience::Many(auds) => auds.iter().any(|aud| aud == audience),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Jwk {
+    kid: String,
+    kty: String,
+    n: String,
+    e: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Default)]
+struct KeyCache {
+    /// The RSA keys' moduli and exponents, keyed by their ID
+    keys: HashMap<String, (Vec<u8>, Vec<u8>)>,
+    fetched_at: Option<Instant>,
+}
+
+fn decode_part<T: DeserializeOwned>(part: &str) -> Option<T> {
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(part).ok()?).ok()
+}
+
+/// Whether the token has the shape of a JWT, as opposed to the tokens of other authentication
+/// methods
+pub fn is_jwt(token: &str) -> bool {
+    token.starts_with("eyJ") && token.split('.').count() == 3
+}
+
+/// The issuer a JWT claims to be from, without verifying it, to tell which verifier it's meant for
+pub fn unverified_issuer(token: &str) -> Option<String> {
+    #[derive(Deserialize)]
+    struct Issuer {
+        iss: String,
+    }
+
+    let payload = token.split('.').nth(1)?;
+    decode_part::<Issuer>(payload).map(|issuer| issuer.iss)
+}
+
+/// Verifies the signatures of RS256 JWTs against the keys published at a JWKS URL, which are
+/// fetched again when a token is signed by an unknown key
+#[derive(Debug)]
+pub struct JwksVerifier {
+    reqwest_client: reqwest::Client,
+    jwks_url: String,
+    keys: tokio::sync::RwLock<KeyCache>,
+}
+
+impl JwksVerifier {
+    pub fn new(reqwest_client: reqwest::Client, jwks_url: String) -> Self {
+        Self {
+            reqwest_client,
+            jwks_url,
+            keys: Default::default(),
+        }
+    }
+
+    async fn fetch_keys(&self) -> Result<HashMap<String, (Vec<u8>, Vec<u8>)>, reqwest::Error> {
+        let jwks = self
+            .reqwest_client
+            .get(&self.jwks_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Jwks>()
+            .await?;
+
+        Ok(jwks
+            .keys
+            .into_iter()
+            .filter(|key| key.kty == "RSA")
+            .filter_map(|key| {
+                Some((
+                    key.kid,
+                    (
+                        URL_SAFE_NO_PAD.decode(key.n).ok()?,
+                        URL_SAFE_NO_PAD.decode(key.e).ok()?,
+                    ),
+                ))
+            })
+            .collect())
+    }
+
+    async fn key(&self, kid: &str) -> Option<(Vec<u8>, Vec<u8>)> {
+        if let Some(key) = self.keys.read().await.keys.get(kid) {
+            return Some(key.clone());
+        }
+
+        // the key may have been rotated in since the keys were last fetched
+        let mut cache = self.keys.write().await;
+        if cache
+            .fetched_at
+            .is_none_or(|fetched_at| fetched_at.elapsed() >= JWKS_REFETCH_INTERVAL)
+        {
+            cache.fetched_at = Some(Instant::now());
+            match self.fetch_keys().await {
+                Ok(keys) => cache.keys = keys,
+                Err(e) => {
+                    tracing::error!("failed to fetch signing keys from {}: {e}", self.jwks_url)
+                }
+            }
+        }
+
+        cache.keys.get(kid).cloned()
+    }
+
+    /// Verifies the token's signature, returning its claims. The claims themselves, such as the
+    /// issuer and expiry, are left for the caller to check
+    pub async fn verify<C: DeserializeOwned>(&self, token: &str) -> Option<C> {
+        let (signed, signature) = token.rsplit_once('.')?;
+        let (header, payload) = signed.split_once('.')?;
+
+        let header = decode_part::<JwtHeader>(header)?;
+        if header.alg != "RS256" {
+            return None;
+        }
+
+        let (n, e) = self.key(&header.kid).await?;
+        RsaPublicKeyComponents { n, e }
+            .verify(
+                &RSA_PKCS1_2048_8192_SHA256,
+                signed.as_bytes(),
+                &URL_SAFE_NO_PAD.decode(signature).ok()?,
+            )
+            .ok()?;
+
+        decode_part(payload)
+    }
+}
@@ -1,10 +1,14 @@
+pub mod accounts;
+pub mod api_tokens;
 mod github;
+pub mod jwt;
 mod none;
+pub mod oauth;
+pub mod oidc;
 mod rw_token;
 mod token;
 
-use crate::{benv, make_reqwest, AppState};
-use actix_governor::{KeyExtractor, SimpleKeyExtractionError};
+use crate::{auth::api_tokens::Permissions, benv, make_reqwest, AppState};
 use actix_web::{
     body::MessageBody,
     dev::{ServiceRequest, ServiceResponse},
@@ -16,7 +20,7 @@ use actix_web::{
 use pesde::source::pesde::IndexConfig;
 use sentry::add_breadcrumb;
 use sha2::{Digest, Sha256};
-use std::fmt::Display;
+use std::{collections::BTreeSet, fmt::Display, path::Path};
 
 #[derive(Debug, Copy, Clone, Hash, PartialOrd, PartialEq, Eq, Ord)]
 pub struct UserId(pub u64);
@@ -26,25 +30,12 @@ impl UserId {
     pub const DEFAULT: UserId = UserId(0);
 }
 
-#[derive(Debug, Clone)]
-pub struct UserIdExtractor;
-
-impl KeyExtractor for UserIdExtractor {
-    type Key = UserId;
-    type KeyExtractionError = SimpleKeyExtractionError<&'static str>;
-
-    fn extract(&self, req: &ServiceRequest) -> Result<Self::Key, Self::KeyExtractionError> {
-        match req.extensions().get::<UserId>() {
-            Some(user_id) => Ok(*user_id),
-            None => Err(SimpleKeyExtractionError::new("UserId not found")),
-        }
-    }
-}
-
 #[derive(Debug)]
 pub enum Auth {
+    Accounts(accounts::Accounts),
     GitHub(github::GitHubAuth),
     None(none::NoneAuth),
+    OpenId(Box<oauth::OpenIdAuth>),
     Token(token::TokenAuth),
     RwToken(rw_token::RwTokenAuth),
 }
@@ -64,8 +55,10 @@ pub trait AuthImpl: Display {
 impl AuthImpl for Auth {
     async fn for_write_request(&self, req: &ServiceRequest) -> Result<Option<UserId>, ActixError> {
         match self {
+            Auth::Accounts(accounts) => accounts.for_write_request(req).await,
             Auth::GitHub(github) => github.for_write_request(req).await,
             Auth::None(none) => none.for_write_request(req).await,
+            Auth::OpenId(openid) => openid.for_write_request(req).await,
             Auth::Token(token) => token.for_write_request(req).await,
             Auth::RwToken(rw_token) => rw_token.for_write_request(req).await,
         }
@@ -73,8 +66,10 @@ impl AuthImpl for Auth {
 
     async fn for_read_request(&self, req: &ServiceRequest) -> Result<Option<UserId>, ActixError> {
         match self {
+            Auth::Accounts(accounts) => accounts.for_read_request(req).await,
             Auth::GitHub(github) => github.for_read_request(req).await,
             Auth::None(none) => none.for_write_request(req).await,
+            Auth::OpenId(openid) => openid.for_read_request(req).await,
             Auth::Token(token) => token.for_write_request(req).await,
             Auth::RwToken(rw_token) => rw_token.for_read_request(req).await,
         }
@@ -82,8 +77,10 @@ impl AuthImpl for Auth {
 
     fn read_needs_auth(&self) -> bool {
         match self {
+            Auth::Accounts(accounts) => accounts.read_needs_auth(),
             Auth::GitHub(github) => github.read_needs_auth(),
             Auth::None(none) => none.read_needs_auth(),
+            Auth::OpenId(openid) => openid.read_needs_auth(),
             Auth::Token(token) => token.read_needs_auth(),
             Auth::RwToken(rw_token) => rw_token.read_needs_auth(),
         }
@@ -93,8 +90,10 @@ impl AuthImpl for Auth {
 impl Display for Auth {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Auth::Accounts(accounts) => write!(f, "{}", accounts),
             Auth::GitHub(github) => write!(f, "{}", github),
             Auth::None(none) => write!(f, "{}", none),
+            Auth::OpenId(openid) => write!(f, "{}", openid),
             Auth::Token(token) => write!(f, "{}", token),
             Auth::RwToken(rw_token) => write!(f, "{}", rw_token),
         }
@@ -106,13 +105,32 @@ pub async fn write_mw(
     req: ServiceRequest,
     next: Next<impl MessageBody + 'static>,
 ) -> Result<ServiceResponse<impl MessageBody>, ActixError> {
-    let user_id = match app_state.auth.for_write_request(&req).await? {
-        Some(user_id) => user_id,
-        None => {
-            return Ok(req
-                .into_response(HttpResponse::Unauthorized().finish())
-                .map_into_right_body())
-        }
+    // API tokens and OIDC tokens are checked first, and invalid ones aren't passed on to the
+    // registry's authentication method
+    let authenticated = if let Some(authenticated) = app_state.api_tokens.authenticate(&req).await {
+        authenticated.map(|(user_id, permissions, grant)| {
+            if let Some(grant) = grant {
+                req.extensions_mut().insert(grant);
+            }
+            (user_id, permissions)
+        })
+    } else if let Some(authenticated) = app_state.trusted_publishing.authenticate(&req).await {
+        authenticated.map(|(user_id, permissions, grant)| {
+            req.extensions_mut().insert(grant);
+            (user_id, permissions)
+        })
+    } else {
+        app_state
+            .auth
+            .for_write_request(&req)
+            .await?
+            .map(|user_id| (user_id, Permissions::All))
+    };
+
+    let Some((user_id, permissions)) = authenticated else {
+        return Ok(req
+            .into_response(HttpResponse::Unauthorized().finish())
+            .map_into_right_body());
     };
 
     add_breadcrumb(sentry::Breadcrumb {
@@ -123,6 +141,7 @@ pub async fn write_mw(
     });
 
     req.extensions_mut().insert(user_id);
+    req.extensions_mut().insert(permissions);
 
     next.call(req).await.map(|res| res.map_into_left_body())
 }
@@ -133,13 +152,21 @@ pub async fn read_mw(
     next: Next<impl MessageBody + 'static>,
 ) -> Result<ServiceResponse<impl MessageBody>, ActixError> {
     if app_state.auth.read_needs_auth() {
-        let user_id = match app_state.auth.for_read_request(&req).await? {
-            Some(user_id) => user_id,
-            None => {
-                return Ok(req
-                    .into_response(HttpResponse::Unauthorized().finish())
-                    .map_into_right_body())
-            }
+        let authenticated = match app_state.api_tokens.authenticate(&req).await {
+            Some(authenticated) => authenticated
+                .filter(|(_, permissions, _)| permissions.can_read())
+                .map(|(user_id, permissions, _)| (user_id, permissions)),
+            None => app_state
+                .auth
+                .for_read_request(&req)
+                .await?
+                .map(|user_id| (user_id, Permissions::All)),
+        };
+
+        let Some((user_id, permissions)) = authenticated else {
+            return Ok(req
+                .into_response(HttpResponse::Unauthorized().finish())
+                .map_into_right_body());
         };
 
         add_breadcrumb(sentry::Breadcrumb {
@@ -150,6 +177,7 @@ pub async fn read_mw(
         });
 
         req.extensions_mut().insert(Some(user_id));
+        req.extensions_mut().insert(permissions);
     } else {
         req.extensions_mut().insert(None::<UserId>);
     }
@@ -157,8 +185,14 @@ pub async fn read_mw(
     next.call(req).await.map(|res| res.map_into_left_body())
 }
 
-pub fn get_auth_from_env(config: &IndexConfig) -> Auth {
-    if let Ok(token) = benv!("ACCESS_TOKEN") {
+pub async fn get_auth_from_env(config: &IndexConfig, data_dir: &Path) -> Auth {
+    if benv!("USER_ACCOUNTS").is_ok() {
+        Auth::Accounts(
+            accounts::Accounts::load(data_dir.join("accounts.json"))
+                .await
+                .expect("failed to load user accounts"),
+        )
+    } else if let Ok(token) = benv!("ACCESS_TOKEN") {
         Auth::Token(token::TokenAuth {
             token: *Sha256::digest(token.as_bytes()).as_ref(),
         })
@@ -171,6 +205,14 @@ pub fn get_auth_from_env(config: &IndexConfig) -> Auth {
                 .expect("index isn't configured for GitHub"),
             client_secret,
         })
+    } else if let Some(provider) = oauth::get_provider_from_env() {
+        Auth::OpenId(Box::new(oauth::OpenIdAuth::new(
+            make_reqwest(),
+            provider,
+            oauth::Identities::load(data_dir.join("identities.json"))
+                .await
+                .expect("failed to load identities"),
+        )))
     } else if let Ok((r, w)) =
         benv!("READ_ACCESS_TOKEN").and_then(|r| benv!("WRITE_ACCESS_TOKEN").map(|w| (r, w)))
     {
@@ -183,6 +225,30 @@ pub fn get_auth_from_env(config: &IndexConfig) -> Auth {
     }
 }
 
+/// The users who administer the registry, given by the `ADMIN_USER_IDS` environment variable as a
+/// comma-separated list. Nobody administers it if it isn't set
+pub fn get_admins_from_env() -> BTreeSet<UserId> {
+    benv!("ADMIN_USER_IDS")
+        .map(|ids| {
+            ids.split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(|id| {
+                    UserId(
+                        id.parse()
+                            .expect("Setting `ADMIN_USER_IDS` must contain valid user IDs"),
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether the request was made by an administrator of the registry, with permission to act as one
+pub fn is_admin(app_state: &AppState, user_id: UserId, permissions: &Permissions) -> bool {
+    app_state.admins.contains(&user_id) && permissions.can_manage_tokens()
+}
+
 pub fn get_token_from_req(req: &ServiceRequest) -> Option<String> {
     let token = req
         .headers()
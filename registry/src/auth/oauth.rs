@@ -0,0 +1,354 @@
+use crate::{
+    auth::{
+        get_token_from_req,
+        jwt::{is_jwt, Audience, JwksVerifier, CLOCK_LEEWAY},
+        AuthImpl, UserId,
+    },
+    benv, write_json_atomically,
+};
+use actix_web::{dev::ServiceRequest, error::ErrorInternalServerError, Error as ActixError};
+use chrono::{DateTime, Utc};
+use fs_err::tokio as fs;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt::Display, path::PathBuf, str::FromStr};
+
+/// An identity provider whose users may use the registry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    GitLab,
+    Google,
+    /// Any OpenID Connect provider
+    Oidc,
+}
+
+impl Provider {
+    /// The issuer used if `OAUTH_ISSUER` isn't set
+    fn default_issuer(&self) -> Option<&'static str> {
+        match self {
+            Provider::GitLab => Some("https://gitlab.com"),
+            Provider::Google => Some("https://accounts.google.com"),
+            Provider::Oidc => None,
+        }
+    }
+
+    /// Whether the issuer of an ID token is the expected one. Google may leave out the scheme
+    fn accepts_issuer(&self, expected: &str, issuer: &str) -> bool {
+        issuer == expected
+            || (*self == Provider::Google && Some(issuer) == expected.strip_prefix("https://"))
+    }
+}
+
+impl FromStr for Provider {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "gitlab" => Ok(Provider::GitLab),
+            "google" => Ok(Provider::Google),
+            "oidc" => Ok(Provider::Oidc),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Provider::GitLab => write!(f, "GitLab"),
+            Provider::Google => write!(f, "Google"),
+            Provider::Oidc => write!(f, "OIDC"),
+        }
+    }
+}
+
+/// How the registry's identity provider is configured
+#[derive(Debug, Clone)]
+pub struct ProviderConfig {
+    pub provider: Provider,
+    /// The issuer URL, which the provider's OpenID configuration is discovered from
+    pub issuer: String,
+    /// The client ID of the registry's OAuth application, which ID tokens must be issued for
+    pub client_id: String,
+}
+
+impl Display for ProviderConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.provider, self.issuer)
+    }
+}
+
+/// The identity provider configured by the `OAUTH_PROVIDER`, `OAUTH_ISSUER` and `OAUTH_CLIENT_ID`
+/// environment variables, if any
+pub fn get_provider_from_env() -> Option<ProviderConfig> {
+    let provider = benv!("OAUTH_PROVIDER").ok()?;
+    let provider = provider.parse::<Provider>().unwrap_or_else(|_| {
+        panic!("Setting `OAUTH_PROVIDER` must be `gitlab`, `google` or `oidc`")
+    });
+
+    let issuer = match benv!("OAUTH_ISSUER") {
+        Ok(issuer) => issuer,
+        Err(_) => provider
+            .default_issuer()
+            .expect("Setting `OAUTH_ISSUER` is required for the `oidc` provider")
+            .to_string(),
+    };
+
+    Some(ProviderConfig {
+        provider,
+        issuer: issuer.trim_end_matches('/').to_string(),
+        client_id: benv!(required "OAUTH_CLIENT_ID"),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenIdConfiguration {
+    issuer: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum DiscoveryError {
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+
+    #[error("the OpenID configuration is for issuer {0}")]
+    IssuerMismatch(String),
+}
+
+/// The provider's issuer as given in its OpenID configuration, and a verifier for its signing keys
+#[derive(Debug)]
+struct Discovered {
+    issuer: String,
+    verifier: JwksVerifier,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    iss: String,
+    aud: Audience,
+    exp: i64,
+    #[serde(default)]
+    nbf: Option<i64>,
+    sub: String,
+    #[serde(default)]
+    preferred_username: Option<String>,
+    #[serde(default)]
+    email: Option<String>,
+}
+
+impl Claims {
+    /// A name for the identity, for administrators to recognize it by
+    fn name(self) -> Option<String> {
+        self.preferred_username.or(self.email)
+    }
+}
+
+/// A provider's user, known to the registry by a user ID
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Identity {
+    pub id: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+fn first_id() -> u64 {
+    1
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IdentitiesData {
+    /// The ID the next identity is given. IDs aren't reused, as ownership records refer to them
+    #[serde(default = "first_id")]
+    next_id: u64,
+    /// The identities, by their issuer and then their subject
+    #[serde(default)]
+    identities: BTreeMap<String, BTreeMap<String, Identity>>,
+}
+
+impl Default for IdentitiesData {
+    fn default() -> Self {
+        Self {
+            next_id: first_id(),
+            identities: BTreeMap::new(),
+        }
+    }
+}
+
+/// The user IDs given to the users of identity providers, persisted in a file of the data
+/// directory. Subjects are arbitrary strings, so they're mapped to IDs as they're first seen, which
+/// ownership records, rate limits and the registry's other settings then refer to
+#[derive(Debug)]
+pub struct Identities {
+    path: PathBuf,
+    data: tokio::sync::RwLock<IdentitiesData>,
+}
+
+impl Identities {
+    pub async fn load(path: PathBuf) -> std::io::Result<Self> {
+        let data = match fs::read_to_string(&path).await {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => IdentitiesData::default(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            path,
+            data: tokio::sync::RwLock::new(data),
+        })
+    }
+
+    async fn save(&self, data: &IdentitiesData) -> std::io::Result<()> {
+        write_json_atomically(&self.path, data).await
+    }
+
+    /// The user ID of the identity, given one if it's seen for the first time. Its name is kept up
+    /// to date
+    pub async fn resolve(
+        &self,
+        issuer: &str,
+        subject: &str,
+        name: Option<String>,
+    ) -> std::io::Result<UserId> {
+        if let Some(identity) = self
+            .data
+            .read()
+            .await
+            .identities
+            .get(issuer)
+            .and_then(|identities| identities.get(subject))
+            .filter(|identity| identity.name == name)
+        {
+            return Ok(UserId(identity.id));
+        }
+
+        let mut data = self.data.write().await;
+        let next_id = data.next_id;
+        let identities = data.identities.entry(issuer.to_string()).or_default();
+        let id = match identities.get_mut(subject) {
+            // another request may have resolved the identity while no lock was held
+            Some(identity) if identity.name == name => return Ok(UserId(identity.id)),
+            Some(identity) => {
+                identity.name = name;
+                identity.id
+            }
+            None => {
+                identities.insert(
+                    subject.to_string(),
+                    Identity {
+                        id: next_id,
+                        name,
+                        created_at: Utc::now(),
+                    },
+                );
+                data.next_id += 1;
+                tracing::info!("identity {subject} of {issuer} is user {next_id}");
+                next_id
+            }
+        };
+        self.save(&data).await?;
+
+        Ok(UserId(id))
+    }
+}
+
+/// Authentication by the ID tokens of an OpenID Connect provider, such as GitLab or Google. The
+/// provider's configuration is discovered on first use
+#[derive(Debug)]
+pub struct OpenIdAuth {
+    reqwest_client: reqwest::Client,
+    config: ProviderConfig,
+    discovered: tokio::sync::OnceCell<Discovered>,
+    identities: Identities,
+}
+
+impl OpenIdAuth {
+    pub fn new(
+        reqwest_client: reqwest::Client,
+        config: ProviderConfig,
+        identities: Identities,
+    ) -> Self {
+        Self {
+            reqwest_client,
+            config,
+            discovered: Default::default(),
+            identities,
+        }
+    }
+
+    async fn discover(&self) -> Result<Discovered, DiscoveryError> {
+        let configuration = self
+            .reqwest_client
+            .get(format!(
+                "{}/.well-known/openid-configuration",
+                self.config.issuer
+            ))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<OpenIdConfiguration>()
+            .await?;
+
+        // the issuer must be the one the configuration was fetched from, otherwise tokens of
+        // another issuer would be accepted (OpenID Connect Discovery 1.0, section 4.3)
+        if configuration.issuer.trim_end_matches('/') != self.config.issuer {
+            return Err(DiscoveryError::IssuerMismatch(configuration.issuer));
+        }
+
+        Ok(Discovered {
+            issuer: configuration.issuer,
+            verifier: JwksVerifier::new(self.reqwest_client.clone(), configuration.jwks_uri),
+        })
+    }
+
+    async fn verify(&self, token: &str) -> Option<Claims> {
+        let discovered = match self.discovered.get_or_try_init(|| self.discover()).await {
+            Ok(discovered) => discovered,
+            Err(e) => {
+                tracing::error!("failed to discover {}: {e}", self.config);
+                return None;
+            }
+        };
+
+        let claims = discovered.verifier.verify::<Claims>(token).await?;
+        let now = Utc::now().timestamp();
+
+        (self
+            .config
+            .provider
+            .accepts_issuer(&discovered.issuer, &claims.iss)
+            && claims.aud.contains(&self.config.client_id)
+            && claims.exp + CLOCK_LEEWAY > now
+            && claims.nbf.is_none_or(|nbf| nbf - CLOCK_LEEWAY <= now))
+        .then_some(claims)
+    }
+}
+
+impl AuthImpl for OpenIdAuth {
+    async fn for_write_request(&self, req: &ServiceRequest) -> Result<Option<UserId>, ActixError> {
+        let Some(token) = get_token_from_req(req) else {
+            return Ok(None);
+        };
+        if !is_jwt(&token) {
+            return Ok(None);
+        }
+
+        let Some(claims) = self.verify(&token).await else {
+            return Ok(None);
+        };
+
+        // the issuer is recorded as configured, as the one in tokens may vary in form
+        let subject = claims.sub.clone();
+        self.identities
+            .resolve(&self.config.issuer, &subject, claims.name())
+            .await
+            .map(Some)
+            .map_err(ErrorInternalServerError)
+    }
+}
+
+impl Display for OpenIdAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.config)
+    }
+}
@@ -0,0 +1,321 @@
+use crate::{
+    auth::{
+        api_tokens::{random_hex, Permissions, TokenScope},
+        get_token_from_req,
+        jwt::{is_jwt, unverified_issuer, Audience, JwksVerifier, CLOCK_LEEWAY},
+        UserId,
+    },
+    write_json_atomically,
+};
+use actix_web::dev::ServiceRequest;
+use chrono::{DateTime, Utc};
+use fs_err::tokio as fs;
+use pesde::names::PackageName;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+use utoipa::ToSchema;
+
+/// The issuer of GitHub Actions' OIDC tokens
+pub const GITHUB_ISSUER: &str = "https://token.actions.githubusercontent.com";
+/// How long publish grants exchanged for OIDC tokens are valid for
+pub const GRANT_DURATION: Duration = Duration::from_secs(15 * 60);
+
+/// A GitHub Actions workflow which may publish a package without a long-lived token
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TrustedPublisher {
+    pub id: String,
+    /// The repository the workflow is in, in the form `owner/repo`
+    pub repository: String,
+    /// The file name of the workflow, such as `release.yml`
+    pub workflow: String,
+    /// The environment the workflow must run in, any if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    /// The user who registered the workflow, whom publishes are attributed to
+    pub user_id: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What an OIDC token was allowed to do, inserted into the requests it or an API token exchanged
+/// for it authenticated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishGrant {
+    /// The repository the token was issued to
+    pub repository: String,
+    /// The packages the token may publish, with the user who registered its workflow for each of
+    /// them, whom their publishes are attributed to
+    pub publishers: BTreeMap<PackageName, u64>,
+    /// Whether the grant came from an OIDC token rather than an API token exchanged for one, as
+    /// only those can be exchanged
+    #[serde(skip)]
+    pub from_oidc_token: bool,
+}
+
+impl PublishGrant {
+    /// The user publishes of the package are attributed to, or `None` if the grant doesn't cover
+    /// it
+    pub fn publisher_of(&self, name: &PackageName) -> Option<UserId> {
+        self.publishers.get(name).copied().map(UserId)
+    }
+
+    /// The scopes of the API tokens acting on the grant
+    pub fn scopes(&self) -> Vec<TokenScope> {
+        self.publishers
+            .keys()
+            .map(|name| TokenScope::Publish(name.to_string()))
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    iss: String,
+    aud: Audience,
+    exp: i64,
+    #[serde(default)]
+    nbf: Option<i64>,
+    repository: String,
+    /// The workflow's path and ref, such as
+    /// `owner/repo/.github/workflows/release.yml@refs/heads/main`
+    workflow_ref: String,
+    #[serde(default)]
+    environment: Option<String>,
+}
+
+impl Claims {
+    fn workflow(&self) -> Option<&str> {
+        let (path, _) = self.workflow_ref.rsplit_once('@')?;
+
+        path.strip_prefix(&self.repository)?
+            .strip_prefix("/.github/workflows/")
+    }
+
+    fn matches(&self, publisher: &TrustedPublisher) -> bool {
+        self.repository.eq_ignore_ascii_case(&publisher.repository)
+            && self.workflow() == Some(publisher.workflow.as_str())
+            && publisher
+                .environment
+                .as_ref()
+                .is_none_or(|environment| self.environment.as_ref() == Some(environment))
+    }
+}
+
+/// Trusted publishing from GitHub Actions. Workflows registered for a package authenticate with
+/// their OIDC token, which is verified against GitHub's signing keys
+#[derive(Debug)]
+pub struct TrustedPublishing {
+    /// The audience OIDC tokens must be issued for
+    audience: String,
+    verifier: JwksVerifier,
+    path: PathBuf,
+    publishers: tokio::sync::RwLock<BTreeMap<PackageName, Vec<TrustedPublisher>>>,
+}
+
+impl TrustedPublishing {
+    pub async fn load(
+        reqwest_client: reqwest::Client,
+        audience: String,
+        path: PathBuf,
+    ) -> std::io::Result<Self> {
+        let publishers = match fs::read_to_string(&path).await {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            audience,
+            verifier: JwksVerifier::new(
+                reqwest_client,
+                format!("{GITHUB_ISSUER}/.well-known/jwks"),
+            ),
+            path,
+            publishers: tokio::sync::RwLock::new(publishers),
+        })
+    }
+
+    async fn save(
+        &self,
+        publishers: &BTreeMap<PackageName, Vec<TrustedPublisher>>,
+    ) -> std::io::Result<()> {
+        write_json_atomically(&self.path, publishers).await
+    }
+
+    async fn verify(&self, token: &str) -> Option<Claims> {
+        let claims = self.verifier.verify::<Claims>(token).await?;
+        let now = Utc::now().timestamp();
+
+        (claims.iss == GITHUB_ISSUER
+            && claims.aud.contains(&self.audience)
+            && claims.exp + CLOCK_LEEWAY > now
+            && claims.nbf.is_none_or(|nbf| nbf - CLOCK_LEEWAY <= now))
+        .then_some(claims)
+    }
+
+    /// The grant of the OIDC token, covering the packages which trust its workflow
+    fn grant(
+        publishers: &BTreeMap<PackageName, Vec<TrustedPublisher>>,
+        claims: &Claims,
+    ) -> PublishGrant {
+        let publishers = publishers
+            .iter()
+            .filter_map(|(name, publishers)| {
+                publishers
+                    .iter()
+                    .find(|publisher| claims.matches(publisher))
+                    .map(|publisher| (name.clone(), publisher.user_id))
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        PublishGrant {
+            repository: claims.repository.clone(),
+            publishers,
+            from_oidc_token: true,
+        }
+    }
+
+    /// Authenticates a request by its OIDC token. Returns `None` if the request doesn't carry an
+    /// OIDC token, and `Some(None)` if the token is invalid or no package trusts its workflow.
+    /// Publishes are attributed to the user who registered the workflow for the package being
+    /// published, and other requests to the one who registered it for the first package
+    pub async fn authenticate(
+        &self,
+        req: &ServiceRequest,
+    ) -> Option<Option<(UserId, Permissions, PublishGrant)>> {
+        let token = get_token_from_req(req)?;
+        // JWTs from other issuers, such as the ID tokens of an OAuth provider, are left to the
+        // registry's authentication method
+        if !is_jwt(&token) || unverified_issuer(&token).as_deref() != Some(GITHUB_ISSUER) {
+            return None;
+        }
+
+        let Some(claims) = self.verify(&token).await else {
+            return Some(None);
+        };
+
+        let grant = Self::grant(&*self.publishers.read().await, &claims);
+        let Some(&user_id) = grant.publishers.values().next() else {
+            return Some(None);
+        };
+
+        Some(Some((
+            UserId(user_id),
+            Permissions::Scoped(grant.scopes()),
+            grant,
+        )))
+    }
+
+    /// The workflows trusted to publish the package
+    pub async fn list(&self, name: &PackageName) -> Vec<TrustedPublisher> {
+        self.publishers
+            .read()
+            .await
+            .get(name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Trusts a workflow to publish the package on behalf of the user
+    pub async fn add(
+        &self,
+        name: PackageName,
+        user_id: UserId,
+        repository: String,
+        workflow: String,
+        environment: Option<String>,
+    ) -> std::io::Result<TrustedPublisher> {
+        let publisher = TrustedPublisher {
+            id: random_hex::<8>(),
+            repository,
+            workflow,
+            environment,
+            user_id: user_id.0,
+            created_at: Utc::now(),
+        };
+
+        let mut publishers = self.publishers.write().await;
+        publishers.entry(name).or_default().push(publisher.clone());
+        self.save(&publishers).await?;
+
+        Ok(publisher)
+    }
+
+    /// Stops trusting a workflow to publish the package, returning whether it was trusted
+    pub async fn remove(&self, name: &PackageName, id: &str) -> std::io::Result<bool> {
+        let mut publishers = self.publishers.write().await;
+
+        let Some(package_publishers) = publishers.get_mut(name) else {
+            return Ok(false);
+        };
+
+        let len = package_publishers.len();
+        package_publishers.retain(|publisher| publisher.id != id);
+        if package_publishers.len() == len {
+            return Ok(false);
+        }
+
+        if package_publishers.is_empty() {
+            publishers.remove(name);
+        }
+
+        self.save(&publishers).await?;
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn publisher(repository: &str, workflow: &str, user_id: u64) -> TrustedPublisher {
+        TrustedPublisher {
+            id: random_hex::<8>(),
+            repository: repository.to_string(),
+            workflow: workflow.to_string(),
+            environment: None,
+            user_id,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn claims(repository: &str, workflow: &str) -> Claims {
+        Claims {
+            iss: GITHUB_ISSUER.to_string(),
+            aud: Audience::One("pesde".to_string()),
+            exp: 0,
+            nbf: None,
+            repository: repository.to_string(),
+            workflow_ref: format!("{repository}/.github/workflows/{workflow}@refs/heads/main"),
+            environment: None,
+        }
+    }
+
+    #[test]
+    fn attributes_packages_to_their_publishers() {
+        let foo = "acme/foo".parse::<PackageName>().unwrap();
+        let bar = "other/bar".parse::<PackageName>().unwrap();
+        let baz = "acme/baz".parse::<PackageName>().unwrap();
+        let publishers = BTreeMap::from([
+            (foo.clone(), vec![publisher("acme/foo", "release.yml", 1)]),
+            // registered by another user for their own package
+            (bar.clone(), vec![publisher("acme/foo", "release.yml", 2)]),
+            (baz.clone(), vec![publisher("acme/foo", "docs.yml", 1)]),
+        ]);
+
+        let grant = TrustedPublishing::grant(&publishers, &claims("acme/foo", "release.yml"));
+        assert_eq!(grant.publisher_of(&foo), Some(UserId(1)));
+        assert_eq!(grant.publisher_of(&bar), Some(UserId(2)));
+        assert_eq!(grant.publisher_of(&baz), None);
+        assert_eq!(
+            grant.scopes(),
+            vec![
+                TokenScope::Publish("acme/foo".to_string()),
+                TokenScope::Publish("other/bar".to_string()),
+            ]
+        );
+
+        let grant = TrustedPublishing::grant(&publishers, &claims("acme/other", "release.yml"));
+        assert!(grant.publishers.is_empty());
+    }
+}
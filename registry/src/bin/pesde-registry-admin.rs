@@ -0,0 +1,352 @@
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use fs_err::tokio as fs;
+use pesde::names::PackageName;
+use reqwest::{header::AUTHORIZATION, Method, RequestBuilder, Response};
+use semver::Version;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{path::PathBuf, time::Duration};
+
+/// How often the progress of a rebuild of the search index is checked
+const REINDEX_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Performs administrative operations on a running pesde registry through its API
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// The URL of the registry's API
+    #[arg(
+        long,
+        env = "PESDE_REGISTRY_URL",
+        default_value = "http://127.0.0.1:8080"
+    )]
+    registry: String,
+
+    /// The token of an administrator, as sent in the `Authorization` header
+    #[arg(long, env = "PESDE_REGISTRY_TOKEN", hide_env_values = true)]
+    token: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Rebuilds the search index, waiting for it to complete
+    Reindex,
+
+    /// Deletes the stored files which the index doesn't reference
+    Gc {
+        /// Only report the orphaned files, without deleting them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Reloads the storage and rate limit settings from the registry's configuration file
+    Reload,
+
+    /// Yanks a version of a package
+    Yank {
+        /// The package to yank
+        package: PackageName,
+
+        /// The version to yank
+        version: Version,
+
+        /// The target to yank, or `any` for all of the version's targets
+        #[arg(short, long, default_value = "any")]
+        target: String,
+
+        /// Unyank the version instead
+        #[arg(long)]
+        undo: bool,
+    },
+
+    /// Bans a user from publishing
+    Ban {
+        /// The ID of the user to ban
+        user_id: u64,
+
+        /// The reason of the ban, which is shown to the user
+        #[arg(short, long)]
+        reason: String,
+    },
+
+    /// Lifts the ban of a user
+    Unban {
+        /// The ID of the user to unban
+        user_id: u64,
+    },
+
+    /// Imports the versions of an index snapshot exported with `pesde snapshot export`. The
+    /// versions' archives must already be stored
+    Import {
+        /// The path of the snapshot
+        snapshot: PathBuf,
+    },
+
+    /// Creates a user account on a registry using `USER_ACCOUNTS`, printing its API key
+    CreateUser {
+        /// The name of the user
+        name: String,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct ReindexProgress {
+    status: String,
+    indexed: u64,
+    total: u64,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+struct Client {
+    reqwest: reqwest::Client,
+    api_url: String,
+    token: String,
+}
+
+impl Client {
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.reqwest
+            .request(method, format!("{}/v0{path}", self.api_url))
+            .header(AUTHORIZATION, &self.token)
+    }
+
+    /// Sends the request, failing with the registry's error message if it wasn't successful
+    async fn send(&self, request: RequestBuilder) -> anyhow::Result<Response> {
+        let response = request
+            .send()
+            .await
+            .context("failed to send request to the registry")?;
+
+        check(response).await
+    }
+}
+
+/// Fails with the registry's error message if the response wasn't successful
+async fn check(response: Response) -> anyhow::Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<Value>(&body)
+        .ok()
+        .and_then(|body| body.get("error")?.as_str().map(str::to_string))
+        .unwrap_or(body);
+
+    if message.is_empty() {
+        anyhow::bail!("the registry responded with {status}")
+    } else {
+        anyhow::bail!("the registry responded with {status}: {message}")
+    }
+}
+
+fn package_path(package: &PackageName) -> String {
+    package.to_string().replace('/', "%2F")
+}
+
+async fn reindex(client: &Client) -> anyhow::Result<()> {
+    let response = client
+        .request(Method::POST, "/admin/reindex")
+        .send()
+        .await
+        .context("failed to send request to the registry")?;
+
+    // a rebuild which is already running is waited for instead
+    if response.status() == reqwest::StatusCode::CONFLICT {
+        println!("the search index is already being rebuilt");
+    } else {
+        check(response).await?;
+    }
+
+    let mut last_indexed = None;
+    loop {
+        let progress: ReindexProgress = client
+            .send(client.request(Method::GET, "/admin/reindex"))
+            .await?
+            .json()
+            .await
+            .context("failed to parse reindex progress")?;
+
+        match progress.status.as_str() {
+            "completed" => {
+                println!("rebuilt the search index with {} packages", progress.total);
+                return Ok(());
+            }
+            "failed" => anyhow::bail!(
+                "failed to rebuild the search index: {}",
+                progress.error.as_deref().unwrap_or("unknown error")
+            ),
+            _ => {}
+        }
+
+        if progress.total > 0 && last_indexed != Some(progress.indexed) {
+            println!("indexed {}/{} packages", progress.indexed, progress.total);
+            last_indexed = Some(progress.indexed);
+        }
+
+        tokio::time::sleep(REINDEX_POLL_INTERVAL).await;
+    }
+}
+
+async fn run(cli: Cli) -> anyhow::Result<()> {
+    let client = Client {
+        reqwest: reqwest::ClientBuilder::new()
+            .user_agent(concat!(
+                env!("CARGO_BIN_NAME"),
+                "/",
+                env!("CARGO_PKG_VERSION")
+            ))
+            .build()?,
+        api_url: cli.registry.trim_end_matches('/').to_string(),
+        token: cli.token,
+    };
+
+    match cli.command {
+        Command::Reindex => reindex(&client).await?,
+        Command::Gc { dry_run } => {
+            let report: Value = client
+                .send(
+                    client
+                        .request(Method::POST, "/storage/gc")
+                        .query(&[("dry_run", dry_run)]),
+                )
+                .await?
+                .json()
+                .await
+                .context("failed to parse garbage collection report")?;
+
+            if let Some(files) = report["orphaned_files"].as_array() {
+                for file in files.iter().filter_map(Value::as_str) {
+                    println!("{file}");
+                }
+            }
+            println!(
+                "{} {} orphaned files, reclaiming {} bytes",
+                if dry_run { "found" } else { "deleted" },
+                report["orphaned_files"].as_array().map_or(0, Vec::len),
+                report["reclaimed_bytes"].as_u64().unwrap_or_default()
+            );
+        }
+        Command::Reload => {
+            let report: Value = client
+                .send(client.request(Method::POST, "/admin/reload"))
+                .await?
+                .json()
+                .await
+                .context("failed to parse reloaded settings")?;
+
+            println!(
+                "storage: {}",
+                report["storage"].as_str().unwrap_or_default()
+            );
+            println!(
+                "rate limits: {}",
+                report["rate_limits"].as_str().unwrap_or_default()
+            );
+        }
+        Command::Yank {
+            package,
+            version,
+            target,
+            undo,
+        } => {
+            let action = if undo { "unyank" } else { "yank" };
+            client
+                .send(client.request(
+                    Method::PATCH,
+                    &format!(
+                        "/packages/{}/{version}/{target}/{action}",
+                        package_path(&package)
+                    ),
+                ))
+                .await?;
+
+            println!("{action}ed {package}@{version} {target}");
+        }
+        Command::Ban { user_id, reason } => {
+            client
+                .send(
+                    client
+                        .request(Method::PUT, &format!("/moderation/bans/{user_id}"))
+                        .json(&json!({ "reason": reason })),
+                )
+                .await?;
+
+            println!("banned user {user_id}");
+        }
+        Command::Unban { user_id } => {
+            client
+                .send(client.request(Method::DELETE, &format!("/moderation/bans/{user_id}")))
+                .await?;
+
+            println!("unbanned user {user_id}");
+        }
+        Command::Import { snapshot } => {
+            let bytes = fs::read(&snapshot)
+                .await
+                .context("failed to read snapshot")?;
+
+            let result: Value = client
+                .send(
+                    client
+                        .request(Method::POST, "/admin/import")
+                        .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+                        .body(bytes),
+                )
+                .await?
+                .json()
+                .await
+                .context("failed to parse import result")?;
+
+            println!(
+                "imported {} versions of {} packages, skipped {} versions already in the index",
+                result["versions"].as_u64().unwrap_or_default(),
+                result["packages"].as_u64().unwrap_or_default(),
+                result["skipped"].as_u64().unwrap_or_default()
+            );
+        }
+        Command::CreateUser { name } => {
+            let user: Value = client
+                .send(
+                    client
+                        .request(Method::POST, "/admin/users")
+                        .json(&json!({ "name": name })),
+                )
+                .await?
+                .json()
+                .await
+                .context("failed to parse created user")?;
+
+            println!(
+                "created user {} with ID {}",
+                user["name"].as_str().unwrap_or_default(),
+                user["id"].as_u64().unwrap_or_default()
+            );
+            println!(
+                "API key: {}",
+                user["key"]["key"].as_str().unwrap_or_default()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[actix_web::main]
+async fn main() {
+    let _ = dotenvy::dotenv();
+
+    if let Err(e) = run(Cli::parse()).await {
+        eprintln!("error: {e}");
+        for cause in e.chain().skip(1) {
+            eprintln!("  caused by: {cause}");
+        }
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    collections::HashMap,
+    env::VarError,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+use thiserror::Error;
+
+/// The kind of value a setting takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Text,
+    /// A non-negative integer
+    Number,
+    /// Enabled by being set to anything in the environment, or to `true` in the file
+    Flag,
+    /// A comma-separated list in the environment, or an array in the file
+    List,
+}
+
+/// The settings of the registry, by the section of the configuration file they're in. In the file,
+/// they're named after their environment variable in lowercase
+const SETTINGS: &[(&str, &[(&str, Kind)])] = &[
+    (
+        "server",
+        &[
+            ("ADDRESS", Kind::Text),
+            ("PORT", Kind::Number),
+            ("DATA_DIR", Kind::Text),
+            ("METRICS_TOKEN", Kind::Text),
+            ("SENTRY_DSN", Kind::Text),
+            ("SHUTDOWN_TIMEOUT_SECONDS", Kind::Number),
+            ("LOG_FORMAT", Kind::Text),
+            ("CORS_ALLOWED_ORIGINS", Kind::List),
+            ("CORS_ALLOWED_METHODS", Kind::List),
+            ("CORS_ALLOWED_HEADERS", Kind::List),
+            ("CORS_MAX_AGE_SECONDS", Kind::Number),
+        ],
+    ),
+    (
+        "index",
+        &[
+            ("INDEX_REPO_URL", Kind::Text),
+            ("GIT_USERNAME", Kind::Text),
+            ("GIT_PASSWORD", Kind::Text),
+            ("COMMITTER_GIT_NAME", Kind::Text),
+            ("COMMITTER_GIT_EMAIL", Kind::Text),
+            ("METADATA_CACHE", Kind::Flag),
+            ("METADATA_CACHE_RECONCILE_MINUTES", Kind::Number),
+            ("SEARCH_RANKING_REFRESH_MINUTES", Kind::Number),
+        ],
+    ),
+    (
+        "storage",
+        &[
+            ("FS_STORAGE_ROOT", Kind::Text),
+            ("FS_STORAGE_PUBLIC_URL", Kind::Text),
+            ("S3_ENDPOINT", Kind::Text),
+            ("S3_BUCKET_NAME", Kind::Text),
+            ("S3_REGION", Kind::Text),
+            ("S3_ACCESS_KEY", Kind::Text),
+            ("S3_SECRET_KEY", Kind::Text),
+            ("S3_PUBLIC_URL", Kind::Text),
+            ("GC_INTERVAL_HOURS", Kind::Number),
+            ("RECONCILE_INTERVAL_HOURS", Kind::Number),
+            ("RECONCILE_FIX", Kind::Flag),
+            ("UPLOAD_SESSION_TIMEOUT_MINUTES", Kind::Number),
+        ],
+    ),
+    (
+        "auth",
+        &[
+            ("USER_ACCOUNTS", Kind::Flag),
+            ("ACCESS_TOKEN", Kind::Text),
+            ("GITHUB_CLIENT_SECRET", Kind::Text),
+            ("OAUTH_PROVIDER", Kind::Text),
+            ("OAUTH_ISSUER", Kind::Text),
+            ("OAUTH_CLIENT_ID", Kind::Text),
+            ("READ_ACCESS_TOKEN", Kind::Text),
+            ("WRITE_ACCESS_TOKEN", Kind::Text),
+            ("READ_NEEDS_AUTH", Kind::Flag),
+            ("ADMIN_USER_IDS", Kind::List),
+            ("RESTRICT_SCOPE_CREATION", Kind::Flag),
+            ("GITHUB_TEAMS_TOKEN", Kind::Text),
+            ("OIDC_AUDIENCE", Kind::Text),
+        ],
+    ),
+    (
+        "limits",
+        &[
+            ("RATE_LIMITS", Kind::List),
+            ("TRUSTED_PROXIES", Kind::List),
+            ("PUBLISH_MAX_FILE_SIZE", Kind::Number),
+            ("PUBLISH_MAX_UNPACKED_SIZE", Kind::Number),
+            ("PUBLISH_FORBIDDEN_EXTENSIONS", Kind::List),
+            ("PUBLISH_MAX_DOC_PAGE_SIZE", Kind::Number),
+            ("PUBLISH_MAX_DOCS_SIZE", Kind::Number),
+            ("DOWNLOADS_DAILY_RETENTION_DAYS", Kind::Number),
+            ("DOWNLOADS_MONTHLY_RETENTION_MONTHS", Kind::Number),
+        ],
+    ),
+    (
+        "telemetry",
+        &[
+            ("OTLP_ENDPOINT", Kind::Text),
+            ("OTLP_SERVICE_NAME", Kind::Text),
+            ("OTLP_SAMPLE_RATIO", Kind::Text),
+        ],
+    ),
+    (
+        "features",
+        &[
+            ("SCAN_SECRETS", Kind::Flag),
+            ("SCAN_COMMAND", Kind::Text),
+            ("SCAN_URL", Kind::Text),
+            ("SCAN_ACTION", Kind::Text),
+            ("WEBHOOK_URLS", Kind::List),
+            ("WEBHOOK_SECRET", Kind::Text),
+            ("EMAIL_FROM", Kind::Text),
+            ("SMTP_URL", Kind::Text),
+            ("SES_REGION", Kind::Text),
+            ("SES_ACCESS_KEY", Kind::Text),
+            ("SES_SECRET_KEY", Kind::Text),
+            ("UPSTREAM_API_URL", Kind::Text),
+            ("UPSTREAM_INDEX_URL", Kind::Text),
+            ("UPSTREAM_TOKEN", Kind::Text),
+            ("UPSTREAM_REFRESH_MINUTES", Kind::Number),
+        ],
+    ),
+];
+
+type File = (PathBuf, HashMap<&'static str, String>);
+
+/// The path of the configuration file, and its values by the name of their environment variable
+static FILE: RwLock<Option<File>> = RwLock::new(None);
+
+/// The configuration file as it was before being reloaded
+pub struct Previous(Option<File>);
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("failed to read configuration file {}: {}", .0.display(), .1)]
+    Read(PathBuf, std::io::Error),
+
+    #[error("failed to parse configuration file: {0}")]
+    Parse(#[from] toml::de::Error),
+
+    #[error("unknown section `{0}`")]
+    UnknownSection(String),
+
+    #[error("unknown setting `{1}` in section `{0}`")]
+    UnknownSetting(String, String),
+
+    #[error("setting `{0}` must be {1}")]
+    InvalidValue(String, &'static str),
+}
+
+fn expected(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Text => "a string",
+        Kind::Number => "a non-negative integer",
+        Kind::Flag => "a boolean",
+        Kind::List => "an array or a comma-separated string",
+    }
+}
+
+/// Converts a value of the file into its environment variable form, or `None` if it's a disabled
+/// flag
+fn file_value(kind: Kind, value: toml::Value) -> Result<Option<String>, ()> {
+    use toml::Value;
+
+    Ok(Some(match (kind, value) {
+        (Kind::Flag, Value::Boolean(enabled)) => return Ok(enabled.then(|| "true".to_string())),
+        (Kind::Flag, _) => return Err(()),
+        (Kind::Number, Value::Integer(number)) if number >= 0 => number.to_string(),
+        (Kind::Text | Kind::List, Value::String(text)) => text,
+        (Kind::Number, Value::String(text)) if text.parse::<u64>().is_ok() => text,
+        (Kind::Text, Value::Integer(number)) => number.to_string(),
+        (Kind::List, Value::Array(items)) => items
+            .into_iter()
+            .map(|item| match item {
+                Value::String(text) => Ok(text),
+                Value::Integer(number) => Ok(number.to_string()),
+                _ => Err(()),
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .join(","),
+        _ => return Err(()),
+    }))
+}
+
+fn read(path: &Path) -> Result<HashMap<&'static str, String>, ConfigError> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+    let table: toml::Table = toml::from_str(&contents)?;
+    let mut values = HashMap::new();
+
+    for (section, settings) in table {
+        let Some((_, known)) = SETTINGS.iter().find(|(name, _)| *name == section) else {
+            return Err(ConfigError::UnknownSection(section));
+        };
+        let toml::Value::Table(settings) = settings else {
+            return Err(ConfigError::UnknownSection(section));
+        };
+
+        for (key, value) in settings {
+            let Some((name, kind)) = known
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(&key))
+            else {
+                return Err(ConfigError::UnknownSetting(section, key));
+            };
+
+            match file_value(*kind, value) {
+                Ok(Some(value)) => {
+                    values.insert(*name, value);
+                }
+                Ok(None) => {}
+                Err(()) => {
+                    return Err(ConfigError::InvalidValue(
+                        format!("{section}.{key}"),
+                        expected(*kind),
+                    ))
+                }
+            }
+        }
+    }
+
+    Ok(values)
+}
+
+/// Loads the configuration file, whose values are used for the settings which aren't set in the
+/// environment
+pub fn load(path: &Path) -> Result<(), ConfigError> {
+    let values = read(path)?;
+    *FILE.write().unwrap() = Some((path.to_path_buf(), values));
+
+    Ok(())
+}
+
+/// Reads the configuration file again, if one was loaded, returning its previous values so that
+/// they can be restored. The previous values are kept if it's invalid
+pub fn reload() -> Result<Previous, ConfigError> {
+    let Some(path) = FILE.read().unwrap().as_ref().map(|(path, _)| path.clone()) else {
+        return Ok(Previous(None));
+    };
+
+    let values = read(&path)?;
+    let previous = Previous(FILE.write().unwrap().replace((path, values)));
+
+    if let Err(e) = validate() {
+        restore(previous);
+        return Err(e);
+    }
+
+    Ok(previous)
+}
+
+/// Restores the values the configuration file had before being reloaded
+pub fn restore(previous: Previous) {
+    if previous.0.is_some() {
+        *FILE.write().unwrap() = previous.0;
+    }
+}
+
+/// Reads a setting from the environment, falling back to the configuration file
+pub fn var(name: &str) -> Result<String, VarError> {
+    match std::env::var(name) {
+        Err(VarError::NotPresent) => FILE
+            .read()
+            .unwrap()
+            .as_ref()
+            .and_then(|(_, values)| values.get(name).cloned())
+            .ok_or(VarError::NotPresent),
+        result => result,
+    }
+}
+
+/// Checks that the numeric settings which are set, from either source, are numbers
+pub fn validate() -> Result<(), ConfigError> {
+    for (_, settings) in SETTINGS {
+        for (name, kind) in *settings {
+            if *kind != Kind::Number {
+                continue;
+            }
+
+            if let Ok(value) = var(name) {
+                if value.parse::<u64>().is_err() {
+                    return Err(ConfigError::InvalidValue(name.to_string(), expected(*kind)));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use crate::benv;
+use actix_cors::Cors;
+use actix_web::http::{header::HeaderName, Method, Uri};
+use std::{fmt::Display, str::FromStr};
+
+/// Which origins may make requests to the registry from browsers
+#[derive(Debug, Clone)]
+pub enum AllowedOrigins {
+    Any,
+    Some(Vec<String>),
+}
+
+/// The CORS policy of the registry
+#[derive(Debug, Clone)]
+pub enum CorsPolicy {
+    /// Allows any request from any origin, used by debug builds unless a policy is configured
+    Permissive,
+    Configured {
+        origins: AllowedOrigins,
+        methods: Vec<Method>,
+        headers: Vec<HeaderName>,
+        max_age: usize,
+    },
+}
+
+fn parse_list<T: FromStr>(name: &str, default: &str) -> Vec<T>
+where
+    T::Err: Display,
+{
+    benv!(name => default)
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            item.parse()
+                .unwrap_or_else(|e| panic!("Setting `{name}` contains invalid value `{item}`: {e}"))
+        })
+        .collect()
+}
+
+/// Reads the CORS policy. Without any of the `CORS_*` settings, release builds only allow reads
+/// from other origins, while debug builds allow everything
+pub fn get_cors_policy_from_env() -> CorsPolicy {
+    let configured = [
+        "CORS_ALLOWED_ORIGINS",
+        "CORS_ALLOWED_METHODS",
+        "CORS_ALLOWED_HEADERS",
+        "CORS_MAX_AGE_SECONDS",
+    ]
+    .into_iter()
+    .any(|name| benv!(name).is_ok());
+    if !configured && cfg!(debug_assertions) {
+        return CorsPolicy::Permissive;
+    }
+
+    let origins = parse_list::<String>("CORS_ALLOWED_ORIGINS", "*");
+    let origins = if origins.iter().any(|origin| origin == "*") {
+        AllowedOrigins::Any
+    } else {
+        for origin in &origins {
+            let valid = origin
+                .parse::<Uri>()
+                .is_ok_and(|uri| uri.scheme().is_some() && uri.host().is_some());
+            if !valid {
+                panic!("Setting `CORS_ALLOWED_ORIGINS` contains invalid origin `{origin}`");
+            }
+        }
+
+        AllowedOrigins::Some(origins)
+    };
+
+    CorsPolicy::Configured {
+        origins,
+        methods: parse_list("CORS_ALLOWED_METHODS", "GET,HEAD"),
+        headers: parse_list("CORS_ALLOWED_HEADERS", "authorization,range"),
+        max_age: benv!(parse "CORS_MAX_AGE_SECONDS" => "3600"),
+    }
+}
+
+impl CorsPolicy {
+    pub fn middleware(&self) -> Cors {
+        let CorsPolicy::Configured {
+            origins,
+            methods,
+            headers,
+            max_age,
+        } = self
+        else {
+            return Cors::permissive();
+        };
+
+        let cors = Cors::default()
+            .allowed_methods(methods.clone())
+            .allowed_headers(headers.clone())
+            // the rate limit and trace headers are useful to any client
+            .expose_any_header()
+            .max_age(*max_age);
+
+        match origins {
+            AllowedOrigins::Any => cors.allow_any_origin(),
+            AllowedOrigins::Some(origins) => origins
+                .iter()
+                .fold(cors, |cors, origin| cors.allowed_origin(origin)),
+        }
+    }
+}
+
+impl Display for CorsPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let CorsPolicy::Configured {
+            origins,
+            methods,
+            headers,
+            max_age,
+        } = self
+        else {
+            return write!(f, "permissive");
+        };
+
+        match origins {
+            AllowedOrigins::Any => write!(f, "any origin")?,
+            AllowedOrigins::Some(origins) => write!(f, "origins {}", origins.join(", "))?,
+        }
+
+        write!(
+            f,
+            ", methods {}, headers {}, max age {max_age}s",
+            methods
+                .iter()
+                .map(Method::as_str)
+                .collect::<Vec<_>>()
+                .join(", "),
+            headers
+                .iter()
+                .map(HeaderName::as_str)
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    }
+}
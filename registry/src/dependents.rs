@@ -0,0 +1,118 @@
+use crate::search::all_packages;
+use futures::StreamExt;
+use pesde::{
+    manifest::DependencyType,
+    names::PackageName,
+    source::{
+        git_index::GitBasedSource,
+        pesde::{IndexFile, PesdePackageSource},
+        specifiers::DependencySpecifiers,
+        version_id::VersionId,
+    },
+    Project,
+};
+use semver::VersionReq;
+use std::collections::{BTreeMap, HashMap};
+use tokio::pin;
+
+/// How a version of a package depends on another package
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    pub requirement: VersionReq,
+    pub ty: DependencyType,
+}
+
+/// The versions of a package which depend on another package
+pub type DependentVersions = BTreeMap<VersionId, Dependency>;
+
+/// The packages which depend on each package of this registry. Yanked versions aren't counted,
+/// as they're excluded from new resolutions
+#[derive(Debug)]
+pub struct Dependents {
+    /// The URL of this registry's index, which dependencies must be resolved from to be counted
+    index_url: gix::Url,
+    dependents: std::sync::RwLock<HashMap<PackageName, BTreeMap<PackageName, DependentVersions>>>,
+}
+
+/// Strips the differences between URLs of the same repository
+fn simplify_url(url: &gix::Url) -> String {
+    let url = url.to_bstring().to_string();
+    let url = url.trim_end_matches('/');
+    url.strip_suffix(".git").unwrap_or(url).to_string()
+}
+
+impl Dependents {
+    pub async fn build(source: &PesdePackageSource, project: &Project) -> Self {
+        let dependents = Self {
+            index_url: source.repo_url().clone(),
+            dependents: Default::default(),
+        };
+
+        let packages = all_packages(source, project).await;
+        pin!(packages);
+
+        while let Some((name, file)) = packages.next().await {
+            dependents.add(&name, &file);
+        }
+
+        dependents
+    }
+
+    /// Whether a dependency on the given index is resolved from this registry
+    pub fn is_own_index(&self, index: Option<&str>) -> bool {
+        index
+            .and_then(|index| gix::Url::try_from(index).ok())
+            .is_some_and(|url| simplify_url(&url) == simplify_url(&self.index_url))
+    }
+
+    fn add(&self, name: &PackageName, file: &IndexFile) {
+        let mut dependents = self.dependents.write().unwrap();
+
+        for (v_id, entry) in file.iter().filter(|(_, entry)| !entry.yanked) {
+            for (specifier, ty) in entry.dependencies.values() {
+                let DependencySpecifiers::Pesde(specifier) = specifier else {
+                    continue;
+                };
+                if !self.is_own_index(specifier.index.as_deref()) {
+                    continue;
+                }
+
+                dependents
+                    .entry(specifier.name.clone())
+                    .or_default()
+                    .entry(name.clone())
+                    .or_default()
+                    .insert(
+                        v_id.clone(),
+                        Dependency {
+                            requirement: specifier.version.clone(),
+                            ty: *ty,
+                        },
+                    );
+            }
+        }
+    }
+
+    /// Updates the dependencies of a package after its versions changed
+    pub fn update(&self, name: &PackageName, file: &IndexFile) {
+        {
+            let mut dependents = self.dependents.write().unwrap();
+            dependents.retain(|_, packages| {
+                packages.remove(name);
+                !packages.is_empty()
+            });
+        }
+
+        self.add(name, file);
+    }
+
+    /// The packages depending on the package, sorted by their names
+    pub fn get(&self, name: &PackageName) -> BTreeMap<PackageName, DependentVersions> {
+        self.dependents
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
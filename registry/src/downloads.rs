@@ -0,0 +1,423 @@
+use crate::{benv, write_json_atomically};
+use chrono::{Datelike, Days, Months, NaiveDate, Utc};
+use fs_err::tokio as fs;
+use pesde::{names::PackageName, source::version_id::VersionId};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use utoipa::ToSchema;
+
+/// How many days of download history are returned by default
+pub const HISTORY_DAYS: u64 = 30;
+/// How often the recorded downloads are written to disk
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long download history is kept, and at which granularity
+#[derive(Debug, Clone, Copy)]
+pub struct DownloadRetention {
+    /// How many days daily downloads are kept for, before being rolled up into monthly ones
+    pub daily_days: u64,
+    /// How many months monthly downloads are kept for
+    pub monthly_months: u32,
+}
+
+pub fn get_download_retention_from_env() -> DownloadRetention {
+    let daily_days: u64 = benv!(parse "DOWNLOADS_DAILY_RETENTION_DAYS" => "90");
+
+    DownloadRetention {
+        // the default history must be kept daily
+        daily_days: daily_days.max(HISTORY_DAYS),
+        monthly_months: benv!(parse "DOWNLOADS_MONTHLY_RETENTION_MONTHS" => "24"),
+    }
+}
+
+impl Display for DownloadRetention {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} days daily, {} months monthly",
+            self.daily_days, self.monthly_months
+        )
+    }
+}
+
+/// The first day of the month of the date
+fn month_of(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap()
+}
+
+/// Downloads over time, bucketed by day for recent days and by month, keyed by their first day,
+/// for older ones. A day's downloads are in either bucket, never both
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Series {
+    daily: BTreeMap<NaiveDate, u64>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    monthly: BTreeMap<NaiveDate, u64>,
+}
+
+impl Series {
+    fn record(&mut self, date: NaiveDate, downloads: u64) {
+        *self.daily.entry(date).or_default() += downloads;
+    }
+
+    /// Rolls the daily downloads before the cutoff up into monthly ones, and drops the monthly
+    /// downloads before the month of the other cutoff
+    fn roll_up(&mut self, daily_cutoff: NaiveDate, monthly_cutoff: NaiveDate) {
+        let recent = self.daily.split_off(&daily_cutoff);
+        for (date, downloads) in std::mem::replace(&mut self.daily, recent) {
+            *self.monthly.entry(month_of(date)).or_default() += downloads;
+        }
+
+        self.monthly = self.monthly.split_off(&month_of(monthly_cutoff));
+    }
+
+    fn is_empty(&self) -> bool {
+        self.daily.is_empty() && self.monthly.is_empty()
+    }
+
+    fn day(&self, date: NaiveDate) -> u64 {
+        self.daily.get(&date).copied().unwrap_or_default()
+    }
+
+    /// The downloads from the start date up to the end date, excluding it. Monthly downloads are
+    /// counted whole if their month starts in the range
+    fn between(&self, start: NaiveDate, end: NaiveDate) -> u64 {
+        self.daily.range(start..end).map(|(_, d)| d).sum::<u64>()
+            + self
+                .monthly
+                .range(month_of(start)..end)
+                .map(|(_, d)| d)
+                .sum::<u64>()
+    }
+
+    fn month(&self, month: NaiveDate) -> u64 {
+        let next_month = month + Months::new(1);
+
+        self.monthly.get(&month).copied().unwrap_or_default()
+            + self
+                .daily
+                .range(month..next_month)
+                .map(|(_, d)| d)
+                .sum::<u64>()
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PackageDownloads {
+    /// The downloads of each version, since they were published
+    versions: BTreeMap<VersionId, u64>,
+    /// The downloads of the package over time
+    #[serde(flatten)]
+    series: Series,
+    /// The downloads of each version over time
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    version_series: BTreeMap<VersionId, Series>,
+}
+
+/// The span of time download statistics are requested for, such as `90d`, `12w`, `6m` or `2y`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Period {
+    Days(u64),
+    Months(u32),
+}
+
+impl Period {
+    /// The start and (exclusive) end of the period ending today, or of the one `back` periods
+    /// before it. Periods in months start on the first day of a month
+    fn range(self, today: NaiveDate, back: u32) -> (NaiveDate, NaiveDate) {
+        match self {
+            Period::Days(days) => {
+                let end = today + Days::new(1) - Days::new(days * u64::from(back));
+                (end - Days::new(days), end)
+            }
+            Period::Months(months) => {
+                let end = month_of(today) + Months::new(1) - Months::new(months * back);
+                (end - Months::new(months), end)
+            }
+        }
+    }
+}
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || format!("invalid period `{s}`, expected a number followed by d, w, m or y");
+
+        let unit = s.chars().last().ok_or_else(invalid)?;
+        let amount = s[..s.len() - unit.len_utf8()]
+            .parse::<u32>()
+            .ok()
+            .filter(|amount| *amount > 0)
+            .ok_or_else(invalid)?;
+
+        match unit {
+            'd' => Ok(Period::Days(amount.into())),
+            'w' => Ok(Period::Days(u64::from(amount) * 7)),
+            'm' => Ok(Period::Months(amount)),
+            'y' => amount
+                .checked_mul(12)
+                .map(Period::Months)
+                .ok_or_else(invalid),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// The granularity of download statistics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Interval {
+    Day,
+    Month,
+}
+
+/// A way of ranking packages by their downloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ranking {
+    /// The most downloaded packages over the period, or since they were published without one
+    Popular(Option<Period>),
+    /// The packages whose downloads grew the most over the period, compared to the period before
+    Trending(Period),
+}
+
+/// A package's place in a ranking
+#[derive(Debug, Clone)]
+pub struct RankedPackage {
+    pub name: PackageName,
+    /// The downloads of the package over the ranking's period
+    pub downloads: u64,
+    /// The downloads of the package over the period before, for trending rankings
+    pub previous_downloads: Option<u64>,
+}
+
+/// The packages of a ranking, in order
+type Ranked = Arc<Vec<RankedPackage>>;
+
+/// Download counts of packages, persisted in a file of the data directory. Downloads are buffered
+/// in memory and periodically merged into the counts, so that recording one doesn't wait on others
+#[derive(Debug)]
+pub struct Downloads {
+    path: PathBuf,
+    retention: DownloadRetention,
+    pending: std::sync::Mutex<HashMap<(PackageName, VersionId), u64>>,
+    counts: tokio::sync::Mutex<BTreeMap<PackageName, PackageDownloads>>,
+    /// The rankings computed since the counts last changed, by the day they were computed on
+    rankings: std::sync::Mutex<HashMap<(Ranking, NaiveDate), Ranked>>,
+}
+
+impl Downloads {
+    pub async fn load(path: PathBuf, retention: DownloadRetention) -> std::io::Result<Self> {
+        let counts = match fs::read_to_string(&path).await {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            path,
+            retention,
+            pending: Default::default(),
+            counts: tokio::sync::Mutex::new(counts),
+            rankings: Default::default(),
+        })
+    }
+
+    pub fn record(&self, name: &PackageName, version_id: &VersionId) {
+        *self
+            .pending
+            .lock()
+            .unwrap()
+            .entry((name.clone(), version_id.clone()))
+            .or_default() += 1;
+    }
+
+    /// Merges the pending downloads into the counts, rolls up the old ones and writes them to disk
+    pub async fn flush(&self) -> std::io::Result<()> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+
+        let mut counts = self.counts.lock().await;
+        let today = Utc::now().date_naive();
+        let daily_cutoff = today - Days::new(self.retention.daily_days - 1);
+        let monthly_cutoff = today - Months::new(self.retention.monthly_months);
+
+        {
+            let changed = !pending.is_empty();
+            self.rankings
+                .lock()
+                .unwrap()
+                .retain(|(_, date), _| !changed && *date == today);
+        }
+
+        for ((name, version_id), downloads) in pending {
+            let package = counts.entry(name).or_default();
+            *package.versions.entry(version_id.clone()).or_default() += downloads;
+            package.series.record(today, downloads);
+            package
+                .version_series
+                .entry(version_id)
+                .or_default()
+                .record(today, downloads);
+        }
+
+        for package in counts.values_mut() {
+            package.series.roll_up(daily_cutoff, monthly_cutoff);
+            for series in package.version_series.values_mut() {
+                series.roll_up(daily_cutoff, monthly_cutoff);
+            }
+            package
+                .version_series
+                .retain(|_, series| !series.is_empty());
+        }
+
+        write_json_atomically(&self.path, &*counts).await
+    }
+
+    /// The downloads of a package which have been flushed, with its daily downloads for each of the
+    /// last days (including those without any downloads), oldest first
+    pub async fn get(
+        &self,
+        name: &PackageName,
+    ) -> (BTreeMap<VersionId, u64>, Vec<(NaiveDate, u64)>) {
+        let versions = self
+            .counts
+            .lock()
+            .await
+            .get(name)
+            .map(|package| package.versions.clone())
+            .unwrap_or_default();
+
+        let (_, recent) = self.series(name, None, Period::Days(HISTORY_DAYS)).await;
+
+        (versions, recent)
+    }
+
+    /// The downloads of a version which have been flushed
+    pub async fn of_version(&self, name: &PackageName, version_id: &VersionId) -> u64 {
+        self.counts
+            .lock()
+            .await
+            .get(name)
+            .and_then(|package| package.versions.get(version_id).copied())
+            .unwrap_or_default()
+    }
+
+    /// The downloads of a package, or of some of its versions, over the period, oldest first. The
+    /// downloads are daily if the period is in days and the daily downloads are still kept for all
+    /// of it, and monthly otherwise
+    pub async fn series(
+        &self,
+        name: &PackageName,
+        version_ids: Option<&[VersionId]>,
+        period: Period,
+    ) -> (Interval, Vec<(NaiveDate, u64)>) {
+        let counts = self.counts.lock().await;
+        let series = counts
+            .get(name)
+            .map(|package| match version_ids {
+                Some(version_ids) => version_ids
+                    .iter()
+                    .filter_map(|version_id| package.version_series.get(version_id))
+                    .collect(),
+                None => vec![&package.series],
+            })
+            .unwrap_or_default();
+
+        let today = Utc::now().date_naive();
+        match period {
+            Period::Days(days) if days <= self.retention.daily_days => (
+                Interval::Day,
+                (0..days)
+                    .rev()
+                    .map(|days| {
+                        let date = today - Days::new(days);
+                        (date, series.iter().map(|s| s.day(date)).sum())
+                    })
+                    .collect(),
+            ),
+            _ => {
+                let months = match period {
+                    Period::Days(days) => u32::try_from(days.div_ceil(30)).unwrap_or(u32::MAX),
+                    Period::Months(months) => months,
+                }
+                .min(self.retention.monthly_months + 1);
+
+                (
+                    Interval::Month,
+                    (0..months)
+                        .rev()
+                        .map(|months| {
+                            let month = month_of(today) - Months::new(months);
+                            (month, series.iter().map(|s| s.month(month)).sum())
+                        })
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    /// The packages ranked by their downloads, most downloaded or grown first. Only packages with
+    /// downloads in the period, or grown downloads for trending rankings, are ranked. Rankings are
+    /// cached until the counts change
+    pub async fn ranking(&self, ranking: Ranking) -> Ranked {
+        let today = Utc::now().date_naive();
+        if let Some(ranked) = self.rankings.lock().unwrap().get(&(ranking, today)) {
+            return ranked.clone();
+        }
+
+        // held until the ranking is cached, as flushing clears the cache while holding it
+        let counts = self.counts.lock().await;
+        let mut ranked = counts
+            .iter()
+            .filter_map(|(name, package)| {
+                let (downloads, previous_downloads) = match ranking {
+                    Ranking::Popular(None) => (package.versions.values().sum(), None),
+                    Ranking::Popular(Some(period)) => {
+                        let (start, end) = period.range(today, 0);
+                        (package.series.between(start, end), None)
+                    }
+                    Ranking::Trending(period) => {
+                        let (start, end) = period.range(today, 0);
+                        let (previous_start, previous_end) = period.range(today, 1);
+                        let downloads = package.series.between(start, end);
+                        let previous = package.series.between(previous_start, previous_end);
+                        if downloads <= previous {
+                            return None;
+                        }
+
+                        (downloads, Some(previous))
+                    }
+                };
+
+                (downloads > 0).then(|| RankedPackage {
+                    name: name.clone(),
+                    downloads,
+                    previous_downloads,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        ranked.sort_by(|a, b| {
+            let growth = |p: &RankedPackage| p.downloads - p.previous_downloads.unwrap_or_default();
+            growth(b)
+                .cmp(&growth(a))
+                .then_with(|| b.downloads.cmp(&a.downloads))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        let ranked = Arc::new(ranked);
+        self.rankings
+            .lock()
+            .unwrap()
+            .insert((ranking, today), ranked.clone());
+
+        ranked
+    }
+}
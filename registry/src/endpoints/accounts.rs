@@ -0,0 +1,338 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    audit::Action,
+    auth::{
+        accounts::{Account, Accounts, ApiKey},
+        api_tokens::Permissions,
+        is_admin, Auth, UserId,
+    },
+    endpoints::openapi::shared::{AccountNotFound, BadRequest, Forbidden, Unauthorized},
+    error::{Error, ErrorResponse},
+    AppState,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NameRequest {
+    name: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = ApiKey)]
+pub struct KeyResponse {
+    id: String,
+    name: String,
+    created_at: DateTime<Utc>,
+}
+
+impl From<ApiKey> for KeyResponse {
+    fn from(key: ApiKey) -> Self {
+        KeyResponse {
+            id: key.id,
+            name: key.name,
+            created_at: key.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = CreatedApiKey)]
+pub struct CreatedKeyResponse {
+    #[serde(flatten)]
+    info: KeyResponse,
+    /// The key itself, which can't be retrieved again
+    key: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AccountResponse {
+    id: u64,
+    name: String,
+    created_at: DateTime<Utc>,
+    keys: Vec<KeyResponse>,
+}
+
+impl AccountResponse {
+    fn new(account: Account, keys: Vec<ApiKey>) -> Self {
+        AccountResponse {
+            id: account.id,
+            name: account.name,
+            created_at: account.created_at,
+            keys: keys.into_iter().map(KeyResponse::from).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedAccountResponse {
+    id: u64,
+    name: String,
+    created_at: DateTime<Utc>,
+    /// The account's first key
+    key: CreatedKeyResponse,
+}
+
+/// The accounts of the registry, if it manages its own users, or the response to send otherwise
+fn accounts(app_state: &AppState) -> Result<&Accounts, HttpResponse> {
+    match &app_state.auth {
+        Auth::Accounts(accounts) => Ok(accounts),
+        _ => Err(HttpResponse::NotFound().json(ErrorResponse {
+            error: "this registry doesn't manage its own users".to_string(),
+        })),
+    }
+}
+
+fn validate_name(name: &str, what: &str) -> Result<String, HttpResponse> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("{what} name cannot be empty"),
+        }));
+    }
+
+    Ok(name.to_string())
+}
+
+/// Lists the registry's user accounts
+///
+/// Only available on registries using `USER_ACCOUNTS`
+#[utoipa::path(
+    get,
+    path = "/v0/admin/users",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The accounts, sorted by ID", body = Vec<AccountResponse>),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = AccountNotFound),
+    )
+)]
+pub async fn get_users(
+    app_state: web::Data<AppState>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+    let accounts = match accounts(&app_state) {
+        Ok(accounts) => accounts,
+        Err(response) => return Ok(response),
+    };
+
+    let users = accounts
+        .list()
+        .await
+        .into_iter()
+        .map(|(account, keys)| AccountResponse::new(account, keys))
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(users))
+}
+
+/// Creates a user account along with its first API key
+#[utoipa::path(
+    post,
+    path = "/v0/admin/users",
+    request_body = NameRequest,
+    security(("token" = [])),
+    responses(
+        (status = 201, description = "The account was created", body = CreatedAccountResponse),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = AccountNotFound),
+        (status = 409, description = "A user with the name already exists", body = ErrorResponse),
+    )
+)]
+pub async fn create_user(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    request: web::Json<NameRequest>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+    let accounts = match accounts(&app_state) {
+        Ok(accounts) => accounts,
+        Err(response) => return Ok(response),
+    };
+    let name = match validate_name(&request.name, "user") {
+        Ok(name) => name,
+        Err(response) => return Ok(response),
+    };
+
+    let Some((account, key, secret)) = accounts.create_user(name).await? else {
+        return Ok(HttpResponse::Conflict().json(ErrorResponse {
+            error: format!("a user named {} already exists", request.name.trim()),
+        }));
+    };
+
+    app_state
+        .audit_log
+        .record(
+            &http_request,
+            *user_id,
+            Action::CreateUser {
+                user: account.id,
+                name: account.name.clone(),
+            },
+        )
+        .await;
+
+    Ok(HttpResponse::Created().json(CreatedAccountResponse {
+        id: account.id,
+        name: account.name,
+        created_at: account.created_at,
+        key: CreatedKeyResponse {
+            info: key.into(),
+            key: secret,
+        },
+    }))
+}
+
+/// Deletes a user account and its API keys
+#[utoipa::path(
+    delete,
+    path = "/v0/admin/users/{id}",
+    params(("id" = u64, Path)),
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The account was deleted"),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = AccountNotFound),
+    )
+)]
+pub async fn delete_user(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    path: web::Path<u64>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+    let accounts = match accounts(&app_state) {
+        Ok(accounts) => accounts,
+        Err(response) => return Ok(response),
+    };
+
+    let user = path.into_inner();
+    if !accounts.delete_user(UserId(user)).await? {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    app_state
+        .audit_log
+        .record(&http_request, *user_id, Action::DeleteUser { user })
+        .await;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Creates an API key for a user account
+#[utoipa::path(
+    post,
+    path = "/v0/admin/users/{id}/keys",
+    params(("id" = u64, Path)),
+    request_body = NameRequest,
+    security(("token" = [])),
+    responses(
+        (status = 201, description = "The key was created", body = CreatedKeyResponse),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = AccountNotFound),
+    )
+)]
+pub async fn create_key(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    path: web::Path<u64>,
+    request: web::Json<NameRequest>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+    let accounts = match accounts(&app_state) {
+        Ok(accounts) => accounts,
+        Err(response) => return Ok(response),
+    };
+    let name = match validate_name(&request.name, "key") {
+        Ok(name) => name,
+        Err(response) => return Ok(response),
+    };
+
+    let user = path.into_inner();
+    let Some((key, secret)) = accounts.create_key(UserId(user), name).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    app_state
+        .audit_log
+        .record(
+            &http_request,
+            *user_id,
+            Action::CreateKey {
+                user,
+                id: key.id.clone(),
+                name: key.name.clone(),
+            },
+        )
+        .await;
+
+    Ok(HttpResponse::Created().json(CreatedKeyResponse {
+        info: key.into(),
+        key: secret,
+    }))
+}
+
+/// Revokes an API key of a user account
+#[utoipa::path(
+    delete,
+    path = "/v0/admin/users/{id}/keys/{key_id}",
+    params(("id" = u64, Path), ("key_id" = String, Path)),
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The key was revoked"),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = AccountNotFound),
+    )
+)]
+pub async fn revoke_key(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    path: web::Path<(u64, String)>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+    let accounts = match accounts(&app_state) {
+        Ok(accounts) => accounts,
+        Err(response) => return Ok(response),
+    };
+
+    let (user, id) = path.into_inner();
+    if !accounts.revoke_key(UserId(user), &id).await? {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    app_state
+        .audit_log
+        .record(&http_request, *user_id, Action::RevokeKey { user, id })
+        .await;
+
+    Ok(HttpResponse::Ok().finish())
+}
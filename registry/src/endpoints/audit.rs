@@ -0,0 +1,104 @@
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{
+    audit::{AuditEntry, AuditQuery},
+    auth::{api_tokens::Permissions, is_admin, UserId},
+    endpoints::openapi::shared::{Forbidden, Unauthorized},
+    error::Error,
+    AppState,
+};
+use pesde::names::PackageName;
+
+pub const DEFAULT_LIMIT: usize = 50;
+pub const MAX_LIMIT: usize = 500;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Request {
+    /// Only the entries of actions taken by this user. Ignored for the user's own activity
+    #[serde(default)]
+    actor: Option<u64>,
+    /// Only the entries about this package
+    #[serde(default)]
+    #[param(value_type = Option<String>)]
+    package: Option<PackageName>,
+    /// Only the entries of this action, such as `publish`
+    #[serde(default)]
+    action: Option<String>,
+    /// Only the entries before this time, to page through the log
+    #[serde(default)]
+    before: Option<DateTime<Utc>>,
+    /// How many entries to return at most
+    #[serde(default)]
+    #[param(minimum = 1, maximum = 500, default = 50)]
+    limit: Option<usize>,
+}
+
+impl Request {
+    fn into_query(self, actor: Option<UserId>) -> AuditQuery {
+        AuditQuery {
+            actor: actor.or(self.actor.map(UserId)),
+            package: self.package,
+            action: self.action,
+            before: self.before,
+            limit: self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+        }
+    }
+}
+
+/// Gets the registry's audit log, newest entries first
+#[utoipa::path(
+    get,
+    path = "/v0/audit",
+    params(Request),
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The entries", body = Vec<AuditEntry>),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+    )
+)]
+pub async fn get_audit_log(
+    app_state: web::Data<AppState>,
+    request: web::Query<Request>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let entries = app_state
+        .audit_log
+        .query(&request.into_inner().into_query(None))
+        .await?;
+
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+/// Gets the user's own entries of the audit log, newest first
+#[utoipa::path(
+    get,
+    path = "/v0/audit/me",
+    params(Request),
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The entries", body = Vec<AuditEntry>),
+        (status = 401, response = Unauthorized),
+    )
+)]
+pub async fn get_own_activity(
+    app_state: web::Data<AppState>,
+    request: web::Query<Request>,
+    user_id: web::ReqData<UserId>,
+) -> Result<impl Responder, Error> {
+    let entries = app_state
+        .audit_log
+        .query(&request.into_inner().into_query(Some(*user_id)))
+        .await?;
+
+    Ok(HttpResponse::Ok().json(entries))
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use semver::VersionReq;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{api_tokens::Permissions, UserId},
+    endpoints::openapi::shared::{BadRequest, Forbidden, NotFound, PackagePath, Unauthorized},
+    error::{Error, ErrorResponse},
+    git::push_changes,
+    metadata_cache::update_cached_package,
+    owners::{owns_package, read_scope_info},
+    webhooks::Event,
+    AppState,
+};
+use pesde::{
+    names::PackageName,
+    source::{
+        git_index::{read_file, root_tree, GitBasedSource},
+        pesde::IndexFile,
+    },
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DeprecateRequest {
+    /// The versions to deprecate, all of them if not given
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    version: Option<VersionReq>,
+    message: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UndeprecateRequest {
+    /// The versions to undeprecate, all of them if not given
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    version: Option<VersionReq>,
+}
+
+/// Deprecates versions of a package
+#[utoipa::path(
+    patch,
+    path = "/v0/packages/{name}/deprecate",
+    params(PackagePath),
+    request_body = DeprecateRequest,
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The versions were deprecated"),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn deprecate_package(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    path: web::Path<PackageName>,
+    request: web::Json<DeprecateRequest>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !permissions.can_yank() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let DeprecateRequest { version, message } = request.into_inner();
+
+    let message = message.trim();
+    if message.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "deprecation message cannot be empty".to_string(),
+        }));
+    }
+
+    set_deprecated(
+        app_state,
+        &http_request,
+        path.into_inner(),
+        version,
+        Some(message.to_string()),
+        user_id.into_inner(),
+    )
+    .await
+}
+
+/// Undeprecates versions of a package
+#[utoipa::path(
+    patch,
+    path = "/v0/packages/{name}/undeprecate",
+    params(PackagePath),
+    request_body = UndeprecateRequest,
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The versions were undeprecated"),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn undeprecate_package(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    path: web::Path<PackageName>,
+    request: web::Json<UndeprecateRequest>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !permissions.can_yank() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    set_deprecated(
+        app_state,
+        &http_request,
+        path.into_inner(),
+        request.into_inner().version,
+        None,
+        user_id.into_inner(),
+    )
+    .await
+}
+
+async fn set_deprecated(
+    app_state: web::Data<AppState>,
+    http_request: &HttpRequest,
+    name: PackageName,
+    version: Option<VersionReq>,
+    deprecated: Option<String>,
+    user_id: UserId,
+) -> Result<HttpResponse, Error> {
+    let source = app_state.write_source().await;
+    source
+        .refresh(&app_state.project, &app_state.reqwest_client)
+        .await
+        .map_err(Box::new)?;
+
+    let (scope, name_part) = name.as_str();
+
+    let mut entries: IndexFile = {
+        let repo = gix::open(source.path(&app_state.project))?;
+        let tree = root_tree(&repo)?;
+
+        match read_scope_info(&tree, scope)? {
+            Some(info) => {
+                if !owns_package(&app_state, &info, name_part, user_id).await? {
+                    return Ok(HttpResponse::Forbidden().finish());
+                }
+            }
+            None => return Ok(HttpResponse::NotFound().finish()),
+        }
+
+        match read_file(&tree, [scope, name_part])? {
+            Some(versions) => toml::de::from_str(&versions)?,
+            None => return Ok(HttpResponse::NotFound().finish()),
+        }
+    };
+
+    let mut found = false;
+    let mut changed = false;
+
+    for (_, entry) in entries.iter_mut().filter(|(v_id, _)| {
+        version
+            .as_ref()
+            .is_none_or(|version| version.matches(v_id.version()))
+    }) {
+        found = true;
+        changed |= entry.deprecated != deprecated;
+        entry.deprecated = deprecated.clone();
+    }
+
+    if !found {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let action = if deprecated.is_some() {
+        "deprecate"
+    } else {
+        "undeprecate"
+    };
+    let versions = version.map_or("*".to_string(), |version| version.to_string());
+
+    if changed {
+        push_changes(
+            &app_state,
+            &source,
+            scope,
+            HashMap::from([(name_part, Some(toml::to_string(&entries)?.into_bytes()))]),
+            &format!("{action} {name}@{versions}"),
+        )?;
+
+        update_cached_package(&app_state, &name, &entries);
+
+        let event = match deprecated {
+            Some(message) => Event::Deprecate {
+                package: name.clone(),
+                versions: versions.clone(),
+                message,
+            },
+            None => Event::Undeprecate {
+                package: name.clone(),
+                versions: versions.clone(),
+            },
+        };
+        app_state
+            .audit_log
+            .record(http_request, user_id, event.clone())
+            .await;
+        app_state.webhooks.send(event);
+    }
+
+    Ok(HttpResponse::Ok().body(format!("{action}d {name}@{versions}")))
+}
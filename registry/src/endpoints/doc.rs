@@ -0,0 +1,45 @@
+use actix_web::{
+    http::header::{HeaderValue, CACHE_CONTROL},
+    web, HttpRequest, HttpResponse, Responder,
+};
+
+use crate::{
+    endpoints::openapi::shared::NotFound,
+    error::Error,
+    storage::{immutable_cache_control, ranged_response, StorageImpl},
+    AppState,
+};
+
+/// Serves a documentation page by the hash of its contents, which can be cached forever
+#[utoipa::path(
+    get,
+    path = "/v0/docs/{hash}",
+    params(("hash" = String, Path)),
+    responses(
+        (status = 200, description = "The page's contents", body = String, content_type = "text/plain"),
+        (status = 206, description = "The part of the page the `Range` header asked for"),
+        (status = 307, description = "The page is served by the storage"),
+        (status = 404, response = NotFound),
+        (status = 416, description = "The `Range` header starts past the end of the page"),
+    )
+)]
+pub async fn get_doc(
+    request: HttpRequest,
+    app_state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<impl Responder, Error> {
+    let hash = path.into_inner();
+    if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let mut response = ranged_response(&request, app_state.storage.get().get_doc(&hash).await?);
+    if response.status().is_success() {
+        response.headers_mut().insert(
+            CACHE_CONTROL,
+            HeaderValue::from_static(immutable_cache_control(&app_state)),
+        );
+    }
+
+    Ok(response)
+}
@@ -0,0 +1,373 @@
+use actix_web::{http::header::LOCATION, web, HttpResponse, Responder};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use semver::Version;
+use serde::Deserialize;
+use std::{collections::BTreeSet, fmt::Write};
+use utoipa::IntoParams;
+
+use crate::{
+    endpoints::{
+        openapi::shared::{DocsPath, NotFound, Target},
+        package_version::{find_version, TargetRequest, VersionRequest},
+    },
+    error::Error,
+    metadata_cache::read_package,
+    readme::{escape_html, render_html},
+    storage::{decompress, StorageImpl},
+    AppState,
+};
+use pesde::{
+    manifest::target::TargetKind,
+    names::PackageName,
+    source::pesde::{DocEntry, DocEntryKind, IndexFile},
+};
+
+/// The characters which are encoded in the segments of the site's URLs
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Query {
+    /// The target whose documentation to serve, defaulting to any of the version's
+    #[serde(default)]
+    #[param(value_type = Option<Target>, inline)]
+    target: Option<TargetKind>,
+}
+
+impl Query {
+    fn target_request(&self) -> TargetRequest {
+        match &self.target {
+            Some(kind) => TargetRequest::Specific(kind.clone()),
+            None => TargetRequest::Any,
+        }
+    }
+}
+
+/// Builds the URLs of the documentation site of a package
+struct Urls<'a> {
+    name: &'a PackageName,
+    target: Option<&'a TargetKind>,
+}
+
+impl Urls<'_> {
+    fn encode(segment: &str) -> String {
+        utf8_percent_encode(segment, PATH_SEGMENT).to_string()
+    }
+
+    fn query(&self) -> String {
+        match self.target {
+            Some(target) => format!("?target={target}"),
+            None => String::new(),
+        }
+    }
+
+    fn version(&self, version: &Version) -> String {
+        let (scope, name) = self.name.as_str();
+        format!("/docs/{scope}/{name}/{version}{}", self.query())
+    }
+
+    fn page(&self, version: &Version, page: &str) -> String {
+        let (scope, name) = self.name.as_str();
+        let page = page
+            .split('/')
+            .map(Self::encode)
+            .collect::<Vec<_>>()
+            .join("/");
+        format!("/docs/{scope}/{name}/{version}/{page}{}", self.query())
+    }
+}
+
+/// The name of the first page of the documentation, in the sidebar's order
+fn first_page(docs: &BTreeSet<DocEntry>) -> Option<&str> {
+    docs.iter().find_map(|doc| match &doc.kind {
+        DocEntryKind::Page { name, .. } => Some(name.as_str()),
+        DocEntryKind::Category { items, .. } => first_page(items),
+    })
+}
+
+/// The label and hash of the page with the given name
+fn find_page<'a>(docs: &'a BTreeSet<DocEntry>, page: &str) -> Option<(&'a str, &'a str)> {
+    docs.iter().find_map(|doc| match &doc.kind {
+        DocEntryKind::Page { name, hash } if name == page => {
+            Some((doc.label.as_str(), hash.as_str()))
+        }
+        DocEntryKind::Page { .. } => None,
+        DocEntryKind::Category { items, .. } => find_page(items, page),
+    })
+}
+
+fn contains_page(docs: &BTreeSet<DocEntry>, page: &str) -> bool {
+    docs.iter().any(|doc| match &doc.kind {
+        DocEntryKind::Page { name, .. } => name == page,
+        DocEntryKind::Category { items, .. } => contains_page(items, page),
+    })
+}
+
+/// Removes the YAML front matter from the start of a page, if it has any
+fn strip_front_matter(content: &str) -> &str {
+    let mut lines = content.split_inclusive('\n');
+    if lines.next().map(str::trim_end) != Some("---") {
+        return content;
+    }
+
+    let mut offset = content.find('\n').map_or(content.len(), |i| i + 1);
+    for line in lines {
+        offset += line.len();
+        if line.trim_end() == "---" {
+            return &content[offset..];
+        }
+    }
+
+    content
+}
+
+fn render_sidebar(
+    docs: &BTreeSet<DocEntry>,
+    urls: &Urls,
+    version: &Version,
+    current: &str,
+    html: &mut String,
+) {
+    html.push_str("<ul>");
+    for doc in docs {
+        let label = escape_html(&doc.label);
+        match &doc.kind {
+            DocEntryKind::Page { name, .. } => {
+                let href = escape_html(&urls.page(version, name));
+                if name == current {
+                    let _ = write!(
+                        html,
+                        r#"<li><a class="active" aria-current="page" href="{href}">{label}</a></li>"#
+                    );
+                } else {
+                    let _ = write!(html, r#"<li><a href="{href}">{label}</a></li>"#);
+                }
+            }
+            DocEntryKind::Category { items, collapsed } => {
+                let open = if *collapsed && !contains_page(items, current) {
+                    ""
+                } else {
+                    " open"
+                };
+                let _ = write!(html, "<li><details{open}><summary>{label}</summary>");
+                render_sidebar(items, urls, version, current, html);
+                html.push_str("</details></li>");
+            }
+        }
+    }
+    html.push_str("</ul>");
+}
+
+fn render_version_switcher(
+    entries: &IndexFile,
+    target: &TargetRequest,
+    urls: &Urls,
+    current_version: &Version,
+    current_page: &str,
+    html: &mut String,
+) {
+    let versions = entries
+        .keys()
+        .map(|v_id| v_id.version())
+        .collect::<BTreeSet<_>>();
+
+    html.push_str(r#"<select aria-label="Version" onchange="location.href = this.value">"#);
+    for version in versions.into_iter().rev() {
+        let Some((_, entry)) =
+            find_version(entries, VersionRequest::Specific(version.clone()), target)
+        else {
+            continue;
+        };
+        if entry.docs.is_empty() {
+            continue;
+        }
+
+        // stay on the same page if the version has it
+        let href = if contains_page(&entry.docs, current_page) {
+            urls.page(version, current_page)
+        } else {
+            urls.version(version)
+        };
+        let selected = if version == current_version {
+            " selected"
+        } else {
+            ""
+        };
+
+        let _ = write!(
+            html,
+            r#"<option value="{}"{selected}>{version}</option>"#,
+            escape_html(&href)
+        );
+    }
+    html.push_str("</select>");
+}
+
+const STYLE: &str = "body{margin:0;font-family:system-ui,sans-serif;display:flex;min-height:100vh}\
+nav{width:16rem;flex-shrink:0;padding:1rem;border-right:1px solid #ddd;box-sizing:border-box}\
+nav ul{list-style:none;padding-left:1rem;margin:0}nav>ul{padding-left:0}\
+nav a{color:inherit;text-decoration:none}nav a.active{font-weight:bold}\
+nav select{width:100%;margin-bottom:1rem}\
+main{flex-grow:1;padding:1rem 2rem;max-width:60rem;overflow-x:auto}\
+pre{padding:1rem;overflow-x:auto;background:#f6f8fa}";
+
+fn redirect(location: String) -> HttpResponse {
+    HttpResponse::Found()
+        .insert_header((LOCATION, location))
+        .finish()
+}
+
+async fn redirect_to_first_page(
+    app_state: &AppState,
+    name: &str,
+    version: VersionRequest,
+    query: &Query,
+) -> Result<HttpResponse, Error> {
+    let Ok(name) = name.parse::<PackageName>() else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let Some(entries) = read_package(app_state, &name).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let Some((v_id, entry)) = find_version(&entries, version, &query.target_request()) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let Some(page) = first_page(&entry.docs) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let urls = Urls {
+        name: &name,
+        target: query.target.as_ref(),
+    };
+    Ok(redirect(urls.page(v_id.version(), page)))
+}
+
+/// Redirects to the documentation of the latest version of a package
+#[utoipa::path(
+    get,
+    path = "/docs/{scope}/{name}",
+    params(DocsPath, Query),
+    responses(
+        (status = 302, description = "A redirect to the first page of the documentation"),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn get_docs_root(
+    app_state: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+    query: web::Query<Query>,
+) -> Result<impl Responder, Error> {
+    let (scope, name) = path.into_inner();
+
+    redirect_to_first_page(
+        &app_state,
+        &format!("{scope}/{name}"),
+        VersionRequest::Latest,
+        &query,
+    )
+    .await
+}
+
+/// Redirects to the documentation of a version of a package
+#[utoipa::path(
+    get,
+    path = "/docs/{scope}/{name}/{version}",
+    params(
+        DocsPath,
+        ("version" = String, Path, description = "The version of the package, or `latest`"),
+        Query,
+    ),
+    responses(
+        (status = 302, description = "A redirect to the first page of the documentation"),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn get_docs_version(
+    app_state: web::Data<AppState>,
+    path: web::Path<(String, String, VersionRequest)>,
+    query: web::Query<Query>,
+) -> Result<impl Responder, Error> {
+    let (scope, name, version) = path.into_inner();
+
+    redirect_to_first_page(&app_state, &format!("{scope}/{name}"), version, &query).await
+}
+
+/// Gets a page of the documentation of a version of a package, rendered to HTML
+#[utoipa::path(
+    get,
+    path = "/docs/{scope}/{name}/{version}/{page}",
+    params(
+        DocsPath,
+        ("version" = String, Path, description = "The version of the package, or `latest`"),
+        ("page" = String, Path, description = "The name of the page, which may contain slashes"),
+        Query,
+    ),
+    responses(
+        (status = 200, description = "The page, with the documentation's navigation and a version switcher", body = String, content_type = "text/html"),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn get_docs_page(
+    app_state: web::Data<AppState>,
+    path: web::Path<(String, String, VersionRequest, String)>,
+    query: web::Query<Query>,
+) -> Result<impl Responder, Error> {
+    let (scope, name, version, page) = path.into_inner();
+    let page = page.trim_end_matches('/');
+
+    let Ok(name) = format!("{scope}/{name}").parse::<PackageName>() else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let Some(entries) = read_package(&app_state, &name).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let target = query.target_request();
+    let Some((v_id, entry)) = find_version(&entries, version, &target) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let Some((label, hash)) = find_page(&entry.docs, page) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let Some(contents) = app_state.storage.get().read_doc(hash).await? else {
+        tracing::error!("doc {hash} of {name}@{v_id} is missing from the storage");
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let contents = match decompress(contents).await {
+        Ok(contents) => contents,
+        Err(e) => {
+            tracing::error!("failed to decompress doc {hash} of {name}@{v_id}: {e}");
+            return Ok(HttpResponse::InternalServerError().finish());
+        }
+    };
+    let contents = String::from_utf8_lossy(&contents);
+
+    let urls = Urls {
+        name: &name,
+        target: query.target.as_ref(),
+    };
+    let version = v_id.version();
+
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        r#"<!DOCTYPE html><html lang="en"><head><meta charset="utf-8"><meta name="viewport" content="width=device-width, initial-scale=1"><title>{} - {name}@{version}</title><link rel="stylesheet" href="/v0/readme.css"><style>{STYLE}</style></head><body><nav><p><strong>{name}</strong></p>"#,
+        escape_html(label),
+    );
+    render_version_switcher(&entries, &target, &urls, version, page, &mut html);
+    render_sidebar(&entry.docs, &urls, version, page, &mut html);
+    let _ = write!(
+        html,
+        "</nav><main>{}</main></body></html>",
+        render_html(strip_front_matter(&contents))
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html))
+}
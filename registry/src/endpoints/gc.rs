@@ -0,0 +1,52 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{
+    auth::{api_tokens::Permissions, is_admin, UserId},
+    endpoints::openapi::shared::{BadRequest, Forbidden, Unauthorized},
+    error::{Error, ErrorResponse},
+    gc::{collect_garbage, GcReport},
+    AppState,
+};
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Request {
+    /// Whether to only report the orphaned files, without deleting them
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Deletes the stored files which the index doesn't reference
+///
+/// Only supported by the filesystem storage
+#[utoipa::path(
+    post,
+    path = "/v0/storage/gc",
+    params(Request),
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The result of the garbage collection", body = GcReport),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+    )
+)]
+pub async fn run_gc(
+    app_state: web::Data<AppState>,
+    request: web::Query<Request>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    match collect_garbage(&app_state, request.dry_run).await? {
+        Some(report) => Ok(HttpResponse::Ok().json(report)),
+        None => Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "the storage backend doesn't support garbage collection".to_string(),
+        })),
+    }
+}
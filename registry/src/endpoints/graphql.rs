@@ -0,0 +1,33 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+
+use crate::{error::ErrorResponse, graphql::RegistrySchema, AppState};
+
+/// Executes a GraphQL query sent as JSON
+pub async fn post_graphql(
+    app_state: web::Data<AppState>,
+    schema: web::Data<RegistrySchema>,
+    request: web::Json<async_graphql::Request>,
+) -> impl Responder {
+    let response = schema.execute(request.into_inner().data(app_state)).await;
+
+    HttpResponse::Ok().json(response)
+}
+
+/// Executes a GraphQL query given in the query string, which lets its response be cached by proxies
+pub async fn get_graphql(
+    app_state: web::Data<AppState>,
+    schema: web::Data<RegistrySchema>,
+    request: HttpRequest,
+) -> impl Responder {
+    let request = match async_graphql::http::parse_query_string(request.query_string()) {
+        Ok(request) => request,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                error: format!("invalid GraphQL request: {e}"),
+            })
+        }
+    };
+    let response = schema.execute(request.data(app_state)).await;
+
+    HttpResponse::Ok().json(response)
+}
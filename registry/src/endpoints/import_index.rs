@@ -0,0 +1,273 @@
+use actix_web::{web, web::Bytes, HttpRequest, HttpResponse, Responder};
+use fs_err::tokio as fs;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+use utoipa::ToSchema;
+
+use crate::{
+    audit::Action,
+    auth::{api_tokens::Permissions, is_admin, UserId},
+    endpoints::openapi::shared::{BadRequest, Binary, Forbidden, Unauthorized},
+    error::{Error, ErrorResponse},
+    git::push_changes,
+    metadata_cache::update_cached_package,
+    search::update_package,
+    AppState,
+};
+use pesde::{
+    names::PackageName,
+    snapshot::import_snapshot,
+    source::{
+        git_index::{read_file, root_tree, GitBasedSource},
+        pesde::{IndexFile, SCOPE_INFO_FILE},
+    },
+    AuthConfig, Project,
+};
+
+/// The maximum size of an index snapshot, in bytes
+pub const MAX_SNAPSHOT_SIZE: usize = 256 * 1024 * 1024;
+
+/// The contents of a snapshot's index
+#[derive(Debug, Default)]
+struct SnapshotIndex {
+    /// The scope info files, by scope
+    scope_infos: BTreeMap<String, String>,
+    packages: Vec<(PackageName, IndexFile)>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ImportResponse {
+    /// How many packages had versions added
+    packages: usize,
+    /// How many versions were added
+    versions: usize,
+    /// How many versions were already in the index, and left unchanged
+    skipped: usize,
+}
+
+/// Finds the single pesde index of an imported snapshot
+async fn snapshot_index(project: &Project) -> Result<Result<PathBuf, String>, Error> {
+    let mut entries = match fs::read_dir(project.data_dir().join("indices")).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Err("the snapshot doesn't contain an index".to_string()))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut indices = vec![];
+    while let Some(entry) = entries.next_entry().await? {
+        indices.push(entry.path());
+    }
+
+    match <[PathBuf; 1]>::try_from(indices) {
+        Ok([index]) => Ok(Ok(index)),
+        Err(indices) if indices.is_empty() => {
+            Ok(Err("the snapshot doesn't contain an index".to_string()))
+        }
+        Err(_) => Ok(Err(
+            "the snapshot contains multiple indices, export it with `--index` to choose one"
+                .to_string(),
+        )),
+    }
+}
+
+/// Reads the packages and scope files of an index's repository, returning why it's invalid if it
+/// can't be read
+fn read_index(path: &Path) -> Result<SnapshotIndex, String> {
+    let repo = gix::open(path).map_err(|e| e.to_string())?;
+    let tree = root_tree(&repo).map_err(|e| e.to_string())?;
+    let mut index = SnapshotIndex::default();
+
+    for entry in tree.iter() {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let object = entry.object().map_err(|e| e.to_string())?;
+
+        // directories will be trees, and files will be blobs
+        if !matches!(object.kind, gix::object::Kind::Tree) {
+            continue;
+        }
+
+        let scope = entry.filename().to_string();
+
+        for inner_entry in object.into_tree().iter() {
+            let inner_entry = inner_entry.map_err(|e| e.to_string())?;
+            let object = inner_entry.object().map_err(|e| e.to_string())?;
+
+            if !matches!(object.kind, gix::object::Kind::Blob) {
+                continue;
+            }
+
+            let name = inner_entry.filename().to_string();
+            let contents = String::from_utf8(object.into_blob().data.clone())
+                .map_err(|_| format!("{scope}/{name} isn't valid UTF-8"))?;
+
+            if name == SCOPE_INFO_FILE {
+                index.scope_infos.insert(scope.clone(), contents);
+                continue;
+            }
+
+            let package = format!("{scope}/{name}")
+                .parse::<PackageName>()
+                .map_err(|e| e.to_string())?;
+            let file = toml::de::from_str(&contents).map_err(|e| format!("{package}: {e}"))?;
+
+            index.packages.push((package, file));
+        }
+    }
+
+    Ok(index)
+}
+
+/// Adds the versions of an index snapshot, as exported by `pesde snapshot export`, to the index.
+/// Versions already in the index are left unchanged, and the scopes' owners are only imported for
+/// scopes which don't exist yet. The versions' archives aren't part of snapshots, so they must
+/// already be stored
+#[utoipa::path(
+    post,
+    path = "/v0/admin/import",
+    request_body(content = inline(Binary), content_type = "application/octet-stream"),
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The snapshot was imported", body = ImportResponse),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+    )
+)]
+pub async fn import_index(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    bytes: Bytes,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let dir = tempfile::tempdir()?;
+    let project = Project::new(
+        dir.path(),
+        None::<PathBuf>,
+        dir.path().join("data"),
+        dir.path().join("cas"),
+        AuthConfig::new(),
+    );
+
+    if let Err(e) = import_snapshot(&project, &bytes).await {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("invalid snapshot: {e}"),
+        }));
+    }
+
+    let index = match snapshot_index(&project).await? {
+        Ok(index) => index,
+        Err(error) => return Ok(HttpResponse::BadRequest().json(ErrorResponse { error })),
+    };
+    let snapshot = match read_index(&index) {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: format!("invalid index: {e}"),
+            }))
+        }
+    };
+
+    let mut response = ImportResponse {
+        packages: 0,
+        versions: 0,
+        skipped: 0,
+    };
+
+    {
+        let source = app_state.write_source().await;
+        source
+            .refresh(&app_state.project, &app_state.reqwest_client)
+            .await
+            .map_err(Box::new)?;
+
+        let mut changes = BTreeMap::<&str, HashMap<&str, Option<Vec<u8>>>>::new();
+        let mut imported = vec![];
+
+        {
+            let repo = gix::open(source.path(&app_state.project))?;
+            let tree = root_tree(&repo)?;
+
+            for (package, versions) in &snapshot.packages {
+                let (scope, name) = package.as_str();
+
+                let mut entries: IndexFile =
+                    toml::de::from_str(&read_file(&tree, [scope, name])?.unwrap_or_default())?;
+
+                let mut added = 0;
+                for (v_id, entry) in versions {
+                    if entries.contains_key(v_id) {
+                        response.skipped += 1;
+                        continue;
+                    }
+
+                    entries.insert(v_id.clone(), entry.clone());
+                    added += 1;
+                }
+
+                if added == 0 {
+                    continue;
+                }
+
+                response.packages += 1;
+                response.versions += added;
+
+                changes
+                    .entry(scope)
+                    .or_default()
+                    .insert(name, Some(toml::to_string(&entries)?.into_bytes()));
+                imported.push((package, entries));
+            }
+
+            // the owners of scopes which don't exist yet are imported along with their packages
+            for (scope, files) in &mut changes {
+                if read_file(&tree, [*scope, SCOPE_INFO_FILE])?.is_some() {
+                    continue;
+                }
+
+                if let Some(info) = snapshot.scope_infos.get(*scope) {
+                    files.insert(SCOPE_INFO_FILE, Some(info.clone().into_bytes()));
+                }
+            }
+        }
+
+        for (scope, files) in changes {
+            push_changes(
+                &app_state,
+                &source,
+                scope,
+                files,
+                &format!("import {scope} from snapshot"),
+            )?;
+        }
+
+        for (package, entries) in imported {
+            app_state.dependents.update(package, &entries);
+            update_cached_package(&app_state, package, &entries);
+            update_package(&app_state, package, entries).await;
+        }
+    }
+
+    app_state
+        .audit_log
+        .record(
+            &http_request,
+            *user_id,
+            Action::Import {
+                packages: response.packages,
+                versions: response.versions,
+            },
+        )
+        .await;
+
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -0,0 +1,65 @@
+use actix_web::{
+    http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH},
+    web, HttpRequest, HttpResponse, Responder,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{endpoints::openapi::shared::NotFound, error::Error, AppState};
+use pesde::source::git_index::{read_file, root_tree, GitBasedSource};
+
+/// Gets a file of the index
+#[utoipa::path(
+    get,
+    path = "/v0/index/{path}",
+    params(("path" = String, Path)),
+    responses(
+        (status = 200, description = "The contents of the file", body = String, content_type = "application/toml"),
+        (status = 304, description = "The file hasn't changed since the given entity tag"),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn get_index_file(
+    request: HttpRequest,
+    app_state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<impl Responder, Error> {
+    let path = path.into_inner();
+    let parts = path.split('/').collect::<Vec<_>>();
+
+    if parts
+        .iter()
+        .any(|part| part.is_empty() || part.starts_with('.'))
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let contents = {
+        let source = app_state.read_source().await;
+        let repo = gix::open(source.path(&app_state.project))?;
+        let tree = root_tree(&repo)?;
+
+        match read_file(&tree, parts)? {
+            Some(contents) => contents,
+            None => return Ok(HttpResponse::NotFound().finish()),
+        }
+    };
+
+    let etag = format!("\"{:x}\"", Sha256::digest(contents.as_bytes()));
+
+    if request
+        .headers()
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag))
+    {
+        return Ok(HttpResponse::NotModified()
+            .insert_header((ETAG, etag))
+            .finish());
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header((ETAG, etag))
+        .insert_header((CACHE_CONTROL, "no-cache"))
+        .content_type("application/toml")
+        .body(contents))
+}
@@ -0,0 +1,70 @@
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{api_tokens::Permissions, is_admin, UserId},
+    endpoints::openapi::shared::{NotFound, Target, Unauthorized},
+    error::Error,
+    index_queue::JobStatus,
+    AppState,
+};
+use pesde::manifest::target::TargetKind;
+
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = Job)]
+struct JobResponse {
+    id: String,
+    status: JobStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    package: String,
+    version: String,
+    #[schema(value_type = Target)]
+    target: TargetKind,
+    created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finished_at: Option<DateTime<Utc>>,
+}
+
+/// Gets a job adding a published version to the index
+///
+/// Only the user who published the version and administrators can see the job
+#[utoipa::path(
+    get,
+    path = "/v0/jobs/{id}",
+    params(("id" = String, Path)),
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The job", body = JobResponse),
+        (status = 401, response = Unauthorized),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn get_job(
+    app_state: web::Data<AppState>,
+    path: web::Path<String>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    let Some(job) = app_state.index_queue.get(&path.into_inner()).await else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    // jobs of other users are hidden, so that unpublished versions aren't revealed
+    if job.user_id != user_id.0 && !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    Ok(HttpResponse::Ok().json(JobResponse {
+        id: job.id,
+        status: job.status,
+        error: job.error,
+        package: job.package.to_string(),
+        version: job.version_id.version().to_string(),
+        target: job.version_id.target().clone(),
+        created_at: job.created_at,
+        finished_at: job.finished_at,
+    }))
+}
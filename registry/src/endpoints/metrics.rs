@@ -0,0 +1,34 @@
+use actix_web::{http::header::AUTHORIZATION, web, HttpRequest, HttpResponse, Responder};
+use constant_time_eq::constant_time_eq_32;
+use sha2::{Digest, Sha256};
+
+use crate::{endpoints::openapi::shared::Unauthorized, metrics::METRICS, AppState};
+
+/// Gets the registry's metrics in Prometheus' text format
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    security(("token" = []), ()),
+    responses(
+        (status = 200, description = "The metrics", body = String, content_type = "text/plain"),
+        (status = 401, response = Unauthorized),
+    )
+)]
+pub async fn get_metrics(app_state: web::Data<AppState>, request: HttpRequest) -> impl Responder {
+    if let Some(metrics_token) = &app_state.metrics_token {
+        let token = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|token| token.to_str().ok())
+            .and_then(|token| token.strip_prefix("Bearer "))
+            .map(|token| Sha256::digest(token.as_bytes()).into());
+
+        if !token.is_some_and(|token: [u8; 32]| constant_time_eq_32(metrics_token, &token)) {
+            return HttpResponse::Unauthorized().finish();
+        }
+    }
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(METRICS.render())
+}
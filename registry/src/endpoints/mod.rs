@@ -1,4 +1,39 @@
+pub mod accounts;
+pub mod audit;
+pub mod deprecate_version;
+pub mod doc;
+pub mod docs_site;
+pub mod gc;
+pub mod graphql;
+pub mod import_index;
+pub mod index;
+pub mod jobs;
+pub mod metrics;
+pub mod moderation;
+pub mod notifications;
+pub mod openapi;
+pub mod owners;
+pub mod package_archive;
+pub mod package_attestation;
+pub mod package_dependents;
+pub mod package_diff;
+pub mod package_downloads;
+pub mod package_files;
+pub mod package_readme;
 pub mod package_version;
 pub mod package_versions;
+pub mod packages;
 pub mod publish_version;
+pub mod rankings;
+pub mod reconcile;
+pub mod reindex;
+pub mod reload;
+pub mod rollback;
+pub mod scope;
 pub mod search;
+pub mod tokens;
+pub mod trusted_publishers;
+pub mod uploads;
+pub mod users;
+pub mod webhooks;
+pub mod yank_version;
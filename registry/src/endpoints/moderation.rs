@@ -0,0 +1,327 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::Utc;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+use crate::{
+    audit::Action,
+    auth::{api_tokens::Permissions, is_admin, UserId},
+    endpoints::openapi::shared::{BadRequest, Forbidden, NotFound, Target, Unauthorized},
+    error::{Error, ErrorResponse},
+    git::push_changes,
+    metadata_cache::update_cached_package,
+    moderation::{Ban, Removal},
+    search::update_package,
+    storage::StorageImpl,
+    AppState,
+};
+use pesde::{
+    manifest::target::TargetKind,
+    names::PackageName,
+    source::{
+        git_index::{read_file, root_tree, GitBasedSource},
+        pesde::IndexFile,
+        version_id::VersionId,
+    },
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RemoveRequest {
+    #[schema(value_type = String)]
+    package: PackageName,
+    /// The version to remove, or all of them if not given
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    version: Option<Version>,
+    /// The target of the version to remove, or all of them if not given
+    #[serde(default)]
+    #[schema(value_type = Option<Target>)]
+    target: Option<TargetKind>,
+    /// The public reason of the removal
+    reason: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct RemoveResponse {
+    removal: Removal,
+    #[schema(value_type = Vec<String>)]
+    removed_versions: Vec<VersionId>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BanRequest {
+    /// The reason of the ban, which is shown to the user
+    reason: String,
+}
+
+/// Removes a package, or versions of it, from the index, search, and storage
+///
+/// Removed versions can't be published again
+#[utoipa::path(
+    post,
+    path = "/v0/moderation/removals",
+    request_body = RemoveRequest,
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The removal, and the versions it removed", body = RemoveResponse),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn remove(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    request: web::Json<RemoveRequest>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let RemoveRequest {
+        package,
+        version,
+        target,
+        reason,
+    } = request.into_inner();
+
+    let reason = reason.trim().to_string();
+    if reason.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "a reason must be given".to_string(),
+        }));
+    }
+    if target.is_some() && version.is_none() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "a target can only be removed along with a version".to_string(),
+        }));
+    }
+
+    let (scope, name_part) = package.as_str();
+
+    let removed = {
+        let source = app_state.write_source().await;
+        source
+            .refresh(&app_state.project, &app_state.reqwest_client)
+            .await
+            .map_err(Box::new)?;
+
+        let mut entries: IndexFile = {
+            let repo = gix::open(source.path(&app_state.project))?;
+            let tree = root_tree(&repo)?;
+
+            match read_file(&tree, [scope, name_part])? {
+                Some(versions) => toml::de::from_str(&versions)?,
+                None => return Ok(HttpResponse::NotFound().finish()),
+            }
+        };
+
+        let removed = entries
+            .keys()
+            .filter(|v_id| {
+                version
+                    .as_ref()
+                    .is_none_or(|version| v_id.version() == version)
+                    && target.as_ref().is_none_or(|target| v_id.target() == target)
+            })
+            .cloned()
+            .collect::<Vec<VersionId>>();
+        if removed.is_empty() {
+            return Ok(HttpResponse::NotFound().finish());
+        }
+
+        for v_id in &removed {
+            entries.remove(v_id);
+        }
+
+        // packages without any versions left are removed from the index entirely
+        let contents = if entries.is_empty() {
+            None
+        } else {
+            Some(toml::to_string(&entries)?.into_bytes())
+        };
+
+        push_changes(
+            &app_state,
+            &source,
+            scope,
+            HashMap::from([(name_part, contents)]),
+            &match (&version, &target) {
+                (Some(version), Some(target)) => format!("remove {package}@{version} {target}"),
+                (Some(version), None) => format!("remove {package}@{version}"),
+                _ => format!("remove {package}"),
+            },
+        )?;
+
+        app_state.dependents.update(&package, &entries);
+        update_cached_package(&app_state, &package, &entries);
+        update_package(&app_state, &package, entries).await;
+
+        removed
+    };
+
+    // the removal is already reflected in the index, so failing to delete the files only leaves
+    // them to be garbage collected
+    for v_id in &removed {
+        if let Err(e) = app_state.storage.get().delete_version(&package, v_id).await {
+            tracing::error!("failed to delete the files of removed {package}@{v_id}: {e}");
+        }
+    }
+
+    let removal = Removal {
+        package: package.clone(),
+        version: version.clone(),
+        target: target.clone(),
+        reason: reason.clone(),
+        removed_at: Utc::now(),
+    };
+    app_state.moderation.record_removal(removal.clone()).await?;
+
+    app_state
+        .audit_log
+        .record(
+            &http_request,
+            *user_id,
+            Action::Remove {
+                package,
+                version,
+                target,
+                reason,
+            },
+        )
+        .await;
+
+    Ok(HttpResponse::Ok().json(RemoveResponse {
+        removal,
+        removed_versions: removed,
+    }))
+}
+
+/// Gets the packages and versions removed by the registry's administrators
+#[utoipa::path(
+    get,
+    path = "/v0/moderation/removals",
+    responses((status = 200, description = "The removals, most recent first", body = Vec<Removal>))
+)]
+pub async fn get_removals(app_state: web::Data<AppState>) -> Result<impl Responder, Error> {
+    Ok(HttpResponse::Ok().json(app_state.moderation.removals().await))
+}
+
+/// Bans a user from publishing
+#[utoipa::path(
+    put,
+    path = "/v0/moderation/bans/{user_id}",
+    params(("user_id" = u64, Path)),
+    request_body = BanRequest,
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The ban", body = Ban),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+    )
+)]
+pub async fn ban_user(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    path: web::Path<u64>,
+    request: web::Json<BanRequest>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let banned = path.into_inner();
+    let reason = request.into_inner().reason.trim().to_string();
+    if reason.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "a reason must be given".to_string(),
+        }));
+    }
+
+    let ban = app_state
+        .moderation
+        .ban(UserId(banned), reason.clone())
+        .await?;
+
+    app_state
+        .audit_log
+        .record(
+            &http_request,
+            *user_id,
+            Action::Ban {
+                user: banned,
+                reason,
+            },
+        )
+        .await;
+
+    Ok(HttpResponse::Ok().json(ban))
+}
+
+/// Lifts a user's ban
+#[utoipa::path(
+    delete,
+    path = "/v0/moderation/bans/{user_id}",
+    params(("user_id" = u64, Path)),
+    security(("token" = [])),
+    responses(
+        (status = 204, description = "The ban was lifted"),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn unban_user(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    path: web::Path<u64>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let banned = path.into_inner();
+    if !app_state.moderation.unban(UserId(banned)).await? {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    app_state
+        .audit_log
+        .record(&http_request, *user_id, Action::Unban { user: banned })
+        .await;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Gets the users banned from publishing, by their IDs
+#[utoipa::path(
+    get,
+    path = "/v0/moderation/bans",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The bans", body = HashMap<String, Ban>),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+    )
+)]
+pub async fn get_bans(
+    app_state: web::Data<AppState>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    Ok(HttpResponse::Ok().json(app_state.moderation.bans().await))
+}
@@ -0,0 +1,85 @@
+use actix_web::{web, HttpResponse, Responder};
+
+use crate::{
+    auth::{api_tokens::Permissions, UserId},
+    endpoints::openapi::shared::{BadRequest, Forbidden, Unauthorized},
+    error::{Error, ErrorResponse},
+    notifications::Preferences,
+    AppState,
+};
+
+fn disabled_response() -> HttpResponse {
+    HttpResponse::NotFound().json(ErrorResponse {
+        error: "this registry doesn't send notifications".to_string(),
+    })
+}
+
+/// Gets the user's notification preferences
+#[utoipa::path(
+    get,
+    path = "/v0/notifications",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The preferences", body = Preferences),
+        (status = 401, response = Unauthorized),
+        (status = 404, description = "The registry doesn't send notifications", body = ErrorResponse),
+    )
+)]
+pub async fn get_preferences(
+    app_state: web::Data<AppState>,
+    user_id: web::ReqData<UserId>,
+) -> Result<impl Responder, Error> {
+    if !app_state.notifications.is_enabled() {
+        return Ok(disabled_response());
+    }
+
+    Ok(HttpResponse::Ok().json(app_state.notifications.preferences(*user_id).await))
+}
+
+/// Replaces the user's notification preferences
+///
+/// Notifications which aren't mentioned are enabled
+#[utoipa::path(
+    put,
+    path = "/v0/notifications",
+    request_body = Preferences,
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The updated preferences", body = Preferences),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, description = "The registry doesn't send notifications", body = ErrorResponse),
+    )
+)]
+pub async fn update_preferences(
+    app_state: web::Data<AppState>,
+    preferences: web::Json<Preferences>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !permissions.can_manage_tokens() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    if !app_state.notifications.is_enabled() {
+        return Ok(disabled_response());
+    }
+
+    let mut preferences = preferences.into_inner();
+    if let Some(email) = &mut preferences.email {
+        *email = email.trim().to_string();
+        if email.parse::<lettre::Address>().is_err() {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: format!("`{email}` is not a valid email address"),
+            }));
+        }
+    }
+
+    app_state
+        .notifications
+        .set_preferences(*user_id, preferences.clone())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(preferences))
+}
@@ -0,0 +1,308 @@
+use crate::{
+    endpoints::{self, openapi::shared::*},
+    error::{ErrorResponse, ViolationsResponse},
+};
+use actix_web::{http::header::LOCATION, HttpResponse, Responder};
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    IntoParams, Modify, OpenApi, ToResponse, ToSchema,
+};
+
+/// The OpenAPI document describing the registry's API, generated from the endpoints' annotations
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "pesde registry",
+        description = "The API of a pesde package registry"
+    ),
+    modifiers(&TokenSecurity, &NoTags),
+    paths(
+        endpoints::metrics::get_metrics,
+        endpoints::docs_site::get_docs_root,
+        endpoints::docs_site::get_docs_version,
+        endpoints::docs_site::get_docs_page,
+        endpoints::package_readme::get_highlight_css,
+        endpoints::search::search_packages,
+        endpoints::index::get_index_file,
+        endpoints::packages::list_packages,
+        endpoints::publish_version::publish_package,
+        endpoints::uploads::create_upload,
+        endpoints::uploads::get_upload,
+        endpoints::uploads::put_chunk,
+        endpoints::uploads::cancel_upload,
+        endpoints::uploads::finalize_upload,
+        endpoints::doc::get_doc,
+        endpoints::package_versions::get_package_versions,
+        endpoints::package_downloads::get_package_downloads,
+        endpoints::package_dependents::get_package_dependents,
+        endpoints::package_diff::get_package_diff,
+        endpoints::deprecate_version::deprecate_package,
+        endpoints::deprecate_version::undeprecate_package,
+        endpoints::owners::get_package_owners,
+        endpoints::owners::add_package_owner,
+        endpoints::owners::remove_package_owner,
+        endpoints::trusted_publishers::get_trusted_publishers,
+        endpoints::trusted_publishers::add_trusted_publisher,
+        endpoints::trusted_publishers::remove_trusted_publisher,
+        endpoints::package_version::get_package_version,
+        endpoints::package_archive::get_package_archive,
+        endpoints::package_attestation::get_package_attestation,
+        endpoints::package_files::get_package_files,
+        endpoints::package_readme::get_package_readme,
+        endpoints::yank_version::yank_package_version,
+        endpoints::yank_version::unyank_package_version,
+        endpoints::rankings::get_popular,
+        endpoints::rankings::get_trending,
+        endpoints::users::get_user_packages,
+        endpoints::scope::get_scope,
+        endpoints::owners::get_scope_owners,
+        endpoints::owners::add_scope_owner,
+        endpoints::owners::remove_scope_owner,
+        endpoints::moderation::get_removals,
+        endpoints::moderation::remove,
+        endpoints::moderation::get_bans,
+        endpoints::moderation::ban_user,
+        endpoints::moderation::unban_user,
+        endpoints::gc::run_gc,
+        endpoints::reindex::get_reindex_progress,
+        endpoints::reindex::start_reindex,
+        endpoints::import_index::import_index,
+        endpoints::reconcile::get_reconcile_report,
+        endpoints::reconcile::run_reconcile,
+        endpoints::reload::reload_config,
+        endpoints::accounts::get_users,
+        endpoints::accounts::create_user,
+        endpoints::accounts::delete_user,
+        endpoints::accounts::create_key,
+        endpoints::accounts::revoke_key,
+        endpoints::rollback::rollback_publish,
+        endpoints::tokens::get_tokens,
+        endpoints::tokens::create_token,
+        endpoints::tokens::revoke_token,
+        endpoints::notifications::get_preferences,
+        endpoints::notifications::update_preferences,
+        endpoints::jobs::get_job,
+        endpoints::trusted_publishers::exchange_oidc_token,
+        endpoints::audit::get_audit_log,
+        endpoints::audit::get_own_activity,
+        endpoints::webhooks::get_deliveries,
+        endpoints::webhooks::redeliver,
+    ),
+    components(
+        schemas(ErrorResponse, ViolationsResponse),
+        responses(Unauthorized, Forbidden, NotFound, NotModified, Gone, BadRequest, TooManyRequests)
+    )
+)]
+pub struct ApiDoc;
+
+/// Adds the `token` security scheme the endpoints refer to
+struct TokenSecurity;
+
+impl Modify for TokenSecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "token",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some(
+                        "A token accepted by the registry's authentication method, an API token, or a GitHub Actions OIDC token",
+                    ))
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// Removes the tags utoipa gives operations by default, which are the modules of their handlers
+struct NoTags;
+
+impl Modify for NoTags {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for item in openapi.paths.paths.values_mut() {
+            for operation in [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.patch,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                operation.tags = None;
+            }
+        }
+    }
+}
+
+/// Redirects to Swagger UI, which is served from the registry itself
+pub async fn get_docs() -> impl Responder {
+    HttpResponse::PermanentRedirect()
+        .insert_header((LOCATION, "/docs/swagger-ui/index.html"))
+        .finish()
+}
+
+/// The parameters and responses shared by the endpoints, which are only used to describe them
+#[allow(dead_code)]
+pub mod shared {
+    use super::*;
+    use utoipa::{
+        openapi::{
+            schema::{KnownFormat, SchemaFormat, Type},
+            ObjectBuilder, RefOr, Schema,
+        },
+        PartialSchema,
+    };
+
+    /// The path of a package's endpoints
+    #[derive(IntoParams)]
+    #[into_params(parameter_in = Path)]
+    pub struct PackagePath {
+        /// The name of the package, with its slash URL-encoded
+        #[param(example = "acme%2Ffoo")]
+        name: String,
+    }
+
+    /// The path of a package version's endpoints
+    #[derive(IntoParams)]
+    #[into_params(parameter_in = Path)]
+    pub struct VersionPath {
+        /// The name of the package, with its slash URL-encoded
+        #[param(example = "acme%2Ffoo")]
+        name: String,
+        /// The version of the package, or `latest`
+        version: String,
+        /// The target of the package, or `any`
+        #[param(value_type = TargetOrAny, inline)]
+        target: String,
+    }
+
+    /// The path of a package's documentation site
+    #[derive(IntoParams)]
+    #[into_params(parameter_in = Path)]
+    pub struct DocsPath {
+        /// The scope of the package
+        scope: String,
+        /// The name of the package, without its scope
+        name: String,
+    }
+
+    /// A target of a package
+    #[derive(ToSchema)]
+    #[schema(as = TargetKind)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Target {
+        Roblox,
+        RobloxServer,
+        Lune,
+        Luau,
+    }
+
+    /// A target of a package, or `any` of its targets
+    #[derive(ToSchema)]
+    #[serde(rename_all = "snake_case")]
+    pub enum TargetOrAny {
+        Any,
+        Roblox,
+        RobloxServer,
+        Lune,
+        Luau,
+    }
+
+    /// The raw bytes of a file, such as an archive
+    pub struct Binary;
+
+    impl PartialSchema for Binary {
+        fn schema() -> RefOr<Schema> {
+            ObjectBuilder::new()
+                .schema_type(Type::String)
+                .format(Some(SchemaFormat::KnownFormat(KnownFormat::Binary)))
+                .into()
+        }
+    }
+
+    impl ToSchema for Binary {}
+
+    /// A maintainer of a package, as given in its manifest
+    #[derive(ToSchema)]
+    pub struct Maintainer {
+        name: String,
+        email: Option<String>,
+        github: Option<String>,
+    }
+
+    /// The headers a published archive is sent with
+    #[derive(IntoParams)]
+    #[into_params(parameter_in = Header)]
+    pub struct PublishHeaders {
+        /// The signature of the archive
+        #[param(rename = "X-Pesde-Signature")]
+        signature: Option<String>,
+        /// The base64-encoded attestation document of the archive, such as a SLSA provenance
+        /// statement or a Sigstore bundle
+        #[param(rename = "X-Pesde-Attestation")]
+        attestation: Option<String>,
+    }
+
+    /// Why an archive was rejected
+    #[derive(ToSchema)]
+    #[serde(untagged)]
+    pub enum PublishError {
+        Error(ErrorResponse),
+        Violations(ViolationsResponse),
+    }
+
+    #[derive(ToResponse)]
+    #[response(
+        description = "The version was already published with the same archive, and its files were stored again"
+    )]
+    pub struct Republished;
+
+    #[derive(ToResponse)]
+    #[response(
+        description = "The package's files were stored, and the version was queued to be added to the index",
+        headers(("Location" = String, description = "The URL of the job adding the version to the index"))
+    )]
+    pub struct Queued;
+
+    #[derive(ToResponse)]
+    #[response(description = "The request isn't authenticated")]
+    pub struct Unauthorized;
+
+    #[derive(ToResponse)]
+    #[response(description = "The user isn't allowed to take this action")]
+    pub struct Forbidden;
+
+    #[derive(ToResponse)]
+    #[response(description = "The resource wasn't found")]
+    pub struct NotFound;
+
+    #[derive(ToResponse)]
+    #[response(
+        description = "The metadata hasn't changed since the given `If-None-Match` entity tag, or `If-Modified-Since` date if no entity tag was given"
+    )]
+    pub struct NotModified;
+
+    #[derive(ToResponse)]
+    #[response(
+        description = "The resource was removed by the registry's administrators, with the reason in the error"
+    )]
+    pub struct Gone(ErrorResponse);
+
+    #[derive(ToResponse)]
+    #[response(description = "The registry doesn't manage its own users, or the user wasn't found")]
+    pub struct AccountNotFound(ErrorResponse);
+
+    #[derive(ToResponse)]
+    #[response(description = "The request is invalid")]
+    pub struct BadRequest(ErrorResponse);
+
+    #[derive(ToResponse)]
+    #[response(
+        description = "The caller's rate limit budget is exhausted",
+        headers(("Retry-After" = u64, description = "How many seconds to wait before retrying"))
+    )]
+    pub struct TooManyRequests;
+}
@@ -0,0 +1,459 @@
+use std::collections::{BTreeSet, HashMap};
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    audit::Action,
+    auth::{api_tokens::Permissions, is_admin, UserId},
+    endpoints::openapi::shared::{BadRequest, Forbidden, NotFound, PackagePath, Unauthorized},
+    error::{Error, ErrorResponse},
+    git::push_changes,
+    notifications::Notification,
+    owners::{owns_package, owns_scope, read_scope_info, Owner, Role},
+    teams::parse_team,
+    AppState,
+};
+use pesde::{
+    names::PackageName,
+    source::{
+        git_index::{read_file, root_tree, GitBasedSource},
+        pesde::{PackageOwners, ScopeInfo, SCOPE_INFO_FILE},
+    },
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct OwnerRequest {
+    #[serde(flatten)]
+    owner: Owner,
+    /// Owners can publish, yank and deprecate versions and manage owners, while publishers can
+    /// only publish new versions
+    #[serde(default)]
+    role: Role,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OwnersResponse {
+    users: BTreeSet<u64>,
+    teams: BTreeSet<String>,
+    publishers: BTreeSet<u64>,
+    publisher_teams: BTreeSet<String>,
+}
+
+impl From<PackageOwners> for OwnersResponse {
+    fn from(owners: PackageOwners) -> Self {
+        OwnersResponse {
+            users: owners.owners,
+            teams: owners.teams,
+            publishers: owners.publishers,
+            publisher_teams: owners.publisher_teams,
+        }
+    }
+}
+
+impl OwnersResponse {
+    fn new(info: &ScopeInfo, package: Option<&str>) -> Self {
+        match package {
+            Some(name) => info.packages.get(name).cloned().unwrap_or_default().into(),
+            None => OwnersResponse {
+                users: info.owners.clone(),
+                teams: info.teams.clone(),
+                publishers: info.publishers.clone(),
+                publisher_teams: info.publisher_teams.clone(),
+            },
+        }
+    }
+}
+
+pub fn is_valid_scope(scope: &str) -> bool {
+    format!("{scope}/a").parse::<PackageName>().is_ok()
+}
+
+/// Gets the owners of a scope
+#[utoipa::path(
+    get,
+    path = "/v0/scopes/{scope}/owners",
+    params(("scope" = String, Path)),
+    responses(
+        (status = 200, description = "The owners", body = OwnersResponse),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn get_scope_owners(
+    app_state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<impl Responder, Error> {
+    let scope = path.into_inner();
+    if !is_valid_scope(&scope) {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let source = app_state.read_source().await;
+    let repo = gix::open(source.path(&app_state.project))?;
+    let tree = root_tree(&repo)?;
+
+    match read_scope_info(&tree, &scope)? {
+        Some(info) => Ok(HttpResponse::Ok().json(OwnersResponse::new(&info, None))),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+/// Gets the owners of a package
+#[utoipa::path(
+    get,
+    path = "/v0/packages/{name}/owners",
+    params(PackagePath),
+    responses(
+        (status = 200, description = "The owners", body = OwnersResponse),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn get_package_owners(
+    app_state: web::Data<AppState>,
+    path: web::Path<PackageName>,
+) -> Result<impl Responder, Error> {
+    let name = path.into_inner();
+    let (scope, name_part) = name.as_str();
+
+    let source = app_state.read_source().await;
+    let repo = gix::open(source.path(&app_state.project))?;
+    let tree = root_tree(&repo)?;
+
+    let Some(info) = read_scope_info(&tree, scope)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    if read_file(&tree, [scope, name_part])?.is_none() {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    Ok(HttpResponse::Ok().json(OwnersResponse::new(&info, Some(name_part))))
+}
+
+/// Adds an owner or publisher of a scope
+#[utoipa::path(
+    post,
+    path = "/v0/scopes/{scope}/owners",
+    params(("scope" = String, Path)),
+    request_body = OwnerRequest,
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The owners after the change", body = OwnersResponse),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn add_scope_owner(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    path: web::Path<String>,
+    request: web::Json<OwnerRequest>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    update_owners(
+        app_state,
+        &http_request,
+        path.into_inner(),
+        None,
+        request.into_inner(),
+        true,
+        *user_id,
+        &permissions,
+    )
+    .await
+}
+
+/// Removes an owner or publisher of a scope
+#[utoipa::path(
+    delete,
+    path = "/v0/scopes/{scope}/owners",
+    params(("scope" = String, Path)),
+    request_body = OwnerRequest,
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The owners after the change", body = OwnersResponse),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn remove_scope_owner(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    path: web::Path<String>,
+    request: web::Json<OwnerRequest>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    update_owners(
+        app_state,
+        &http_request,
+        path.into_inner(),
+        None,
+        request.into_inner(),
+        false,
+        *user_id,
+        &permissions,
+    )
+    .await
+}
+
+/// Adds an owner or publisher of a package
+#[utoipa::path(
+    post,
+    path = "/v0/packages/{name}/owners",
+    params(PackagePath),
+    request_body = OwnerRequest,
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The owners after the change", body = OwnersResponse),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn add_package_owner(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    path: web::Path<PackageName>,
+    request: web::Json<OwnerRequest>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    let name = path.into_inner();
+
+    update_owners(
+        app_state,
+        &http_request,
+        name.as_str().0.to_string(),
+        Some(name),
+        request.into_inner(),
+        true,
+        *user_id,
+        &permissions,
+    )
+    .await
+}
+
+/// Removes an owner or publisher of a package
+#[utoipa::path(
+    delete,
+    path = "/v0/packages/{name}/owners",
+    params(PackagePath),
+    request_body = OwnerRequest,
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The owners after the change", body = OwnersResponse),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn remove_package_owner(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    path: web::Path<PackageName>,
+    request: web::Json<OwnerRequest>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    let name = path.into_inner();
+
+    update_owners(
+        app_state,
+        &http_request,
+        name.as_str().0.to_string(),
+        Some(name),
+        request.into_inner(),
+        false,
+        *user_id,
+        &permissions,
+    )
+    .await
+}
+
+/// Adds or removes an owner or publisher of the scope, or of one of its packages. Owners of a scope
+/// can manage the owners of the scope and of its packages, owners of a package can manage the
+/// owners of the package, and administrators can manage the owners of any scope, including
+/// claiming new ones. Publishers can't manage owners
+#[allow(clippy::too_many_arguments)]
+async fn update_owners(
+    app_state: web::Data<AppState>,
+    request: &HttpRequest,
+    scope: String,
+    package: Option<PackageName>,
+    OwnerRequest { owner, role }: OwnerRequest,
+    add: bool,
+    user_id: UserId,
+    permissions: &Permissions,
+) -> Result<HttpResponse, Error> {
+    if !permissions.can_manage_tokens() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    if !is_valid_scope(&scope) {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let owner = match owner {
+        Owner::User(id) => Owner::User(id),
+        Owner::Team(team) => {
+            if !app_state.teams.is_enabled() {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                    error: "this registry doesn't support team owners".to_string(),
+                }));
+            }
+
+            match parse_team(&team) {
+                Some(team) => Owner::Team(team),
+                None => {
+                    return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                        error: "team must be in the form `org/team`".to_string(),
+                    }))
+                }
+            }
+        }
+    };
+
+    let source = app_state.write_source().await;
+    source
+        .refresh(&app_state.project, &app_state.reqwest_client)
+        .await
+        .map_err(Box::new)?;
+
+    let name_part = package.as_ref().map(|name| name.as_str().1);
+    let admin = is_admin(&app_state, user_id, permissions);
+
+    let mut info = {
+        let repo = gix::open(source.path(&app_state.project))?;
+        let tree = root_tree(&repo)?;
+
+        let info = match read_scope_info(&tree, &scope)? {
+            Some(info) => info,
+            // administrators can claim scopes which don't exist yet on behalf of their owners
+            None if admin && add && name_part.is_none() => ScopeInfo::default(),
+            None => return Ok(HttpResponse::NotFound().finish()),
+        };
+
+        if let Some(name_part) = name_part {
+            if read_file(&tree, [scope.as_str(), name_part])?.is_none() {
+                return Ok(HttpResponse::NotFound().finish());
+            }
+        }
+
+        info
+    };
+
+    let authorized = admin
+        || match name_part {
+            Some(name_part) => owns_package(&app_state, &info, name_part, user_id).await?,
+            None => owns_scope(&app_state, &info, user_id).await?,
+        };
+    if !authorized {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    if add {
+        if let Owner::Team(team) = &owner {
+            if !app_state.teams.exists(team).await? {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                    error: format!("team {team} doesn't exist"),
+                }));
+            }
+        }
+    }
+
+    let changed = {
+        let (users, teams) = match (name_part, role) {
+            (Some(name_part), role) => {
+                let owners = info.packages.entry(name_part.to_string()).or_default();
+                match role {
+                    Role::Owner => (&mut owners.owners, &mut owners.teams),
+                    Role::Publisher => (&mut owners.publishers, &mut owners.publisher_teams),
+                }
+            }
+            (None, Role::Owner) => (&mut info.owners, &mut info.teams),
+            (None, Role::Publisher) => (&mut info.publishers, &mut info.publisher_teams),
+        };
+
+        match (&owner, add) {
+            (Owner::User(id), true) => users.insert(*id),
+            (Owner::User(id), false) => users.remove(id),
+            (Owner::Team(team), true) => teams.insert(team.clone()),
+            (Owner::Team(team), false) => teams.remove(team),
+        }
+    };
+    info.packages.retain(|_, owners| !owners.is_empty());
+
+    if !changed {
+        return Ok(if add {
+            HttpResponse::Ok().json(OwnersResponse::new(&info, name_part))
+        } else {
+            HttpResponse::NotFound().finish()
+        });
+    }
+
+    if name_part.is_none() && info.owners.is_empty() && info.teams.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "a scope must have at least one owner".to_string(),
+        }));
+    }
+
+    let target = match &package {
+        Some(name) => name.to_string(),
+        None => format!("scope {scope}"),
+    };
+    let message = if add {
+        format!("add {owner} as {} of {target}", role.with_article())
+    } else {
+        format!("remove {owner} as {} of {target}", role.with_article())
+    };
+
+    push_changes(
+        &app_state,
+        &source,
+        &scope,
+        HashMap::from([(SCOPE_INFO_FILE, Some(toml::to_string(&info)?.into_bytes()))]),
+        &message,
+    )?;
+
+    let response = OwnersResponse::new(&info, name_part);
+    let action = if add {
+        app_state
+            .notifications
+            .notify(
+                &info,
+                Notification::OwnerAdded {
+                    scope: scope.clone(),
+                    package: package.clone(),
+                    owner: owner.clone(),
+                    role,
+                    added_by: user_id,
+                },
+            )
+            .await;
+
+        Action::AddOwner {
+            scope,
+            package,
+            owner,
+            role,
+        }
+    } else {
+        Action::RemoveOwner {
+            scope,
+            package,
+            owner,
+            role,
+        }
+    };
+    app_state.audit_log.record(request, user_id, action).await;
+
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -0,0 +1,75 @@
+use actix_web::{
+    http::header::{HeaderValue, CACHE_CONTROL, RANGE},
+    web, HttpRequest, HttpResponse, Responder,
+};
+use semver::Version;
+
+use crate::{
+    endpoints::openapi::shared::{Binary, NotFound, TooManyRequests, VersionPath},
+    error::Error,
+    metadata_cache::read_package,
+    storage::{immutable_cache_control, ranged_response, StorageImpl},
+    AppState,
+};
+use pesde::{manifest::target::TargetKind, names::PackageName, source::version_id::VersionId};
+
+/// Serves the archive of a version at a URL containing its checksum, which can be cached forever
+///
+/// Redirects to the storage if it's served elsewhere
+#[utoipa::path(
+    get,
+    path = "/v0/packages/{name}/{version}/{target}/archive/{checksum}",
+    params(
+        VersionPath,
+        ("checksum" = String, Path, description = "The SHA-256 checksum of the archive, as found in the index"),
+    ),
+    responses(
+        (status = 200, description = "The archive", body = inline(Binary), content_type = "application/octet-stream"),
+        (status = 206, description = "The part of the archive the `Range` header asked for, such as when resuming a download"),
+        (status = 307, description = "The archive is served by the storage"),
+        (status = 404, response = NotFound),
+        (status = 416, description = "The `Range` header starts past the end of the archive"),
+        (status = 429, response = TooManyRequests),
+    )
+)]
+pub async fn get_package_archive(
+    request: HttpRequest,
+    app_state: web::Data<AppState>,
+    path: web::Path<(PackageName, Version, TargetKind, String)>,
+) -> Result<impl Responder, Error> {
+    let (name, version, target, checksum) = path.into_inner();
+    let v_id = VersionId::new(version, target);
+
+    let Some(entries) = read_package(&app_state, &name).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    if !entries
+        .get(&v_id)
+        .and_then(|entry| entry.checksum.as_deref())
+        .is_some_and(|expected| expected.eq_ignore_ascii_case(&checksum))
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let mut response = ranged_response(
+        &request,
+        app_state.storage.get().get_package(&name, &v_id).await?,
+    );
+    if response.status().is_client_error() {
+        return Ok(response);
+    }
+
+    // requests resuming an interrupted download were already counted
+    if !request.headers().contains_key(RANGE) {
+        app_state.downloads.record(&name, &v_id);
+    }
+
+    if response.status().is_success() {
+        response.headers_mut().insert(
+            CACHE_CONTROL,
+            HeaderValue::from_static(immutable_cache_control(&app_state)),
+        );
+    }
+
+    Ok(response)
+}
@@ -0,0 +1,52 @@
+use actix_web::{http::header::CONTENT_TYPE, web, HttpResponse, Responder};
+
+use crate::{
+    endpoints::{
+        openapi::shared::{NotFound, VersionPath},
+        package_version::{find_version, TargetRequest, VersionRequest},
+    },
+    error::Error,
+    metadata_cache::read_package,
+    storage::StorageImpl,
+    AppState,
+};
+use pesde::names::PackageName;
+
+/// Serves the attestation document published alongside the archive of a version
+#[utoipa::path(
+    get,
+    path = "/v0/packages/{name}/{version}/{target}/attestation",
+    params(VersionPath),
+    responses(
+        (status = 200, description = "The attestation document, as it was published", body = Object),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn get_package_attestation(
+    app_state: web::Data<AppState>,
+    path: web::Path<(PackageName, VersionRequest, TargetRequest)>,
+) -> Result<impl Responder, Error> {
+    let (name, version, target) = path.into_inner();
+
+    let entries = match read_package(&app_state, &name).await? {
+        Some(versions) => versions,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let Some((v_id, _)) = find_version(&entries, version, &target) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let Some(attestation) = app_state
+        .storage
+        .get()
+        .read_attestation(&name, v_id)
+        .await?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    Ok(HttpResponse::Ok()
+        .append_header((CONTENT_TYPE, "application/json"))
+        .body(attestation))
+}
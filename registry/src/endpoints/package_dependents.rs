@@ -0,0 +1,132 @@
+use actix_web::{web, HttpResponse, Responder};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use utoipa::{
+    openapi::{schema::Type, Object, ObjectBuilder},
+    IntoParams, ToSchema,
+};
+
+use crate::{
+    endpoints::openapi::shared::{BadRequest, NotFound, PackagePath, Target},
+    error::Error,
+    metadata_cache::read_package,
+    AppState,
+};
+use pesde::{
+    manifest::{target::TargetKind, DependencyType},
+    names::PackageName,
+};
+
+/// The amount of dependents returned if the request doesn't specify a limit
+const DEFAULT_LIMIT: usize = 50;
+/// The maximum amount of dependents returned at once
+const MAX_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Request {
+    #[serde(default)]
+    offset: Option<usize>,
+    #[serde(default)]
+    #[param(minimum = 1, maximum = 100, default = 50)]
+    limit: Option<usize>,
+    /// Only counts the dependents whose requirements match this version, such as to see who a
+    /// yank would affect
+    #[serde(default)]
+    #[param(value_type = Option<String>)]
+    version: Option<Version>,
+}
+
+fn dependency_type_schema() -> Object {
+    ObjectBuilder::new()
+        .schema_type(Type::String)
+        .enum_values(Some(["standard", "peer", "dev"]))
+        .build()
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct DependentVersion {
+    version: String,
+    #[schema(value_type = Target)]
+    target: TargetKind,
+    requirement: String,
+    #[serde(rename = "type")]
+    #[schema(schema_with = dependency_type_schema)]
+    ty: DependencyType,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct DependentResponse {
+    name: String,
+    versions: Vec<DependentVersion>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct DependentsResponse {
+    data: Vec<DependentResponse>,
+    count: usize,
+}
+
+/// Gets the packages of this registry which depend on a package
+#[utoipa::path(
+    get,
+    path = "/v0/packages/{name}/dependents",
+    params(PackagePath, Request),
+    responses(
+        (status = 200, description = "The dependents, sorted by their names. Yanked versions aren't included", body = DependentsResponse),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn get_package_dependents(
+    app_state: web::Data<AppState>,
+    path: web::Path<PackageName>,
+    request: web::Query<Request>,
+) -> Result<impl Responder, Error> {
+    let name = path.into_inner();
+
+    if read_package(&app_state, &name).await?.is_none() {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let dependents = app_state
+        .dependents
+        .get(&name)
+        .into_iter()
+        .filter_map(|(name, versions)| {
+            let versions = versions
+                .into_iter()
+                .filter(|(_, dependency)| {
+                    request
+                        .version
+                        .as_ref()
+                        .is_none_or(|version| dependency.requirement.matches(version))
+                })
+                .map(|(v_id, dependency)| DependentVersion {
+                    version: v_id.version().to_string(),
+                    target: v_id.target().clone(),
+                    requirement: dependency.requirement.to_string(),
+                    ty: dependency.ty,
+                })
+                .collect::<Vec<_>>();
+
+            (!versions.is_empty()).then(|| DependentResponse {
+                name: name.to_string(),
+                versions,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let count = dependents.len();
+    let limit = request.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let dependents = dependents
+        .into_iter()
+        .skip(request.offset.unwrap_or_default())
+        .take(limit)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(DependentsResponse {
+        data: dependents,
+        count,
+    }))
+}
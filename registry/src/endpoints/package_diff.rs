@@ -0,0 +1,149 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    archive::{read_file_list, ArchiveFile},
+    endpoints::{
+        openapi::shared::{BadRequest, NotFound, PackagePath, Target},
+        package_version::{find_version, TargetRequest, VersionRequest},
+    },
+    error::Error,
+    metadata_cache::read_package,
+    AppState,
+};
+use pesde::{manifest::target::TargetKind, names::PackageName};
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Query {
+    /// The version to compare from, or `latest`
+    #[param(value_type = String)]
+    from: VersionRequest,
+    /// The version to compare to, or `latest`
+    #[param(value_type = String)]
+    to: VersionRequest,
+    /// The target to compare, defaulting to any of the `to` version's
+    #[serde(default)]
+    #[param(value_type = Option<Target>, inline)]
+    target: Option<TargetKind>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = DiffFile)]
+struct File {
+    path: String,
+    size: u64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ModifiedFile {
+    path: String,
+    old_size: u64,
+    new_size: u64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct DiffResponse {
+    from: String,
+    to: String,
+    #[schema(value_type = Target)]
+    target: TargetKind,
+    added: Vec<File>,
+    removed: Vec<File>,
+    modified: Vec<ModifiedFile>,
+}
+
+fn diff(
+    from: BTreeMap<String, ArchiveFile>,
+    mut to: BTreeMap<String, ArchiveFile>,
+) -> (Vec<File>, Vec<File>, Vec<ModifiedFile>) {
+    let mut removed = vec![];
+    let mut modified = vec![];
+
+    for (path, old) in from {
+        match to.remove(&path) {
+            Some(new) if new.hash != old.hash => modified.push(ModifiedFile {
+                path,
+                old_size: old.size,
+                new_size: new.size,
+            }),
+            Some(_) => {}
+            None => removed.push(File {
+                path,
+                size: old.size,
+            }),
+        }
+    }
+
+    let added = to
+        .into_iter()
+        .map(|(path, file)| File {
+            path,
+            size: file.size,
+        })
+        .collect();
+
+    (added, removed, modified)
+}
+
+/// Compares the files of two versions of a package
+#[utoipa::path(
+    get,
+    path = "/v0/packages/{name}/diff",
+    params(PackagePath, Query),
+    responses(
+        (status = 200, description = "The files which were added, removed, or modified", body = DiffResponse),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn get_package_diff(
+    app_state: web::Data<AppState>,
+    path: web::Path<PackageName>,
+    query: web::Query<Query>,
+) -> Result<impl Responder, Error> {
+    let name = path.into_inner();
+    let query = query.into_inner();
+
+    let entries = match read_package(&app_state, &name).await? {
+        Some(versions) => versions,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let target = match query.target {
+        Some(kind) => TargetRequest::Specific(kind),
+        None => TargetRequest::Any,
+    };
+    let Some((to, to_entry)) = find_version(&entries, query.to, &target) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    // both versions are compared with the same target
+    let target = to_entry.target.kind();
+    let Some((from, _)) = find_version(
+        &entries,
+        query.from,
+        &TargetRequest::Specific(target.clone()),
+    ) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let (Some(from_files), Some(to_files)) = (
+        read_file_list(&app_state, &name, from).await?,
+        read_file_list(&app_state, &name, to).await?,
+    ) else {
+        return Ok(HttpResponse::InternalServerError().finish());
+    };
+
+    let (added, removed, modified) = diff(from_files, to_files);
+
+    Ok(HttpResponse::Ok().json(DiffResponse {
+        from: from.version().to_string(),
+        to: to.version().to_string(),
+        target,
+        added,
+        removed,
+        modified,
+    }))
+}
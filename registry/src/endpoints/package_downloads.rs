@@ -0,0 +1,129 @@
+use actix_web::{web, HttpResponse, Responder};
+use chrono::NaiveDate;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    downloads::{Interval, Period},
+    endpoints::openapi::shared::{BadRequest, NotFound, PackagePath, Target},
+    error::{Error, ErrorResponse},
+    metadata_cache::read_package,
+    AppState,
+};
+use pesde::{manifest::target::TargetKind, names::PackageName, source::version_id::VersionId};
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Request {
+    /// The span of time to return the downloads over, as a number followed by `d`, `w`, `m` or
+    /// `y`, such as `90d`
+    #[serde(default)]
+    period: Option<String>,
+    /// Only counts the downloads of this version in the series
+    #[serde(default)]
+    #[param(value_type = Option<String>)]
+    version: Option<Version>,
+    /// Only counts the downloads of this target in the series, of the version if there's one
+    #[serde(default)]
+    #[param(value_type = Option<Target>, inline)]
+    target: Option<TargetKind>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct DateDownloads {
+    date: NaiveDate,
+    downloads: u64,
+}
+
+impl From<(NaiveDate, u64)> for DateDownloads {
+    fn from((date, downloads): (NaiveDate, u64)) -> Self {
+        Self { date, downloads }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct DownloadsResponse {
+    total: u64,
+    #[schema(value_type = BTreeMap<String, u64>)]
+    versions: BTreeMap<VersionId, u64>,
+    recent: Vec<DateDownloads>,
+    /// The granularity of the series, if a period was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interval: Option<Interval>,
+    /// The downloads over the requested period, oldest first. Monthly buckets are dated by their
+    /// first day
+    #[serde(skip_serializing_if = "Option::is_none")]
+    series: Option<Vec<DateDownloads>>,
+}
+
+/// Gets the download counts of a package
+#[utoipa::path(
+    get,
+    path = "/v0/packages/{name}/downloads",
+    params(PackagePath, Request),
+    responses(
+        (status = 200, description = "The download counts", body = DownloadsResponse),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+    )
+)]
+
+pub async fn get_package_downloads(
+    app_state: web::Data<AppState>,
+    path: web::Path<PackageName>,
+    request: web::Query<Request>,
+) -> Result<impl Responder, Error> {
+    let name = path.into_inner();
+    let request = request.into_inner();
+
+    let Some(entries) = read_package(&app_state, &name).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let period = match request.period.as_deref().map(str::parse::<Period>) {
+        Some(Ok(period)) => Some(period),
+        Some(Err(error)) => return Ok(HttpResponse::BadRequest().json(ErrorResponse { error })),
+        None => None,
+    };
+
+    let version_ids = (request.version.is_some() || request.target.is_some()).then(|| {
+        entries
+            .keys()
+            .filter(|v_id| {
+                request
+                    .version
+                    .as_ref()
+                    .is_none_or(|version| v_id.version() == version)
+                    && request
+                        .target
+                        .as_ref()
+                        .is_none_or(|target| v_id.target() == target)
+            })
+            .cloned()
+            .collect::<Vec<VersionId>>()
+    });
+
+    let (versions, recent) = app_state.downloads.get(&name).await;
+
+    let mut response = DownloadsResponse {
+        total: versions.values().sum(),
+        versions,
+        recent: recent.into_iter().map(Into::into).collect(),
+        interval: None,
+        series: None,
+    };
+
+    if let Some(period) = period {
+        let (interval, series) = app_state
+            .downloads
+            .series(&name, version_ids.as_deref(), period)
+            .await;
+
+        response.interval = Some(interval);
+        response.series = Some(series.into_iter().map(Into::into).collect());
+    }
+
+    Ok(HttpResponse::Ok().json(response))
+}
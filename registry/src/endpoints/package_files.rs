@@ -0,0 +1,132 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+use crate::{
+    archive::{read_file_list, ArchiveFile},
+    endpoints::{
+        openapi::shared::{NotFound, VersionPath},
+        package_version::{find_version, TargetRequest, VersionRequest},
+    },
+    error::Error,
+    metadata_cache::read_package,
+    AppState,
+};
+use pesde::names::PackageName;
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[schema(as = FileTreeEntry)]
+enum TreeEntry {
+    File {
+        name: String,
+        size: u64,
+        /// The SHA-256 checksum of the file's content
+        hash: String,
+    },
+    Directory {
+        name: String,
+        /// The total size of the files in the directory
+        size: u64,
+        #[schema(no_recursion)]
+        children: Vec<TreeEntry>,
+    },
+}
+
+impl TreeEntry {
+    fn size(&self) -> u64 {
+        match self {
+            TreeEntry::File { size, .. } | TreeEntry::Directory { size, .. } => *size,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Directory {
+    directories: BTreeMap<String, Directory>,
+    files: BTreeMap<String, ArchiveFile>,
+}
+
+impl Directory {
+    fn insert(&mut self, path: &str, file: ArchiveFile) {
+        match path.split_once('/') {
+            Some((directory, rest)) => self
+                .directories
+                .entry(directory.to_string())
+                .or_default()
+                .insert(rest, file),
+            None => {
+                self.files.insert(path.to_string(), file);
+            }
+        }
+    }
+
+    /// Converts the directory's contents into entries, with directories before files
+    fn into_entries(self) -> Vec<TreeEntry> {
+        let directories = self.directories.into_iter().map(|(name, directory)| {
+            let children = directory.into_entries();
+
+            TreeEntry::Directory {
+                name,
+                size: children.iter().map(TreeEntry::size).sum(),
+                children,
+            }
+        });
+        let files = self.files.into_iter().map(|(name, file)| TreeEntry::File {
+            name,
+            size: file.size,
+            hash: file.hash,
+        });
+
+        directories.chain(files).collect()
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct FilesResponse {
+    /// The total size of the files in the archive
+    size: u64,
+    tree: Vec<TreeEntry>,
+}
+
+/// Gets the tree of the files in the archive of a version of a package
+#[utoipa::path(
+    get,
+    path = "/v0/packages/{name}/{version}/{target}/files",
+    params(VersionPath),
+    responses(
+        (status = 200, description = "The tree of files, with directories before files", body = FilesResponse),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn get_package_files(
+    app_state: web::Data<AppState>,
+    path: web::Path<(PackageName, VersionRequest, TargetRequest)>,
+) -> Result<impl Responder, Error> {
+    let (name, version, target) = path.into_inner();
+
+    let entries = match read_package(&app_state, &name).await? {
+        Some(versions) => versions,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let Some((v_id, _)) = find_version(&entries, version, &target) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let Some(files) = read_file_list(&app_state, &name, v_id).await? else {
+        return Ok(HttpResponse::InternalServerError().finish());
+    };
+
+    let mut root = Directory::default();
+    for (path, file) in files {
+        root.insert(&path, file);
+    }
+    let tree = root.into_entries();
+
+    Ok(HttpResponse::Ok().json(FilesResponse {
+        size: tree.iter().map(TreeEntry::size).sum(),
+        tree,
+    }))
+}
@@ -0,0 +1,95 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    endpoints::{
+        openapi::shared::{BadRequest, NotFound, VersionPath},
+        package_version::{find_version, TargetRequest, VersionRequest},
+    },
+    error::Error,
+    metadata_cache::read_package,
+    readme::{render_html, HIGHLIGHT_CSS},
+    storage::{decompress, StorageImpl},
+    AppState,
+};
+use pesde::names::PackageName;
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Render {
+    Html,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Query {
+    /// The format to render the README to, returning its markdown if not given
+    #[serde(default)]
+    render: Option<Render>,
+}
+
+/// Gets the README of a version of a package
+#[utoipa::path(
+    get,
+    path = "/v0/packages/{name}/{version}/{target}/readme",
+    params(VersionPath, Query),
+    responses(
+        (status = 200, description = "The README", content(
+            (String = "text/markdown"),
+            (String = "text/html"),
+        )),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn get_package_readme(
+    app_state: web::Data<AppState>,
+    path: web::Path<(PackageName, VersionRequest, TargetRequest)>,
+    query: web::Query<Query>,
+) -> Result<impl Responder, Error> {
+    let (name, version, target) = path.into_inner();
+
+    let entries = match read_package(&app_state, &name).await? {
+        Some(versions) => versions,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let Some((v_id, _)) = find_version(&entries, version, &target) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let Some(contents) = app_state.storage.get().read_readme(&name, v_id).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let contents = match decompress(contents).await {
+        Ok(contents) => contents,
+        Err(e) => {
+            tracing::error!("failed to decompress readme of {name}@{v_id}: {e}");
+            return Ok(HttpResponse::InternalServerError().finish());
+        }
+    };
+    let readme = String::from_utf8_lossy(&contents);
+
+    Ok(match query.render {
+        Some(Render::Html) => HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(render_html(&readme)),
+        None => HttpResponse::Ok()
+            .content_type("text/markdown; charset=utf-8")
+            .body(readme.into_owned()),
+    })
+}
+
+/// Gets the stylesheet of the code highlighted in rendered READMEs
+#[utoipa::path(
+    get,
+    path = "/v0/readme.css",
+    responses((status = 200, description = "The stylesheet", body = String, content_type = "text/css"))
+)]
+pub async fn get_highlight_css() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/css; charset=utf-8")
+        .body(HIGHLIGHT_CSS.as_str())
+}
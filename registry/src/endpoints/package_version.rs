@@ -1,14 +1,34 @@
-use actix_web::{http::header::ACCEPT, web, HttpRequest, HttpResponse, Responder};
+use actix_web::{
+    http::header::{ACCEPT, RANGE},
+    web, HttpRequest, HttpResponse, Responder,
+};
 use semver::Version;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Display, Formatter},
+};
+use utoipa::{IntoParams, ToSchema};
 
-use crate::{error::Error, package::PackageResponse, storage::StorageImpl, AppState};
+use crate::{
+    endpoints::openapi::shared::{
+        Binary, Gone, NotFound, NotModified, TooManyRequests, VersionPath,
+    },
+    error::Error,
+    git::index_modified_at,
+    metadata_cache::read_package,
+    moderation::removed_response,
+    package::{conditional_json, PackageResponse},
+    storage::{ranged_response, StorageImpl},
+    AppState,
+};
 use pesde::{
-    manifest::target::TargetKind,
+    manifest::{target::TargetKind, DependencyType, Metadata},
     names::PackageName,
     source::{
-        git_index::{read_file, root_tree, GitBasedSource},
-        pesde::{DocEntryKind, IndexFile},
+        pesde::{DocEntry, DocEntryKind, IndexFile, IndexFileEntry},
+        specifiers::DependencySpecifiers,
+        version_id::VersionId,
     },
 };
 
@@ -56,11 +76,75 @@ impl<'de> Deserialize<'de> for TargetRequest {
     }
 }
 
-#[derive(Debug, Deserialize)]
+impl Display for TargetRequest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TargetRequest::Any => write!(f, "any"),
+            TargetRequest::Specific(kind) => write!(f, "{kind}"),
+        }
+    }
+}
+
+/// Finds the entry of the requested version and target of a package
+pub fn find_version<'a>(
+    entries: &'a IndexFile,
+    version: VersionRequest,
+    target: &TargetRequest,
+) -> Option<(&'a VersionId, &'a IndexFileEntry)> {
+    let version = match version {
+        VersionRequest::Latest => entries.keys().map(|k| k.version()).max()?.clone(),
+        VersionRequest::Specific(version) => version,
+    };
+
+    let mut versions = entries
+        .iter()
+        .filter(|(v_id, _)| *v_id.version() == version);
+
+    match target {
+        TargetRequest::Any => versions.min_by_key(|(v_id, _)| v_id.target().clone()),
+        TargetRequest::Specific(kind) => versions.find(|(_, entry)| entry.target.kind() == *kind),
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct Query {
+    /// The name of a documentation page to return
     doc: Option<String>,
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+struct PackageVersionResponse {
+    #[serde(flatten)]
+    package: PackageResponse,
+    #[schema(value_type = Vec<Object>)]
+    docs: BTreeSet<DocEntry>,
+    #[schema(value_type = Object)]
+    dependencies: BTreeMap<String, (DependencySpecifiers, DependencyType)>,
+    #[schema(value_type = Object)]
+    metadata: Metadata,
+}
+
+/// Gets a version of a package
+///
+/// Returns the version's metadata by default. The archive is returned if `application/octet-stream`
+/// is accepted, and the README if `text/plain` is accepted
+#[utoipa::path(
+    get,
+    path = "/v0/packages/{name}/{version}/{target}",
+    params(VersionPath, Query),
+    responses(
+        (status = 200, description = "The version", content(
+            (PackageVersionResponse = "application/json"),
+            (inline(Binary) = "application/octet-stream"),
+            (String = "text/plain"),
+        )),
+        (status = 304, response = NotModified),
+        (status = 404, response = NotFound),
+        (status = 410, response = Gone),
+        (status = 429, response = TooManyRequests),
+    )
+)]
 pub async fn get_package_version(
     request: HttpRequest,
     app_state: web::Data<AppState>,
@@ -69,48 +153,36 @@ pub async fn get_package_version(
 ) -> Result<impl Responder, Error> {
     let (name, version, target) = path.into_inner();
 
-    let (scope, name_part) = name.as_str();
-
-    let entries: IndexFile = {
-        let source = app_state.source.lock().await;
-        let repo = gix::open(source.path(&app_state.project))?;
-        let tree = root_tree(&repo)?;
-
-        match read_file(&tree, [scope, name_part])? {
-            Some(versions) => toml::de::from_str(&versions)?,
-            None => return Ok(HttpResponse::NotFound().finish()),
+    let entries = match read_package(&app_state, &name).await? {
+        Some(versions) => versions,
+        None => {
+            return Ok(match app_state.moderation.removal_of(&name, None).await {
+                Some(removal) => removed_response(&removal),
+                None => HttpResponse::NotFound().finish(),
+            })
         }
     };
 
-    let Some((v_id, entry, targets)) = ({
-        let version = match version {
-            VersionRequest::Latest => match entries.keys().map(|k| k.version()).max() {
-                Some(latest) => latest.clone(),
-                None => return Ok(HttpResponse::NotFound().finish()),
-            },
-            VersionRequest::Specific(version) => version,
+    let requested_version = match &version {
+        VersionRequest::Specific(version) => Some(version.clone()),
+        VersionRequest::Latest => None,
+    };
+    let Some((v_id, entry)) = find_version(&entries, version, &target) else {
+        let removal = match &requested_version {
+            Some(version) => app_state.moderation.removal_of(&name, Some(version)).await,
+            None => None,
         };
 
-        let versions = entries
-            .iter()
-            .filter(|(v_id, _)| *v_id.version() == version);
-
-        match target {
-            TargetRequest::Any => versions.clone().min_by_key(|(v_id, _)| *v_id.target()),
-            TargetRequest::Specific(kind) => versions
-                .clone()
-                .find(|(_, entry)| entry.target.kind() == kind),
-        }
-        .map(|(v_id, entry)| {
-            (
-                v_id,
-                entry,
-                versions.map(|(_, entry)| (&entry.target).into()).collect(),
-            )
-        })
-    }) else {
-        return Ok(HttpResponse::NotFound().finish());
+        return Ok(match removal {
+            Some(removal) => removed_response(&removal),
+            None => HttpResponse::NotFound().finish(),
+        });
     };
+    let targets = entries
+        .iter()
+        .filter(|(other, _)| other.version() == v_id.version())
+        .map(|(_, entry)| entry.into())
+        .collect();
 
     if let Some(doc_name) = query.doc.as_deref() {
         let hash = 'finder: {
@@ -130,7 +202,10 @@ pub async fn get_package_version(
             return Ok(HttpResponse::NotFound().finish());
         };
 
-        return app_state.storage.get_doc(&hash).await;
+        return Ok(ranged_response(
+            &request,
+            app_state.storage.get().get_doc(&hash).await?,
+        ));
     }
 
     let accept = request
@@ -144,14 +219,23 @@ pub async fn get_package_version(
         });
 
     if let Some(readme) = accept {
-        return if readme {
-            app_state.storage.get_readme(&name, v_id).await
-        } else {
-            app_state.storage.get_package(&name, v_id).await
-        };
+        if readme {
+            return app_state.storage.get().get_readme(&name, v_id).await;
+        }
+
+        let response = ranged_response(
+            &request,
+            app_state.storage.get().get_package(&name, v_id).await?,
+        );
+        // requests resuming an interrupted download were already counted
+        if !response.status().is_client_error() && !request.headers().contains_key(RANGE) {
+            app_state.downloads.record(&name, v_id);
+        }
+
+        return Ok(response);
     }
 
-    let response = PackageResponse {
+    let package = PackageResponse {
         name: name.to_string(),
         version: v_id.version().to_string(),
         targets,
@@ -160,11 +244,17 @@ pub async fn get_package_version(
         license: entry.license.clone().unwrap_or_default(),
         authors: entry.authors.clone(),
         repository: entry.repository.clone().map(|url| url.to_string()),
+        maintainers: entry.maintainers.clone(),
+        funding: entry.funding.iter().map(|url| url.to_string()).collect(),
+        deprecated: entry.deprecated.clone(),
     };
 
-    let mut value = serde_json::to_value(response)?;
-    value["docs"] = serde_json::to_value(entry.docs.clone())?;
-    value["dependencies"] = serde_json::to_value(entry.dependencies.clone())?;
+    let response = PackageVersionResponse {
+        package,
+        docs: entry.docs.clone(),
+        dependencies: entry.dependencies.clone(),
+        metadata: entry.metadata.clone(),
+    };
 
-    Ok(HttpResponse::Ok().json(value))
+    conditional_json(&request, index_modified_at(&app_state).await?, &response)
 }
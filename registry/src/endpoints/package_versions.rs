@@ -1,54 +1,88 @@
 use std::collections::{BTreeMap, BTreeSet};
 
-use actix_web::{web, HttpResponse, Responder};
-
-use crate::{error::Error, package::PackageResponse, AppState};
-use pesde::{
-    names::PackageName,
-    source::{
-        git_index::{read_file, root_tree, GitBasedSource},
-        pesde::IndexFile,
-    },
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+
+use crate::{
+    endpoints::openapi::shared::{Gone, NotFound, NotModified, PackagePath},
+    error::Error,
+    git::index_modified_at,
+    metadata_cache::read_package,
+    moderation::removed_response,
+    package::{conditional_json, PackageResponse},
+    AppState,
 };
+use pesde::{manifest::Metadata, names::PackageName};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, ToSchema)]
+struct PackageVersionsResponse {
+    #[serde(flatten)]
+    package: PackageResponse,
+    #[serde(skip_serializing_if = "Metadata::is_empty")]
+    #[schema(value_type = Object)]
+    metadata: Metadata,
+}
 
+/// Gets the versions of a package
+#[utoipa::path(
+    get,
+    path = "/v0/packages/{name}",
+    params(PackagePath),
+    responses(
+        (status = 200, description = "The versions of the package", body = Vec<PackageVersionsResponse>),
+        (status = 304, response = NotModified),
+        (status = 404, response = NotFound),
+        (status = 410, response = Gone),
+    )
+)]
 pub async fn get_package_versions(
+    request: HttpRequest,
     app_state: web::Data<AppState>,
     path: web::Path<PackageName>,
 ) -> Result<impl Responder, Error> {
     let name = path.into_inner();
 
-    let (scope, name_part) = name.as_str();
-
-    let versions: IndexFile = {
-        let source = app_state.source.lock().await;
-        let repo = gix::open(source.path(&app_state.project))?;
-        let tree = root_tree(&repo)?;
-
-        match read_file(&tree, [scope, name_part])? {
-            Some(versions) => toml::de::from_str(&versions)?,
-            None => return Ok(HttpResponse::NotFound().finish()),
+    let versions = match read_package(&app_state, &name).await? {
+        Some(versions) => versions,
+        None => {
+            return Ok(match app_state.moderation.removal_of(&name, None).await {
+                Some(removal) => removed_response(&removal),
+                None => HttpResponse::NotFound().finish(),
+            })
         }
     };
 
     let mut responses = BTreeMap::new();
 
     for (v_id, entry) in versions {
-        let info = responses
-            .entry(v_id.version().clone())
-            .or_insert_with(|| PackageResponse {
-                name: name.to_string(),
-                version: v_id.version().to_string(),
-                targets: BTreeSet::new(),
-                description: entry.description.unwrap_or_default(),
-                published_at: entry.published_at,
-                license: entry.license.unwrap_or_default(),
-                authors: entry.authors.clone(),
-                repository: entry.repository.clone().map(|url| url.to_string()),
-            });
-
-        info.targets.insert(entry.target.into());
-        info.published_at = info.published_at.max(entry.published_at);
+        let info =
+            responses
+                .entry(v_id.version().clone())
+                .or_insert_with(|| PackageVersionsResponse {
+                    package: PackageResponse {
+                        name: name.to_string(),
+                        version: v_id.version().to_string(),
+                        targets: BTreeSet::new(),
+                        description: entry.description.clone().unwrap_or_default(),
+                        published_at: entry.published_at,
+                        license: entry.license.clone().unwrap_or_default(),
+                        authors: entry.authors.clone(),
+                        repository: entry.repository.clone().map(|url| url.to_string()),
+                        maintainers: entry.maintainers.clone(),
+                        funding: entry.funding.iter().map(|url| url.to_string()).collect(),
+                        deprecated: entry.deprecated.clone(),
+                    },
+                    metadata: entry.metadata.clone(),
+                });
+
+        info.package.targets.insert((&entry).into());
+        info.package.published_at = info.package.published_at.max(entry.published_at);
     }
 
-    Ok(HttpResponse::Ok().json(responses.into_values().collect::<Vec<_>>()))
+    conditional_json(
+        &request,
+        index_modified_at(&app_state).await?,
+        &responses.into_values().collect::<Vec<_>>(),
+    )
 }
@@ -0,0 +1,84 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    endpoints::openapi::shared::BadRequest, error::Error, metadata_cache::read_all_packages,
+    package::PackageResponse, AppState,
+};
+
+/// The amount of packages returned if the request doesn't specify a limit
+const DEFAULT_LIMIT: usize = 50;
+/// The maximum amount of packages returned at once
+const MAX_LIMIT: usize = 100;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Sort {
+    #[default]
+    Name,
+    /// The most recently published packages first
+    Published,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Request {
+    #[serde(default)]
+    offset: Option<usize>,
+    #[serde(default)]
+    #[param(minimum = 1, maximum = 100, default = 50)]
+    limit: Option<usize>,
+    #[serde(default)]
+    sort: Sort,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PackageListResponse {
+    data: Vec<PackageResponse>,
+    /// The amount of packages
+    count: usize,
+}
+
+/// Lists every package of the registry, summarized by its latest version
+#[utoipa::path(
+    get,
+    path = "/v0/packages",
+    params(Request),
+    responses(
+        (status = 200, description = "The packages", body = PackageListResponse),
+        (status = 400, response = BadRequest),
+    )
+)]
+pub async fn list_packages(
+    app_state: web::Data<AppState>,
+    request: web::Query<Request>,
+) -> Result<impl Responder, Error> {
+    let mut packages = read_all_packages(&app_state)
+        .await
+        .into_iter()
+        .filter_map(|(name, versions)| PackageResponse::latest(&name, &versions))
+        .collect::<Vec<_>>();
+
+    match request.sort {
+        Sort::Name => packages.sort_by(|a, b| a.name.cmp(&b.name)),
+        Sort::Published => packages.sort_by(|a, b| {
+            b.published_at
+                .cmp(&a.published_at)
+                .then_with(|| a.name.cmp(&b.name))
+        }),
+    }
+
+    let count = packages.len();
+    let limit = request.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let packages = packages
+        .into_iter()
+        .skip(request.offset.unwrap_or_default())
+        .take(limit)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(PackageListResponse {
+        data: packages,
+        count,
+    }))
+}
@@ -1,25 +1,41 @@
 use crate::{
-    auth::UserId,
-    benv,
-    error::{Error, ErrorResponse},
-    search::update_version,
+    archive::list_files,
+    audit::Action,
+    auth::{
+        api_tokens::{random_hex, Permissions},
+        is_admin,
+        oidc::PublishGrant,
+        UserId,
+    },
+    endpoints::openapi::shared::{
+        Binary, Forbidden, PublishError, PublishHeaders, Queued, Republished, TooManyRequests,
+        Unauthorized,
+    },
+    error::{Error, ErrorResponse, ViolationsResponse},
+    index_queue::{prepare_changes, Job, JobStatus, Rejection},
+    scan::ScanAction,
     storage::StorageImpl,
+    validation::{check_docs, check_entries, check_exports},
     AppState,
 };
-use actix_web::{web, web::Bytes, HttpResponse, Responder};
+use actix_web::{
+    http::header::LOCATION, web, web::Bytes, HttpMessage, HttpRequest, HttpResponse, Responder,
+};
 use async_compression::Level;
+use base64::{engine::general_purpose::STANDARD, Engine};
 use convert_case::{Case, Casing};
 use fs_err::tokio as fs;
 use futures::{future::join_all, join};
-use git2::{Remote, Repository, Signature};
 use pesde::{
-    manifest::Manifest,
+    manifest::{target::TargetKind, Manifest},
     source::{
-        git_index::{read_file, root_tree, GitBasedSource},
-        pesde::{DocEntry, DocEntryKind, IndexFile, IndexFileEntry, ScopeInfo, SCOPE_INFO_FILE},
+        git_index::{root_tree, GitBasedSource},
+        pesde::{
+            signing::{self, SIGNATURE_HEADER},
+            ArchiveCompression, DocEntry, DocEntryKind, IndexFileEntry,
+        },
         specifiers::DependencySpecifiers,
         version_id::VersionId,
-        IGNORED_DIRS, IGNORED_FILES,
     },
     MANIFEST_FILE_NAME,
 };
@@ -28,34 +44,25 @@ use serde::Deserialize;
 use sha2::{Digest, Sha256};
 use std::{
     collections::{BTreeSet, HashMap},
-    io::{Cursor, Write},
+    io::Cursor,
+    path::Path,
 };
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-fn signature<'a>() -> Signature<'a> {
-    Signature::now(
-        &benv!(required "COMMITTER_GIT_NAME"),
-        &benv!(required "COMMITTER_GIT_EMAIL"),
-    )
-    .unwrap()
-}
-
-fn get_refspec(repo: &Repository, remote: &mut Remote) -> Result<String, git2::Error> {
-    let upstream_branch_buf = repo.branch_upstream_name(repo.head()?.name().unwrap())?;
-    let upstream_branch = upstream_branch_buf.as_str().unwrap();
+/// The HTTP header carrying the base64-encoded attestation document of a published archive, such
+/// as a SLSA provenance statement or a Sigstore bundle
+pub const ATTESTATION_HEADER: &str = "X-Pesde-Attestation";
 
-    let refspec_buf = remote
-        .refspecs()
-        .find(|r| r.direction() == git2::Direction::Fetch && r.dst_matches(upstream_branch))
+/// The path of a file in the docs directory, relative to it and with forward slashes. Its
+/// components were checked to be valid UTF-8 while walking the directory
+fn doc_path(package_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(package_dir.join("docs"))
         .unwrap()
-        .rtransform(upstream_branch)?;
-    let refspec = refspec_buf.as_str().unwrap();
-
-    Ok(refspec.to_string())
+        .to_string_lossy()
+        // ensure that the path is always using forward slashes
+        .replace('\\', "/")
 }
 
-const ADDITIONAL_FORBIDDEN_FILES: &[&str] = &["default.project.json"];
-
 #[derive(Debug, Deserialize, Default)]
 struct DocEntryInfo {
     #[serde(default)]
@@ -66,28 +73,130 @@ struct DocEntryInfo {
     collapsed: bool,
 }
 
+/// Publishes a package
+#[utoipa::path(
+    post,
+    path = "/v0/packages",
+    params(PublishHeaders),
+    request_body(content = inline(Binary), content_type = "application/octet-stream"),
+    security(("token" = [])),
+    responses(
+        (status = 200, response = Republished),
+        (status = 202, response = Queued),
+        (status = 400, description = "The request or archive is invalid", body = PublishError),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 409, description = "The version was already published"),
+        (status = 429, response = TooManyRequests),
+    )
+)]
 pub async fn publish_package(
     app_state: web::Data<AppState>,
+    request: HttpRequest,
     bytes: Bytes,
     user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
 ) -> Result<impl Responder, Error> {
-    let source = app_state.source.lock().await;
-    source.refresh(&app_state.project).await.map_err(Box::new)?;
-    let config = source.config(&app_state.project).await?;
+    publish_archive(&app_state, &request, bytes, *user_id, &permissions).await
+}
+
+/// Publishes an archive, whose signature and attestation are read from the request's headers
+pub async fn publish_archive(
+    app_state: &AppState,
+    request: &HttpRequest,
+    bytes: Bytes,
+    user_id: UserId,
+    permissions: &Permissions,
+) -> Result<HttpResponse, Error> {
+    let grant = request.extensions().get::<PublishGrant>().cloned();
+    // publishes acting on a grant are checked once the package, and so its publisher, is known
+    if grant.is_none() {
+        if let Some(ban) = app_state.moderation.ban_of(user_id).await {
+            return Ok(HttpResponse::Forbidden().json(ErrorResponse {
+                error: format!("you are banned from publishing: {}", ban.reason),
+            }));
+        }
+    }
+
+    // the index is only read to validate the publish, since the worker committing it to the index
+    // checks it again
+    let (config, index_url) = {
+        let source = app_state.read_source().await;
+        (
+            source.config(&app_state.project).await?,
+            source.repo_url().clone(),
+        )
+    };
+
+    let archive_signature = match request.headers().get(SIGNATURE_HEADER) {
+        Some(signature) => {
+            let Ok(signature) = signature.to_str() else {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                    error: "signature header is not valid UTF-8".to_string(),
+                }));
+            };
+
+            if let Err(e) = signing::verify(&bytes, signature, &config.trusted_keys) {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                    error: format!("invalid signature: {e}"),
+                }));
+            }
+
+            Some(signature.to_string())
+        }
+        None => None,
+    };
+
+    // checked before unpacking, so that nothing is written outside of the package's directory
+    let violations = check_entries(&bytes, &app_state.publish_limits).await?;
+    if !violations.is_empty() {
+        return Err(Error::InvalidPublish(violations));
+    }
+
+    let attestation = match request.headers().get(ATTESTATION_HEADER) {
+        Some(attestation) => {
+            let Some(attestation) = attestation
+                .to_str()
+                .ok()
+                .and_then(|attestation| STANDARD.decode(attestation.trim()).ok())
+            else {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                    error: "attestation header is not valid base64".to_string(),
+                }));
+            };
+
+            if serde_json::from_slice::<serde_json::Value>(&attestation).is_err() {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                    error: "attestation is not a JSON document".to_string(),
+                }));
+            }
+
+            Some(attestation)
+        }
+        None => None,
+    };
 
     let package_dir = tempfile::tempdir()?;
 
     {
-        let mut decoder = async_compression::tokio::bufread::GzipDecoder::new(Cursor::new(&bytes));
+        let compression = ArchiveCompression::detect(&bytes).ok_or_else(|| {
+            Error::InvalidArchive("archive is not compressed with gzip or zstd".into())
+        })?;
+        let mut decoder = compression.decoder(&bytes[..]);
         let mut archive = tokio_tar::Archive::new(&mut decoder);
 
         archive.unpack(package_dir.path()).await?;
     }
 
+    let files = list_files(&bytes).await?;
+    let file_list = serde_json::to_vec(&files)?;
+
     let mut manifest = None::<Manifest>;
     let mut readme = None::<Vec<u8>>;
     let mut docs = BTreeSet::new();
     let mut docs_pages = HashMap::new();
+    let mut docs_page_sizes = HashMap::new();
+    let mut docs_violations = vec![];
 
     let mut read_dir = fs::read_dir(package_dir.path()).await?;
     while let Some(entry) = read_dir.next_entry().await? {
@@ -98,12 +207,6 @@ pub async fn publish_package(
             .to_string();
 
         if entry.file_type().await?.is_dir() {
-            if IGNORED_DIRS.contains(&file_name.as_str()) {
-                return Err(Error::InvalidArchive(format!(
-                    "archive contains forbidden directory: {file_name}"
-                )));
-            }
-
             if file_name == "docs" {
                 let mut stack = vec![(
                     BTreeSet::new(),
@@ -137,7 +240,16 @@ pub async fn publish_package(
 
                         if file_name == "_category_.json" {
                             let info = fs::read_to_string(entry.path()).await?;
-                            let mut info: DocEntryInfo = serde_json::from_str(&info)?;
+                            let mut info: DocEntryInfo = match serde_json::from_str(&info) {
+                                Ok(info) => info,
+                                Err(e) => {
+                                    docs_violations.push(format!(
+                                        "`{}` isn't valid: {e}",
+                                        doc_path(package_dir.path(), &entry.path())
+                                    ));
+                                    continue;
+                                }
+                            };
                             let old_info = category_info.take();
                             info.label = info.label.or(old_info.and_then(|i| i.label));
                             *category_info = Some(info);
@@ -151,6 +263,7 @@ pub async fn publish_package(
                         let content = fs::read_to_string(entry.path()).await?;
                         let content = content.trim();
                         let hash = format!("{:x}", Sha256::digest(content.as_bytes()));
+                        docs_page_sizes.insert(hash.clone(), content.len() as u64);
 
                         let mut gz = async_compression::tokio::bufread::GzipEncoder::with_quality(
                             Cursor::new(content.as_bytes().to_vec()),
@@ -182,33 +295,29 @@ pub async fn publish_package(
                             .and_then(|l| l.strip_prefix("# "))
                             .map(|s| s.to_string());
 
-                        let info: DocEntryInfo =
-                            serde_yaml::from_str(&front_matter).map_err(|_| {
-                                Error::InvalidArchive(format!(
-                                    "doc {file_name}'s frontmatter isn't valid YAML"
-                                ))
-                            })?;
+                        let name = doc_path(package_dir.path(), &entry.path().with_extension(""));
+
+                        let info: DocEntryInfo = match serde_yaml::from_str(&front_matter) {
+                            Ok(info) => info,
+                            Err(_) => {
+                                docs_violations
+                                    .push(format!("doc `{name}`'s frontmatter isn't valid YAML"));
+                                continue;
+                            }
+                        };
 
-                        set.insert(DocEntry {
-                            label: info.label.or(h1).unwrap_or(file_name.to_case(Case::Title)),
+                        let label = info.label.or(h1).unwrap_or(file_name.to_case(Case::Title));
+                        // entries are ordered by their position and label, so a duplicate would
+                        // silently replace the other
+                        if !set.insert(DocEntry {
+                            label: label.clone(),
                             position: info.sidebar_position,
-                            kind: DocEntryKind::Page {
-                                name: entry
-                                    .path()
-                                    .strip_prefix(package_dir.path().join("docs"))
-                                    .unwrap()
-                                    .with_extension("")
-                                    .to_str()
-                                    .ok_or_else(|| {
-                                        Error::InvalidArchive(
-                                            "file name contains non UTF-8 characters".into(),
-                                        )
-                                    })?
-                                    // ensure that the path is always using forward slashes
-                                    .replace("\\", "/"),
-                                hash,
-                            },
-                        });
+                            kind: DocEntryKind::Page { name, hash },
+                        }) {
+                            docs_violations.push(format!(
+                                "multiple docs in the same category are labelled `{label}`"
+                            ));
+                        }
                     }
 
                     // should never be None
@@ -217,9 +326,10 @@ pub async fn publish_package(
 
                     if let Some((set, _, _)) = stack.last_mut() {
                         let category_info = category_info.unwrap_or_default();
+                        let label = category_info.label.unwrap();
 
-                        set.insert(DocEntry {
-                            label: category_info.label.unwrap(),
+                        let inserted = set.insert(DocEntry {
+                            label: label.clone(),
                             position: category_info.sidebar_position,
                             kind: DocEntryKind::Category {
                                 items: {
@@ -230,6 +340,11 @@ pub async fn publish_package(
                                 collapsed: category_info.collapsed,
                             },
                         });
+                        if !inserted {
+                            docs_violations.push(format!(
+                                "multiple docs in the same category are labelled `{label}`"
+                            ));
+                        }
                     }
                 }
             }
@@ -237,14 +352,6 @@ pub async fn publish_package(
             continue;
         }
 
-        if IGNORED_FILES.contains(&file_name.as_str())
-            || ADDITIONAL_FORBIDDEN_FILES.contains(&file_name.as_str())
-        {
-            return Err(Error::InvalidArchive(format!(
-                "archive contains forbidden file: {file_name}"
-            )));
-        }
-
         if file_name == MANIFEST_FILE_NAME {
             let content = fs::read_to_string(entry.path()).await?;
 
@@ -255,12 +362,6 @@ pub async fn publish_package(
             .filter(|(file, ext)| *file == "readme" && (*ext == "md" || *ext == "txt"))
             .is_some()
         {
-            if readme.is_some() {
-                return Err(Error::InvalidArchive(
-                    "archive contains multiple readme files".into(),
-                ));
-            }
-
             let mut file = fs::File::open(entry.path()).await?;
 
             let mut gz = async_compression::tokio::write::GzipEncoder::new(vec![]);
@@ -276,6 +377,27 @@ pub async fn publish_package(
         ));
     };
 
+    // `any` is used by the package version endpoint to request any target
+    if matches!(&manifest.target.kind(), TargetKind::Custom(name) if name == "any") {
+        return Err(Error::InvalidArchive(
+            "custom target name `any` is reserved".into(),
+        ));
+    }
+
+    let violations = check_exports(&manifest, &files);
+    if !violations.is_empty() {
+        return Err(Error::InvalidPublish(violations));
+    }
+
+    docs_violations.extend(check_docs(
+        &docs,
+        &docs_page_sizes,
+        &app_state.publish_limits,
+    ));
+    if !docs_violations.is_empty() {
+        return Err(Error::InvalidPublish(docs_violations));
+    }
+
     add_breadcrumb(sentry::Breadcrumb {
         category: Some("publish".into()),
         message: Some(format!(
@@ -290,7 +412,7 @@ pub async fn publish_package(
         ..Default::default()
     });
 
-    {
+    let dependencies = {
         let dependencies = manifest.all_dependencies().map_err(|e| {
             Error::InvalidArchive(format!("manifest has invalid dependencies: {e}"))
         })?;
@@ -304,7 +426,7 @@ pub async fn publish_package(
                         .filter(|index| match gix::Url::try_from(*index) {
                             Ok(url) => config
                                 .other_registries_allowed
-                                .is_allowed_or_same(source.repo_url().clone(), url),
+                                .is_allowed_or_same(index_url.clone(), url),
                             Err(_) => false,
                         })
                         .is_none()
@@ -342,164 +464,248 @@ pub async fn publish_package(
                         "non-transformed workspace dependency".into(),
                     ));
                 }
+                DependencySpecifiers::Local(_) => {
+                    return Err(Error::InvalidArchive(
+                        "local registry dependencies are not allowed".into(),
+                    ));
+                }
+                DependencySpecifiers::Custom(_) => {
+                    return Err(Error::InvalidArchive(
+                        "custom source dependencies are not allowed".into(),
+                    ));
+                }
             }
         }
 
-        let repo = Repository::open_bare(source.path(&app_state.project))?;
-        let gix_repo = gix::open(repo.path())?;
+        if !permissions.can_publish(&manifest.name) {
+            return Ok(HttpResponse::Forbidden().finish());
+        }
 
-        let gix_tree = root_tree(&gix_repo)?;
+        if let Some(removal) = app_state
+            .moderation
+            .removal_of(&manifest.name, Some(&manifest.version))
+            .await
+        {
+            return Ok(HttpResponse::Forbidden().json(ErrorResponse {
+                error: format!(
+                    "this version was removed by the registry's administrators, and can't be published again: {}",
+                    removal.reason
+                ),
+            }));
+        }
 
-        let (scope, name) = manifest.name.as_str();
-        let mut oids = vec![];
+        dependencies
+    };
 
-        match read_file(&gix_tree, [scope, SCOPE_INFO_FILE])? {
-            Some(info) => {
-                let info: ScopeInfo = toml::de::from_str(&info)?;
-                if !info.owners.contains(&user_id.0) {
-                    return Ok(HttpResponse::Forbidden().finish());
-                }
-            }
-            None => {
-                let scope_info = toml::to_string(&ScopeInfo {
-                    owners: BTreeSet::from([user_id.0]),
-                })?;
-
-                let mut blob_writer = repo.blob_writer(None)?;
-                blob_writer.write_all(scope_info.as_bytes())?;
-                oids.push((SCOPE_INFO_FILE, blob_writer.commit()?));
-            }
-        };
-
-        let mut entries: IndexFile =
-            toml::de::from_str(&read_file(&gix_tree, [scope, name])?.unwrap_or_default())?;
-
-        let new_entry = IndexFileEntry {
-            target: manifest.target.clone(),
-            published_at: chrono::Utc::now(),
-            description: manifest.description.clone(),
-            license: manifest.license.clone(),
-            authors: manifest.authors.clone(),
-            repository: manifest.repository.clone(),
-            docs,
-
-            dependencies,
-        };
-
-        let this_version = entries
-            .keys()
-            .find(|v_id| *v_id.version() == manifest.version);
-        if let Some(this_version) = this_version {
-            let other_entry = entries.get(this_version).unwrap();
-
-            // description cannot be different - which one to render in the "Recently published" list?
-            // the others cannot be different because what to return from the versions endpoint?
-            if other_entry.description != new_entry.description
-                || other_entry.license != new_entry.license
-                || other_entry.authors != new_entry.authors
-                || other_entry.repository != new_entry.repository
-            {
-                return Ok(HttpResponse::BadRequest().json(ErrorResponse {
-                    error: "same version with different description or license already exists"
-                        .to_string(),
+    // the package's trusted publishers may have been registered by different users, and its
+    // publishes are attributed to the one who registered the workflow for it
+    let user_id = match grant {
+        Some(grant) => {
+            let Some(user_id) = grant.publisher_of(&manifest.name) else {
+                return Ok(HttpResponse::Forbidden().finish());
+            };
+            if let Some(ban) = app_state.moderation.ban_of(user_id).await {
+                return Ok(HttpResponse::Forbidden().json(ErrorResponse {
+                    error: format!("the publisher of this package is banned: {}", ban.reason),
                 }));
             }
-        }
 
-        if entries
-            .insert(
-                VersionId::new(manifest.version.clone(), manifest.target.kind()),
-                new_entry.clone(),
-            )
-            .is_some()
-        {
-            return Ok(HttpResponse::Conflict().finish());
+            user_id
         }
+        None => user_id,
+    };
 
-        let mut remote = repo.find_remote("origin")?;
-        let refspec = get_refspec(&repo, &mut remote)?;
+    let version_id = VersionId::new(manifest.version.clone(), manifest.target.kind());
+    let mut entry = IndexFileEntry {
+        target: manifest.target.clone(),
+        published_at: chrono::Utc::now(),
+        description: manifest.description.clone(),
+        license: manifest.license.clone(),
+        authors: manifest.authors.clone(),
+        repository: manifest.repository.clone(),
+        maintainers: manifest.maintainers.clone(),
+        funding: manifest.funding.clone(),
+        docs,
+        metadata: manifest.metadata.clone(),
+
+        dependencies,
+
+        checksum: Some(format!("{:x}", Sha256::digest(&bytes))),
+        signature: archive_signature,
+        yanked: false,
+        deprecated: None,
+    };
+    let may_create_scope =
+        !app_state.restrict_scope_creation || is_admin(app_state, user_id, permissions);
 
-        let reference = repo.find_reference(&refspec)?;
+    // reserved before checking the index, so that a version can't be published twice at once and
+    // overwrite the files of the other publish
+    let Some(reservation) = app_state.index_queue.reserve(&manifest.name, &version_id) else {
+        return Ok(HttpResponse::Conflict().finish());
+    };
 
+    // publishing a version again with the same archive stores its files again, completing the
+    // publish if storing them was interrupted
+    let republished = {
+        let source = app_state.read_source().await;
+        let repo = gix::open(source.path(&app_state.project))?;
+        let gix_tree = root_tree(&repo)?;
+
+        match prepare_changes(
+            app_state,
+            &gix_tree,
+            user_id,
+            may_create_scope,
+            &manifest.name,
+            &version_id,
+            &entry,
+        )
+        .await?
         {
-            let index_content = toml::to_string(&entries)?;
-            let mut blob_writer = repo.blob_writer(None)?;
-            blob_writer.write_all(index_content.as_bytes())?;
-            oids.push((name, blob_writer.commit()?));
-        }
-
-        let old_root_tree = reference.peel_to_tree()?;
-        let old_scope_tree = match old_root_tree.get_name(scope) {
-            Some(entry) => Some(repo.find_tree(entry.id())?),
-            None => None,
-        };
-
-        let mut scope_tree = repo.treebuilder(old_scope_tree.as_ref())?;
-        for (file, oid) in oids {
-            scope_tree.insert(file, oid, 0o100644)?;
+            Ok(_) => false,
+            Err(Rejection::Published) => true,
+            Err(rejection) => {
+                return Ok(match rejection {
+                    Rejection::Forbidden(None) => HttpResponse::Forbidden().finish(),
+                    Rejection::Forbidden(Some(error)) => {
+                        HttpResponse::Forbidden().json(ErrorResponse { error })
+                    }
+                    Rejection::BadRequest(error) => {
+                        HttpResponse::BadRequest().json(ErrorResponse { error })
+                    }
+                    Rejection::Conflict | Rejection::Published => HttpResponse::Conflict().finish(),
+                });
+            }
         }
+    };
 
-        let scope_tree_id = scope_tree.write()?;
-        let mut root_tree = repo.treebuilder(Some(&repo.find_tree(old_root_tree.id())?))?;
-        root_tree.insert(scope, scope_tree_id, 0o040000)?;
-
-        let tree_oid = root_tree.write()?;
-
-        repo.commit(
-            Some("HEAD"),
-            &signature(),
-            &signature(),
-            &format!(
-                "add {}@{} {}",
-                manifest.name, manifest.version, manifest.target
-            ),
-            &repo.find_tree(tree_oid)?,
-            &[&reference.peel_to_commit()?],
-        )?;
-
-        let mut push_options = git2::PushOptions::new();
-        let mut remote_callbacks = git2::RemoteCallbacks::new();
-
-        let git_creds = app_state.project.auth_config().git_credentials().unwrap();
-        remote_callbacks.credentials(|_, _, _| {
-            git2::Cred::userpass_plaintext(&git_creds.username, &git_creds.password)
-        });
-
-        push_options.remote_callbacks(remote_callbacks);
+    // scanned once the publish is known to be allowed, since scanners may be slow. Versions
+    // published again were scanned the first time
+    let findings = if republished {
+        vec![]
+    } else {
+        app_state.scanners.scan(&bytes, package_dir.path()).await?
+    };
+    let quarantined = !findings.is_empty() && app_state.scanners.action == ScanAction::Quarantine;
+    if !findings.is_empty() {
+        let violations = findings.iter().map(ToString::to_string).collect();
 
-        remote.push(&[refspec], Some(&mut push_options))?;
+        app_state
+            .audit_log
+            .record(
+                request,
+                user_id,
+                Action::Scan {
+                    package: manifest.name.clone(),
+                    version: manifest.version.clone(),
+                    target: manifest.target.kind(),
+                    outcome: app_state.scanners.action,
+                    findings,
+                },
+            )
+            .await;
 
-        update_version(&app_state, &manifest.name, new_entry);
+        match app_state.scanners.action {
+            ScanAction::Reject => {
+                return Ok(HttpResponse::BadRequest().json(ViolationsResponse {
+                    error: "archive was rejected by the registry's scanners".to_string(),
+                    violations,
+                }));
+            }
+            ScanAction::Quarantine => {
+                tracing::warn!(
+                    "quarantining {}@{} {}: {}",
+                    manifest.name,
+                    manifest.version,
+                    manifest.target,
+                    violations.join(", ")
+                );
+                entry.yanked = true;
+            }
+        }
     }
 
-    let version_id = VersionId::new(manifest.version.clone(), manifest.target.kind());
-
-    let (a, b, c) = join!(
-        app_state
-            .storage
-            .store_package(&manifest.name, &version_id, bytes.to_vec()),
+    // the files are stored before the version is added to the index, so that the index never
+    // references missing files. If the job fails, they're left to be garbage collected
+    let storage = app_state.storage.get();
+    let (a, b, c, d, e) = join!(
+        storage.store_package(&manifest.name, &version_id, bytes.to_vec()),
+        storage.store_file_list(&manifest.name, &version_id, file_list),
         join_all(
             docs_pages
                 .into_iter()
-                .map(|(hash, content)| app_state.storage.store_doc(hash, content)),
+                .map(|(hash, content)| storage.store_doc(hash, content)),
         ),
         async {
             if let Some(readme) = readme {
-                app_state
-                    .storage
+                storage
                     .store_readme(&manifest.name, &version_id, readme)
                     .await
             } else {
                 Ok(())
             }
+        },
+        async {
+            if let Some(attestation) = attestation {
+                storage
+                    .store_attestation(&manifest.name, &version_id, attestation)
+                    .await
+            } else {
+                Ok(())
+            }
         }
     );
     a?;
-    b.into_iter().collect::<Result<(), _>>()?;
-    c?;
+    b?;
+    c.into_iter().collect::<Result<(), _>>()?;
+    d?;
+    e?;
+
+    if republished {
+        tracing::info!(
+            "stored the files of {}@{} {} again",
+            manifest.name,
+            manifest.version,
+            manifest.target
+        );
+
+        return Ok(HttpResponse::Ok().body(format!(
+            "{}@{} {} was already published, its files were stored again",
+            manifest.name, manifest.version, manifest.target
+        )));
+    }
 
-    Ok(HttpResponse::Ok().body(format!(
-        "published {}@{} {}",
-        manifest.name, manifest.version, manifest.target
-    )))
+    let job = Job {
+        id: random_hex::<8>(),
+        status: JobStatus::Queued,
+        error: None,
+        user_id: user_id.0,
+        ip: app_state
+            .rate_limits
+            .get()
+            .client_ip(request)
+            .map(|ip| ip.to_string()),
+        may_create_scope,
+        package: manifest.name.clone(),
+        version_id,
+        entry,
+        created_at: chrono::Utc::now(),
+        finished_at: None,
+    };
+    let status_url = format!("{}/v0/jobs/{}", config.api(), job.id);
+    app_state.index_queue.enqueue(reservation, job).await?;
+
+    Ok(HttpResponse::Accepted()
+        .insert_header((LOCATION, status_url.clone()))
+        .body(format!(
+            "queued {}@{} {} for publishing{}, its status is available at {status_url}",
+            manifest.name,
+            manifest.version,
+            manifest.target,
+            if quarantined {
+                " yanked, as the registry's scanners found issues in it which its administrators will review"
+            } else {
+                ""
+            }
+        )))
 }
@@ -0,0 +1,127 @@
+use actix_web::{http::header::CACHE_CONTROL, web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    downloads::{Period, Ranking, FLUSH_INTERVAL},
+    endpoints::openapi::shared::BadRequest,
+    error::{Error, ErrorResponse},
+    metadata_cache::read_local_package,
+    package::PackageResponse,
+    AppState,
+};
+
+/// The amount of packages returned if the request doesn't specify a limit
+const DEFAULT_LIMIT: usize = 10;
+/// The maximum amount of packages returned at once
+const MAX_LIMIT: usize = 100;
+/// The period trending packages are ranked over if the request doesn't specify one
+const DEFAULT_TRENDING_PERIOD: Period = Period::Days(7);
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Request {
+    /// The span of time to rank the packages over, as a number followed by `d`, `w`, `m` or `y`,
+    /// such as `30d`
+    #[serde(default)]
+    period: Option<String>,
+    #[serde(default)]
+    #[param(minimum = 1, maximum = 100, default = 10)]
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct RankedPackageResponse {
+    #[serde(flatten)]
+    package: PackageResponse,
+    /// The downloads of the package over the period
+    downloads: u64,
+    /// The downloads of the package over the period before, for trending packages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous_downloads: Option<u64>,
+}
+
+async fn get_ranking(
+    app_state: &AppState,
+    request: Request,
+    ranking: impl FnOnce(Option<Period>) -> Ranking,
+) -> Result<HttpResponse, Error> {
+    let period = match request.period.as_deref().map(str::parse::<Period>) {
+        Some(Ok(period)) => Some(period),
+        Some(Err(error)) => return Ok(HttpResponse::BadRequest().json(ErrorResponse { error })),
+        None => None,
+    };
+    let limit = request.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let ranked = app_state.downloads.ranking(ranking(period)).await;
+
+    let mut packages = vec![];
+    for ranked in ranked.iter() {
+        if packages.len() == limit {
+            break;
+        }
+
+        // packages which were removed, or whose every version is yanked, aren't ranked
+        let Some(versions) = read_local_package(app_state, &ranked.name).await? else {
+            continue;
+        };
+        if versions.values().all(|entry| entry.yanked) {
+            continue;
+        }
+
+        if let Some(package) = PackageResponse::latest(&ranked.name, &versions) {
+            packages.push(RankedPackageResponse {
+                package,
+                downloads: ranked.downloads,
+                previous_downloads: ranked.previous_downloads,
+            });
+        }
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header((
+            CACHE_CONTROL,
+            format!("public, max-age={}", FLUSH_INTERVAL.as_secs()),
+        ))
+        .json(packages))
+}
+
+/// Lists the most downloaded packages, over the requested period or since they were published
+#[utoipa::path(
+    get,
+    path = "/v0/popular",
+    params(Request),
+    responses(
+        (status = 200, description = "The packages, most downloaded first", body = Vec<RankedPackageResponse>),
+        (status = 400, response = BadRequest),
+    )
+)]
+pub async fn get_popular(
+    app_state: web::Data<AppState>,
+    request: web::Query<Request>,
+) -> Result<impl Responder, Error> {
+    get_ranking(&app_state, request.into_inner(), Ranking::Popular).await
+}
+
+/// Lists the packages whose downloads grew the most over the requested period, compared to the
+/// period before it
+///
+/// The period defaults to `7d`
+#[utoipa::path(
+    get,
+    path = "/v0/trending",
+    params(Request),
+    responses(
+        (status = 200, description = "The packages, most grown first", body = Vec<RankedPackageResponse>),
+        (status = 400, response = BadRequest),
+    )
+)]
+pub async fn get_trending(
+    app_state: web::Data<AppState>,
+    request: web::Query<Request>,
+) -> Result<impl Responder, Error> {
+    get_ranking(&app_state, request.into_inner(), |period| {
+        Ranking::Trending(period.unwrap_or(DEFAULT_TRENDING_PERIOD))
+    })
+    .await
+}
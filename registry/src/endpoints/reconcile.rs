@@ -0,0 +1,79 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{
+    auth::{api_tokens::Permissions, is_admin, UserId},
+    endpoints::openapi::shared::{Forbidden, Unauthorized},
+    error::Error,
+    reconcile::ReconcileReport,
+    AppState,
+};
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Request {
+    /// Whether to roll back the partially published versions which are found
+    #[serde(default)]
+    fix: bool,
+}
+
+/// Cross-checks the index and storage for partially published versions
+///
+/// Finds the versions in the index whose archive isn't stored, and the stored versions which aren't
+/// in the index. The latter are only found with the filesystem storage
+#[utoipa::path(
+    post,
+    path = "/v0/admin/reconcile",
+    params(Request),
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The report", body = ReconcileReport),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+    )
+)]
+pub async fn run_reconcile(
+    app_state: web::Data<AppState>,
+    request: web::Query<Request>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let report = app_state
+        .reconciler
+        .reconcile(&app_state, request.fix)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Gets the report of the last cross-check of the index and storage
+#[utoipa::path(
+    get,
+    path = "/v0/admin/reconcile",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The report", body = ReconcileReport),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, description = "The index and storage weren't cross-checked yet"),
+    )
+)]
+pub async fn get_reconcile_report(
+    app_state: web::Data<AppState>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    Ok(match app_state.reconciler.last_report() {
+        Some(report) => HttpResponse::Ok().json(report),
+        None => HttpResponse::NotFound().finish(),
+    })
+}
@@ -0,0 +1,60 @@
+use actix_web::{web, HttpResponse, Responder};
+
+use crate::{
+    auth::{api_tokens::Permissions, is_admin, UserId},
+    endpoints::openapi::shared::{Forbidden, Unauthorized},
+    error::Error,
+    search::{reindex, ReindexProgress},
+    AppState,
+};
+
+/// Rebuilds the search index from the index in the background
+#[utoipa::path(
+    post,
+    path = "/v0/admin/reindex",
+    security(("token" = [])),
+    responses(
+        (status = 202, description = "The rebuild was started", body = ReindexProgress),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 409, description = "A rebuild is already in progress", body = ReindexProgress),
+    )
+)]
+pub async fn start_reindex(
+    app_state: web::Data<AppState>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    if !reindex(app_state.clone()) {
+        return Ok(HttpResponse::Conflict().json(app_state.search_indexer.progress()));
+    }
+
+    Ok(HttpResponse::Accepted().json(app_state.search_indexer.progress()))
+}
+
+/// Gets the progress of the last rebuild of the search index
+#[utoipa::path(
+    get,
+    path = "/v0/admin/reindex",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The progress", body = ReindexProgress),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+    )
+)]
+pub async fn get_reindex_progress(
+    app_state: web::Data<AppState>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    Ok(HttpResponse::Ok().json(app_state.search_indexer.progress()))
+}
@@ -0,0 +1,39 @@
+use actix_web::{web, HttpResponse, Responder};
+
+use crate::{
+    auth::{api_tokens::Permissions, is_admin, UserId},
+    endpoints::openapi::shared::{BadRequest, Forbidden, Unauthorized},
+    error::{Error, ErrorResponse},
+    reload::{reload, ReloadReport},
+    AppState,
+};
+
+/// Reloads the storage and rate limit settings
+///
+/// Reads the configuration file again, and replaces the storage and rate limits without dropping
+/// connections. Environment variables keep the values they had when the registry started
+#[utoipa::path(
+    post,
+    path = "/v0/admin/reload",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The settings in effect", body = ReloadReport),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+    )
+)]
+pub async fn reload_config(
+    app_state: web::Data<AppState>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    match reload(&app_state) {
+        Ok(report) => Ok(HttpResponse::Ok().json(report)),
+        Err(error) => Ok(HttpResponse::BadRequest().json(ErrorResponse { error })),
+    }
+}
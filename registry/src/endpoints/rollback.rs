@@ -0,0 +1,95 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    audit::Action,
+    auth::{api_tokens::Permissions, is_admin, UserId},
+    endpoints::openapi::shared::{Forbidden, NotFound, Target, Unauthorized},
+    error::{Error, ErrorResponse},
+    reconcile::{roll_back, PartialState, RollbackRejection},
+    AppState,
+};
+use pesde::{manifest::target::TargetKind, names::PackageName, source::version_id::VersionId};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RollbackRequest {
+    #[schema(value_type = String)]
+    package: PackageName,
+    #[schema(value_type = String)]
+    version: Version,
+    #[schema(value_type = Target)]
+    target: TargetKind,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct RollbackResponse {
+    /// What was left of the version
+    state: PartialState,
+}
+
+/// Rolls back a version which was only partially published, so that it's either in the index
+/// without its archive being stored, or the other way around. Unlike a removal, the version can
+/// be published again afterwards
+#[utoipa::path(
+    post,
+    path = "/v0/admin/rollback",
+    request_body = RollbackRequest,
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The version was rolled back", body = RollbackResponse),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+        (status = 409, description = "The version is being published, or was fully published", body = ErrorResponse),
+    )
+)]
+pub async fn rollback_publish(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    request: web::Json<RollbackRequest>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let RollbackRequest {
+        package,
+        version,
+        target,
+    } = request.into_inner();
+    let version_id = VersionId::new(version.clone(), target.clone());
+
+    let state = match roll_back(&app_state, &package, &version_id).await? {
+        Ok(state) => state,
+        Err(RollbackRejection::BeingPublished) => {
+            return Ok(HttpResponse::Conflict().json(ErrorResponse {
+                error: "version is being published".to_string(),
+            }));
+        }
+        Err(RollbackRejection::FullyPublished) => {
+            return Ok(HttpResponse::Conflict().json(ErrorResponse {
+                error: "version was fully published, it can only be removed".to_string(),
+            }));
+        }
+        Err(RollbackRejection::NotFound) => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    app_state
+        .audit_log
+        .record(
+            &http_request,
+            *user_id,
+            Action::Rollback {
+                package,
+                version,
+                target,
+            },
+        )
+        .await;
+
+    Ok(HttpResponse::Ok().json(RollbackResponse { state }))
+}
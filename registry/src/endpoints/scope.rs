@@ -0,0 +1,50 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    endpoints::{openapi::shared::NotFound, owners::is_valid_scope},
+    error::Error,
+    metadata_cache::read_scope_packages,
+    package::PackageResponse,
+    AppState,
+};
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ScopeResponse {
+    scope: String,
+    /// The packages of the scope, summarized by their latest versions and sorted by name
+    packages: Vec<PackageResponse>,
+}
+
+/// Gets the packages of a scope, summarized by their latest versions
+#[utoipa::path(
+    get,
+    path = "/v0/scopes/{scope}",
+    params(("scope" = String, Path)),
+    responses(
+        (status = 200, description = "The packages of the scope, sorted by name", body = ScopeResponse),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn get_scope(
+    app_state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<impl Responder, Error> {
+    let scope = path.into_inner();
+    if !is_valid_scope(&scope) {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let Some(packages) = read_scope_packages(&app_state, &scope).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let mut packages = packages
+        .into_iter()
+        .filter_map(|(name, versions)| PackageResponse::latest(&name, &versions))
+        .collect::<Vec<_>>();
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(HttpResponse::Ok().json(ScopeResponse { scope, packages }))
+}
@@ -1,104 +1,289 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Instant,
+};
 
 use actix_web::{web, HttpResponse, Responder};
-use serde::Deserialize;
-use tantivy::{collector::Count, query::AllQuery, schema::Value, DateTime, Order};
-
-use crate::{error::Error, package::PackageResponse, AppState};
-use pesde::{
-    names::PackageName,
-    source::{
-        git_index::{read_file, root_tree, GitBasedSource},
-        pesde::IndexFile,
-    },
+use serde::{Deserialize, Serialize};
+use tantivy::{
+    collector::{Count, DocSetCollector, FacetCollector, TopDocs},
+    query::{AllQuery, BooleanQuery, ConstScoreQuery, Occur, Query, TermQuery},
+    schema::{Facet, IndexRecordOption, Value},
+    DateTime, DocAddress, DocId, Order, Score, SegmentReader, Term,
+};
+
+use crate::{
+    endpoints::openapi::shared::{BadRequest, TooManyRequests},
+    error::Error,
+    metadata_cache::read_package,
+    metrics::METRICS,
+    package::PackageResponse,
+    search::{parse_query, ranking_factor, text_query, FACET_CATEGORIES},
+    AppState,
 };
+use pesde::names::PackageName;
+use utoipa::{IntoParams, ToSchema};
+
+/// The amount of results returned if the request doesn't specify a limit
+const DEFAULT_LIMIT: usize = 50;
+/// The maximum amount of results returned at once
+const MAX_LIMIT: usize = 100;
+
+#[derive(Debug, Clone, Copy, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Sort {
+    Relevance,
+    Published,
+    Name,
+}
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct Request {
-    #[serde(default)]
+    /// The text to search for, in package names, descriptions and READMEs. Words of three or more
+    /// characters also match words in names and descriptions within one or two typos of them, and
+    /// quoted phrases are matched as a whole. It can contain `target:`, `license:` and `scope:`
+    /// qualifiers, such as `http target:lune`, which filter the results like the parameters of the
+    /// same names. Their values can be quoted to contain spaces
+    #[serde(default, alias = "q")]
     query: Option<String>,
     #[serde(default)]
     offset: Option<usize>,
+    #[serde(default)]
+    #[param(minimum = 1, maximum = 100, default = 50)]
+    limit: Option<usize>,
+    /// Relevance blends how well packages match the query with their downloads over the last 30
+    /// days and how recently they were published. Defaults to relevance when searching, and to the
+    /// most recently published packages otherwise
+    #[serde(default)]
+    sort: Option<Sort>,
+    #[serde(default)]
+    target: Option<String>,
+    #[serde(default)]
+    license: Option<String>,
+    #[serde(default)]
+    scope: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SearchResponse {
+    data: Vec<PackageResponse>,
+    /// The amount of packages matching the search
+    count: usize,
+    /// The amount of matching packages for each value of each filter
+    #[schema(value_type = BTreeMap<String, BTreeMap<String, u64>>)]
+    facets: BTreeMap<&'static str, BTreeMap<String, u64>>,
 }
 
+/// Searches packages
+#[utoipa::path(
+    get,
+    path = "/v0/search",
+    params(Request),
+    responses(
+        (status = 200, description = "The matching packages", body = SearchResponse),
+        (status = 400, response = BadRequest),
+        (status = 429, response = TooManyRequests),
+    )
+)]
 pub async fn search_packages(
     app_state: web::Data<AppState>,
     request: web::Query<Request>,
 ) -> Result<impl Responder, Error> {
+    let start = Instant::now();
+    // the query doesn't await anything, so the span can be entered until it's done
+    let query_span = tracing::info_span!("search_query").entered();
     let searcher = app_state.search_reader.searcher();
     let schema = searcher.schema();
 
     let id = schema.get_field("id").unwrap();
 
-    let query = request.query.as_deref().unwrap_or_default().trim();
+    let parsed = parse_query(request.query.as_deref().unwrap_or_default());
+    let query = parsed.text.as_str();
+
+    let sort = request.sort.unwrap_or(if query.is_empty() {
+        Sort::Published
+    } else {
+        Sort::Relevance
+    });
 
     let query = if query.is_empty() {
         Box::new(AllQuery)
     } else {
-        app_state.query_parser.parse_query(query)?
+        text_query(searcher.index(), &app_state.query_parser, query)?
     };
 
-    let (count, top_docs) = searcher
-        .search(
-            &query,
-            &(
-                Count,
-                tantivy::collector::TopDocs::with_limit(50)
-                    .and_offset(request.offset.unwrap_or_default())
-                    .order_by_fast_field::<DateTime>("published_at", Order::Desc),
-            ),
+    // the values of each category, from both the parameters and the query's qualifiers
+    let mut filter_values = BTreeMap::<&str, Vec<&str>>::new();
+    for (category, value) in FACET_CATEGORIES
+        .into_iter()
+        .zip([&request.target, &request.license, &request.scope])
+        .filter_map(|(category, value)| value.as_deref().map(|value| (category, value)))
+        .chain(
+            parsed
+                .filters
+                .iter()
+                .map(|(category, value)| (*category, value.as_str())),
         )
-        .unwrap();
+    {
+        filter_values.entry(category).or_default().push(value);
+    }
+
+    let facets = schema.get_field("facets").unwrap();
+    let filters = filter_values
+        .into_iter()
+        .map(|(category, values)| {
+            // packages match a category if they have any of its values
+            let query = BooleanQuery::new(
+                values
+                    .into_iter()
+                    .map(|value| {
+                        let term = Term::from_facet(facets, &Facet::from_path([category, value]));
+                        (
+                            Occur::Should,
+                            Box::new(TermQuery::new(term, IndexRecordOption::Basic))
+                                as Box<dyn Query>,
+                        )
+                    })
+                    .collect(),
+            );
+            // filters only narrow down the results, without affecting their relevance
+            let query = ConstScoreQuery::new(Box::new(query), 0.0);
 
-    let source = app_state.source.lock().await;
-    let repo = gix::open(source.path(&app_state.project))?;
-    let tree = root_tree(&repo)?;
+            (Occur::Must, Box::new(query) as Box<dyn Query>)
+        })
+        .collect::<Vec<_>>();
+
+    let query = if filters.is_empty() {
+        query
+    } else {
+        Box::new(BooleanQuery::new(
+            std::iter::once((Occur::Must, query))
+                .chain(filters)
+                .collect(),
+        ))
+    };
 
-    let top_docs = top_docs
+    let mut facet_collector = FacetCollector::for_field("facets");
+    for category in FACET_CATEGORIES {
+        facet_collector.add_facet(Facet::from_path([category]));
+    }
+    let facet_counts = searcher.search(&query, &facet_collector).unwrap();
+    let facet_counts = FACET_CATEGORIES
         .into_iter()
-        .map(|(_, doc_address)| {
-            let doc = searcher.doc::<HashMap<_, _>>(doc_address).unwrap();
-
-            let id = doc
-                .get(&id)
-                .unwrap()
-                .as_str()
-                .unwrap()
-                .parse::<PackageName>()
-                .unwrap();
-            let (scope, name) = id.as_str();
+        .map(|category| {
+            let counts = facet_counts
+                .get(Facet::from_path([category]))
+                .map(|(facet, count)| (facet.to_path().last().unwrap().to_string(), count))
+                .collect::<BTreeMap<_, _>>();
 
-            let versions: IndexFile =
-                toml::de::from_str(&read_file(&tree, [scope, name]).unwrap().unwrap()).unwrap();
+            (category, counts)
+        })
+        .collect::<BTreeMap<_, _>>();
 
-            let (latest_version, entry) = versions
-                .iter()
-                .max_by_key(|(v_id, _)| v_id.version())
+    let limit = request.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    // there can't be more results than documents, and the collectors allocate for the offset
+    let offset = request
+        .offset
+        .unwrap_or_default()
+        .min(searcher.num_docs() as usize);
+    let top_docs = TopDocs::with_limit(limit).and_offset(offset);
+
+    let (count, top_docs): (usize, Vec<DocAddress>) = match sort {
+        Sort::Relevance => {
+            let now = chrono::Utc::now().timestamp();
+            let top_docs = top_docs.tweak_score(move |segment: &SegmentReader| {
+                let downloads = segment.fast_fields().u64("downloads").unwrap();
+                let published_at = segment.fast_fields().date("published_at").unwrap();
+
+                move |doc: DocId, score: Score| {
+                    let days_since_published = published_at
+                        .first(doc)
+                        .map(|date| (now - date.into_timestamp_secs()) as f32 / 86_400.0)
+                        .unwrap_or_default();
+
+                    score
+                        * ranking_factor(
+                            downloads.first(doc).unwrap_or_default(),
+                            days_since_published,
+                        )
+                }
+            });
+            let (count, top_docs) = searcher.search(&query, &(Count, top_docs)).unwrap();
+            (
+                count,
+                top_docs.into_iter().map(|(_, address)| address).collect(),
+            )
+        }
+        Sort::Published => {
+            let (count, top_docs) = searcher
+                .search(
+                    &query,
+                    &(
+                        Count,
+                        top_docs.order_by_fast_field::<DateTime>("published_at", Order::Desc),
+                    ),
+                )
                 .unwrap();
+            (
+                count,
+                top_docs.into_iter().map(|(_, address)| address).collect(),
+            )
+        }
+        // tantivy can't order by text fields, so all results are sorted by their stored names,
+        // which is fine for the amount of packages a registry has
+        Sort::Name => {
+            let addresses = searcher.search(&query, &DocSetCollector).unwrap();
+            let mut names = addresses
+                .into_iter()
+                .map(|address| {
+                    let doc = searcher.doc::<HashMap<_, _>>(address).unwrap();
+                    let name = doc.get(&id).unwrap().as_str().unwrap().to_string();
 
-            PackageResponse {
-                name: id.to_string(),
-                version: latest_version.version().to_string(),
-                targets: versions
-                    .iter()
-                    .filter(|(v_id, _)| v_id.version() == latest_version.version())
-                    .map(|(_, entry)| (&entry.target).into())
+                    (name, address)
+                })
+                .collect::<Vec<_>>();
+            names.sort();
+
+            (
+                names.len(),
+                names
+                    .into_iter()
+                    .skip(offset)
+                    .take(limit)
+                    .map(|(_, address)| address)
                     .collect(),
-                description: entry.description.clone().unwrap_or_default(),
-                published_at: versions
-                    .values()
-                    .max_by_key(|entry| entry.published_at)
-                    .unwrap()
-                    .published_at,
-                license: entry.license.clone().unwrap_or_default(),
-                authors: entry.authors.clone(),
-                repository: entry.repository.clone().map(|url| url.to_string()),
-            }
-        })
-        .collect::<Vec<_>>();
+            )
+        }
+    };
+
+    drop(query_span);
+
+    let mut packages = Vec::with_capacity(top_docs.len());
+    for doc_address in top_docs {
+        let doc = searcher.doc::<HashMap<_, _>>(doc_address).unwrap();
+
+        let id = doc
+            .get(&id)
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .parse::<PackageName>()
+            .unwrap();
+
+        // the package may have been removed since the search index was last updated
+        let Some(versions) = read_package(&app_state, &id).await? else {
+            continue;
+        };
+
+        packages.extend(PackageResponse::latest(&id, &versions));
+    }
+
+    METRICS.observe_search(start.elapsed());
 
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "data": top_docs,
-        "count": count,
-    })))
+    Ok(HttpResponse::Ok().json(SearchResponse {
+        data: packages,
+        count,
+        facets: facet_counts,
+    }))
 }
@@ -0,0 +1,204 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    audit::Action,
+    auth::{
+        api_tokens::{ApiToken, Permissions, TokenScope},
+        UserId,
+    },
+    endpoints::openapi::shared::{BadRequest, Forbidden, NotFound, Unauthorized},
+    error::{Error, ErrorResponse},
+    AppState,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateTokenRequest {
+    name: String,
+    /// `read`, `publish:<PATTERN>`, `yank` or `admin`
+    #[schema(value_type = Vec<String>)]
+    scopes: Vec<TokenScope>,
+    /// How many days the token is valid for, forever if not given
+    #[serde(default)]
+    #[schema(minimum = 1)]
+    expires_in_days: Option<u32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = Token)]
+pub struct TokenResponse {
+    id: String,
+    name: String,
+    /// `read`, `publish:<PATTERN>`, `yank` or `admin`
+    #[schema(value_type = Vec<String>)]
+    scopes: Vec<TokenScope>,
+    created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiToken> for TokenResponse {
+    fn from(token: ApiToken) -> Self {
+        TokenResponse {
+            id: token.id,
+            name: token.name,
+            scopes: token.scopes,
+            created_at: token.created_at,
+            expires_at: token.expires_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedTokenResponse {
+    #[serde(flatten)]
+    info: TokenResponse,
+    /// The token itself, which can't be retrieved again
+    token: String,
+}
+
+/// Creates an API token
+#[utoipa::path(
+    post,
+    path = "/v0/tokens",
+    request_body = CreateTokenRequest,
+    security(("token" = [])),
+    responses(
+        (status = 201, description = "The token was created", body = CreatedTokenResponse),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+    )
+)]
+pub async fn create_token(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    request: web::Json<CreateTokenRequest>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !permissions.can_manage_tokens() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let CreateTokenRequest {
+        name,
+        scopes,
+        expires_in_days,
+    } = request.into_inner();
+
+    let name = name.trim();
+    if name.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "token name cannot be empty".to_string(),
+        }));
+    }
+
+    if scopes.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "token must have at least one scope".to_string(),
+        }));
+    }
+
+    let expires_at = match expires_in_days {
+        Some(0) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error: "token must be valid for at least one day".to_string(),
+            }))
+        }
+        Some(days) => Some(Utc::now() + Duration::days(days.into())),
+        None => None,
+    };
+
+    let (token, secret) = app_state
+        .api_tokens
+        .create(*user_id, name.to_string(), scopes, expires_at, None)
+        .await?;
+
+    app_state
+        .audit_log
+        .record(
+            &http_request,
+            *user_id,
+            Action::CreateToken {
+                id: token.id.clone(),
+                name: token.name.clone(),
+            },
+        )
+        .await;
+
+    Ok(HttpResponse::Created().json(CreatedTokenResponse {
+        info: token.into(),
+        token: secret,
+    }))
+}
+
+/// Gets the user's API tokens
+#[utoipa::path(
+    get,
+    path = "/v0/tokens",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The tokens", body = Vec<TokenResponse>),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+    )
+)]
+pub async fn get_tokens(
+    app_state: web::Data<AppState>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !permissions.can_manage_tokens() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let tokens = app_state
+        .api_tokens
+        .list(user_id.into_inner())
+        .await
+        .into_iter()
+        .map(TokenResponse::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+/// Revokes an API token
+#[utoipa::path(
+    delete,
+    path = "/v0/tokens/{id}",
+    params(("id" = String, Path)),
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The token was revoked"),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn revoke_token(
+    app_state: web::Data<AppState>,
+    request: HttpRequest,
+    path: web::Path<String>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !permissions.can_manage_tokens() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let id = path.into_inner();
+    if !app_state.api_tokens.revoke(*user_id, &id).await? {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    app_state
+        .audit_log
+        .record(&request, *user_id, Action::RevokeToken { id: id.clone() })
+        .await;
+
+    Ok(HttpResponse::Ok().body(format!("revoked token {id}")))
+}
@@ -0,0 +1,276 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    audit::Action,
+    auth::{
+        api_tokens::Permissions,
+        oidc::{PublishGrant, TrustedPublisher, GRANT_DURATION},
+        UserId,
+    },
+    endpoints::openapi::shared::{BadRequest, Forbidden, NotFound, PackagePath, Unauthorized},
+    error::{Error, ErrorResponse},
+    owners::{owns_package, read_scope_info},
+    AppState,
+};
+use pesde::{
+    names::PackageName,
+    source::git_index::{root_tree, GitBasedSource},
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AddTrustedPublisherRequest {
+    #[schema(example = "acme/foo")]
+    repository: String,
+    #[schema(example = "release.yml")]
+    workflow: String,
+    #[serde(default)]
+    environment: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrantResponse {
+    token: String,
+    expires_at: DateTime<Utc>,
+    #[schema(value_type = Vec<String>)]
+    packages: Vec<PackageName>,
+}
+
+/// Whether the user may manage the trusted publishers of the package, which requires owning it and
+/// being able to manage tokens
+async fn can_manage(
+    app_state: &AppState,
+    name: &PackageName,
+    user_id: UserId,
+    permissions: &Permissions,
+) -> Result<Option<HttpResponse>, Error> {
+    if !permissions.can_manage_tokens() {
+        return Ok(Some(HttpResponse::Forbidden().finish()));
+    }
+
+    let source = app_state.read_source().await;
+    let repo = gix::open(source.path(&app_state.project))?;
+    let tree = root_tree(&repo)?;
+
+    let (scope, name_part) = name.as_str();
+    match read_scope_info(&tree, scope)? {
+        Some(info) => {
+            if !owns_package(app_state, &info, name_part, user_id).await? {
+                return Ok(Some(HttpResponse::Forbidden().finish()));
+            }
+        }
+        None => return Ok(Some(HttpResponse::NotFound().finish())),
+    }
+
+    Ok(None)
+}
+
+/// Gets the workflows trusted to publish a package
+#[utoipa::path(
+    get,
+    path = "/v0/packages/{name}/trusted-publishers",
+    params(PackagePath),
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The trusted workflows", body = Vec<TrustedPublisher>),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn get_trusted_publishers(
+    app_state: web::Data<AppState>,
+    path: web::Path<PackageName>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    let name = path.into_inner();
+    if let Some(response) = can_manage(&app_state, &name, *user_id, &permissions).await? {
+        return Ok(response);
+    }
+
+    Ok(HttpResponse::Ok().json(app_state.trusted_publishing.list(&name).await))
+}
+
+/// Trusts a workflow to publish a package
+#[utoipa::path(
+    post,
+    path = "/v0/packages/{name}/trusted-publishers",
+    params(PackagePath),
+    request_body = AddTrustedPublisherRequest,
+    security(("token" = [])),
+    responses(
+        (status = 201, description = "The workflow is trusted", body = TrustedPublisher),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn add_trusted_publisher(
+    app_state: web::Data<AppState>,
+    http_request: HttpRequest,
+    path: web::Path<PackageName>,
+    request: web::Json<AddTrustedPublisherRequest>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    let name = path.into_inner();
+    if let Some(response) = can_manage(&app_state, &name, *user_id, &permissions).await? {
+        return Ok(response);
+    }
+
+    let AddTrustedPublisherRequest {
+        repository,
+        workflow,
+        environment,
+    } = request.into_inner();
+
+    if !repository
+        .split_once('/')
+        .is_some_and(|(owner, repo)| !owner.is_empty() && !repo.is_empty() && !repo.contains('/'))
+    {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "repository must be in the form `owner/repo`".to_string(),
+        }));
+    }
+
+    if workflow.contains('/') || !(workflow.ends_with(".yml") || workflow.ends_with(".yaml")) {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "workflow must be the file name of a workflow, such as `release.yml`"
+                .to_string(),
+        }));
+    }
+
+    let publisher = app_state
+        .trusted_publishing
+        .add(
+            name.clone(),
+            *user_id,
+            repository,
+            workflow,
+            environment.filter(|environment| !environment.is_empty()),
+        )
+        .await?;
+
+    app_state
+        .audit_log
+        .record(
+            &http_request,
+            *user_id,
+            Action::AddTrustedPublisher {
+                package: name,
+                id: publisher.id.clone(),
+                repository: publisher.repository.clone(),
+                workflow: publisher.workflow.clone(),
+            },
+        )
+        .await;
+
+    Ok(HttpResponse::Created().json(publisher))
+}
+
+/// Stops trusting a workflow to publish a package
+#[utoipa::path(
+    delete,
+    path = "/v0/packages/{name}/trusted-publishers/{id}",
+    params(PackagePath, ("id" = String, Path)),
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The workflow isn't trusted anymore"),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn remove_trusted_publisher(
+    app_state: web::Data<AppState>,
+    request: HttpRequest,
+    path: web::Path<(PackageName, String)>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    let (name, id) = path.into_inner();
+    if let Some(response) = can_manage(&app_state, &name, *user_id, &permissions).await? {
+        return Ok(response);
+    }
+
+    if !app_state.trusted_publishing.remove(&name, &id).await? {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    app_state
+        .audit_log
+        .record(
+            &request,
+            *user_id,
+            Action::RemoveTrustedPublisher {
+                package: name.clone(),
+                id: id.clone(),
+            },
+        )
+        .await;
+
+    Ok(HttpResponse::Ok().body(format!("removed trusted publisher {id} from {name}")))
+}
+
+/// Exchanges an OIDC token for a short-lived API token which can publish the packages trusting
+/// its workflow
+#[utoipa::path(
+    post,
+    path = "/v0/oidc/token",
+    security(("token" = [])),
+    responses(
+        (status = 201, description = "The API token", body = GrantResponse),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+    )
+)]
+pub async fn exchange_oidc_token(
+    app_state: web::Data<AppState>,
+    request: HttpRequest,
+    user_id: web::ReqData<UserId>,
+    grant: Option<web::ReqData<PublishGrant>>,
+) -> Result<impl Responder, Error> {
+    let Some(grant) = grant
+        .map(web::ReqData::into_inner)
+        .filter(|grant| grant.from_oidc_token)
+    else {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "only GitHub Actions OIDC tokens can be exchanged".to_string(),
+        }));
+    };
+    let packages = grant.publishers.keys().cloned().collect();
+
+    let expires_at = Utc::now() + GRANT_DURATION;
+    let (info, token) = app_state
+        .api_tokens
+        .create(
+            *user_id,
+            format!("GitHub Actions ({})", grant.repository),
+            grant.scopes(),
+            Some(expires_at),
+            Some(grant),
+        )
+        .await?;
+
+    app_state
+        .audit_log
+        .record(
+            &request,
+            *user_id,
+            Action::CreateToken {
+                id: info.id,
+                name: info.name,
+            },
+        )
+        .await;
+
+    Ok(HttpResponse::Created().json(GrantResponse {
+        token,
+        expires_at,
+        packages,
+    }))
+}
@@ -0,0 +1,272 @@
+use crate::{
+    auth::{api_tokens::Permissions, UserId},
+    endpoints::{
+        openapi::shared::{
+            Binary, Forbidden, NotFound, PublishError, PublishHeaders, Queued, Republished,
+            TooManyRequests, Unauthorized,
+        },
+        publish_version::publish_archive,
+    },
+    error::{Error, ErrorResponse},
+    uploads::{UploadRejection, UploadStatus},
+    AppState,
+};
+use actix_web::{web, web::Bytes, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateRequest {
+    /// The size of the whole archive, in bytes
+    #[schema(minimum = 1)]
+    size: u64,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ChunkRequest {
+    /// Where the chunk starts in the archive
+    offset: u64,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FinalizeRequest {
+    /// The hex-encoded SHA-256 hash of the whole archive
+    sha256: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = Upload)]
+struct UploadResponse {
+    id: String,
+    /// The size of the whole archive, in bytes
+    size: u64,
+    /// How many bytes of the archive were received
+    received: u64,
+}
+
+impl UploadResponse {
+    fn new(id: String, status: UploadStatus) -> Self {
+        Self {
+            id,
+            size: status.size,
+            received: status.received,
+        }
+    }
+}
+
+fn rejection_response(rejection: UploadRejection) -> HttpResponse {
+    match rejection {
+        UploadRejection::NotFound => HttpResponse::NotFound().finish(),
+        UploadRejection::OffsetMismatch(received) => HttpResponse::Conflict().json(ErrorResponse {
+            error: format!("expected a chunk starting at offset {received}"),
+        }),
+        UploadRejection::TooLarge => HttpResponse::PayloadTooLarge().json(ErrorResponse {
+            error: "chunk goes past the size of the archive".to_string(),
+        }),
+        UploadRejection::Incomplete(status) => HttpResponse::Conflict().json(ErrorResponse {
+            error: format!(
+                "only {} of the archive's {} bytes were received",
+                status.received, status.size
+            ),
+        }),
+    }
+}
+
+/// Opens an upload session, for an archive too large to be published in a single request
+///
+/// Sessions which don't receive a chunk within the registry's timeout are closed, and sessions
+/// don't survive the registry restarting
+#[utoipa::path(
+    post,
+    path = "/v0/uploads",
+    request_body = CreateRequest,
+    security(("token" = [])),
+    responses(
+        (status = 201, description = "The session was opened", body = UploadResponse),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 413, description = "The archive is larger than the maximum archive size"),
+        (status = 409, description = "The user has too many sessions open"),
+    )
+)]
+pub async fn create_upload(
+    app_state: web::Data<AppState>,
+    body: web::Json<CreateRequest>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !permissions.can_publish_any() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    if let Some(ban) = app_state.moderation.ban_of(*user_id).await {
+        return Ok(HttpResponse::Forbidden().json(ErrorResponse {
+            error: format!("you are banned from publishing: {}", ban.reason),
+        }));
+    }
+
+    let config = app_state
+        .read_source()
+        .await
+        .config(&app_state.project)
+        .await?;
+    if body.size == 0 || body.size > config.max_archive_size as u64 {
+        return Ok(HttpResponse::PayloadTooLarge().json(ErrorResponse {
+            error: format!(
+                "archive size must be between 1 and {} bytes",
+                config.max_archive_size
+            ),
+        }));
+    }
+
+    let Some(id) = app_state.uploads.create(*user_id, body.size).await? else {
+        // not a 429, as waiting doesn't help until a session is finished, cancelled or expires
+        return Ok(HttpResponse::Conflict().json(ErrorResponse {
+            error: "too many uploads are open, finish or cancel one of them first".to_string(),
+        }));
+    };
+
+    Ok(HttpResponse::Created().json(UploadResponse {
+        id,
+        size: body.size,
+        received: 0,
+    }))
+}
+
+/// Returns how much of an archive was received, so that its upload can be resumed
+#[utoipa::path(
+    get,
+    path = "/v0/uploads/{id}",
+    params(("id" = String, Path)),
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The session", body = UploadResponse),
+        (status = 401, response = Unauthorized),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn get_upload(
+    app_state: web::Data<AppState>,
+    path: web::Path<String>,
+    user_id: web::ReqData<UserId>,
+) -> Result<impl Responder, Error> {
+    let id = path.into_inner();
+
+    Ok(match app_state.uploads.status(&id, *user_id).await {
+        Some(status) => HttpResponse::Ok().json(UploadResponse::new(id, status)),
+        None => HttpResponse::NotFound().finish(),
+    })
+}
+
+/// Appends a chunk of the archive to an upload session
+#[utoipa::path(
+    put,
+    path = "/v0/uploads/{id}",
+    params(("id" = String, Path), ChunkRequest),
+    request_body(content = inline(Binary), content_type = "application/octet-stream"),
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The chunk was received", body = UploadResponse),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+        (status = 409, description = "The chunk doesn't start where the received bytes end", body = ErrorResponse),
+        (status = 413, description = "The chunk goes past the size of the archive", body = ErrorResponse),
+    )
+)]
+pub async fn put_chunk(
+    app_state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<ChunkRequest>,
+    bytes: Bytes,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !permissions.can_publish_any() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let id = path.into_inner();
+
+    Ok(
+        match app_state
+            .uploads
+            .append(&id, *user_id, query.offset, &bytes)
+            .await?
+        {
+            Ok(status) => HttpResponse::Ok().json(UploadResponse::new(id, status)),
+            Err(rejection) => rejection_response(rejection),
+        },
+    )
+}
+
+/// Publishes the uploaded archive once its hash was checked, closing the session
+///
+/// The archive is published as if it was sent to `POST /v0/packages`
+#[utoipa::path(
+    post,
+    path = "/v0/uploads/{id}/finalize",
+    params(("id" = String, Path), PublishHeaders),
+    request_body = FinalizeRequest,
+    security(("token" = [])),
+    responses(
+        (status = 200, response = Republished),
+        (status = 202, response = Queued),
+        (status = 400, description = "The archive doesn't match its hash, or the request or archive is invalid", body = PublishError),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+        (status = 409, description = "Not all of the archive was received, or the version was already published"),
+        (status = 429, response = TooManyRequests),
+    )
+)]
+pub async fn finalize_upload(
+    app_state: web::Data<AppState>,
+    request: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<FinalizeRequest>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    let archive = match app_state.uploads.complete(&path, *user_id).await? {
+        Ok(archive) => archive,
+        Err(rejection) => return Ok(rejection_response(rejection)),
+    };
+
+    let hash = format!("{:x}", Sha256::digest(&archive));
+    if !hash.eq_ignore_ascii_case(body.sha256.trim()) {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!(
+                "the uploaded archive's hash is {hash}, which doesn't match the expected hash. The upload must be restarted"
+            ),
+        }));
+    }
+
+    publish_archive(&app_state, &request, archive.into(), *user_id, &permissions).await
+}
+
+/// Closes an upload session, discarding the bytes it received
+#[utoipa::path(
+    delete,
+    path = "/v0/uploads/{id}",
+    params(("id" = String, Path)),
+    security(("token" = [])),
+    responses(
+        (status = 204, description = "The session was closed"),
+        (status = 401, response = Unauthorized),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn cancel_upload(
+    app_state: web::Data<AppState>,
+    path: web::Path<String>,
+    user_id: web::ReqData<UserId>,
+) -> Result<impl Responder, Error> {
+    Ok(if app_state.uploads.cancel(&path, *user_id).await? {
+        HttpResponse::NoContent().finish()
+    } else {
+        HttpResponse::NotFound().finish()
+    })
+}
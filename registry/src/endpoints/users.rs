@@ -0,0 +1,105 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use actix_web::{web, HttpResponse, Responder};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    audit::AuditQuery,
+    auth::UserId,
+    error::Error,
+    metadata_cache::read_all_packages,
+    owners::{owns_package, read_scope_info},
+    package::PackageResponse,
+    AppState,
+};
+use pesde::{
+    names::PackageName,
+    source::git_index::{root_tree, GitBasedSource},
+};
+
+#[derive(Debug, Serialize, ToSchema)]
+struct UserPackage {
+    #[serde(flatten)]
+    package: PackageResponse,
+    /// Whether the user owns the package, directly or through its scope or a team
+    owner: bool,
+    /// Whether the user published any of the package's versions
+    publisher: bool,
+}
+
+/// Lists the packages a user owns or has published, sorted by name
+#[utoipa::path(
+    get,
+    path = "/v0/users/{id}/packages",
+    params(("id" = u64, Path, description = "The user's ID")),
+    responses(
+        (status = 200, description = "The packages, summarized by their latest versions and sorted by name", body = Vec<UserPackage>),
+    )
+)]
+pub async fn get_user_packages(
+    app_state: web::Data<AppState>,
+    path: web::Path<u64>,
+) -> Result<impl Responder, Error> {
+    let user_id = UserId(path.into_inner());
+
+    let packages = read_all_packages(&app_state).await;
+
+    let scope_infos = {
+        let scopes = packages
+            .iter()
+            .map(|(name, _)| name.as_str().0)
+            .collect::<BTreeSet<_>>();
+
+        let source = app_state.read_source().await;
+        let repo = gix::open(source.path(&app_state.project))?;
+        let tree = root_tree(&repo)?;
+
+        let mut scope_infos = HashMap::new();
+        for scope in scopes {
+            if let Some(info) = read_scope_info(&tree, scope)? {
+                scope_infos.insert(scope.to_string(), info);
+            }
+        }
+        scope_infos
+    };
+
+    // publishes are attributed from the audit log, so only those recorded in it are known
+    let published = app_state
+        .audit_log
+        .query(&AuditQuery {
+            actor: Some(user_id),
+            action: Some("publish".to_string()),
+            limit: usize::MAX,
+            ..Default::default()
+        })
+        .await?
+        .into_iter()
+        .filter_map(|entry| entry.action.package().cloned())
+        .collect::<HashSet<PackageName>>();
+
+    let mut user_packages = vec![];
+    for (name, versions) in packages {
+        let (scope, name_part) = name.as_str();
+
+        let owner = match scope_infos.get(scope) {
+            Some(info) => owns_package(&app_state, info, name_part, user_id).await?,
+            None => false,
+        };
+        let publisher = published.contains(&name);
+        if !owner && !publisher {
+            continue;
+        }
+
+        if let Some(package) = PackageResponse::latest(&name, &versions) {
+            user_packages.push(UserPackage {
+                package,
+                owner,
+                publisher,
+            });
+        }
+    }
+    user_packages.sort_by(|a, b| a.package.name.cmp(&b.package.name));
+
+    Ok(HttpResponse::Ok().json(user_packages))
+}
@@ -0,0 +1,69 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{api_tokens::Permissions, is_admin, UserId},
+    endpoints::openapi::shared::{Forbidden, NotFound, Unauthorized},
+    error::Error,
+    webhooks::Delivery,
+    AppState,
+};
+
+#[derive(Debug, Serialize, ToSchema)]
+struct RedeliveryResponse {
+    /// The ID of the new delivery
+    id: String,
+}
+
+/// Gets the last webhook deliveries
+#[utoipa::path(
+    get,
+    path = "/v0/webhooks/deliveries",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The deliveries, newest first", body = Vec<Delivery>),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+    )
+)]
+pub async fn get_deliveries(
+    app_state: web::Data<AppState>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    Ok(HttpResponse::Ok().json(app_state.webhooks.deliveries()))
+}
+
+/// Sends a webhook delivery again
+#[utoipa::path(
+    post,
+    path = "/v0/webhooks/deliveries/{id}/redeliver",
+    params(("id" = String, Path)),
+    security(("token" = [])),
+    responses(
+        (status = 202, description = "The delivery was queued", body = RedeliveryResponse),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn redeliver(
+    app_state: web::Data<AppState>,
+    path: web::Path<String>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !is_admin(&app_state, *user_id, &permissions) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    match app_state.webhooks.redeliver(&path.into_inner()) {
+        Some(id) => Ok(HttpResponse::Accepted().json(RedeliveryResponse { id })),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
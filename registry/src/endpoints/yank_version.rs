@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use semver::Version;
+
+use crate::{
+    auth::{api_tokens::Permissions, is_admin, UserId},
+    endpoints::{
+        openapi::shared::{Forbidden, NotFound, Unauthorized, VersionPath},
+        package_version::TargetRequest,
+    },
+    error::Error,
+    git::push_changes,
+    metadata_cache::update_cached_package,
+    notifications::Notification,
+    owners::{owns_package, read_scope_info},
+    search::update_package,
+    webhooks::Event,
+    AppState,
+};
+use pesde::{
+    names::PackageName,
+    source::{
+        git_index::{read_file, root_tree, GitBasedSource},
+        pesde::IndexFile,
+    },
+};
+
+/// Yanks a version of a package
+///
+/// Allowed for the package's owners and administrators
+#[utoipa::path(
+    patch,
+    path = "/v0/packages/{name}/{version}/{target}/yank",
+    params(VersionPath),
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The version was yanked"),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn yank_package_version(
+    app_state: web::Data<AppState>,
+    request: HttpRequest,
+    path: web::Path<(PackageName, Version, TargetRequest)>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !permissions.can_yank() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    set_yanked(
+        app_state,
+        &request,
+        path.into_inner(),
+        user_id.into_inner(),
+        &permissions,
+        true,
+    )
+    .await
+}
+
+/// Unyanks a version of a package
+#[utoipa::path(
+    patch,
+    path = "/v0/packages/{name}/{version}/{target}/unyank",
+    params(VersionPath),
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The version was unyanked"),
+        (status = 401, response = Unauthorized),
+        (status = 403, response = Forbidden),
+        (status = 404, response = NotFound),
+    )
+)]
+pub async fn unyank_package_version(
+    app_state: web::Data<AppState>,
+    request: HttpRequest,
+    path: web::Path<(PackageName, Version, TargetRequest)>,
+    user_id: web::ReqData<UserId>,
+    permissions: web::ReqData<Permissions>,
+) -> Result<impl Responder, Error> {
+    if !permissions.can_yank() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    set_yanked(
+        app_state,
+        &request,
+        path.into_inner(),
+        user_id.into_inner(),
+        &permissions,
+        false,
+    )
+    .await
+}
+
+async fn set_yanked(
+    app_state: web::Data<AppState>,
+    request: &HttpRequest,
+    (name, version, target): (PackageName, Version, TargetRequest),
+    user_id: UserId,
+    permissions: &Permissions,
+    yanked: bool,
+) -> Result<HttpResponse, Error> {
+    let source = app_state.write_source().await;
+    source
+        .refresh(&app_state.project, &app_state.reqwest_client)
+        .await
+        .map_err(Box::new)?;
+
+    let (scope, name_part) = name.as_str();
+
+    let (info, mut entries): (_, IndexFile) = {
+        let repo = gix::open(source.path(&app_state.project))?;
+        let tree = root_tree(&repo)?;
+
+        let info = match read_scope_info(&tree, scope)? {
+            Some(info) => {
+                // administrators can yank any package, such as to respond to a report
+                if !is_admin(&app_state, user_id, permissions)
+                    && !owns_package(&app_state, &info, name_part, user_id).await?
+                {
+                    return Ok(HttpResponse::Forbidden().finish());
+                }
+
+                info
+            }
+            None => return Ok(HttpResponse::NotFound().finish()),
+        };
+
+        match read_file(&tree, [scope, name_part])? {
+            Some(versions) => (info, toml::de::from_str(&versions)?),
+            None => return Ok(HttpResponse::NotFound().finish()),
+        }
+    };
+
+    let mut found = false;
+    let mut changed = false;
+
+    for (_, entry) in entries.iter_mut().filter(|(v_id, _)| {
+        *v_id.version() == version
+            && match &target {
+                TargetRequest::Any => true,
+                TargetRequest::Specific(kind) => v_id.target() == kind,
+            }
+    }) {
+        found = true;
+        changed |= entry.yanked != yanked;
+        entry.yanked = yanked;
+    }
+
+    if !found {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let action = if yanked { "yank" } else { "unyank" };
+
+    if changed {
+        push_changes(
+            &app_state,
+            &source,
+            scope,
+            HashMap::from([(name_part, Some(toml::to_string(&entries)?.into_bytes()))]),
+            &format!("{action} {name}@{version} {target}"),
+        )?;
+
+        app_state.dependents.update(&name, &entries);
+        update_cached_package(&app_state, &name, &entries);
+        update_package(&app_state, &name, entries).await;
+
+        let event = if yanked {
+            Event::Yank {
+                package: name.clone(),
+                version: version.clone(),
+                target: target.to_string(),
+            }
+        } else {
+            Event::Unyank {
+                package: name.clone(),
+                version: version.clone(),
+                target: target.to_string(),
+            }
+        };
+        app_state
+            .audit_log
+            .record(request, user_id, event.clone())
+            .await;
+        app_state.webhooks.send(event);
+
+        if yanked {
+            app_state
+                .notifications
+                .notify(
+                    &info,
+                    Notification::Yank {
+                        package: name.clone(),
+                        version: version.clone(),
+                        target: target.to_string(),
+                        yanked_by: user_id,
+                    },
+                )
+                .await;
+        }
+    }
+
+    Ok(HttpResponse::Ok().body(format!("{action}ed {name}@{version} {target}")))
+}
@@ -2,6 +2,7 @@ use actix_web::{body::BoxBody, HttpResponse, ResponseError};
 use pesde::source::git_index::errors::{ReadFile, RefreshError, TreeError};
 use serde::Serialize;
 use thiserror::Error;
+use utoipa::ToSchema;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -26,6 +27,9 @@ pub enum Error {
     #[error("invalid archive")]
     InvalidArchive(String),
 
+    #[error("archive violates the publish limits")]
+    InvalidPublish(Vec<String>),
+
     #[error("failed to read index config")]
     Config(#[from] pesde::source::pesde::errors::ConfigError),
 
@@ -46,13 +50,26 @@ pub enum Error {
 
     #[error("failed to get root tree")]
     RootTree(#[from] TreeError),
+
+    #[error("failed to read commit")]
+    Commit(#[from] gix::object::commit::Error),
+
+    #[error("failed to decode tree")]
+    DecodeTree(#[from] gix::objs::decode::Error),
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ErrorResponse {
     pub error: String,
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ViolationsResponse {
+    pub error: String,
+    /// Every way the archive violates the publish limits
+    pub violations: Vec<String>,
+}
+
 impl ResponseError for Error {
     fn error_response(&self) -> HttpResponse<BoxBody> {
         match self {
@@ -65,6 +82,12 @@ impl ResponseError for Error {
             Error::InvalidArchive(e) => HttpResponse::BadRequest().json(ErrorResponse {
                 error: format!("archive is invalid: {e}"),
             }),
+            Error::InvalidPublish(violations) => {
+                HttpResponse::BadRequest().json(ViolationsResponse {
+                    error: "archive is invalid".to_string(),
+                    violations: violations.clone(),
+                })
+            }
             e => {
                 tracing::error!("unhandled error: {e:?}");
                 HttpResponse::InternalServerError().finish()
@@ -0,0 +1,116 @@
+use crate::{error::Error, metrics::METRICS, search::all_packages, storage::Storage, AppState};
+use futures::StreamExt;
+use pesde::source::pesde::{DocEntry, DocEntryKind};
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashSet},
+    path::PathBuf,
+    time::Duration,
+};
+use tokio::pin;
+use utoipa::ToSchema;
+
+/// How old unreferenced files must be to be collected, so that the files of publishes which
+/// haven't been pushed to the index yet aren't
+pub const GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// Held while collecting garbage, so that collections don't run concurrently
+static GC_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// The stored files which the index references
+#[derive(Debug, Default)]
+pub struct ReferencedFiles {
+    /// The directories of the published versions, relative to the storage's root
+    pub versions: HashSet<PathBuf>,
+    /// The hashes of the documentation pages
+    pub docs: HashSet<String>,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct GcReport {
+    pub dry_run: bool,
+    pub scanned_files: u64,
+    /// The files which were, or would have been, deleted, relative to the storage's root
+    pub orphaned_files: Vec<String>,
+    pub reclaimed_bytes: u64,
+}
+
+fn collect_doc_hashes(docs: &BTreeSet<DocEntry>, hashes: &mut HashSet<String>) {
+    for doc in docs {
+        match &doc.kind {
+            DocEntryKind::Page { hash, .. } => {
+                hashes.insert(hash.clone());
+            }
+            DocEntryKind::Category { items, .. } => collect_doc_hashes(items, hashes),
+        }
+    }
+}
+
+/// Collects the stored files which the index, or publishes waiting to be added to it, reference
+pub async fn referenced_files(app_state: &AppState) -> ReferencedFiles {
+    let mut referenced = ReferencedFiles::default();
+
+    let source = app_state.read_source().await;
+    let packages = all_packages(&source, &app_state.project).await;
+    pin!(packages);
+
+    while let Some((name, file)) = packages.next().await {
+        let (scope, name) = name.as_str();
+
+        for (v_id, entry) in file {
+            referenced.versions.insert(
+                PathBuf::from(scope)
+                    .join(name)
+                    .join(v_id.version().to_string())
+                    .join(v_id.target().to_string()),
+            );
+            collect_doc_hashes(&entry.docs, &mut referenced.docs);
+        }
+    }
+
+    // queued publishes have their files stored before they're added to the index
+    for job in app_state.index_queue.unfinished().await {
+        let (scope, name) = job.package.as_str();
+
+        referenced.versions.insert(
+            PathBuf::from(scope)
+                .join(name)
+                .join(job.version_id.version().to_string())
+                .join(job.version_id.target().to_string()),
+        );
+        collect_doc_hashes(&job.entry.docs, &mut referenced.docs);
+    }
+
+    referenced
+}
+
+/// Deletes the stored files which the index doesn't reference, such as those left by failed
+/// publishes. Returns `None` if the storage backend doesn't support garbage collection
+pub async fn collect_garbage(
+    app_state: &AppState,
+    dry_run: bool,
+) -> Result<Option<GcReport>, Error> {
+    let storage = app_state.storage.get();
+    let Storage::FS(storage) = &*storage else {
+        return Ok(None);
+    };
+
+    let _guard = GC_LOCK.lock().await;
+
+    let referenced = referenced_files(app_state).await;
+    let report = storage.collect_garbage(&referenced, dry_run).await?;
+
+    if !report.dry_run {
+        METRICS.record_gc(report.orphaned_files.len() as u64, report.reclaimed_bytes);
+    }
+
+    tracing::info!(
+        "garbage collection{} found {} orphaned files out of {}, totalling {} bytes",
+        if dry_run { " (dry run)" } else { "" },
+        report.orphaned_files.len(),
+        report.scanned_files,
+        report.reclaimed_bytes
+    );
+
+    Ok(Some(report))
+}
@@ -0,0 +1,112 @@
+use crate::{benv, error::Error, AppState};
+use chrono::{DateTime, Utc};
+use git2::{Remote, Repository, Signature};
+use pesde::source::{
+    git_index::{root_commit, GitBasedSource},
+    pesde::PesdePackageSource,
+};
+use std::collections::HashMap;
+
+fn signature<'a>() -> Signature<'a> {
+    Signature::now(
+        &benv!(required "COMMITTER_GIT_NAME"),
+        &benv!(required "COMMITTER_GIT_EMAIL"),
+    )
+    .unwrap()
+}
+
+fn get_refspec(repo: &Repository, remote: &mut Remote) -> Result<String, git2::Error> {
+    let upstream_branch_buf = repo.branch_upstream_name(repo.head()?.name().unwrap())?;
+    let upstream_branch = upstream_branch_buf.as_str().unwrap();
+
+    let refspec_buf = remote
+        .refspecs()
+        .find(|r| r.direction() == git2::Direction::Fetch && r.dst_matches(upstream_branch))
+        .unwrap()
+        .rtransform(upstream_branch)?;
+    let refspec = refspec_buf.as_str().unwrap();
+
+    Ok(refspec.to_string())
+}
+
+/// Commits the files into the scope's directory of the index, removing those without contents,
+/// and pushes the commit
+#[tracing::instrument(skip_all, fields(scope))]
+pub fn push_changes(
+    app_state: &AppState,
+    source: &PesdePackageSource,
+    scope: &str,
+    files: HashMap<&str, Option<Vec<u8>>>,
+    message: &str,
+) -> Result<(), git2::Error> {
+    let repo = Repository::open_bare(source.path(&app_state.project))?;
+
+    let oids = files
+        .into_iter()
+        .map(|(name, contents)| {
+            Ok((
+                name,
+                contents.map(|contents| repo.blob(&contents)).transpose()?,
+            ))
+        })
+        .collect::<Result<HashMap<_, _>, git2::Error>>()?;
+
+    let mut remote = repo.find_remote("origin")?;
+    let refspec = get_refspec(&repo, &mut remote)?;
+
+    let reference = repo.find_reference(&refspec)?;
+
+    let old_root_tree = reference.peel_to_tree()?;
+    let old_scope_tree = match old_root_tree.get_name(scope) {
+        Some(entry) => Some(repo.find_tree(entry.id())?),
+        None => None,
+    };
+
+    let mut scope_tree = repo.treebuilder(old_scope_tree.as_ref())?;
+    for (file, oid) in oids {
+        match oid {
+            Some(oid) => {
+                scope_tree.insert(file, oid, 0o100644)?;
+            }
+            None => scope_tree.remove(file)?,
+        }
+    }
+
+    let scope_tree_id = scope_tree.write()?;
+    let mut root_tree = repo.treebuilder(Some(&repo.find_tree(old_root_tree.id())?))?;
+    root_tree.insert(scope, scope_tree_id, 0o040000)?;
+
+    let tree_oid = root_tree.write()?;
+
+    repo.commit(
+        Some("HEAD"),
+        &signature(),
+        &signature(),
+        message,
+        &repo.find_tree(tree_oid)?,
+        &[&reference.peel_to_commit()?],
+    )?;
+
+    let mut push_options = git2::PushOptions::new();
+    let mut remote_callbacks = git2::RemoteCallbacks::new();
+
+    let git_creds = app_state.project.auth_config().git_credentials().unwrap();
+    remote_callbacks.credentials(|_, _, _| {
+        git2::Cred::userpass_plaintext(&git_creds.username, &git_creds.password)
+    });
+
+    push_options.remote_callbacks(remote_callbacks);
+
+    remote.push(&[refspec], Some(&mut push_options))?;
+
+    Ok(())
+}
+
+/// The time the index was last changed at, which is when the commit it's at was made
+pub async fn index_modified_at(app_state: &AppState) -> Result<DateTime<Utc>, Error> {
+    let source = app_state.read_source().await;
+    let repo = gix::open(source.path(&app_state.project))?;
+    let time = root_commit(&repo)?.time()?;
+
+    Ok(DateTime::from_timestamp(time.seconds, 0).unwrap_or_default())
+}
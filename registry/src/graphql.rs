@@ -0,0 +1,384 @@
+use crate::{
+    endpoints::package_version::{find_version, TargetRequest, VersionRequest},
+    error::Error,
+    metadata_cache::read_package,
+    owners::read_scope_info,
+    AppState,
+};
+use actix_web::web;
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Enum, ErrorExtensions, Object, Schema, SimpleObject,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use pesde::{
+    manifest::DependencyType,
+    names::PackageName,
+    source::{
+        git_index::{root_tree, GitBasedSource},
+        pesde::{IndexFile, IndexFileEntry},
+        specifiers::DependencySpecifiers,
+        version_id::VersionId,
+    },
+};
+
+/// The most packages which can be queried at once
+const MAX_PACKAGES: usize = 100;
+
+pub type RegistrySchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Builds the schema of the GraphQL API. Queries are limited in depth and complexity, as
+/// dependencies can be followed into other packages
+pub fn build_schema() -> RegistrySchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .limit_depth(12)
+        .limit_complexity(1000)
+        .finish()
+}
+
+/// Turns an error of the registry into one which doesn't expose its details, like the REST
+/// endpoints' internal server errors
+fn internal_error(e: Error) -> async_graphql::Error {
+    tracing::error!("unhandled error: {e:?}");
+    async_graphql::Error::new("internal server error").extend_with(|_, e| e.set("code", "INTERNAL"))
+}
+
+fn app_state<'a>(ctx: &Context<'a>) -> &'a AppState {
+    ctx.data_unchecked::<web::Data<AppState>>()
+}
+
+fn parse_name(name: &str) -> async_graphql::Result<PackageName> {
+    name.parse()
+        .map_err(|e| async_graphql::Error::new(format!("invalid package name: {e}")))
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// A package of this registry, or null if it doesn't exist
+    async fn package(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+    ) -> async_graphql::Result<Option<Package>> {
+        Package::read(app_state(ctx), parse_name(&name)?).await
+    }
+
+    /// Packages of this registry, in the order of their names, with null for those which don't
+    /// exist
+    async fn packages(
+        &self,
+        ctx: &Context<'_>,
+        names: Vec<String>,
+    ) -> async_graphql::Result<Vec<Option<Package>>> {
+        if names.len() > MAX_PACKAGES {
+            return Err(async_graphql::Error::new(format!(
+                "at most {MAX_PACKAGES} packages can be queried at once"
+            )));
+        }
+
+        let names = names
+            .iter()
+            .map(|name| parse_name(name))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        futures::future::try_join_all(
+            names
+                .into_iter()
+                .map(|name| Package::read(app_state(ctx), name)),
+        )
+        .await
+    }
+}
+
+pub struct Package {
+    name: PackageName,
+    file: IndexFile,
+}
+
+impl Package {
+    async fn read(
+        app_state: &AppState,
+        name: PackageName,
+    ) -> async_graphql::Result<Option<Package>> {
+        Ok(read_package(app_state, &name)
+            .await
+            .map_err(internal_error)?
+            .map(|file| Package { name, file }))
+    }
+
+    fn make_version(&self, v_id: &VersionId, entry: &IndexFileEntry) -> Version {
+        Version {
+            name: self.name.clone(),
+            v_id: v_id.clone(),
+            entry: entry.clone(),
+        }
+    }
+}
+
+#[Object]
+impl Package {
+    async fn name(&self) -> String {
+        self.name.to_string()
+    }
+
+    /// The versions of the package, newest first, with an entry for each of their targets
+    async fn versions(&self, #[graphql(default = true)] include_yanked: bool) -> Vec<Version> {
+        self.file
+            .iter()
+            .rev()
+            .filter(|(_, entry)| include_yanked || !entry.yanked)
+            .map(|(v_id, entry)| self.make_version(v_id, entry))
+            .collect()
+    }
+
+    /// A version of the package, or null if it doesn't exist. Defaults to the latest version, and
+    /// any of its targets
+    async fn version(
+        &self,
+        #[graphql(default = "latest")] version: String,
+        target: Option<String>,
+    ) -> async_graphql::Result<Option<Version>> {
+        let version = if version.eq_ignore_ascii_case("latest") {
+            VersionRequest::Latest
+        } else {
+            VersionRequest::Specific(
+                version
+                    .parse()
+                    .map_err(|e| async_graphql::Error::new(format!("invalid version: {e}")))?,
+            )
+        };
+        let target = match target {
+            Some(target) => TargetRequest::Specific(
+                target
+                    .parse()
+                    .map_err(|e| async_graphql::Error::new(format!("invalid target: {e}")))?,
+            ),
+            None => TargetRequest::Any,
+        };
+
+        Ok(find_version(&self.file, version, &target)
+            .map(|(v_id, entry)| self.make_version(v_id, entry)))
+    }
+
+    /// The owners of the package. Owners of its scope aren't included
+    async fn owners(&self, ctx: &Context<'_>) -> async_graphql::Result<Owners> {
+        let app_state = app_state(ctx);
+        let (scope, name_part) = self.name.as_str();
+
+        let info = {
+            let source = app_state.read_source().await;
+            let repo =
+                gix::open(source.path(&app_state.project)).map_err(|e| internal_error(e.into()))?;
+            let tree = root_tree(&repo).map_err(|e| internal_error(e.into()))?;
+            read_scope_info(&tree, scope).map_err(internal_error)?
+        };
+        let owners = info
+            .and_then(|info| info.packages.get(name_part).cloned())
+            .unwrap_or_default();
+
+        Ok(Owners {
+            users: owners.owners.into_iter().collect(),
+            teams: owners.teams.into_iter().collect(),
+            publishers: owners.publishers.into_iter().collect(),
+            publisher_teams: owners.publisher_teams.into_iter().collect(),
+        })
+    }
+
+    /// The downloads of the package, which are counted periodically
+    async fn downloads(&self, ctx: &Context<'_>) -> Downloads {
+        let (versions, recent) = app_state(ctx).downloads.get(&self.name).await;
+
+        Downloads {
+            total: versions.values().sum(),
+            recent: recent
+                .into_iter()
+                .map(|(date, downloads)| DateDownloads { date, downloads })
+                .collect(),
+        }
+    }
+}
+
+/// A version of a package, for one of its targets
+pub struct Version {
+    name: PackageName,
+    v_id: VersionId,
+    entry: IndexFileEntry,
+}
+
+#[Object]
+impl Version {
+    async fn version(&self) -> String {
+        self.v_id.version().to_string()
+    }
+
+    async fn target(&self) -> Target {
+        let target = &self.entry.target;
+
+        Target {
+            kind: target.kind().to_string(),
+            lib: target.lib_path().is_some(),
+            types: target.types_path().is_some(),
+            bin: target.bin_path().is_some(),
+            scripts: target
+                .scripts()
+                .map(|scripts| scripts.keys().cloned().collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    async fn published_at(&self) -> DateTime<Utc> {
+        self.entry.published_at
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.entry.description.as_deref()
+    }
+
+    async fn license(&self) -> Option<&str> {
+        self.entry.license.as_deref()
+    }
+
+    async fn authors(&self) -> &[String] {
+        &self.entry.authors
+    }
+
+    async fn repository(&self) -> Option<String> {
+        self.entry.repository.as_ref().map(ToString::to_string)
+    }
+
+    async fn funding(&self) -> Vec<String> {
+        self.entry.funding.iter().map(ToString::to_string).collect()
+    }
+
+    async fn yanked(&self) -> bool {
+        self.entry.yanked
+    }
+
+    /// The message the version was deprecated with, if it was
+    async fn deprecated(&self) -> Option<&str> {
+        self.entry.deprecated.as_deref()
+    }
+
+    async fn dependencies(&self) -> Vec<Dependency> {
+        self.entry
+            .dependencies
+            .iter()
+            .map(|(alias, (specifier, ty))| Dependency {
+                alias: alias.clone(),
+                specifier: specifier.clone(),
+                ty: *ty,
+            })
+            .collect()
+    }
+
+    /// The downloads of the version, which are counted periodically
+    async fn downloads(&self, ctx: &Context<'_>) -> u64 {
+        app_state(ctx)
+            .downloads
+            .of_version(&self.name, &self.v_id)
+            .await
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Target {
+    kind: String,
+    lib: bool,
+    types: bool,
+    bin: bool,
+    scripts: Vec<String>,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(name = "DependencyType")]
+pub enum DependencyKind {
+    Standard,
+    Peer,
+    Dev,
+}
+
+impl From<DependencyType> for DependencyKind {
+    fn from(ty: DependencyType) -> Self {
+        match ty {
+            DependencyType::Standard => DependencyKind::Standard,
+            DependencyType::Peer => DependencyKind::Peer,
+            DependencyType::Dev => DependencyKind::Dev,
+        }
+    }
+}
+
+/// A dependency of a version
+pub struct Dependency {
+    alias: String,
+    specifier: DependencySpecifiers,
+    ty: DependencyType,
+}
+
+#[Object]
+impl Dependency {
+    /// The name the dependency is imported by
+    async fn alias(&self) -> &str {
+        &self.alias
+    }
+
+    #[graphql(name = "type")]
+    async fn ty(&self) -> DependencyKind {
+        self.ty.into()
+    }
+
+    /// The dependency as it's written in manifests, such as `acme/foo@^1.0.0`
+    async fn specifier(&self) -> String {
+        self.specifier.to_string()
+    }
+
+    /// The range of versions the dependency accepts, if it specifies one
+    async fn version_requirement(&self) -> Option<String> {
+        self.specifier.version_req().map(ToString::to_string)
+    }
+
+    /// The target the dependency requests, if it requests one
+    async fn target(&self) -> Option<String> {
+        self.specifier.target().map(ToString::to_string)
+    }
+
+    /// The package depended on, if it's a package of this registry
+    async fn package(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Package>> {
+        let DependencySpecifiers::Pesde(specifier) = &self.specifier else {
+            return Ok(None);
+        };
+        let app_state = app_state(ctx);
+        if !app_state
+            .dependents
+            .is_own_index(specifier.index.as_deref())
+        {
+            return Ok(None);
+        }
+
+        Package::read(app_state, specifier.name.clone()).await
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Owners {
+    users: Vec<u64>,
+    /// GitHub teams, as `org/team`
+    teams: Vec<String>,
+    /// Users who may only publish new versions
+    publishers: Vec<u64>,
+    /// GitHub teams who may only publish new versions
+    publisher_teams: Vec<String>,
+}
+
+#[derive(SimpleObject)]
+pub struct Downloads {
+    total: u64,
+    /// The daily downloads of the last days, oldest first
+    recent: Vec<DateDownloads>,
+}
+
+#[derive(SimpleObject)]
+pub struct DateDownloads {
+    date: NaiveDate,
+    downloads: u64,
+}
@@ -0,0 +1,515 @@
+use crate::{
+    auth::UserId,
+    error::Error,
+    git::push_changes,
+    metadata_cache::update_cached_package,
+    metrics::METRICS,
+    notifications::Notification,
+    owners::{may_publish, read_scope_info},
+    search::update_package,
+    webhooks::Event,
+    write_json_atomically, AppState,
+};
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use fs_err::tokio as fs;
+use pesde::{
+    names::PackageName,
+    retry::RetryPolicy,
+    source::{
+        git_index::{read_file, root_tree, GitBasedSource},
+        pesde::{IndexFile, IndexFileEntry, ScopeInfo, SCOPE_INFO_FILE},
+        version_id::VersionId,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    path::PathBuf,
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use utoipa::ToSchema;
+
+/// How long finished jobs are kept around for their status to be queried
+const FINISHED_JOB_RETENTION: chrono::Duration = chrono::Duration::days(1);
+
+/// How committing a job to the index is retried when it fails, such as when pushing fails
+const RETRY_POLICY: RetryPolicy = RetryPolicy {
+    max_retries: 5,
+    initial_delay: Duration::from_secs(1),
+    max_delay: Duration::from_secs(60),
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Processing,
+    Completed,
+    Failed,
+}
+
+impl JobStatus {
+    fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed)
+    }
+}
+
+/// A published version waiting to be added to the index. Its files are stored before it's queued,
+/// so that it can be committed even if the registry restarts in the meantime
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub status: JobStatus,
+    /// Why the job failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The user who published the version
+    pub user_id: u64,
+    /// The IP address the version was published from, for the audit log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    /// Whether the user may create the package's scope if it doesn't exist yet
+    pub may_create_scope: bool,
+    pub package: PackageName,
+    pub version_id: VersionId,
+    pub entry: IndexFileEntry,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Why a version can't be added to the index
+#[derive(Debug)]
+pub enum Rejection {
+    /// The user isn't allowed to publish the package, optionally with a reason
+    Forbidden(Option<String>),
+    /// The version conflicts with other versions of the package
+    BadRequest(String),
+    /// The version was already published
+    Conflict,
+    /// The version was already published with the same archive, so publishing it again only
+    /// completes the publish if it was interrupted
+    Published,
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::Forbidden(Some(reason)) => write!(f, "{reason}"),
+            Rejection::Forbidden(None) => write!(f, "not allowed to publish this package"),
+            Rejection::BadRequest(reason) => write!(f, "{reason}"),
+            Rejection::Conflict | Rejection::Published => {
+                write!(f, "package version already exists")
+            }
+        }
+    }
+}
+
+/// The changes publishing a version makes to the index
+pub struct IndexChanges {
+    /// The information of the package's scope, if it's created by the publish
+    pub scope_info: Option<String>,
+    pub entries: IndexFile,
+}
+
+/// Checks whether the user may add the entry to the package's index file, returning the changes
+/// to make to the index if so
+pub async fn prepare_changes(
+    app_state: &AppState,
+    tree: &gix::Tree<'_>,
+    user_id: UserId,
+    may_create_scope: bool,
+    package: &PackageName,
+    version_id: &VersionId,
+    entry: &IndexFileEntry,
+) -> Result<Result<IndexChanges, Rejection>, Error> {
+    let (scope, name) = package.as_str();
+
+    if let Some(upstream) = &app_state.upstream {
+        if upstream.mirrors_scope(scope).await {
+            return Ok(Err(Rejection::Forbidden(Some(format!(
+                "scope {scope} is mirrored from the upstream registry"
+            )))));
+        }
+    }
+
+    let scope_info = match read_scope_info(tree, scope)? {
+        Some(info) => {
+            if !may_publish(app_state, &info, name, user_id).await? {
+                return Ok(Err(Rejection::Forbidden(None)));
+            }
+
+            None
+        }
+        None => {
+            if !may_create_scope {
+                return Ok(Err(Rejection::Forbidden(Some(format!(
+                    "scope {scope} doesn't exist"
+                )))));
+            }
+
+            Some(toml::to_string(&ScopeInfo {
+                owners: BTreeSet::from([user_id.0]),
+                ..Default::default()
+            })?)
+        }
+    };
+
+    let mut entries: IndexFile =
+        toml::de::from_str(&read_file(tree, [scope, name])?.unwrap_or_default())?;
+
+    let other_entry = entries
+        .iter()
+        .find(|(v_id, _)| v_id.version() == version_id.version())
+        .map(|(_, entry)| entry);
+    if let Some(other_entry) = other_entry {
+        // description cannot be different - which one to render in the "Recently published" list?
+        // the others cannot be different because what to return from the versions endpoint?
+        if other_entry.description != entry.description
+            || other_entry.license != entry.license
+            || other_entry.authors != entry.authors
+            || other_entry.repository != entry.repository
+            || other_entry.maintainers != entry.maintainers
+            || other_entry.funding != entry.funding
+        {
+            return Ok(Err(Rejection::BadRequest(
+                "same version with different description, license, authors, repository, maintainers or funding already exists"
+                    .to_string(),
+            )));
+        }
+    }
+
+    if let Some(other_entry) = entries.insert(version_id.clone(), entry.clone()) {
+        return Ok(Err(
+            if other_entry.checksum.is_some() && other_entry.checksum == entry.checksum {
+                Rejection::Published
+            } else {
+                Rejection::Conflict
+            },
+        ));
+    }
+
+    Ok(Ok(IndexChanges {
+        scope_info,
+        entries,
+    }))
+}
+
+/// Marks a version as being published until it's queued, or released if publishing it fails
+/// before that
+pub struct Reservation<'a> {
+    queue: &'a IndexQueue,
+    key: Option<(PackageName, VersionId)>,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.queue.reserved.lock().unwrap().remove(&key);
+        }
+    }
+}
+
+/// The queue of versions waiting to be added to the index, persisted in a file of the data
+/// directory. Jobs are committed one at a time by [`run_worker`], so publishing doesn't need to
+/// wait for the index to be pushed
+#[derive(Debug)]
+pub struct IndexQueue {
+    path: PathBuf,
+    jobs: tokio::sync::Mutex<BTreeMap<String, Job>>,
+    /// The versions which are being published, to reject publishing them twice at once
+    reserved: std::sync::Mutex<HashSet<(PackageName, VersionId)>>,
+    /// Taken once the queue is closed, so that the worker stops after the queued jobs
+    sender: std::sync::Mutex<Option<mpsc::UnboundedSender<String>>>,
+}
+
+impl IndexQueue {
+    /// Loads the queue, returning the receiver of the IDs of the jobs to process. Jobs which
+    /// weren't finished before the registry stopped are queued again
+    pub async fn load(path: PathBuf) -> std::io::Result<(Self, mpsc::UnboundedReceiver<String>)> {
+        let jobs: BTreeMap<String, Job> = match fs::read_to_string(&path).await {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        let mut unfinished = jobs
+            .values()
+            .filter(|job| !job.status.is_finished())
+            .collect::<Vec<_>>();
+        unfinished.sort_by_key(|job| job.created_at);
+        for job in &unfinished {
+            sender.send(job.id.clone()).unwrap();
+        }
+
+        let reserved = unfinished
+            .into_iter()
+            .map(|job| (job.package.clone(), job.version_id.clone()))
+            .collect();
+
+        Ok((
+            Self {
+                path,
+                jobs: tokio::sync::Mutex::new(jobs),
+                reserved: std::sync::Mutex::new(reserved),
+                sender: std::sync::Mutex::new(Some(sender)),
+            },
+            receiver,
+        ))
+    }
+
+    async fn save(&self, jobs: &mut BTreeMap<String, Job>) -> std::io::Result<()> {
+        let cutoff = Utc::now() - FINISHED_JOB_RETENTION;
+        jobs.retain(|_, job| {
+            job.finished_at
+                .is_none_or(|finished_at| finished_at > cutoff)
+        });
+
+        write_json_atomically(&self.path, jobs).await
+    }
+
+    /// Reserves the version for publishing, returning `None` if it's already being published
+    pub fn reserve(
+        &self,
+        package: &PackageName,
+        version_id: &VersionId,
+    ) -> Option<Reservation<'_>> {
+        let key = (package.clone(), version_id.clone());
+        if !self.reserved.lock().unwrap().insert(key.clone()) {
+            return None;
+        }
+
+        Some(Reservation {
+            queue: self,
+            key: Some(key),
+        })
+    }
+
+    /// Persists the job and queues it. The version stays reserved until the job is finished
+    pub async fn enqueue(&self, mut reservation: Reservation<'_>, job: Job) -> std::io::Result<()> {
+        let id = job.id.clone();
+
+        {
+            let mut jobs = self.jobs.lock().await;
+            jobs.insert(id.clone(), job);
+            if let Err(e) = self.save(&mut jobs).await {
+                jobs.remove(&id);
+                return Err(e);
+            }
+        }
+
+        reservation.key = None;
+        // jobs enqueued after the queue is closed are committed once the registry restarts
+        if let Some(sender) = &*self.sender.lock().unwrap() {
+            sender.send(id).unwrap();
+        }
+
+        Ok(())
+    }
+
+    /// Stops accepting jobs, so that [`run_worker`] returns once the queued jobs are committed
+    pub fn close(&self) {
+        self.sender.lock().unwrap().take();
+    }
+
+    pub async fn get(&self, id: &str) -> Option<Job> {
+        self.jobs.lock().await.get(id).cloned()
+    }
+
+    /// The jobs which haven't been finished yet
+    pub async fn unfinished(&self) -> Vec<Job> {
+        self.jobs
+            .lock()
+            .await
+            .values()
+            .filter(|job| !job.status.is_finished())
+            .cloned()
+            .collect()
+    }
+
+    async fn set_status(
+        &self,
+        id: &str,
+        status: JobStatus,
+        error: Option<String>,
+    ) -> std::io::Result<Option<Job>> {
+        let mut jobs = self.jobs.lock().await;
+        let Some(job) = jobs.get_mut(id) else {
+            return Ok(None);
+        };
+
+        job.status = status;
+        job.error = error;
+        if status.is_finished() {
+            job.finished_at = Some(Utc::now());
+            self.reserved
+                .lock()
+                .unwrap()
+                .remove(&(job.package.clone(), job.version_id.clone()));
+        }
+        let job = job.clone();
+
+        self.save(&mut jobs).await?;
+
+        Ok(Some(job))
+    }
+}
+
+/// Adds the job's version to the index, unless it has been committed already (such as when the
+/// registry stopped before the job was marked as completed)
+async fn commit(app_state: &AppState, job: &Job) -> Result<Result<(), Rejection>, Error> {
+    let source = app_state.write_source().await;
+    source
+        .refresh(&app_state.project, &app_state.reqwest_client)
+        .await
+        .map_err(Box::new)?;
+
+    let (scope, name) = job.package.as_str();
+
+    let changes = {
+        let repo = gix::open(source.path(&app_state.project))?;
+        let tree = root_tree(&repo)?;
+
+        let entries: IndexFile =
+            toml::de::from_str(&read_file(&tree, [scope, name])?.unwrap_or_default())?;
+        if entries
+            .get(&job.version_id)
+            .is_some_and(|entry| entry.checksum == job.entry.checksum)
+        {
+            return Ok(Ok(()));
+        }
+
+        match prepare_changes(
+            app_state,
+            &tree,
+            UserId(job.user_id),
+            job.may_create_scope,
+            &job.package,
+            &job.version_id,
+            &job.entry,
+        )
+        .await?
+        {
+            Ok(changes) => changes,
+            Err(rejection) => return Ok(Err(rejection)),
+        }
+    };
+
+    let mut files = HashMap::new();
+    if let Some(scope_info) = changes.scope_info {
+        files.insert(SCOPE_INFO_FILE, Some(scope_info.into_bytes()));
+    }
+    files.insert(name, Some(toml::to_string(&changes.entries)?.into_bytes()));
+
+    push_changes(
+        app_state,
+        &source,
+        scope,
+        files,
+        &format!(
+            "add {}@{} {}",
+            job.package,
+            job.version_id.version(),
+            job.entry.target
+        ),
+    )?;
+
+    app_state.dependents.update(&job.package, &changes.entries);
+    update_cached_package(app_state, &job.package, &changes.entries);
+    update_package(app_state, &job.package, changes.entries).await;
+
+    Ok(Ok(()))
+}
+
+/// Notifies the owners of the job's package of its publish
+async fn notify_publish(app_state: &AppState, job: &Job) -> Result<(), Error> {
+    let info = {
+        let source = app_state.read_source().await;
+        let repo = gix::open(source.path(&app_state.project))?;
+        let tree = root_tree(&repo)?;
+
+        read_scope_info(&tree, job.package.as_str().0)?
+    };
+
+    if let Some(info) = info {
+        app_state
+            .notifications
+            .notify(
+                &info,
+                Notification::Publish {
+                    package: job.package.clone(),
+                    version: job.version_id.version().clone(),
+                    target: job.version_id.target().clone(),
+                    publisher: UserId(job.user_id),
+                },
+            )
+            .await;
+    }
+
+    Ok(())
+}
+
+/// Commits the queued jobs to the index one at a time, until the queue is closed
+pub async fn run_worker(
+    app_state: web::Data<AppState>,
+    mut receiver: mpsc::UnboundedReceiver<String>,
+) {
+    while let Some(id) = receiver.recv().await {
+        let job = match app_state
+            .index_queue
+            .set_status(&id, JobStatus::Processing, None)
+            .await
+        {
+            Ok(Some(job)) => job,
+            Ok(None) => continue,
+            Err(e) => {
+                tracing::error!("failed to update the status of index job {id}: {e}");
+                continue;
+            }
+        };
+
+        let result = RETRY_POLICY
+            .retry(|| commit(&app_state, &job), |_| true)
+            .await;
+
+        let (status, error) = match result {
+            Ok(Ok(())) => (JobStatus::Completed, None),
+            Ok(Err(rejection)) => (JobStatus::Failed, Some(rejection.to_string())),
+            Err(e) => {
+                tracing::error!("failed to commit index job {id}: {e:?}");
+                (
+                    JobStatus::Failed,
+                    Some("failed to update the index".to_string()),
+                )
+            }
+        };
+
+        if let Err(e) = app_state.index_queue.set_status(&id, status, error).await {
+            tracing::error!("failed to update the status of index job {id}: {e}");
+        }
+
+        if status != JobStatus::Completed {
+            continue;
+        }
+
+        let event = Event::Publish {
+            package: job.package.clone(),
+            version: job.version_id.version().clone(),
+            target: job.version_id.target().clone(),
+        };
+        app_state
+            .audit_log
+            .record_from(job.ip.clone(), UserId(job.user_id), event.clone())
+            .await;
+        app_state.webhooks.send(event);
+        if let Err(e) = notify_publish(&app_state, &job).await {
+            tracing::error!("failed to notify the owners of {}: {e}", job.package);
+        }
+        METRICS.record_publish();
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{auth::UserId, benv};
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    error::Error as ActixError,
+    middleware::Next,
+    HttpMessage,
+};
+use std::{fmt::Display, str::FromStr, time::Instant};
+use tracing_actix_web::RequestId;
+
+/// The format the registry's logs are written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable lines
+    Text,
+    /// A JSON object per line, for log aggregators
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown log format `{s}`")),
+        }
+    }
+}
+
+impl Display for LogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+pub fn get_log_format_from_env() -> LogFormat {
+    benv!(parse "LOG_FORMAT" => "text")
+}
+
+/// Logs each request once it completes, with the user who made it if they were authenticated
+pub async fn access_log_mw(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, ActixError> {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let request_id = req.extensions().get::<RequestId>().map(ToString::to_string);
+
+    let result = next.call(req).await;
+
+    let (route, status, user_id) = match &result {
+        Ok(res) => {
+            let extensions = res.request().extensions();
+            let user_id = extensions
+                .get::<UserId>()
+                .copied()
+                .or_else(|| extensions.get::<Option<UserId>>().copied().flatten());
+
+            (
+                res.request().match_pattern(),
+                res.status(),
+                user_id.map(|user_id| user_id.0),
+            )
+        }
+        Err(e) => (None, e.as_response_error().status_code(), None),
+    };
+
+    tracing::info!(
+        request_id,
+        user_id,
+        method,
+        route = route.as_deref().unwrap_or("unmatched"),
+        status = status.as_u16(),
+        latency_ms = start.elapsed().as_secs_f64() * 1000.0,
+        "request completed"
+    );
+
+    result
+}
@@ -1,10 +1,30 @@
 use crate::{
-    auth::{get_auth_from_env, Auth, UserIdExtractor},
-    search::make_search,
+    audit::AuditLog,
+    auth::{
+        accounts::Accounts, api_tokens::ApiTokens, get_admins_from_env, get_auth_from_env,
+        oauth::get_provider_from_env, oidc::TrustedPublishing, Auth, UserId,
+    },
+    cors::get_cors_policy_from_env,
+    dependents::Dependents,
+    downloads::{get_download_retention_from_env, Downloads, FLUSH_INTERVAL},
+    index_queue::{run_worker, IndexQueue},
+    logging::{get_log_format_from_env, LogFormat},
+    metadata_cache::MetadataCache,
+    metrics::METRICS,
+    moderation::Moderation,
+    notifications::{get_mailer_from_env, Notifications},
+    rate_limit::{get_rate_limits_from_env, RateLimits, CLEANUP_INTERVAL},
+    reconcile::Reconciler,
+    reload::Reloadable,
+    scan::{get_scanners_from_env, Scanners},
+    search::{make_search, ranking_downloads, refresh_downloads, SearchIndexer},
     storage::{get_storage_from_env, Storage},
+    teams::{get_teams_from_env, GitHubTeams},
+    uploads::Uploads,
+    upstream::{get_upstream_from_env, sync_package, Upstream},
+    validation::{get_publish_limits_from_env, PublishLimits},
+    webhooks::{get_webhooks_from_env, Webhooks},
 };
-use actix_cors::Cors;
-use actix_governor::{Governor, GovernorConfigBuilder};
 use actix_web::{
     middleware::{from_fn, Compress, NormalizePath, TrailingSlash},
     rt::System,
@@ -12,23 +32,61 @@ use actix_web::{
     web::PayloadConfig,
     App, HttpServer,
 };
+use clap::Parser;
 use fs_err::tokio as fs;
 use pesde::{
     source::{pesde::PesdePackageSource, traits::PackageSource},
     AuthConfig, Project,
 };
-use std::{env::current_dir, path::PathBuf};
-use tracing::level_filters::LevelFilter;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeSet,
+    env::current_dir,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+use tokio::io::AsyncWriteExt;
+use tracing::{level_filters::LevelFilter, Instrument};
 use tracing_subscriber::{
     fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter,
 };
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
+mod archive;
+mod audit;
 mod auth;
+mod config;
+mod cors;
+mod dependents;
+mod downloads;
 mod endpoints;
 mod error;
+mod gc;
+mod git;
+mod graphql;
+mod index_queue;
+mod logging;
+mod metadata_cache;
+mod metrics;
+mod moderation;
+mod notifications;
+mod owners;
 mod package;
+mod rate_limit;
+mod readme;
+mod reconcile;
+mod reload;
+mod scan;
 mod search;
 mod storage;
+mod teams;
+mod telemetry;
+mod uploads;
+mod upstream;
+mod validation;
+mod webhooks;
 
 pub fn make_reqwest() -> reqwest::Client {
     reqwest::ClientBuilder::new()
@@ -41,57 +99,209 @@ pub fn make_reqwest() -> reqwest::Client {
         .unwrap()
 }
 
+/// Replaces the file with the value serialized as JSON. It's written to a temporary file which is
+/// synced to disk before being renamed over the file, so that a crash leaves either the old or the
+/// new contents rather than a truncated file
+pub async fn write_json_atomically<T: Serialize + ?Sized>(
+    path: &Path,
+    value: &T,
+) -> std::io::Result<()> {
+    let tmp_path = path.with_extension("json.tmp");
+    let mut file = fs::File::create(&tmp_path).await?;
+    file.write_all(&serde_json::to_vec(value)?).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    fs::rename(&tmp_path, path).await
+}
+
 pub struct AppState {
-    pub source: tokio::sync::Mutex<PesdePackageSource>,
+    pub source: tokio::sync::RwLock<PesdePackageSource>,
     pub project: Project,
-    pub storage: Storage,
+    /// The client the index repository is fetched with
+    pub reqwest_client: reqwest::Client,
+    pub storage: Reloadable<Storage>,
     pub auth: Auth,
+    pub api_tokens: ApiTokens,
+    pub trusted_publishing: TrustedPublishing,
+    pub admins: BTreeSet<UserId>,
+    /// Whether only administrators can create scopes, rather than whoever publishes to them first
+    pub restrict_scope_creation: bool,
+    pub teams: GitHubTeams,
+    /// The hash of the token required to scrape metrics, if any
+    pub metrics_token: Option<[u8; 32]>,
+    pub downloads: Downloads,
+    pub dependents: Dependents,
+    pub webhooks: Webhooks,
+    pub notifications: Notifications,
+    pub audit_log: AuditLog,
+    pub moderation: Moderation,
+    pub index_queue: IndexQueue,
+    pub rate_limits: Reloadable<RateLimits>,
+    pub publish_limits: PublishLimits,
+    pub scanners: Scanners,
+    /// The cache of the packages' index files, if enabled
+    pub metadata_cache: Option<MetadataCache>,
+    /// The registry whose packages are mirrored by this one, if any
+    pub upstream: Option<Upstream>,
+    /// The archives being uploaded in chunks
+    pub uploads: Uploads,
+    pub reconciler: Reconciler,
 
     pub search_reader: tantivy::IndexReader,
-    pub search_writer: std::sync::Mutex<tantivy::IndexWriter>,
+    pub search_indexer: SearchIndexer,
     pub query_parser: tantivy::query::QueryParser,
 }
 
+impl AppState {
+    /// Locks the index source for reading, recording how long the lock was waited for
+    pub async fn read_source(&self) -> tokio::sync::RwLockReadGuard<'_, PesdePackageSource> {
+        let start = Instant::now();
+        let source = self
+            .source
+            .read()
+            .instrument(tracing::info_span!("index_lock", mode = "read"))
+            .await;
+        METRICS.observe_index_lock_wait("read", start.elapsed());
+
+        source
+    }
+
+    /// Locks the index source for writing, recording how long the lock was waited for
+    pub async fn write_source(&self) -> tokio::sync::RwLockWriteGuard<'_, PesdePackageSource> {
+        let start = Instant::now();
+        let source = self
+            .source
+            .write()
+            .instrument(tracing::info_span!("index_lock", mode = "write"))
+            .await;
+        METRICS.observe_index_lock_wait("write", start.elapsed());
+
+        source
+    }
+}
+
 #[macro_export]
 macro_rules! benv {
     ($name:expr) => {
-        std::env::var($name)
+        $crate::config::var($name)
     };
     ($name:expr => $default:expr) => {
         benv!($name).unwrap_or($default.to_string())
     };
     (required $name:expr) => {
-        benv!($name).expect(concat!("Environment variable `", $name, "` must be set"))
+        benv!($name).expect(concat!("Setting `", $name, "` must be set"))
     };
     (parse $name:expr) => {
         benv!($name)
             .map(|v| v.parse().expect(concat!(
-                "Environment variable `",
+                "Setting `",
                 $name,
                 "` must be a valid value"
             )))
     };
     (parse required $name:expr) => {
-        benv!(parse $name).expect(concat!("Environment variable `", $name, "` must be set"))
+        benv!(parse $name).expect(concat!("Setting `", $name, "` must be set"))
     };
     (parse $name:expr => $default:expr) => {
         benv!($name => $default)
             .parse()
             .expect(concat!(
-                "Environment variable `",
+                "Setting `",
                 $name,
                 "` must a valid value"
             ))
     };
 }
 
+/// The pesde registry
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// A TOML file to read the settings which aren't set in the environment from
+    #[arg(long, env = "CONFIG_FILE")]
+    config: Option<PathBuf>,
+
+    /// Validate the configuration and exit, without starting the registry
+    #[arg(long)]
+    check_config: bool,
+
+    /// Create a user account with the given name and print its API key, for registries using
+    /// `USER_ACCOUNTS`. The registry must not be running
+    #[arg(long, value_name = "NAME")]
+    create_user: Option<String>,
+}
+
+/// Builds the parts of the registry which are configured without the index or the data directory,
+/// panicking with the invalid setting's name if any
+fn check_config() {
+    let _: u16 = benv!(parse "PORT" => "8080");
+
+    let _: gix::Url = benv!(required "INDEX_REPO_URL")
+        .try_into()
+        .expect("Setting `INDEX_REPO_URL` must be a valid URL");
+    benv!(required "GIT_USERNAME");
+    benv!(required "GIT_PASSWORD");
+    benv!(required "COMMITTER_GIT_NAME");
+    benv!(required "COMMITTER_GIT_EMAIL");
+
+    println!("storage: {}", get_storage_from_env());
+    println!("download retention: {}", get_download_retention_from_env());
+    println!("rate limits: {}", get_rate_limits_from_env());
+    println!("publish limits: {}", get_publish_limits_from_env());
+    println!("scanners: {}", get_scanners_from_env(make_reqwest()));
+    println!("webhooks: {}", get_webhooks_from_env(make_reqwest()).len());
+    match get_mailer_from_env(make_reqwest()) {
+        Some(mailer) => println!("notifications: {mailer}"),
+        None => println!("notifications: disabled"),
+    }
+    match get_provider_from_env() {
+        Some(provider) => println!("oauth provider: {provider}"),
+        None => println!("oauth provider: none"),
+    }
+    println!("admins: {}", get_admins_from_env().len());
+    println!("log format: {}", get_log_format_from_env());
+    println!("cors: {}", get_cors_policy_from_env());
+}
+
+/// The directory the registry's data is stored in
+fn data_dir() -> PathBuf {
+    let cwd = current_dir().unwrap();
+    PathBuf::from(benv!("DATA_DIR" => "{CWD}/data").replace("{CWD}", cwd.to_str().unwrap()))
+}
+
+/// Creates a user account, such as the first administrator of a registry using `USER_ACCOUNTS`
+async fn create_user(name: String) -> std::io::Result<()> {
+    let data_dir = data_dir();
+    fs::create_dir_all(&data_dir).await?;
+
+    let name = name.trim();
+    if name.is_empty() {
+        eprintln!("user name cannot be empty");
+        std::process::exit(1);
+    }
+
+    let accounts = Accounts::load(data_dir.join("accounts.json")).await?;
+    let Some((account, _, secret)) = accounts.create_user(name.to_string()).await? else {
+        eprintln!("a user named {name} already exists");
+        std::process::exit(1);
+    };
+
+    println!("created user {} with ID {}", account.name, account.id);
+    println!("API key: {secret}");
+    if benv!("USER_ACCOUNTS").is_err() {
+        println!("the registry only accepts the key once `USER_ACCOUNTS` is set");
+    }
+
+    Ok(())
+}
+
 async fn run() -> std::io::Result<()> {
     let address = benv!("ADDRESS" => "127.0.0.1");
     let port: u16 = benv!(parse "PORT" => "8080");
 
     let cwd = current_dir().unwrap();
-    let data_dir =
-        PathBuf::from(benv!("DATA_DIR" => "{CWD}/data").replace("{CWD}", cwd.to_str().unwrap()));
+    let data_dir = data_dir();
     fs::create_dir_all(&data_dir).await.unwrap();
 
     let project = Project::new(
@@ -104,9 +314,11 @@ async fn run() -> std::io::Result<()> {
             password: benv!(required "GIT_PASSWORD"),
         })),
     );
-    let source = PesdePackageSource::new(benv!(required "INDEX_REPO_URL").try_into().unwrap());
+    // the index is pushed to, which shallow clones can't do
+    let source = PesdePackageSource::new(benv!(required "INDEX_REPO_URL").try_into().unwrap())
+        .with_full_history();
     source
-        .refresh(&project)
+        .refresh(&project, &make_reqwest())
         .await
         .expect("failed to refresh source");
     let config = source
@@ -114,55 +326,379 @@ async fn run() -> std::io::Result<()> {
         .await
         .expect("failed to get index config");
 
-    let (search_reader, search_writer, query_parser) = make_search(&project, &source).await;
+    let downloads = {
+        let retention = get_download_retention_from_env();
+        tracing::info!("download retention: {retention}");
+        Downloads::load(data_dir.join("downloads.json"), retention)
+            .await
+            .expect("failed to load download counts")
+    };
+    let storage = {
+        let storage = get_storage_from_env();
+        tracing::info!("storage: {storage}");
+        Reloadable::new(storage)
+    };
+    let search_downloads = ranking_downloads(&downloads).await;
+    let (search_reader, search_writer, query_parser) =
+        make_search(&project, &source, &storage.get(), &search_downloads).await;
+    let dependents = Dependents::build(&source, &project).await;
+    let metadata_cache = if benv!("METADATA_CACHE").is_ok() {
+        let cache = MetadataCache::from_env(&source, &project, &data_dir).await;
+        tracing::info!("metadata cache: {} packages", cache.len());
+        Some(cache)
+    } else {
+        None
+    };
+    let (index_queue, index_jobs) = IndexQueue::load(data_dir.join("index_jobs.json"))
+        .await
+        .expect("failed to load index jobs");
 
     let app_data = web::Data::new(AppState {
-        storage: {
-            let storage = get_storage_from_env();
-            tracing::info!("storage: {storage}");
-            storage
-        },
+        storage,
         auth: {
-            let auth = get_auth_from_env(&config);
+            let auth = get_auth_from_env(&config, &data_dir).await;
             tracing::info!("auth: {auth}");
             auth
         },
-        source: tokio::sync::Mutex::new(source),
+        api_tokens: ApiTokens::load(data_dir.join("tokens.json"))
+            .await
+            .expect("failed to load API tokens"),
+        trusted_publishing: TrustedPublishing::load(
+            make_reqwest(),
+            benv!("OIDC_AUDIENCE" => config.api()),
+            data_dir.join("trusted_publishers.json"),
+        )
+        .await
+        .expect("failed to load trusted publishers"),
+        admins: get_admins_from_env(),
+        restrict_scope_creation: benv!("RESTRICT_SCOPE_CREATION").is_ok(),
+        teams: get_teams_from_env(make_reqwest()),
+        metrics_token: benv!("METRICS_TOKEN")
+            .ok()
+            .map(|token| Sha256::digest(token.as_bytes()).into()),
+        downloads,
+        dependents,
+        audit_log: AuditLog::open(data_dir.join("audit.jsonl"))
+            .await
+            .expect("failed to open audit log"),
+        moderation: Moderation::load(data_dir.join("moderation.json"))
+            .await
+            .expect("failed to load moderation data"),
+        index_queue,
+        metadata_cache,
+        upstream: {
+            let upstream = get_upstream_from_env(make_reqwest(), data_dir.join("upstream.json"))
+                .await
+                .expect("failed to load mirrored packages");
+            if let Some(upstream) = &upstream {
+                tracing::info!("upstream: {}", upstream.api_url());
+            }
+            upstream
+        },
+        uploads: {
+            let minutes: u64 = benv!(parse "UPLOAD_SESSION_TIMEOUT_MINUTES" => "60");
+            tracing::info!("upload session timeout: {minutes} minutes");
+            Uploads::new(
+                data_dir.join("uploads"),
+                std::time::Duration::from_secs(minutes * 60),
+            )
+            .await
+            .expect("failed to create uploads directory")
+        },
+        reconciler: Reconciler::default(),
+        rate_limits: {
+            let rate_limits = get_rate_limits_from_env();
+            tracing::info!("rate limits: {rate_limits}");
+            Reloadable::new(rate_limits)
+        },
+        publish_limits: {
+            let publish_limits = get_publish_limits_from_env();
+            tracing::info!("publish limits: {publish_limits}");
+            publish_limits
+        },
+        scanners: {
+            let scanners = get_scanners_from_env(make_reqwest());
+            tracing::info!("scanners: {scanners}");
+            scanners
+        },
+        webhooks: {
+            let webhooks = get_webhooks_from_env(make_reqwest());
+            tracing::info!("webhooks: {}", webhooks.len());
+            webhooks
+        },
+        notifications: {
+            let mailer = get_mailer_from_env(make_reqwest());
+            match &mailer {
+                Some(mailer) => tracing::info!("notifications: {mailer}"),
+                None => tracing::info!("notifications: disabled"),
+            }
+            Notifications::load(data_dir.join("notifications.json"), mailer)
+                .await
+                .expect("failed to load notification preferences")
+        },
+        source: tokio::sync::RwLock::new(source),
         project,
+        reqwest_client: make_reqwest(),
 
+        search_indexer: SearchIndexer::spawn(search_writer, search_reader.clone()),
         search_reader,
-        search_writer: std::sync::Mutex::new(search_writer),
         query_parser,
     });
 
-    let publish_governor_config = GovernorConfigBuilder::default()
-        .key_extractor(UserIdExtractor)
-        .burst_size(12)
-        .seconds_per_request(60)
-        .use_headers()
-        .finish()
-        .unwrap();
+    // kept outside of the server, so that the download counts can be written once it stops
+    let server_app_data = app_data.clone();
+
+    let worker = actix_web::rt::spawn(run_worker(app_data.clone(), index_jobs));
+
+    {
+        let app_data = app_data.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = app_data.downloads.flush().await {
+                    tracing::error!("failed to write download counts: {e}");
+                }
+            }
+        });
+    }
+
+    {
+        let app_data = app_data.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(CLEANUP_INTERVAL);
+            loop {
+                interval.tick().await;
+                app_data.rate_limits.get().retain_recent();
+            }
+        });
+    }
+
+    {
+        let app_data = app_data.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(CLEANUP_INTERVAL);
+            loop {
+                interval.tick().await;
+                app_data.uploads.remove_expired().await;
+            }
+        });
+    }
+
+    {
+        let minutes: u64 = benv!(parse "SEARCH_RANKING_REFRESH_MINUTES" => "60");
+        let app_data = app_data.clone();
+        actix_web::rt::spawn(async move {
+            let mut indexed = search_downloads;
+            let mut interval =
+                actix_web::rt::time::interval(std::time::Duration::from_secs(minutes * 60));
+            // the search index was just built, so the first tick is skipped
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                indexed = refresh_downloads(&app_data, indexed).await;
+            }
+        });
+    }
+
+    if app_data.metadata_cache.is_some() {
+        let minutes: u64 = benv!(parse "METADATA_CACHE_RECONCILE_MINUTES" => "15");
+        let app_data = app_data.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval =
+                actix_web::rt::time::interval(std::time::Duration::from_secs(minutes * 60));
+            // the cache was just built, so the first tick is skipped
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let source = app_data.write_source().await;
+                if let Err(e) = source
+                    .refresh(&app_data.project, &app_data.reqwest_client)
+                    .await
+                {
+                    tracing::error!("failed to refresh source for metadata cache: {e}");
+                    continue;
+                }
+
+                let cache = app_data.metadata_cache.as_ref().unwrap();
+                let stale = cache.reconcile(&source, &app_data.project).await;
+                if stale > 0 {
+                    tracing::warn!("metadata cache had {stale} stale packages");
+                }
+            }
+        });
+    }
+
+    if app_data.upstream.is_some() {
+        let minutes: u64 = benv!(parse "UPSTREAM_REFRESH_MINUTES" => "60");
+        let app_data = app_data.clone();
+        actix_web::rt::spawn(async move {
+            let period = std::time::Duration::from_secs(minutes * 60);
+            let mut interval = actix_web::rt::time::interval(period);
+            loop {
+                interval.tick().await;
+                let upstream = app_data.upstream.as_ref().unwrap();
+                let cutoff = chrono::Utc::now() - period;
+
+                for package in upstream.synced_before(cutoff).await {
+                    if let Err(e) = sync_package(&app_data, &package).await {
+                        tracing::error!("failed to sync {package} with upstream: {e}");
+                    }
+                }
+            }
+        });
+    }
+
+    let gc_interval_hours: Option<u64> = benv!(parse "GC_INTERVAL_HOURS").ok();
+    if let Some(hours) = gc_interval_hours {
+        let app_data = app_data.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval =
+                actix_web::rt::time::interval(std::time::Duration::from_secs(hours * 60 * 60));
+            // the first tick completes immediately, and collecting garbage while starting up
+            // isn't needed
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                match gc::collect_garbage(&app_data, false).await {
+                    Ok(Some(_)) => {}
+                    Ok(None) => {
+                        tracing::warn!("the storage backend doesn't support garbage collection");
+                        break;
+                    }
+                    Err(e) => tracing::error!("failed to collect garbage: {e}"),
+                }
+            }
+        });
+    }
+
+    let reconcile_interval_hours: Option<u64> = benv!(parse "RECONCILE_INTERVAL_HOURS").ok();
+    if let Some(hours) = reconcile_interval_hours {
+        let fix = benv!("RECONCILE_FIX").is_ok();
+        tracing::info!(
+            "reconciling every {hours} hours{}",
+            if fix { ", rolling back mismatches" } else { "" }
+        );
+
+        let app_data = app_data.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval =
+                actix_web::rt::time::interval(std::time::Duration::from_secs(hours * 60 * 60));
+            // the first tick completes immediately, and publishes interrupted by the last
+            // shutdown may still be resumed
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Err(e) = app_data.reconciler.reconcile(&app_data, fix).await {
+                    tracing::error!("failed to reconcile the index and storage: {e}");
+                }
+            }
+        });
+    }
+
+    #[cfg(unix)]
+    {
+        let app_data = app_data.clone();
+        actix_web::rt::spawn(async move {
+            use actix_web::rt::signal::unix::{signal, SignalKind};
+
+            let mut hangups = signal(SignalKind::hangup()).expect("failed to listen for SIGHUP");
+            while hangups.recv().await.is_some() {
+                if let Err(e) = reload::reload(&app_data) {
+                    tracing::error!("failed to reload configuration: {e}");
+                }
+            }
+        });
+    }
+
+    let cors_policy = get_cors_policy_from_env();
+    tracing::info!("cors: {cors_policy}");
+
+    let shutdown_timeout =
+        std::time::Duration::from_secs(benv!(parse "SHUTDOWN_TIMEOUT_SECONDS" => "30"));
+
+    let openapi = endpoints::openapi::ApiDoc::openapi();
+    let graphql_schema = web::Data::new(graphql::build_schema());
 
     HttpServer::new(move || {
         App::new()
             .wrap(sentry_actix::Sentry::with_transaction())
             .wrap(NormalizePath::new(TrailingSlash::Trim))
-            .wrap(Cors::permissive())
+            .wrap(cors_policy.middleware())
+            .wrap(from_fn(logging::access_log_mw))
+            .wrap(from_fn(telemetry::trace_id_mw))
             .wrap(tracing_actix_web::TracingLogger::default())
             .wrap(Compress::default())
-            .app_data(app_data.clone())
+            .wrap(from_fn(metrics::metrics_mw))
+            .app_data(server_app_data.clone())
+            .app_data(graphql_schema.clone())
             .route(
                 "/",
                 web::get().to(|| async {
                     concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"))
                 }),
             )
+            .route("/metrics", web::get().to(endpoints::metrics::get_metrics))
+            .route("/docs", web::get().to(endpoints::openapi::get_docs))
+            .service(
+                SwaggerUi::new("/docs/swagger-ui/{_:.*}")
+                    .url("/docs/openapi.json", openapi.clone()),
+            )
+            .route(
+                "/docs/{scope}/{name}",
+                web::get()
+                    .to(endpoints::docs_site::get_docs_root)
+                    .wrap(from_fn(auth::read_mw)),
+            )
+            .route(
+                "/docs/{scope}/{name}/{version}",
+                web::get()
+                    .to(endpoints::docs_site::get_docs_version)
+                    .wrap(from_fn(auth::read_mw)),
+            )
+            .route(
+                "/docs/{scope}/{name}/{version}/{page:.+}",
+                web::get()
+                    .to(endpoints::docs_site::get_docs_page)
+                    .wrap(from_fn(auth::read_mw)),
+            )
+            .route(
+                "/graphql",
+                web::get()
+                    .to(endpoints::graphql::get_graphql)
+                    .wrap(from_fn(rate_limit::search_mw))
+                    .wrap(from_fn(auth::read_mw)),
+            )
+            .route(
+                "/graphql",
+                web::post()
+                    .to(endpoints::graphql::post_graphql)
+                    .wrap(from_fn(rate_limit::search_mw))
+                    .wrap(from_fn(auth::read_mw)),
+            )
             .service(
                 web::scope("/v0")
+                    .route(
+                        "/readme.css",
+                        web::get().to(endpoints::package_readme::get_highlight_css),
+                    )
                     .route(
                         "/search",
                         web::get()
                             .to(endpoints::search::search_packages)
+                            .wrap(from_fn(rate_limit::search_mw))
+                            .wrap(from_fn(auth::read_mw)),
+                    )
+                    .route(
+                        "/index/{path:.*}",
+                        web::get()
+                            .to(endpoints::index::get_index_file)
+                            .wrap(from_fn(auth::read_mw)),
+                    )
+                    .route(
+                        "/docs/{hash}",
+                        web::get()
+                            .to(endpoints::doc::get_doc)
                             .wrap(from_fn(auth::read_mw)),
                     )
                     .route(
@@ -171,28 +707,410 @@ async fn run() -> std::io::Result<()> {
                             .to(endpoints::package_versions::get_package_versions)
                             .wrap(from_fn(auth::read_mw)),
                     )
+                    .route(
+                        "/packages/{name}/downloads",
+                        web::get()
+                            .to(endpoints::package_downloads::get_package_downloads)
+                            .wrap(from_fn(auth::read_mw)),
+                    )
+                    .route(
+                        "/packages/{name}/dependents",
+                        web::get()
+                            .to(endpoints::package_dependents::get_package_dependents)
+                            .wrap(from_fn(auth::read_mw)),
+                    )
+                    .route(
+                        "/packages/{name}/diff",
+                        web::get()
+                            .to(endpoints::package_diff::get_package_diff)
+                            .wrap(from_fn(auth::read_mw)),
+                    )
+                    .route(
+                        "/packages/{name}/deprecate",
+                        web::patch()
+                            .to(endpoints::deprecate_version::deprecate_package)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/packages/{name}/undeprecate",
+                        web::patch()
+                            .to(endpoints::deprecate_version::undeprecate_package)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/packages/{name}/owners",
+                        web::get()
+                            .to(endpoints::owners::get_package_owners)
+                            .wrap(from_fn(auth::read_mw)),
+                    )
+                    .route(
+                        "/packages/{name}/owners",
+                        web::post()
+                            .to(endpoints::owners::add_package_owner)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/packages/{name}/owners",
+                        web::delete()
+                            .to(endpoints::owners::remove_package_owner)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/packages/{name}/trusted-publishers",
+                        web::get()
+                            .to(endpoints::trusted_publishers::get_trusted_publishers)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/packages/{name}/trusted-publishers",
+                        web::post()
+                            .to(endpoints::trusted_publishers::add_trusted_publisher)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/packages/{name}/trusted-publishers/{id}",
+                        web::delete()
+                            .to(endpoints::trusted_publishers::remove_trusted_publisher)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
                     .route(
                         "/packages/{name}/{version}/{target}",
                         web::get()
                             .to(endpoints::package_version::get_package_version)
+                            .wrap(from_fn(rate_limit::download_mw))
+                            .wrap(from_fn(auth::read_mw)),
+                    )
+                    .route(
+                        "/packages/{name}/{version}/{target}/archive/{checksum}",
+                        web::get()
+                            .to(endpoints::package_archive::get_package_archive)
+                            .wrap(from_fn(rate_limit::download_mw))
+                            .wrap(from_fn(auth::read_mw)),
+                    )
+                    .route(
+                        "/packages/{name}/{version}/{target}/attestation",
+                        web::get()
+                            .to(endpoints::package_attestation::get_package_attestation)
+                            .wrap(from_fn(auth::read_mw)),
+                    )
+                    .route(
+                        "/packages/{name}/{version}/{target}/files",
+                        web::get()
+                            .to(endpoints::package_files::get_package_files)
+                            .wrap(from_fn(auth::read_mw)),
+                    )
+                    .route(
+                        "/packages/{name}/{version}/{target}/readme",
+                        web::get()
+                            .to(endpoints::package_readme::get_package_readme)
+                            .wrap(from_fn(auth::read_mw)),
+                    )
+                    .route(
+                        "/packages/{name}/{version}/{target}/yank",
+                        web::patch()
+                            .to(endpoints::yank_version::yank_package_version)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/packages/{name}/{version}/{target}/unyank",
+                        web::patch()
+                            .to(endpoints::yank_version::unyank_package_version)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/popular",
+                        web::get()
+                            .to(endpoints::rankings::get_popular)
+                            .wrap(from_fn(auth::read_mw)),
+                    )
+                    .route(
+                        "/trending",
+                        web::get()
+                            .to(endpoints::rankings::get_trending)
+                            .wrap(from_fn(auth::read_mw)),
+                    )
+                    .route(
+                        "/users/{id}/packages",
+                        web::get()
+                            .to(endpoints::users::get_user_packages)
+                            .wrap(from_fn(auth::read_mw)),
+                    )
+                    .route(
+                        "/scopes/{scope}",
+                        web::get()
+                            .to(endpoints::scope::get_scope)
+                            .wrap(from_fn(auth::read_mw)),
+                    )
+                    .route(
+                        "/scopes/{scope}/owners",
+                        web::get()
+                            .to(endpoints::owners::get_scope_owners)
                             .wrap(from_fn(auth::read_mw)),
                     )
+                    .route(
+                        "/scopes/{scope}/owners",
+                        web::post()
+                            .to(endpoints::owners::add_scope_owner)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/scopes/{scope}/owners",
+                        web::delete()
+                            .to(endpoints::owners::remove_scope_owner)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/moderation/removals",
+                        web::get()
+                            .to(endpoints::moderation::get_removals)
+                            .wrap(from_fn(auth::read_mw)),
+                    )
+                    .route(
+                        "/moderation/removals",
+                        web::post()
+                            .to(endpoints::moderation::remove)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/moderation/bans",
+                        web::get()
+                            .to(endpoints::moderation::get_bans)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/moderation/bans/{user_id}",
+                        web::put()
+                            .to(endpoints::moderation::ban_user)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/moderation/bans/{user_id}",
+                        web::delete()
+                            .to(endpoints::moderation::unban_user)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/storage/gc",
+                        web::post()
+                            .to(endpoints::gc::run_gc)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/admin/reindex",
+                        web::post()
+                            .to(endpoints::reindex::start_reindex)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/admin/reload",
+                        web::post()
+                            .to(endpoints::reload::reload_config)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/admin/reconcile",
+                        web::get()
+                            .to(endpoints::reconcile::get_reconcile_report)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/admin/reconcile",
+                        web::post()
+                            .to(endpoints::reconcile::run_reconcile)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .service(
+                        web::resource("/admin/import")
+                            .app_data(PayloadConfig::new(
+                                endpoints::import_index::MAX_SNAPSHOT_SIZE,
+                            ))
+                            .route(
+                                web::post()
+                                    .to(endpoints::import_index::import_index)
+                                    .wrap(from_fn(auth::write_mw)),
+                            ),
+                    )
+                    .route(
+                        "/admin/rollback",
+                        web::post()
+                            .to(endpoints::rollback::rollback_publish)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/admin/reindex",
+                        web::get()
+                            .to(endpoints::reindex::get_reindex_progress)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/admin/users",
+                        web::get()
+                            .to(endpoints::accounts::get_users)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/admin/users",
+                        web::post()
+                            .to(endpoints::accounts::create_user)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/admin/users/{id}",
+                        web::delete()
+                            .to(endpoints::accounts::delete_user)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/admin/users/{id}/keys",
+                        web::post()
+                            .to(endpoints::accounts::create_key)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/admin/users/{id}/keys/{key_id}",
+                        web::delete()
+                            .to(endpoints::accounts::revoke_key)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/tokens",
+                        web::get()
+                            .to(endpoints::tokens::get_tokens)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/tokens",
+                        web::post()
+                            .to(endpoints::tokens::create_token)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/tokens/{id}",
+                        web::delete()
+                            .to(endpoints::tokens::revoke_token)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/notifications",
+                        web::get()
+                            .to(endpoints::notifications::get_preferences)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/notifications",
+                        web::put()
+                            .to(endpoints::notifications::update_preferences)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/audit",
+                        web::get()
+                            .to(endpoints::audit::get_audit_log)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/audit/me",
+                        web::get()
+                            .to(endpoints::audit::get_own_activity)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/webhooks/deliveries",
+                        web::get()
+                            .to(endpoints::webhooks::get_deliveries)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/webhooks/deliveries/{id}/redeliver",
+                        web::post()
+                            .to(endpoints::webhooks::redeliver)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/jobs/{id}",
+                        web::get()
+                            .to(endpoints::jobs::get_job)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
+                    .route(
+                        "/oidc/token",
+                        web::post()
+                            .to(endpoints::trusted_publishers::exchange_oidc_token)
+                            .wrap(from_fn(auth::write_mw)),
+                    )
                     .service(
                         web::scope("/packages")
                             .app_data(PayloadConfig::new(config.max_archive_size))
+                            .route(
+                                "",
+                                web::get()
+                                    .to(endpoints::packages::list_packages)
+                                    .wrap(from_fn(auth::read_mw)),
+                            )
                             .route(
                                 "",
                                 web::post()
                                     .to(endpoints::publish_version::publish_package)
-                                    .wrap(Governor::new(&publish_governor_config))
+                                    .wrap(from_fn(rate_limit::publish_mw))
+                                    .wrap(from_fn(auth::write_mw)),
+                            ),
+                    )
+                    .service(
+                        web::scope("/uploads")
+                            .app_data(PayloadConfig::new(config.max_archive_size))
+                            .route(
+                                "",
+                                web::post()
+                                    .to(endpoints::uploads::create_upload)
+                                    .wrap(from_fn(auth::write_mw)),
+                            )
+                            .route(
+                                "/{id}",
+                                web::get()
+                                    .to(endpoints::uploads::get_upload)
+                                    .wrap(from_fn(auth::write_mw)),
+                            )
+                            .route(
+                                "/{id}",
+                                web::put()
+                                    .to(endpoints::uploads::put_chunk)
+                                    .wrap(from_fn(auth::write_mw)),
+                            )
+                            .route(
+                                "/{id}",
+                                web::delete()
+                                    .to(endpoints::uploads::cancel_upload)
+                                    .wrap(from_fn(auth::write_mw)),
+                            )
+                            .route(
+                                "/{id}/finalize",
+                                web::post()
+                                    .to(endpoints::uploads::finalize_upload)
+                                    .wrap(from_fn(rate_limit::publish_mw))
                                     .wrap(from_fn(auth::write_mw)),
                             ),
                     ),
             )
     })
     .bind((address, port))?
+    .shutdown_timeout(shutdown_timeout.as_secs())
     .run()
-    .await
+    .await?;
+
+    // the server stops accepting requests on SIGINT or SIGTERM, and returns once the requests in
+    // flight completed. The jobs which are still queued are committed after that, and those which
+    // don't finish in time are committed once the registry restarts
+    tracing::info!("shutting down");
+    app_data.index_queue.close();
+    if actix_web::rt::time::timeout(shutdown_timeout, worker)
+        .await
+        .is_err()
+    {
+        tracing::warn!("index jobs didn't finish in time, they'll be committed on restart");
+    }
+    app_data.search_indexer.flush().await;
+
+    app_data.downloads.flush().await
 }
 
 // can't use #[actix_web::main] because of Sentry:
@@ -201,6 +1119,28 @@ async fn run() -> std::io::Result<()> {
 fn main() -> std::io::Result<()> {
     let _ = dotenvy::dotenv();
 
+    let args = Args::parse();
+    if let Some(path) = &args.config {
+        if let Err(e) = config::load(path) {
+            eprintln!("invalid configuration: {e}");
+            std::process::exit(1);
+        }
+    }
+    if let Err(e) = config::validate() {
+        eprintln!("invalid configuration: {e}");
+        std::process::exit(1);
+    }
+
+    if args.check_config {
+        check_config();
+        println!("configuration is valid");
+        return Ok(());
+    }
+
+    if let Some(name) = args.create_user {
+        return System::new().block_on(create_user(name));
+    }
+
     let tracing_env_filter = EnvFilter::builder()
         .with_default_directive(LevelFilter::INFO.into())
         .from_env_lossy()
@@ -212,14 +1152,26 @@ fn main() -> std::io::Result<()> {
         .add_directive("hyper=info".parse().unwrap())
         .add_directive("h2=info".parse().unwrap());
 
+    let tracer_provider = telemetry::get_tracer_provider_from_env();
+    let log_format = get_log_format_from_env();
+
     tracing_subscriber::registry()
         .with(tracing_env_filter)
-        .with(
+        .with((log_format == LogFormat::Text).then(|| {
             tracing_subscriber::fmt::layer()
                 .compact()
-                .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE),
-        )
+                .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
+        }))
+        // requests are logged once they complete, so the spans' events are left out
+        .with((log_format == LogFormat::Json).then(|| {
+            tracing_subscriber::fmt::layer()
+                .json()
+                .flatten_event(true)
+                .with_current_span(true)
+                .with_span_list(false)
+        }))
         .with(sentry::integrations::tracing::layer())
+        .with(tracer_provider.as_ref().map(telemetry::layer))
         .init();
 
     let guard = sentry::init(sentry::ClientOptions {
@@ -238,5 +1190,14 @@ fn main() -> std::io::Result<()> {
         tracing::info!("sentry **NOT** initialized");
     }
 
-    System::new().block_on(run())
+    let result = System::new().block_on(run());
+
+    // the spans which weren't exported yet are exported before exiting
+    if let Some(provider) = tracer_provider {
+        if let Err(e) = provider.shutdown() {
+            tracing::error!("failed to export remaining spans: {e}");
+        }
+    }
+
+    result
 }
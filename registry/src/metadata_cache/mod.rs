@@ -0,0 +1,296 @@
+use crate::{benv, error::Error, search::all_packages, upstream::mirror_package, AppState};
+use futures::StreamExt;
+use pesde::{
+    names::PackageName,
+    source::{
+        git_index::{errors::ReadFile, read_file, root_tree, GitBasedSource},
+        pesde::{IndexFile, PesdePackageSource, SCOPE_INFO_FILE},
+    },
+    Project,
+};
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+/// Where the metadata cache keeps the index files
+#[derive(Debug)]
+enum Backend {
+    Memory(std::sync::RwLock<HashMap<PackageName, Arc<IndexFile>>>),
+    #[cfg(feature = "sqlite")]
+    Sqlite(sqlite::SqliteCache),
+}
+
+/// A cache of the index files of packages, so that read endpoints don't need to open the index
+/// and parse the files on every request. It's filled when the registry starts, updated whenever
+/// the registry changes a package, and reconciled with the index periodically to pick up
+/// changes made to the index by others. The files are kept in memory, or in a SQLite database
+/// when the registry is built with the `sqlite` feature, which doesn't hold the whole index in
+/// memory. Database errors are logged and treated as misses, so reads fall back to the index
+#[derive(Debug)]
+pub struct MetadataCache {
+    backend: Backend,
+}
+
+impl MetadataCache {
+    async fn read_all(
+        source: &PesdePackageSource,
+        project: &Project,
+    ) -> HashMap<PackageName, IndexFile> {
+        all_packages(source, project).await.collect().await
+    }
+
+    /// Builds the cache configured by the `METADATA_CACHE` environment variable, filling it from
+    /// the index
+    #[cfg_attr(not(feature = "sqlite"), allow(unused_variables))]
+    pub async fn from_env(source: &PesdePackageSource, project: &Project, data_dir: &Path) -> Self {
+        let cache = match benv!("METADATA_CACHE").as_deref() {
+            #[cfg(feature = "sqlite")]
+            Ok("sqlite") => {
+                let path = benv!(parse "METADATA_CACHE_PATH")
+                    .unwrap_or_else(|_| data_dir.join("metadata_cache.sqlite"));
+                let cache = sqlite::SqliteCache::open(&path).unwrap_or_else(|e| {
+                    panic!("failed to open metadata cache at {}: {e}", path.display())
+                });
+                Self {
+                    backend: Backend::Sqlite(cache),
+                }
+            }
+            #[cfg(not(feature = "sqlite"))]
+            Ok("sqlite") => panic!(
+                "Setting `METADATA_CACHE` to `sqlite` requires the registry to be built with the `sqlite` feature"
+            ),
+            _ => Self {
+                backend: Backend::Memory(Default::default()),
+            },
+        };
+
+        cache.reconcile(source, project).await;
+        cache
+    }
+
+    /// The amount of cached packages
+    pub fn len(&self) -> usize {
+        match &self.backend {
+            Backend::Memory(files) => files.read().unwrap().len(),
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite(cache) => cache.len().unwrap_or_else(|e| {
+                tracing::error!("failed to count metadata cache entries: {e}");
+                0
+            }),
+        }
+    }
+
+    pub fn get(&self, name: &PackageName) -> Option<Arc<IndexFile>> {
+        match &self.backend {
+            Backend::Memory(files) => files.read().unwrap().get(name).cloned(),
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite(cache) => match cache.get(name) {
+                Ok(file) => file.map(Arc::new),
+                Err(e) => {
+                    tracing::error!("failed to read {name} from metadata cache: {e}");
+                    None
+                }
+            },
+        }
+    }
+
+    /// The cached files of every package
+    pub fn all(&self) -> Vec<(PackageName, Arc<IndexFile>)> {
+        match &self.backend {
+            Backend::Memory(files) => files
+                .read()
+                .unwrap()
+                .iter()
+                .map(|(name, file)| (name.clone(), file.clone()))
+                .collect(),
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite(cache) => match cache.all() {
+                Ok(files) => files
+                    .into_iter()
+                    .map(|(name, file)| (name, Arc::new(file)))
+                    .collect(),
+                Err(e) => {
+                    tracing::error!("failed to read metadata cache: {e}");
+                    vec![]
+                }
+            },
+        }
+    }
+
+    /// Updates the cached file of a package after its versions changed. Packages without any
+    /// versions are removed from the cache
+    pub fn update(&self, name: &PackageName, file: &IndexFile) {
+        match &self.backend {
+            Backend::Memory(files) => {
+                let mut files = files.write().unwrap();
+
+                if file.is_empty() {
+                    files.remove(name);
+                } else {
+                    files.insert(name.clone(), Arc::new(file.clone()));
+                }
+            }
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite(cache) => {
+                if let Err(e) = cache.set(name, Some(file).filter(|file| !file.is_empty())) {
+                    tracing::error!("failed to update {name} in metadata cache: {e}");
+                }
+            }
+        }
+    }
+
+    /// Replaces the cached files with those of the index, returning how many packages differed
+    pub async fn reconcile(&self, source: &PesdePackageSource, project: &Project) -> usize {
+        let fresh = Self::read_all(source, project).await;
+
+        match &self.backend {
+            Backend::Memory(files) => {
+                let mut files = files.write().unwrap();
+
+                let stale = files
+                    .iter()
+                    .filter(|(name, file)| fresh.get(*name) != Some(&***file))
+                    .count()
+                    + fresh
+                        .keys()
+                        .filter(|name| !files.contains_key(*name))
+                        .count();
+
+                *files = fresh
+                    .into_iter()
+                    .map(|(name, file)| (name, Arc::new(file)))
+                    .collect();
+
+                stale
+            }
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite(cache) => cache.replace_all(&fresh).unwrap_or_else(|e| {
+                tracing::error!("failed to reconcile metadata cache: {e}");
+                0
+            }),
+        }
+    }
+}
+
+/// Reads the index file of a package, mirroring it from the upstream registry if this registry
+/// doesn't have it. Returns `None` if the package doesn't exist
+pub async fn read_package(
+    app_state: &AppState,
+    name: &PackageName,
+) -> Result<Option<IndexFile>, Error> {
+    match read_local_package(app_state, name).await? {
+        Some(file) => Ok(Some(file)),
+        None => mirror_package(app_state, name).await,
+    }
+}
+
+/// Reads the index file of a package, from the metadata cache if it's enabled and falling back
+/// to the index otherwise. Returns `None` if the package doesn't exist
+pub async fn read_local_package(
+    app_state: &AppState,
+    name: &PackageName,
+) -> Result<Option<IndexFile>, Error> {
+    if let Some(file) = app_state
+        .metadata_cache
+        .as_ref()
+        .and_then(|cache| cache.get(name))
+    {
+        return Ok(Some(IndexFile::clone(&file)));
+    }
+
+    let (scope, name_part) = name.as_str();
+    let file: Option<IndexFile> = {
+        let source = app_state.read_source().await;
+        let repo = gix::open(source.path(&app_state.project))?;
+        let tree = root_tree(&repo)?;
+
+        match read_file(&tree, [scope, name_part])? {
+            Some(file) => Some(toml::de::from_str(&file)?),
+            None => None,
+        }
+    };
+
+    if let (Some(cache), Some(file)) = (&app_state.metadata_cache, &file) {
+        cache.update(name, file);
+    }
+
+    Ok(file)
+}
+
+/// Reads the index files of every package this registry has, from the metadata cache if it's
+/// enabled and from the index otherwise
+pub async fn read_all_packages(app_state: &AppState) -> Vec<(PackageName, Arc<IndexFile>)> {
+    if let Some(cache) = &app_state.metadata_cache {
+        return cache.all();
+    }
+
+    let source = app_state.read_source().await;
+    all_packages(&source, &app_state.project)
+        .await
+        .map(|(name, file)| (name, Arc::new(file)))
+        .collect()
+        .await
+}
+
+/// Reads the index files of the packages of a scope, from the metadata cache if it's enabled and
+/// from the index otherwise. Returns `None` if the scope doesn't exist
+pub async fn read_scope_packages(
+    app_state: &AppState,
+    scope: &str,
+) -> Result<Option<Vec<(PackageName, Arc<IndexFile>)>>, Error> {
+    let source = app_state.read_source().await;
+    let repo = gix::open(source.path(&app_state.project))?;
+    let tree = root_tree(&repo)?;
+
+    let Some(entry) = tree
+        .lookup_entry([scope])
+        .map_err(|e| ReadFile::Lookup(scope.to_string(), e))?
+    else {
+        return Ok(None);
+    };
+    let object = entry
+        .object()
+        .map_err(|e| ReadFile::Lookup(scope.to_string(), e))?;
+    if !matches!(object.kind, gix::object::Kind::Tree) {
+        return Ok(None);
+    }
+
+    let mut packages = vec![];
+    for entry in object.into_tree().iter() {
+        let entry = entry?;
+        let file_name = entry.filename().to_string();
+        if file_name == SCOPE_INFO_FILE {
+            continue;
+        }
+
+        let Ok(name) = format!("{scope}/{file_name}").parse::<PackageName>() else {
+            continue;
+        };
+
+        if let Some(file) = app_state
+            .metadata_cache
+            .as_ref()
+            .and_then(|cache| cache.get(&name))
+        {
+            packages.push((name, file));
+            continue;
+        }
+
+        let Some(file) = read_file(&tree, [scope, &file_name])? else {
+            continue;
+        };
+        let file: IndexFile = toml::de::from_str(&file)?;
+        update_cached_package(app_state, &name, &file);
+        packages.push((name, Arc::new(file)));
+    }
+
+    Ok(Some(packages))
+}
+
+/// Updates the metadata cache, if it's enabled, after a package's versions changed
+pub fn update_cached_package(app_state: &AppState, name: &PackageName, file: &IndexFile) {
+    if let Some(cache) = &app_state.metadata_cache {
+        cache.update(name, file);
+    }
+}
@@ -0,0 +1,173 @@
+use pesde::{names::PackageName, source::pesde::IndexFile};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{collections::HashMap, path::Path, sync::Mutex};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("database error")]
+    Database(#[from] rusqlite::Error),
+
+    #[error("failed to serialize index file")]
+    Serialize(#[from] toml::ser::Error),
+
+    #[error("failed to deserialize index file")]
+    Deserialize(#[from] toml::de::Error),
+}
+
+/// The index files of packages stored in a SQLite database, as the same TOML they have in the
+/// index. Queries are short, so they're run on the calling thread
+#[derive(Debug)]
+pub struct SqliteCache {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteCache {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS packages (
+                name TEXT PRIMARY KEY NOT NULL,
+                file TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub fn len(&self) -> Result<usize, Error> {
+        Ok(self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM packages", [], |row| row.get(0))?)
+    }
+
+    pub fn get(&self, name: &PackageName) -> Result<Option<IndexFile>, Error> {
+        let file = self
+            .conn
+            .lock()
+            .unwrap()
+            .prepare_cached("SELECT file FROM packages WHERE name = ?1")?
+            .query_row([name.to_string()], |row| row.get::<_, String>(0))
+            .optional()?;
+
+        Ok(file.map(|file| toml::from_str(&file)).transpose()?)
+    }
+
+    /// The files of every package. Rows whose name or file can't be parsed are skipped
+    pub fn all(&self) -> Result<Vec<(PackageName, IndexFile)>, Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached("SELECT name, file FROM packages")?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(name, file)| Some((name.parse().ok()?, toml::from_str(&file).ok()?)))
+            .collect())
+    }
+
+    /// Stores the file of a package, or removes the package if it's `None`
+    pub fn set(&self, name: &PackageName, file: Option<&IndexFile>) -> Result<(), Error> {
+        let conn = self.conn.lock().unwrap();
+
+        match file {
+            Some(file) => conn
+                .prepare_cached(
+                    "INSERT INTO packages (name, file) VALUES (?1, ?2)
+                    ON CONFLICT (name) DO UPDATE SET file = excluded.file",
+                )?
+                .execute(params![name.to_string(), toml::to_string(file)?])?,
+            None => conn
+                .prepare_cached("DELETE FROM packages WHERE name = ?1")?
+                .execute([name.to_string()])?,
+        };
+
+        Ok(())
+    }
+
+    /// Replaces every stored file with the given ones, returning how many packages differed
+    pub fn replace_all(&self, files: &HashMap<PackageName, IndexFile>) -> Result<usize, Error> {
+        let serialized = files
+            .iter()
+            .map(|(name, file)| Ok((name.to_string(), toml::to_string(file)?)))
+            .collect::<Result<HashMap<_, _>, Error>>()?;
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        let existing = tx
+            .prepare("SELECT name, file FROM packages")?
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<HashMap<_, _>, _>>()?;
+
+        let mut stale = 0;
+        {
+            let mut delete = tx.prepare("DELETE FROM packages WHERE name = ?1")?;
+            for name in existing.keys() {
+                if !serialized.contains_key(name) {
+                    delete.execute([name])?;
+                    stale += 1;
+                }
+            }
+
+            let mut upsert = tx.prepare(
+                "INSERT INTO packages (name, file) VALUES (?1, ?2)
+                ON CONFLICT (name) DO UPDATE SET file = excluded.file",
+            )?;
+            for (name, file) in &serialized {
+                if existing.get(name) != Some(file) {
+                    upsert.execute([name, file])?;
+                    stale += 1;
+                }
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(stale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(version: &str) -> IndexFile {
+        toml::from_str(&format!(
+            "[\"{version} luau\"]\n\
+             target = {{ environment = \"luau\", lib = \"init.luau\" }}\n\
+             published_at = \"2024-01-01T00:00:00Z\""
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn stores_and_reconciles_files() {
+        let cache = SqliteCache::open(Path::new(":memory:")).unwrap();
+        let foo = "acme/foo".parse::<PackageName>().unwrap();
+        let bar = "acme/bar".parse::<PackageName>().unwrap();
+
+        cache.set(&foo, Some(&file("1.0.0"))).unwrap();
+        assert_eq!(cache.get(&foo).unwrap(), Some(file("1.0.0")));
+        assert_eq!(cache.get(&bar).unwrap(), None);
+
+        let fresh = HashMap::from([(foo.clone(), file("1.0.0")), (bar.clone(), file("2.0.0"))]);
+        assert_eq!(cache.replace_all(&fresh).unwrap(), 1);
+        assert_eq!(cache.replace_all(&fresh).unwrap(), 0);
+        assert_eq!(cache.len().unwrap(), 2);
+
+        cache.set(&foo, None).unwrap();
+        assert_eq!(cache.all().unwrap(), vec![(bar, file("2.0.0"))]);
+    }
+}
@@ -0,0 +1,364 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    error::Error as ActixError,
+    middleware::Next,
+};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tracing::Instrument;
+
+/// The upper bounds of the buckets of latency histograms, in seconds
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The registry's metrics. They're global rather than part of the app state, so that code without
+/// access to it (such as storage backends) can be instrumented too
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+#[derive(Debug, Clone)]
+struct Histogram {
+    /// The amount of observations in each bucket, not including those of the previous buckets
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: [0; LATENCY_BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn render(&self, output: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(self.buckets) {
+            cumulative += count;
+            writeln!(
+                output,
+                "{name}_bucket{{{labels}{separator}le=\"{bound}\"}} {cumulative}"
+            )
+            .unwrap();
+        }
+        writeln!(
+            output,
+            "{name}_bucket{{{labels}{separator}le=\"+Inf\"}} {}",
+            self.count
+        )
+        .unwrap();
+        let label_set = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{labels}}}")
+        };
+        writeln!(output, "{name}_sum{label_set} {}", self.sum).unwrap();
+        writeln!(output, "{name}_count{label_set} {}", self.count).unwrap();
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn header(output: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(output, "# HELP {name} {help}").unwrap();
+    writeln!(output, "# TYPE {name} {kind}").unwrap();
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Requests, by method, route and status
+    requests: Mutex<BTreeMap<(String, String, u16), u64>>,
+    /// Request latencies, by method and route
+    request_durations: Mutex<BTreeMap<(String, String), Histogram>>,
+    publishes: AtomicU64,
+    search_durations: Mutex<Histogram>,
+    /// Storage operation latencies, by operation
+    storage_durations: Mutex<BTreeMap<&'static str, Histogram>>,
+    /// How long requests waited for the index lock, by whether they needed to write
+    index_lock_waits: Mutex<BTreeMap<&'static str, Histogram>>,
+    gc_deleted_files: AtomicU64,
+    gc_reclaimed_bytes: AtomicU64,
+    /// Indexed versions whose archive was missing in the last reconciliation
+    reconcile_missing_archives: AtomicU64,
+    /// Stored versions which weren't in the index in the last reconciliation
+    reconcile_unindexed_versions: AtomicU64,
+    reconcile_fixed: AtomicU64,
+}
+
+impl Metrics {
+    pub fn record_publish(&self) {
+        self.publishes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_gc(&self, deleted_files: u64, reclaimed_bytes: u64) {
+        self.gc_deleted_files
+            .fetch_add(deleted_files, Ordering::Relaxed);
+        self.gc_reclaimed_bytes
+            .fetch_add(reclaimed_bytes, Ordering::Relaxed);
+    }
+
+    pub fn record_reconcile(&self, missing_archives: u64, unindexed_versions: u64, fixed: u64) {
+        self.reconcile_missing_archives
+            .store(missing_archives, Ordering::Relaxed);
+        self.reconcile_unindexed_versions
+            .store(unindexed_versions, Ordering::Relaxed);
+        self.reconcile_fixed.fetch_add(fixed, Ordering::Relaxed);
+    }
+
+    pub fn observe_search(&self, duration: Duration) {
+        self.search_durations.lock().unwrap().observe(duration);
+    }
+
+    pub fn observe_index_lock_wait(&self, mode: &'static str, duration: Duration) {
+        self.index_lock_waits
+            .lock()
+            .unwrap()
+            .entry(mode)
+            .or_default()
+            .observe(duration);
+    }
+
+    /// Runs a storage operation in a span of its own, recording how long it took
+    pub async fn time_storage<T>(
+        &self,
+        operation: &'static str,
+        fut: impl Future<Output = T>,
+    ) -> T {
+        let start = Instant::now();
+        let result = fut
+            .instrument(tracing::info_span!("storage", operation))
+            .await;
+
+        self.storage_durations
+            .lock()
+            .unwrap()
+            .entry(operation)
+            .or_default()
+            .observe(start.elapsed());
+
+        result
+    }
+
+    fn record_request(&self, method: String, route: String, status: u16, duration: Duration) {
+        *self
+            .requests
+            .lock()
+            .unwrap()
+            .entry((method.clone(), route.clone(), status))
+            .or_default() += 1;
+
+        self.request_durations
+            .lock()
+            .unwrap()
+            .entry((method, route))
+            .or_default()
+            .observe(duration);
+    }
+
+    /// The metrics in Prometheus' text exposition format
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+
+        header(
+            &mut output,
+            "pesde_registry_http_requests_total",
+            "counter",
+            "HTTP requests handled, by method, route and status",
+        );
+        for ((method, route, status), count) in self.requests.lock().unwrap().iter() {
+            writeln!(
+                output,
+                "pesde_registry_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{status}\"}} {count}",
+                escape(method),
+                escape(route),
+            )
+            .unwrap();
+        }
+
+        header(
+            &mut output,
+            "pesde_registry_http_request_duration_seconds",
+            "histogram",
+            "Latency of HTTP requests, by method and route",
+        );
+        for ((method, route), histogram) in self.request_durations.lock().unwrap().iter() {
+            histogram.render(
+                &mut output,
+                "pesde_registry_http_request_duration_seconds",
+                &format!("method=\"{}\",route=\"{}\"", escape(method), escape(route)),
+            );
+        }
+
+        header(
+            &mut output,
+            "pesde_registry_publishes_total",
+            "counter",
+            "Packages published",
+        );
+        writeln!(
+            output,
+            "pesde_registry_publishes_total {}",
+            self.publishes.load(Ordering::Relaxed)
+        )
+        .unwrap();
+
+        header(
+            &mut output,
+            "pesde_registry_search_duration_seconds",
+            "histogram",
+            "Latency of searches",
+        );
+        self.search_durations.lock().unwrap().render(
+            &mut output,
+            "pesde_registry_search_duration_seconds",
+            "",
+        );
+
+        header(
+            &mut output,
+            "pesde_registry_storage_duration_seconds",
+            "histogram",
+            "Latency of storage operations, by operation",
+        );
+        for (operation, histogram) in self.storage_durations.lock().unwrap().iter() {
+            histogram.render(
+                &mut output,
+                "pesde_registry_storage_duration_seconds",
+                &format!("operation=\"{operation}\""),
+            );
+        }
+
+        header(
+            &mut output,
+            "pesde_registry_index_lock_wait_seconds",
+            "histogram",
+            "Time spent waiting for the index lock, by lock mode",
+        );
+        for (mode, histogram) in self.index_lock_waits.lock().unwrap().iter() {
+            histogram.render(
+                &mut output,
+                "pesde_registry_index_lock_wait_seconds",
+                &format!("mode=\"{mode}\""),
+            );
+        }
+
+        header(
+            &mut output,
+            "pesde_registry_gc_deleted_files_total",
+            "counter",
+            "Orphaned files deleted by garbage collection",
+        );
+        writeln!(
+            output,
+            "pesde_registry_gc_deleted_files_total {}",
+            self.gc_deleted_files.load(Ordering::Relaxed)
+        )
+        .unwrap();
+
+        header(
+            &mut output,
+            "pesde_registry_gc_reclaimed_bytes_total",
+            "counter",
+            "Bytes reclaimed by garbage collection",
+        );
+        writeln!(
+            output,
+            "pesde_registry_gc_reclaimed_bytes_total {}",
+            self.gc_reclaimed_bytes.load(Ordering::Relaxed)
+        )
+        .unwrap();
+
+        header(
+            &mut output,
+            "pesde_registry_reconcile_missing_archives",
+            "gauge",
+            "Indexed versions whose archive was missing in the last reconciliation",
+        );
+        writeln!(
+            output,
+            "pesde_registry_reconcile_missing_archives {}",
+            self.reconcile_missing_archives.load(Ordering::Relaxed)
+        )
+        .unwrap();
+
+        header(
+            &mut output,
+            "pesde_registry_reconcile_unindexed_versions",
+            "gauge",
+            "Stored versions which weren't in the index in the last reconciliation",
+        );
+        writeln!(
+            output,
+            "pesde_registry_reconcile_unindexed_versions {}",
+            self.reconcile_unindexed_versions.load(Ordering::Relaxed)
+        )
+        .unwrap();
+
+        header(
+            &mut output,
+            "pesde_registry_reconcile_fixed_total",
+            "counter",
+            "Partially published versions rolled back by reconciliation",
+        );
+        writeln!(
+            output,
+            "pesde_registry_reconcile_fixed_total {}",
+            self.reconcile_fixed.load(Ordering::Relaxed)
+        )
+        .unwrap();
+
+        output
+    }
+}
+
+pub async fn metrics_mw(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, ActixError> {
+    let start = Instant::now();
+    let method = req.method().to_string();
+
+    let result = next.call(req).await;
+
+    let (route, status) = match &result {
+        Ok(res) => (res.request().match_pattern(), res.status()),
+        Err(e) => (None, e.as_response_error().status_code()),
+    };
+    METRICS.record_request(
+        method,
+        route.unwrap_or_else(|| "unmatched".to_string()),
+        status.as_u16(),
+        start.elapsed(),
+    );
+
+    result
+}
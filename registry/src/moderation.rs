@@ -0,0 +1,163 @@
+use crate::{
+    auth::UserId, endpoints::openapi::shared::Target, error::ErrorResponse, write_json_atomically,
+};
+use actix_web::HttpResponse;
+use chrono::{DateTime, Utc};
+use fs_err::tokio as fs;
+use pesde::{manifest::target::TargetKind, names::PackageName};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf};
+use utoipa::ToSchema;
+
+/// A package, or versions of it, removed by an administrator
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Removal {
+    #[schema(value_type = String)]
+    pub package: PackageName,
+    /// The version which was removed, or `None` if the whole package was
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub version: Option<Version>,
+    /// The target of the version which was removed, or `None` if all of its targets were
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Target>)]
+    pub target: Option<TargetKind>,
+    /// The public reason of the removal
+    pub reason: String,
+    pub removed_at: DateTime<Utc>,
+}
+
+impl Removal {
+    /// Whether the removal covers the version, or the whole package if `None`
+    pub fn covers(&self, package: &PackageName, version: Option<&Version>) -> bool {
+        if self.package != *package {
+            return false;
+        }
+
+        match (&self.version, version) {
+            (None, _) => true,
+            (Some(removed), Some(version)) => removed == version,
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// The response to requests for a removed package or version, telling why it was removed
+pub fn removed_response(removal: &Removal) -> HttpResponse {
+    HttpResponse::Gone().json(ErrorResponse {
+        error: format!(
+            "removed by the registry's administrators: {}",
+            removal.reason
+        ),
+    })
+}
+
+/// A user banned from publishing
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Ban {
+    /// The reason of the ban, which is shown to the user
+    pub reason: String,
+    pub banned_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ModerationData {
+    #[serde(default)]
+    removals: Vec<Removal>,
+    #[serde(default)]
+    bans: BTreeMap<u64, Ban>,
+}
+
+/// The removals and bans made by administrators, persisted in a file of the data directory
+#[derive(Debug)]
+pub struct Moderation {
+    path: PathBuf,
+    data: tokio::sync::RwLock<ModerationData>,
+}
+
+impl Moderation {
+    pub async fn load(path: PathBuf) -> std::io::Result<Self> {
+        let data = match fs::read_to_string(&path).await {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => ModerationData::default(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            path,
+            data: tokio::sync::RwLock::new(data),
+        })
+    }
+
+    async fn save(&self, data: &ModerationData) -> std::io::Result<()> {
+        write_json_atomically(&self.path, data).await
+    }
+
+    pub async fn record_removal(&self, removal: Removal) -> std::io::Result<()> {
+        let mut data = self.data.write().await;
+        data.removals.push(removal);
+        self.save(&data).await
+    }
+
+    /// The removals, most recent first
+    pub async fn removals(&self) -> Vec<Removal> {
+        self.data
+            .read()
+            .await
+            .removals
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+
+    /// The most recent removal covering the version, or the whole package if `None`
+    pub async fn removal_of(
+        &self,
+        package: &PackageName,
+        version: Option<&Version>,
+    ) -> Option<Removal> {
+        self.data
+            .read()
+            .await
+            .removals
+            .iter()
+            .rev()
+            .find(|removal| removal.covers(package, version))
+            .cloned()
+    }
+
+    pub async fn ban(&self, user_id: UserId, reason: String) -> std::io::Result<Ban> {
+        let ban = Ban {
+            reason,
+            banned_at: Utc::now(),
+        };
+
+        let mut data = self.data.write().await;
+        data.bans.insert(user_id.0, ban.clone());
+        self.save(&data).await?;
+
+        Ok(ban)
+    }
+
+    /// Lifts the user's ban, returning whether they were banned
+    pub async fn unban(&self, user_id: UserId) -> std::io::Result<bool> {
+        let mut data = self.data.write().await;
+        if data.bans.remove(&user_id.0).is_none() {
+            return Ok(false);
+        }
+
+        self.save(&data).await?;
+
+        Ok(true)
+    }
+
+    pub async fn ban_of(&self, user_id: UserId) -> Option<Ban> {
+        self.data.read().await.bans.get(&user_id.0).cloned()
+    }
+
+    pub async fn bans(&self) -> BTreeMap<u64, Ban> {
+        self.data.read().await.bans.clone()
+    }
+}
@@ -0,0 +1,434 @@
+use crate::{
+    auth::UserId,
+    benv,
+    owners::{Owner, Role},
+    write_json_atomically,
+};
+use chrono::Utc;
+use fs_err::tokio as fs;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use pesde::{
+    manifest::target::TargetKind, names::PackageName, retry::RetryPolicy, source::pesde::ScopeInfo,
+};
+use ring::hmac;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+use utoipa::ToSchema;
+
+/// Which notifications a user receives, and where
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[schema(as = NotificationPreferences)]
+pub struct Preferences {
+    /// The address notifications are sent to. Nothing is sent without one
+    #[serde(default)]
+    #[schema(format = Email)]
+    pub email: Option<String>,
+    /// Whether to be notified of versions published to the user's scopes and packages
+    #[serde(default = "enabled")]
+    pub publish: bool,
+    /// Whether to be notified of owners added to the user's scopes and packages
+    #[serde(default = "enabled")]
+    pub owner_added: bool,
+    /// Whether to be notified of versions of the user's scopes and packages being yanked
+    #[serde(default = "enabled")]
+    pub yank: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            email: None,
+            publish: true,
+            owner_added: true,
+            yank: true,
+        }
+    }
+}
+
+/// Something which happened to a scope or package, which its owners are notified of
+#[derive(Debug, Clone)]
+pub enum Notification {
+    Publish {
+        package: PackageName,
+        version: Version,
+        target: TargetKind,
+        publisher: UserId,
+    },
+    OwnerAdded {
+        scope: String,
+        /// The package the owner was added to, or `None` if they were added to the whole scope
+        package: Option<PackageName>,
+        owner: Owner,
+        role: Role,
+        added_by: UserId,
+    },
+    Yank {
+        package: PackageName,
+        version: Version,
+        /// The target which was yanked, or `any` if all of them were
+        target: String,
+        yanked_by: UserId,
+    },
+}
+
+impl Notification {
+    fn wanted_by(&self, preferences: &Preferences) -> bool {
+        match self {
+            Notification::Publish { .. } => preferences.publish,
+            Notification::OwnerAdded { .. } => preferences.owner_added,
+            Notification::Yank { .. } => preferences.yank,
+        }
+    }
+
+    /// The package the notification is about, if it isn't about a whole scope
+    fn package(&self) -> Option<&PackageName> {
+        match self {
+            Notification::Publish { package, .. } | Notification::Yank { package, .. } => {
+                Some(package)
+            }
+            Notification::OwnerAdded { package, .. } => package.as_ref(),
+        }
+    }
+
+    /// The scope or package the notification is about, for display
+    fn target(&self) -> String {
+        match (self, self.package()) {
+            (_, Some(package)) => package.to_string(),
+            (Notification::OwnerAdded { scope, .. }, None) => format!("scope {scope}"),
+            (_, None) => unreachable!("only owner notifications can be about a whole scope"),
+        }
+    }
+
+    fn subject(&self) -> String {
+        match self {
+            Notification::Publish {
+                package, version, ..
+            } => format!("{package}@{version} was published"),
+            Notification::OwnerAdded { owner, role, .. } => {
+                format!(
+                    "{owner} was added as {} of {}",
+                    role.with_article(),
+                    self.target()
+                )
+            }
+            Notification::Yank {
+                package, version, ..
+            } => format!("{package}@{version} was yanked"),
+        }
+    }
+
+    fn body(&self) -> String {
+        let what = match self {
+            Notification::Publish {
+                package,
+                version,
+                target,
+                publisher,
+            } => format!(
+                "Version {version} of {package} was published for the {target} target by user {}.",
+                publisher.0
+            ),
+            Notification::OwnerAdded {
+                owner,
+                role,
+                added_by,
+                ..
+            } => format!(
+                "{owner} was added as {} of {} by user {}.",
+                role.with_article(),
+                self.target(),
+                added_by.0
+            ),
+            Notification::Yank {
+                package,
+                version,
+                target,
+                yanked_by,
+            } => format!(
+                "Version {version} of {package} was yanked for the {target} target by user {}.",
+                yanked_by.0
+            ),
+        };
+
+        format!(
+            "{what}\n\nYou're receiving this email because you're an owner of {}. If you didn't \
+             expect this, check who has access to it. You can choose which notifications you \
+             receive with the registry's notification preferences.\n",
+            self.target()
+        )
+    }
+}
+
+/// Sends emails through Amazon SES' API
+#[derive(Debug)]
+struct Ses {
+    reqwest_client: reqwest::Client,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+impl Ses {
+    async fn send(
+        &self,
+        from: &Mailbox,
+        to: &str,
+        subject: &str,
+        body: &str,
+    ) -> Result<(), String> {
+        let host = format!("email.{}.amazonaws.com", self.region);
+        let path = "/v2/email/outbound-emails";
+        let payload = serde_json::json!({
+            "FromEmailAddress": from.to_string(),
+            "Destination": { "ToAddresses": [to] },
+            "Content": {
+                "Simple": {
+                    "Subject": { "Data": subject },
+                    "Body": { "Text": { "Data": body } },
+                },
+            },
+        })
+        .to_string();
+
+        // the request is signed with AWS Signature Version 4
+        let now = Utc::now();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let credential_scope = format!("{date}/{}/ses/aws4_request", self.region);
+
+        let canonical_request = format!(
+            "POST\n{path}\n\ncontent-type:application/json\nhost:{host}\nx-amz-date:{timestamp}\n\n\
+             content-type;host;x-amz-date\n{}",
+            hex(&Sha256::digest(payload.as_bytes()))
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{timestamp}\n{credential_scope}\n{}",
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let signing_key = [date.as_str(), &self.region, "ses", "aws4_request"]
+            .into_iter()
+            .fold(
+                format!("AWS4{}", self.secret_key).into_bytes(),
+                |key, part| {
+                    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &key), part.as_bytes())
+                        .as_ref()
+                        .to_vec()
+                },
+            );
+        let signature = hmac::sign(
+            &hmac::Key::new(hmac::HMAC_SHA256, &signing_key),
+            string_to_sign.as_bytes(),
+        );
+
+        let request = self
+            .reqwest_client
+            .post(format!("https://{host}{path}"))
+            .header("content-type", "application/json")
+            .header("x-amz-date", timestamp)
+            .header(
+                "authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{credential_scope}, \
+                     SignedHeaders=content-type;host;x-amz-date, Signature={}",
+                    self.access_key,
+                    hex(signature.as_ref())
+                ),
+            )
+            .body(payload);
+
+        let retry_policy = RetryPolicy {
+            max_retries: 3,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        };
+
+        match retry_policy.send(request).await {
+            Ok(response) if response.status().is_success() => Ok(()),
+            Ok(response) => Err(format!("SES responded with {}", response.status())),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Transport {
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+    Ses(Ses),
+}
+
+/// Sends emails from the registry's address
+#[derive(Debug)]
+pub struct Mailer {
+    from: Mailbox,
+    transport: Transport,
+}
+
+impl Mailer {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), String> {
+        match &self.transport {
+            Transport::Smtp(transport) => {
+                let message = Message::builder()
+                    .from(self.from.clone())
+                    .to(to.parse().map_err(|e| format!("invalid address: {e}"))?)
+                    .subject(subject)
+                    .header(ContentType::TEXT_PLAIN)
+                    .body(body.to_string())
+                    .map_err(|e| e.to_string())?;
+
+                transport
+                    .send(message)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }
+            Transport::Ses(ses) => ses.send(&self.from, to, subject, body).await,
+        }
+    }
+}
+
+impl Display for Mailer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.transport {
+            Transport::Smtp(_) => write!(f, "SMTP")?,
+            Transport::Ses(ses) => write!(f, "SES ({})", ses.region)?,
+        }
+
+        write!(f, ", from {}", self.from)
+    }
+}
+
+/// Creates the mailer configured by either `SMTP_URL` or `SES_REGION`, if any
+pub fn get_mailer_from_env(reqwest_client: reqwest::Client) -> Option<Mailer> {
+    let transport = match (benv!("SMTP_URL"), benv!("SES_REGION")) {
+        (Ok(_), Ok(_)) => panic!("Only one of the settings `SMTP_URL` and `SES_REGION` can be set"),
+        (Ok(url), Err(_)) => Transport::Smtp(
+            AsyncSmtpTransport::<Tokio1Executor>::from_url(&url)
+                .unwrap_or_else(|e| panic!("Setting `SMTP_URL` is invalid: {e}"))
+                .build(),
+        ),
+        (Err(_), Ok(region)) => Transport::Ses(Ses {
+            reqwest_client,
+            region,
+            access_key: benv!(required "SES_ACCESS_KEY"),
+            secret_key: benv!(required "SES_SECRET_KEY"),
+        }),
+        (Err(_), Err(_)) => return None,
+    };
+
+    Some(Mailer {
+        from: benv!(required "EMAIL_FROM")
+            .parse()
+            .unwrap_or_else(|e| panic!("Setting `EMAIL_FROM` is invalid: {e}")),
+        transport,
+    })
+}
+
+/// The notification preferences of users, persisted in a file of the data directory, and the
+/// mailer notifications are sent with
+#[derive(Debug)]
+pub struct Notifications {
+    path: PathBuf,
+    preferences: tokio::sync::RwLock<BTreeMap<u64, Preferences>>,
+    mailer: Option<Arc<Mailer>>,
+}
+
+impl Notifications {
+    pub async fn load(path: PathBuf, mailer: Option<Mailer>) -> std::io::Result<Self> {
+        let preferences = match fs::read_to_string(&path).await {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            path,
+            preferences: tokio::sync::RwLock::new(preferences),
+            mailer: mailer.map(Arc::new),
+        })
+    }
+
+    /// Whether the registry sends notifications
+    pub fn is_enabled(&self) -> bool {
+        self.mailer.is_some()
+    }
+
+    pub async fn preferences(&self, user_id: UserId) -> Preferences {
+        self.preferences
+            .read()
+            .await
+            .get(&user_id.0)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub async fn set_preferences(
+        &self,
+        user_id: UserId,
+        preferences: Preferences,
+    ) -> std::io::Result<()> {
+        let mut all = self.preferences.write().await;
+        all.insert(user_id.0, preferences);
+
+        write_json_atomically(&self.path, &*all).await
+    }
+
+    /// Emails the owners of the scope, and of the package the notification is about, who want to
+    /// be notified of it in the background. Members of owning teams aren't notified, as teams
+    /// can't be listed
+    pub async fn notify(&self, info: &ScopeInfo, notification: Notification) {
+        let Some(mailer) = &self.mailer else {
+            return;
+        };
+
+        let mut owners = info.owners.clone();
+        if let Some(package) = notification.package() {
+            if let Some(package_owners) = info.packages.get(package.as_str().1) {
+                owners.extend(&package_owners.owners);
+            }
+        }
+
+        let recipients = {
+            let preferences = self.preferences.read().await;
+            owners
+                .iter()
+                .filter_map(|owner| preferences.get(owner))
+                .filter(|preferences| notification.wanted_by(preferences))
+                .filter_map(|preferences| preferences.email.clone())
+                .collect::<BTreeSet<_>>()
+        };
+        if recipients.is_empty() {
+            return;
+        }
+
+        let subject = notification.subject();
+        let body = notification.body();
+        let mailer = mailer.clone();
+        actix_web::rt::spawn(async move {
+            for recipient in recipients {
+                if let Err(e) = mailer.send(&recipient, &subject, &body).await {
+                    tracing::warn!("failed to send notification \"{subject}\": {e}");
+                }
+            }
+        });
+    }
+}
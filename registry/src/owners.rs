@@ -0,0 +1,144 @@
+use crate::{auth::UserId, error::Error, AppState};
+use pesde::source::{
+    git_index::read_file,
+    pesde::{ScopeInfo, SCOPE_INFO_FILE},
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use utoipa::ToSchema;
+
+/// An owner of a scope or package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Owner {
+    /// A user, by their ID
+    User(u64),
+    /// The members of a GitHub team, as `org/team`
+    Team(String),
+}
+
+impl Display for Owner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Owner::User(id) => write!(f, "user {id}"),
+            Owner::Team(team) => write!(f, "team {team}"),
+        }
+    }
+}
+
+/// What an owner of a scope or package may do
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Publishing, yanking and deprecating versions, and managing owners and trusted publishers
+    #[default]
+    Owner,
+    /// Only publishing new versions, such as for CI
+    Publisher,
+}
+
+impl Role {
+    pub fn is_owner(&self) -> bool {
+        *self == Role::Owner
+    }
+
+    /// The role with its indefinite article, such as `an owner`
+    pub fn with_article(&self) -> &'static str {
+        match self {
+            Role::Owner => "an owner",
+            Role::Publisher => "a publisher",
+        }
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Role::Owner => write!(f, "owner"),
+            Role::Publisher => write!(f, "publisher"),
+        }
+    }
+}
+
+/// Reads the information of the scope from the index, if the scope exists
+#[allow(clippy::result_large_err)]
+pub fn read_scope_info(tree: &gix::Tree, scope: &str) -> Result<Option<ScopeInfo>, Error> {
+    match read_file(tree, [scope, SCOPE_INFO_FILE])? {
+        Some(info) => Ok(Some(toml::de::from_str(&info)?)),
+        None => Ok(None),
+    }
+}
+
+/// Whether the user owns the scope, either directly or through one of its teams
+pub async fn owns_scope(
+    app_state: &AppState,
+    info: &ScopeInfo,
+    user_id: UserId,
+) -> Result<bool, Error> {
+    if info.owners.contains(&user_id.0) {
+        return Ok(true);
+    }
+
+    app_state.teams.is_member_of_any(&info.teams, user_id).await
+}
+
+/// Whether the user owns the package of the scope, either as an owner of the scope or as one of
+/// the package's own owners
+pub async fn owns_package(
+    app_state: &AppState,
+    info: &ScopeInfo,
+    name: &str,
+    user_id: UserId,
+) -> Result<bool, Error> {
+    if owns_scope(app_state, info, user_id).await? {
+        return Ok(true);
+    }
+
+    let Some(owners) = info.packages.get(name) else {
+        return Ok(false);
+    };
+
+    if owners.owners.contains(&user_id.0) {
+        return Ok(true);
+    }
+
+    app_state
+        .teams
+        .is_member_of_any(&owners.teams, user_id)
+        .await
+}
+
+/// Whether the user may publish the package of the scope, either as an owner of it or as one of
+/// the publishers of the scope or the package
+pub async fn may_publish(
+    app_state: &AppState,
+    info: &ScopeInfo,
+    name: &str,
+    user_id: UserId,
+) -> Result<bool, Error> {
+    if owns_package(app_state, info, name, user_id).await? {
+        return Ok(true);
+    }
+
+    if info.publishers.contains(&user_id.0)
+        || app_state
+            .teams
+            .is_member_of_any(&info.publisher_teams, user_id)
+            .await?
+    {
+        return Ok(true);
+    }
+
+    let Some(owners) = info.packages.get(name) else {
+        return Ok(false);
+    };
+
+    if owners.publishers.contains(&user_id.0) {
+        return Ok(true);
+    }
+
+    app_state
+        .teams
+        .is_member_of_any(&owners.publisher_teams, user_id)
+        .await
+}
@@ -1,33 +1,51 @@
+use crate::{
+    endpoints::openapi::shared::{Maintainer as MaintainerSchema, Target},
+    error::Error,
+};
+use actix_web::{
+    http::header::{
+        Header, HttpDate, IfModifiedSince, CACHE_CONTROL, ETAG, IF_NONE_MATCH, LAST_MODIFIED,
+    },
+    HttpRequest, HttpResponse,
+};
 use chrono::{DateTime, Utc};
-use pesde::manifest::target::{Target, TargetKind};
+use pesde::{
+    manifest::{target::TargetKind, Maintainer},
+    names::PackageName,
+    source::pesde::{IndexFile, IndexFileEntry},
+};
 use serde::Serialize;
-use std::collections::BTreeSet;
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeSet, time::SystemTime};
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Eq, PartialEq)]
+#[derive(Debug, Serialize, Eq, PartialEq, ToSchema)]
 pub struct TargetInfo {
+    #[schema(value_type = Target)]
     kind: TargetKind,
     lib: bool,
+    types: bool,
     bin: bool,
     #[serde(skip_serializing_if = "BTreeSet::is_empty")]
     scripts: BTreeSet<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    yanked: bool,
 }
 
-impl From<Target> for TargetInfo {
-    fn from(target: Target) -> Self {
-        (&target).into()
-    }
-}
+impl From<&IndexFileEntry> for TargetInfo {
+    fn from(entry: &IndexFileEntry) -> Self {
+        let target = &entry.target;
 
-impl From<&Target> for TargetInfo {
-    fn from(target: &Target) -> Self {
         TargetInfo {
             kind: target.kind(),
             lib: target.lib_path().is_some(),
+            types: target.types_path().is_some(),
             bin: target.bin_path().is_some(),
             scripts: target
                 .scripts()
                 .map(|scripts| scripts.keys().cloned().collect())
                 .unwrap_or_default(),
+            yanked: entry.yanked,
         }
     }
 }
@@ -44,7 +62,7 @@ impl PartialOrd for TargetInfo {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct PackageResponse {
     pub name: String,
     pub version: String,
@@ -58,4 +76,84 @@ pub struct PackageResponse {
     pub authors: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub repository: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[schema(value_type = Vec<MaintainerSchema>)]
+    pub maintainers: Vec<Maintainer>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub funding: Vec<String>,
+    /// The deprecation message, if the version is deprecated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
+}
+
+impl PackageResponse {
+    /// Summarizes a package by its latest version, with the time any of its versions was last
+    /// published. Yanked versions are only considered if there are no others. Returns `None` if
+    /// the package has no versions
+    pub fn latest(name: &PackageName, versions: &IndexFile) -> Option<Self> {
+        let (latest_version, entry) = versions
+            .iter()
+            .max_by_key(|(v_id, entry)| (!entry.yanked, v_id.version()))?;
+
+        Some(PackageResponse {
+            name: name.to_string(),
+            version: latest_version.version().to_string(),
+            targets: versions
+                .iter()
+                .filter(|(v_id, _)| v_id.version() == latest_version.version())
+                .map(|(_, entry)| entry.into())
+                .collect(),
+            description: entry.description.clone().unwrap_or_default(),
+            published_at: versions
+                .values()
+                .map(|entry| entry.published_at)
+                .max()
+                .unwrap(),
+            license: entry.license.clone().unwrap_or_default(),
+            authors: entry.authors.clone(),
+            repository: entry.repository.clone().map(|url| url.to_string()),
+            maintainers: entry.maintainers.clone(),
+            funding: entry.funding.iter().map(|url| url.to_string()).collect(),
+            deprecated: entry.deprecated.clone(),
+        })
+    }
+}
+
+/// Responds with the JSON metadata of a package, or with 304 Not Modified if the copy the client
+/// has is still fresh. As with the index files, the entity tag is the hash of the body, and
+/// `If-Modified-Since` is only considered if the client didn't send an entity tag
+#[allow(clippy::result_large_err)]
+pub fn conditional_json(
+    request: &HttpRequest,
+    modified_at: DateTime<Utc>,
+    value: &impl Serialize,
+) -> Result<HttpResponse, Error> {
+    let body = serde_json::to_vec(value)?;
+    let etag = format!("\"{:x}\"", Sha256::digest(&body));
+    let last_modified = HttpDate::from(SystemTime::from(modified_at));
+
+    let not_modified = match request.headers().get(IF_NONE_MATCH) {
+        Some(value) => value.to_str().is_ok_and(|value| {
+            value.split(',').any(|tag| {
+                let tag = tag.trim();
+                tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag
+            })
+        }),
+        None => IfModifiedSince::parse(request)
+            .is_ok_and(|IfModifiedSince(since)| last_modified <= since),
+    };
+
+    if not_modified {
+        return Ok(HttpResponse::NotModified()
+            .insert_header((ETAG, etag))
+            .insert_header((LAST_MODIFIED, last_modified))
+            .finish());
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header((ETAG, etag))
+        .insert_header((LAST_MODIFIED, last_modified))
+        .insert_header((CACHE_CONTROL, "no-cache"))
+        .content_type("application/json")
+        .body(body))
 }
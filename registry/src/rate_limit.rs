@@ -0,0 +1,629 @@
+use crate::{
+    auth::{api_tokens::Permissions, get_token_from_req, Auth, AuthImpl, UserId},
+    benv, AppState,
+};
+use actix_governor::governor::{
+    clock::{Clock, DefaultClock},
+    middleware::StateInformationMiddleware,
+    state::keyed::DefaultKeyedStateStore,
+    NotUntil, Quota, RateLimiter,
+};
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    error::Error as ActixError,
+    http::header::{HeaderName, HeaderValue, RETRY_AFTER},
+    middleware::Next,
+    web, HttpMessage, HttpRequest, HttpResponse,
+};
+use ipnet::IpNet;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    net::IpAddr,
+    num::NonZeroU32,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The header proxies append the address of the client they forwarded a request for to
+const FORWARDED_FOR_HEADER: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// How often the state of callers which have their budgets fully replenished is dropped
+pub const CLEANUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// How long the users of credentials verified by the registry's authentication method are
+/// remembered for, so that the authentication provider isn't asked about every request
+const VERIFIED_CREDENTIALS_TTL: Duration = Duration::from_secs(5 * 60);
+
+type VerifiedCredentials = HashMap<[u8; 32], (UserId, Instant)>;
+
+fn credentials_key(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+/// A group of routes sharing a rate limit
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LimitedRoute {
+    Publish,
+    Search,
+    Download,
+}
+
+/// The kind of caller a budget applies to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Tier {
+    /// Callers which aren't authenticated, limited by their IP address
+    Anonymous,
+    /// Callers authenticated by the registry's authentication method
+    Authenticated,
+    /// Callers authenticated by an API token or a trusted publisher
+    Token,
+}
+
+impl FromStr for LimitedRoute {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "publish" => Ok(Self::Publish),
+            "search" => Ok(Self::Search),
+            "download" => Ok(Self::Download),
+            _ => Err(format!("unknown route `{s}`")),
+        }
+    }
+}
+
+impl Display for LimitedRoute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Publish => write!(f, "publish"),
+            Self::Search => write!(f, "search"),
+            Self::Download => write!(f, "download"),
+        }
+    }
+}
+
+impl FromStr for Tier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "anonymous" => Ok(Self::Anonymous),
+            "authenticated" => Ok(Self::Authenticated),
+            "token" => Ok(Self::Token),
+            _ => Err(format!("unknown tier `{s}`")),
+        }
+    }
+}
+
+impl Display for Tier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Anonymous => write!(f, "anonymous"),
+            Self::Authenticated => write!(f, "authenticated"),
+            Self::Token => write!(f, "token"),
+        }
+    }
+}
+
+/// An amount of requests which can be made at once, and which is replenished evenly over a period
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Budget {
+    requests: NonZeroU32,
+    period: Duration,
+}
+
+impl Budget {
+    fn quota(&self) -> Quota {
+        Quota::with_period(self.period / self.requests.get())
+            .expect("period is checked to not be zero")
+            .allow_burst(self.requests)
+    }
+}
+
+impl FromStr for Budget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (requests, period) = s
+            .split_once('/')
+            .ok_or_else(|| format!("budget `{s}` must be in the form `<requests>/<period>`"))?;
+
+        let requests = requests
+            .trim()
+            .parse::<NonZeroU32>()
+            .map_err(|e| format!("invalid request count in budget `{s}`: {e}"))?;
+
+        let period = period.trim();
+        let (amount, unit) = period.split_at(period.len().saturating_sub(1));
+        let unit = match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            _ => return Err(format!("period in budget `{s}` must end with s, m, h or d")),
+        };
+        let period = amount
+            .parse::<u64>()
+            .ok()
+            .filter(|amount| *amount > 0)
+            .map(|amount| Duration::from_secs(amount * unit))
+            .ok_or_else(|| format!("invalid period in budget `{s}`"))?;
+
+        Ok(Self { requests, period })
+    }
+}
+
+impl Display for Budget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}s", self.requests, self.period.as_secs())
+    }
+}
+
+/// Who a request is counted against
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Caller {
+    User(UserId),
+    Ip(IpAddr),
+}
+
+type Limiter =
+    RateLimiter<Caller, DefaultKeyedStateStore<Caller>, DefaultClock, StateInformationMiddleware>;
+
+/// The budgets of each route and tier. Routes and tiers without a budget aren't limited
+pub struct RateLimits {
+    budgets: BTreeMap<(LimitedRoute, Tier), Budget>,
+    limiters: BTreeMap<(LimitedRoute, Tier), Arc<Limiter>>,
+    /// The proxies whose `X-Forwarded-For` headers are trusted to contain the client's address
+    trusted_proxies: Vec<IpNet>,
+    /// The users of recently verified credentials, by the hashes of the credentials
+    verified_credentials: Arc<Mutex<VerifiedCredentials>>,
+}
+
+/// The budgets used unless configured otherwise. Publishing matches the registry's previous limit,
+/// and anonymous reads are limited enough to deter scraping
+fn default_budgets() -> BTreeMap<(LimitedRoute, Tier), Budget> {
+    let budget = |requests, minutes: u64| Budget {
+        requests: NonZeroU32::new(requests).unwrap(),
+        period: Duration::from_secs(minutes * 60),
+    };
+
+    BTreeMap::from([
+        ((LimitedRoute::Publish, Tier::Authenticated), budget(12, 12)),
+        ((LimitedRoute::Publish, Tier::Token), budget(12, 12)),
+        ((LimitedRoute::Search, Tier::Anonymous), budget(60, 1)),
+        ((LimitedRoute::Download, Tier::Anonymous), budget(300, 1)),
+    ])
+}
+
+/// Parses rate limits in the form `<route>.<tier>=<requests>/<period>`, separated by commas, on
+/// top of the default ones. A budget of `unlimited` removes the limit
+fn parse_rate_limits(
+    value: &str,
+    mut budgets: BTreeMap<(LimitedRoute, Tier), Budget>,
+) -> Result<BTreeMap<(LimitedRoute, Tier), Budget>, String> {
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (key, budget) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("rate limit `{entry}` must be in the form `<key>=<budget>`"))?;
+        let (route, tier) = key.trim().split_once('.').ok_or_else(|| {
+            format!("rate limit key `{key}` must be in the form `<route>.<tier>`")
+        })?;
+        let key = (route.parse()?, tier.parse()?);
+
+        match budget.trim() {
+            "unlimited" => {
+                budgets.remove(&key);
+            }
+            budget => {
+                budgets.insert(key, budget.parse()?);
+            }
+        }
+    }
+
+    Ok(budgets)
+}
+
+/// Parses IP addresses and networks in CIDR notation, separated by commas
+fn parse_trusted_proxies(value: &str) -> Result<Vec<IpNet>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|proxy| !proxy.is_empty())
+        .map(|proxy| {
+            proxy
+                .parse::<IpNet>()
+                .or_else(|_| proxy.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| format!("`{proxy}` is not an IP address or network"))
+        })
+        .collect()
+}
+
+pub fn get_rate_limits_from_env() -> RateLimits {
+    let budgets = match benv!("RATE_LIMITS") {
+        Ok(value) => parse_rate_limits(&value, default_budgets())
+            .unwrap_or_else(|e| panic!("Setting `RATE_LIMITS` is invalid: {e}")),
+        Err(_) => default_budgets(),
+    };
+    let trusted_proxies = match benv!("TRUSTED_PROXIES") {
+        Ok(value) => parse_trusted_proxies(&value)
+            .unwrap_or_else(|e| panic!("Setting `TRUSTED_PROXIES` is invalid: {e}")),
+        Err(_) => vec![],
+    };
+
+    RateLimits {
+        limiters: budgets
+            .iter()
+            .map(|(key, budget)| {
+                (
+                    *key,
+                    Arc::new(
+                        RateLimiter::keyed(budget.quota())
+                            .with_middleware::<StateInformationMiddleware>(),
+                    ),
+                )
+            })
+            .collect(),
+        budgets,
+        trusted_proxies,
+        verified_credentials: Default::default(),
+    }
+}
+
+impl RateLimits {
+    /// Determines the address of the client which made the request. When it was made by a trusted
+    /// proxy, the closest address in `X-Forwarded-For` which isn't a trusted proxy is used, as the
+    /// addresses before it could have been made up by the client
+    pub fn client_ip(&self, req: &HttpRequest) -> Option<IpAddr> {
+        let peer = req.peer_addr()?.ip();
+        let is_trusted = |ip: &IpAddr| self.trusted_proxies.iter().any(|net| net.contains(ip));
+        if !is_trusted(&peer) {
+            return Some(peer);
+        }
+
+        let mut client = peer;
+        for header in req.headers().get_all(FORWARDED_FOR_HEADER).rev() {
+            let Ok(header) = header.to_str() else {
+                return Some(client);
+            };
+
+            for hop in header.rsplit(',') {
+                let Ok(ip) = hop.trim().parse::<IpAddr>() else {
+                    return Some(client);
+                };
+                client = ip;
+                if !is_trusted(&ip) {
+                    return Some(client);
+                }
+            }
+        }
+
+        Some(client)
+    }
+
+    /// Drops the state of callers whose budgets are fully replenished, to keep memory usage bounded
+    pub fn retain_recent(&self) {
+        for limiter in self.limiters.values() {
+            limiter.retain_recent();
+        }
+
+        self.verified_credentials
+            .lock()
+            .unwrap()
+            .retain(|_, (_, verified_at)| verified_at.elapsed() < VERIFIED_CREDENTIALS_TTL);
+    }
+
+    /// The user of recently verified credentials
+    fn verified_user(&self, token: &str) -> Option<UserId> {
+        self.verified_credentials
+            .lock()
+            .unwrap()
+            .get(&credentials_key(token))
+            .filter(|(_, verified_at)| verified_at.elapsed() < VERIFIED_CREDENTIALS_TTL)
+            .map(|(user_id, _)| *user_id)
+    }
+
+    /// Verifies the credentials of the request with the registry's authentication method,
+    /// remembering the user they belong to. Invalid credentials aren't remembered, so that they
+    /// can't fill up memory
+    async fn verify_user(&self, auth: &Auth, req: &ServiceRequest, token: &str) -> Option<UserId> {
+        let user_id = auth.for_read_request(req).await.ok().flatten()?;
+        self.verified_credentials
+            .lock()
+            .unwrap()
+            .insert(credentials_key(token), (user_id, Instant::now()));
+
+        Some(user_id)
+    }
+
+    /// Carries the state of the callers over to the new rate limits, for the budgets which didn't
+    /// change
+    pub fn reloaded(&self, mut new: RateLimits) -> RateLimits {
+        for (key, limiter) in &mut new.limiters {
+            if self.budgets.get(key) == new.budgets.get(key) {
+                *limiter = self.limiters[key].clone();
+            }
+        }
+        new.verified_credentials = self.verified_credentials.clone();
+
+        new
+    }
+}
+
+impl Display for RateLimits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.budgets.is_empty() {
+            return write!(f, "none");
+        }
+
+        for (i, ((route, tier), budget)) in self.budgets.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{route}.{tier}={budget}")?;
+        }
+
+        if !self.trusted_proxies.is_empty() {
+            write!(
+                f,
+                " (trusted proxies: {})",
+                self.trusted_proxies
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Who made a request
+enum Identity {
+    /// A caller of the tier
+    Known(Tier, Caller),
+    /// A caller presenting credentials which only the registry's authentication method can verify,
+    /// and which weren't verified recently
+    Unverified(IpAddr, String),
+}
+
+/// Determines the tier and identity of the caller. On registries with public reads the
+/// authentication middleware doesn't run, so callers presenting credentials are identified here,
+/// letting them skip the limits of anonymous callers. Only callers without valid credentials are
+/// limited by their IP address
+async fn identify(
+    app_state: &AppState,
+    rate_limits: &RateLimits,
+    req: &ServiceRequest,
+) -> Option<Identity> {
+    let user_id = {
+        let extensions = req.extensions();
+        extensions
+            .get::<UserId>()
+            .copied()
+            .or_else(|| extensions.get::<Option<UserId>>().copied().flatten())
+            .map(|user_id| {
+                let tier = match extensions.get::<Permissions>() {
+                    Some(Permissions::Scoped(_)) => Tier::Token,
+                    _ => Tier::Authenticated,
+                };
+                Identity::Known(tier, Caller::User(user_id))
+            })
+    };
+    if user_id.is_some() {
+        return user_id;
+    }
+
+    let ip = rate_limits.client_ip(req.request());
+    match app_state.api_tokens.authenticate(req).await {
+        Some(Some((user_id, _, _))) => {
+            return Some(Identity::Known(Tier::Token, Caller::User(user_id)))
+        }
+        Some(None) => {}
+        // without an authentication method every caller would be the same user
+        None if !matches!(app_state.auth, Auth::None(_)) => {
+            if let Some(token) = get_token_from_req(req) {
+                if let Some(user_id) = rate_limits.verified_user(&token) {
+                    return Some(Identity::Known(Tier::Authenticated, Caller::User(user_id)));
+                }
+
+                return ip.map(|ip| Identity::Unverified(ip, token));
+            }
+        }
+        None => {}
+    }
+
+    ip.map(|ip| Identity::Known(Tier::Anonymous, Caller::Ip(ip)))
+}
+
+fn too_many_requests(negative: &NotUntil<<DefaultClock as Clock>::Instant>) -> HttpResponse {
+    let wait_time = negative
+        .wait_time_from(DefaultClock::default().now())
+        .as_secs();
+
+    HttpResponse::TooManyRequests()
+        .insert_header((RETRY_AFTER, wait_time))
+        .insert_header(("x-ratelimit-after", wait_time))
+        .insert_header(("x-ratelimit-limit", negative.quota().burst_size().get()))
+        .insert_header(("x-ratelimit-remaining", 0))
+        .body(format!("Too many requests, retry in {wait_time}s"))
+}
+
+async fn limit(
+    route: LimitedRoute,
+    app_state: web::Data<AppState>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, ActixError> {
+    let rate_limits = app_state.rate_limits.get();
+    let (tier, caller, credentials) = match identify(&app_state, &rate_limits, &req).await {
+        Some(Identity::Known(tier, caller)) => (tier, caller, None),
+        Some(Identity::Unverified(ip, token)) => (Tier::Anonymous, Caller::Ip(ip), Some(token)),
+        None => return next.call(req).await.map(|res| res.map_into_left_body()),
+    };
+    // callers with unverified credentials are unlimited like anonymous ones, so their credentials
+    // needn't be verified
+    let Some(limiter) = rate_limits.limiters.get(&(route, tier)).cloned() else {
+        return next.call(req).await.map(|res| res.map_into_left_body());
+    };
+
+    let mut snapshot = match limiter.check_key(&caller) {
+        Ok(snapshot) => snapshot,
+        Err(negative) => {
+            return Ok(req
+                .into_response(too_many_requests(&negative))
+                .map_into_right_body())
+        }
+    };
+
+    // credentials are only verified once their caller is within its anonymous budget, so that
+    // made up credentials can't be used to make requests to the authentication provider without
+    // limit
+    if let Some(token) = credentials {
+        if let Some(user_id) = rate_limits.verify_user(&app_state.auth, &req, &token).await {
+            let Some(limiter) = rate_limits
+                .limiters
+                .get(&(route, Tier::Authenticated))
+                .cloned()
+            else {
+                return next.call(req).await.map(|res| res.map_into_left_body());
+            };
+
+            snapshot = match limiter.check_key(&Caller::User(user_id)) {
+                Ok(snapshot) => snapshot,
+                Err(negative) => {
+                    return Ok(req
+                        .into_response(too_many_requests(&negative))
+                        .map_into_right_body())
+                }
+            };
+        }
+    }
+
+    let mut res = next.call(req).await?;
+    let headers = res.headers_mut();
+    headers.insert(
+        HeaderName::from_static("x-ratelimit-limit"),
+        HeaderValue::from(snapshot.quota().burst_size().get()),
+    );
+    headers.insert(
+        HeaderName::from_static("x-ratelimit-remaining"),
+        HeaderValue::from(snapshot.remaining_burst_capacity()),
+    );
+
+    Ok(res.map_into_left_body())
+}
+
+pub async fn publish_mw(
+    app_state: web::Data<AppState>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, ActixError> {
+    limit(LimitedRoute::Publish, app_state, req, next).await
+}
+
+pub async fn search_mw(
+    app_state: web::Data<AppState>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, ActixError> {
+    limit(LimitedRoute::Search, app_state, req, next).await
+}
+
+pub async fn download_mw(
+    app_state: web::Data<AppState>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, ActixError> {
+    limit(LimitedRoute::Download, app_state, req, next).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn rate_limits(trusted_proxies: &str) -> RateLimits {
+        RateLimits {
+            budgets: BTreeMap::new(),
+            limiters: BTreeMap::new(),
+            trusted_proxies: parse_trusted_proxies(trusted_proxies).unwrap(),
+            verified_credentials: Default::default(),
+        }
+    }
+
+    fn client_ip(rate_limits: &RateLimits, peer: &str, forwarded_for: &[&str]) -> IpAddr {
+        let mut req = TestRequest::default().peer_addr(format!("{peer}:443").parse().unwrap());
+        for header in forwarded_for {
+            req = req.append_header((FORWARDED_FOR_HEADER, *header));
+        }
+
+        rate_limits.client_ip(&req.to_http_request()).unwrap()
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn ignores_forwarded_for_of_untrusted_peers() {
+        let rate_limits = rate_limits("10.0.0.0/8");
+        assert_eq!(
+            client_ip(&rate_limits, "203.0.113.7", &["198.51.100.1"]),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn uses_closest_untrusted_hop() {
+        let rate_limits = rate_limits("10.0.0.0/8");
+
+        // the client made up the left-most hop
+        assert_eq!(
+            client_ip(
+                &rate_limits,
+                "10.0.0.1",
+                &["1.2.3.4, 198.51.100.1, 10.0.0.2"]
+            ),
+            ip("198.51.100.1")
+        );
+        // every hop is a trusted proxy
+        assert_eq!(
+            client_ip(&rate_limits, "10.0.0.1", &["10.0.0.3, 10.0.0.2"]),
+            ip("10.0.0.3")
+        );
+    }
+
+    #[test]
+    fn reads_multiple_forwarded_for_headers_in_order() {
+        let rate_limits = rate_limits("10.0.0.0/8");
+        assert_eq!(
+            client_ip(
+                &rate_limits,
+                "10.0.0.1",
+                &["1.2.3.4, 198.51.100.1", "10.0.0.3", "10.0.0.2"]
+            ),
+            ip("198.51.100.1")
+        );
+    }
+
+    #[test]
+    fn stops_at_unparsable_hops() {
+        let rate_limits = rate_limits("10.0.0.0/8");
+        assert_eq!(
+            client_ip(
+                &rate_limits,
+                "10.0.0.1",
+                &["198.51.100.1, unknown, 10.0.0.2"]
+            ),
+            ip("10.0.0.2")
+        );
+        assert_eq!(
+            client_ip(&rate_limits, "10.0.0.1", &["198.51.100.1, garbage"]),
+            ip("10.0.0.1")
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd};
+use std::{borrow::Cow, sync::LazyLock};
+use syntect::{
+    highlighting::ThemeSet,
+    html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+
+/// The prefix of the classes of highlighted code, so that they can't clash with a frontend's own
+const CLASS_PREFIX: &str = "hl-";
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed {
+    prefix: CLASS_PREFIX,
+};
+/// The theme the stylesheet of highlighted code is generated from
+const THEME: &str = "InspiredGitHub";
+
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+static SANITIZER: LazyLock<ammonia::Builder<'static>> = LazyLock::new(|| {
+    let mut builder = ammonia::Builder::default();
+    builder
+        .add_tag_attributes("span", ["class"])
+        .add_tag_attributes("code", ["class"])
+        .add_tag_attributes("pre", ["class"])
+        .attribute_filter(|element, attribute, value| {
+            if attribute != "class" {
+                return Some(value.into());
+            }
+
+            // only the classes added by the renderer are kept
+            let prefix = match element {
+                "code" => "language-",
+                _ => CLASS_PREFIX,
+            };
+            let classes = value
+                .split_whitespace()
+                .filter(|class| class.starts_with(prefix))
+                .collect::<Vec<_>>();
+
+            (!classes.is_empty()).then(|| classes.join(" ").into())
+        });
+    builder
+});
+
+/// The stylesheet of highlighted code in rendered READMEs
+pub static HIGHLIGHT_CSS: LazyLock<String> = LazyLock::new(|| {
+    let themes = ThemeSet::load_defaults();
+    css_for_theme_with_class_style(&themes.themes[THEME], CLASS_STYLE)
+        .expect("failed to generate highlighting stylesheet")
+});
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn highlight(language: &str, code: &str) -> Option<String> {
+    // Luau isn't supported, but its syntax is a superset of Lua's
+    let token = match language.to_ascii_lowercase().as_str() {
+        "luau" => Cow::Borrowed("lua"),
+        language => Cow::Owned(language.to_string()),
+    };
+    let syntax = SYNTAX_SET.find_syntax_by_token(&token)?;
+
+    let mut generator =
+        ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAX_SET, CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        generator
+            .parse_html_for_line_which_includes_newline(line)
+            .ok()?;
+    }
+
+    Some(generator.finalize())
+}
+
+/// The text of a README's markdown without its formatting, such as for it to be searched
+pub fn plain_text(markdown: &str) -> String {
+    let mut text = String::with_capacity(markdown.len());
+
+    for event in Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES) {
+        match event {
+            Event::Text(content) | Event::Code(content) => text.push_str(&content),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::Item
+                | TagEnd::CodeBlock
+                | TagEnd::BlockQuote(_)
+                | TagEnd::TableCell,
+            ) => text.push('\n'),
+            _ => {}
+        }
+    }
+
+    text
+}
+
+/// Renders the markdown of a README to sanitized HTML, with its code blocks highlighted
+pub fn render_html(markdown: &str) -> String {
+    let parser = Parser::new_ext(
+        markdown,
+        Options::ENABLE_TABLES
+            | Options::ENABLE_FOOTNOTES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TASKLISTS,
+    );
+
+    let mut events = Vec::new();
+    let mut code_block: Option<(CowStr, String)> = None;
+
+    for event in parser {
+        match (event, &mut code_block) {
+            (Event::Start(Tag::CodeBlock(kind)), None) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => {
+                        // the info string may contain attributes after the language
+                        match info.split_whitespace().next() {
+                            Some(language) => CowStr::from(language.to_string()),
+                            None => CowStr::from(""),
+                        }
+                    }
+                    CodeBlockKind::Indented => CowStr::from(""),
+                };
+                code_block = Some((language, String::new()));
+            }
+            (Event::Text(text), Some((_, code))) => code.push_str(&text),
+            (Event::End(TagEnd::CodeBlock), Some(_)) => {
+                let (language, code) = code_block.take().unwrap();
+
+                let highlighted = (!language.is_empty())
+                    .then(|| highlight(&language, &code))
+                    .flatten()
+                    .unwrap_or_else(|| escape_html(&code));
+                let class = if language.is_empty() {
+                    String::new()
+                } else {
+                    format!(r#" class="language-{}""#, escape_html(&language))
+                };
+
+                events.push(Event::Html(
+                    format!("<pre><code{class}>{highlighted}</code></pre>\n").into(),
+                ));
+            }
+            (event, _) => events.push(event),
+        }
+    }
+
+    let mut output = String::new();
+    html::push_html(&mut output, events.into_iter());
+
+    SANITIZER.clean(&output).to_string()
+}
@@ -0,0 +1,313 @@
+use crate::{
+    endpoints::openapi::shared::Target,
+    error::Error,
+    gc::referenced_files,
+    git::push_changes,
+    metadata_cache::update_cached_package,
+    metrics::METRICS,
+    search::{all_packages, update_package},
+    storage::{Storage, StorageImpl},
+    AppState,
+};
+use chrono::{DateTime, Utc};
+use futures::{stream, StreamExt};
+use pesde::{
+    manifest::target::TargetKind,
+    names::PackageName,
+    source::{
+        git_index::{read_file, root_tree, GitBasedSource},
+        pesde::IndexFile,
+        version_id::VersionId,
+    },
+};
+use semver::Version;
+use serde::Serialize;
+use std::{collections::HashMap, path::Path};
+use tokio::pin;
+use utoipa::ToSchema;
+
+/// How many archives are checked for at once
+const CHECK_CONCURRENCY: usize = 16;
+
+/// What was left of a partially published version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PartialState {
+    /// The version is in the index, but its archive isn't stored
+    IndexOnly,
+    /// The version's archive is stored, but it isn't in the index
+    StorageOnly,
+}
+
+/// Why a version can't be rolled back
+#[derive(Debug)]
+pub enum RollbackRejection {
+    /// The version is being published
+    BeingPublished,
+    /// The version is both in the index and stored
+    FullyPublished,
+    /// The version is neither in the index nor stored
+    NotFound,
+}
+
+/// Rolls back a version which was only partially published, by removing it from the index if its
+/// archive isn't stored, or deleting its files if it isn't in the index. Unlike a removal, the
+/// version can be published again afterwards
+pub async fn roll_back(
+    app_state: &AppState,
+    package: &PackageName,
+    version_id: &VersionId,
+) -> Result<Result<PartialState, RollbackRejection>, Error> {
+    // reserved so that the version isn't rolled back while it's being published, and can't be
+    // published while it's rolled back
+    let Some(_reservation) = app_state.index_queue.reserve(package, version_id) else {
+        return Ok(Err(RollbackRejection::BeingPublished));
+    };
+
+    let stored = app_state
+        .storage
+        .get()
+        .package_exists(package, version_id)
+        .await?;
+
+    let (scope, name) = package.as_str();
+
+    let state = {
+        let source = app_state.write_source().await;
+        source
+            .refresh(&app_state.project, &app_state.reqwest_client)
+            .await
+            .map_err(Box::new)?;
+
+        let mut entries: IndexFile = {
+            let repo = gix::open(source.path(&app_state.project))?;
+            let tree = root_tree(&repo)?;
+
+            toml::de::from_str(&read_file(&tree, [scope, name])?.unwrap_or_default())?
+        };
+
+        let state = match (entries.contains_key(version_id), stored) {
+            (true, true) => return Ok(Err(RollbackRejection::FullyPublished)),
+            (false, false) => return Ok(Err(RollbackRejection::NotFound)),
+            (true, false) => PartialState::IndexOnly,
+            (false, true) => PartialState::StorageOnly,
+        };
+
+        if state == PartialState::IndexOnly {
+            entries.remove(version_id);
+
+            // packages without any versions left are removed from the index entirely
+            let contents = if entries.is_empty() {
+                None
+            } else {
+                Some(toml::to_string(&entries)?.into_bytes())
+            };
+
+            push_changes(
+                app_state,
+                &source,
+                scope,
+                HashMap::from([(name, contents)]),
+                &format!(
+                    "roll back {package}@{} {}",
+                    version_id.version(),
+                    version_id.target()
+                ),
+            )?;
+
+            app_state.dependents.update(package, &entries);
+            update_cached_package(app_state, package, &entries);
+            update_package(app_state, package, entries).await;
+        }
+
+        state
+    };
+
+    // the rest of the version's files may have been stored, even if its archive wasn't
+    app_state
+        .storage
+        .get()
+        .delete_version(package, version_id)
+        .await?;
+
+    Ok(Ok(state))
+}
+
+/// A version which is only partially published
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Mismatch {
+    #[schema(value_type = String)]
+    pub package: PackageName,
+    #[schema(value_type = String)]
+    pub version: Version,
+    #[schema(value_type = Target)]
+    pub target: TargetKind,
+    pub state: PartialState,
+    /// Whether the version was rolled back
+    pub fixed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReconcileReport {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// Whether the mismatches found were rolled back
+    pub fix: bool,
+    /// How many versions of the index were checked for their archive
+    pub checked_versions: u64,
+    /// Whether the stored versions were checked for being in the index, which only the
+    /// filesystem storage supports
+    pub checked_storage: bool,
+    pub mismatches: Vec<Mismatch>,
+}
+
+/// Cross-checks the index against the storage, keeping the report of the last run
+#[derive(Debug, Default)]
+pub struct Reconciler {
+    /// Held while reconciling, so that reconciliations don't run concurrently
+    lock: tokio::sync::Mutex<()>,
+    last_report: std::sync::Mutex<Option<ReconcileReport>>,
+}
+
+/// Parses the directory of a stored version, relative to the storage's root
+fn parse_version_dir(path: &Path) -> Option<(PackageName, VersionId)> {
+    let components = path
+        .iter()
+        .map(|component| component.to_str())
+        .collect::<Option<Vec<_>>>()?;
+    let [scope, name, version, target] = components.as_slice() else {
+        return None;
+    };
+
+    Some((
+        format!("{scope}/{name}").parse().ok()?,
+        VersionId::new(version.parse().ok()?, target.parse().ok()?),
+    ))
+}
+
+impl Reconciler {
+    pub fn last_report(&self) -> Option<ReconcileReport> {
+        self.last_report.lock().unwrap().clone()
+    }
+
+    /// Finds the versions in the index whose archive isn't stored, and the stored versions which
+    /// aren't in the index, rolling them back if `fix` is set
+    pub async fn reconcile(
+        &self,
+        app_state: &AppState,
+        fix: bool,
+    ) -> Result<ReconcileReport, Error> {
+        let _guard = self.lock.lock().await;
+        let started_at = Utc::now();
+
+        let indexed = {
+            let source = app_state.read_source().await;
+            let packages = all_packages(&source, &app_state.project).await;
+            pin!(packages);
+
+            let mut indexed = vec![];
+            while let Some((name, file)) = packages.next().await {
+                indexed.extend(file.into_keys().map(|v_id| (name.clone(), v_id)));
+            }
+            indexed
+        };
+        let checked_versions = indexed.len() as u64;
+
+        let mut mismatches = vec![];
+        let mut checks = stream::iter(indexed)
+            .map(|(name, v_id)| async move {
+                let exists = app_state.storage.get().package_exists(&name, &v_id).await;
+                (name, v_id, exists)
+            })
+            .buffer_unordered(CHECK_CONCURRENCY);
+        while let Some((name, v_id, exists)) = checks.next().await {
+            if !exists? {
+                mismatches.push((name, v_id, PartialState::IndexOnly));
+            }
+        }
+        drop(checks);
+
+        let checked_storage = match &*app_state.storage.get() {
+            Storage::FS(storage) => {
+                // the files of publishes waiting to be added to the index are referenced, and
+                // those of publishes which haven't been queued yet are within the grace period
+                let referenced = referenced_files(app_state).await;
+
+                for dir in storage.stored_versions().await? {
+                    if referenced.versions.contains(&dir) {
+                        continue;
+                    }
+
+                    match parse_version_dir(&dir) {
+                        Some((name, v_id)) => {
+                            mismatches.push((name, v_id, PartialState::StorageOnly))
+                        }
+                        None => tracing::warn!("unknown directory in storage: {}", dir.display()),
+                    }
+                }
+
+                true
+            }
+            Storage::S3(_) => false,
+        };
+
+        let mut report = ReconcileReport {
+            started_at,
+            finished_at: started_at,
+            fix,
+            checked_versions,
+            checked_storage,
+            mismatches: Vec::with_capacity(mismatches.len()),
+        };
+
+        for (name, v_id, state) in mismatches {
+            let fixed = if fix {
+                match roll_back(app_state, &name, &v_id).await {
+                    Ok(Ok(_)) => true,
+                    Ok(Err(rejection)) => {
+                        tracing::warn!("couldn't roll back {name}@{v_id}: {rejection:?}");
+                        false
+                    }
+                    Err(e) => {
+                        tracing::error!("failed to roll back {name}@{v_id}: {e}");
+                        false
+                    }
+                }
+            } else {
+                false
+            };
+
+            report.mismatches.push(Mismatch {
+                package: name,
+                version: v_id.version().clone(),
+                target: v_id.target().clone(),
+                state,
+                fixed,
+            });
+        }
+        report.finished_at = Utc::now();
+
+        let count = |state| {
+            report
+                .mismatches
+                .iter()
+                .filter(|mismatch| mismatch.state == state)
+                .count() as u64
+        };
+        METRICS.record_reconcile(
+            count(PartialState::IndexOnly),
+            count(PartialState::StorageOnly),
+            report.mismatches.iter().filter(|m| m.fixed).count() as u64,
+        );
+
+        tracing::info!(
+            "reconciliation checked {checked_versions} versions and found {} mismatches{}",
+            report.mismatches.len(),
+            if fix { ", which were rolled back" } else { "" }
+        );
+
+        *self.last_report.lock().unwrap() = Some(report.clone());
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{
+    config,
+    rate_limit::get_rate_limits_from_env,
+    storage::{get_storage_from_env, Storage},
+    AppState,
+};
+use serde::Serialize;
+use std::{
+    any::Any,
+    panic::AssertUnwindSafe,
+    sync::{Arc, RwLock},
+};
+use utoipa::ToSchema;
+
+/// A value which can be replaced while the registry runs. Requests keep using the value they got
+/// until they complete
+pub struct Reloadable<T>(RwLock<Arc<T>>);
+
+impl<T> Reloadable<T> {
+    pub fn new(value: T) -> Self {
+        Self(RwLock::new(Arc::new(value)))
+    }
+
+    pub fn get(&self) -> Arc<T> {
+        self.0.read().unwrap().clone()
+    }
+
+    fn set(&self, value: T) {
+        *self.0.write().unwrap() = Arc::new(value);
+    }
+}
+
+/// The settings in effect after a reload
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReloadReport {
+    pub storage: String,
+    pub rate_limits: String,
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<String>()
+        .cloned()
+        .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+        .unwrap_or_else(|| "invalid configuration".to_string())
+}
+
+/// Reads the configuration file again, and replaces the storage and rate limits with the ones it
+/// and the environment configure. Nothing is replaced if the configuration is invalid
+pub fn reload(app_state: &AppState) -> Result<ReloadReport, String> {
+    let previous = config::reload().map_err(|e| e.to_string())?;
+
+    // the settings are read the same way as when starting, which panics if they're invalid
+    let (storage, rate_limits): (Storage, _) =
+        match std::panic::catch_unwind(AssertUnwindSafe(|| {
+            (get_storage_from_env(), get_rate_limits_from_env())
+        })) {
+            Ok(settings) => settings,
+            Err(payload) => {
+                config::restore(previous);
+                return Err(panic_message(payload));
+            }
+        };
+
+    // callers keep what's left of their budgets unless those were changed
+    let rate_limits = app_state.rate_limits.get().reloaded(rate_limits);
+
+    let report = ReloadReport {
+        storage: storage.to_string(),
+        rate_limits: rate_limits.to_string(),
+    };
+
+    app_state.storage.set(storage);
+    app_state.rate_limits.set(rate_limits);
+
+    tracing::info!(
+        "reloaded configuration, storage: {}, rate limits: {}",
+        report.storage,
+        report.rate_limits
+    );
+
+    Ok(report)
+}
@@ -0,0 +1,282 @@
+use crate::{
+    benv,
+    error::{Error, ReqwestErrorExt},
+};
+use futures::StreamExt;
+use pesde::source::pesde::ArchiveCompression;
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, path::Path, process::Stdio, sync::LazyLock};
+use tokio::io::AsyncReadExt;
+
+/// The patterns of secrets which shouldn't be published, by the kind of secret they match
+static SECRET_PATTERNS: LazyLock<Vec<(&str, Regex)>> = LazyLock::new(|| {
+    [
+        ("AWS access key", r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b"),
+        ("GitHub token", r"\bgh[pousr]_[A-Za-z0-9]{36,}\b"),
+        ("GitHub token", r"\bgithub_pat_[A-Za-z0-9_]{80,}\b"),
+        ("Slack token", r"\bxox[abposr]-[A-Za-z0-9-]{10,}"),
+        ("Stripe secret key", r"\b[rs]k_live_[0-9A-Za-z]{24,}\b"),
+        ("Google API key", r"\bAIza[0-9A-Za-z_-]{35}\b"),
+        (
+            "Discord webhook",
+            r"https://(?:ptb\.|canary\.)?discord(?:app)?\.com/api/webhooks/\d+/[\w-]+",
+        ),
+        ("Roblox session cookie", r"_\|WARNING:-DO-NOT-SHARE-THIS\."),
+        (
+            "private key",
+            r"-----BEGIN (?:RSA |EC |DSA |OPENSSH |PGP |ENCRYPTED )?PRIVATE KEY(?: BLOCK)?-----",
+        ),
+    ]
+    .into_iter()
+    .map(|(kind, pattern)| (kind, Regex::new(pattern).unwrap()))
+    .collect()
+});
+
+/// Something a scanner found in a published archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Finding {
+    /// The scanner which found it
+    #[serde(default)]
+    pub scanner: String,
+    /// The file it was found in, if it's specific to one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub description: String,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}: `{path}`: {}", self.scanner, self.description),
+            None => write!(f, "{}: {}", self.scanner, self.description),
+        }
+    }
+}
+
+/// What happens to a version which a scanner found something in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanAction {
+    /// The publish is rejected
+    Reject,
+    /// The version is published yanked, for the registry's administrators to review
+    Quarantine,
+}
+
+impl Display for ScanAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScanAction::Reject => write!(f, "reject"),
+            ScanAction::Quarantine => write!(f, "quarantine"),
+        }
+    }
+}
+
+/// A scanner run over published archives
+#[derive(Debug)]
+pub enum Scanner {
+    /// Matches the archive's files against patterns of well known secrets
+    Secrets,
+    /// Runs a command with the path of the unpacked archive as its last argument. A non-zero exit
+    /// status means something was found, with each line of its output being a finding
+    Command { program: String, args: Vec<String> },
+    /// Sends the archive to an HTTP service, which responds with its findings
+    Http {
+        url: String,
+        reqwest: reqwest::Client,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct HttpScanResponse {
+    #[serde(default)]
+    findings: Vec<Finding>,
+}
+
+async fn scan_secrets(archive: &[u8]) -> std::io::Result<Vec<Finding>> {
+    let Some(compression) = ArchiveCompression::detect(archive) else {
+        return Ok(vec![]);
+    };
+    let mut archive = tokio_tar::Archive::new(compression.decoder(archive));
+
+    let mut findings = vec![];
+    let mut entries = archive.entries()?;
+    while let Some(entry) = entries.next().await {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path()?.to_string_lossy().replace('\\', "/");
+        let path = path.trim_start_matches("./").to_string();
+
+        let mut contents = vec![];
+        entry.read_to_end(&mut contents).await?;
+
+        for (kind, pattern) in SECRET_PATTERNS.iter() {
+            let Some(found) = pattern.find(&contents) else {
+                continue;
+            };
+
+            let line = contents[..found.start()]
+                .iter()
+                .filter(|byte| **byte == b'\n')
+                .count()
+                + 1;
+            findings.push(Finding {
+                scanner: "secrets".to_string(),
+                path: Some(path.clone()),
+                description: format!("possible {kind} on line {line}"),
+            });
+        }
+    }
+
+    Ok(findings)
+}
+
+impl Scanner {
+    fn name(&self) -> &'static str {
+        match self {
+            Scanner::Secrets => "secrets",
+            Scanner::Command { .. } => "command",
+            Scanner::Http { .. } => "http",
+        }
+    }
+
+    /// Scans the archive, whose files are unpacked in the directory
+    async fn scan(&self, archive: &[u8], dir: &Path) -> Result<Vec<Finding>, Error> {
+        let mut findings = match self {
+            Scanner::Secrets => scan_secrets(archive).await?,
+            Scanner::Command { program, args } => {
+                let output = tokio::process::Command::new(program)
+                    .args(args)
+                    .arg(dir)
+                    .stdin(Stdio::null())
+                    .kill_on_drop(true)
+                    .output()
+                    .await?;
+
+                if output.status.success() {
+                    vec![]
+                } else {
+                    let findings = String::from_utf8_lossy(&output.stdout)
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty())
+                        .map(|line| Finding {
+                            scanner: String::new(),
+                            path: None,
+                            description: line.to_string(),
+                        })
+                        .collect::<Vec<_>>();
+
+                    if findings.is_empty() {
+                        vec![Finding {
+                            scanner: String::new(),
+                            path: None,
+                            description: format!("exited with {}", output.status),
+                        }]
+                    } else {
+                        findings
+                    }
+                }
+            }
+            Scanner::Http { url, reqwest } => {
+                reqwest
+                    .post(url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+                    .body(archive.to_vec())
+                    .send()
+                    .await?
+                    .into_error()
+                    .await?
+                    .json::<HttpScanResponse>()
+                    .await?
+                    .findings
+            }
+        };
+
+        for finding in &mut findings {
+            if finding.scanner.is_empty() {
+                finding.scanner = self.name().to_string();
+            }
+        }
+
+        Ok(findings)
+    }
+}
+
+/// The scanners published archives are run through, and what happens to versions they find
+/// something in
+#[derive(Debug)]
+pub struct Scanners {
+    scanners: Vec<Scanner>,
+    pub action: ScanAction,
+}
+
+pub fn get_scanners_from_env(reqwest: reqwest::Client) -> Scanners {
+    let mut scanners = vec![];
+
+    if benv!("SCAN_SECRETS").is_ok() {
+        scanners.push(Scanner::Secrets);
+    }
+
+    if let Ok(command) = benv!("SCAN_COMMAND") {
+        let mut parts = command.split_whitespace().map(str::to_string);
+        let program = parts
+            .next()
+            .expect("Setting `SCAN_COMMAND` must not be empty");
+        scanners.push(Scanner::Command {
+            program,
+            args: parts.collect(),
+        });
+    }
+
+    if let Ok(url) = benv!("SCAN_URL") {
+        scanners.push(Scanner::Http { url, reqwest });
+    }
+
+    Scanners {
+        scanners,
+        action: match benv!("SCAN_ACTION" => "reject").as_str() {
+            "reject" => ScanAction::Reject,
+            "quarantine" => ScanAction::Quarantine,
+            _ => panic!("Setting `SCAN_ACTION` must be `reject` or `quarantine`"),
+        },
+    }
+}
+
+impl Scanners {
+    /// Runs every scanner over the archive, whose files are unpacked in the directory. A scanner
+    /// failing fails the scan, so that archives aren't published unscanned
+    pub async fn scan(&self, archive: &[u8], dir: &Path) -> Result<Vec<Finding>, Error> {
+        let mut findings = vec![];
+        for scanner in &self.scanners {
+            findings.extend(scanner.scan(archive, dir).await.inspect_err(|e| {
+                tracing::error!("{} scanner failed: {e:?}", scanner.name());
+            })?);
+        }
+
+        Ok(findings)
+    }
+}
+
+impl Display for Scanners {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.scanners.is_empty() {
+            return write!(f, "none");
+        }
+
+        write!(
+            f,
+            "{} ({})",
+            self.scanners
+                .iter()
+                .map(Scanner::name)
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.action
+        )
+    }
+}
@@ -1,22 +1,41 @@
-use crate::AppState;
+use crate::{
+    downloads::{Downloads, Period, Ranking, HISTORY_DAYS},
+    metadata_cache::read_local_package,
+    readme::plain_text,
+    storage::{decompress, Storage, StorageImpl},
+    AppState,
+};
+use actix_web::web;
 use async_stream::stream;
 use futures::{Stream, StreamExt};
 use pesde::{
     names::PackageName,
     source::{
         git_index::{root_tree, GitBasedSource},
-        pesde::{IndexFile, IndexFileEntry, PesdePackageSource, SCOPE_INFO_FILE},
+        pesde::{IndexFile, PesdePackageSource, SCOPE_INFO_FILE},
+        version_id::VersionId,
     },
     Project,
 };
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
 use tantivy::{
     doc,
-    query::QueryParser,
-    schema::{IndexRecordOption, TextFieldIndexing, TextOptions, FAST, STORED, STRING},
+    query::{
+        BooleanQuery, BoostQuery, ConstScoreQuery, FuzzyTermQuery, Occur, Query, QueryParser,
+        QueryParserError, TermQuery,
+    },
+    schema::{
+        Facet, FacetOptions, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, FAST,
+        STORED, STRING,
+    },
     tokenizer::TextAnalyzer,
-    DateTime, IndexReader, IndexWriter, Term,
+    DateTime, Index, IndexReader, IndexWriter, TantivyDocument, Term,
 };
-use tokio::pin;
+use utoipa::ToSchema;
 
 pub async fn all_packages(
     source: &PesdePackageSource,
@@ -67,10 +86,127 @@ pub async fn all_packages(
     }
 }
 
-pub async fn make_search(
-    project: &Project,
-    source: &PesdePackageSource,
-) -> (IndexReader, IndexWriter, QueryParser) {
+/// The categories of the facets packages can be filtered by
+pub const FACET_CATEGORIES: [&str; 3] = ["target", "license", "scope"];
+/// The tokenizer of the fields matched fuzzily, splitting text into lowercase words
+const WORDS_TOKENIZER: &str = "default";
+/// How much a fuzzy match in each field counts towards a package's relevance. Fuzzy matches are
+/// scored constantly and lower than the ngram fields score, so that exact matches rank first
+const FUZZY_FIELD_BOOSTS: [(&str, f32); 2] = [("name_words", 1.0), ("description_words", 0.5)];
+/// The score of packages whose name is the query, high enough for them to rank above any others
+const EXACT_NAME_SCORE: f32 = 1000.0;
+/// The maximum length of the README text indexed for a package, in bytes
+const MAX_README_LENGTH: usize = 64 * 1024;
+/// How many READMEs are read from the storage at once while indexing packages
+const README_CONCURRENCY: usize = 16;
+/// The span of time whose downloads search results are ranked by
+const RANKING_PERIOD: Period = Period::Days(HISTORY_DAYS);
+/// How much the relevance of a package grows with the logarithm of its downloads
+const DOWNLOADS_WEIGHT: f32 = 0.1;
+/// How many days after a package was last published its relevance is reduced by a quarter. It's
+/// reduced by half at most, so that finished packages which don't need updates still rank
+const RECENCY_HALF_LIFE_DAYS: f32 = 365.0;
+
+/// The downloads of every package with any over the span of time search results are ranked by
+pub async fn ranking_downloads(downloads: &Downloads) -> HashMap<PackageName, u64> {
+    downloads
+        .ranking(Ranking::Popular(Some(RANKING_PERIOD)))
+        .await
+        .iter()
+        .map(|package| (package.name.clone(), package.downloads))
+        .collect()
+}
+
+/// What the text relevance of a package is multiplied by when ranking search results, so that
+/// downloaded and maintained packages outrank abandoned or squatted ones
+pub fn ranking_factor(downloads: u64, days_since_published: f32) -> f32 {
+    let popularity = 1.0 + DOWNLOADS_WEIGHT * (downloads as f32).ln_1p();
+    let recency = 0.5 + 0.5 * 0.5f32.powf(days_since_published.max(0.0) / RECENCY_HALF_LIFE_DAYS);
+
+    popularity * recency
+}
+
+/// A package to index, with what its search document is built from besides its index file
+struct IndexedPackage {
+    name: PackageName,
+    file: IndexFile,
+    /// The downloads of the package over the span of time search results are ranked by
+    downloads: u64,
+    /// The text of the README of the version the document describes
+    readme: Option<String>,
+}
+
+/// The version a package's search document describes, its latest that isn't yanked
+fn document_version(file: &IndexFile) -> Option<&VersionId> {
+    file.iter()
+        .rev()
+        .find(|(_, entry)| !entry.yanked)
+        .map(|(v_id, _)| v_id)
+}
+
+/// Reads the README of the version a package's search document describes, as plain text
+async fn readme_text(storage: &Storage, name: &PackageName, file: &IndexFile) -> Option<String> {
+    let v_id = document_version(file)?;
+
+    let contents = match storage.read_readme(name, v_id).await {
+        Ok(contents) => contents?,
+        Err(e) => {
+            tracing::error!("failed to read readme of {name}@{v_id} to index it: {e}");
+            return None;
+        }
+    };
+    let contents = match decompress(contents).await {
+        Ok(contents) => contents,
+        Err(e) => {
+            tracing::error!("failed to decompress readme of {name}@{v_id} to index it: {e}");
+            return None;
+        }
+    };
+
+    let mut text = plain_text(&String::from_utf8_lossy(&contents));
+    if text.len() > MAX_README_LENGTH {
+        let mut end = MAX_README_LENGTH;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+
+    Some(text)
+}
+
+impl IndexedPackage {
+    async fn new(
+        storage: &Storage,
+        downloads: &HashMap<PackageName, u64>,
+        name: PackageName,
+        file: IndexFile,
+    ) -> Self {
+        Self {
+            readme: readme_text(storage, &name, &file).await,
+            downloads: downloads.get(&name).copied().unwrap_or_default(),
+            name,
+            file,
+        }
+    }
+}
+
+/// Gathers what the search documents of the packages are built from, reading their READMEs from
+/// the storage a few at a time
+async fn indexed_packages(
+    storage: &Storage,
+    downloads: &HashMap<PackageName, u64>,
+    packages: Vec<(PackageName, IndexFile)>,
+) -> Vec<IndexedPackage> {
+    futures::stream::iter(packages)
+        .map(|(name, file)| IndexedPackage::new(storage, downloads, name, file))
+        .buffered(README_CONCURRENCY)
+        .collect()
+        .await
+}
+
+/// Creates the in-memory search index, with the fields package documents are made of
+fn create_search_index() -> Index {
     let mut schema_builder = tantivy::schema::SchemaBuilder::new();
 
     let field_options = TextOptions::default().set_indexing_options(
@@ -79,11 +215,32 @@ pub async fn make_search(
             .set_index_option(IndexRecordOption::WithFreqsAndPositions),
     );
 
-    let id_field = schema_builder.add_text_field("id", STRING | STORED);
-    let scope = schema_builder.add_text_field("scope", field_options.clone());
-    let name = schema_builder.add_text_field("name", field_options.clone());
-    let description = schema_builder.add_text_field("description", field_options);
-    let published_at = schema_builder.add_date_field("published_at", FAST);
+    schema_builder.add_text_field("id", STRING | STORED);
+    schema_builder.add_text_field("name_exact", STRING);
+    schema_builder.add_text_field("scope", field_options.clone());
+    schema_builder.add_text_field("name", field_options.clone());
+    schema_builder.add_text_field("description", field_options);
+    // the ngram fields can't match typos, so the words are also indexed to be matched fuzzily
+    let words_options = TextOptions::default().set_indexing_options(
+        TextFieldIndexing::default()
+            .set_tokenizer(WORDS_TOKENIZER)
+            .set_index_option(IndexRecordOption::Basic),
+    );
+    schema_builder.add_text_field("name_words", words_options.clone());
+    schema_builder.add_text_field("description_words", words_options);
+    schema_builder.add_text_field(
+        "readme",
+        TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer(WORDS_TOKENIZER)
+                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+        ),
+    );
+    schema_builder.add_date_field("published_at", FAST);
+    // the recent downloads, which are refreshed periodically
+    schema_builder.add_u64_field("downloads", FAST);
+    // the targets, license and scope of packages, under `/target`, `/license` and `/scope`
+    schema_builder.add_facet_field("facets", FacetOptions::default());
 
     let search_index = tantivy::Index::create_in_ram(schema_builder.build());
     search_index.tokenizers().register(
@@ -93,6 +250,34 @@ pub async fn make_search(
             .build(),
     );
 
+    search_index
+}
+
+/// The parser of search queries, matching the scopes, names, descriptions and READMEs of packages
+fn create_query_parser(search_index: &Index) -> QueryParser {
+    let schema = search_index.schema();
+    let scope = schema.get_field("scope").unwrap();
+    let name = schema.get_field("name").unwrap();
+    let description = schema.get_field("description").unwrap();
+    let readme = schema.get_field("readme").unwrap();
+
+    let mut query_parser =
+        QueryParser::for_index(search_index, vec![scope, name, description, readme]);
+    query_parser.set_field_boost(scope, 2.0);
+    query_parser.set_field_boost(name, 3.5);
+    query_parser.set_field_boost(readme, 0.5);
+
+    query_parser
+}
+
+pub async fn make_search(
+    project: &Project,
+    source: &PesdePackageSource,
+    storage: &Storage,
+    downloads: &HashMap<PackageName, u64>,
+) -> (IndexReader, IndexWriter, QueryParser) {
+    let search_index = create_search_index();
+
     let search_reader = search_index
         .reader_builder()
         .reload_policy(tantivy::ReloadPolicy::Manual)
@@ -100,49 +285,617 @@ pub async fn make_search(
         .unwrap();
     let mut search_writer = search_index.writer(50_000_000).unwrap();
 
-    let stream = all_packages(source, project).await;
-    pin!(stream);
+    let packages = all_packages(source, project)
+        .await
+        .collect::<Vec<_>>()
+        .await;
+
+    for package in indexed_packages(storage, downloads, packages).await {
+        if package.file.is_empty() {
+            tracing::error!("no versions found for {}", package.name);
+            continue;
+        }
 
-    while let Some((pkg_name, mut file)) = stream.next().await {
-        let Some((_, latest_entry)) = file.pop_last() else {
-            tracing::error!("no versions found for {pkg_name}");
+        let Some(document) = package_document(&search_writer.index().schema(), &package) else {
+            tracing::debug!(
+                "all versions of {} are yanked, not indexing it",
+                package.name
+            );
             continue;
         };
 
-        search_writer.add_document(doc!(
-            id_field => pkg_name.to_string(),
-            scope => pkg_name.as_str().0,
-            name => pkg_name.as_str().1,
-            description => latest_entry.description.unwrap_or_default(),
-            published_at => DateTime::from_timestamp_secs(latest_entry.published_at.timestamp()),
-        )).unwrap();
+        search_writer.add_document(document).unwrap();
     }
 
     search_writer.commit().unwrap();
     search_reader.reload().unwrap();
 
-    let mut query_parser = QueryParser::for_index(&search_index, vec![scope, name, description]);
-    query_parser.set_field_boost(scope, 2.0);
-    query_parser.set_field_boost(name, 3.5);
+    let query_parser = create_query_parser(&search_index);
 
     (search_reader, search_writer, query_parser)
 }
 
-pub fn update_version(app_state: &AppState, name: &PackageName, entry: IndexFileEntry) {
-    let mut search_writer = app_state.search_writer.lock().unwrap();
-    let schema = search_writer.index().schema();
-    let id_field = schema.get_field("id").unwrap();
+/// The search document of a package, describing its latest version that isn't yanked. Packages
+/// whose versions are all yanked aren't searchable
+fn package_document(schema: &Schema, package: &IndexedPackage) -> Option<TantivyDocument> {
+    let name = &package.name;
+    let file = package
+        .file
+        .iter()
+        .filter(|(_, entry)| !entry.yanked)
+        .map(|(v_id, entry)| (v_id.clone(), entry.clone()))
+        .collect::<IndexFile>();
+    let (latest_version, latest_entry) = file.last_key_value()?;
+    let (scope, name_part) = name.as_str();
 
-    search_writer.delete_term(Term::from_field_text(id_field, &name.to_string()));
+    let description = latest_entry.description.clone().unwrap_or_default();
 
-    search_writer.add_document(doc!(
-        id_field => name.to_string(),
-        schema.get_field("scope").unwrap() => name.as_str().0,
-        schema.get_field("name").unwrap() => name.as_str().1,
-        schema.get_field("description").unwrap() => entry.description.unwrap_or_default(),
-        schema.get_field("published_at").unwrap() => DateTime::from_timestamp_secs(entry.published_at.timestamp())
-    )).unwrap();
+    let mut document = doc!(
+        schema.get_field("id").unwrap() => name.to_string(),
+        schema.get_field("scope").unwrap() => scope,
+        schema.get_field("name").unwrap() => name_part,
+        schema.get_field("name_words").unwrap() => name_part,
+        schema.get_field("name_exact").unwrap() => name_part,
+        schema.get_field("description").unwrap() => description.clone(),
+        schema.get_field("description_words").unwrap() => description,
+        schema.get_field("published_at").unwrap() => DateTime::from_timestamp_secs(
+            file.values().map(|entry| entry.published_at).max()?.timestamp()
+        ),
+        schema.get_field("downloads").unwrap() => package.downloads,
+    );
+    if let Some(readme) = &package.readme {
+        document.add_text(schema.get_field("readme").unwrap(), readme);
+    }
 
-    search_writer.commit().unwrap();
-    app_state.search_reader.reload().unwrap();
+    let facets = schema.get_field("facets").unwrap();
+    for v_id in file
+        .keys()
+        .filter(|v_id| v_id.version() == latest_version.version())
+    {
+        document.add_facet(
+            facets,
+            Facet::from_path(["target", &v_id.target().to_string()]),
+        );
+    }
+    if let Some(license) = &latest_entry.license {
+        document.add_facet(facets, Facet::from_path(["license", license]));
+    }
+    document.add_facet(facets, Facet::from_path(["scope", scope]));
+
+    Some(document)
+}
+
+/// The maximum amount of edits a word can be from those it matches fuzzily, by its length. Short
+/// words aren't matched fuzzily, as they'd match most others
+fn fuzzy_distance(word: &str) -> Option<u8> {
+    match word.chars().count() {
+        0..=2 => None,
+        3..=5 => Some(1),
+        _ => Some(2),
+    }
+}
+
+/// A query matching the words of the text fuzzily, so that typos still find packages. The last
+/// word is matched as a prefix too, as it may not be fully typed yet. Returns `None` if none of the
+/// words are long enough to be matched fuzzily
+fn fuzzy_query(index: &Index, text: &str) -> Option<Box<dyn Query>> {
+    let schema = index.schema();
+    let mut analyzer = index.tokenizers().get(WORDS_TOKENIZER)?;
+
+    let mut words = vec![];
+    analyzer
+        .token_stream(text)
+        .process(&mut |token| words.push(token.text.clone()));
+    let last = words.len().saturating_sub(1);
+
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![];
+    for (i, word) in words.iter().enumerate() {
+        let Some(distance) = fuzzy_distance(word) else {
+            continue;
+        };
+
+        for (field, boost) in FUZZY_FIELD_BOOSTS {
+            let term = Term::from_field_text(schema.get_field(field).unwrap(), word);
+            let query = if i == last {
+                FuzzyTermQuery::new_prefix(term, distance, true)
+            } else {
+                FuzzyTermQuery::new(term, distance, true)
+            };
+
+            clauses.push((
+                Occur::Should,
+                Box::new(BoostQuery::new(Box::new(query), boost)),
+            ));
+        }
+    }
+
+    (!clauses.is_empty()).then(|| Box::new(BooleanQuery::new(clauses)) as Box<dyn Query>)
+}
+
+/// A search query, with its qualifiers separated from its text
+#[derive(Debug, Default)]
+pub struct ParsedQuery {
+    /// The text of the query, including its quoted phrases
+    pub text: String,
+    /// The facets the results are filtered by, as their category and value
+    pub filters: Vec<(&'static str, String)>,
+}
+
+/// Parses a search query such as `http target:lune license:MIT "request builder"`, separating its
+/// `target:`, `license:` and `scope:` qualifiers from its text. The values of qualifiers can be
+/// quoted to contain spaces, and the text is left to the query parser, which searches quoted
+/// phrases as a whole
+pub fn parse_query(query: &str) -> ParsedQuery {
+    let mut parsed = ParsedQuery::default();
+    let mut chars = query.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut token = String::new();
+        let mut quoted = false;
+        while let Some(c) = chars.next_if(|c| quoted || !c.is_whitespace()) {
+            if c == '"' {
+                quoted = !quoted;
+            }
+            token.push(c);
+        }
+
+        let filter = token.split_once(':').and_then(|(key, value)| {
+            let category = FACET_CATEGORIES
+                .into_iter()
+                .find(|category| category.eq_ignore_ascii_case(key))?;
+            let value = value
+                .strip_prefix('"')
+                .map(|value| value.strip_suffix('"').unwrap_or(value))
+                .unwrap_or(value);
+
+            (!value.is_empty()).then(|| (category, value.to_string()))
+        });
+
+        match filter {
+            Some(filter) => parsed.filters.push(filter),
+            None => {
+                if !parsed.text.is_empty() {
+                    parsed.text.push(' ');
+                }
+                parsed.text.push_str(&token);
+            }
+        }
+    }
+
+    parsed
+}
+
+/// The query of a search's text, matching parts of package names and descriptions as parsed by
+/// the query parser, and the words outside of quoted phrases fuzzily. Packages whose name, with
+/// or without its scope, is the text rank first
+pub fn text_query(
+    index: &Index,
+    query_parser: &QueryParser,
+    text: &str,
+) -> Result<Box<dyn Query>, QueryParserError> {
+    let schema = index.schema();
+    let mut clauses = vec![(Occur::Should, query_parser.parse_query(text)?)];
+
+    // quoted phrases are only matched as a whole
+    let unquoted = text.split('"').step_by(2).collect::<Vec<_>>().join(" ");
+    if let Some(fuzzy) = fuzzy_query(index, &unquoted) {
+        clauses.push((Occur::Should, fuzzy));
+    }
+
+    let text = text.to_lowercase();
+    let field = if text.contains('/') {
+        "id"
+    } else {
+        "name_exact"
+    };
+    let exact = TermQuery::new(
+        Term::from_field_text(schema.get_field(field).unwrap(), &text),
+        IndexRecordOption::Basic,
+    );
+    clauses.push((
+        Occur::Should,
+        Box::new(ConstScoreQuery::new(Box::new(exact), EXACT_NAME_SCORE)),
+    ));
+
+    Ok(Box::new(BooleanQuery::new(clauses)))
+}
+
+/// How many updates can wait to be indexed before queueing more waits for them
+const QUEUE_CAPACITY: usize = 1024;
+/// The maximum amount of updates committed at once
+const MAX_BATCH_SIZE: usize = 256;
+
+enum IndexerMessage {
+    /// Updates the document of a package
+    Update(IndexedPackage),
+    /// Replaces every document with those of the packages
+    Rebuild(Vec<IndexedPackage>),
+    /// Notifies the sender once the updates queued before it are committed
+    Flush(tokio::sync::oneshot::Sender<()>),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReindexStatus {
+    #[default]
+    Idle,
+    /// The packages were read from the index, and wait for earlier updates to be applied
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// The progress of the last rebuild of the search index
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct ReindexProgress {
+    pub status: ReindexStatus,
+    /// How many packages were indexed so far
+    pub indexed: usize,
+    pub total: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Applies updates to the search index on a dedicated thread, so that requests don't wait for
+/// the index to be committed. Updates which are queued while a batch is being committed are
+/// committed together
+#[derive(Debug)]
+pub struct SearchIndexer {
+    sender: tokio::sync::mpsc::Sender<IndexerMessage>,
+    progress: Arc<std::sync::Mutex<ReindexProgress>>,
+}
+
+impl SearchIndexer {
+    pub fn spawn(mut search_writer: IndexWriter, search_reader: IndexReader) -> Self {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<IndexerMessage>(QUEUE_CAPACITY);
+        let progress = Arc::new(std::sync::Mutex::new(ReindexProgress::default()));
+
+        let thread_progress = progress.clone();
+        std::thread::Builder::new()
+            .name("search-indexer".to_string())
+            .spawn(move || {
+                let progress = thread_progress;
+                let schema = search_writer.index().schema();
+                let id_field = schema.get_field("id").unwrap();
+
+                while let Some(message) = receiver.blocking_recv() {
+                    let mut batch = vec![message];
+                    while batch.len() < MAX_BATCH_SIZE {
+                        match receiver.try_recv() {
+                            Ok(message) => batch.push(message),
+                            Err(_) => break,
+                        }
+                    }
+
+                    let batch_size = batch.len();
+                    let _span =
+                        tracing::info_span!("search_commit", updates = batch_size).entered();
+                    let mut rebuilt = false;
+                    let mut flushed = vec![];
+                    for message in batch {
+                        match message {
+                            IndexerMessage::Update(package) => {
+                                search_writer.delete_term(Term::from_field_text(
+                                    id_field,
+                                    &package.name.to_string(),
+                                ));
+
+                                if let Some(document) = package_document(&schema, &package) {
+                                    if let Err(e) = search_writer.add_document(document) {
+                                        tracing::error!("failed to index {}: {e}", package.name);
+                                    }
+                                }
+                            }
+                            IndexerMessage::Rebuild(packages) => {
+                                rebuilt = true;
+                                progress.lock().unwrap().status = ReindexStatus::Running;
+
+                                if let Err(e) = search_writer.delete_all_documents() {
+                                    tracing::error!("failed to clear search index: {e}");
+                                }
+
+                                for (i, package) in packages.into_iter().enumerate() {
+                                    if let Some(document) = package_document(&schema, &package) {
+                                        if let Err(e) = search_writer.add_document(document) {
+                                            tracing::error!(
+                                                "failed to index {}: {e}",
+                                                package.name
+                                            );
+                                        }
+                                    }
+
+                                    if (i + 1) % 100 == 0 {
+                                        progress.lock().unwrap().indexed = i + 1;
+                                    }
+                                }
+                            }
+                            IndexerMessage::Flush(sender) => flushed.push(sender),
+                        }
+                    }
+
+                    let result = search_writer
+                        .commit()
+                        .map_err(|e| format!("failed to commit search index: {e}"))
+                        .and_then(|_| {
+                            search_reader
+                                .reload()
+                                .map_err(|e| format!("failed to reload search index: {e}"))
+                        });
+                    if let Err(e) = &result {
+                        tracing::error!("{e} after {batch_size} updates");
+                    }
+
+                    for sender in flushed {
+                        let _ = sender.send(());
+                    }
+
+                    if rebuilt {
+                        let mut progress = progress.lock().unwrap();
+                        progress.finished_at = Some(chrono::Utc::now());
+                        match result {
+                            Ok(()) => {
+                                progress.status = ReindexStatus::Completed;
+                                progress.indexed = progress.total;
+                                tracing::info!(
+                                    "rebuilt search index of {} packages",
+                                    progress.total
+                                );
+                            }
+                            Err(e) => {
+                                progress.status = ReindexStatus::Failed;
+                                progress.error = Some(e);
+                            }
+                        }
+                    }
+                }
+            })
+            .expect("failed to spawn search indexer thread");
+
+        Self { sender, progress }
+    }
+
+    pub fn progress(&self) -> ReindexProgress {
+        self.progress.lock().unwrap().clone()
+    }
+
+    /// Waits for the updates queued so far to be committed
+    pub async fn flush(&self) {
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        if self
+            .sender
+            .send(IndexerMessage::Flush(sender))
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        let _ = receiver.await;
+    }
+}
+
+/// Queues an update of a package's search document after its versions changed. Failing to do so
+/// doesn't fail the change, as the package is reindexed when the registry restarts
+#[tracing::instrument(skip_all, fields(package = %name))]
+pub async fn update_package(app_state: &AppState, name: &PackageName, file: IndexFile) {
+    let downloads = ranking_downloads(&app_state.downloads).await;
+    let package =
+        IndexedPackage::new(&app_state.storage.get(), &downloads, name.clone(), file).await;
+
+    if app_state
+        .search_indexer
+        .sender
+        .send(IndexerMessage::Update(package))
+        .await
+        .is_err()
+    {
+        tracing::error!("search indexer stopped, not updating {name}'s search document");
+    }
+}
+
+/// Starts rebuilding the search index from the index in the background, returning `false` if a
+/// rebuild is already in progress
+pub fn reindex(app_state: web::Data<AppState>) -> bool {
+    {
+        let mut progress = app_state.search_indexer.progress.lock().unwrap();
+        if matches!(
+            progress.status,
+            ReindexStatus::Queued | ReindexStatus::Running
+        ) {
+            return false;
+        }
+
+        *progress = ReindexProgress {
+            status: ReindexStatus::Queued,
+            started_at: Some(chrono::Utc::now()),
+            ..Default::default()
+        };
+    }
+
+    actix_web::rt::spawn(async move {
+        // the lock is held until the packages are queued, so that updates made after they were
+        // read are applied after the rebuild instead of being overwritten by it
+        let source = app_state.read_source().await;
+
+        let packages = all_packages(&source, &app_state.project)
+            .await
+            .collect::<Vec<_>>()
+            .await;
+        app_state.search_indexer.progress.lock().unwrap().total = packages.len();
+        let downloads = ranking_downloads(&app_state.downloads).await;
+        let packages = indexed_packages(&app_state.storage.get(), &downloads, packages).await;
+
+        if app_state
+            .search_indexer
+            .sender
+            .send(IndexerMessage::Rebuild(packages))
+            .await
+            .is_err()
+        {
+            let mut progress = app_state.search_indexer.progress.lock().unwrap();
+            progress.status = ReindexStatus::Failed;
+            progress.finished_at = Some(chrono::Utc::now());
+            progress.error = Some("search indexer stopped".to_string());
+        }
+    });
+
+    true
+}
+
+/// Updates the search documents of the packages whose downloads changed since they were indexed
+/// with the given ones, returning the downloads they're now indexed with
+pub async fn refresh_downloads(
+    app_state: &AppState,
+    indexed: HashMap<PackageName, u64>,
+) -> HashMap<PackageName, u64> {
+    let downloads = ranking_downloads(&app_state.downloads).await;
+
+    let changed = downloads
+        .iter()
+        .filter(|(name, downloads)| indexed.get(*name) != Some(*downloads))
+        .map(|(name, _)| name)
+        .chain(indexed.keys().filter(|name| !downloads.contains_key(*name)))
+        .cloned()
+        .collect::<BTreeSet<_>>();
+
+    for name in changed {
+        let file = match read_local_package(app_state, &name).await {
+            Ok(Some(file)) => file,
+            Ok(None) => continue,
+            Err(e) => {
+                tracing::error!("failed to read {name} to refresh its downloads: {e}");
+                continue;
+            }
+        };
+
+        let package = IndexedPackage::new(&app_state.storage.get(), &downloads, name, file).await;
+        if app_state
+            .search_indexer
+            .sender
+            .send(IndexerMessage::Update(package))
+            .await
+            .is_err()
+        {
+            tracing::error!("search indexer stopped, not refreshing downloads");
+            break;
+        }
+    }
+
+    downloads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pesde::source::pesde::IndexFileEntry;
+    use tantivy::{collector::TopDocs, schema::Value};
+
+    fn package(name: &str, description: &str) -> IndexedPackage {
+        let entry = toml::from_str::<IndexFileEntry>(&format!(
+            "target = {{ environment = \"luau\", lib = \"init.luau\" }}\n\
+             published_at = \"2024-01-01T00:00:00Z\"\n\
+             description = {description:?}"
+        ))
+        .unwrap();
+
+        IndexedPackage {
+            name: name.parse().unwrap(),
+            file: IndexFile::from([(
+                VersionId::new("1.0.0".parse().unwrap(), entry.target.kind()),
+                entry,
+            )]),
+            downloads: 0,
+            readme: None,
+        }
+    }
+
+    /// The IDs of the packages matching the query, from the most to the least relevant
+    fn search(
+        packages: &[(&str, &str)],
+        query: impl FnOnce(&Index) -> Box<dyn Query>,
+    ) -> Vec<String> {
+        let index = create_search_index();
+        let schema = index.schema();
+        let mut writer = index.writer_with_num_threads(1, 15_000_000).unwrap();
+        for (name, description) in packages {
+            let document = package_document(&schema, &package(name, description)).unwrap();
+            writer.add_document(document).unwrap();
+        }
+        writer.commit().unwrap();
+
+        let searcher = index.reader().unwrap().searcher();
+        let id = schema.get_field("id").unwrap();
+
+        searcher
+            .search(&query(&index), &TopDocs::with_limit(10))
+            .unwrap()
+            .into_iter()
+            .map(|(_, address)| {
+                let document = searcher.doc::<TantivyDocument>(address).unwrap();
+                document
+                    .get_first(id)
+                    .unwrap()
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    const PACKAGES: [(&str, &str); 3] = [
+        ("acme/promise", "A Promise implementation"),
+        ("acme/promises", "Utilities for working with promises"),
+        ("acme/signal", "Signals for Luau"),
+    ];
+
+    #[test]
+    fn fuzzy_query_matches_typos() {
+        let results = search(&PACKAGES, |index| fuzzy_query(index, "promse").unwrap());
+
+        assert!(results.contains(&"acme/promise".to_string()));
+        assert!(!results.contains(&"acme/signal".to_string()));
+    }
+
+    #[test]
+    fn fuzzy_query_skips_short_words() {
+        let index = create_search_index();
+
+        assert!(fuzzy_query(&index, "pr").is_none());
+    }
+
+    #[test]
+    fn exact_name_ranks_first() {
+        let results = search(&PACKAGES, |index| {
+            text_query(index, &create_query_parser(index), "promise").unwrap()
+        });
+
+        assert_eq!(results.first().map(String::as_str), Some("acme/promise"));
+        assert!(results.contains(&"acme/promises".to_string()));
+    }
+
+    #[test]
+    fn exact_name_ranks_first_with_typo_matches() {
+        let packages = [
+            ("acme/promise", "Futures"),
+            (
+                "acme/premise",
+                "Promise promise promise, a premise for promises",
+            ),
+        ];
+        let results = search(&packages, |index| {
+            text_query(index, &create_query_parser(index), "premise").unwrap()
+        });
+
+        assert_eq!(results, ["acme/premise", "acme/promise"]);
+    }
 }
@@ -1,18 +1,29 @@
-use crate::{error::Error, storage::StorageImpl};
+use crate::{
+    error::Error,
+    gc::{GcReport, ReferencedFiles, GRACE_PERIOD},
+    storage::StorageImpl,
+};
 use actix_web::{
-    http::header::{CONTENT_ENCODING, CONTENT_TYPE},
+    http::header::{CONTENT_ENCODING, CONTENT_TYPE, LOCATION},
     HttpResponse,
 };
 use fs_err::tokio as fs;
-use pesde::{names::PackageName, source::version_id::VersionId};
+use pesde::{
+    names::PackageName,
+    source::{pesde::ArchiveCompression, version_id::VersionId},
+};
 use std::{
     fmt::Display,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
 #[derive(Debug)]
 pub struct FSStorage {
     pub root: PathBuf,
+    /// The URL the root is publicly served at, such as by a CDN, which requests are redirected to
+    /// rather than being served by the registry
+    pub public_url: Option<String>,
 }
 
 async fn read_file_to_response(path: &Path, content_type: &str) -> Result<HttpResponse, Error> {
@@ -26,6 +37,154 @@ async fn read_file_to_response(path: &Path, content_type: &str) -> Result<HttpRe
     })
 }
 
+/// Whether the file, relative to the storage's root, is a stored file the index doesn't reference.
+/// Files which the storage doesn't create are never considered orphaned
+fn is_orphaned(path: &Path, referenced: &ReferencedFiles) -> bool {
+    let Some(components) = path
+        .iter()
+        .map(|component| component.to_str())
+        .collect::<Option<Vec<_>>>()
+    else {
+        return false;
+    };
+
+    match components.as_slice() {
+        ["Doc", file] => file
+            .strip_suffix(".gz")
+            .is_some_and(|hash| !referenced.docs.contains(hash)),
+        [_, _, _, _, "pkg.tar.gz" | "readme.gz" | "files.json" | "attestation.json"] => path
+            .parent()
+            .is_some_and(|version| !referenced.versions.contains(version)),
+        _ => false,
+    }
+}
+
+impl FSStorage {
+    /// Redirects to the file at the public URL, if there is one
+    fn redirect(&self, path: &Path) -> Option<HttpResponse> {
+        let public_url = self.public_url.as_deref()?;
+        let path = path
+            .strip_prefix(&self.root)
+            .ok()?
+            .iter()
+            .map(|component| component.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        Some(
+            HttpResponse::TemporaryRedirect()
+                .append_header((LOCATION, format!("{public_url}/{path}")))
+                .finish(),
+        )
+    }
+
+    /// Lists the directories of the stored versions whose files are all older than the grace
+    /// period, relative to the storage's root
+    pub async fn stored_versions(&self) -> Result<Vec<PathBuf>, Error> {
+        let cutoff = SystemTime::now() - GRACE_PERIOD;
+        let mut versions = Vec::new();
+
+        let mut queue = vec![PathBuf::new()];
+        while let Some(relative) = queue.pop() {
+            let mut entries = match fs::read_dir(self.root.join(&relative)).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            // a version's directory is the fourth level, under its scope, name and version
+            let is_version = relative.components().count() == 4;
+            let mut is_recent = false;
+            let mut has_files = false;
+
+            while let Some(entry) = entries.next_entry().await? {
+                let path = relative.join(entry.file_name());
+                let metadata = entry.metadata().await?;
+
+                if metadata.is_dir() {
+                    if !is_version && path != Path::new("Doc") {
+                        queue.push(path);
+                    }
+                    continue;
+                }
+
+                has_files = true;
+                is_recent |= metadata.modified()? > cutoff;
+            }
+
+            if is_version && has_files && !is_recent {
+                versions.push(relative);
+            }
+        }
+
+        Ok(versions)
+    }
+
+    /// Deletes the stored files which aren't referenced, and are older than the grace period
+    pub async fn collect_garbage(
+        &self,
+        referenced: &ReferencedFiles,
+        dry_run: bool,
+    ) -> Result<GcReport, Error> {
+        let cutoff = SystemTime::now() - GRACE_PERIOD;
+        let mut report = GcReport {
+            dry_run,
+            ..Default::default()
+        };
+        let mut deleted_dirs = Vec::new();
+
+        let mut queue = vec![PathBuf::new()];
+        while let Some(relative) = queue.pop() {
+            let mut entries = match fs::read_dir(self.root.join(&relative)).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            while let Some(entry) = entries.next_entry().await? {
+                let path = relative.join(entry.file_name());
+                let metadata = entry.metadata().await?;
+
+                if metadata.is_dir() {
+                    queue.push(path);
+                    continue;
+                }
+
+                report.scanned_files += 1;
+
+                if !is_orphaned(&path, referenced) || metadata.modified()? > cutoff {
+                    continue;
+                }
+
+                report.reclaimed_bytes += metadata.len();
+                report.orphaned_files.push(path.display().to_string());
+
+                if !dry_run {
+                    fs::remove_file(self.root.join(&path)).await?;
+                    if let Some(parent) = path.parent() {
+                        deleted_dirs.push(parent.to_path_buf());
+                    }
+                }
+            }
+        }
+
+        // remove the directories left empty, deepest first. failing to remove one means it isn't
+        // empty, so its parents aren't either
+        deleted_dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+        for dir in deleted_dirs {
+            for ancestor in dir.ancestors() {
+                if ancestor.as_os_str().is_empty()
+                    || fs::remove_dir(self.root.join(ancestor)).await.is_err()
+                {
+                    break;
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
 impl StorageImpl for FSStorage {
     async fn store_package(
         &self,
@@ -62,7 +221,179 @@ impl StorageImpl for FSStorage {
             .join(version.version().to_string())
             .join(version.target().to_string());
 
-        read_file_to_response(&path.join("pkg.tar.gz"), "application/gzip").await
+        if let Some(response) = self.redirect(&path.join("pkg.tar.gz")) {
+            return Ok(response);
+        }
+
+        // archives keep the compression they were published with, regardless of the file name
+        Ok(match fs::read(path.join("pkg.tar.gz")).await {
+            Ok(contents) => {
+                let compression = ArchiveCompression::detect(&contents).unwrap_or_default();
+
+                HttpResponse::Ok()
+                    .append_header((CONTENT_TYPE, compression.content_type()))
+                    .append_header((CONTENT_ENCODING, compression.content_encoding()))
+                    .body(contents)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HttpResponse::NotFound().finish(),
+            Err(e) => return Err(e.into()),
+        })
+    }
+
+    async fn read_package(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let (scope, name) = package_name.as_str();
+
+        let path = self
+            .root
+            .join(scope)
+            .join(name)
+            .join(version.version().to_string())
+            .join(version.target().to_string());
+
+        match fs::read(path.join("pkg.tar.gz")).await {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn package_exists(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<bool, Error> {
+        let (scope, name) = package_name.as_str();
+
+        let path = self
+            .root
+            .join(scope)
+            .join(name)
+            .join(version.version().to_string())
+            .join(version.target().to_string());
+
+        match fs::metadata(path.join("pkg.tar.gz")).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn store_file_list(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+        contents: Vec<u8>,
+    ) -> Result<(), Error> {
+        let (scope, name) = package_name.as_str();
+
+        let path = self
+            .root
+            .join(scope)
+            .join(name)
+            .join(version.version().to_string())
+            .join(version.target().to_string());
+        fs::create_dir_all(&path).await?;
+
+        fs::write(path.join("files.json"), &contents).await?;
+
+        Ok(())
+    }
+
+    async fn read_file_list(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let (scope, name) = package_name.as_str();
+
+        let path = self
+            .root
+            .join(scope)
+            .join(name)
+            .join(version.version().to_string())
+            .join(version.target().to_string());
+
+        match fs::read(path.join("files.json")).await {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn store_attestation(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+        contents: Vec<u8>,
+    ) -> Result<(), Error> {
+        let (scope, name) = package_name.as_str();
+
+        let path = self
+            .root
+            .join(scope)
+            .join(name)
+            .join(version.version().to_string())
+            .join(version.target().to_string());
+        fs::create_dir_all(&path).await?;
+
+        fs::write(path.join("attestation.json"), &contents).await?;
+
+        Ok(())
+    }
+
+    async fn read_attestation(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let (scope, name) = package_name.as_str();
+
+        let path = self
+            .root
+            .join(scope)
+            .join(name)
+            .join(version.version().to_string())
+            .join(version.target().to_string());
+
+        match fs::read(path.join("attestation.json")).await {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete_version(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<(), Error> {
+        let (scope, name) = package_name.as_str();
+
+        let path = self
+            .root
+            .join(scope)
+            .join(name)
+            .join(version.version().to_string())
+            .join(version.target().to_string());
+
+        match fs::remove_dir_all(&path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+
+        // remove the version's, package's and scope's directories if they were left empty
+        for ancestor in path.ancestors().skip(1).take(3) {
+            if fs::remove_dir(ancestor).await.is_err() {
+                break;
+            }
+        }
+
+        Ok(())
     }
 
     async fn store_readme(
@@ -100,9 +431,34 @@ impl StorageImpl for FSStorage {
             .join(version.version().to_string())
             .join(version.target().to_string());
 
+        if let Some(response) = self.redirect(&path.join("readme.gz")) {
+            return Ok(response);
+        }
+
         read_file_to_response(&path.join("readme.gz"), "text/plain").await
     }
 
+    async fn read_readme(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let (scope, name) = package_name.as_str();
+
+        let path = self
+            .root
+            .join(scope)
+            .join(name)
+            .join(version.version().to_string())
+            .join(version.target().to_string());
+
+        match fs::read(path.join("readme.gz")).await {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     async fn store_doc(&self, doc_hash: String, contents: Vec<u8>) -> Result<(), Error> {
         let path = self.root.join("Doc");
         fs::create_dir_all(&path).await?;
@@ -115,8 +471,22 @@ impl StorageImpl for FSStorage {
     async fn get_doc(&self, doc_hash: &str) -> Result<HttpResponse, Error> {
         let path = self.root.join("Doc");
 
+        if let Some(response) = self.redirect(&path.join(format!("{doc_hash}.gz"))) {
+            return Ok(response);
+        }
+
         read_file_to_response(&path.join(format!("{doc_hash}.gz")), "text/plain").await
     }
+
+    async fn read_doc(&self, doc_hash: &str) -> Result<Option<Vec<u8>>, Error> {
+        let path = self.root.join("Doc");
+
+        match fs::read(path.join(format!("{doc_hash}.gz"))).await {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
 }
 
 impl Display for FSStorage {
@@ -1,8 +1,17 @@
-use crate::{benv, error::Error, make_reqwest};
-use actix_web::HttpResponse;
+use crate::{auth::AuthImpl, benv, error::Error, make_reqwest, metrics::METRICS, AppState};
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    http::{
+        header::{HeaderValue, ACCEPT_RANGES, CONTENT_RANGE, RANGE},
+        StatusCode,
+    },
+    HttpRequest, HttpResponse,
+};
+use async_compression::tokio::bufread::GzipDecoder;
 use pesde::{names::PackageName, source::version_id::VersionId};
 use rusty_s3::{Bucket, Credentials, UrlStyle};
 use std::fmt::Display;
+use tokio::io::AsyncReadExt;
 
 mod fs;
 mod s3;
@@ -25,6 +34,54 @@ pub trait StorageImpl: Display {
         package_name: &PackageName,
         version: &VersionId,
     ) -> Result<HttpResponse, crate::error::Error>;
+    /// Reads the archive of the version, rather than serving it
+    async fn read_package(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<Option<Vec<u8>>, crate::error::Error>;
+    /// Whether the archive of the version is stored, without reading it
+    async fn package_exists(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<bool, crate::error::Error>;
+
+    /// Stores the JSON listing of the files in the version's archive
+    async fn store_file_list(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+        contents: Vec<u8>,
+    ) -> Result<(), crate::error::Error>;
+    /// Reads the JSON listing of the files in the version's archive
+    async fn read_file_list(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<Option<Vec<u8>>, crate::error::Error>;
+
+    /// Stores the attestation document published alongside the version's archive
+    async fn store_attestation(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+        contents: Vec<u8>,
+    ) -> Result<(), crate::error::Error>;
+    /// Reads the attestation document published alongside the version's archive
+    async fn read_attestation(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<Option<Vec<u8>>, crate::error::Error>;
+
+    /// Deletes the archive, README, file listing and attestation of the version. Its documentation pages
+    /// are kept, as they may be shared with other versions
+    async fn delete_version(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<(), crate::error::Error>;
 
     async fn store_readme(
         &self,
@@ -37,6 +94,12 @@ pub trait StorageImpl: Display {
         package_name: &PackageName,
         version: &VersionId,
     ) -> Result<HttpResponse, crate::error::Error>;
+    /// Reads the gzip compressed README of the version, rather than serving it
+    async fn read_readme(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<Option<Vec<u8>>, crate::error::Error>;
 
     async fn store_doc(
         &self,
@@ -44,6 +107,128 @@ pub trait StorageImpl: Display {
         contents: Vec<u8>,
     ) -> Result<(), crate::error::Error>;
     async fn get_doc(&self, doc_hash: &str) -> Result<HttpResponse, crate::error::Error>;
+    /// Reads the gzip compressed documentation page, rather than serving it
+    async fn read_doc(&self, doc_hash: &str) -> Result<Option<Vec<u8>>, crate::error::Error>;
+}
+
+/// The `Cache-Control` header of files addressed by the hash of their contents. Registries whose
+/// reads need authentication keep them out of shared caches
+pub fn immutable_cache_control(app_state: &AppState) -> &'static str {
+    if app_state.auth.read_needs_auth() {
+        "private, max-age=31536000, immutable"
+    } else {
+        "public, max-age=31536000, immutable"
+    }
+}
+
+/// The part of a body a `Range` header asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteRange {
+    /// The whole body, if the header isn't a single range of bytes
+    Whole,
+    /// The bytes from the start to the end, inclusive
+    Part(u64, u64),
+    /// None of the body, as the range starts past its end
+    Unsatisfiable,
+}
+
+fn parse_range(header: &str, len: u64) -> ByteRange {
+    let Some((start, end)) = header
+        .trim()
+        .strip_prefix("bytes=")
+        .filter(|ranges| !ranges.contains(','))
+        .and_then(|range| range.split_once('-'))
+    else {
+        return ByteRange::Whole;
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    let Some(last) = len.checked_sub(1) else {
+        return ByteRange::Unsatisfiable;
+    };
+
+    if start.is_empty() {
+        // the last bytes of the body
+        return match end.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(suffix) => ByteRange::Part(len.saturating_sub(suffix), last),
+            Err(_) => ByteRange::Whole,
+        };
+    }
+
+    let Ok(start) = start.parse::<u64>() else {
+        return ByteRange::Whole;
+    };
+    let end = if end.is_empty() {
+        last
+    } else {
+        match end.parse::<u64>() {
+            Ok(end) if end >= start => end.min(last),
+            _ => return ByteRange::Whole,
+        }
+    };
+
+    if start > last {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Part(start, end)
+    }
+}
+
+/// Serves the part of a stored file the request's `Range` header asks for, so that downloads can
+/// be resumed. Responses which aren't the file itself, such as redirects to where it's stored,
+/// are left as they are
+pub fn ranged_response(request: &HttpRequest, response: HttpResponse) -> HttpResponse {
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (mut response, body) = response.into_parts();
+    let body = match body.try_into_bytes() {
+        Ok(body) => body,
+        Err(body) => return response.set_body(body),
+    };
+    response
+        .headers_mut()
+        .insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+
+    let len = body.len() as u64;
+    let range = request
+        .headers()
+        .get(RANGE)
+        .and_then(|range| range.to_str().ok())
+        .map_or(ByteRange::Whole, |range| parse_range(range, len));
+
+    match range {
+        ByteRange::Whole => response.set_body(BoxBody::new(body)),
+        ByteRange::Part(start, end) => {
+            *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+            response.headers_mut().insert(
+                CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes {start}-{end}/{len}")).unwrap(),
+            );
+
+            response.set_body(BoxBody::new(body.slice(start as usize..=end as usize)))
+        }
+        ByteRange::Unsatisfiable => HttpResponse::RangeNotSatisfiable()
+            .insert_header((CONTENT_RANGE, format!("bytes */{len}")))
+            .finish(),
+    }
+}
+
+/// Decompresses contents read from the storage, which may have already been decompressed by the
+/// storage's HTTP client
+pub async fn decompress(contents: Vec<u8>) -> std::io::Result<Vec<u8>> {
+    if !contents.starts_with(&[0x1f, 0x8b]) {
+        return Ok(contents);
+    }
+
+    let mut decompressed = Vec::new();
+    GzipDecoder::new(contents.as_slice())
+        .read_to_end(&mut decompressed)
+        .await?;
+
+    Ok(decompressed)
 }
 
 impl StorageImpl for Storage {
@@ -53,10 +238,14 @@ impl StorageImpl for Storage {
         version: &VersionId,
         contents: Vec<u8>,
     ) -> Result<(), Error> {
-        match self {
-            Storage::S3(s3) => s3.store_package(package_name, version, contents).await,
-            Storage::FS(fs) => fs.store_package(package_name, version, contents).await,
-        }
+        METRICS
+            .time_storage("store_package", async {
+                match self {
+                    Storage::S3(s3) => s3.store_package(package_name, version, contents).await,
+                    Storage::FS(fs) => fs.store_package(package_name, version, contents).await,
+                }
+            })
+            .await
     }
 
     async fn get_package(
@@ -64,10 +253,121 @@ impl StorageImpl for Storage {
         package_name: &PackageName,
         version: &VersionId,
     ) -> Result<HttpResponse, Error> {
-        match self {
-            Storage::S3(s3) => s3.get_package(package_name, version).await,
-            Storage::FS(fs) => fs.get_package(package_name, version).await,
-        }
+        METRICS
+            .time_storage("get_package", async {
+                match self {
+                    Storage::S3(s3) => s3.get_package(package_name, version).await,
+                    Storage::FS(fs) => fs.get_package(package_name, version).await,
+                }
+            })
+            .await
+    }
+
+    async fn read_package(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        METRICS
+            .time_storage("read_package", async {
+                match self {
+                    Storage::S3(s3) => s3.read_package(package_name, version).await,
+                    Storage::FS(fs) => fs.read_package(package_name, version).await,
+                }
+            })
+            .await
+    }
+
+    async fn package_exists(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<bool, Error> {
+        METRICS
+            .time_storage("package_exists", async {
+                match self {
+                    Storage::S3(s3) => s3.package_exists(package_name, version).await,
+                    Storage::FS(fs) => fs.package_exists(package_name, version).await,
+                }
+            })
+            .await
+    }
+
+    async fn store_file_list(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+        contents: Vec<u8>,
+    ) -> Result<(), Error> {
+        METRICS
+            .time_storage("store_file_list", async {
+                match self {
+                    Storage::S3(s3) => s3.store_file_list(package_name, version, contents).await,
+                    Storage::FS(fs) => fs.store_file_list(package_name, version, contents).await,
+                }
+            })
+            .await
+    }
+
+    async fn read_file_list(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        METRICS
+            .time_storage("read_file_list", async {
+                match self {
+                    Storage::S3(s3) => s3.read_file_list(package_name, version).await,
+                    Storage::FS(fs) => fs.read_file_list(package_name, version).await,
+                }
+            })
+            .await
+    }
+
+    async fn store_attestation(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+        contents: Vec<u8>,
+    ) -> Result<(), Error> {
+        METRICS
+            .time_storage("store_attestation", async {
+                match self {
+                    Storage::S3(s3) => s3.store_attestation(package_name, version, contents).await,
+                    Storage::FS(fs) => fs.store_attestation(package_name, version, contents).await,
+                }
+            })
+            .await
+    }
+
+    async fn read_attestation(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        METRICS
+            .time_storage("read_attestation", async {
+                match self {
+                    Storage::S3(s3) => s3.read_attestation(package_name, version).await,
+                    Storage::FS(fs) => fs.read_attestation(package_name, version).await,
+                }
+            })
+            .await
+    }
+
+    async fn delete_version(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<(), Error> {
+        METRICS
+            .time_storage("delete_version", async {
+                match self {
+                    Storage::S3(s3) => s3.delete_version(package_name, version).await,
+                    Storage::FS(fs) => fs.delete_version(package_name, version).await,
+                }
+            })
+            .await
     }
 
     async fn store_readme(
@@ -76,10 +376,14 @@ impl StorageImpl for Storage {
         version: &VersionId,
         contents: Vec<u8>,
     ) -> Result<(), Error> {
-        match self {
-            Storage::S3(s3) => s3.store_readme(package_name, version, contents).await,
-            Storage::FS(fs) => fs.store_readme(package_name, version, contents).await,
-        }
+        METRICS
+            .time_storage("store_readme", async {
+                match self {
+                    Storage::S3(s3) => s3.store_readme(package_name, version, contents).await,
+                    Storage::FS(fs) => fs.store_readme(package_name, version, contents).await,
+                }
+            })
+            .await
     }
 
     async fn get_readme(
@@ -87,24 +391,62 @@ impl StorageImpl for Storage {
         package_name: &PackageName,
         version: &VersionId,
     ) -> Result<HttpResponse, Error> {
-        match self {
-            Storage::S3(s3) => s3.get_readme(package_name, version).await,
-            Storage::FS(fs) => fs.get_readme(package_name, version).await,
-        }
+        METRICS
+            .time_storage("get_readme", async {
+                match self {
+                    Storage::S3(s3) => s3.get_readme(package_name, version).await,
+                    Storage::FS(fs) => fs.get_readme(package_name, version).await,
+                }
+            })
+            .await
+    }
+
+    async fn read_readme(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        METRICS
+            .time_storage("read_readme", async {
+                match self {
+                    Storage::S3(s3) => s3.read_readme(package_name, version).await,
+                    Storage::FS(fs) => fs.read_readme(package_name, version).await,
+                }
+            })
+            .await
     }
 
     async fn store_doc(&self, doc_hash: String, contents: Vec<u8>) -> Result<(), Error> {
-        match self {
-            Storage::S3(s3) => s3.store_doc(doc_hash, contents).await,
-            Storage::FS(fs) => fs.store_doc(doc_hash, contents).await,
-        }
+        METRICS
+            .time_storage("store_doc", async {
+                match self {
+                    Storage::S3(s3) => s3.store_doc(doc_hash, contents).await,
+                    Storage::FS(fs) => fs.store_doc(doc_hash, contents).await,
+                }
+            })
+            .await
     }
 
     async fn get_doc(&self, doc_hash: &str) -> Result<HttpResponse, Error> {
-        match self {
-            Storage::S3(s3) => s3.get_doc(doc_hash).await,
-            Storage::FS(fs) => fs.get_doc(doc_hash).await,
-        }
+        METRICS
+            .time_storage("get_doc", async {
+                match self {
+                    Storage::S3(s3) => s3.get_doc(doc_hash).await,
+                    Storage::FS(fs) => fs.get_doc(doc_hash).await,
+                }
+            })
+            .await
+    }
+
+    async fn read_doc(&self, doc_hash: &str) -> Result<Option<Vec<u8>>, Error> {
+        METRICS
+            .time_storage("read_doc", async {
+                match self {
+                    Storage::S3(s3) => s3.read_doc(doc_hash).await,
+                    Storage::FS(fs) => fs.read_doc(doc_hash).await,
+                }
+            })
+            .await
     }
 }
 
@@ -117,6 +459,12 @@ impl Display for Storage {
     }
 }
 
+fn public_url(name: &str) -> Option<String> {
+    benv!(name)
+        .ok()
+        .map(|url| url.trim_end_matches('/').to_string())
+}
+
 pub fn get_storage_from_env() -> Storage {
     if let Ok(endpoint) = benv!(parse "S3_ENDPOINT") {
         Storage::S3(s3::S3Storage {
@@ -132,9 +480,13 @@ pub fn get_storage_from_env() -> Storage {
                 benv!(required "S3_SECRET_KEY"),
             ),
             reqwest_client: make_reqwest(),
+            public_url: public_url("S3_PUBLIC_URL"),
         })
     } else if let Ok(root) = benv!(parse "FS_STORAGE_ROOT") {
-        Storage::FS(fs::FSStorage { root })
+        Storage::FS(fs::FSStorage {
+            root,
+            public_url: public_url("FS_STORAGE_PUBLIC_URL"),
+        })
     } else {
         panic!("no storage backend configured")
     }
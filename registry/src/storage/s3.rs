@@ -3,10 +3,16 @@ use crate::{
     storage::StorageImpl,
 };
 use actix_web::{http::header::LOCATION, HttpResponse};
-use pesde::{names::PackageName, source::version_id::VersionId};
-use reqwest::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use pesde::{
+    names::PackageName,
+    source::{pesde::ArchiveCompression, version_id::VersionId},
+};
+use reqwest::{
+    header::{CONTENT_ENCODING, CONTENT_TYPE},
+    StatusCode,
+};
 use rusty_s3::{
-    actions::{GetObject, PutObject},
+    actions::{DeleteObject, GetObject, HeadObject, PutObject},
     Bucket, Credentials, S3Action,
 };
 use std::{fmt::Display, time::Duration};
@@ -16,10 +22,29 @@ pub struct S3Storage {
     pub s3_bucket: Bucket,
     pub s3_credentials: Credentials,
     pub reqwest_client: reqwest::Client,
+    /// The URL the bucket is publicly served at, such as by a CDN, which requests are redirected
+    /// to rather than presigned URLs of the bucket
+    pub public_url: Option<String>,
 }
 
 pub const S3_SIGN_DURATION: Duration = Duration::from_secs(60 * 15);
 
+impl S3Storage {
+    /// Redirects to the object, at the public URL if there is one and presigned otherwise
+    fn redirect(&self, key: &str) -> HttpResponse {
+        let url = match &self.public_url {
+            Some(public_url) => format!("{public_url}/{key}"),
+            None => GetObject::new(&self.s3_bucket, Some(&self.s3_credentials), key)
+                .sign(S3_SIGN_DURATION)
+                .to_string(),
+        };
+
+        HttpResponse::TemporaryRedirect()
+            .append_header((LOCATION, url))
+            .finish()
+    }
+}
+
 impl StorageImpl for S3Storage {
     async fn store_package(
         &self,
@@ -27,6 +52,9 @@ impl StorageImpl for S3Storage {
         version: &VersionId,
         contents: Vec<u8>,
     ) -> Result<(), Error> {
+        // archives keep the compression they were published with, regardless of the object name
+        let compression = ArchiveCompression::detect(&contents).unwrap_or_default();
+
         let object_url = PutObject::new(
             &self.s3_bucket,
             Some(&self.s3_credentials),
@@ -40,8 +68,8 @@ impl StorageImpl for S3Storage {
 
         self.reqwest_client
             .put(object_url)
-            .header(CONTENT_TYPE, "application/gzip")
-            .header(CONTENT_ENCODING, "gzip")
+            .header(CONTENT_TYPE, compression.content_type())
+            .header(CONTENT_ENCODING, compression.content_encoding())
             .body(contents)
             .send()
             .await?
@@ -56,6 +84,18 @@ impl StorageImpl for S3Storage {
         package_name: &PackageName,
         version: &VersionId,
     ) -> Result<HttpResponse, Error> {
+        Ok(self.redirect(&format!(
+            "{package_name}/{}/{}/pkg.tar.gz",
+            version.version(),
+            version.target()
+        )))
+    }
+
+    async fn read_package(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<Option<Vec<u8>>, Error> {
         let object_url = GetObject::new(
             &self.s3_bucket,
             Some(&self.s3_credentials),
@@ -67,9 +107,172 @@ impl StorageImpl for S3Storage {
         )
         .sign(S3_SIGN_DURATION);
 
-        Ok(HttpResponse::TemporaryRedirect()
-            .append_header((LOCATION, object_url.as_str()))
-            .finish())
+        let response = self.reqwest_client.get(object_url).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.into_error().await?.bytes().await?.to_vec()))
+    }
+
+    async fn package_exists(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<bool, Error> {
+        let object_url = HeadObject::new(
+            &self.s3_bucket,
+            Some(&self.s3_credentials),
+            &format!(
+                "{package_name}/{}/{}/pkg.tar.gz",
+                version.version(),
+                version.target()
+            ),
+        )
+        .sign(S3_SIGN_DURATION);
+
+        let response = self.reqwest_client.head(object_url).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+
+        response.into_error().await?;
+
+        Ok(true)
+    }
+
+    async fn store_file_list(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+        contents: Vec<u8>,
+    ) -> Result<(), Error> {
+        let object_url = PutObject::new(
+            &self.s3_bucket,
+            Some(&self.s3_credentials),
+            &format!(
+                "{package_name}/{}/{}/files.json",
+                version.version(),
+                version.target()
+            ),
+        )
+        .sign(S3_SIGN_DURATION);
+
+        self.reqwest_client
+            .put(object_url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(contents)
+            .send()
+            .await?
+            .into_error()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn read_file_list(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let object_url = GetObject::new(
+            &self.s3_bucket,
+            Some(&self.s3_credentials),
+            &format!(
+                "{package_name}/{}/{}/files.json",
+                version.version(),
+                version.target()
+            ),
+        )
+        .sign(S3_SIGN_DURATION);
+
+        let response = self.reqwest_client.get(object_url).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.into_error().await?.bytes().await?.to_vec()))
+    }
+
+    async fn store_attestation(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+        contents: Vec<u8>,
+    ) -> Result<(), Error> {
+        let object_url = PutObject::new(
+            &self.s3_bucket,
+            Some(&self.s3_credentials),
+            &format!(
+                "{package_name}/{}/{}/attestation.json",
+                version.version(),
+                version.target()
+            ),
+        )
+        .sign(S3_SIGN_DURATION);
+
+        self.reqwest_client
+            .put(object_url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(contents)
+            .send()
+            .await?
+            .into_error()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn read_attestation(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let object_url = GetObject::new(
+            &self.s3_bucket,
+            Some(&self.s3_credentials),
+            &format!(
+                "{package_name}/{}/{}/attestation.json",
+                version.version(),
+                version.target()
+            ),
+        )
+        .sign(S3_SIGN_DURATION);
+
+        let response = self.reqwest_client.get(object_url).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.into_error().await?.bytes().await?.to_vec()))
+    }
+
+    async fn delete_version(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<(), Error> {
+        for file in ["pkg.tar.gz", "readme.gz", "files.json", "attestation.json"] {
+            let object_url = DeleteObject::new(
+                &self.s3_bucket,
+                Some(&self.s3_credentials),
+                &format!(
+                    "{package_name}/{}/{}/{file}",
+                    version.version(),
+                    version.target()
+                ),
+            )
+            .sign(S3_SIGN_DURATION);
+
+            self.reqwest_client
+                .delete(object_url)
+                .send()
+                .await?
+                .into_error()
+                .await?;
+        }
+
+        Ok(())
     }
 
     async fn store_readme(
@@ -107,6 +310,18 @@ impl StorageImpl for S3Storage {
         package_name: &PackageName,
         version: &VersionId,
     ) -> Result<HttpResponse, Error> {
+        Ok(self.redirect(&format!(
+            "{package_name}/{}/{}/readme.gz",
+            version.version(),
+            version.target()
+        )))
+    }
+
+    async fn read_readme(
+        &self,
+        package_name: &PackageName,
+        version: &VersionId,
+    ) -> Result<Option<Vec<u8>>, Error> {
         let object_url = GetObject::new(
             &self.s3_bucket,
             Some(&self.s3_credentials),
@@ -118,9 +333,12 @@ impl StorageImpl for S3Storage {
         )
         .sign(S3_SIGN_DURATION);
 
-        Ok(HttpResponse::TemporaryRedirect()
-            .append_header((LOCATION, object_url.as_str()))
-            .finish())
+        let response = self.reqwest_client.get(object_url).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.into_error().await?.bytes().await?.to_vec()))
     }
 
     async fn store_doc(&self, doc_hash: String, contents: Vec<u8>) -> Result<(), Error> {
@@ -146,6 +364,10 @@ impl StorageImpl for S3Storage {
     }
 
     async fn get_doc(&self, doc_hash: &str) -> Result<HttpResponse, Error> {
+        Ok(self.redirect(&format!("Doc/{}.gz", doc_hash)))
+    }
+
+    async fn read_doc(&self, doc_hash: &str) -> Result<Option<Vec<u8>>, Error> {
         let object_url = GetObject::new(
             &self.s3_bucket,
             Some(&self.s3_credentials),
@@ -153,9 +375,12 @@ impl StorageImpl for S3Storage {
         )
         .sign(S3_SIGN_DURATION);
 
-        Ok(HttpResponse::TemporaryRedirect()
-            .append_header((LOCATION, object_url.as_str()))
-            .finish())
+        let response = self.reqwest_client.get(object_url).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.into_error().await?.bytes().await?.to_vec()))
     }
 }
 
@@ -0,0 +1,148 @@
+use crate::{
+    auth::UserId,
+    benv,
+    error::{Error, ReqwestErrorExt},
+};
+use reqwest::{header::AUTHORIZATION, StatusCode};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
+
+/// How long the members of a team are cached for
+const MEMBERS_TTL: Duration = Duration::from_secs(5 * 60);
+/// The amount of members requested per page
+const PAGE_SIZE: usize = 100;
+
+/// The IDs of a team's members, and when they were fetched
+type CachedMembers = (Instant, Arc<BTreeSet<u64>>);
+
+#[derive(Debug, Deserialize)]
+struct Member {
+    id: u64,
+}
+
+/// Resolves the members of GitHub teams, which can own scopes and packages
+#[derive(Debug)]
+pub struct GitHubTeams {
+    reqwest_client: reqwest::Client,
+    /// A token which can read the members of the teams, if teams are enabled
+    token: Option<String>,
+    /// The IDs of the members of teams, by the teams' `org/team` names
+    members: RwLock<HashMap<String, CachedMembers>>,
+}
+
+/// Parses a team in the `org/team` format, normalizing its case
+pub fn parse_team(team: &str) -> Option<String> {
+    let (org, slug) = team.split_once('/')?;
+    let valid = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+
+    (valid(org) && valid(slug)).then(|| team.to_ascii_lowercase())
+}
+
+impl GitHubTeams {
+    pub fn is_enabled(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// Fetches the members of the team, or `None` if it doesn't exist
+    async fn fetch_members(&self, team: &str) -> Result<Option<BTreeSet<u64>>, Error> {
+        let (Some(token), Some((org, slug))) = (&self.token, team.split_once('/')) else {
+            return Ok(None);
+        };
+
+        let mut members = BTreeSet::new();
+        for page in 1.. {
+            let response = self
+                .reqwest_client
+                .get(format!(
+                    "https://api.github.com/orgs/{org}/teams/{slug}/members"
+                ))
+                .query(&[("per_page", PAGE_SIZE), ("page", page)])
+                .header(AUTHORIZATION, format!("Bearer {token}"))
+                .header("Accept", "application/vnd.github+json")
+                .send()
+                .await?;
+
+            if response.status() == StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+
+            let page = response.into_error().await?.json::<Vec<Member>>().await?;
+            let last = page.len() < PAGE_SIZE;
+            members.extend(page.into_iter().map(|member| member.id));
+
+            if last {
+                break;
+            }
+        }
+
+        Ok(Some(members))
+    }
+
+    /// The members of the team, which are cached for a few minutes
+    async fn members(&self, team: &str) -> Result<Arc<BTreeSet<u64>>, Error> {
+        if let Some((fetched_at, members)) = self.members.read().await.get(team) {
+            if fetched_at.elapsed() < MEMBERS_TTL {
+                return Ok(members.clone());
+            }
+        }
+
+        let members = Arc::new(self.fetch_members(team).await?.unwrap_or_default());
+        self.members
+            .write()
+            .await
+            .insert(team.to_string(), (Instant::now(), members.clone()));
+
+        Ok(members)
+    }
+
+    /// Whether the team exists and its members can be read
+    pub async fn exists(&self, team: &str) -> Result<bool, Error> {
+        Ok(match self.fetch_members(team).await? {
+            Some(members) => {
+                self.members
+                    .write()
+                    .await
+                    .insert(team.to_string(), (Instant::now(), Arc::new(members)));
+                true
+            }
+            None => false,
+        })
+    }
+
+    /// Whether the user is a member of any of the teams
+    pub async fn is_member_of_any<'a>(
+        &self,
+        teams: impl IntoIterator<Item = &'a String>,
+        user_id: UserId,
+    ) -> Result<bool, Error> {
+        if !self.is_enabled() {
+            return Ok(false);
+        }
+
+        for team in teams {
+            if self.members(team).await?.contains(&user_id.0) {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+pub fn get_teams_from_env(reqwest_client: reqwest::Client) -> GitHubTeams {
+    GitHubTeams {
+        reqwest_client,
+        token: benv!("GITHUB_TEAMS_TOKEN").ok(),
+        members: Default::default(),
+    }
+}
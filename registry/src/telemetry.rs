@@ -0,0 +1,88 @@
+use crate::benv;
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    error::Error as ActixError,
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+};
+use opentelemetry::{
+    global,
+    trace::{TraceContextExt, TracerProvider as _},
+    KeyValue,
+};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    runtime,
+    trace::{Sampler, Tracer, TracerProvider},
+    Resource,
+};
+use tracing::Subscriber;
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// The header the ID of the trace a request is part of is returned in
+const TRACE_ID_HEADER: HeaderName = HeaderName::from_static("x-trace-id");
+
+/// Creates the tracer provider exporting spans to the OTLP collector configured by
+/// `OTLP_ENDPOINT`, if any. Requests which carry a `traceparent` header are made part of the
+/// caller's trace
+pub fn get_tracer_provider_from_env() -> Option<TracerProvider> {
+    let endpoint = benv!("OTLP_ENDPOINT").ok()?;
+    let service_name = benv!("OTLP_SERVICE_NAME" => "pesde-registry");
+    let sample_ratio: f64 = benv!(parse "OTLP_SAMPLE_RATIO" => "1.0");
+
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()
+        .expect("failed to create OTLP exporter");
+
+    // the batches are exported from a thread of their own, as the provider is created before the
+    // runtime is started
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::TokioCurrentThread)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            sample_ratio,
+        ))))
+        .with_resource(Resource::new([KeyValue::new("service.name", service_name)]))
+        .build();
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider.clone());
+
+    Some(provider)
+}
+
+/// Creates the layer recording spans with the provider's tracer
+pub fn layer<S>(provider: &TracerProvider) -> OpenTelemetryLayer<S, Tracer>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+}
+
+/// Returns the ID of the request's trace in the `x-trace-id` header, so that the spans of a
+/// request can be found from its response
+pub async fn trace_id_mw(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, ActixError> {
+    let span_context = tracing::Span::current()
+        .context()
+        .span()
+        .span_context()
+        .clone();
+
+    let mut res = next.call(req).await?;
+
+    if span_context.is_valid() {
+        res.headers_mut().insert(
+            TRACE_ID_HEADER,
+            HeaderValue::from_str(&span_context.trace_id().to_string()).unwrap(),
+        );
+    }
+
+    Ok(res)
+}
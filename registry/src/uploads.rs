@@ -0,0 +1,256 @@
+use crate::auth::{api_tokens::random_hex, UserId};
+use fs_err::tokio as fs;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{io::AsyncWriteExt, sync::Mutex};
+
+/// How many upload sessions a user may have open at once
+const MAX_SESSIONS_PER_USER: usize = 4;
+
+/// An archive being uploaded in chunks, to be published once all of it was received
+#[derive(Debug)]
+struct Session {
+    user_id: UserId,
+    /// The size of the whole archive
+    size: u64,
+    /// Locked while a chunk is written, so that chunks are written one at a time
+    progress: Mutex<Progress>,
+}
+
+#[derive(Debug)]
+struct Progress {
+    /// How many bytes of the archive were received
+    received: u64,
+    updated_at: Instant,
+}
+
+/// The state of an upload session
+#[derive(Debug, Clone, Copy)]
+pub struct UploadStatus {
+    pub size: u64,
+    pub received: u64,
+}
+
+/// Why a chunk, or the completion of an upload, was rejected
+#[derive(Debug)]
+pub enum UploadRejection {
+    /// The session doesn't exist, expired, or belongs to another user
+    NotFound,
+    /// The chunk doesn't start where the received bytes end, which is given
+    OffsetMismatch(u64),
+    /// The chunk goes past the size of the archive
+    TooLarge,
+    /// Not all of the archive was received yet
+    Incomplete(UploadStatus),
+}
+
+/// The archives being uploaded in chunks. Their received bytes are kept in files, which are
+/// dropped when the registry restarts
+#[derive(Debug)]
+pub struct Uploads {
+    dir: PathBuf,
+    /// How long a session may go without receiving a chunk before it's dropped
+    timeout: Duration,
+    sessions: std::sync::Mutex<HashMap<String, Arc<Session>>>,
+}
+
+impl Uploads {
+    /// Creates an empty directory for the received bytes to be kept in, removing the sessions
+    /// which were open when the registry last stopped
+    pub async fn new(dir: PathBuf, timeout: Duration) -> std::io::Result<Self> {
+        if fs::metadata(&dir).await.is_ok() {
+            fs::remove_dir_all(&dir).await?;
+        }
+        fs::create_dir_all(&dir).await?;
+
+        Ok(Self {
+            dir,
+            timeout,
+            sessions: Default::default(),
+        })
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    fn session(&self, id: &str, user_id: UserId) -> Option<Arc<Session>> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(id)
+            .filter(|session| session.user_id == user_id)
+            .cloned()
+    }
+
+    fn is_open(&self, id: &str) -> bool {
+        self.sessions.lock().unwrap().contains_key(id)
+    }
+
+    /// Opens a session for an archive of the given size, returning its ID, or `None` if the user
+    /// has too many sessions open
+    pub async fn create(&self, user_id: UserId, size: u64) -> std::io::Result<Option<String>> {
+        let id = random_hex::<16>();
+        let session = Arc::new(Session {
+            user_id,
+            size,
+            progress: Mutex::new(Progress {
+                received: 0,
+                updated_at: Instant::now(),
+            }),
+        });
+        // locked until the file exists, so that chunks aren't written before it's created
+        let _progress = session.progress.try_lock().unwrap();
+
+        {
+            let mut sessions = self.sessions.lock().unwrap();
+            let open = sessions
+                .values()
+                .filter(|session| session.user_id == user_id)
+                .count();
+            if open >= MAX_SESSIONS_PER_USER {
+                return Ok(None);
+            }
+
+            sessions.insert(id.clone(), session.clone());
+        }
+
+        if let Err(e) = fs::write(self.path(&id), []).await {
+            self.sessions.lock().unwrap().remove(&id);
+            return Err(e);
+        }
+
+        Ok(Some(id))
+    }
+
+    /// Returns the state of the user's session
+    pub async fn status(&self, id: &str, user_id: UserId) -> Option<UploadStatus> {
+        let session = self.session(id, user_id)?;
+        let progress = session.progress.lock().await;
+
+        Some(UploadStatus {
+            size: session.size,
+            received: progress.received,
+        })
+    }
+
+    /// Appends a chunk starting at the given offset to the user's session
+    pub async fn append(
+        &self,
+        id: &str,
+        user_id: UserId,
+        offset: u64,
+        chunk: &[u8],
+    ) -> std::io::Result<Result<UploadStatus, UploadRejection>> {
+        let Some(session) = self.session(id, user_id) else {
+            return Ok(Err(UploadRejection::NotFound));
+        };
+        let mut progress = session.progress.lock().await;
+
+        if offset != progress.received {
+            return Ok(Err(UploadRejection::OffsetMismatch(progress.received)));
+        }
+        if progress.received + chunk.len() as u64 > session.size {
+            return Ok(Err(UploadRejection::TooLarge));
+        }
+
+        // the session may have been closed while waiting for the lock
+        if !self.is_open(id) {
+            return Ok(Err(UploadRejection::NotFound));
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(self.path(id))
+            .await?;
+        file.write_all(chunk).await?;
+        file.flush().await?;
+
+        progress.received += chunk.len() as u64;
+        progress.updated_at = Instant::now();
+
+        Ok(Ok(UploadStatus {
+            size: session.size,
+            received: progress.received,
+        }))
+    }
+
+    /// Closes the user's session, returning the whole archive
+    pub async fn complete(
+        &self,
+        id: &str,
+        user_id: UserId,
+    ) -> std::io::Result<Result<Vec<u8>, UploadRejection>> {
+        let Some(session) = self.session(id, user_id) else {
+            return Ok(Err(UploadRejection::NotFound));
+        };
+        let progress = session.progress.lock().await;
+
+        if progress.received < session.size {
+            return Ok(Err(UploadRejection::Incomplete(UploadStatus {
+                size: session.size,
+                received: progress.received,
+            })));
+        }
+
+        if self.sessions.lock().unwrap().remove(id).is_none() {
+            return Ok(Err(UploadRejection::NotFound));
+        }
+
+        let path = self.path(id);
+        let archive = fs::read(&path).await;
+        fs::remove_file(&path).await?;
+
+        archive.map(Ok)
+    }
+
+    /// Closes the user's session, discarding the bytes it received. Returns whether it existed
+    pub async fn cancel(&self, id: &str, user_id: UserId) -> std::io::Result<bool> {
+        let Some(session) = self.session(id, user_id) else {
+            return Ok(false);
+        };
+        let _progress = session.progress.lock().await;
+
+        if self.sessions.lock().unwrap().remove(id).is_none() {
+            return Ok(false);
+        }
+
+        fs::remove_file(self.path(id)).await?;
+
+        Ok(true)
+    }
+
+    /// Closes the sessions which didn't receive a chunk within the timeout
+    pub async fn remove_expired(&self) {
+        let expired = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let expired = sessions
+                .iter()
+                .filter(|(_, session)| {
+                    // sessions receiving a chunk aren't expired
+                    session
+                        .progress
+                        .try_lock()
+                        .is_ok_and(|progress| progress.updated_at.elapsed() > self.timeout)
+                })
+                .map(|(id, _)| id.clone())
+                .collect::<Vec<_>>();
+
+            for id in &expired {
+                sessions.remove(id);
+            }
+
+            expired
+        };
+
+        for id in expired {
+            if let Err(e) = fs::remove_file(self.path(&id)).await {
+                tracing::error!("failed to remove expired upload {id}: {e}");
+            }
+        }
+    }
+}
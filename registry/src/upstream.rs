@@ -0,0 +1,411 @@
+use crate::{
+    benv,
+    error::{Error, ReqwestErrorExt},
+    git::push_changes,
+    metadata_cache::{read_local_package, update_cached_package},
+    owners::read_scope_info,
+    search::update_package,
+    storage::{decompress, StorageImpl},
+    write_json_atomically, AppState,
+};
+use chrono::{DateTime, Utc};
+use fs_err::tokio as fs;
+use pesde::{
+    names::PackageName,
+    source::{
+        git_index::{read_file, root_tree, GitBasedSource},
+        pesde::{DocEntry, DocEntryKind, IndexFile, IndexFileEntry},
+        specifiers::DependencySpecifiers,
+        version_id::VersionId,
+    },
+};
+use reqwest::{
+    header::{ACCEPT, AUTHORIZATION},
+    StatusCode,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use tokio::io::AsyncWriteExt;
+
+/// How long a package which the upstream registry doesn't have isn't requested from it again
+const MISSING_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// A registry whose packages are mirrored by this one when they're first requested, and kept in
+/// sync with it afterwards
+#[derive(Debug)]
+pub struct Upstream {
+    /// The URL of the upstream registry's API
+    api_url: String,
+    token: Option<String>,
+    /// The URL of the upstream registry's index, which dependencies on its packages are changed
+    /// to point to this registry's index instead of
+    index_url: Option<gix::Url>,
+    reqwest: reqwest::Client,
+    path: PathBuf,
+    /// The mirrored packages, and when they were last synced with the upstream registry
+    mirrored: tokio::sync::Mutex<BTreeMap<PackageName, DateTime<Utc>>>,
+    /// The packages the upstream registry doesn't have, and when that was found out
+    missing: std::sync::Mutex<HashMap<PackageName, Instant>>,
+    /// Held while syncing, so that packages aren't mirrored twice at once
+    syncing: tokio::sync::Mutex<()>,
+}
+
+pub async fn get_upstream_from_env(
+    reqwest: reqwest::Client,
+    path: PathBuf,
+) -> std::io::Result<Option<Upstream>> {
+    let Ok(api_url) = benv!("UPSTREAM_API_URL") else {
+        return Ok(None);
+    };
+
+    let mirrored = match fs::read_to_string(&path).await {
+        Ok(contents) => serde_json::from_str(&contents)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => return Err(e),
+    };
+
+    Ok(Some(Upstream {
+        api_url: api_url.trim_end_matches('/').to_string(),
+        token: benv!("UPSTREAM_TOKEN").ok(),
+        index_url: benv!("UPSTREAM_INDEX_URL").ok().map(|url| {
+            url.as_str()
+                .try_into()
+                .expect("Setting `UPSTREAM_INDEX_URL` must be a valid URL")
+        }),
+        reqwest,
+        path,
+        mirrored: tokio::sync::Mutex::new(mirrored),
+        missing: Default::default(),
+        syncing: Default::default(),
+    }))
+}
+
+impl Upstream {
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    pub async fn is_mirrored(&self, package: &PackageName) -> bool {
+        self.mirrored.lock().await.contains_key(package)
+    }
+
+    /// Whether any package of the scope is mirrored, in which case the scope belongs to the
+    /// upstream registry
+    pub async fn mirrors_scope(&self, scope: &str) -> bool {
+        self.mirrored
+            .lock()
+            .await
+            .keys()
+            .any(|package| package.as_str().0 == scope)
+    }
+
+    /// The mirrored packages which were last synced before the cutoff
+    pub async fn synced_before(&self, cutoff: DateTime<Utc>) -> Vec<PackageName> {
+        self.mirrored
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, synced_at)| **synced_at < cutoff)
+            .map(|(package, _)| package.clone())
+            .collect()
+    }
+
+    async fn mark_synced(&self, package: &PackageName) -> std::io::Result<()> {
+        let mut mirrored = self.mirrored.lock().await;
+        mirrored.insert(package.clone(), Utc::now());
+
+        write_json_atomically(&self.path, &*mirrored).await
+    }
+
+    fn get(&self, url: String) -> reqwest::RequestBuilder {
+        let request = self.reqwest.get(url);
+        match &self.token {
+            Some(token) => request.header(AUTHORIZATION, token),
+            None => request,
+        }
+    }
+
+    /// Fetches a file from the upstream registry, returning `None` if it doesn't exist
+    async fn fetch(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+        accept: Option<&str>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let mut request = self.get(format!("{}/v0/{path}", self.api_url)).query(query);
+        if let Some(accept) = accept {
+            request = request.header(ACCEPT, accept);
+        }
+
+        let response = request.send().await?;
+        if response.status() == StatusCode::NOT_FOUND || response.status() == StatusCode::GONE {
+            return Ok(None);
+        }
+
+        Ok(Some(response.into_error().await?.bytes().await?.to_vec()))
+    }
+}
+
+/// Points dependencies on the upstream registry's packages to this registry's index
+fn rewrite_dependencies(entry: &mut IndexFileEntry, from: &gix::Url, to: &gix::Url) {
+    for (specifier, _) in entry.dependencies.values_mut() {
+        let DependencySpecifiers::Pesde(specifier) = specifier else {
+            continue;
+        };
+
+        if specifier
+            .index
+            .as_deref()
+            .and_then(|index| gix::Url::try_from(index).ok())
+            .is_some_and(|index| index == *from)
+        {
+            specifier.index = Some(to.to_bstring().to_string());
+        }
+    }
+}
+
+async fn gzip(contents: Vec<u8>) -> std::io::Result<Vec<u8>> {
+    let contents = decompress(contents).await?;
+
+    let mut gz = async_compression::tokio::write::GzipEncoder::new(vec![]);
+    gz.write_all(&contents).await?;
+    gz.shutdown().await?;
+
+    Ok(gz.into_inner())
+}
+
+/// Stores the archive, README, attestation and documentation pages of a version of the upstream registry.
+/// Returns `false` if its archive is missing or doesn't match its checksum
+async fn mirror_files(
+    app_state: &AppState,
+    upstream: &Upstream,
+    package: &PackageName,
+    v_id: &VersionId,
+    entry: &IndexFileEntry,
+) -> Result<bool, Error> {
+    let path = format!(
+        "packages/{}/{}/{}",
+        package.to_string().replace('/', "%2F"),
+        v_id.version(),
+        v_id.target()
+    );
+
+    let Some(archive) = upstream
+        .fetch(&path, &[], Some("application/octet-stream"))
+        .await?
+    else {
+        return Ok(false);
+    };
+    if entry.checksum.as_deref().is_some_and(|checksum| {
+        !checksum.eq_ignore_ascii_case(&format!("{:x}", Sha256::digest(&archive)))
+    }) {
+        tracing::warn!("archive of {package}@{v_id} from upstream doesn't match its checksum");
+        return Ok(false);
+    }
+    app_state
+        .storage
+        .get()
+        .store_package(package, v_id, archive)
+        .await?;
+
+    if let Some(readme) = upstream.fetch(&path, &[], Some("text/plain")).await? {
+        app_state
+            .storage
+            .get()
+            .store_readme(package, v_id, gzip(readme).await?)
+            .await?;
+    }
+
+    if let Some(attestation) = upstream
+        .fetch(&format!("{path}/attestation"), &[], None)
+        .await?
+    {
+        app_state
+            .storage
+            .get()
+            .store_attestation(package, v_id, attestation)
+            .await?;
+    }
+
+    let mut docs = entry.docs.iter().collect::<Vec<&DocEntry>>();
+    while let Some(doc) = docs.pop() {
+        match &doc.kind {
+            DocEntryKind::Page { name, hash } => {
+                let Some(page) = upstream.fetch(&path, &[("doc", name)], None).await? else {
+                    continue;
+                };
+
+                app_state
+                    .storage
+                    .get()
+                    .store_doc(hash.clone(), gzip(page).await?)
+                    .await?;
+            }
+            DocEntryKind::Category { items, .. } => docs.extend(items),
+        }
+    }
+
+    Ok(true)
+}
+
+/// Syncs a package with the upstream registry, mirroring the versions this registry doesn't have
+/// yet and updating whether the mirrored ones are yanked or deprecated. Returns the package's
+/// index file, or `None` if neither registry has it
+pub async fn sync_package(
+    app_state: &AppState,
+    package: &PackageName,
+) -> Result<Option<IndexFile>, Error> {
+    let Some(upstream) = &app_state.upstream else {
+        return read_local_package(app_state, package).await;
+    };
+    if app_state
+        .moderation
+        .removal_of(package, None)
+        .await
+        .is_some()
+    {
+        return read_local_package(app_state, package).await;
+    }
+    let _guard = upstream.syncing.lock().await;
+
+    let (scope, name) = package.as_str();
+    let Some(contents) = upstream
+        .fetch(&format!("index/{scope}/{name}"), &[], None)
+        .await?
+    else {
+        if !upstream.is_mirrored(package).await {
+            upstream
+                .missing
+                .lock()
+                .unwrap()
+                .insert(package.clone(), Instant::now());
+        }
+
+        return read_local_package(app_state, package).await;
+    };
+    let upstream_entries: IndexFile = toml::de::from_str(&String::from_utf8_lossy(&contents))?;
+
+    let rewrite = match &upstream.index_url {
+        Some(from) => Some((
+            from.clone(),
+            app_state.read_source().await.repo_url().clone(),
+        )),
+        None => None,
+    };
+    let local_entries = read_local_package(app_state, package)
+        .await?
+        .unwrap_or_default();
+
+    // the files of new versions are mirrored before the index is locked, as it may take a while
+    let mut mirrored = IndexFile::new();
+    for (v_id, mut entry) in upstream_entries {
+        if let Some((from, to)) = &rewrite {
+            rewrite_dependencies(&mut entry, from, to);
+        }
+
+        let is_new = !local_entries.contains_key(&v_id);
+        if is_new
+            && (app_state
+                .moderation
+                .removal_of(package, Some(v_id.version()))
+                .await
+                .is_some()
+                || !mirror_files(app_state, upstream, package, &v_id, &entry).await?)
+        {
+            continue;
+        }
+
+        mirrored.insert(v_id, entry);
+    }
+
+    let entries = {
+        let source = app_state.write_source().await;
+        source
+            .refresh(&app_state.project, &app_state.reqwest_client)
+            .await
+            .map_err(Box::new)?;
+
+        let old_entries: IndexFile = {
+            let repo = gix::open(source.path(&app_state.project))?;
+            let tree = root_tree(&repo)?;
+            toml::de::from_str(&read_file(&tree, [scope, name])?.unwrap_or_default())?
+        };
+
+        let mut entries = old_entries.clone();
+        for (v_id, entry) in mirrored {
+            match entries.get_mut(&v_id) {
+                Some(local) => {
+                    local.yanked = entry.yanked;
+                    local.deprecated = entry.deprecated;
+                }
+                None => {
+                    entries.insert(v_id, entry);
+                }
+            }
+        }
+
+        if entries != old_entries {
+            push_changes(
+                app_state,
+                &source,
+                scope,
+                HashMap::from([(name, Some(toml::to_string(&entries)?.into_bytes()))]),
+                &format!("mirror {package}"),
+            )?;
+        }
+
+        entries
+    };
+
+    app_state.dependents.update(package, &entries);
+    update_cached_package(app_state, package, &entries);
+    update_package(app_state, package, entries.clone()).await;
+
+    upstream.mark_synced(package).await?;
+
+    Ok((!entries.is_empty()).then_some(entries))
+}
+
+/// Mirrors a package this registry doesn't have from the upstream registry, if there's one and
+/// the package wasn't removed by this registry's administrators
+pub async fn mirror_package(
+    app_state: &AppState,
+    package: &PackageName,
+) -> Result<Option<IndexFile>, Error> {
+    let Some(upstream) = &app_state.upstream else {
+        return Ok(None);
+    };
+
+    if upstream
+        .missing
+        .lock()
+        .unwrap()
+        .get(package)
+        .is_some_and(|found_at| found_at.elapsed() < MISSING_TTL)
+    {
+        return Ok(None);
+    }
+
+    // scopes created on this registry take precedence over the upstream registry's
+    let scope_exists = {
+        let source = app_state.read_source().await;
+        let repo = gix::open(source.path(&app_state.project))?;
+        let tree = root_tree(&repo)?;
+        read_scope_info(&tree, package.as_str().0)?.is_some()
+    };
+    if scope_exists {
+        return Ok(None);
+    }
+
+    match sync_package(app_state, package).await {
+        Ok(entries) => Ok(entries),
+        Err(e) => {
+            tracing::error!("failed to mirror {package} from upstream: {e}");
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{archive::ArchiveFile, benv};
+use futures::StreamExt;
+use pesde::{
+    manifest::Manifest,
+    source::{
+        pesde::{ArchiveCompression, DocEntry, DocEntryKind},
+        IGNORED_DIRS, IGNORED_FILES,
+    },
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
+};
+use tokio_tar::EntryType;
+
+/// Files which can't be published, on top of those pesde ignores when downloading
+const ADDITIONAL_FORBIDDEN_FILES: &[&str] = &["default.project.json"];
+
+/// The limits published archives are checked against, on top of the index's maximum archive size
+#[derive(Debug)]
+pub struct PublishLimits {
+    /// The maximum size of a file in an archive, in bytes
+    pub max_file_size: Option<u64>,
+    /// The maximum size of all files in an archive once unpacked, in bytes
+    pub max_unpacked_size: Option<u64>,
+    /// The extensions of files which can't be published, lowercase and without the leading dot
+    pub forbidden_extensions: BTreeSet<String>,
+    /// The maximum size of a documentation page, in bytes
+    pub max_doc_page_size: u64,
+    /// The maximum size of all documentation pages, in bytes
+    pub max_docs_size: u64,
+}
+
+pub fn get_publish_limits_from_env() -> PublishLimits {
+    PublishLimits {
+        max_file_size: benv!(parse "PUBLISH_MAX_FILE_SIZE").ok(),
+        max_unpacked_size: benv!(parse "PUBLISH_MAX_UNPACKED_SIZE").ok(),
+        forbidden_extensions: benv!("PUBLISH_FORBIDDEN_EXTENSIONS")
+            .map(|extensions| {
+                extensions
+                    .split(',')
+                    .map(|extension| extension.trim().trim_start_matches('.').to_lowercase())
+                    .filter(|extension| !extension.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        max_doc_page_size: benv!(parse "PUBLISH_MAX_DOC_PAGE_SIZE" => "1048576"),
+        max_docs_size: benv!(parse "PUBLISH_MAX_DOCS_SIZE" => "16777216"),
+    }
+}
+
+impl Display for PublishLimits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.max_file_size {
+            Some(size) => write!(f, "files up to {size} bytes")?,
+            None => write!(f, "files of any size")?,
+        }
+        match self.max_unpacked_size {
+            Some(size) => write!(f, ", up to {size} bytes unpacked")?,
+            None => write!(f, ", any size unpacked")?,
+        }
+        if !self.forbidden_extensions.is_empty() {
+            write!(
+                f,
+                ", forbidden extensions: {}",
+                self.forbidden_extensions
+                    .iter()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?;
+        }
+        write!(
+            f,
+            ", doc pages up to {} bytes, up to {} bytes of docs",
+            self.max_doc_page_size, self.max_docs_size
+        )?;
+
+        Ok(())
+    }
+}
+
+fn is_readme(file_name: &str) -> bool {
+    file_name
+        .to_lowercase()
+        .split_once('.')
+        .is_some_and(|(file, ext)| file == "readme" && (ext == "md" || ext == "txt"))
+}
+
+/// Checks the entries of an archive before it's unpacked, returning every violation found
+pub async fn check_entries(archive: &[u8], limits: &PublishLimits) -> std::io::Result<Vec<String>> {
+    let mut violations = Vec::new();
+
+    let Some(compression) = ArchiveCompression::detect(archive) else {
+        violations.push("archive is not compressed with gzip or zstd".to_string());
+        return Ok(violations);
+    };
+    let mut archive = tokio_tar::Archive::new(compression.decoder(archive));
+
+    let mut readmes = 0;
+    let mut unpacked_size = 0;
+    let mut entries = archive.entries()?;
+    while let Some(entry) = entries.next().await {
+        let entry = entry?;
+        let path = String::from_utf8_lossy(&entry.path_bytes()).replace('\\', "/");
+        let path = path.trim_start_matches("./").to_string();
+
+        if path.starts_with('/') || path.split('/').next().is_some_and(|c| c.ends_with(':')) {
+            violations.push(format!("`{path}` is an absolute path"));
+            continue;
+        }
+        if path.split('/').any(|component| component == "..") {
+            violations.push(format!("`{path}` is outside of the package"));
+            continue;
+        }
+
+        let entry_type = entry.header().entry_type();
+        if entry_type == EntryType::Directory {
+            let name = path.trim_end_matches('/');
+            if IGNORED_DIRS.contains(&name) {
+                violations.push(format!("`{name}` is a forbidden directory"));
+            }
+            continue;
+        }
+        if !entry_type.is_file() {
+            let kind = if entry_type.is_symlink() {
+                "symbolic link"
+            } else if entry_type.is_hard_link() {
+                "hard link"
+            } else {
+                "special file"
+            };
+            violations.push(format!("`{path}` is a {kind}, only files can be published"));
+            continue;
+        }
+
+        if let Some((dir, _)) = path.split_once('/') {
+            if IGNORED_DIRS.contains(&dir) {
+                // the directory itself may not have an entry
+                violations.push(format!("`{dir}` is a forbidden directory"));
+                continue;
+            }
+        } else if IGNORED_FILES.contains(&path.as_str())
+            || ADDITIONAL_FORBIDDEN_FILES.contains(&path.as_str())
+        {
+            violations.push(format!("`{path}` is a forbidden file"));
+        } else if is_readme(&path) {
+            readmes += 1;
+        }
+
+        let size = entry.header().size()?;
+        unpacked_size += size;
+        if let Some(max) = limits.max_file_size.filter(|max| size > *max) {
+            violations.push(format!(
+                "`{path}` is {size} bytes, larger than the maximum of {max} bytes"
+            ));
+        }
+
+        let file_name = path.rsplit('/').next().unwrap_or(&path);
+        if let Some((_, extension)) = file_name.rsplit_once('.') {
+            if limits
+                .forbidden_extensions
+                .contains(&extension.to_lowercase())
+            {
+                violations.push(format!("`{path}` has a forbidden extension"));
+            }
+        }
+    }
+
+    if readmes > 1 {
+        violations.push("archive contains multiple readme files".to_string());
+    }
+    if let Some(max) = limits.max_unpacked_size.filter(|max| unpacked_size > *max) {
+        violations.push(format!(
+            "archive is {unpacked_size} bytes unpacked, larger than the maximum of {max} bytes"
+        ));
+    }
+
+    Ok(violations)
+}
+
+/// Checks that the manifest's target exports something, and that the files it exports are in the
+/// archive, returning every violation found
+pub fn check_exports(manifest: &Manifest, files: &BTreeMap<String, ArchiveFile>) -> Vec<String> {
+    let target = &manifest.target;
+    if target.lib_path().is_none()
+        && target.types_path().is_none()
+        && target.bin_path().is_none()
+        && target.scripts().is_none_or(|scripts| scripts.is_empty())
+    {
+        return vec!["target doesn't export a lib, types, bin or scripts".to_string()];
+    }
+
+    [
+        ("lib", target.lib_path()),
+        ("types", target.types_path()),
+        ("bin", target.bin_path()),
+    ]
+    .into_iter()
+    .filter_map(|(kind, path)| {
+        let path = path?.normalize();
+        (!files.contains_key(path.as_str()))
+            .then(|| format!("{kind} export `{path}` isn't in the archive"))
+    })
+    .collect()
+}
+
+/// Checks the documentation of a package, given the sizes of its pages by their hashes, returning
+/// every violation found
+pub fn check_docs(
+    docs: &BTreeSet<DocEntry>,
+    page_sizes: &HashMap<String, u64>,
+    limits: &PublishLimits,
+) -> Vec<String> {
+    fn check_doc_entries(
+        entries: &BTreeSet<DocEntry>,
+        categories: &mut Vec<String>,
+        page_sizes: &HashMap<String, u64>,
+        limits: &PublishLimits,
+        violations: &mut Vec<String>,
+    ) {
+        for entry in entries {
+            match &entry.kind {
+                DocEntryKind::Page { name, hash } => match page_sizes.get(hash) {
+                    Some(size) if *size > limits.max_doc_page_size => violations.push(format!(
+                        "doc `{name}` is {size} bytes, larger than the maximum of {} bytes",
+                        limits.max_doc_page_size
+                    )),
+                    Some(_) => {}
+                    None => violations.push(format!("doc `{name}`'s contents are missing")),
+                },
+                DocEntryKind::Category { items, .. } => {
+                    // the docs site links categories by their labels, so a category labelled like
+                    // one containing it would link back to it
+                    if categories
+                        .iter()
+                        .any(|category| category.eq_ignore_ascii_case(&entry.label))
+                    {
+                        violations.push(format!(
+                            "doc category `{}` is labelled like a category containing it",
+                            entry.label
+                        ));
+                    }
+
+                    categories.push(entry.label.clone());
+                    check_doc_entries(items, categories, page_sizes, limits, violations);
+                    categories.pop();
+                }
+            }
+        }
+    }
+
+    let mut violations = vec![];
+    check_doc_entries(docs, &mut vec![], page_sizes, limits, &mut violations);
+
+    let size = page_sizes.values().sum::<u64>();
+    if size > limits.max_docs_size {
+        violations.push(format!(
+            "docs are {size} bytes, larger than the maximum of {} bytes",
+            limits.max_docs_size
+        ));
+    }
+
+    violations
+}
@@ -0,0 +1,268 @@
+use crate::{auth::api_tokens::random_hex, benv};
+use chrono::{DateTime, Utc};
+use pesde::{manifest::target::TargetKind, names::PackageName, retry::RetryPolicy};
+use ring::hmac;
+use semver::Version;
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use utoipa::ToSchema;
+
+/// How many deliveries are kept in the delivery log
+pub const MAX_DELIVERIES: usize = 100;
+
+/// Something which happened to a package, which webhooks are notified of
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Publish {
+        package: PackageName,
+        version: Version,
+        target: TargetKind,
+    },
+    Yank {
+        package: PackageName,
+        version: Version,
+        /// The target which was yanked, or `any` if all of them were
+        target: String,
+    },
+    Unyank {
+        package: PackageName,
+        version: Version,
+        /// The target which was unyanked, or `any` if all of them were
+        target: String,
+    },
+    Deprecate {
+        package: PackageName,
+        /// The range of versions which were deprecated
+        versions: String,
+        message: String,
+    },
+    Undeprecate {
+        package: PackageName,
+        /// The range of versions which were undeprecated
+        versions: String,
+    },
+}
+
+impl Event {
+    fn name(&self) -> &'static str {
+        match self {
+            Event::Publish { .. } => "publish",
+            Event::Yank { .. } => "yank",
+            Event::Unyank { .. } => "unyank",
+            Event::Deprecate { .. } => "deprecate",
+            Event::Undeprecate { .. } => "undeprecate",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+    id: &'a str,
+    timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+/// An attempt to notify a webhook of an event
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Delivery {
+    pub id: String,
+    /// The index of the webhook in the configured webhooks. Their URLs aren't exposed, since they
+    /// often contain secrets
+    pub webhook: usize,
+    #[schema(value_type = String)]
+    pub event: &'static str,
+    /// The delivery this one redelivers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redelivery_of: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub status: DeliveryStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip)]
+    body: Arc<str>,
+}
+
+/// Webhooks configured by the registry's operators, which are sent signed JSON payloads
+#[derive(Debug, Clone)]
+pub struct Webhooks {
+    reqwest_client: reqwest::Client,
+    urls: Arc<[reqwest::Url]>,
+    key: Option<hmac::Key>,
+    retry_policy: RetryPolicy,
+    deliveries: Arc<Mutex<VecDeque<Delivery>>>,
+}
+
+pub fn get_webhooks_from_env(reqwest_client: reqwest::Client) -> Webhooks {
+    let urls = benv!("WEBHOOK_URLS")
+        .map(|urls| {
+            urls.split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(|url| {
+                    url.parse()
+                        .expect("Setting `WEBHOOK_URLS` must contain valid URLs")
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let key = (!urls.is_empty()).then(|| {
+        hmac::Key::new(
+            hmac::HMAC_SHA256,
+            benv!(required "WEBHOOK_SECRET").as_bytes(),
+        )
+    });
+
+    Webhooks {
+        reqwest_client,
+        urls: urls.into(),
+        key,
+        retry_policy: RetryPolicy {
+            max_retries: 5,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+        },
+        deliveries: Default::default(),
+    }
+}
+
+impl Webhooks {
+    /// The amount of configured webhooks
+    pub fn len(&self) -> usize {
+        self.urls.len()
+    }
+
+    /// Notifies every webhook of the event in the background
+    pub fn send(&self, event: Event) {
+        for webhook in 0..self.urls.len() {
+            let id = random_hex::<8>();
+            let body = serde_json::to_string(&Payload {
+                id: &id,
+                timestamp: Utc::now(),
+                event: &event,
+            })
+            .unwrap();
+
+            self.deliver(Delivery {
+                id,
+                webhook,
+                event: event.name(),
+                redelivery_of: None,
+                created_at: Utc::now(),
+                status: DeliveryStatus::Pending,
+                response_status: None,
+                error: None,
+                body: body.into(),
+            });
+        }
+    }
+
+    /// Sends the payload of a delivery again in the background, returning the new delivery's ID
+    pub fn redeliver(&self, id: &str) -> Option<String> {
+        let original = self
+            .deliveries
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|delivery| delivery.id == id)
+            .cloned()?;
+
+        let id = random_hex::<8>();
+        self.deliver(Delivery {
+            id: id.clone(),
+            redelivery_of: Some(original.id),
+            created_at: Utc::now(),
+            status: DeliveryStatus::Pending,
+            response_status: None,
+            error: None,
+            ..original
+        });
+
+        Some(id)
+    }
+
+    /// The logged deliveries, newest first
+    pub fn deliveries(&self) -> Vec<Delivery> {
+        self.deliveries
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+
+    fn deliver(&self, delivery: Delivery) {
+        let request = self
+            .reqwest_client
+            .post(self.urls[delivery.webhook].clone())
+            .header("content-type", "application/json")
+            .header("x-pesde-event", delivery.event)
+            .header("x-pesde-delivery", &delivery.id)
+            .header(
+                "x-pesde-signature-256",
+                format!(
+                    "sha256={}",
+                    hmac::sign(self.key.as_ref().unwrap(), delivery.body.as_bytes())
+                        .as_ref()
+                        .iter()
+                        .map(|byte| format!("{byte:02x}"))
+                        .collect::<String>()
+                ),
+            )
+            .body(delivery.body.to_string());
+
+        let id = delivery.id.clone();
+        {
+            let mut deliveries = self.deliveries.lock().unwrap();
+            if deliveries.len() >= MAX_DELIVERIES {
+                deliveries.pop_front();
+            }
+            deliveries.push_back(delivery);
+        }
+
+        let webhooks = self.clone();
+        actix_web::rt::spawn(async move {
+            let (status, response_status, error) = match webhooks.retry_policy.send(request).await {
+                Ok(response) if response.status().is_success() => {
+                    (DeliveryStatus::Delivered, Some(response.status()), None)
+                }
+                Ok(response) => (DeliveryStatus::Failed, Some(response.status()), None),
+                Err(e) => (DeliveryStatus::Failed, None, Some(e.to_string())),
+            };
+
+            if status == DeliveryStatus::Failed {
+                tracing::warn!("failed to deliver webhook payload {id}");
+            }
+
+            // the delivery may have been pushed out of the log in the meantime
+            if let Some(delivery) = webhooks
+                .deliveries
+                .lock()
+                .unwrap()
+                .iter_mut()
+                .find(|delivery| delivery.id == id)
+            {
+                delivery.status = status;
+                delivery.response_status = response_status.map(|status| status.as_u16());
+                delivery.error = error;
+            }
+        });
+    }
+}
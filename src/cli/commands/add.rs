@@ -1,23 +1,25 @@
-use std::{collections::HashSet, str::FromStr};
+use std::collections::HashSet;
 
 use anyhow::Context;
 use clap::Args;
 use colored::Colorize;
+use relative_path::RelativePathBuf;
 use semver::VersionReq;
 
 use crate::cli::{config::read_config, AnyPackageIdentifier, VersionedPackageName};
 use pesde::{
-    manifest::target::TargetKind,
+    manifest::{target::TargetKind, DependencyType},
     names::PackageNames,
     source::{
         git::{specifier::GitDependencySpecifier, GitPackageSource},
+        local::specifier::LocalDependencySpecifier,
         pesde::{specifier::PesdeDependencySpecifier, PesdePackageSource},
         specifiers::DependencySpecifiers,
         traits::PackageSource,
         workspace::WorkspacePackageSource,
         PackageSources,
     },
-    Project, DEFAULT_INDEX_NAME,
+    Project,
 };
 
 #[derive(Debug, Args)]
@@ -38,6 +40,10 @@ pub struct AddCommand {
     #[arg(short, long)]
     alias: Option<String>,
 
+    /// The path of the package inside of the repository, for Git dependencies
+    #[arg(long)]
+    path: Option<RelativePathBuf>,
+
     /// Whether to add the package as a peer dependency
     #[arg(short, long)]
     peer: bool,
@@ -48,19 +54,29 @@ pub struct AddCommand {
 }
 
 impl AddCommand {
-    pub async fn run(self, project: Project) -> anyhow::Result<()> {
+    pub async fn run(self, project: Project, reqwest: reqwest::Client) -> anyhow::Result<()> {
         let manifest = project
             .deser_manifest()
             .await
             .context("failed to read manifest")?;
 
+        if let Some(target) = self
+            .target
+            .as_ref()
+            .filter(|target| !manifest.is_target_declared(target))
+        {
+            anyhow::bail!("custom target {target} is not declared in `custom_targets`");
+        }
+
         let (source, specifier) = match &self.name {
             AnyPackageIdentifier::PackageName(versioned) => match &versioned {
                 VersionedPackageName(PackageNames::Pesde(name), version) => {
-                    let index = manifest
-                        .indices
-                        .get(self.index.as_deref().unwrap_or(DEFAULT_INDEX_NAME))
-                        .cloned();
+                    let index = project.index_url(
+                        &manifest.indices,
+                        self.index
+                            .as_deref()
+                            .unwrap_or(manifest.default_index_name()),
+                    );
 
                     if let Some(index) = self.index.as_ref().filter(|_| index.is_none()) {
                         println!("{}: index {index} not found", "error".red().bold());
@@ -78,6 +94,7 @@ impl AddCommand {
                         version: version.clone().unwrap_or(VersionReq::STAR),
                         index: self.index,
                         target: self.target,
+                        platform: Default::default(),
                     });
 
                     (source, specifier)
@@ -86,7 +103,11 @@ impl AddCommand {
                 VersionedPackageName(PackageNames::Wally(name), version) => {
                     let index = manifest
                         .wally_indices
-                        .get(self.index.as_deref().unwrap_or(DEFAULT_INDEX_NAME))
+                        .get(
+                            self.index
+                                .as_deref()
+                                .unwrap_or(manifest.default_index_name()),
+                        )
                         .cloned();
 
                     if let Some(index) = self.index.as_ref().filter(|_| index.is_none()) {
@@ -103,6 +124,7 @@ impl AddCommand {
                             name: name.clone(),
                             version: version.clone().unwrap_or(VersionReq::STAR),
                             index: self.index,
+                            platform: Default::default(),
                         },
                     );
 
@@ -114,7 +136,8 @@ impl AddCommand {
                 DependencySpecifiers::Git(GitDependencySpecifier {
                     repo: url.clone(),
                     rev: rev.to_string(),
-                    path: None,
+                    path: self.path.clone(),
+                    platform: Default::default(),
                 }),
             ),
             AnyPackageIdentifier::Workspace(VersionedPackageName(name, version)) => (
@@ -124,12 +147,13 @@ impl AddCommand {
                         name: name.clone(),
                         version: version.clone().unwrap_or_default(),
                         target: self.target,
+                        platform: Default::default(),
                     },
                 ),
             ),
         };
         source
-            .refresh(&project)
+            .refresh(&project, &reqwest)
             .await
             .context("failed to refresh package source")?;
 
@@ -137,6 +161,7 @@ impl AddCommand {
             .resolve(
                 &specifier,
                 &project,
+                &reqwest,
                 manifest.target.kind(),
                 &mut HashSet::new(),
             )
@@ -152,49 +177,37 @@ impl AddCommand {
         };
 
         let project_target = manifest.target.kind();
-        let mut manifest = toml_edit::DocumentMut::from_str(
-            &project
-                .read_manifest()
-                .await
-                .context("failed to read manifest")?,
-        )
-        .context("failed to parse manifest")?;
-        let dependency_key = if self.peer {
-            "peer_dependencies"
+        let mut editor = project
+            .manifest_editor()
+            .await
+            .context("failed to read manifest")?;
+        let (dependency_ty, dependency_key) = if self.peer {
+            (DependencyType::Peer, "peer_dependencies")
         } else if self.dev {
-            "dev_dependencies"
+            (DependencyType::Dev, "dev_dependencies")
         } else {
-            "dependencies"
+            (DependencyType::Standard, "dependencies")
         };
 
         let alias = self.alias.unwrap_or_else(|| match self.name.clone() {
             AnyPackageIdentifier::PackageName(versioned) => versioned.0.as_str().1.to_string(),
-            AnyPackageIdentifier::Url((url, _)) => url
-                .path
-                .to_string()
-                .split('/')
-                .last()
-                .map(|s| s.to_string())
-                .unwrap_or(url.path.to_string()),
+            AnyPackageIdentifier::Url((url, _)) => {
+                match self.path.as_ref().and_then(|path| path.file_name()) {
+                    Some(name) => name.to_string(),
+                    None => url
+                        .path
+                        .to_string()
+                        .split('/')
+                        .next_back()
+                        .map(|s| s.to_string())
+                        .unwrap_or(url.path.to_string()),
+                }
+            }
             AnyPackageIdentifier::Workspace(versioned) => versioned.0.as_str().1.to_string(),
         });
 
-        let field = &mut manifest[dependency_key]
-            .or_insert(toml_edit::Item::Table(toml_edit::Table::new()))[&alias];
-
-        match specifier {
+        let specifier = match specifier {
             DependencySpecifiers::Pesde(spec) => {
-                field["name"] = toml_edit::value(spec.name.clone().to_string());
-                field["version"] = toml_edit::value(format!("^{}", version_id.version()));
-
-                if *version_id.target() != project_target {
-                    field["target"] = toml_edit::value(version_id.target().to_string());
-                }
-
-                if let Some(index) = spec.index.filter(|i| i != DEFAULT_INDEX_NAME) {
-                    field["index"] = toml_edit::value(index);
-                }
-
                 println!(
                     "added {}@{} {} to {}",
                     spec.name,
@@ -202,46 +215,93 @@ impl AddCommand {
                     version_id.target(),
                     dependency_key
                 );
+
+                DependencySpecifiers::Pesde(PesdeDependencySpecifier {
+                    version: VersionReq::parse(&format!("^{}", version_id.version()))
+                        .context("failed to parse version requirement")?,
+                    target: (*version_id.target() != project_target)
+                        .then_some(version_id.target().clone()),
+                    index: spec.index.filter(|i| i != manifest.default_index_name()),
+                    ..spec
+                })
             }
             #[cfg(feature = "wally-compat")]
             DependencySpecifiers::Wally(spec) => {
-                field["wally"] = toml_edit::value(spec.name.clone().to_string());
-                field["version"] = toml_edit::value(format!("^{}", version_id.version()));
-
-                if let Some(index) = spec.index.filter(|i| i != DEFAULT_INDEX_NAME) {
-                    field["index"] = toml_edit::value(index);
-                }
-
                 println!(
                     "added wally {}@{} to {}",
                     spec.name,
                     version_id.version(),
                     dependency_key
                 );
+
+                DependencySpecifiers::Wally(
+                    pesde::source::wally::specifier::WallyDependencySpecifier {
+                        version: VersionReq::parse(&format!("^{}", version_id.version()))
+                            .context("failed to parse version requirement")?,
+                        index: spec.index.filter(|i| i != manifest.default_index_name()),
+                        ..spec
+                    },
+                )
             }
             DependencySpecifiers::Git(spec) => {
-                field["repo"] = toml_edit::value(spec.repo.to_bstring().to_string());
-                field["rev"] = toml_edit::value(spec.rev.clone());
+                match &spec.path {
+                    Some(path) => println!(
+                        "added git {}#{} ({path}) to {}",
+                        spec.repo, spec.rev, dependency_key
+                    ),
+                    None => println!("added git {}#{} to {}", spec.repo, spec.rev, dependency_key),
+                }
 
-                println!("added git {}#{} to {}", spec.repo, spec.rev, dependency_key);
+                DependencySpecifiers::Git(spec)
             }
             DependencySpecifiers::Workspace(spec) => {
-                field["workspace"] = toml_edit::value(spec.name.clone().to_string());
-                if let AnyPackageIdentifier::Workspace(versioned) = self.name {
-                    if let Some(version) = versioned.1 {
-                        field["version"] = toml_edit::value(version.to_string());
-                    }
-                }
-
                 println!(
                     "added workspace {}@{} to {}",
                     spec.name, spec.version, dependency_key
                 );
+
+                DependencySpecifiers::Workspace(spec)
+            }
+            DependencySpecifiers::Local(spec) => {
+                println!(
+                    "added local {}@{} {} to {}",
+                    spec.name,
+                    version_id.version(),
+                    version_id.target(),
+                    dependency_key
+                );
+
+                DependencySpecifiers::Local(LocalDependencySpecifier {
+                    version: VersionReq::parse(&format!("^{}", version_id.version()))
+                        .context("failed to parse version requirement")?,
+                    target: (*version_id.target() != project_target)
+                        .then_some(version_id.target().clone()),
+                    ..spec
+                })
+            }
+            DependencySpecifiers::Custom(_) => {
+                unreachable!("custom sources can't be added from the CLI")
+            }
+        };
+
+        editor
+            .add_dependency(dependency_ty, &alias, &specifier)
+            .context("failed to add dependency to manifest")?;
+
+        // the version type of workspace dependencies defaults to `^`, so it's only written if given
+        if matches!(
+            self.name,
+            AnyPackageIdentifier::Workspace(VersionedPackageName(_, None))
+        ) {
+            if let Some(specifier) =
+                editor.document_mut()[dependency_key][&alias].as_table_like_mut()
+            {
+                specifier.remove("version");
             }
         }
 
         project
-            .write_manifest(manifest.to_string())
+            .write_manifest_editor(&editor)
             .await
             .context("failed to write manifest")?;
 
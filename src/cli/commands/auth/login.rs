@@ -57,7 +57,7 @@ impl LoginCommand {
 
         let source = PesdePackageSource::new(index_url.clone());
         source
-            .refresh(project)
+            .refresh(project, reqwest)
             .await
             .context("failed to refresh index")?;
 
@@ -1,6 +1,7 @@
 use crate::cli::config::read_config;
+use anyhow::Context;
 use clap::{Args, Subcommand};
-use pesde::{errors::ManifestReadError, Project, DEFAULT_INDEX_NAME};
+use pesde::{errors::ManifestReadError, Project};
 
 mod login;
 mod logout;
@@ -9,7 +10,7 @@ mod whoami;
 
 #[derive(Debug, Args)]
 pub struct AuthSubcommand {
-    /// The index to use. Defaults to `default`, or the configured default index if current directory doesn't have a manifest
+    /// The index to use, which may also be a Wally index. Defaults to the manifest's default index, or the configured default index if current directory doesn't have a manifest
     #[arg(short, long)]
     pub index: Option<String>,
 
@@ -40,27 +41,32 @@ impl AuthSubcommand {
             },
         };
 
-        let index_url = match self.index.as_deref() {
-            Some(index) => match index.try_into() {
-                Ok(url) => Some(url),
-                Err(_) => None,
-            },
-            None => match manifest {
-                Some(_) => None,
-                None => Some(read_config().await?.default_index),
-            },
-        };
+        let index_url = match manifest {
+            Some(manifest) => {
+                let index_name = self
+                    .index
+                    .as_deref()
+                    .unwrap_or(manifest.default_index_name());
 
-        let index_url = match index_url {
-            Some(url) => url,
-            None => {
-                let index_name = self.index.as_deref().unwrap_or(DEFAULT_INDEX_NAME);
+                let index = project.index_url(&manifest.indices, index_name);
+                #[cfg(feature = "wally-compat")]
+                let index = index.or_else(|| manifest.wally_indices.get(index_name).cloned());
 
-                match manifest.unwrap().indices.get(index_name) {
-                    Some(index) => index.clone(),
+                match index.or_else(|| {
+                    self.index
+                        .as_deref()
+                        .and_then(|index| index.try_into().ok())
+                }) {
+                    Some(index) => index,
                     None => anyhow::bail!("index {index_name} not found in manifest"),
                 }
             }
+            None => match self.index.as_deref() {
+                Some(index) => index
+                    .try_into()
+                    .with_context(|| format!("invalid index url {index}"))?,
+                None => read_config().await?.default_index,
+            },
         };
 
         match self.command {
@@ -0,0 +1,18 @@
+use anyhow::Context;
+use clap::Args;
+use pesde::{source::invalidate_indices, Project};
+
+#[derive(Debug, Args)]
+pub struct InvalidateCommand {}
+
+impl InvalidateCommand {
+    pub async fn run(self, project: Project) -> anyhow::Result<()> {
+        invalidate_indices(&project)
+            .await
+            .context("failed to invalidate cached indices")?;
+
+        println!("cached indices will be refreshed the next time they're used");
+
+        Ok(())
+    }
+}
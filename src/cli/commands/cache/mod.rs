@@ -0,0 +1,18 @@
+use clap::Subcommand;
+use pesde::Project;
+
+mod invalidate;
+
+#[derive(Debug, Subcommand)]
+pub enum CacheCommands {
+    /// Marks the cached indices as stale, so they're refreshed the next time they're used
+    Invalidate(invalidate::InvalidateCommand),
+}
+
+impl CacheCommands {
+    pub async fn run(self, project: Project) -> anyhow::Result<()> {
+        match self {
+            CacheCommands::Invalidate(invalidate) => invalidate.run(project).await,
+        }
+    }
+}
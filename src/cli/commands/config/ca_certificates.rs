@@ -0,0 +1,60 @@
+use crate::cli::config::{read_config, write_config};
+use anyhow::Context;
+use clap::Args;
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct CaCertificatesCommand {
+    /// The path of a PEM file with additional root certificates to trust, don't pass any value to check the current path
+    #[arg(index = 1)]
+    path: Option<PathBuf>,
+
+    /// Removes the additional root certificates
+    #[arg(short, long, conflicts_with = "path")]
+    reset: bool,
+}
+
+impl CaCertificatesCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let mut config = read_config().await?;
+
+        let path = match self.path {
+            Some(path) => path,
+            None if self.reset => {
+                config.ca_certificates = None;
+                write_config(&config).await?;
+                println!("additional root certificates removed");
+
+                return Ok(());
+            }
+            None => {
+                match &config.ca_certificates {
+                    Some(path) => println!("current root certificates: {}", path.display()),
+                    None => println!("no additional root certificates configured"),
+                }
+
+                return Ok(());
+            }
+        };
+
+        let path = std::path::absolute(&path)
+            .with_context(|| format!("failed to resolve path {}", path.display()))?;
+        let pem = fs_err::tokio::read(&path)
+            .await
+            .context("failed to read root certificates")?;
+        let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+            .context("failed to parse root certificates")?;
+        anyhow::ensure!(!certificates.is_empty(), "no certificates found in file");
+
+        println!(
+            "root certificates set to: {} ({} certificates)",
+            path.display(),
+            certificates.len()
+        );
+
+        config.ca_certificates = Some(path);
+        write_config(&config).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+use crate::cli::config::{read_config, write_config, CliConfig};
+use clap::Args;
+
+#[derive(Debug, Args)]
+pub struct IndexTtlCommand {
+    /// The number of seconds for which refreshed indices are reused, don't pass any value to check the current value
+    #[arg(index = 1)]
+    seconds: Option<u64>,
+
+    /// Resets the time to the default value
+    #[arg(short, long, conflicts_with = "seconds")]
+    reset: bool,
+}
+
+impl IndexTtlCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let mut config = read_config().await?;
+
+        let seconds = if self.reset {
+            Some(CliConfig::default().index_ttl_secs)
+        } else {
+            self.seconds
+        };
+
+        match seconds {
+            Some(seconds) => {
+                config.index_ttl_secs = seconds;
+                write_config(&config).await?;
+                println!("index ttl set to: {seconds}s");
+            }
+            None => {
+                println!("current index ttl: {}s", config.index_ttl_secs);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,17 +1,42 @@
 use clap::Subcommand;
 
+mod ca_certificates;
 mod default_index;
+mod index_ttl;
+mod proxy;
+mod retries;
+mod wally_git_tokens;
 
 #[derive(Debug, Subcommand)]
 pub enum ConfigCommands {
+    /// Configuration for additional root certificates to trust
+    CaCertificates(ca_certificates::CaCertificatesCommand),
+
     /// Configuration for the default index
     DefaultIndex(default_index::DefaultIndexCommand),
+
+    /// Configuration for how long refreshed indices are reused
+    IndexTtl(index_ttl::IndexTtlCommand),
+
+    /// Configuration for the proxy requests are sent through
+    Proxy(proxy::ProxyCommand),
+
+    /// Configuration for how failed network operations are retried
+    Retries(retries::RetriesCommand),
+
+    /// Configuration for whether the tokens of Wally indices are used to fetch their repositories
+    WallyGitTokens(wally_git_tokens::WallyGitTokensCommand),
 }
 
 impl ConfigCommands {
     pub async fn run(self) -> anyhow::Result<()> {
         match self {
+            ConfigCommands::CaCertificates(ca_certificates) => ca_certificates.run().await,
             ConfigCommands::DefaultIndex(default_index) => default_index.run().await,
+            ConfigCommands::IndexTtl(index_ttl) => index_ttl.run().await,
+            ConfigCommands::Proxy(proxy) => proxy.run().await,
+            ConfigCommands::Retries(retries) => retries.run().await,
+            ConfigCommands::WallyGitTokens(wally_git_tokens) => wally_git_tokens.run().await,
         }
     }
 }
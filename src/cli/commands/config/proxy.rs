@@ -0,0 +1,66 @@
+use crate::cli::config::{read_config, write_config};
+use clap::Args;
+
+#[derive(Debug, Args)]
+pub struct ProxyCommand {
+    /// The URL of the proxy to send requests through, don't pass any value to check the current proxy
+    #[arg(index = 1)]
+    proxy: Option<url::Url>,
+
+    /// A comma-separated list of hosts which aren't reached through the proxy
+    #[arg(short, long)]
+    no_proxy: Option<String>,
+
+    /// Removes the proxy, falling back to the proxy environment variables
+    #[arg(short, long, conflicts_with_all = ["proxy", "no_proxy"])]
+    reset: bool,
+}
+
+impl ProxyCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let mut config = read_config().await?;
+
+        if self.reset {
+            config.proxy = None;
+            config.no_proxy = None;
+            write_config(&config).await?;
+            println!("proxy removed");
+
+            return Ok(());
+        }
+
+        if self.proxy.is_none() && self.no_proxy.is_none() {
+            match &config.proxy {
+                Some(proxy) => println!("current proxy: {proxy}"),
+                None => println!("no proxy configured"),
+            }
+
+            if let Some(no_proxy) = &config.no_proxy {
+                println!("hosts not using the proxy: {no_proxy}");
+            }
+
+            return Ok(());
+        }
+
+        if let Some(proxy) = self.proxy {
+            config.proxy = Some(proxy.to_string());
+        }
+
+        if let Some(no_proxy) = self.no_proxy {
+            config.no_proxy = Some(no_proxy);
+        }
+
+        write_config(&config).await?;
+
+        match &config.proxy {
+            Some(proxy) => println!("proxy set to: {proxy}"),
+            None => println!("no proxy configured"),
+        }
+
+        if let Some(no_proxy) = &config.no_proxy {
+            println!("hosts not using the proxy: {no_proxy}");
+        }
+
+        Ok(())
+    }
+}
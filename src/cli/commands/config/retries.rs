@@ -0,0 +1,52 @@
+use crate::cli::config::{read_config, write_config, CliConfig};
+use clap::Args;
+
+#[derive(Debug, Args)]
+pub struct RetriesCommand {
+    /// The number of times failed network operations are retried, don't pass any value to check the current values
+    #[arg(index = 1)]
+    count: Option<u32>,
+
+    /// The delay before the first retry in milliseconds, doubling with every following retry
+    #[arg(short, long)]
+    delay_ms: Option<u64>,
+
+    /// Resets the values to the defaults
+    #[arg(short, long, conflicts_with_all = ["count", "delay_ms"])]
+    reset: bool,
+}
+
+impl RetriesCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let mut config = read_config().await?;
+
+        if self.reset {
+            let default = CliConfig::default();
+            config.max_retries = default.max_retries;
+            config.retry_delay_ms = default.retry_delay_ms;
+        } else if self.count.is_none() && self.delay_ms.is_none() {
+            println!(
+                "current retries: {} (starting with a delay of {}ms)",
+                config.max_retries, config.retry_delay_ms
+            );
+
+            return Ok(());
+        }
+
+        if let Some(count) = self.count {
+            config.max_retries = count;
+        }
+
+        if let Some(delay_ms) = self.delay_ms {
+            config.retry_delay_ms = delay_ms;
+        }
+
+        write_config(&config).await?;
+        println!(
+            "retries set to: {} (starting with a delay of {}ms)",
+            config.max_retries, config.retry_delay_ms
+        );
+
+        Ok(())
+    }
+}
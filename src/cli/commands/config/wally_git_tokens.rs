@@ -0,0 +1,28 @@
+use crate::cli::config::{read_config, write_config};
+use clap::Args;
+
+#[derive(Debug, Args)]
+pub struct WallyGitTokensCommand {
+    /// Whether the tokens of Wally indices are also used to fetch their repositories, don't pass any value to check the current value
+    #[arg(index = 1)]
+    enabled: Option<bool>,
+}
+
+impl WallyGitTokensCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let mut config = read_config().await?;
+
+        match self.enabled {
+            Some(enabled) => {
+                config.wally_git_tokens = enabled;
+                write_config(&config).await?;
+                println!("wally git tokens set to: {enabled}");
+            }
+            None => {
+                println!("wally git tokens: {}", config.wally_git_tokens);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use crate::cli::{config::read_config, progress_bar, VersionedPackageName};
+use crate::cli::{config::read_config, reporters::CliReporter, VersionedPackageName};
 use anyhow::Context;
 use clap::Args;
 use fs_err::tokio as fs;
@@ -6,6 +6,7 @@ use pesde::{
     linking::generator::generate_bin_linking_module,
     manifest::target::TargetKind,
     names::PackageName,
+    resolver::ResolveOptions,
     source::{
         pesde::{specifier::PesdeDependencySpecifier, PesdePackageSource},
         traits::PackageSource,
@@ -42,7 +43,7 @@ impl ExecuteCommand {
         .context("no index specified")?;
         let source = PesdePackageSource::new(index);
         source
-            .refresh(&project)
+            .refresh(&project, &reqwest)
             .await
             .context("failed to refresh source")?;
 
@@ -53,10 +54,17 @@ impl ExecuteCommand {
                 version: version_req.clone(),
                 index: None,
                 target: None,
+                platform: Default::default(),
             };
 
             if let Some(res) = source
-                .resolve(&specifier, &project, TargetKind::Lune, &mut HashSet::new())
+                .resolve(
+                    &specifier,
+                    &project,
+                    &reqwest,
+                    TargetKind::Lune,
+                    &mut HashSet::new(),
+                )
                 .await
                 .context("failed to resolve package")?
                 .1
@@ -66,7 +74,13 @@ impl ExecuteCommand {
             }
 
             source
-                .resolve(&specifier, &project, TargetKind::Luau, &mut HashSet::new())
+                .resolve(
+                    &specifier,
+                    &project,
+                    &reqwest,
+                    TargetKind::Luau,
+                    &mut HashSet::new(),
+                )
                 .await
                 .context("failed to resolve package")?
                 .1
@@ -96,10 +110,12 @@ impl ExecuteCommand {
         );
 
         let (fs, target) = source
-            .download(&pkg_ref, &project, &reqwest)
+            .download(&pkg_ref, &project, &reqwest, &())
             .await
             .context("failed to download package")?;
-        let bin_path = target.bin_path().context("package has no binary export")?;
+        let bin_path = target
+            .host_bin_path()
+            .context("package has no binary export for this platform")?;
 
         fs.write_to(tempdir.path(), project.cas_dir(), true)
             .await
@@ -108,11 +124,26 @@ impl ExecuteCommand {
         let mut refreshed_sources = HashSet::new();
 
         let graph = project
-            .dependency_graph(None, &mut refreshed_sources, true)
+            .dependency_graph(
+                None,
+                &mut refreshed_sources,
+                &reqwest,
+                ResolveOptions {
+                    is_published_package: true,
+                    install_peers: true,
+                    ..Default::default()
+                },
+            )
             .await
             .context("failed to build dependency graph")?;
         let graph = Arc::new(graph);
 
+        let reporter = Arc::new(CliReporter::new(
+            graph.values().map(|versions| versions.len() as u64).sum(),
+            "📥 ".to_string(),
+            "downloading dependencies".to_string(),
+        )?);
+
         let (rx, downloaded_graph) = project
             .download_and_link(
                 &graph,
@@ -121,18 +152,14 @@ impl ExecuteCommand {
                 true,
                 true,
                 |_| async { Ok::<_, std::io::Error>(()) },
+                reporter.clone(),
             )
             .await
             .context("failed to download dependencies")?;
 
-        progress_bar(
-            graph.values().map(|versions| versions.len() as u64).sum(),
-            rx,
-            "📥 ".to_string(),
-            "downloading dependencies".to_string(),
-            "downloaded dependencies".to_string(),
-        )
-        .await?;
+        reporter
+            .run(rx, "downloaded dependencies".to_string())
+            .await?;
 
         downloaded_graph
             .await
@@ -5,7 +5,7 @@ use colored::Colorize;
 use inquire::validator::Validation;
 use pesde::{
     errors::ManifestReadError,
-    manifest::{target::TargetKind, DependencyType},
+    manifest::{editor::ManifestEditor, target::TargetKind, DependencyType},
     names::PackageName,
     source::{
         git_index::GitBasedSource,
@@ -37,7 +37,7 @@ impl Display for PackageNameOrCustom {
 }
 
 impl InitCommand {
-    pub async fn run(self, project: Project) -> anyhow::Result<()> {
+    pub async fn run(self, project: Project, reqwest: reqwest::Client) -> anyhow::Result<()> {
         match project.read_manifest().await {
             Ok(_) => {
                 println!("{}", "project already initialized".red());
@@ -47,7 +47,8 @@ impl InitCommand {
             Err(e) => return Err(e.into()),
         };
 
-        let mut manifest = toml_edit::DocumentMut::new();
+        let mut editor = ManifestEditor::default();
+        let manifest = editor.document_mut();
 
         manifest["name"] = toml_edit::value(
             inquire::Text::new("what is the name of the project?")
@@ -134,7 +135,7 @@ impl InitCommand {
             )
             .unwrap()
         {
-            PackageSource::refresh(&source, &project)
+            PackageSource::refresh(&source, &project, &reqwest)
                 .await
                 .context("failed to refresh package source")?;
             let config = source
@@ -192,8 +193,10 @@ impl InitCommand {
                             version: VersionReq::STAR,
                             index: None,
                             target: None,
+                            platform: Default::default(),
                         },
                         &project,
+                        &reqwest,
                         TargetKind::Lune,
                         &mut HashSet::new(),
                     )
@@ -216,13 +219,20 @@ impl InitCommand {
                     ));
                 }
 
-                let dev_deps = &mut manifest["dev_dependencies"]
-                    .or_insert(toml_edit::Item::Table(toml_edit::Table::new()));
-
-                let field = &mut dev_deps["scripts"];
-                field["name"] = toml_edit::value(pkg_ref.name.to_string());
-                field["version"] = toml_edit::value(format!("^{}", v_id.version()));
-                field["target"] = toml_edit::value(v_id.target().to_string());
+                editor
+                    .add_dependency(
+                        DependencyType::Dev,
+                        "scripts",
+                        &DependencySpecifiers::Pesde(PesdeDependencySpecifier {
+                            name: pkg_ref.name.clone(),
+                            version: VersionReq::parse(&format!("^{}", v_id.version()))
+                                .context("failed to parse scripts package version")?,
+                            index: None,
+                            target: Some(v_id.target().clone()),
+                            platform: Default::default(),
+                        }),
+                    )
+                    .context("failed to add scripts package to manifest")?;
 
                 for (alias, (spec, ty)) in pkg_ref.dependencies {
                     if ty != DependencyType::Peer {
@@ -233,11 +243,19 @@ impl InitCommand {
                         continue;
                     };
 
-                    let field = &mut dev_deps[alias];
-                    field["name"] = toml_edit::value(spec.name.to_string());
-                    field["version"] = toml_edit::value(spec.version.to_string());
-                    field["target"] =
-                        toml_edit::value(spec.target.unwrap_or_else(|| *v_id.target()).to_string());
+                    editor
+                        .add_dependency(
+                            DependencyType::Dev,
+                            &alias,
+                            &DependencySpecifiers::Pesde(PesdeDependencySpecifier {
+                                index: None,
+                                target: Some(
+                                    spec.target.clone().unwrap_or_else(|| v_id.target().clone()),
+                                ),
+                                ..spec
+                            }),
+                        )
+                        .context("failed to add scripts package dependency to manifest")?;
                 }
             } else {
                 println!(
@@ -250,7 +268,7 @@ impl InitCommand {
             }
         }
 
-        project.write_manifest(manifest.to_string()).await?;
+        project.write_manifest_editor(&editor).await?;
 
         println!(
             "{}\n{}: run `install` to fully finish setup",
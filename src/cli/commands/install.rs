@@ -1,5 +1,12 @@
 use crate::cli::{
-    bin_dir, files::make_executable, progress_bar, run_on_workspace_members, up_to_date_lockfile,
+    bin_dir,
+    files::make_executable,
+    luaurc::update_luaurc_aliases,
+    refresh_sources,
+    reporters::CliReporter,
+    run_on_workspace_members,
+    shadows::{apply_shadows, read_shadows},
+    up_to_date_lockfile,
 };
 use anyhow::Context;
 use clap::Args;
@@ -7,8 +14,8 @@ use colored::{ColoredString, Colorize};
 use fs_err::tokio as fs;
 use futures::future::try_join_all;
 use pesde::{
-    download_and_link::filter_graph, lockfile::Lockfile, manifest::target::TargetKind, Project,
-    MANIFEST_FILE_NAME,
+    download_and_link::filter_graph, lockfile::Lockfile, manifest::target::TargetKind,
+    resolver::ResolveOptions, Project, MANIFEST_FILE_NAME,
 };
 use std::{
     collections::{BTreeSet, HashMap, HashSet},
@@ -25,13 +32,21 @@ pub struct InstallCommand {
     /// Whether to not install dev dependencies
     #[arg(long)]
     prod: bool,
+
+    /// Whether to install unresolved peer dependencies as direct dependencies of the project
+    #[arg(long)]
+    install_peers: bool,
+
+    /// Whether to error on unresolved peer dependencies. If peer dependencies are installed, only on those which can't be installed
+    #[arg(long)]
+    strict_peers: bool,
 }
 
-fn bin_link_file(alias: &str) -> String {
+fn bin_link_file(alias: &str, target_kinds: &[TargetKind]) -> String {
     let mut all_combinations = BTreeSet::new();
 
-    for a in TargetKind::VARIANTS {
-        for b in TargetKind::VARIANTS {
+    for a in target_kinds {
+        for b in target_kinds {
             all_combinations.insert((a, b));
         }
     }
@@ -140,8 +155,8 @@ impl InstallCommand {
         {
             let mut deleted_folders = HashMap::new();
 
-            for target_kind in TargetKind::VARIANTS {
-                let folder = manifest.target.kind().packages_folder(target_kind);
+            for target_kind in project.target_kinds() {
+                let folder = manifest.target.kind().packages_folder(&target_kind);
                 let package_dir = project.package_dir();
 
                 deleted_folders
@@ -182,10 +197,29 @@ impl InstallCommand {
                 .collect()
         });
 
+        refresh_sources(
+            &project,
+            &reqwest,
+            &manifest,
+            old_graph.as_ref(),
+            &mut refreshed_sources,
+            format!("{} 🔄 ", job(2)),
+        )
+        .await?;
+
         println!("{} 📦 building dependency graph", job(2));
 
         let graph = project
-            .dependency_graph(old_graph.as_ref(), &mut refreshed_sources, false)
+            .dependency_graph(
+                old_graph.as_ref(),
+                &mut refreshed_sources,
+                &reqwest,
+                ResolveOptions {
+                    install_peers: self.install_peers,
+                    strict_peers: self.strict_peers,
+                    ..Default::default()
+                },
+            )
             .await
             .context("failed to build dependency graph")?;
         let graph = Arc::new(graph);
@@ -193,6 +227,13 @@ impl InstallCommand {
         let bin_folder = bin_dir().await?;
 
         let downloaded_graph = {
+            let reporter = Arc::new(CliReporter::new(
+                graph.values().map(|versions| versions.len() as u64).sum(),
+                format!("{} 📥 ", job(3)),
+                "downloading dependencies".to_string(),
+            )?);
+
+            let target_kinds = project.target_kinds();
             let (rx, downloaded_graph) = project
                 .download_and_link(
                     &graph,
@@ -200,16 +241,18 @@ impl InstallCommand {
                     &reqwest,
                     self.prod,
                     true,
-                    |graph| {
+                    move |graph| {
                         let graph = graph.clone();
+                        let target_kinds = target_kinds.clone();
 
                         async move {
                             try_join_all(
                                 graph
                                     .values()
                                     .flat_map(|versions| versions.values())
-                                    .filter(|node| node.target.bin_path().is_some())
+                                    .filter(|node| node.target.host_bin_path().is_some())
                                     .filter_map(|node| node.node.direct.as_ref())
+                                    .filter(|(_, spec, _)| spec.platform().matches_host())
                                     .map(|(alias, _, _)| alias)
                                     .filter(|alias| {
                                         if *alias == env!("CARGO_BIN_NAME") {
@@ -223,6 +266,7 @@ impl InstallCommand {
                                     })
                                     .map(|alias| {
                                         let bin_folder = bin_folder.clone();
+                                        let target_kinds = &target_kinds;
                                         async move {
                                             let bin_exec_file = bin_folder.join(alias).with_extension(std::env::consts::EXE_EXTENSION);
 
@@ -230,7 +274,7 @@ impl InstallCommand {
                                             fs::create_dir_all(&impl_folder).await.context("failed to create bin link folder")?;
 
                                             let bin_file = impl_folder.join(alias).with_extension("luau");
-                                            fs::write(&bin_file, bin_link_file(alias))
+                                            fs::write(&bin_file, bin_link_file(alias, target_kinds))
                                                 .await
                                                 .context("failed to write bin link file")?;
 
@@ -267,19 +311,15 @@ exec lune run "$(dirname "$0")/.impl/{alias}.luau" -- "$@""#
                                 .await
                                 .map(|_| ())
                         }
-                    }
+                    },
+                    reporter.clone(),
                 )
                 .await
                 .context("failed to download dependencies")?;
 
-            progress_bar(
-                graph.values().map(|versions| versions.len() as u64).sum(),
-                rx,
-                format!("{} 📥 ", job(3)),
-                "downloading dependencies".to_string(),
-                "downloaded dependencies".to_string(),
-            )
-            .await?;
+            reporter
+                .run(rx, "downloaded dependencies".to_string())
+                .await?;
 
             downloaded_graph
                 .await
@@ -293,7 +333,7 @@ exec lune run "$(dirname "$0")/.impl/{alias}.luau" -- "$@""#
                 .await
                 .context("failed to apply patches")?;
 
-            progress_bar(
+            crate::cli::progress_bar(
                 manifest.patches.values().map(|v| v.len() as u64).sum(),
                 rx,
                 format!("{} 🩹 ", job(JOBS - 1)),
@@ -303,6 +343,21 @@ exec lune run "$(dirname "$0")/.impl/{alias}.luau" -- "$@""#
             .await?;
         }
 
+        let shadows = read_shadows(&project).await?;
+        if !shadows.packages.is_empty() {
+            apply_shadows(&project, &downloaded_graph, &shadows)
+                .await
+                .context("failed to apply shadows")?;
+        }
+
+        update_luaurc_aliases(
+            &project,
+            &manifest,
+            &filter_graph(&downloaded_graph, self.prod),
+        )
+        .await
+        .context("failed to update .luaurc aliases")?;
+
         println!("{} 🧹 finishing up", job(JOBS));
 
         project
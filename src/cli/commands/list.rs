@@ -0,0 +1,54 @@
+use crate::cli::{shadows::read_shadows, up_to_date_lockfile};
+use clap::Args;
+use colored::Colorize;
+use pesde::Project;
+
+#[derive(Debug, Args)]
+pub struct ListCommand {}
+
+impl ListCommand {
+    pub async fn run(self, project: Project) -> anyhow::Result<()> {
+        let graph = match up_to_date_lockfile(&project).await? {
+            Some(file) => file.graph,
+            None => {
+                anyhow::bail!(
+                    "lockfile is out of sync, run `{} install` to update it",
+                    env!("CARGO_BIN_NAME")
+                );
+            }
+        };
+
+        let shadows = read_shadows(&project).await?;
+
+        for (name, versions) in &graph {
+            for (version_id, node) in versions {
+                let shadow = shadows.packages.get(name);
+
+                match &node.node.direct {
+                    Some((alias, _, ty)) => print!(
+                        "{} {name} {} ({ty:?})",
+                        alias.bold(),
+                        version_id.to_string().dimmed()
+                    ),
+                    // indirect dependencies are only listed when shadowed
+                    None if shadow.is_some() => {
+                        print!("{name} {}", version_id.to_string().dimmed())
+                    }
+                    None => continue,
+                }
+
+                if let Some(path) = shadow {
+                    print!(
+                        " {} {}",
+                        "[shadowed]".yellow().bold(),
+                        path.display().to_string().cyan()
+                    );
+                }
+
+                println!();
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -2,10 +2,12 @@ use pesde::Project;
 
 mod add;
 mod auth;
+mod cache;
 mod config;
 mod execute;
 mod init;
 mod install;
+mod list;
 mod outdated;
 #[cfg(feature = "patches")]
 mod patch;
@@ -17,6 +19,8 @@ mod run;
 mod self_install;
 #[cfg(feature = "version-management")]
 mod self_upgrade;
+mod shadow;
+mod snapshot;
 mod update;
 
 #[derive(Debug, clap::Subcommand)]
@@ -28,6 +32,14 @@ pub enum Subcommand {
     #[command(subcommand)]
     Config(config::ConfigCommands),
 
+    /// Cache-related commands
+    #[command(subcommand)]
+    Cache(cache::CacheCommands),
+
+    /// Offline snapshot-related commands
+    #[command(subcommand)]
+    Snapshot(snapshot::SnapshotCommands),
+
     /// Initializes a manifest file in the current directory
     Init(init::InitCommand),
 
@@ -65,6 +77,12 @@ pub enum Subcommand {
     /// Checks for outdated dependencies
     Outdated(outdated::OutdatedCommand),
 
+    /// Lists the project's dependencies
+    List(list::ListCommand),
+
+    /// Temporarily replaces a dependency with a local checkout
+    Shadow(shadow::ShadowCommand),
+
     /// Executes a binary package without needing to be run in a project directory
     #[clap(name = "x", visible_alias = "execute", visible_alias = "exec")]
     Execute(execute::ExecuteCommand),
@@ -75,7 +93,9 @@ impl Subcommand {
         match self {
             Subcommand::Auth(auth) => auth.run(project, reqwest).await,
             Subcommand::Config(config) => config.run().await,
-            Subcommand::Init(init) => init.run(project).await,
+            Subcommand::Cache(cache) => cache.run(project).await,
+            Subcommand::Snapshot(snapshot) => snapshot.run(project, reqwest).await,
+            Subcommand::Init(init) => init.run(project, reqwest).await,
             Subcommand::Run(run) => run.run(project).await,
             Subcommand::Install(install) => install.run(project, reqwest).await,
             Subcommand::Publish(publish) => publish.run(project, reqwest).await,
@@ -87,9 +107,11 @@ impl Subcommand {
             Subcommand::PatchCommit(patch_commit) => patch_commit.run(project).await,
             #[cfg(feature = "version-management")]
             Subcommand::SelfUpgrade(self_upgrade) => self_upgrade.run(reqwest).await,
-            Subcommand::Add(add) => add.run(project).await,
+            Subcommand::Add(add) => add.run(project, reqwest).await,
             Subcommand::Update(update) => update.run(project, reqwest).await,
-            Subcommand::Outdated(outdated) => outdated.run(project).await,
+            Subcommand::Outdated(outdated) => outdated.run(project, reqwest).await,
+            Subcommand::List(list) => list.run(project).await,
+            Subcommand::Shadow(shadow) => shadow.run(project).await,
             Subcommand::Execute(execute) => execute.run(project, reqwest).await,
         }
     }
@@ -23,7 +23,7 @@ pub struct OutdatedCommand {
 }
 
 impl OutdatedCommand {
-    pub async fn run(self, project: Project) -> anyhow::Result<()> {
+    pub async fn run(self, project: Project, reqwest: reqwest::Client) -> anyhow::Result<()> {
         let graph = match up_to_date_lockfile(&project).await? {
             Some(file) => file.graph,
             None => {
@@ -44,9 +44,10 @@ impl OutdatedCommand {
 
         refresh_sources(
             &project,
+            &reqwest,
             graph
-                .iter()
-                .flat_map(|(_, versions)| versions.iter())
+                .values()
+                .flat_map(|versions| versions.iter())
                 .map(|(_, node)| node.node.pkg_ref.source()),
             &mut refreshed_sources,
         )
@@ -56,11 +57,13 @@ impl OutdatedCommand {
 
         if try_join_all(
             graph
-                .into_iter()
-                .flat_map(|(_, versions)| versions.into_iter())
+                .into_values()
+                .flat_map(|versions| versions.into_iter())
                 .map(|(current_version_id, node)| {
                     let project = project.clone();
+                    let reqwest = reqwest.clone();
                     let refreshed_sources = refreshed_sources.clone();
+                    let manifest_target_kind = manifest_target_kind.clone();
                     async move {
                         let Some((alias, mut specifier, _)) = node.node.direct else {
                             return Ok::<bool, anyhow::Error>(true);
@@ -86,6 +89,10 @@ impl OutdatedCommand {
                                 }
                                 DependencySpecifiers::Git(_) => {}
                                 DependencySpecifiers::Workspace(_) => {}
+                                DependencySpecifiers::Local(ref mut spec) => {
+                                    spec.version = VersionReq::STAR;
+                                }
+                                DependencySpecifiers::Custom(_) => {}
                             };
                         }
 
@@ -93,6 +100,7 @@ impl OutdatedCommand {
                             .resolve(
                                 &specifier,
                                 &project,
+                                &reqwest,
                                 manifest_target_kind,
                                 &mut *refreshed_sources.lock().await,
                             )
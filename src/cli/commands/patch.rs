@@ -49,7 +49,7 @@ impl PatchCommand {
         fs::create_dir_all(&directory).await?;
 
         source
-            .download(&node.node.pkg_ref, &project, &reqwest)
+            .download(&node.node.pkg_ref, &project, &reqwest, &())
             .await?
             .0
             .write_to(&directory, project.cas_dir(), false)
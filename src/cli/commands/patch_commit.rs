@@ -3,7 +3,8 @@ use anyhow::Context;
 use clap::Args;
 use fs_err::tokio as fs;
 use pesde::{names::PackageNames, patches::create_patch, source::version_id::VersionId, Project};
-use std::{path::PathBuf, str::FromStr};
+use relative_path::RelativePathBuf;
+use std::path::PathBuf;
 
 #[derive(Debug, Args)]
 pub struct PatchCommitCommand {
@@ -48,13 +49,10 @@ impl PatchCommitCommand {
             .and_then(|versions| versions.get(&version_id))
             .context("package not found in graph")?;
 
-        let mut manifest = toml_edit::DocumentMut::from_str(
-            &project
-                .read_manifest()
-                .await
-                .context("failed to read manifest")?,
-        )
-        .context("failed to parse manifest")?;
+        let mut editor = project
+            .manifest_editor()
+            .await
+            .context("failed to read manifest")?;
 
         let patch = create_patch(&self.directory).context("failed to create patch")?;
         fs::remove_dir_all(self.directory)
@@ -77,12 +75,14 @@ impl PatchCommitCommand {
             .await
             .context("failed to write patch file")?;
 
-        manifest["patches"].or_insert(toml_edit::Item::Table(toml_edit::Table::new()))
-            [&name.to_string()][&version_id.to_string()] =
-            toml_edit::value(format!("patches/{patch_file_name}"));
+        editor.add_patch(
+            &name,
+            &version_id,
+            &RelativePathBuf::from("patches").join(&patch_file_name),
+        );
 
         project
-            .write_manifest(manifest.to_string())
+            .write_manifest_editor(&editor)
             .await
             .context("failed to write manifest")?;
 
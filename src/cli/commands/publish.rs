@@ -1,6 +1,5 @@
 use crate::cli::{display_err, run_on_workspace_members, up_to_date_lockfile};
 use anyhow::Context;
-use async_compression::Level;
 use clap::Args;
 use colored::Colorize;
 use fs_err::tokio as fs;
@@ -8,10 +7,15 @@ use fs_err::tokio as fs;
 use pesde::{
     manifest::{target::Target, DependencyType},
     matching_globs_old_behaviour,
+    retry::RetryPolicy,
     scripts::ScriptName,
     source::{
         git_index::GitBasedSource,
-        pesde::{specifier::PesdeDependencySpecifier, PesdePackageSource},
+        pesde::{
+            signing::{SigningKey, SIGNATURE_HEADER},
+            specifier::PesdeDependencySpecifier,
+            ArchiveCompression, PesdePackageSource,
+        },
         specifiers::DependencySpecifiers,
         traits::PackageSource,
         workspace::{
@@ -22,12 +26,120 @@ use pesde::{
     },
     Project, DEFAULT_INDEX_NAME, MANIFEST_FILE_NAME,
 };
-use reqwest::{header::AUTHORIZATION, StatusCode};
+use reqwest::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    StatusCode,
+};
 use semver::VersionReq;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use std::{collections::HashSet, path::PathBuf};
 use tempfile::Builder;
 use tokio::io::{AsyncSeekExt, AsyncWriteExt};
 
+/// Archives larger than this are uploaded in chunks, so that uploading them doesn't time out
+const CHUNKED_UPLOAD_THRESHOLD: usize = 8 * 1024 * 1024;
+/// The size of the chunks archives are uploaded in
+const UPLOAD_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+struct UploadResponse {
+    id: String,
+    received: usize,
+}
+
+/// Uploads the archive in chunks, resuming from what the registry received if a chunk fails,
+/// then publishes it. Returns `None` if the registry doesn't support chunked uploads
+async fn upload_in_chunks(
+    project: &Project,
+    reqwest: &reqwest::Client,
+    api: &str,
+    token: Option<&str>,
+    archive: &[u8],
+    signature: Option<&str>,
+) -> anyhow::Result<Option<reqwest::Response>> {
+    let authorize = |mut request: reqwest::RequestBuilder| {
+        if let Some(token) = token {
+            request = request.header(AUTHORIZATION, token);
+        }
+        request
+    };
+    let into_upload = |response: reqwest::Response| async move {
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            anyhow::bail!("failed to upload archive: {status} ({text})");
+        }
+
+        response
+            .json::<UploadResponse>()
+            .await
+            .context("failed to parse upload response")
+    };
+
+    // opening a session and finalizing it aren't retried, as a retry after a lost response would
+    // open another session, or fail to find the finalized one
+    let response = RetryPolicy::never()
+        .send(
+            authorize(reqwest.post(format!("{api}/v0/uploads")))
+                .header(CONTENT_TYPE, "application/json")
+                .body(format!(r#"{{"size":{}}}"#, archive.len())),
+        )
+        .await
+        .context("failed to create upload")?;
+    // registries which don't support chunked uploads are published to in a single request
+    if matches!(
+        response.status(),
+        StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED
+    ) {
+        return Ok(None);
+    }
+    let upload = into_upload(response).await?;
+    let upload_url = format!("{api}/v0/uploads/{}", upload.id);
+
+    let mut offset = upload.received;
+    while offset < archive.len() {
+        let end = (offset + UPLOAD_CHUNK_SIZE).min(archive.len());
+        tracing::debug!("uploading bytes {offset}..{end} of {}", archive.len());
+
+        let response = project
+            .retry_policy()
+            .send(
+                authorize(reqwest.put(format!("{upload_url}?offset={offset}")))
+                    .body(archive[offset..end].to_vec()),
+            )
+            .await
+            .context("failed to upload chunk")?;
+
+        offset = if response.status() == StatusCode::CONFLICT {
+            // a previous attempt was received, but its response wasn't, so the upload resumes
+            // from what the registry has
+            let response = project
+                .retry_policy()
+                .send(authorize(reqwest.get(&upload_url)))
+                .await
+                .context("failed to get upload status")?;
+            into_upload(response).await?.received
+        } else {
+            into_upload(response).await?.received
+        };
+    }
+
+    let mut request = authorize(reqwest.post(format!("{upload_url}/finalize")))
+        .header(CONTENT_TYPE, "application/json")
+        .body(format!(r#"{{"sha256":"{:x}"}}"#, Sha256::digest(archive)));
+    if let Some(signature) = signature {
+        request = request.header(SIGNATURE_HEADER, signature);
+    }
+
+    let response = RetryPolicy::never()
+        .send(request)
+        .await
+        .context("failed to finalize upload")?;
+
+    Ok(Some(response))
+}
+
 #[derive(Debug, Args, Clone)]
 pub struct PublishCommand {
     /// Whether to output a tarball instead of publishing
@@ -38,9 +150,22 @@ pub struct PublishCommand {
     #[arg(short, long)]
     yes: bool,
 
-    /// The index to publish to
-    #[arg(short, long, default_value_t = DEFAULT_INDEX_NAME.to_string())]
-    index: String,
+    /// The index to publish to, defaults to the manifest's default index
+    #[arg(short, long)]
+    index: Option<String>,
+
+    /// A file containing the base64-encoded Ed25519 PKCS#8 key to sign the archive with, defaults to the `PESDE_SIGNING_KEY` environment variable
+    #[arg(long)]
+    signing_key: Option<PathBuf>,
+
+    /// The compression of the archive, either gzip or zstd. Older versions of pesde can't install packages compressed with zstd
+    #[arg(long, default_value_t)]
+    compression: ArchiveCompression,
+
+    /// Publish to a Wally registry instead, using an index from the `wally_indices` field
+    #[cfg(feature = "wally-compat")]
+    #[arg(long)]
+    wally: bool,
 }
 
 impl PublishCommand {
@@ -71,6 +196,7 @@ impl PublishCommand {
         }
 
         if manifest.target.lib_path().is_none()
+            && manifest.target.types_path().is_none()
             && manifest.target.bin_path().is_none()
             && manifest.target.scripts().is_none_or(|s| s.is_empty())
         {
@@ -111,14 +237,13 @@ impl PublishCommand {
             .canonicalize()
             .context("failed to canonicalize package directory")?;
 
-        let mut archive = tokio_tar::Builder::new(
-            async_compression::tokio::write::GzipEncoder::with_quality(vec![], Level::Best),
-        );
+        let mut archive = tokio_tar::Builder::new(vec![]);
 
         let mut display_build_files: Vec<String> = vec![];
 
-        let (lib_path, bin_path, scripts, target_kind) = (
+        let (lib_path, types_path, bin_path, scripts, target_kind) = (
             manifest.target.lib_path().cloned(),
+            manifest.target.types_path().cloned(),
             manifest.target.bin_path().cloned(),
             manifest.target.scripts().cloned(),
             manifest.target.kind(),
@@ -195,7 +320,11 @@ info: otherwise, the file was deemed unnecessary, if you don't understand why, p
             }
         }
 
-        for (name, path) in [("lib path", lib_path), ("bin path", bin_path)] {
+        for (name, path) in [
+            ("lib path", lib_path),
+            ("types path", types_path),
+            ("bin path", bin_path),
+        ] {
             let Some(relative_export_path) = path else {
                 continue;
             };
@@ -365,6 +494,11 @@ info: otherwise, the file was deemed unnecessary, if you don't understand why, p
             }
         }
 
+        // the target is still borrowed mutably, so `Manifest::default_index_name` can't be used
+        let default_index_name = manifest
+            .default_index
+            .clone()
+            .unwrap_or_else(|| DEFAULT_INDEX_NAME.to_string());
         for specifier in manifest
             .dependencies
             .values_mut()
@@ -376,12 +510,11 @@ info: otherwise, the file was deemed unnecessary, if you don't understand why, p
                     let index_name = specifier
                         .index
                         .as_deref()
-                        .unwrap_or(DEFAULT_INDEX_NAME)
+                        .unwrap_or(&default_index_name)
                         .to_string();
                     specifier.index = Some(
-                        manifest
-                            .indices
-                            .get(&index_name)
+                        project
+                            .index_url(&manifest.indices, &index_name)
                             .context(format!("index {index_name} not found in indices field"))?
                             .to_string(),
                     );
@@ -391,7 +524,7 @@ info: otherwise, the file was deemed unnecessary, if you don't understand why, p
                     let index_name = specifier
                         .index
                         .as_deref()
-                        .unwrap_or(DEFAULT_INDEX_NAME)
+                        .unwrap_or(&default_index_name)
                         .to_string();
                     specifier.index = Some(
                         manifest
@@ -406,7 +539,13 @@ info: otherwise, the file was deemed unnecessary, if you don't understand why, p
                 DependencySpecifiers::Git(_) => {}
                 DependencySpecifiers::Workspace(spec) => {
                     let pkg_ref = WorkspacePackageSource
-                        .resolve(spec, project, target_kind, &mut HashSet::new())
+                        .resolve(
+                            spec,
+                            project,
+                            &reqwest,
+                            target_kind.clone(),
+                            &mut HashSet::new(),
+                        )
                         .await
                         .context("failed to resolve workspace package")?
                         .1
@@ -439,15 +578,25 @@ info: otherwise, the file was deemed unnecessary, if you don't understand why, p
                                 .context(format!("failed to parse version for {v}"))?,
                         },
                         index: Some(
-                            manifest
-                                .indices
-                                .get(DEFAULT_INDEX_NAME)
+                            project
+                                .index_url(&manifest.indices, manifest.default_index_name())
                                 .context("missing default index in workspace package manifest")?
                                 .to_string(),
                         ),
-                        target: Some(spec.target.unwrap_or(manifest.target.kind())),
+                        target: Some(spec.target.clone().unwrap_or(manifest.target.kind())),
+                        platform: spec.platform.clone(),
                     });
                 }
+                DependencySpecifiers::Local(spec) => {
+                    anyhow::bail!(
+                        "dependency `{spec}` is from a local registry, which can't be published to"
+                    );
+                }
+                DependencySpecifiers::Custom(spec) => {
+                    anyhow::bail!(
+                        "dependency `{spec}` is from a custom source, which can't be published"
+                    );
+                }
             }
         }
 
@@ -490,6 +639,13 @@ info: otherwise, the file was deemed unnecessary, if you don't understand why, p
                     .lib_path()
                     .map_or("(none)".to_string(), |p| p.to_string())
             );
+            println!(
+                "\ttypes path: {}",
+                manifest
+                    .target
+                    .types_path()
+                    .map_or("(none)".to_string(), |p| p.to_string())
+            );
 
             if roblox_target {
                 println!("\tbuild files: {}", display_build_files.join(", "));
@@ -516,7 +672,7 @@ info: otherwise, the file was deemed unnecessary, if you don't understand why, p
             println!(
                 "includes: {}",
                 paths
-                    .into_iter()
+                    .iter()
                     .map(|p| p.to_string_lossy().to_string())
                     .collect::<Vec<_>>()
                     .join(", ")
@@ -534,6 +690,28 @@ info: otherwise, the file was deemed unnecessary, if you don't understand why, p
             println!();
         }
 
+        #[cfg(feature = "wally-compat")]
+        if self.wally {
+            let index_name = self
+                .index
+                .as_deref()
+                .unwrap_or(manifest.default_index_name());
+            let index_url = manifest
+                .wally_indices
+                .get(index_name)
+                .context(format!("missing wally index {index_name}"))?;
+
+            return crate::cli::wally::publish(
+                project,
+                &reqwest,
+                &manifest,
+                &paths,
+                index_url,
+                self.dry_run,
+            )
+            .await;
+        }
+
         let temp_path = Builder::new().make(|_| Ok(()))?.into_temp_path();
         let mut temp_manifest = fs::OpenOptions::new()
             .create(true)
@@ -560,22 +738,25 @@ info: otherwise, the file was deemed unnecessary, if you don't understand why, p
             .append_file(MANIFEST_FILE_NAME, temp_manifest.file_mut())
             .await?;
 
-        let mut encoder = archive
+        let tarball = archive
             .into_inner()
             .await
             .context("failed to finish archive")?;
-        encoder
-            .shutdown()
+        let archive = self
+            .compression
+            .compress(&tarball)
             .await
-            .context("failed to finish archive")?;
-        let archive = encoder.into_inner();
-
-        let index_url = manifest
-            .indices
-            .get(&self.index)
-            .context(format!("missing index {}", self.index))?;
+            .context("failed to compress archive")?;
+
+        let index_name = self
+            .index
+            .as_deref()
+            .unwrap_or(manifest.default_index_name());
+        let index_url = &project
+            .index_url(&manifest.indices, index_name)
+            .context(format!("missing index {index_name}"))?;
         let source = PesdePackageSource::new(index_url.clone());
-        PackageSource::refresh(&source, project)
+        PackageSource::refresh(&source, project, &reqwest)
             .await
             .context("failed to refresh source")?;
         let config = source
@@ -610,27 +791,83 @@ info: otherwise, the file was deemed unnecessary, if you don't understand why, p
             anyhow::bail!("dependency `{disallowed}` is not allowed on this index");
         }
 
+        let signing_key = match &self.signing_key {
+            Some(path) => Some(
+                fs::read_to_string(path)
+                    .await
+                    .context("failed to read signing key")?,
+            ),
+            None => std::env::var("PESDE_SIGNING_KEY").ok(),
+        };
+        let signature = match signing_key {
+            Some(key) => {
+                let key = SigningKey::from_base64(&key).context("failed to parse signing key")?;
+                println!("signing archive with key {}", key.public_key());
+
+                Some(key.sign(&archive))
+            }
+            None => None,
+        };
+
         if self.dry_run {
-            fs::write("package.tar.gz", archive).await?;
+            let file_name = format!("package.{}", self.compression.extension());
+
+            fs::write(&file_name, archive).await?;
+            if let Some(signature) = &signature {
+                fs::write(format!("{file_name}.sig"), signature).await?;
+            }
 
             println!(
                 "{}",
-                "(dry run) package written to package.tar.gz".green().bold()
+                format!("(dry run) package written to {file_name}")
+                    .green()
+                    .bold()
             );
 
             return Ok(());
         }
 
-        let mut request = reqwest
-            .post(format!("{}/v0/packages", config.api()))
-            .body(archive);
-
-        if let Some(token) = project.auth_config().tokens().get(index_url) {
+        let token = project.auth_config().tokens().get(index_url);
+        if token.is_some() {
             tracing::debug!("using token for {index_url}");
-            request = request.header(AUTHORIZATION, token);
         }
 
-        let response = request.send().await.context("failed to send request")?;
+        let chunked_response = if archive.len() > CHUNKED_UPLOAD_THRESHOLD {
+            upload_in_chunks(
+                project,
+                &reqwest,
+                config.api(),
+                token.map(String::as_str),
+                &archive,
+                signature.as_deref(),
+            )
+            .await?
+        } else {
+            None
+        };
+
+        let response = match chunked_response {
+            Some(response) => response,
+            None => {
+                let mut request = reqwest
+                    .post(format!("{}/v0/packages", config.api()))
+                    .body(archive);
+
+                if let Some(signature) = signature {
+                    request = request.header(SIGNATURE_HEADER, signature);
+                }
+
+                if let Some(token) = token {
+                    request = request.header(AUTHORIZATION, token);
+                }
+
+                project
+                    .retry_policy()
+                    .send(request)
+                    .await
+                    .context("failed to send request")?
+            }
+        };
 
         let status = response.status();
         let text = response
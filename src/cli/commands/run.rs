@@ -3,19 +3,116 @@ use anyhow::Context;
 use clap::Args;
 use futures::{StreamExt, TryStreamExt};
 use pesde::{
+    download_and_link::filter_graph,
     linking::generator::generate_bin_linking_module,
+    manifest::Manifest,
     names::{PackageName, PackageNames},
-    Project, MANIFEST_FILE_NAME, PACKAGES_CONTAINER_NAME,
+    Project, MANIFEST_FILE_NAME, PACKAGES_CONTAINER_NAME, SCRIPTS_LINK_FOLDER,
 };
 use relative_path::RelativePathBuf;
 use std::{
-    collections::HashSet, env::current_dir, ffi::OsString, io::Write, path::PathBuf,
+    collections::{BTreeSet, HashSet},
+    env::current_dir,
+    ffi::OsString,
+    io::Write,
+    path::PathBuf,
     process::Command,
 };
 
+/// Splits `<alias>:<script>` into the alias and the script name. Only done if the prefix is the
+/// alias of a dependency and the name isn't a path, such as `C:\script.luau` on Windows
+fn split_dependency_script<'a>(
+    project: &Project,
+    manifest: &Manifest,
+    name: &'a str,
+) -> Option<(&'a str, &'a str)> {
+    let (alias, script_name) = name.split_once(':')?;
+
+    let is_drive_prefix = alias.len() == 1
+        && alias.chars().all(|c| c.is_ascii_alphabetic())
+        && script_name.starts_with(['/', '\\']);
+    if is_drive_prefix
+        || script_name.contains(['/', '\\'])
+        || project.package_dir().join(name).exists()
+    {
+        return None;
+    }
+
+    [
+        &manifest.dependencies,
+        &manifest.peer_dependencies,
+        &manifest.dev_dependencies,
+    ]
+    .into_iter()
+    .any(|deps| deps.contains_key(alias))
+    .then_some((alias, script_name))
+}
+
+/// Finds the linked script of a direct dependency, named either `<alias>:<script>` or, if only one
+/// dependency exports it, just `<script>`
+async fn find_dependency_script(
+    project: &Project,
+    manifest: &Manifest,
+    name: &str,
+) -> anyhow::Result<Option<PathBuf>> {
+    let (alias_filter, script_name) = match split_dependency_script(project, manifest, name) {
+        Some((alias, script_name)) => (Some(alias), script_name),
+        None => (None, name),
+    };
+
+    let Some(lockfile) = up_to_date_lockfile(project).await? else {
+        if alias_filter.is_some() {
+            anyhow::bail!("outdated lockfile, please run the install command first");
+        }
+
+        return Ok(None);
+    };
+
+    let aliases = filter_graph(&lockfile.graph, false)
+        .into_values()
+        .flat_map(|versions| versions.into_values())
+        .filter_map(|node| {
+            let (alias, _, _) = node.node.direct?;
+            if alias_filter.is_some_and(|alias_filter| alias_filter != alias) {
+                return None;
+            }
+
+            node.target
+                .scripts()?
+                .contains_key(script_name)
+                .then_some(alias)
+        })
+        .collect::<BTreeSet<_>>();
+
+    let alias = match aliases.len() {
+        0 if alias_filter.is_some() => {
+            anyhow::bail!("no direct dependency exports a script matching `{name}`")
+        }
+        0 => return Ok(None),
+        1 => aliases.into_iter().next().unwrap(),
+        _ => anyhow::bail!(
+            "script `{script_name}` is exported by multiple dependencies, use one of: {}",
+            aliases
+                .into_iter()
+                .map(|alias| format!("{alias}:{script_name}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+
+    Ok(Some(
+        project
+            .package_dir()
+            .join(SCRIPTS_LINK_FOLDER)
+            .join(alias)
+            .join(format!("{script_name}.luau")),
+    ))
+}
+
 #[derive(Debug, Args)]
 pub struct RunCommand {
-    /// The package name, script name, or path to a script to run
+    /// The package name, script name (of the project, or of a dependency as `<alias>:<script>`),
+    /// or path to a script to run
     #[arg(index = 1)]
     package_or_script: Option<String>,
 
@@ -66,7 +163,7 @@ impl RunCommand {
 
         if let Ok(pkg_name) = package_or_script.parse::<PackageName>() {
             let graph = if let Some(lockfile) = up_to_date_lockfile(&project).await? {
-                lockfile.graph
+                filter_graph(&lockfile.graph, false)
             } else {
                 anyhow::bail!("outdated lockfile, please run the install command first")
             };
@@ -78,7 +175,7 @@ impl RunCommand {
                     continue;
                 }
 
-                let Some(bin_path) = node.target.bin_path() else {
+                let Some(bin_path) = node.target.host_bin_path() else {
                     anyhow::bail!("package has no bin path");
                 };
 
@@ -112,6 +209,13 @@ impl RunCommand {
                 );
                 return Ok(());
             }
+
+            if let Some(script_path) =
+                find_dependency_script(&project, &manifest, &package_or_script).await?
+            {
+                run(project.package_dir().to_path_buf(), script_path);
+                return Ok(());
+            }
         };
 
         let relative_path = RelativePathBuf::from(package_or_script);
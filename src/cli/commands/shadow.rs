@@ -0,0 +1,102 @@
+use crate::cli::shadows::{read_shadows, write_shadows};
+use anyhow::Context;
+use clap::Args;
+use colored::Colorize;
+use fs_err::tokio as fs;
+use pesde::{names::PackageNames, Project};
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct ShadowCommand {
+    /// The package to shadow
+    #[arg(index = 1, required_unless_present = "clear")]
+    package: Option<PackageNames>,
+
+    /// The path to the local checkout of the package
+    #[arg(index = 2, required_unless_present = "clear")]
+    path: Option<PathBuf>,
+
+    /// Remove the shadow of the given package, or of all packages if none is given
+    #[arg(long, conflicts_with = "path")]
+    clear: bool,
+}
+
+impl ShadowCommand {
+    pub async fn run(self, project: Project) -> anyhow::Result<()> {
+        let mut shadows = read_shadows(&project).await?;
+
+        if self.clear {
+            match self.package {
+                Some(package) => {
+                    if shadows.packages.remove(&package).is_none() {
+                        anyhow::bail!("package {package} is not shadowed");
+                    }
+                }
+                None => shadows.packages.clear(),
+            }
+        } else {
+            let package = self.package.unwrap();
+            let path = self.path.unwrap();
+            let path = fs::canonicalize(&path)
+                .await
+                .context(format!("failed to resolve {}", path.display()))?;
+
+            if !path.is_dir() {
+                anyhow::bail!("{} is not a directory", path.display());
+            }
+
+            println!(
+                "shadowing {} with {}",
+                package.to_string().bold(),
+                path.display().to_string().cyan()
+            );
+
+            shadows.packages.insert(package, path);
+        }
+
+        write_shadows(&project, &shadows).await?;
+
+        println!(
+            "run `{} install` to apply the changes",
+            env!("CARGO_BIN_NAME")
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::shadows::{tests::project, Shadows};
+
+    #[tokio::test]
+    async fn clears_shadows() {
+        let root = tempfile::tempdir().unwrap();
+        let project = project(root.path());
+        let foo = "acme/foo".parse::<PackageNames>().unwrap();
+        let bar = "acme/bar".parse::<PackageNames>().unwrap();
+        let shadows = Shadows {
+            packages: [&foo, &bar]
+                .into_iter()
+                .map(|name| (name.clone(), root.path().join(name.as_str().1)))
+                .collect(),
+        };
+        write_shadows(&project, &shadows).await.unwrap();
+
+        let clear = |package: Option<&PackageNames>| ShadowCommand {
+            package: package.cloned(),
+            path: None,
+            clear: true,
+        };
+
+        clear(Some(&foo)).run(project.clone()).await.unwrap();
+        let shadows = read_shadows(&project).await.unwrap();
+        assert_eq!(shadows.packages.keys().collect::<Vec<_>>(), vec![&bar]);
+
+        assert!(clear(Some(&foo)).run(project.clone()).await.is_err());
+
+        clear(None).run(project.clone()).await.unwrap();
+        assert!(read_shadows(&project).await.unwrap().packages.is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::cli::up_to_date_lockfile;
+use anyhow::Context;
+use clap::Args;
+use colored::Colorize;
+use fs_err::tokio as fs;
+use pesde::{
+    snapshot::export_snapshot,
+    source::{pesde::PesdePackageSource, traits::PackageSource, PackageSources},
+    Project,
+};
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct ExportCommand {
+    /// The path to write the snapshot to
+    #[arg(index = 1)]
+    output: PathBuf,
+
+    /// The indices to export, defaults to all of the project's indices
+    #[arg(short, long)]
+    index: Vec<String>,
+
+    /// Whether to also export the packages of the project's lockfile
+    #[arg(short, long)]
+    packages: bool,
+}
+
+impl ExportCommand {
+    pub async fn run(self, project: Project, reqwest: reqwest::Client) -> anyhow::Result<()> {
+        let manifest = project
+            .deser_manifest()
+            .await
+            .context("failed to read manifest")?;
+
+        let sources = manifest.indices.iter().map(|(name, url)| {
+            (
+                name,
+                PackageSources::Pesde(PesdePackageSource::new(url.clone())),
+            )
+        });
+        #[cfg(feature = "wally-compat")]
+        let sources = sources.chain(manifest.wally_indices.iter().map(|(name, url)| {
+            (
+                name,
+                PackageSources::Wally(pesde::source::wally::WallyPackageSource::new(url.clone())),
+            )
+        }));
+        let sources = sources.collect::<Vec<_>>();
+
+        for name in &self.index {
+            if !sources.iter().any(|(source_name, _)| *source_name == name) {
+                anyhow::bail!("index {name} not found in manifest");
+            }
+        }
+
+        let sources = sources
+            .into_iter()
+            .filter(|(name, _)| self.index.is_empty() || self.index.contains(name))
+            .map(|(_, source)| source)
+            .collect::<Vec<_>>();
+
+        for source in &sources {
+            source
+                .refresh(&project, &reqwest)
+                .await
+                .context("failed to refresh source")?;
+        }
+
+        let lockfile = if self.packages {
+            match up_to_date_lockfile(&project).await? {
+                Some(lockfile) => Some(lockfile),
+                None => anyhow::bail!(
+                    "lockfile is out of sync, run `{} install` to update it",
+                    env!("CARGO_BIN_NAME")
+                ),
+            }
+        } else {
+            None
+        };
+
+        let snapshot = export_snapshot(&project, sources, lockfile.as_ref())
+            .await
+            .context("failed to export snapshot")?;
+
+        fs::write(&self.output, snapshot)
+            .await
+            .context("failed to write snapshot")?;
+
+        println!(
+            "{}",
+            format!("exported snapshot to {}", self.output.display())
+                .green()
+                .bold()
+        );
+
+        Ok(())
+    }
+}
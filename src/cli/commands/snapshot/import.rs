@@ -0,0 +1,38 @@
+use anyhow::Context;
+use clap::Args;
+use colored::Colorize;
+use fs_err::tokio as fs;
+use pesde::{snapshot::import_snapshot, Project};
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct ImportCommand {
+    /// The path of the snapshot to import
+    #[arg(index = 1)]
+    input: PathBuf,
+}
+
+impl ImportCommand {
+    pub async fn run(self, project: Project) -> anyhow::Result<()> {
+        let bytes = fs::read(&self.input)
+            .await
+            .context("failed to read snapshot")?;
+
+        import_snapshot(&project, &bytes)
+            .await
+            .context("failed to import snapshot")?;
+
+        println!(
+            "{}",
+            format!("imported snapshot {}", self.input.display())
+                .green()
+                .bold()
+        );
+        println!(
+            "run `{} --no-refresh install` to install using it",
+            env!("CARGO_BIN_NAME")
+        );
+
+        Ok(())
+    }
+}
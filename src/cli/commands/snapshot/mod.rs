@@ -0,0 +1,23 @@
+use clap::Subcommand;
+use pesde::Project;
+
+mod export;
+mod import;
+
+#[derive(Debug, Subcommand)]
+pub enum SnapshotCommands {
+    /// Exports the project's indices, and optionally its packages, into a snapshot for offline use
+    Export(export::ExportCommand),
+
+    /// Imports a snapshot, so that the project can be installed without network access
+    Import(import::ImportCommand),
+}
+
+impl SnapshotCommands {
+    pub async fn run(self, project: Project, reqwest: reqwest::Client) -> anyhow::Result<()> {
+        match self {
+            SnapshotCommands::Export(export) => export.run(project, reqwest).await,
+            SnapshotCommands::Import(import) => import.run(project).await,
+        }
+    }
+}
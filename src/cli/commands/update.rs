@@ -1,8 +1,8 @@
-use crate::cli::{progress_bar, run_on_workspace_members};
+use crate::cli::{refresh_sources, reporters::CliReporter, run_on_workspace_members};
 use anyhow::Context;
 use clap::Args;
 use colored::Colorize;
-use pesde::{lockfile::Lockfile, Project};
+use pesde::{lockfile::Lockfile, resolver::ResolveOptions, Project};
 use std::{collections::HashSet, sync::Arc};
 use tokio::sync::Mutex;
 
@@ -25,8 +25,23 @@ impl UpdateCommand {
                 .on_bright_black()
         );
 
+        refresh_sources(
+            &project,
+            &reqwest,
+            &manifest,
+            None,
+            &mut refreshed_sources,
+            "🔄 ".to_string(),
+        )
+        .await?;
+
         let graph = project
-            .dependency_graph(None, &mut refreshed_sources, false)
+            .dependency_graph(
+                None,
+                &mut refreshed_sources,
+                &reqwest,
+                ResolveOptions::default(),
+            )
             .await
             .context("failed to build dependency graph")?;
         let graph = Arc::new(graph);
@@ -39,6 +54,12 @@ impl UpdateCommand {
                 overrides: manifest.overrides,
 
                 graph: {
+                    let reporter = Arc::new(CliReporter::new(
+                        graph.values().map(|versions| versions.len() as u64).sum(),
+                        "📥 ".to_string(),
+                        "downloading dependencies".to_string(),
+                    )?);
+
                     let (rx, downloaded_graph) = project
                         .download_and_link(
                             &graph,
@@ -47,18 +68,14 @@ impl UpdateCommand {
                             false,
                             false,
                             |_| async { Ok::<_, std::io::Error>(()) },
+                            reporter.clone(),
                         )
                         .await
                         .context("failed to download dependencies")?;
 
-                    progress_bar(
-                        graph.values().map(|versions| versions.len() as u64).sum(),
-                        rx,
-                        "📥 ".to_string(),
-                        "downloading dependencies".to_string(),
-                        "downloaded dependencies".to_string(),
-                    )
-                    .await?;
+                    reporter
+                        .run(rx, "downloaded dependencies".to_string())
+                        .await?;
 
                     downloaded_graph
                         .await
@@ -2,8 +2,15 @@ use crate::cli::{auth::Tokens, home_dir};
 use anyhow::Context;
 use fs_err::tokio as fs;
 use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf};
 use tracing::instrument;
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitCredentials {
+    pub username: String,
+    pub password: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct CliConfig {
@@ -15,6 +22,30 @@ pub struct CliConfig {
 
     pub tokens: Tokens,
 
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mirrors: BTreeMap<String, Vec<String>>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub git_credentials: BTreeMap<String, GitCredentials>,
+
+    pub index_ttl_secs: u64,
+
+    pub max_retries: u32,
+
+    pub retry_delay_ms: u64,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_certificates: Option<PathBuf>,
+
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub wally_git_tokens: bool,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub last_checked_updates: Option<(chrono::DateTime<chrono::Utc>, semver::Version)>,
 }
@@ -26,6 +57,24 @@ impl Default for CliConfig {
 
             tokens: Tokens(Default::default()),
 
+            mirrors: BTreeMap::new(),
+
+            git_credentials: BTreeMap::new(),
+
+            index_ttl_secs: 5 * 60,
+
+            max_retries: 3,
+
+            retry_delay_ms: 500,
+
+            proxy: None,
+
+            no_proxy: None,
+
+            ca_certificates: None,
+
+            wally_git_tokens: false,
+
             last_checked_updates: None,
         }
     }
@@ -0,0 +1,218 @@
+use anyhow::Context;
+use fs_err::tokio as fs;
+use pesde::{lockfile::DownloadedGraph, manifest::Manifest, Project};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub const LUAURC_FILE_NAME: &str = ".luaurc";
+
+/// Writes aliases for the direct dependencies into the project's `.luaurc`, keeping user-defined
+/// aliases and removing ones for packages which are no longer installed
+pub async fn update_luaurc_aliases(
+    project: &Project,
+    manifest: &Manifest,
+    graph: &DownloadedGraph,
+) -> anyhow::Result<()> {
+    if !manifest.luaurc_aliases {
+        return Ok(());
+    }
+
+    let target = manifest.target.kind();
+
+    let aliases = graph
+        .values()
+        .flat_map(|versions| versions.iter())
+        .filter(|(_, node)| node.target.lib_path().is_some() || node.target.types_path().is_some())
+        .filter_map(|(version_id, node)| {
+            let (alias, _, _) = node.node.direct.as_ref()?;
+
+            Some((
+                alias.clone(),
+                format!("{}/{alias}", target.packages_folder(version_id.target())),
+            ))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    // aliases pointing into a packages folder are the ones managed by us
+    let target_kinds = project.target_kinds();
+    let packages_folders = graph
+        .values()
+        .flat_map(|versions| versions.keys())
+        .map(|version_id| version_id.target())
+        .chain(&target_kinds)
+        .map(|dependency_target| format!("{}/", target.packages_folder(dependency_target)))
+        .collect::<BTreeSet<_>>();
+    let is_managed = |value: &serde_json::Value| {
+        value.as_str().is_some_and(|value| {
+            packages_folders
+                .iter()
+                .any(|folder| value.starts_with(folder.as_str()))
+        })
+    };
+
+    let path = project.package_dir().join(LUAURC_FILE_NAME);
+    let contents = match fs::read_to_string(&path).await {
+        Ok(contents) => Some(contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e).context(format!("failed to read {LUAURC_FILE_NAME}")),
+    };
+
+    let Some(contents) = merge_aliases(contents.as_deref(), aliases, is_managed)? else {
+        return Ok(());
+    };
+
+    fs::write(&path, contents)
+        .await
+        .context(format!("failed to write {LUAURC_FILE_NAME}"))
+}
+
+/// Merges the aliases into the contents of a `.luaurc`, keeping its other keys in their order and
+/// its indentation. Returns `None` if the file doesn't need to change
+fn merge_aliases(
+    contents: Option<&str>,
+    aliases: BTreeMap<String, String>,
+    is_managed: impl Fn(&serde_json::Value) -> bool,
+) -> anyhow::Result<Option<String>> {
+    let mut luaurc = match contents {
+        Some(contents) => match serde_json::from_str::<serde_json::Value>(contents) {
+            Ok(serde_json::Value::Object(luaurc)) => luaurc,
+            _ => {
+                tracing::warn!("{LUAURC_FILE_NAME} is not a JSON object, not updating aliases");
+                return Ok(None);
+            }
+        },
+        None if aliases.is_empty() => return Ok(None),
+        None => Default::default(),
+    };
+    let original = luaurc.clone();
+
+    let existing = luaurc
+        .entry("aliases")
+        .or_insert_with(|| serde_json::Value::Object(Default::default()));
+    let Some(existing) = existing.as_object_mut() else {
+        tracing::warn!("aliases in {LUAURC_FILE_NAME} are not an object, not updating them");
+        return Ok(None);
+    };
+
+    existing.retain(|alias, value| aliases.contains_key(alias) || !is_managed(value));
+
+    for (alias, value) in aliases {
+        match existing.get(&alias) {
+            Some(current) if !is_managed(current) => {
+                tracing::warn!(
+                    "alias {alias} in {LUAURC_FILE_NAME} is user-defined, not overwriting it"
+                );
+            }
+            _ => {
+                existing.insert(alias, serde_json::Value::String(value));
+            }
+        }
+    }
+
+    if existing.is_empty() {
+        luaurc.shift_remove("aliases");
+    }
+
+    if luaurc == original {
+        return Ok(None);
+    }
+
+    // the indentation of the first indented line, as editors would keep it
+    let indent = contents
+        .and_then(|contents| {
+            contents.lines().find_map(|line| {
+                let indent = &line[..line.len() - line.trim_start().len()];
+                (!indent.is_empty()).then_some(indent)
+            })
+        })
+        .unwrap_or("  ");
+
+    let mut contents = vec![];
+    let mut serializer = serde_json::Serializer::with_formatter(
+        &mut contents,
+        serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes()),
+    );
+    serde::Serialize::serialize(&luaurc, &mut serializer)
+        .context(format!("failed to serialize {LUAURC_FILE_NAME}"))?;
+    let mut contents = String::from_utf8(contents).unwrap();
+    contents.push('\n');
+
+    Ok(Some(contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_managed(value: &serde_json::Value) -> bool {
+        value
+            .as_str()
+            .is_some_and(|value| value.starts_with("luau_packages/"))
+    }
+
+    #[test]
+    fn keeps_user_keys_and_aliases() {
+        let contents = r#"{
+    "languageMode": "strict",
+    "aliases": {
+        "utils": "src/utils",
+        "old": "luau_packages/old"
+    },
+    "lint": {
+        "*": true
+    }
+}
+"#;
+
+        let merged = merge_aliases(
+            Some(contents),
+            BTreeMap::from([
+                ("foo".to_string(), "luau_packages/foo".to_string()),
+                ("utils".to_string(), "luau_packages/utils".to_string()),
+            ]),
+            is_managed,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            merged,
+            r#"{
+    "languageMode": "strict",
+    "aliases": {
+        "utils": "src/utils",
+        "foo": "luau_packages/foo"
+    },
+    "lint": {
+        "*": true
+    }
+}
+"#
+        );
+        assert_eq!(
+            merge_aliases(
+                Some(&merged),
+                BTreeMap::from([("foo".to_string(), "luau_packages/foo".to_string())]),
+                is_managed,
+            )
+            .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn removes_emptied_aliases() {
+        let merged = merge_aliases(
+            Some("{\n\t\"aliases\": {\n\t\t\"old\": \"luau_packages/old\"\n\t},\n\t\"languageMode\": \"strict\"\n}\n"),
+            BTreeMap::new(),
+            is_managed,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(merged, "{\n\t\"languageMode\": \"strict\"\n}\n");
+        assert_eq!(
+            merge_aliases(None, BTreeMap::new(), is_managed).unwrap(),
+            None
+        );
+    }
+}
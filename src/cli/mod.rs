@@ -3,10 +3,14 @@ use colored::Colorize;
 use fs_err::tokio as fs;
 use futures::StreamExt;
 use pesde::{
-    lockfile::Lockfile,
-    manifest::target::TargetKind,
+    lockfile::{DependencyGraph, Lockfile},
+    manifest::{target::TargetKind, Manifest},
     names::{PackageName, PackageNames},
-    source::{version_id::VersionId, workspace::specifier::VersionTypeOrReq},
+    refresh_sources_with_status,
+    source::{
+        traits::PackageRef, version_id::VersionId, workspace::specifier::VersionTypeOrReq,
+        PackageSources,
+    },
     Project,
 };
 use relative_path::RelativePathBuf;
@@ -24,8 +28,13 @@ pub mod auth;
 pub mod commands;
 pub mod config;
 pub mod files;
+pub mod luaurc;
+pub mod reporters;
+pub mod shadows;
 #[cfg(feature = "version-management")]
 pub mod version;
+#[cfg(feature = "wally-compat")]
+pub mod wally;
 
 pub const HOME_DIR: &str = concat!(".", env!("CARGO_PKG_NAME"));
 
@@ -73,9 +82,9 @@ pub async fn up_to_date_lockfile(project: &Project) -> anyhow::Result<Option<Loc
 
     let specs = lockfile
         .graph
-        .iter()
-        .flat_map(|(_, versions)| versions)
-        .filter_map(|(_, node)| {
+        .values()
+        .flat_map(|versions| versions.values())
+        .filter_map(|node| {
             node.node
                 .direct
                 .as_ref()
@@ -200,12 +209,20 @@ pub async fn progress_bar<E: std::error::Error + Into<anyhow::Error>>(
     progress_msg: String,
     finish_msg: String,
 ) -> anyhow::Result<()> {
+    if !reporters::is_interactive() {
+        println!("{prefix}{progress_msg}");
+
+        while let Some(result) = rx.recv().await {
+            println!("  {}", result.map_err(Into::into)?);
+        }
+
+        println!("{prefix}{finish_msg}");
+
+        return Ok(());
+    }
+
     let bar = indicatif::ProgressBar::new(len)
-        .with_style(
-            indicatif::ProgressStyle::default_bar()
-                .template("{prefix}[{elapsed_precise}] {bar:40.208/166} {pos}/{len} {msg}")?
-                .progress_chars("█▓▒░ "),
-        )
+        .with_style(reporters::bar_style()?)
         .with_prefix(prefix)
         .with_message(progress_msg);
     bar.enable_steady_tick(Duration::from_millis(100));
@@ -226,7 +243,43 @@ pub async fn progress_bar<E: std::error::Error + Into<anyhow::Error>>(
     Ok(())
 }
 
-pub fn shift_project_dir(project: &Project, pkg_dir: PathBuf) -> Project {
+/// Refreshes the sources of the manifest and of the previous graph concurrently, showing the
+/// progress of the refresh
+pub async fn refresh_sources(
+    project: &Project,
+    reqwest: &reqwest::Client,
+    manifest: &Manifest,
+    previous_graph: Option<&DependencyGraph>,
+    refreshed_sources: &mut HashSet<PackageSources>,
+    prefix: String,
+) -> anyhow::Result<()> {
+    let sources = project
+        .manifest_sources(manifest)
+        .context("failed to get the project's sources")?;
+    let previous_sources = previous_graph
+        .into_iter()
+        .flat_map(|graph| graph.values())
+        .flat_map(|versions| versions.values())
+        .map(|node| node.pkg_ref.source());
+
+    let (len, rx) = refresh_sources_with_status(
+        project,
+        reqwest,
+        sources.into_iter().chain(previous_sources),
+        refreshed_sources,
+    );
+
+    progress_bar(
+        len as u64,
+        rx,
+        prefix,
+        "refreshing sources".to_string(),
+        "refreshed sources".to_string(),
+    )
+    .await
+}
+
+pub fn shift_project_dir(project: &Project, pkg_dir: PathBuf, manifest: &Manifest) -> Project {
     Project::new(
         pkg_dir,
         Some(project.package_dir()),
@@ -234,6 +287,8 @@ pub fn shift_project_dir(project: &Project, pkg_dir: PathBuf) -> Project {
         project.cas_dir(),
         project.auth_config().clone(),
     )
+    .with_default_index(project.default_index().cloned())
+    .with_custom_targets(manifest.custom_targets.clone())
 }
 
 pub async fn run_on_workspace_members<F: Future<Output = anyhow::Result<()>>>(
@@ -259,7 +314,7 @@ pub async fn run_on_workspace_members<F: Future<Output = anyhow::Result<()>>>(
 
         // don't run on the current workspace root
         if relative_path != "" {
-            f(shift_project_dir(project, path)).await?;
+            f(shift_project_dir(project, path, &manifest)).await?;
         }
 
         results
@@ -0,0 +1,141 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use pesde::reporters::{DownloadProgressReporter, DownloadsReporter};
+use std::{
+    io::IsTerminal,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+/// Whether progress is drawn as progress bars. When not outputting to a terminal, progress is
+/// printed as plain lines instead
+pub fn is_interactive() -> bool {
+    std::io::stderr().is_terminal()
+}
+
+pub fn bar_style() -> anyhow::Result<ProgressStyle> {
+    Ok(ProgressStyle::default_bar()
+        .template("{prefix}[{elapsed_precise}] {bar:40.208/166} {pos}/{len} {msg}")?
+        .progress_chars("█▓▒░ "))
+}
+
+/// Shows the progress of downloading and linking a graph of packages, with a bar for the overall
+/// progress and one for each package being downloaded
+pub struct CliReporter {
+    multi: MultiProgress,
+    root: ProgressBar,
+    download_style: ProgressStyle,
+    prefix: String,
+    progress_msg: String,
+    linking: AtomicBool,
+}
+
+impl CliReporter {
+    pub fn new(len: u64, prefix: String, progress_msg: String) -> anyhow::Result<Self> {
+        let multi = MultiProgress::new();
+
+        let root = multi.add(
+            ProgressBar::new(len)
+                .with_style(bar_style()?)
+                .with_prefix(prefix.clone())
+                .with_message(progress_msg.clone()),
+        );
+
+        if is_interactive() {
+            root.enable_steady_tick(Duration::from_millis(100));
+        } else {
+            println!("{prefix}{progress_msg}");
+        }
+
+        Ok(Self {
+            multi,
+            root,
+            download_style: ProgressStyle::default_spinner()
+                .template("    {spinner} {msg} {bytes}/{total_bytes}")?,
+            prefix,
+            progress_msg,
+            linking: AtomicBool::new(false),
+        })
+    }
+
+    /// Advances the overall progress with the results from the receiver, until all of them have
+    /// been received
+    pub async fn run<E: std::error::Error + Into<anyhow::Error>>(
+        &self,
+        mut rx: tokio::sync::mpsc::Receiver<Result<String, E>>,
+        finish_msg: String,
+    ) -> anyhow::Result<()> {
+        while let Some(result) = rx.recv().await {
+            let text = result.map_err(Into::into)?;
+            self.root.inc(1);
+
+            if !is_interactive() {
+                println!("  {text}");
+            }
+        }
+
+        if is_interactive() {
+            self.root.finish_with_message(finish_msg);
+        } else {
+            println!("{}{finish_msg}", self.prefix);
+        }
+
+        Ok(())
+    }
+}
+
+impl DownloadsReporter for CliReporter {
+    type DownloadProgressReporter = CliDownloadProgressReporter;
+
+    fn report_download(&self, name: String) -> Self::DownloadProgressReporter {
+        self.root.set_message(self.progress_msg.clone());
+
+        let bar = if is_interactive() {
+            self.multi.add(
+                ProgressBar::no_length()
+                    .with_style(self.download_style.clone())
+                    .with_message(name.clone()),
+            )
+        } else {
+            ProgressBar::hidden()
+        };
+
+        CliDownloadProgressReporter { bar, name }
+    }
+
+    fn report_linking(&self) {
+        if is_interactive() {
+            self.root.set_message("linking dependencies");
+        } else if !self.linking.swap(true, Ordering::Relaxed) {
+            // dependencies are linked in multiple passes, which only need to be reported once
+            println!("{}linking dependencies", self.prefix);
+        }
+    }
+}
+
+/// Shows the progress of downloading a single package
+pub struct CliDownloadProgressReporter {
+    bar: ProgressBar,
+    name: String,
+}
+
+impl DownloadProgressReporter for CliDownloadProgressReporter {
+    fn report_start(&self) {
+        self.bar.enable_steady_tick(Duration::from_millis(100));
+    }
+
+    fn report_progress(&self, downloaded: u64, total: Option<u64>) {
+        if let Some(total) = total {
+            self.bar.set_length(total);
+        }
+
+        self.bar.set_position(downloaded);
+    }
+
+    fn report_extract(&self) {
+        self.bar.set_message(format!("{} (extracting)", self.name));
+    }
+
+    fn report_done(&self) {
+        self.bar.finish_and_clear();
+    }
+}
@@ -0,0 +1,231 @@
+use anyhow::Context;
+use fs_err::tokio as fs;
+use pesde::{
+    lockfile::DownloadedGraph, names::PackageNames, Project, PACKAGES_CONTAINER_NAME,
+    SCRIPTS_LINK_FOLDER,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+pub const SHADOWS_FILE_NAME: &str = "shadows.toml";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Shadows {
+    #[serde(default)]
+    pub packages: BTreeMap<PackageNames, PathBuf>,
+}
+
+fn shadows_dir(project: &Project) -> PathBuf {
+    project.package_dir().join(SCRIPTS_LINK_FOLDER)
+}
+
+pub async fn read_shadows(project: &Project) -> anyhow::Result<Shadows> {
+    let contents = match fs::read_to_string(shadows_dir(project).join(SHADOWS_FILE_NAME)).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Shadows::default()),
+        Err(e) => return Err(e).context("failed to read shadows file"),
+    };
+
+    toml::from_str(&contents).context("failed to parse shadows file")
+}
+
+pub async fn write_shadows(project: &Project, shadows: &Shadows) -> anyhow::Result<()> {
+    let dir = shadows_dir(project);
+    let path = dir.join(SHADOWS_FILE_NAME);
+
+    if shadows.packages.is_empty() {
+        return match fs::remove_file(&path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).context("failed to remove shadows file"),
+        };
+    }
+
+    fs::create_dir_all(&dir)
+        .await
+        .context("failed to create shadows directory")?;
+
+    // the shadows are local to this machine, so they must never be committed
+    let gitignore = dir.join(".gitignore");
+    if !gitignore.exists() {
+        fs::write(&gitignore, format!("{SHADOWS_FILE_NAME}\n"))
+            .await
+            .context("failed to write shadows gitignore")?;
+    }
+
+    fs::write(
+        &path,
+        toml::to_string(shadows).context("failed to serialize shadows")?,
+    )
+    .await
+    .context("failed to write shadows file")
+}
+
+pub async fn apply_shadows(
+    project: &Project,
+    graph: &DownloadedGraph,
+    shadows: &Shadows,
+) -> anyhow::Result<()> {
+    let manifest_target_kind = project
+        .deser_manifest()
+        .await
+        .context("failed to read manifest")?
+        .target
+        .kind();
+
+    for (name, local_path) in &shadows.packages {
+        let Some(versions) = graph.get(name) else {
+            tracing::warn!("shadow for {name} not applied because it is not in the graph");
+            continue;
+        };
+
+        for (version_id, node) in versions {
+            let container_folder = node.node.container_folder(
+                &project
+                    .package_dir()
+                    .join(manifest_target_kind.packages_folder(version_id.target()))
+                    .join(PACKAGES_CONTAINER_NAME),
+                name,
+                version_id.version(),
+            );
+
+            // the folders of the dependencies' linking modules are generated, so they're kept
+            let dependency_folders = node
+                .node
+                .dependencies
+                .iter()
+                .filter_map(|(dep_name, (dep_version_id, _))| {
+                    graph.get(dep_name)?.get(dep_version_id)
+                })
+                .map(|dep| node.node.base_folder(version_id, dep.target.kind()))
+                .collect::<HashSet<_>>();
+            let is_dependency_folder = |name: &OsStr| {
+                name.to_str()
+                    .is_some_and(|name| dependency_folders.contains(name))
+            };
+
+            tracing::debug!(
+                "shadowing {name}@{version_id} with {}",
+                local_path.display()
+            );
+
+            fs::create_dir_all(&container_folder)
+                .await
+                .context("failed to create shadowed package folder")?;
+
+            let mut read_dir = fs::read_dir(&container_folder)
+                .await
+                .context("failed to read shadowed package folder")?;
+            while let Some(entry) = read_dir
+                .next_entry()
+                .await
+                .context("failed to read shadowed package entry")?
+            {
+                if is_dependency_folder(&entry.file_name()) {
+                    continue;
+                }
+
+                remove_entry(&entry.path())
+                    .await
+                    .context("failed to remove shadowed package contents")?;
+            }
+
+            let mut read_dir = fs::read_dir(local_path)
+                .await
+                .context("failed to read shadowing package folder")?;
+            while let Some(entry) = read_dir
+                .next_entry()
+                .await
+                .context("failed to read shadowing package entry")?
+            {
+                if is_dependency_folder(&entry.file_name()) {
+                    continue;
+                }
+
+                let link = container_folder.join(entry.file_name());
+
+                #[cfg(unix)]
+                fs::symlink(entry.path(), &link)
+                    .await
+                    .context("failed to link shadowing package contents")?;
+                #[cfg(windows)]
+                if entry
+                    .file_type()
+                    .await
+                    .context("failed to read shadowing package entry type")?
+                    .is_dir()
+                {
+                    fs::symlink_dir(entry.path(), &link).await
+                } else {
+                    fs::symlink_file(entry.path(), &link).await
+                }
+                .context("failed to link shadowing package contents")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes a file, directory or link to either
+async fn remove_entry(path: &Path) -> std::io::Result<()> {
+    let metadata = fs::symlink_metadata(path).await?;
+
+    if metadata.is_dir() {
+        return fs::remove_dir_all(path).await;
+    }
+
+    // links to directories are directories themselves on Windows
+    #[cfg(windows)]
+    if metadata.is_symlink() && fs::metadata(path).await.is_ok_and(|m| m.is_dir()) {
+        return fs::remove_dir(path).await;
+    }
+
+    fs::remove_file(path).await
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn project(root: &Path) -> Project {
+        Project::new(
+            root.join("project"),
+            None::<PathBuf>,
+            root.join("data"),
+            root.join("cas"),
+            pesde::AuthConfig::new(),
+        )
+    }
+
+    #[tokio::test]
+    async fn round_trips_shadows() {
+        let root = tempfile::tempdir().unwrap();
+        let project = project(root.path());
+        let name = "acme/foo".parse::<PackageNames>().unwrap();
+
+        assert!(read_shadows(&project).await.unwrap().packages.is_empty());
+
+        let shadows = Shadows {
+            packages: BTreeMap::from([(name.clone(), root.path().join("foo"))]),
+        };
+        write_shadows(&project, &shadows).await.unwrap();
+
+        let read = read_shadows(&project).await.unwrap();
+        assert_eq!(read.packages, shadows.packages);
+        assert_eq!(
+            fs::read_to_string(shadows_dir(&project).join(".gitignore"))
+                .await
+                .unwrap(),
+            format!("{SHADOWS_FILE_NAME}\n")
+        );
+
+        write_shadows(&project, &Shadows::default()).await.unwrap();
+        assert!(!shadows_dir(&project).join(SHADOWS_FILE_NAME).exists());
+        assert!(read_shadows(&project).await.unwrap().packages.is_empty());
+    }
+}
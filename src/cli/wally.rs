@@ -0,0 +1,249 @@
+use anyhow::Context;
+use async_zip::{base::write::ZipFileWriter, Compression, ZipEntryBuilder};
+use colored::Colorize;
+use fs_err::tokio as fs;
+use pesde::{
+    manifest::{target::Target, Manifest},
+    source::{
+        specifiers::DependencySpecifiers,
+        traits::PackageSource,
+        wally::{wally_version, WallyPackageSource},
+    },
+    Project, MANIFEST_FILE_NAME,
+};
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::PathBuf,
+};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct WallyPackage {
+    name: String,
+    version: semver::Version,
+    registry: String,
+    realm: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    license: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repository: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct WallyManifest {
+    package: WallyPackage,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    dependencies: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    server_dependencies: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    dev_dependencies: BTreeMap<String, String>,
+}
+
+/// Converts a manifest, whose dependency indices have already been replaced with their URLs,
+/// into a Wally manifest
+fn wally_manifest(manifest: &Manifest, registry: &gix::Url) -> anyhow::Result<WallyManifest> {
+    let realm = match manifest.target {
+        Target::Roblox { .. } => "shared",
+        Target::RobloxServer { .. } => "server",
+        _ => anyhow::bail!("only roblox and roblox_server packages can be published to Wally"),
+    };
+
+    if !manifest.peer_dependencies.is_empty() {
+        anyhow::bail!("peer dependencies can't be expressed in Wally");
+    }
+
+    let registry = registry.to_bstring().to_string();
+    let (scope, name) = manifest.name.as_str();
+
+    let mut wally_manifest = WallyManifest {
+        package: WallyPackage {
+            // Wally names may not contain underscores
+            name: format!("{}/{}", scope.replace('_', "-"), name.replace('_', "-")),
+            version: manifest.version.clone(),
+            registry: registry.clone(),
+            realm,
+            description: manifest.description.clone(),
+            license: manifest.license.clone(),
+            authors: manifest.authors.clone(),
+            repository: manifest.repository.as_ref().map(|url| url.to_string()),
+        },
+        dependencies: BTreeMap::new(),
+        server_dependencies: BTreeMap::new(),
+        dev_dependencies: BTreeMap::new(),
+    };
+
+    for (deps, is_dev) in [
+        (&manifest.dependencies, false),
+        (&manifest.dev_dependencies, true),
+    ] {
+        for (alias, spec) in deps {
+            let DependencySpecifiers::Wally(spec) = spec else {
+                anyhow::bail!("dependency `{alias}` can't be expressed in Wally");
+            };
+
+            if spec.index.as_deref() != Some(registry.as_str()) {
+                anyhow::bail!(
+                    "dependency `{alias}` is not from the Wally registry being published to"
+                );
+            }
+
+            if spec.platform != Default::default() {
+                anyhow::bail!("dependency `{alias}` has platform conditions, which can't be expressed in Wally");
+            }
+
+            let wally_deps = if is_dev {
+                &mut wally_manifest.dev_dependencies
+            } else if realm == "server" {
+                &mut wally_manifest.server_dependencies
+            } else {
+                &mut wally_manifest.dependencies
+            };
+
+            wally_deps.insert(alias.clone(), format!("{}@{}", spec.name, spec.version));
+        }
+    }
+
+    Ok(wally_manifest)
+}
+
+/// Publishes the package to a Wally registry
+pub async fn publish(
+    project: &Project,
+    reqwest: &reqwest::Client,
+    manifest: &Manifest,
+    paths: &HashSet<PathBuf>,
+    index_url: &gix::Url,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let wally_manifest = wally_manifest(manifest, index_url)?;
+    let lib_path = manifest
+        .target
+        .lib_path()
+        .context("Wally packages must have a lib export")?;
+
+    let mut archive = ZipFileWriter::with_tokio(vec![]);
+
+    for relative_path in paths {
+        let path = project.package_dir().join(relative_path);
+
+        if !path.is_file()
+            || relative_path
+                .file_name()
+                .is_some_and(|name| name.eq_ignore_ascii_case(MANIFEST_FILE_NAME))
+        {
+            continue;
+        }
+
+        let contents = fs::read(&path)
+            .await
+            .context(format!("failed to read `{}`", relative_path.display()))?;
+        let name = relative_path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        archive
+            .write_entry_whole(
+                ZipEntryBuilder::new(name.into(), Compression::Deflate),
+                &contents,
+            )
+            .await
+            .context(format!(
+                "failed to add `{}` to archive",
+                relative_path.display()
+            ))?;
+    }
+
+    let project_file = serde_json::json!({
+        "name": manifest.name.as_str().1,
+        "tree": {
+            "$path": lib_path.as_str(),
+        },
+    });
+
+    for (name, contents) in [
+        (
+            "wally.toml",
+            toml::to_string(&wally_manifest).context("failed to serialize wally manifest")?,
+        ),
+        (
+            "default.project.json",
+            serde_json::to_string_pretty(&project_file)
+                .context("failed to serialize project file")?,
+        ),
+    ] {
+        archive
+            .write_entry_whole(
+                ZipEntryBuilder::new(name.to_string().into(), Compression::Deflate),
+                contents.as_bytes(),
+            )
+            .await
+            .context(format!("failed to add {name} to archive"))?;
+    }
+
+    let archive = archive
+        .close()
+        .await
+        .context("failed to finish archive")?
+        .into_inner();
+
+    if dry_run {
+        fs::write("package.zip", archive).await?;
+
+        println!(
+            "{}",
+            "(dry run) package written to package.zip".green().bold()
+        );
+
+        return Ok(());
+    }
+
+    let source = WallyPackageSource::new(index_url.clone());
+    PackageSource::refresh(&source, project, reqwest)
+        .await
+        .context("failed to refresh wally index")?;
+    let config = source
+        .config(project)
+        .await
+        .context("failed to get wally index config")?;
+
+    let mut request = reqwest
+        .post(format!("{}/v1/publish", config.api()))
+        .header(CONTENT_TYPE, "application/octet-stream")
+        .header("Wally-Version", wally_version())
+        .body(archive);
+
+    if let Some(token) = project.auth_config().tokens().get(index_url) {
+        tracing::debug!("using token for {index_url}");
+        request = request.header(AUTHORIZATION, token);
+    }
+
+    let response = project
+        .retry_policy()
+        .send(request)
+        .await
+        .context("failed to send request")?;
+
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .context("failed to get response text")?;
+
+    if !status.is_success() {
+        anyhow::bail!("failed to publish package to wally: {status} ({text})");
+    }
+
+    println!("{text}");
+
+    Ok(())
+}
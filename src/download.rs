@@ -1,7 +1,8 @@
 use crate::{
-    lockfile::{DependencyGraph, DownloadedDependencyGraphNode, DownloadedGraph},
+    lockfile::{host_nodes, DependencyGraph, DownloadedDependencyGraphNode, DownloadedGraph},
     manifest::DependencyType,
     refresh_sources,
+    reporters::{DownloadProgressReporter, DownloadsReporter},
     source::{
         traits::{PackageRef, PackageSource},
         PackageSources,
@@ -23,9 +24,13 @@ pub(crate) type MultithreadDownloadJob = (
 );
 
 impl Project {
-    /// Downloads a graph of dependencies
-    #[instrument(skip(self, graph, refreshed_sources, reqwest), level = "debug")]
-    pub async fn download_graph(
+    /// Downloads a graph of dependencies, reporting the progress of each package to the reporter
+    #[instrument(
+        skip(self, graph, refreshed_sources, reqwest, reporter),
+        level = "debug"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn download_graph<R: DownloadsReporter + 'static>(
         &self,
         graph: &DependencyGraph,
         refreshed_sources: &mut HashSet<PackageSources>,
@@ -33,6 +38,7 @@ impl Project {
         prod: bool,
         write: bool,
         wally: bool,
+        reporter: Arc<R>,
     ) -> Result<MultithreadDownloadJob, errors::DownloadGraphError> {
         let manifest = self.deser_manifest().await?;
         let manifest_target_kind = manifest.target.kind();
@@ -40,23 +46,27 @@ impl Project {
 
         let (tx, rx) = tokio::sync::mpsc::channel(
             graph
-                .iter()
-                .map(|(_, versions)| versions.len())
+                .values()
+                .map(|versions| versions.len())
                 .sum::<usize>()
                 .max(1),
         );
 
         refresh_sources(
             self,
+            reqwest,
             graph
-                .iter()
-                .flat_map(|(_, versions)| versions.iter())
+                .values()
+                .flat_map(|versions| versions.iter())
                 .map(|(_, node)| node.pkg_ref.source()),
             refreshed_sources,
         )
         .await?;
 
         let project = Arc::new(self.clone());
+        // the packages which aren't used on this platform are still downloaded, as their targets
+        // are recorded in the lockfile
+        let host_nodes = host_nodes(graph, |node| node);
 
         for (name, versions) in graph {
             for (version_id, node) in versions {
@@ -66,6 +76,7 @@ impl Project {
                 }
 
                 let tx = tx.clone();
+                let used = host_nodes.contains(&(name.clone(), version_id.clone()));
 
                 let name = name.clone();
                 let version_id = version_id.clone();
@@ -82,6 +93,8 @@ impl Project {
                 let downloaded_graph = downloaded_graph.clone();
 
                 let package_dir = self.package_dir().to_path_buf();
+                let manifest_target_kind = manifest_target_kind.clone();
+                let reporter = reporter.clone();
 
                 tokio::spawn(
                     async move {
@@ -106,25 +119,38 @@ impl Project {
                         }
 
                         let project = project.clone();
+                        let display_name = format!("{name}@{version_id}");
+                        let progress = reporter.report_download(display_name.clone());
 
                         tracing::debug!("downloading");
+                        progress.report_start();
 
-                        let (fs, target) =
-                            match source.download(&node.pkg_ref, &project, &reqwest).await {
-                                Ok(target) => target,
-                                Err(e) => {
-                                    tx.send(Err(Box::new(e).into())).await.unwrap();
-                                    return;
-                                }
-                            };
+                        let (fs, target) = match source
+                            .download(&node.pkg_ref, &project, &reqwest, &progress)
+                            .await
+                        {
+                            Ok(target) => target,
+                            Err(e) => {
+                                progress.report_done();
+                                tx.send(Err(Box::new(e).into())).await.unwrap();
+                                return;
+                            }
+                        };
 
                         tracing::debug!("downloaded");
 
                         if write {
-                            if !prod || node.resolved_ty != DependencyType::Dev {
+                            progress.report_extract();
+
+                            if !used {
+                                tracing::debug!(
+                                    "skipping write to disk, not used on this platform"
+                                );
+                            } else if !prod || node.resolved_ty != DependencyType::Dev {
                                 match fs.write_to(container_folder, project.cas_dir(), true).await {
                                     Ok(_) => {}
                                     Err(e) => {
+                                        progress.report_done();
                                         tx.send(Err(errors::DownloadGraphError::WriteFailed(e)))
                                             .await
                                             .unwrap();
@@ -138,7 +164,7 @@ impl Project {
                             }
                         }
 
-                        let display_name = format!("{name}@{version_id}");
+                        progress.report_done();
 
                         {
                             let mut downloaded_graph = downloaded_graph.lock().unwrap();
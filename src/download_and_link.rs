@@ -1,6 +1,7 @@
 use crate::{
-    lockfile::{DependencyGraph, DownloadedGraph},
+    lockfile::{host_nodes, DependencyGraph, DownloadedGraph},
     manifest::DependencyType,
+    reporters::DownloadsReporter,
     source::PackageSources,
     Project,
 };
@@ -13,11 +14,10 @@ use std::{
 use tokio::sync::Mutex;
 use tracing::{instrument, Instrument};
 
-/// Filters a graph to only include production dependencies, if `prod` is `true`
+/// Filters a graph to only include the dependencies used on this platform, and only production
+/// dependencies if `prod` is `true`
 pub fn filter_graph(graph: &DownloadedGraph, prod: bool) -> DownloadedGraph {
-    if !prod {
-        return graph.clone();
-    }
+    let host_nodes = host_nodes(graph, |node| &node.node);
 
     graph
         .iter()
@@ -26,8 +26,13 @@ pub fn filter_graph(graph: &DownloadedGraph, prod: bool) -> DownloadedGraph {
                 name.clone(),
                 versions
                     .iter()
-                    .filter(|(_, node)| node.node.resolved_ty != DependencyType::Dev)
-                    .map(|(v_id, node)| (v_id.clone(), node.clone()))
+                    .filter(|(v_id, _)| host_nodes.contains(&(name.clone(), (*v_id).clone())))
+                    .filter(|(_, node)| !prod || node.node.resolved_ty != DependencyType::Dev)
+                    .map(|(v_id, node)| {
+                        let mut node = node.clone();
+                        node.node.retain_host_dependencies();
+                        (v_id.clone(), node)
+                    })
                     .collect(),
             )
         })
@@ -39,15 +44,18 @@ pub type DownloadAndLinkReceiver =
     tokio::sync::mpsc::Receiver<Result<String, crate::download::errors::DownloadGraphError>>;
 
 impl Project {
-    /// Downloads a graph of dependencies and links them in the correct order
+    /// Downloads a graph of dependencies and links them in the correct order, reporting the
+    /// progress to the reporter
     #[instrument(
-        skip(self, graph, refreshed_sources, reqwest, pesde_cb),
+        skip(self, graph, refreshed_sources, reqwest, pesde_cb, reporter),
         level = "debug"
     )]
+    #[allow(clippy::too_many_arguments)]
     pub async fn download_and_link<
         F: FnOnce(&Arc<DownloadedGraph>) -> R + Send + 'static,
         R: Future<Output = Result<(), E>> + Send,
         E: Send + Sync + 'static,
+        D: DownloadsReporter + 'static,
     >(
         &self,
         graph: &Arc<DependencyGraph>,
@@ -56,6 +64,7 @@ impl Project {
         prod: bool,
         write: bool,
         pesde_cb: F,
+        reporter: Arc<D>,
     ) -> Result<
         (
             DownloadAndLinkReceiver,
@@ -65,8 +74,8 @@ impl Project {
     > {
         let (tx, rx) = tokio::sync::mpsc::channel(
             graph
-                .iter()
-                .map(|(_, versions)| versions.len())
+                .values()
+                .map(|versions| versions.len())
                 .sum::<usize>()
                 .max(1),
         );
@@ -84,7 +93,15 @@ impl Project {
 
                 // step 1. download pesde dependencies
                 let (mut pesde_rx, pesde_graph) = this
-                    .download_graph(&graph, &mut refreshed_sources, &reqwest, prod, write, false)
+                    .download_graph(
+                        &graph,
+                        &mut refreshed_sources,
+                        &reqwest,
+                        prod,
+                        write,
+                        false,
+                        reporter.clone(),
+                    )
                     .instrument(tracing::debug_span!("download (pesde)"))
                     .await?;
 
@@ -96,6 +113,7 @@ impl Project {
 
                 // step 2. link pesde dependencies. do so without types
                 if write {
+                    reporter.report_linking();
                     this.link_dependencies(&filter_graph(&pesde_graph, prod), false)
                         .instrument(tracing::debug_span!("link (pesde)"))
                         .await?;
@@ -111,7 +129,15 @@ impl Project {
 
                 // step 3. download wally dependencies
                 let (mut wally_rx, wally_graph) = this
-                    .download_graph(&graph, &mut refreshed_sources, &reqwest, prod, write, true)
+                    .download_graph(
+                        &graph,
+                        &mut refreshed_sources,
+                        &reqwest,
+                        prod,
+                        write,
+                        true,
+                        reporter.clone(),
+                    )
                     .instrument(tracing::debug_span!("download (wally)"))
                     .await?;
 
@@ -141,6 +167,7 @@ impl Project {
 
                 // step 4. link ALL dependencies. do so with types
                 if write {
+                    reporter.report_linking();
                     this.link_dependencies(&filter_graph(&graph, prod), true)
                         .instrument(tracing::debug_span!("link (all)"))
                         .await?;
@@ -5,17 +5,22 @@
 
 use crate::{
     lockfile::Lockfile,
-    manifest::Manifest,
-    source::{traits::PackageSource, PackageSources},
+    manifest::{
+        target::{CustomTargetConfig, TargetKind},
+        Manifest, SignaturePolicy,
+    },
+    retry::RetryPolicy,
+    source::{custom::CustomSource, traits::PackageSource, PackageSources, RefreshPolicy},
 };
 use async_stream::stream;
 use fs_err::tokio as fs;
 use futures::{future::try_join_all, Stream};
 use gix::sec::identity::Account;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::Debug,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 use tracing::instrument;
 use wax::Pattern;
@@ -35,10 +40,16 @@ pub mod names;
 /// Patching packages
 #[cfg(feature = "patches")]
 pub mod patches;
+/// Reporting the progress of long-running operations
+pub mod reporters;
 /// Resolving packages
 pub mod resolver;
+/// Retrying network operations which failed transiently
+pub mod retry;
 /// Running scripts
 pub mod scripts;
+/// Exporting and importing snapshots of indices and packages for offline use
+pub mod snapshot;
 /// Package sources
 pub mod source;
 pub(crate) mod util;
@@ -60,6 +71,8 @@ pub const SCRIPTS_LINK_FOLDER: &str = ".pesde";
 pub struct AuthConfig {
     tokens: HashMap<gix::Url, String>,
     git_credentials: Option<Account>,
+    index_git_credentials: HashMap<gix::Url, Account>,
+    wally_git_tokens: bool,
 }
 
 impl AuthConfig {
@@ -91,10 +104,53 @@ impl AuthConfig {
         &self.tokens
     }
 
+    /// Set the git credentials of specific indices, which take precedence over the general git credentials
+    pub fn with_index_git_credentials<I: IntoIterator<Item = (gix::Url, Account)>>(
+        mut self,
+        index_git_credentials: I,
+    ) -> Self {
+        self.index_git_credentials = index_git_credentials.into_iter().collect();
+        self
+    }
+
     /// Get the git credentials
     pub fn git_credentials(&self) -> Option<&Account> {
         self.git_credentials.as_ref()
     }
+
+    /// Set whether the tokens of Wally indices are also used to fetch their repositories, as
+    /// private Wally registries require
+    pub fn with_wally_git_tokens(mut self, wally_git_tokens: bool) -> Self {
+        self.wally_git_tokens = wally_git_tokens;
+        self
+    }
+
+    /// Get the git credentials to use for a repository. The repository's own credentials take
+    /// precedence over the general git credentials
+    pub fn git_credentials_for(&self, url: &gix::Url) -> Option<Account> {
+        self.index_git_credentials
+            .get(url)
+            .or(self.git_credentials.as_ref())
+            .cloned()
+    }
+
+    /// Get the git credentials to use for a Wally index's repository. If enabled, the index's
+    /// token is used when the repository doesn't have its own credentials
+    #[cfg(feature = "wally-compat")]
+    pub(crate) fn wally_git_credentials_for(&self, url: &gix::Url) -> Option<Account> {
+        if let Some(account) = self.index_git_credentials.get(url) {
+            return Some(account.clone());
+        }
+
+        if let Some(token) = self.tokens.get(url).filter(|_| self.wally_git_tokens) {
+            return Some(Account {
+                username: "x-access-token".to_string(),
+                password: token.strip_prefix("Bearer ").unwrap_or(token).to_string(),
+            });
+        }
+
+        self.git_credentials.clone()
+    }
 }
 
 /// The main struct of the pesde library, representing a project
@@ -105,6 +161,15 @@ pub struct Project {
     data_dir: PathBuf,
     auth_config: AuthConfig,
     cas_dir: PathBuf,
+    default_index: Option<gix::Url>,
+    custom_targets: BTreeMap<String, CustomTargetConfig>,
+    mirrors: BTreeMap<gix::Url, Vec<gix::Url>>,
+    refresh_policy: RefreshPolicy,
+    retry_policy: RetryPolicy,
+    signature_policy: SignaturePolicy,
+    custom_sources: BTreeMap<String, Arc<dyn CustomSource>>,
+    #[cfg(feature = "wally-compat")]
+    wally_realms: source::wally::WallyRealmsConfig,
 }
 
 impl Project {
@@ -122,9 +187,24 @@ impl Project {
             data_dir: data_dir.as_ref().to_path_buf(),
             auth_config,
             cas_dir: cas_dir.as_ref().to_path_buf(),
+            default_index: None,
+            custom_targets: BTreeMap::new(),
+            mirrors: BTreeMap::new(),
+            refresh_policy: RefreshPolicy::default(),
+            retry_policy: RetryPolicy::default(),
+            signature_policy: SignaturePolicy::default(),
+            custom_sources: BTreeMap::new(),
+            #[cfg(feature = "wally-compat")]
+            wally_realms: Default::default(),
         }
     }
 
+    /// Set the index used when the manifest doesn't define the default index
+    pub fn with_default_index(mut self, default_index: Option<gix::Url>) -> Self {
+        self.default_index = default_index;
+        self
+    }
+
     /// The directory of the package
     pub fn package_dir(&self) -> &Path {
         &self.package_dir
@@ -150,6 +230,124 @@ impl Project {
         &self.cas_dir
     }
 
+    /// Set the configuration of custom targets
+    pub fn with_custom_targets(
+        mut self,
+        custom_targets: BTreeMap<String, CustomTargetConfig>,
+    ) -> Self {
+        self.custom_targets = custom_targets;
+        self
+    }
+
+    /// The configuration of custom targets
+    pub fn custom_targets(&self) -> &BTreeMap<String, CustomTargetConfig> {
+        &self.custom_targets
+    }
+
+    /// All targets packages may be installed for: the built-in targets and the declared custom
+    /// targets
+    pub fn target_kinds(&self) -> Vec<TargetKind> {
+        TargetKind::VARIANTS
+            .iter()
+            .cloned()
+            .chain(self.custom_targets.keys().cloned().map(TargetKind::Custom))
+            .collect()
+    }
+
+    /// Returns whether packages of the dependency's target can be used by packages of the given
+    /// target without explicitly requesting it, taking custom target configuration into account
+    pub fn is_target_compatible(&self, target: &TargetKind, dependency: &TargetKind) -> bool {
+        target.is_compatible_with(dependency)
+            || matches!(target, TargetKind::Custom(name) if self
+                .custom_targets
+                .get(name)
+                .is_some_and(|config| config.compatible_with.contains(dependency)))
+    }
+
+    /// Set how the realms of Wally packages translate into pesde targets
+    #[cfg(feature = "wally-compat")]
+    pub fn with_wally_realms(mut self, wally_realms: source::wally::WallyRealmsConfig) -> Self {
+        self.wally_realms = wally_realms;
+        self
+    }
+
+    /// How the realms of Wally packages translate into pesde targets
+    #[cfg(feature = "wally-compat")]
+    pub fn wally_realms(&self) -> &source::wally::WallyRealmsConfig {
+        &self.wally_realms
+    }
+
+    /// Set the user-configured mirrors of indices
+    pub fn with_mirrors(mut self, mirrors: BTreeMap<gix::Url, Vec<gix::Url>>) -> Self {
+        self.mirrors = mirrors;
+        self
+    }
+
+    /// The user-configured mirrors of an index
+    pub fn mirrors(&self, index_url: &gix::Url) -> &[gix::Url] {
+        self.mirrors.get(index_url).map_or(&[], Vec::as_slice)
+    }
+
+    /// Set how cached indices are refreshed
+    pub fn with_refresh_policy(mut self, refresh_policy: RefreshPolicy) -> Self {
+        self.refresh_policy = refresh_policy;
+        self
+    }
+
+    /// How cached indices are refreshed
+    pub fn refresh_policy(&self) -> RefreshPolicy {
+        self.refresh_policy
+    }
+
+    /// Set how network operations are retried when they fail transiently
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// How network operations are retried when they fail transiently
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    /// Set how the signatures of pesde packages are verified
+    pub fn with_signature_policy(mut self, signature_policy: SignaturePolicy) -> Self {
+        self.signature_policy = signature_policy;
+        self
+    }
+
+    /// How the signatures of pesde packages are verified
+    pub fn signature_policy(&self) -> SignaturePolicy {
+        self.signature_policy
+    }
+
+    /// Register a custom package source, used by dependencies declaring `custom = "<name>"`
+    pub fn with_custom_source<S: CustomSource + 'static>(mut self, name: &str, source: S) -> Self {
+        self.custom_sources
+            .insert(name.to_string(), Arc::new(source));
+        self
+    }
+
+    /// The custom package source registered under the name
+    pub fn custom_source(&self, name: &str) -> Option<&dyn CustomSource> {
+        self.custom_sources.get(name).map(|source| source.as_ref())
+    }
+
+    /// The index used when the manifest doesn't define the default index
+    pub fn default_index(&self) -> Option<&gix::Url> {
+        self.default_index.as_ref()
+    }
+
+    /// Get the URL of a pesde index from a manifest's indices by its name, falling back to the
+    /// project's default index for the default index name
+    pub fn index_url(&self, indices: &BTreeMap<String, gix::Url>, name: &str) -> Option<gix::Url> {
+        indices.get(name).cloned().or_else(|| {
+            (name == DEFAULT_INDEX_NAME)
+                .then(|| self.default_index.clone())
+                .flatten()
+        })
+    }
+
     /// Read the manifest file
     #[instrument(skip(self), ret(level = "trace"), level = "debug")]
     pub async fn read_manifest(&self) -> Result<String, errors::ManifestReadError> {
@@ -162,7 +360,13 @@ impl Project {
     #[instrument(skip(self), ret(level = "trace"), level = "debug")]
     pub async fn deser_manifest(&self) -> Result<Manifest, errors::ManifestReadError> {
         let string = fs::read_to_string(self.package_dir.join(MANIFEST_FILE_NAME)).await?;
-        Ok(toml::from_str(&string)?)
+        let manifest: Manifest = toml::from_str(&string)?;
+
+        if let Some(target) = manifest.undeclared_custom_target() {
+            return Err(errors::ManifestReadError::UndeclaredCustomTarget(target));
+        }
+
+        Ok(manifest)
     }
 
     /// Write the manifest file
@@ -182,8 +386,9 @@ impl Project {
     #[instrument(skip(self, lockfile), level = "debug")]
     pub async fn write_lockfile(
         &self,
-        lockfile: Lockfile,
+        mut lockfile: Lockfile,
     ) -> Result<(), errors::LockfileWriteError> {
+        lockfile.prune();
         let string = toml::to_string(&lockfile)?;
         fs::write(self.package_dir.join(LOCKFILE_FILE_NAME), string).await?;
         Ok(())
@@ -230,6 +435,61 @@ impl Project {
     }
 }
 
+/// The globs of a manifest's includes, matched with the old behaviour of including whole root
+/// entries by their name (`src` instead of `src/**`)
+pub(crate) struct OldBehaviourGlobs<'a> {
+    negative_globs: wax::Any<'a>,
+    positive_globs: wax::Any<'a>,
+    file_names: HashSet<&'a str>,
+}
+
+impl<'a> OldBehaviourGlobs<'a> {
+    /// Builds the globs
+    pub(crate) fn new<I: IntoIterator<Item = &'a str>>(globs: I) -> Result<Self, wax::BuildError> {
+        let (negative_globs, positive_globs) = globs
+            .into_iter()
+            .partition::<Vec<_>, _>(|glob| glob.starts_with('!'));
+
+        let negative_globs = wax::any(
+            negative_globs
+                .into_iter()
+                .map(|glob| wax::Glob::new(&glob[1..]))
+                .collect::<Result<Vec<_>, _>>()?,
+        )?;
+
+        let (positive_globs, file_names) = positive_globs
+            .into_iter()
+            // only globs we can be sure of (maintaining compatibility with old "only file/dir name" system)
+            .partition::<Vec<_>, _>(|glob| glob.contains('/'));
+        let file_names = file_names.into_iter().collect::<HashSet<_>>();
+
+        let positive_globs = wax::any(
+            positive_globs
+                .into_iter()
+                .map(wax::Glob::new)
+                .collect::<Result<Vec<_>, _>>()?,
+        )?;
+
+        Ok(Self {
+            negative_globs,
+            positive_globs,
+            file_names,
+        })
+    }
+
+    /// Whether an entry of the root directory is included (along with all of its contents) by
+    /// its name
+    pub(crate) fn is_root_name_match(&self, name: &str) -> bool {
+        self.file_names.contains(name)
+    }
+
+    /// Whether a relative path is matched by the globs
+    pub(crate) fn is_glob_match<P: AsRef<Path>>(&self, relative_path: P) -> bool {
+        let relative_path = relative_path.as_ref();
+        self.positive_globs.is_match(relative_path) && !self.negative_globs.is_match(relative_path)
+    }
+}
+
 /// Gets all matching paths in a directory
 #[deprecated(
     since = "0.5.0-rc.13",
@@ -245,29 +505,7 @@ pub async fn matching_globs_old_behaviour<
     globs: I,
     relative: bool,
 ) -> Result<HashSet<PathBuf>, errors::MatchingGlobsError> {
-    let (negative_globs, positive_globs) = globs
-        .into_iter()
-        .partition::<Vec<_>, _>(|glob| glob.starts_with('!'));
-
-    let negative_globs = wax::any(
-        negative_globs
-            .into_iter()
-            .map(|glob| wax::Glob::new(&glob[1..]))
-            .collect::<Result<Vec<_>, _>>()?,
-    )?;
-
-    let (positive_globs, file_names) = positive_globs
-        .into_iter()
-        // only globs we can be sure of (maintaining compatibility with old "only file/dir name" system)
-        .partition::<Vec<_>, _>(|glob| glob.contains('/'));
-    let file_names = file_names.into_iter().collect::<HashSet<_>>();
-
-    let positive_globs = wax::any(
-        positive_globs
-            .into_iter()
-            .map(wax::Glob::new)
-            .collect::<Result<Vec<_>, _>>()?,
-    )?;
+    let globs = OldBehaviourGlobs::new(globs)?;
 
     let mut read_dirs = vec![(fs::read_dir(dir.as_ref().to_path_buf()).await?, false)];
     let mut paths = HashSet::new();
@@ -279,8 +517,10 @@ pub async fn matching_globs_old_behaviour<
             let path = entry.path();
             let relative_path = path.strip_prefix(dir.as_ref()).unwrap();
             let file_name = path.file_name().unwrap();
-            let is_filename_match =
-                is_root && file_name.to_str().is_some_and(|s| file_names.contains(s));
+            let is_filename_match = is_root
+                && file_name
+                    .to_str()
+                    .is_some_and(|s| globs.is_root_name_match(s));
 
             if entry.file_type().await?.is_dir() {
                 read_dirs.push((
@@ -292,10 +532,7 @@ pub async fn matching_globs_old_behaviour<
                 }
             }
 
-            if (is_entire_dir_included || is_filename_match)
-                || (positive_globs.is_match(relative_path)
-                    && !negative_globs.is_match(relative_path))
-            {
+            if is_entire_dir_included || is_filename_match || globs.is_glob_match(relative_path) {
                 paths.insert(if relative {
                     relative_path.to_path_buf()
                 } else {
@@ -372,6 +609,7 @@ pub async fn matching_globs<'a, P: AsRef<Path> + Debug, I: IntoIterator<Item = &
 /// Refreshes the sources asynchronously
 pub async fn refresh_sources<I: Iterator<Item = PackageSources>>(
     project: &Project,
+    reqwest: &reqwest::Client,
     sources: I,
     refreshed_sources: &mut HashSet<PackageSources>,
 ) -> Result<(), Box<source::errors::RefreshError>> {
@@ -379,7 +617,7 @@ pub async fn refresh_sources<I: Iterator<Item = PackageSources>>(
         let needs_refresh = refreshed_sources.insert(source.clone());
         async move {
             if needs_refresh {
-                source.refresh(project).await.map_err(Box::new)
+                source.refresh(project, reqwest).await.map_err(Box::new)
             } else {
                 Ok(())
             }
@@ -389,6 +627,52 @@ pub async fn refresh_sources<I: Iterator<Item = PackageSources>>(
     .map(|_| ())
 }
 
+/// Reads additional root certificates from a PEM bundle, to be added to the HTTP client
+pub fn root_certificates<P: AsRef<Path>>(
+    path: P,
+) -> Result<Vec<reqwest::Certificate>, errors::RootCertificatesError> {
+    let pem = fs_err::read(path.as_ref())?;
+    reqwest::Certificate::from_pem_bundle(&pem).map_err(Into::into)
+}
+
+/// A receiver of the sources' refresh results, along with the amount of sources being refreshed
+pub type RefreshSourcesJob = (
+    usize,
+    tokio::sync::mpsc::Receiver<Result<String, errors::RefreshSourceError>>,
+);
+
+/// Refreshes the sources concurrently, reporting each source through the returned receiver as
+/// soon as its refresh finishes
+pub fn refresh_sources_with_status<I: Iterator<Item = PackageSources>>(
+    project: &Project,
+    reqwest: &reqwest::Client,
+    sources: I,
+    refreshed_sources: &mut HashSet<PackageSources>,
+) -> RefreshSourcesJob {
+    let sources = sources
+        .filter(|source| refreshed_sources.insert(source.clone()))
+        .collect::<Vec<_>>();
+    let (tx, rx) = tokio::sync::mpsc::channel(sources.len().max(1));
+    let len = sources.len();
+
+    for source in sources {
+        let tx = tx.clone();
+        let project = project.clone();
+        let reqwest = reqwest.clone();
+
+        tokio::spawn(async move {
+            let result = match source.refresh(&project, &reqwest).await {
+                Ok(()) => Ok(source.to_string()),
+                Err(e) => Err(errors::RefreshSourceError(source.to_string(), Box::new(e))),
+            };
+
+            tx.send(result).await.unwrap();
+        });
+    }
+
+    (len, rx)
+}
+
 /// Errors that can occur when using the pesde library
 pub mod errors {
     use std::path::PathBuf;
@@ -405,6 +689,10 @@ pub mod errors {
         /// An error occurred while deserializing the manifest file
         #[error("error deserializing manifest file")]
         Serde(#[from] toml::de::Error),
+
+        /// The manifest uses a custom target which isn't declared in its `custom_targets`
+        #[error("custom target {0} is not declared in `custom_targets`")]
+        UndeclaredCustomTarget(crate::manifest::target::TargetKind),
     }
 
     /// Errors that can occur when reading the lockfile
@@ -466,4 +754,25 @@ pub mod errors {
         #[error("error building glob")]
         BuildGlob(#[from] wax::BuildError),
     }
+
+    /// An error that occurred while refreshing one of several sources
+    #[derive(Debug, Error)]
+    #[error("error refreshing source {0}")]
+    pub struct RefreshSourceError(
+        pub String,
+        #[source] pub Box<crate::source::errors::RefreshError>,
+    );
+
+    /// Errors that can occur when reading the additional root certificates
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum RootCertificatesError {
+        /// An error occurred reading the certificates file
+        #[error("error reading certificates file")]
+        Io(#[from] std::io::Error),
+
+        /// The certificates file isn't a valid PEM bundle
+        #[error("error parsing certificates file")]
+        Parse(#[from] reqwest::Error),
+    }
 }
@@ -66,6 +66,28 @@ pub fn generate_lib_linking_module<I: IntoIterator<Item = S>, S: AsRef<str>>(
     output
 }
 
+/// Generate a linking module for a library which exports its types from a separate file. The
+/// types file is only required for the type checker, the module returns before it's reached at
+/// runtime
+pub fn generate_types_linking_module<I: IntoIterator<Item = S>, S: AsRef<str>>(
+    types_path: &str,
+    lib_path: Option<&str>,
+    types: I,
+) -> String {
+    let mut output = match lib_path {
+        Some(lib_path) => format!("do\n\treturn require({lib_path})\nend\n\n"),
+        None => "do\n\treturn {}\nend\n\n".to_string(),
+    };
+
+    output.push_str(&format!("local module = require({types_path})\n"));
+
+    for ty in types {
+        output.push_str(ty.as_ref());
+    }
+
+    output
+}
+
 fn luau_style_path(path: &Path) -> String {
     let path = path
         .components()
@@ -5,7 +5,7 @@ use crate::{
     names::PackageNames,
     scripts::{execute_script, ScriptName},
     source::{
-        fs::{cas_path, store_in_cas},
+        fs::{cas_path, link_from_cas, store_in_cas},
         traits::PackageRef,
         version_id::VersionId,
     },
@@ -40,7 +40,7 @@ async fn write_cas(destination: PathBuf, cas_dir: &Path, contents: &str) -> std:
         Err(e) => return Err(e),
     };
 
-    fs::hard_link(cas_path(&hash, cas_dir), destination).await
+    link_from_cas(&cas_path(&hash, cas_dir), &destination).await
 }
 
 impl Project {
@@ -52,7 +52,7 @@ impl Project {
         with_types: bool,
     ) -> Result<(), errors::LinkingError> {
         let manifest = self.deser_manifest().await?;
-        let manifest_target_kind = manifest.target.kind();
+        let manifest_target_kind = &manifest.target.kind();
         let manifest = Arc::new(manifest);
 
         // step 1. link all non-wally packages (and their dependencies) temporarily without types
@@ -73,8 +73,13 @@ impl Project {
             Ok::<_, errors::LinkingError>((
                 name,
                 try_join_all(versions.iter().map(|(version_id, node)| async move {
-                    let Some(lib_file) = node.target.lib_path() else {
-                        return Ok((version_id, vec![]));
+                    // types are exported by the types file if there is one, otherwise by the lib file
+                    let (types_file, is_lib) = match node.target.types_path() {
+                        Some(types_file) => (types_file, false),
+                        None => match node.target.lib_path() {
+                            Some(lib_file) => (lib_file, true),
+                            None => return Ok((version_id, vec![])),
+                        },
                     };
 
                     let container_folder = node.node.container_folder(
@@ -86,15 +91,21 @@ impl Project {
                         version_id.version(),
                     );
 
-                    let types = if lib_file.as_str() != LINK_LIB_NO_FILE_FOUND {
-                        let lib_file = lib_file.to_path(&container_folder);
+                    let types = if types_file.as_str() != LINK_LIB_NO_FILE_FOUND {
+                        let lib_file = types_file.to_path(&container_folder);
 
                         let contents = match fs::read_to_string(&lib_file).await {
                             Ok(contents) => contents,
                             Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                                return Err(errors::LinkingError::LibFileNotFound(
-                                    lib_file.display().to_string(),
-                                ));
+                                return Err(if is_lib {
+                                    errors::LinkingError::LibFileNotFound(
+                                        lib_file.display().to_string(),
+                                    )
+                                } else {
+                                    errors::LinkingError::TypesFileNotFound(
+                                        lib_file.display().to_string(),
+                                    )
+                                });
                             }
                             Err(e) => return Err(e.into()),
                         };
@@ -176,24 +187,37 @@ impl Project {
     ) -> Result<(), errors::LinkingError> {
         static NO_TYPES: Vec<String> = Vec::new();
 
-        if let Some(lib_file) = node.target.lib_path() {
-            let lib_module = generator::generate_lib_linking_module(
-                &generator::get_lib_require_path(
-                    &node.target.kind(),
-                    base_folder,
-                    lib_file,
-                    container_folder,
-                    node.node.pkg_ref.use_new_structure(),
-                    root_container_folder,
-                    relative_container_folder,
-                    manifest,
-                )?,
-                package_types
-                    .get(name)
-                    .and_then(|v| v.get(version_id))
-                    .unwrap_or(&NO_TYPES),
-            );
-
+        let require_path = |file: &relative_path::RelativePathBuf| {
+            generator::get_lib_require_path(
+                &node.target.kind(),
+                base_folder,
+                file,
+                container_folder,
+                node.node.pkg_ref.use_new_structure(),
+                root_container_folder,
+                relative_container_folder,
+                manifest,
+            )
+        };
+        let types = package_types
+            .get(name)
+            .and_then(|v| v.get(version_id))
+            .unwrap_or(&NO_TYPES);
+
+        let lib_module = match (node.target.types_path(), node.target.lib_path()) {
+            (Some(types_file), lib_file) => Some(generator::generate_types_linking_module(
+                &require_path(types_file)?,
+                lib_file.map(require_path).transpose()?.as_deref(),
+                types,
+            )),
+            (None, Some(lib_file)) => Some(generator::generate_lib_linking_module(
+                &require_path(lib_file)?,
+                types,
+            )),
+            (None, None) => None,
+        };
+
+        if let Some(lib_module) = lib_module {
             write_cas(
                 base_folder.join(format!("{alias}.luau")),
                 self.cas_dir(),
@@ -202,7 +226,7 @@ impl Project {
             .await?;
         }
 
-        if let Some(bin_file) = node.target.bin_path() {
+        if let Some(bin_file) = node.target.host_bin_path() {
             let bin_module = generator::generate_bin_linking_module(
                 container_folder,
                 &generator::get_bin_require_path(base_folder, bin_file, container_folder),
@@ -384,6 +408,10 @@ pub mod errors {
         #[error("library file at {0} not found")]
         LibFileNotFound(String),
 
+        /// The types file was not found
+        #[error("types file at {0} not found")]
+        TypesFileNotFound(String),
+
         /// An error occurred while parsing a Luau script
         #[error("error parsing Luau script at {0}")]
         FullMoon(String, Vec<full_moon::Error>),
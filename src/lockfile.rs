@@ -14,7 +14,7 @@ use relative_path::RelativePathBuf;
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashSet},
     path::{Path, PathBuf},
 };
 
@@ -40,7 +40,28 @@ pub struct DependencyGraphNode {
 }
 
 impl DependencyGraphNode {
-    pub(crate) fn base_folder(&self, version_id: &VersionId, project_target: TargetKind) -> String {
+    /// Removes the dependencies which don't apply to the platform pesde is running on, and the
+    /// direct dependency if its specifier doesn't
+    pub(crate) fn retain_host_dependencies(&mut self) {
+        let pkg_ref = &self.pkg_ref;
+        self.dependencies.retain(|_, (_, alias)| {
+            pkg_ref
+                .dependencies()
+                .get(alias.as_str())
+                .is_none_or(|(spec, _)| spec.platform().matches_host())
+        });
+
+        if self
+            .direct
+            .as_ref()
+            .is_some_and(|(_, spec, _)| !spec.platform().matches_host())
+        {
+            self.direct = None;
+        }
+    }
+
+    /// Returns the folder, relative to the container folder, to link this package's dependencies of the given target in
+    pub fn base_folder(&self, version_id: &VersionId, project_target: TargetKind) -> String {
         if self.pkg_ref.use_new_structure() {
             version_id.target().packages_folder(&project_target)
         } else {
@@ -74,6 +95,87 @@ impl DependencyGraphNode {
     }
 }
 
+/// The nodes of the graph which are reachable from a direct dependency through the dependencies
+/// whose specifiers satisfy the predicate. Overridden dependencies are checked by the specifiers
+/// the packages declared them with
+fn reachable_nodes<Node>(
+    graph: &Graph<Node>,
+    get_node: impl Fn(&Node) -> &DependencyGraphNode,
+    applies: impl Fn(&DependencySpecifiers) -> bool,
+) -> HashSet<(PackageNames, VersionId)> {
+    let mut reachable = HashSet::new();
+    let mut queue = graph
+        .iter()
+        .flat_map(|(name, versions)| {
+            versions
+                .iter()
+                .filter(|(_, node)| {
+                    get_node(node)
+                        .direct
+                        .as_ref()
+                        .is_some_and(|(_, spec, _)| applies(spec))
+                })
+                .map(move |(version_id, _)| (name.clone(), version_id.clone()))
+        })
+        .collect::<Vec<_>>();
+
+    while let Some((name, version_id)) = queue.pop() {
+        let Some(node) = graph
+            .get(&name)
+            .and_then(|versions| versions.get(&version_id))
+        else {
+            continue;
+        };
+        let node = get_node(node);
+
+        queue.extend(
+            node.dependencies
+                .iter()
+                .filter(|(_, (_, alias))| {
+                    node.pkg_ref
+                        .dependencies()
+                        .get(alias)
+                        .is_none_or(|(spec, _)| applies(spec))
+                })
+                .map(|(name, (version_id, _))| (name.clone(), version_id.clone()))
+                .filter(|dep| !reachable.contains(dep)),
+        );
+        reachable.insert((name, version_id));
+    }
+
+    reachable
+}
+
+/// Removes nodes from the graph which aren't reachable from any direct dependency
+pub(crate) fn prune_graph<Node>(
+    graph: &mut Graph<Node>,
+    get_node: impl Fn(&Node) -> &DependencyGraphNode,
+) {
+    let reachable = reachable_nodes(graph, get_node, |_| true);
+
+    for (name, versions) in graph.iter_mut() {
+        versions.retain(|version_id, _| {
+            let keep = reachable.contains(&(name.clone(), version_id.clone()));
+            if !keep {
+                tracing::debug!("pruning unreachable {name}@{version_id}");
+            }
+            keep
+        });
+    }
+
+    graph.retain(|_, versions| !versions.is_empty());
+}
+
+/// The nodes of the graph which are used on the platform pesde is running on. Platform conditions
+/// aren't evaluated when resolving, so that the lockfile is the same on every platform; they're
+/// recorded in it as part of the dependencies' specifiers instead
+pub(crate) fn host_nodes<Node>(
+    graph: &Graph<Node>,
+    get_node: impl Fn(&Node) -> &DependencyGraphNode,
+) -> HashSet<(PackageNames, VersionId)> {
+    reachable_nodes(graph, get_node, |spec| spec.platform().matches_host())
+}
+
 /// A graph of `DependencyGraphNode`s
 pub type DependencyGraph = Graph<DependencyGraphNode>;
 
@@ -111,3 +213,246 @@ pub struct Lockfile {
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub graph: DownloadedGraph,
 }
+
+impl Lockfile {
+    /// Removes nodes from the graph which aren't reachable from any direct dependency
+    pub fn prune(&mut self) {
+        prune_graph(&mut self.graph, |node| &node.node);
+    }
+}
+
+/// Builders of dependency graphs for tests
+#[cfg(test)]
+pub(crate) mod test_graph {
+    use super::*;
+    use crate::source::pesde::{pkg_ref::PesdePackageRef, specifier::PesdeDependencySpecifier};
+
+    pub fn name(name: &str) -> PackageName {
+        name.parse().unwrap()
+    }
+
+    pub fn version_id(version: &str) -> VersionId {
+        VersionId::new(version.parse().unwrap(), TargetKind::Luau)
+    }
+
+    pub fn specifier(package: &str, version: &str) -> DependencySpecifiers {
+        DependencySpecifiers::Pesde(PesdeDependencySpecifier {
+            name: name(package),
+            version: version.parse().unwrap(),
+            index: None,
+            target: None,
+            platform: Default::default(),
+        })
+    }
+
+    /// A node of a Luau package, depending on the exact versions of other packages. Direct
+    /// dependencies are aliased by the name of their package, without its scope
+    pub fn node(
+        package: &str,
+        version: &str,
+        direct: bool,
+        dependencies: &[(&str, &str)],
+    ) -> DependencyGraphNode {
+        DependencyGraphNode {
+            direct: direct.then(|| {
+                (
+                    package.split_once('/').unwrap().1.to_string(),
+                    specifier(package, &format!("^{version}")),
+                    DependencyType::Standard,
+                )
+            }),
+            dependencies: dependencies
+                .iter()
+                .map(|(dep, dep_version)| {
+                    (
+                        PackageNames::Pesde(name(dep)),
+                        (
+                            version_id(dep_version),
+                            dep.split_once('/').unwrap().1.to_string(),
+                        ),
+                    )
+                })
+                .collect(),
+            resolved_ty: DependencyType::Standard,
+            is_peer: false,
+            pkg_ref: PackageRefs::Pesde(PesdePackageRef {
+                name: name(package),
+                version: version.parse().unwrap(),
+                index_url: gix::url::parse("https://github.com/pesde-pkg/index".into()).unwrap(),
+                dependencies: dependencies
+                    .iter()
+                    .map(|(dep, dep_version)| {
+                        (
+                            dep.split_once('/').unwrap().1.to_string(),
+                            (
+                                specifier(dep, &format!("^{dep_version}")),
+                                DependencyType::Standard,
+                            ),
+                        )
+                    })
+                    .collect(),
+                target: Target::Luau {
+                    lib: Some("init.luau".into()),
+                    types: None,
+                    bin: None,
+                    bin_platform: Default::default(),
+                    scripts: Default::default(),
+                },
+                checksum: None,
+                signature: None,
+            }),
+        }
+    }
+
+    /// A graph of the nodes, keyed by their packages
+    pub fn graph(nodes: impl IntoIterator<Item = DependencyGraphNode>) -> DependencyGraph {
+        let mut graph = DependencyGraph::new();
+
+        for node in nodes {
+            let PackageRefs::Pesde(pkg_ref) = &node.pkg_ref else {
+                unreachable!()
+            };
+
+            graph
+                .entry(PackageNames::Pesde(pkg_ref.name.clone()))
+                .or_default()
+                .insert(
+                    VersionId::new(pkg_ref.version.clone(), TargetKind::Luau),
+                    node,
+                );
+        }
+
+        graph
+    }
+
+    /// The graph with the targets of its packages, as if they were downloaded
+    pub fn downloaded(graph: DependencyGraph) -> DownloadedGraph {
+        graph
+            .into_iter()
+            .map(|(name, versions)| {
+                (
+                    name,
+                    versions
+                        .into_iter()
+                        .map(|(version_id, node)| {
+                            let PackageRefs::Pesde(pkg_ref) = &node.pkg_ref else {
+                                unreachable!()
+                            };
+                            let target = pkg_ref.target.clone();
+                            (version_id, DownloadedDependencyGraphNode { target, node })
+                        })
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{test_graph::*, *};
+
+    fn packages(lockfile: &Lockfile) -> Vec<String> {
+        lockfile
+            .graph
+            .iter()
+            .flat_map(|(name, versions)| {
+                versions
+                    .keys()
+                    .map(move |version_id| format!("{name}@{version_id}"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn prune_round_trip() {
+        let graph = downloaded(graph([
+            node("acme/app_dep", "1.2.0", true, &[("acme/util", "2.0.0")]),
+            node("acme/util", "2.0.0", false, &[]),
+            // left over from a previous resolution, and depended on by nothing
+            node("acme/stale", "0.1.0", false, &[("acme/util", "1.0.0")]),
+            node("acme/util", "1.0.0", false, &[]),
+        ]));
+
+        let mut lockfile = Lockfile {
+            name: name("acme/app"),
+            version: "0.1.0".parse().unwrap(),
+            target: TargetKind::Luau,
+            overrides: Default::default(),
+            workspace: Default::default(),
+            graph,
+        };
+
+        let serialized = toml::to_string(&lockfile).unwrap();
+        let mut deserialized = toml::from_str::<Lockfile>(&serialized).unwrap();
+        assert_eq!(packages(&deserialized), packages(&lockfile));
+
+        lockfile.prune();
+        deserialized.prune();
+        let expected = vec![
+            "acme/app_dep@1.2.0 luau".to_string(),
+            "acme/util@2.0.0 luau".to_string(),
+        ];
+        assert_eq!(packages(&lockfile), expected);
+        assert_eq!(packages(&deserialized), expected);
+
+        let reserialized = toml::to_string(&deserialized).unwrap();
+        assert_eq!(reserialized, toml::to_string(&lockfile).unwrap());
+
+        let pruned = toml::from_str::<Lockfile>(&reserialized).unwrap();
+        assert_eq!(packages(&pruned), expected);
+        let app_dep =
+            &pruned.graph[&PackageNames::Pesde(name("acme/app_dep"))][&version_id("1.2.0")].node;
+        assert!(app_dep.direct.is_some());
+        assert_eq!(
+            app_dep.dependencies[&PackageNames::Pesde(name("acme/util"))].0,
+            version_id("2.0.0")
+        );
+    }
+
+    #[test]
+    fn platform_conditions_are_kept_in_lockfile() {
+        let mut app_dep = node("acme/app_dep", "1.2.0", true, &[("acme/util", "2.0.0")]);
+        let PackageRefs::Pesde(pkg_ref) = &mut app_dep.pkg_ref else {
+            unreachable!()
+        };
+        let DependencySpecifiers::Pesde(util) =
+            &mut pkg_ref.dependencies.get_mut("util").unwrap().0
+        else {
+            unreachable!()
+        };
+        util.platform.target_os = Some("not-an-os".to_string());
+
+        let graph = downloaded(graph([app_dep, node("acme/util", "2.0.0", false, &[])]));
+
+        let lockfile = Lockfile {
+            name: name("acme/app"),
+            version: "0.1.0".parse().unwrap(),
+            target: TargetKind::Luau,
+            overrides: Default::default(),
+            workspace: Default::default(),
+            graph,
+        };
+
+        let mut lockfile =
+            toml::from_str::<Lockfile>(&toml::to_string(&lockfile).unwrap()).unwrap();
+        // the package isn't used on this platform, but is still resolved for the others
+        lockfile.prune();
+        assert_eq!(
+            packages(&lockfile),
+            vec![
+                "acme/app_dep@1.2.0 luau".to_string(),
+                "acme/util@2.0.0 luau".to_string(),
+            ]
+        );
+
+        let host_nodes = host_nodes(&lockfile.graph, |node| &node.node);
+        assert_eq!(
+            host_nodes,
+            HashSet::from([(
+                PackageNames::Pesde(name("acme/app_dep")),
+                version_id("1.2.0")
+            )])
+        );
+    }
+}
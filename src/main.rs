@@ -1,13 +1,18 @@
 #[cfg(feature = "version-management")]
 use crate::cli::version::{check_for_updates, get_or_download_version, TagInfo};
-use crate::cli::{auth::get_tokens, display_err, home_dir, HOME_DIR};
+use crate::cli::{auth::get_tokens, config::read_config, display_err, home_dir, HOME_DIR};
 use anyhow::Context;
 use clap::{builder::styling::AnsiColor, Parser};
 use fs_err::tokio as fs;
-use pesde::{matching_globs, AuthConfig, Project, MANIFEST_FILE_NAME};
+use gix::sec::identity::Account;
+use pesde::{
+    errors::ManifestReadError, matching_globs, retry::RetryPolicy, root_certificates,
+    source::RefreshPolicy, AuthConfig, Project, MANIFEST_FILE_NAME,
+};
 use std::{
     collections::HashSet,
     path::{Path, PathBuf},
+    time::Duration,
 };
 use tempfile::NamedTempFile;
 use tracing::instrument;
@@ -38,6 +43,14 @@ struct Cli {
     #[arg(short = 'v', short_alias = 'V', long, action = clap::builder::ArgAction::Version)]
     version: (),
 
+    /// Refresh indices even if they were refreshed recently
+    #[arg(long, global = true, conflicts_with = "no_refresh")]
+    refresh: bool,
+
+    /// Use the cached indices and repositories without refreshing them, only fetching the missing ones
+    #[arg(long, global = true)]
+    no_refresh: bool,
+
     #[command(subcommand)]
     subcommand: cli::commands::Subcommand,
 }
@@ -245,13 +258,67 @@ async fn run() -> anyhow::Result<()> {
 
     tracing::debug!("using cas dir in {}", cas_dir.display());
 
+    let config = read_config().await?;
+
+    let index_ttl = Duration::from_secs(config.index_ttl_secs);
+    let mirrors = config
+        .mirrors
+        .iter()
+        .map(|(index, mirrors)| {
+            let index = gix::Url::try_from(index.as_str())
+                .with_context(|| format!("invalid index url {index} in mirrors"))?;
+            let mirrors = mirrors
+                .iter()
+                .map(|mirror| {
+                    gix::Url::try_from(mirror.as_str())
+                        .with_context(|| format!("invalid mirror url {mirror}"))
+                })
+                .collect::<anyhow::Result<_>>()?;
+
+            Ok((index, mirrors))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    let git_credentials = config
+        .git_credentials
+        .iter()
+        .map(|(repo, credentials)| {
+            let repo = gix::Url::try_from(repo.as_str())
+                .with_context(|| format!("invalid repository url {repo} in git credentials"))?;
+
+            Ok((
+                repo,
+                Account {
+                    username: credentials.username.clone(),
+                    password: credentials.password.clone(),
+                },
+            ))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
     let project = Project::new(
         project_root_dir,
         project_workspace_dir,
         data_dir,
         cas_dir,
-        AuthConfig::new().with_tokens(get_tokens().await?.0),
-    );
+        AuthConfig::new()
+            .with_tokens(get_tokens().await?.0)
+            .with_index_git_credentials(git_credentials)
+            .with_wally_git_tokens(config.wally_git_tokens),
+    )
+    .with_default_index(Some(config.default_index))
+    .with_mirrors(mirrors);
+    let project = match project.deser_manifest().await {
+        Ok(manifest) => {
+            #[cfg(feature = "wally-compat")]
+            let project = project.with_wally_realms(manifest.wally_realms);
+
+            project
+                .with_custom_targets(manifest.custom_targets)
+                .with_signature_policy(manifest.signature_policy)
+        }
+        Err(ManifestReadError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => project,
+        Err(e) => return Err(e).context("failed to read manifest"),
+    };
 
     let reqwest = {
         let mut headers = reqwest::header::HeaderMap::new();
@@ -263,14 +330,37 @@ async fn run() -> anyhow::Result<()> {
                 .context("failed to create accept header")?,
         );
 
-        reqwest::Client::builder()
+        let mut builder = reqwest::Client::builder()
             .user_agent(concat!(
                 env!("CARGO_PKG_NAME"),
                 "/",
                 env!("CARGO_PKG_VERSION")
             ))
-            .default_headers(headers)
-            .build()?
+            .default_headers(headers);
+
+        // git repositories are fetched with this client too, so they go through the proxy as well
+        if let Some(proxy) = &config.proxy {
+            builder = builder.proxy(
+                reqwest::Proxy::all(proxy)
+                    .context("invalid proxy url")?
+                    .no_proxy(
+                        config
+                            .no_proxy
+                            .as_deref()
+                            .and_then(reqwest::NoProxy::from_string),
+                    ),
+            );
+        }
+
+        if let Some(path) = &config.ca_certificates {
+            for certificate in root_certificates(path).with_context(|| {
+                format!("failed to read root certificates from {}", path.display())
+            })? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        builder.build()?
     };
 
     #[cfg(feature = "version-management")]
@@ -304,6 +394,20 @@ async fn run() -> anyhow::Result<()> {
 
     let cli = Cli::parse();
 
+    let project = project
+        .with_refresh_policy(if cli.refresh {
+            RefreshPolicy::Always
+        } else if cli.no_refresh {
+            RefreshPolicy::Never
+        } else {
+            RefreshPolicy::IfOlderThan(index_ttl)
+        })
+        .with_retry_policy(RetryPolicy {
+            max_retries: config.max_retries,
+            initial_delay: Duration::from_millis(config.retry_delay_ms),
+            ..Default::default()
+        });
+
     cli.subcommand.run(project, reqwest).await
 }
 
@@ -0,0 +1,291 @@
+use crate::{manifest::DependencyType, source::specifiers::DependencySpecifiers, Project};
+use std::{fmt::Display, str::FromStr};
+use toml_edit::DocumentMut;
+use tracing::instrument;
+
+/// An editor for manifests which performs targeted edits, preserving the file's formatting and comments
+#[derive(Debug, Clone, Default)]
+pub struct ManifestEditor {
+    document: DocumentMut,
+}
+
+fn dependency_key(ty: DependencyType) -> &'static str {
+    match ty {
+        DependencyType::Standard => "dependencies",
+        DependencyType::Peer => "peer_dependencies",
+        DependencyType::Dev => "dev_dependencies",
+    }
+}
+
+const DEPENDENCY_TYPES: [DependencyType; 3] = [
+    DependencyType::Standard,
+    DependencyType::Peer,
+    DependencyType::Dev,
+];
+
+impl ManifestEditor {
+    /// Removes the dependency with the alias from the table of the type, removing the table too if
+    /// it's left empty. Returns whether the dependency was found
+    fn remove_dependency_of_type(&mut self, ty: DependencyType, alias: &str) -> bool {
+        let key = dependency_key(ty);
+        let Some(table) = self
+            .document
+            .get_mut(key)
+            .and_then(toml_edit::Item::as_table_like_mut)
+        else {
+            return false;
+        };
+
+        if table.remove(alias).is_none() {
+            return false;
+        }
+
+        if table.is_empty() {
+            self.document.remove(key);
+        }
+
+        true
+    }
+
+    /// Adds a dependency, replacing any existing dependency with the same alias, of any type
+    pub fn add_dependency(
+        &mut self,
+        ty: DependencyType,
+        alias: &str,
+        specifier: &DependencySpecifiers,
+    ) -> Result<(), errors::ManifestEditError> {
+        let value = toml::Value::try_from(specifier)?
+            .to_string()
+            .parse::<toml_edit::Value>()?;
+
+        // aliases must be unique across the dependency types
+        for other_ty in DEPENDENCY_TYPES
+            .into_iter()
+            .filter(|other_ty| *other_ty != ty)
+        {
+            self.remove_dependency_of_type(other_ty, alias);
+        }
+
+        self.document[dependency_key(ty)]
+            .or_insert(toml_edit::Item::Table(toml_edit::Table::new()))[alias] =
+            toml_edit::Item::Value(value);
+
+        Ok(())
+    }
+
+    /// Removes the dependency with the alias, returning its type if it was found
+    pub fn remove_dependency(&mut self, alias: &str) -> Option<DependencyType> {
+        DEPENDENCY_TYPES
+            .into_iter()
+            .find(|ty| self.remove_dependency_of_type(*ty, alias))
+    }
+
+    /// Sets the version requirement of the dependency with the alias, keeping the rest of its
+    /// specifier. Returns whether the dependency was found with a version requirement to set
+    pub fn set_version(&mut self, alias: &str, version: &semver::VersionReq) -> bool {
+        let Some(ty) = DEPENDENCY_TYPES.into_iter().find(|ty| {
+            self.document
+                .get(dependency_key(*ty))
+                .and_then(toml_edit::Item::as_table_like)
+                .is_some_and(|table| table.contains_key(alias))
+        }) else {
+            return false;
+        };
+        let Some(version_item) = self.document[dependency_key(ty)][alias]
+            .as_table_like_mut()
+            .and_then(|specifier| specifier.get_mut("version"))
+        else {
+            return false;
+        };
+
+        let Some(old) = version_item.as_value() else {
+            return false;
+        };
+        let mut new = toml_edit::Value::from(version.to_string());
+        *new.decor_mut() = old.decor().clone();
+        *version_item = toml_edit::Item::Value(new);
+
+        true
+    }
+
+    /// Adds a patch for the given package version
+    #[cfg(feature = "patches")]
+    pub fn add_patch(
+        &mut self,
+        name: &crate::names::PackageNames,
+        version_id: &crate::source::version_id::VersionId,
+        path: &relative_path::RelativePath,
+    ) {
+        self.document["patches"].or_insert(toml_edit::Item::Table(toml_edit::Table::new()))
+            [&name.to_string()][&version_id.to_string()] = toml_edit::value(path.as_str());
+    }
+
+    /// Returns the underlying document, for edits not covered by the editor's methods
+    pub fn document_mut(&mut self) -> &mut DocumentMut {
+        &mut self.document
+    }
+}
+
+impl FromStr for ManifestEditor {
+    type Err = toml_edit::TomlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(ManifestEditor {
+            document: s.parse()?,
+        })
+    }
+}
+
+impl Display for ManifestEditor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.document)
+    }
+}
+
+impl Project {
+    /// Reads the manifest into an editor
+    #[instrument(skip(self), level = "debug")]
+    pub async fn manifest_editor(&self) -> Result<ManifestEditor, errors::ManifestEditorReadError> {
+        let string = self.read_manifest().await?;
+        Ok(string.parse()?)
+    }
+
+    /// Writes the manifest from an editor
+    #[instrument(skip(self, editor), level = "debug")]
+    pub async fn write_manifest_editor(&self, editor: &ManifestEditor) -> std::io::Result<()> {
+        self.write_manifest(editor.to_string()).await
+    }
+}
+
+/// Errors that can occur when editing manifests
+pub mod errors {
+    use thiserror::Error;
+
+    /// Errors that can occur when editing a manifest
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum ManifestEditError {
+        /// A value could not be serialized
+        #[error("error serializing value")]
+        Serialize(#[from] toml::ser::Error),
+
+        /// A serialized value could not be parsed into an editable value
+        #[error("error parsing serialized value")]
+        Parse(#[from] toml_edit::TomlError),
+    }
+
+    /// Errors that can occur when reading a manifest into an editor
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum ManifestEditorReadError {
+        /// An error occurred while reading the manifest
+        #[error("error reading manifest")]
+        Read(#[from] crate::errors::ManifestReadError),
+
+        /// An error occurred while parsing the manifest
+        #[error("error parsing manifest")]
+        Parse(#[from] toml_edit::TomlError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::pesde::specifier::PesdeDependencySpecifier;
+
+    const MANIFEST: &str = r#"name = "acme/app"
+version = "0.1.0"
+target = { environment = "luau" }
+
+# runtime dependencies
+[dependencies]
+foo = { name = "acme/foo",   version = "^1.0.0" } # pinned for now
+bar = { name = "acme/bar", version = "^2.0.0" }
+
+[dev_dependencies.baz]
+name = "acme/baz"
+version = "^3.0.0" # latest
+
+[peer_dependencies]
+qux = { name = "acme/qux", version = "^4.0.0" }
+"#;
+
+    fn editor() -> ManifestEditor {
+        MANIFEST.parse().unwrap()
+    }
+
+    #[test]
+    fn add_dependency_replaces_other_types() {
+        let mut editor = editor();
+        editor
+            .add_dependency(
+                DependencyType::Dev,
+                "qux",
+                &DependencySpecifiers::Pesde(PesdeDependencySpecifier {
+                    name: "acme/qux".parse().unwrap(),
+                    version: "^5.0.0".parse().unwrap(),
+                    index: None,
+                    target: None,
+                    platform: Default::default(),
+                }),
+            )
+            .unwrap();
+
+        let edited = editor.to_string();
+        // the peer dependency table is left empty, so it's removed
+        assert!(!edited.contains("peer_dependencies"), "{edited}");
+        assert!(edited.contains("# runtime dependencies"), "{edited}");
+
+        let manifest = toml::from_str::<crate::manifest::Manifest>(&edited).unwrap();
+        assert!(manifest.peer_dependencies.is_empty());
+        assert_eq!(
+            manifest.dev_dependencies["qux"]
+                .version_req()
+                .unwrap()
+                .to_string(),
+            "^5.0.0"
+        );
+        manifest.all_dependencies().unwrap();
+    }
+
+    #[test]
+    fn remove_dependency_keeps_formatting() {
+        let mut editor = editor();
+        assert_eq!(
+            editor.remove_dependency("bar"),
+            Some(DependencyType::Standard)
+        );
+        assert_eq!(editor.remove_dependency("baz"), Some(DependencyType::Dev));
+        assert_eq!(editor.remove_dependency("missing"), None);
+
+        assert_eq!(
+            editor.to_string(),
+            r#"name = "acme/app"
+version = "0.1.0"
+target = { environment = "luau" }
+
+# runtime dependencies
+[dependencies]
+foo = { name = "acme/foo",   version = "^1.0.0" } # pinned for now
+
+[peer_dependencies]
+qux = { name = "acme/qux", version = "^4.0.0" }
+"#
+        );
+    }
+
+    #[test]
+    fn set_version_keeps_formatting() {
+        let mut editor = editor();
+        assert!(editor.set_version("foo", &"^1.2.0".parse().unwrap()));
+        assert!(editor.set_version("baz", &"^3.1.0".parse().unwrap()));
+        assert!(!editor.set_version("missing", &"^1.0.0".parse().unwrap()));
+
+        assert_eq!(
+            editor.to_string(),
+            MANIFEST
+                .replace(r#"version = "^1.0.0" }"#, r#"version = "^1.2.0" }"#)
+                .replace(r#"version = "^3.0.0""#, r#"version = "^3.1.0""#)
+        );
+    }
+}
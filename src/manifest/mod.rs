@@ -1,7 +1,11 @@
 use crate::{
-    manifest::{overrides::OverrideKey, target::Target},
+    manifest::{
+        overrides::OverrideKey,
+        target::{CustomTargetConfig, Target, TargetKind},
+    },
     names::PackageName,
     source::specifiers::DependencySpecifiers,
+    DEFAULT_INDEX_NAME,
 };
 use relative_path::RelativePathBuf;
 use semver::Version;
@@ -9,11 +13,66 @@ use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
 use tracing::instrument;
 
+/// Editing manifests
+pub mod editor;
 /// Overrides
 pub mod overrides;
+/// Platform conditions
+pub mod platform;
 /// Targets
 pub mod target;
 
+/// A maintainer of a package
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Maintainer {
+    /// The name of the maintainer
+    pub name: String,
+    /// The email of the maintainer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// The GitHub username of the maintainer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub github: Option<String>,
+}
+
+/// Arbitrary metadata of a package, for use by external tools
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(transparent)]
+pub struct Metadata(pub toml::Table);
+
+impl Metadata {
+    /// Whether the metadata is empty
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// floats are compared by their bits, so that equality is reflexive and the metadata can be `Eq`
+fn metadata_value_eq(a: &toml::Value, b: &toml::Value) -> bool {
+    match (a, b) {
+        (toml::Value::Float(a), toml::Value::Float(b)) => a.to_bits() == b.to_bits(),
+        (toml::Value::Array(a), toml::Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| metadata_value_eq(a, b))
+        }
+        (toml::Value::Table(a), toml::Value::Table(b)) => metadata_table_eq(a, b),
+        (a, b) => a == b,
+    }
+}
+
+fn metadata_table_eq(a: &toml::Table, b: &toml::Table) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .all(|(key, a)| b.get(key).is_some_and(|b| metadata_value_eq(a, b)))
+}
+
+impl PartialEq for Metadata {
+    fn eq(&self, other: &Self) -> bool {
+        metadata_table_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Metadata {}
+
 /// A package manifest
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Manifest {
@@ -33,8 +92,17 @@ pub struct Manifest {
     /// The repository of the package
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub repository: Option<url::Url>,
+    /// The maintainers of the package
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub maintainers: Vec<Maintainer>,
+    /// The URLs where the package can be funded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub funding: Vec<url::Url>,
     /// The target of the package
     pub target: Target,
+    /// The configuration of custom targets used by this project
+    #[serde(default, skip_serializing)]
+    pub custom_targets: BTreeMap<String, CustomTargetConfig>,
     /// Whether the package is private
     #[serde(default)]
     pub private: bool,
@@ -48,6 +116,9 @@ pub struct Manifest {
         deserialize_with = "crate::util::deserialize_gix_url_map"
     )]
     pub indices: BTreeMap<String, gix::Url>,
+    /// The name of the index used by pesde and Wally dependencies which don't specify one
+    #[serde(default, skip_serializing)]
+    pub default_index: Option<String>,
     /// The indices to use for the package's wally dependencies
     #[cfg(feature = "wally-compat")]
     #[serde(
@@ -56,6 +127,10 @@ pub struct Manifest {
         deserialize_with = "crate::util::deserialize_gix_url_map"
     )]
     pub wally_indices: BTreeMap<String, gix::Url>,
+    /// How the realms of Wally dependencies translate into pesde targets
+    #[cfg(feature = "wally-compat")]
+    #[serde(default, skip_serializing)]
+    pub wally_realms: crate::source::wally::WallyRealmsConfig,
     /// The overrides this package has
     #[serde(default, skip_serializing)]
     pub overrides: BTreeMap<OverrideKey, DependencySpecifiers>,
@@ -78,6 +153,25 @@ pub struct Manifest {
     /// The Roblox place of this project
     #[serde(default, skip_serializing)]
     pub place: BTreeMap<target::RobloxPlaceKind, String>,
+    /// How multiple versions of the same package in one target are handled
+    #[serde(default, skip_serializing)]
+    pub duplicate_versions: DuplicateVersionsPolicy,
+    /// Whether unresolved peer dependencies should be installed as direct dependencies of the project
+    #[serde(default, skip_serializing)]
+    pub install_peers: bool,
+    /// Whether unresolved peer dependencies should be errors. If `install_peers` is enabled, only the peer dependencies which can't be installed are
+    #[serde(default, skip_serializing)]
+    pub strict_peers: bool,
+    /// How the signatures of pesde packages are verified when downloading them
+    #[serde(default, skip_serializing)]
+    pub signature_policy: SignaturePolicy,
+    /// Whether aliases for dependencies should be written to the project's `.luaurc`. The file is
+    /// rewritten as formatted JSON, so it's opt-in
+    #[serde(default, skip_serializing)]
+    pub luaurc_aliases: bool,
+    /// Arbitrary metadata for use by external tools
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    pub metadata: Metadata,
 
     /// The standard dependencies of the package
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
@@ -105,7 +199,38 @@ pub enum DependencyType {
     Dev,
 }
 
+/// The policy for multiple versions of the same package coexisting in one target's packages folder
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateVersionsPolicy {
+    /// Multiple versions may coexist
+    #[default]
+    Allow,
+    /// Multiple versions are unified into the highest one which satisfies every dependant, or result in an error if there is none
+    Unify,
+    /// Multiple versions result in an error
+    Error,
+}
+
+/// The policy for verifying the signatures of pesde packages' archives
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SignaturePolicy {
+    /// Signatures are verified if present, and unsigned packages of indices declaring trusted keys are warned about
+    #[default]
+    Warn,
+    /// Packages must be signed by a key trusted by their index
+    Require,
+    /// Signatures are not verified
+    Ignore,
+}
+
 impl Manifest {
+    /// The name of the index used by pesde and Wally dependencies which don't specify one
+    pub fn default_index_name(&self) -> &str {
+        self.default_index.as_deref().unwrap_or(DEFAULT_INDEX_NAME)
+    }
+
     /// Get all dependencies from the manifest
     #[instrument(skip(self), ret(level = "trace"), level = "debug")]
     pub fn all_dependencies(
@@ -130,6 +255,38 @@ impl Manifest {
 
         Ok(all_deps)
     }
+
+    /// Returns whether the target is a built-in target or a custom target declared in this
+    /// manifest
+    pub fn is_target_declared(&self, target: &TargetKind) -> bool {
+        match target {
+            TargetKind::Custom(name) => self.custom_targets.contains_key(name),
+            _ => true,
+        }
+    }
+
+    /// Returns the first custom target used by this manifest which isn't declared in its
+    /// `custom_targets`
+    pub fn undeclared_custom_target(&self) -> Option<TargetKind> {
+        let target = self.target.kind();
+
+        std::iter::once(&target)
+            .chain(
+                self.custom_targets
+                    .values()
+                    .flat_map(|config| &config.compatible_with),
+            )
+            .chain(
+                self.dependencies
+                    .values()
+                    .chain(self.peer_dependencies.values())
+                    .chain(self.dev_dependencies.values())
+                    .chain(self.overrides.values())
+                    .filter_map(DependencySpecifiers::target),
+            )
+            .find(|target| !self.is_target_declared(target))
+            .cloned()
+    }
 }
 
 /// Errors that can occur when interacting with manifests
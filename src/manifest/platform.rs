@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// A condition on the platform pesde is running on
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PlatformCondition {
+    /// The operating system this applies to, as in Rust's `std::env::consts::OS`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_os: Option<String>,
+    /// The architecture this applies to, as in Rust's `std::env::consts::ARCH`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_arch: Option<String>,
+}
+
+impl PlatformCondition {
+    /// Returns whether this condition has no requirements
+    pub fn is_empty(&self) -> bool {
+        self.target_os.is_none() && self.target_arch.is_none()
+    }
+
+    /// Returns whether the current platform satisfies this condition
+    pub fn matches_host(&self) -> bool {
+        self.target_os
+            .as_deref()
+            .is_none_or(|os| os == std::env::consts::OS)
+            && self
+                .target_arch
+                .as_deref()
+                .is_none_or(|arch| arch == std::env::consts::ARCH)
+    }
+}
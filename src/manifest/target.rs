@@ -1,3 +1,4 @@
+use crate::manifest::platform::PlatformCondition;
 use relative_path::RelativePathBuf;
 use serde::{Deserialize, Serialize};
 use serde_with::{DeserializeFromStr, SerializeDisplay};
@@ -9,7 +10,7 @@ use std::{
 
 /// A kind of target
 #[derive(
-    SerializeDisplay, DeserializeFromStr, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord,
+    SerializeDisplay, DeserializeFromStr, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord,
 )]
 pub enum TargetKind {
     /// A Roblox target
@@ -20,6 +21,8 @@ pub enum TargetKind {
     Lune,
     /// A Luau target
     Luau,
+    /// A custom target, for runtimes not supported by pesde out of the box
+    Custom(String),
 }
 
 impl Display for TargetKind {
@@ -29,10 +32,13 @@ impl Display for TargetKind {
             TargetKind::RobloxServer => write!(f, "roblox_server"),
             TargetKind::Lune => write!(f, "lune"),
             TargetKind::Luau => write!(f, "luau"),
+            TargetKind::Custom(name) => write!(f, "{name}"),
         }
     }
 }
 
+/// Parses any well-formed target name. Whether a custom target is declared depends on the
+/// manifest, which is checked when it's read
 impl FromStr for TargetKind {
     type Err = errors::TargetKindFromStr;
 
@@ -42,13 +48,19 @@ impl FromStr for TargetKind {
             "roblox_server" => Ok(Self::RobloxServer),
             "lune" => Ok(Self::Lune),
             "luau" => Ok(Self::Luau),
+            t if !t.is_empty()
+                && t.chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') =>
+            {
+                Ok(Self::Custom(t.to_string()))
+            }
             t => Err(errors::TargetKindFromStr::Unknown(t.to_string())),
         }
     }
 }
 
 impl TargetKind {
-    /// All possible target variants
+    /// All built-in target variants
     pub const VARIANTS: &'static [TargetKind] = &[
         TargetKind::Roblox,
         TargetKind::RobloxServer,
@@ -73,6 +85,28 @@ impl TargetKind {
     pub fn is_roblox(&self) -> bool {
         matches!(self, TargetKind::Roblox | TargetKind::RobloxServer)
     }
+
+    /// Returns whether this target is a custom target
+    pub fn is_custom(&self) -> bool {
+        matches!(self, TargetKind::Custom(_))
+    }
+
+    /// Returns whether a project of this target can use packages of the dependency's target
+    /// without explicitly requesting it. Custom targets configure this through
+    /// [`crate::Project::is_target_compatible`]
+    pub fn is_compatible_with(&self, dependency: &Self) -> bool {
+        if self == dependency {
+            return true;
+        }
+
+        matches!(
+            (self, dependency),
+            // server-only code can use packages shared between the server and the client
+            (TargetKind::RobloxServer, TargetKind::Roblox)
+                // Lune can run packages written in plain Luau
+                | (TargetKind::Lune, TargetKind::Luau)
+        )
+    }
 }
 
 /// A target of a package
@@ -84,6 +118,9 @@ pub enum Target {
         /// The path to the lib export file
         #[serde(default, skip_serializing_if = "Option::is_none")]
         lib: Option<RelativePathBuf>,
+        /// The path to the file exporting the package's types
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        types: Option<RelativePathBuf>,
         /// The files to include in the sync tool's config
         #[serde(default)]
         build_files: BTreeSet<String>,
@@ -93,6 +130,9 @@ pub enum Target {
         /// The path to the lib export file
         #[serde(default, skip_serializing_if = "Option::is_none")]
         lib: Option<RelativePathBuf>,
+        /// The path to the file exporting the package's types
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        types: Option<RelativePathBuf>,
         /// The files to include in the sync tool's config
         #[serde(default)]
         build_files: BTreeSet<String>,
@@ -102,9 +142,15 @@ pub enum Target {
         /// The path to the lib export file
         #[serde(default, skip_serializing_if = "Option::is_none")]
         lib: Option<RelativePathBuf>,
+        /// The path to the file exporting the package's types
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        types: Option<RelativePathBuf>,
         /// The path to the bin export file
         #[serde(default, skip_serializing_if = "Option::is_none")]
         bin: Option<RelativePathBuf>,
+        /// The platform the bin export is restricted to
+        #[serde(default, skip_serializing_if = "PlatformCondition::is_empty")]
+        bin_platform: PlatformCondition,
         /// The exported scripts
         #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
         scripts: BTreeMap<String, RelativePathBuf>,
@@ -114,15 +160,64 @@ pub enum Target {
         /// The path to the lib export file
         #[serde(default, skip_serializing_if = "Option::is_none")]
         lib: Option<RelativePathBuf>,
+        /// The path to the file exporting the package's types
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        types: Option<RelativePathBuf>,
+        /// The path to the bin export file
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bin: Option<RelativePathBuf>,
+        /// The platform the bin export is restricted to
+        #[serde(default, skip_serializing_if = "PlatformCondition::is_empty")]
+        bin_platform: PlatformCondition,
+        /// The exported scripts
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        scripts: BTreeMap<String, RelativePathBuf>,
+    },
+    /// A custom target
+    Custom {
+        /// The name of the custom target
+        #[serde(deserialize_with = "deserialize_custom_target_name")]
+        name: String,
+        /// The path to the lib export file
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        lib: Option<RelativePathBuf>,
+        /// The path to the file exporting the package's types
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        types: Option<RelativePathBuf>,
         /// The path to the bin export file
         #[serde(default, skip_serializing_if = "Option::is_none")]
         bin: Option<RelativePathBuf>,
+        /// The platform the bin export is restricted to
+        #[serde(default, skip_serializing_if = "PlatformCondition::is_empty")]
+        bin_platform: PlatformCondition,
         /// The exported scripts
         #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
         scripts: BTreeMap<String, RelativePathBuf>,
     },
 }
 
+fn deserialize_custom_target_name<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<String, D::Error> {
+    let name = String::deserialize(deserializer)?;
+
+    match name.parse() {
+        Ok(TargetKind::Custom(name)) => Ok(name),
+        Ok(_) => Err(serde::de::Error::custom(format!(
+            "custom target name {name} is reserved for a built-in target"
+        ))),
+        Err(e) => Err(serde::de::Error::custom(e)),
+    }
+}
+
+/// The configuration of a custom target
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CustomTargetConfig {
+    /// The targets whose packages can be used by this target without explicitly requesting them
+    #[serde(default)]
+    pub compatible_with: BTreeSet<TargetKind>,
+}
+
 impl Target {
     /// Returns the kind of this target
     pub fn kind(&self) -> TargetKind {
@@ -131,6 +226,7 @@ impl Target {
             Target::RobloxServer { .. } => TargetKind::RobloxServer,
             Target::Lune { .. } => TargetKind::Lune,
             Target::Luau { .. } => TargetKind::Luau,
+            Target::Custom { name, .. } => TargetKind::Custom(name.clone()),
         }
     }
 
@@ -141,6 +237,18 @@ impl Target {
             Target::RobloxServer { lib, .. } => lib.as_ref(),
             Target::Lune { lib, .. } => lib.as_ref(),
             Target::Luau { lib, .. } => lib.as_ref(),
+            Target::Custom { lib, .. } => lib.as_ref(),
+        }
+    }
+
+    /// Returns the path to the types export file
+    pub fn types_path(&self) -> Option<&RelativePathBuf> {
+        match self {
+            Target::Roblox { types, .. } => types.as_ref(),
+            Target::RobloxServer { types, .. } => types.as_ref(),
+            Target::Lune { types, .. } => types.as_ref(),
+            Target::Luau { types, .. } => types.as_ref(),
+            Target::Custom { types, .. } => types.as_ref(),
         }
     }
 
@@ -151,9 +259,22 @@ impl Target {
             Target::RobloxServer { .. } => None,
             Target::Lune { bin, .. } => bin.as_ref(),
             Target::Luau { bin, .. } => bin.as_ref(),
+            Target::Custom { bin, .. } => bin.as_ref(),
         }
     }
 
+    /// Returns the path to the bin export file, if it is applicable to the current platform
+    pub fn host_bin_path(&self) -> Option<&RelativePathBuf> {
+        let bin_platform = match self {
+            Target::Lune { bin_platform, .. } => bin_platform,
+            Target::Luau { bin_platform, .. } => bin_platform,
+            Target::Custom { bin_platform, .. } => bin_platform,
+            _ => return None,
+        };
+
+        self.bin_path().filter(|_| bin_platform.matches_host())
+    }
+
     /// Returns the Roblox build files
     pub fn build_files(&self) -> Option<&BTreeSet<String>> {
         match self {
@@ -168,6 +289,7 @@ impl Target {
         match self {
             Target::Lune { scripts, .. } => Some(scripts),
             Target::Luau { scripts, .. } => Some(scripts),
+            Target::Custom { scripts, .. } => Some(scripts),
             _ => None,
         }
     }
@@ -221,7 +343,7 @@ pub mod errors {
     #[non_exhaustive]
     pub enum TargetKindFromStr {
         /// The target kind is unknown
-        #[error("unknown target kind {0}")]
+        #[error("unknown target kind {0}, custom target names may only contain lowercase letters, digits, and underscores")]
         Unknown(String),
     }
 }
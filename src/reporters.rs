@@ -0,0 +1,53 @@
+/// Reports the progress of downloading and linking a graph of packages
+pub trait DownloadsReporter: Send + Sync {
+    /// The reporter of a single package's download
+    type DownloadProgressReporter: DownloadProgressReporter + 'static;
+
+    /// Reports that a package started downloading, returning the reporter of its progress
+    fn report_download(&self, name: String) -> Self::DownloadProgressReporter;
+
+    /// Reports that the downloaded packages are being linked
+    fn report_linking(&self) {}
+}
+
+impl DownloadsReporter for () {
+    type DownloadProgressReporter = ();
+
+    fn report_download(&self, _name: String) -> Self::DownloadProgressReporter {}
+}
+
+/// Reports the progress of downloading a single package
+pub trait DownloadProgressReporter: Send + Sync {
+    /// Reports that the download started
+    fn report_start(&self) {}
+
+    /// Reports the amount of bytes downloaded so far, along with the total if it's known
+    fn report_progress(&self, _downloaded: u64, _total: Option<u64>) {}
+
+    /// Reports that the package's contents are being extracted
+    fn report_extract(&self) {}
+
+    /// Reports that the download finished
+    fn report_done(&self) {}
+}
+
+impl DownloadProgressReporter for () {}
+
+/// Reads the body of a response, reporting the progress of the download
+#[cfg(feature = "wally-compat")]
+pub(crate) async fn read_response(
+    mut response: reqwest::Response,
+    reporter: &dyn DownloadProgressReporter,
+) -> Result<Vec<u8>, reqwest::Error> {
+    let total = response.content_length();
+    let mut bytes = Vec::with_capacity(total.unwrap_or_default() as usize);
+
+    reporter.report_progress(0, total);
+
+    while let Some(chunk) = response.chunk().await? {
+        bytes.extend_from_slice(&chunk);
+        reporter.report_progress(bytes.len() as u64, total);
+    }
+
+    Ok(bytes)
+}
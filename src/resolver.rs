@@ -1,7 +1,8 @@
 use crate::{
-    lockfile::{DependencyGraph, DependencyGraphNode},
-    manifest::DependencyType,
+    lockfile::{prune_graph, DependencyGraph, DependencyGraphNode},
+    manifest::{target::TargetKind, DependencyType, DuplicateVersionsPolicy, Manifest},
     names::PackageNames,
+    refresh_sources,
     source::{
         pesde::PesdePackageSource,
         specifiers::DependencySpecifiers,
@@ -9,11 +10,26 @@ use crate::{
         version_id::VersionId,
         PackageSources,
     },
-    Project, DEFAULT_INDEX_NAME,
+    Project,
 };
-use std::collections::{btree_map::Entry, HashMap, HashSet, VecDeque};
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use tracing::{instrument, Instrument};
 
+/// Options for building a dependency graph
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResolveOptions {
+    /// Used by the `x` command. If true, specifier indices are expected to be URLs, and peer
+    /// dependencies aren't checked
+    pub is_published_package: bool,
+    /// Install unresolved peer dependencies as direct dependencies, in addition to the manifest's
+    /// `install_peers`
+    pub install_peers: bool,
+    /// Make unresolved peer dependencies errors instead of warnings, in addition to the
+    /// manifest's `strict_peers`. Takes effect after `install_peers`, so only peers which couldn't
+    /// be installed are errors
+    pub strict_peers: bool,
+}
+
 fn insert_node(
     graph: &mut DependencyGraph,
     name: PackageNames,
@@ -53,10 +69,613 @@ fn insert_node(
     }
 }
 
+/// The specifier without its version requirement, for comparing what else of specifiers changed.
+/// Returns `None` for specifiers without a version requirement
+fn without_version_req(specifier: &DependencySpecifiers) -> Option<DependencySpecifiers> {
+    let mut specifier = specifier.clone();
+    match &mut specifier {
+        DependencySpecifiers::Pesde(spec) => spec.version = semver::VersionReq::STAR,
+        #[cfg(feature = "wally-compat")]
+        DependencySpecifiers::Wally(spec) => spec.version = semver::VersionReq::STAR,
+        DependencySpecifiers::Local(spec) => spec.version = semver::VersionReq::STAR,
+        _ => return None,
+    }
+
+    Some(specifier)
+}
+
+/// Whether the package locked for a dependency can be used for its specifier, when resolved for
+/// the target. Specifiers without a version requirement must be the one the package was locked
+/// with, and others may only have had their version requirement changed to one the locked version
+/// still matches
+fn can_reuse_locked(
+    specifier: &DependencySpecifiers,
+    locked_specifier: &DependencySpecifiers,
+    target: &TargetKind,
+    locked_name: &PackageNames,
+    locked_version_id: &VersionId,
+) -> bool {
+    if specifier == locked_specifier {
+        return true;
+    }
+
+    let Some(req) = specifier.version_req() else {
+        return false;
+    };
+    if !req.matches(locked_version_id.version())
+        || without_version_req(specifier) != without_version_req(locked_specifier)
+    {
+        return false;
+    }
+
+    match specifier {
+        DependencySpecifiers::Pesde(spec) => {
+            *locked_name == PackageNames::Pesde(spec.name.clone())
+                && spec.target.as_ref().unwrap_or(target) == locked_version_id.target()
+        }
+        DependencySpecifiers::Local(spec) => {
+            *locked_name == PackageNames::Pesde(spec.name.clone())
+                && spec.target.as_ref().unwrap_or(target) == locked_version_id.target()
+        }
+        #[cfg(feature = "wally-compat")]
+        DependencySpecifiers::Wally(spec) => *locked_name == PackageNames::Wally(spec.name.clone()),
+        _ => false,
+    }
+}
+
+/// Copies the dependencies of a node of the previous graph into the graph as they were locked,
+/// along with their own dependencies
+fn insert_locked_dependencies(
+    graph: &mut DependencyGraph,
+    previous_graph: &DependencyGraph,
+    node: &DependencyGraphNode,
+    path: Vec<String>,
+) {
+    let mut queue = node
+        .dependencies
+        .iter()
+        .map(|(name, (version, dep_alias))| {
+            (
+                name,
+                version,
+                path.iter()
+                    .cloned()
+                    .chain(std::iter::once(dep_alias.to_string()))
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect::<VecDeque<_>>();
+
+    while let Some((dep_name, dep_version, path)) = queue.pop_front() {
+        let inner_span = tracing::info_span!("resolve dependency", path = path.join(">"));
+        let _inner_guard = inner_span.enter();
+        let Some(dep_node) = previous_graph
+            .get(dep_name)
+            .and_then(|v| v.get(dep_version))
+        else {
+            tracing::warn!("dependency {dep_name}@{dep_version} not found in previous graph");
+            continue;
+        };
+
+        // the dependencies of packages already in the graph are either copied already, or
+        // queued to be resolved
+        let is_new = graph
+            .get(dep_name)
+            .is_none_or(|versions| !versions.contains_key(dep_version));
+
+        tracing::debug!("resolved sub-dependency {dep_name}@{dep_version}");
+        insert_node(
+            graph,
+            dep_name.clone(),
+            dep_version.clone(),
+            dep_node.clone(),
+            false,
+        );
+
+        if !is_new {
+            continue;
+        }
+
+        dep_node
+            .dependencies
+            .iter()
+            .map(|(name, (version, alias))| {
+                (
+                    name,
+                    version,
+                    path.iter()
+                        .cloned()
+                        .chain(std::iter::once(alias.to_string()))
+                        .collect(),
+                )
+            })
+            .for_each(|dep| queue.push_back(dep));
+    }
+}
+
+fn apply_duplicate_versions_policy(
+    graph: &mut DependencyGraph,
+    policy: DuplicateVersionsPolicy,
+) -> Result<(), Box<errors::DependencyGraphError>> {
+    if policy == DuplicateVersionsPolicy::Allow {
+        return Ok(());
+    }
+
+    let mut replacements = HashMap::<(PackageNames, VersionId), VersionId>::new();
+
+    for (name, versions) in graph.iter() {
+        let mut by_target = BTreeMap::<TargetKind, Vec<&VersionId>>::new();
+        for version_id in versions.keys() {
+            by_target
+                .entry(version_id.target().clone())
+                .or_default()
+                .push(version_id);
+        }
+
+        for (target, version_ids) in by_target {
+            if version_ids.len() < 2 {
+                continue;
+            }
+
+            if policy == DuplicateVersionsPolicy::Error {
+                return Err(Box::new(errors::DependencyGraphError::DuplicateVersions(
+                    name.to_string(),
+                    target,
+                    version_ids
+                        .iter()
+                        .map(|v| v.version().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                )));
+            }
+
+            // a node can only be the direct dependency of one alias, so unifying the versions
+            // of several aliases would drop all but one of them from the project
+            let direct_aliases = version_ids
+                .iter()
+                .filter_map(|version_id| versions[*version_id].direct.as_ref())
+                .map(|(alias, _, _)| alias.as_str())
+                .collect::<Vec<_>>();
+            if direct_aliases.len() > 1 {
+                return Err(Box::new(errors::DependencyGraphError::CannotUnifyDirect(
+                    name.to_string(),
+                    target,
+                    direct_aliases.join(", "),
+                )));
+            }
+
+            // version ids are sorted, so the highest acceptable one is picked by searching from the end
+            let Some(unified) = version_ids.iter().rev().find(|candidate| {
+                version_ids.iter().all(|version_id| {
+                    version_id == *candidate
+                        || dependant_specifiers(graph, name, version_id).all(|specifier| {
+                            specifier
+                                .version_req()
+                                .is_some_and(|req| req.matches(candidate.version()))
+                        })
+                })
+            }) else {
+                return Err(Box::new(errors::DependencyGraphError::CannotUnify(
+                    name.to_string(),
+                    target,
+                    version_ids
+                        .iter()
+                        .map(|v| v.version().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                )));
+            };
+
+            for version_id in version_ids.iter().filter(|v| *v != unified) {
+                tracing::warn!("unifying {name}@{version_id} into {name}@{unified}");
+                replacements.insert((name.clone(), (*version_id).clone()), (*unified).clone());
+            }
+        }
+    }
+
+    for ((name, version_id), unified) in &replacements {
+        let versions = graph.get_mut(name).unwrap();
+        let removed = versions.remove(version_id).unwrap();
+        let kept = versions.get_mut(unified).unwrap();
+
+        if kept.direct.is_none() {
+            kept.direct = removed.direct;
+        }
+
+        if kept.resolved_ty == DependencyType::Peer {
+            kept.resolved_ty = removed.resolved_ty;
+        }
+
+        kept.is_peer &= removed.is_peer;
+    }
+
+    for versions in graph.values_mut() {
+        for node in versions.values_mut() {
+            for (dep_name, (dep_version_id, _)) in node.dependencies.iter_mut() {
+                if let Some(unified) = replacements.get(&(dep_name.clone(), dep_version_id.clone()))
+                {
+                    *dep_version_id = unified.clone();
+                }
+            }
+        }
+    }
+
+    // the dependencies of the removed versions may now be unused
+    prune_graph(graph, |node| node);
+
+    Ok(())
+}
+
+/// The specifiers which the package's version was resolved from, by the project and the packages depending on it
+fn dependant_specifiers<'a>(
+    graph: &'a DependencyGraph,
+    name: &'a PackageNames,
+    version_id: &'a VersionId,
+) -> impl Iterator<Item = &'a DependencySpecifiers> {
+    let node = &graph[name][version_id];
+
+    node.direct.iter().map(|(_, specifier, _)| specifier).chain(
+        graph
+            .values()
+            .flat_map(BTreeMap::values)
+            .filter_map(move |dependant| {
+                let (dep_version_id, alias) = dependant.dependencies.get(name)?;
+                (dep_version_id == version_id)
+                    .then(|| dependant.pkg_ref.dependencies().get(alias))
+                    .flatten()
+                    .map(|(specifier, _)| specifier)
+            }),
+    )
+}
+
+/// Finds a cycle in the graph, returning the packages forming it, with the first package repeated at the end
+fn find_cycle(graph: &DependencyGraph) -> Option<Vec<(&PackageNames, &VersionId)>> {
+    // packages which have been fully explored, and thus can't be part of a new cycle
+    let mut done = HashSet::new();
+
+    for (name, versions) in graph {
+        for version_id in versions.keys() {
+            if done.contains(&(name, version_id)) {
+                continue;
+            }
+
+            let mut path = vec![(name, version_id)];
+            let mut stack = vec![graph[name][version_id].dependencies.iter()];
+
+            while let Some(dependencies) = stack.last_mut() {
+                let Some((dep_name, (dep_version_id, _))) = dependencies.next() else {
+                    done.insert(path.pop().unwrap());
+                    stack.pop();
+                    continue;
+                };
+
+                let dep = (dep_name, dep_version_id);
+
+                if let Some(index) = path.iter().position(|p| *p == dep) {
+                    let mut cycle = path.split_off(index);
+                    cycle.push(dep);
+                    return Some(cycle);
+                }
+
+                if done.contains(&dep) {
+                    continue;
+                }
+
+                let Some(dep_node) = graph
+                    .get(dep_name)
+                    .and_then(|versions| versions.get(dep_version_id))
+                else {
+                    continue;
+                };
+
+                path.push(dep);
+                stack.push(dep_node.dependencies.iter());
+            }
+        }
+    }
+
+    None
+}
+
+/// The version a prerelease is released as, to compare prereleases with requirements, which
+/// only match the prereleases of the versions they name
+fn release_of(version: &semver::Version) -> semver::Version {
+    semver::Version::new(version.major, version.minor, version.patch)
+}
+
+/// Builds a hint listing the closest available versions of a package whose specifier matched no versions
+async fn version_suggestions(
+    project: &Project,
+    reqwest: &reqwest::Client,
+    source: &PackageSources,
+    specifier: &DependencySpecifiers,
+    target: TargetKind,
+    refreshed_sources: &mut HashSet<PackageSources>,
+) -> String {
+    let with_version = |version: semver::VersionReq, target_override: Option<TargetKind>| {
+        let mut specifier = specifier.clone();
+        match &mut specifier {
+            DependencySpecifiers::Pesde(spec) => {
+                spec.version = version;
+                if target_override.is_some() {
+                    spec.target = target_override;
+                }
+            }
+            DependencySpecifiers::Local(spec) => {
+                spec.version = version;
+                if target_override.is_some() {
+                    spec.target = target_override;
+                }
+            }
+            // wally packages are only published for one target
+            #[cfg(feature = "wally-compat")]
+            DependencySpecifiers::Wally(spec) if target_override.is_none() => {
+                spec.version = version;
+            }
+            _ => return None,
+        }
+        Some(specifier)
+    };
+    let specifier_target = match specifier {
+        DependencySpecifiers::Pesde(spec) => spec.target.clone(),
+        DependencySpecifiers::Local(spec) => spec.target.clone(),
+        _ => None,
+    };
+    let target = specifier_target.clone().unwrap_or(target);
+
+    let Some(star_specifier) = with_version(semver::VersionReq::STAR, None) else {
+        return "no other versions available".to_string();
+    };
+
+    let versions = match source
+        .resolve(
+            &star_specifier,
+            project,
+            reqwest,
+            target.clone(),
+            refreshed_sources,
+        )
+        .await
+    {
+        Ok((_, versions)) => versions,
+        Err(e) => {
+            tracing::debug!("failed to query versions for suggestions: {e}");
+            return "no suggestions available".to_string();
+        }
+    };
+
+    // `*` matches no prereleases, so they're read from the package's index file instead
+    let index_file = match (source, specifier) {
+        (PackageSources::Pesde(source), DependencySpecifiers::Pesde(spec)) => source
+            .read_index_file(&spec.name, project, reqwest)
+            .await
+            .map_err(|e| e.to_string()),
+        (PackageSources::Local(source), DependencySpecifiers::Local(spec)) => source
+            .read_index_file(&spec.name, project)
+            .await
+            .map_err(|e| e.to_string()),
+        _ => Ok(Default::default()),
+    };
+    let prereleases = match index_file {
+        Ok(index_file) => index_file
+            .into_iter()
+            .filter(|(version_id, entry)| {
+                !entry.yanked
+                    && !version_id.version().pre.is_empty()
+                    && match &specifier_target {
+                        Some(specifier_target) => specifier_target == version_id.target(),
+                        None => project.is_target_compatible(&target, version_id.target()),
+                    }
+            })
+            .map(|(version_id, _)| version_id.version().clone())
+            .collect::<BTreeSet<_>>(),
+        Err(e) => {
+            tracing::debug!("failed to query prereleases for suggestions: {e}");
+            BTreeSet::new()
+        }
+    };
+
+    if !versions.is_empty() || !prereleases.is_empty() {
+        let major_of = |version: &semver::Version| {
+            (
+                version.major,
+                if version.major == 0 { version.minor } else { 0 },
+            )
+        };
+
+        // suggest the highest version of each major, as the closest candidates to the requirement.
+        // Prereleases are only suggested this way for majors without releases
+        let mut by_major = BTreeMap::new();
+        for version_id in versions.into_keys() {
+            let version = version_id.version().clone();
+            by_major.insert(major_of(&version), version);
+        }
+        let released_majors = by_major.keys().copied().collect::<HashSet<_>>();
+        for version in &prereleases {
+            if !released_majors.contains(&major_of(version)) {
+                by_major.insert(major_of(version), version.clone());
+            }
+        }
+
+        // along with the prereleases which would satisfy the requirement once released
+        let matching_prereleases = prereleases.iter().filter(|version| {
+            specifier
+                .version_req()
+                .is_some_and(|req| req.matches(&release_of(version)))
+        });
+
+        return format!(
+            "available: {}",
+            by_major
+                .into_values()
+                .rev()
+                .take(5)
+                .chain(matching_prereleases.rev().take(5).cloned())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    let mut other_targets = vec![];
+    for other_target in project.target_kinds().iter().filter(|t| **t != target) {
+        let Some(star_specifier) =
+            with_version(semver::VersionReq::STAR, Some(other_target.clone()))
+        else {
+            continue;
+        };
+
+        if source
+            .resolve(
+                &star_specifier,
+                project,
+                reqwest,
+                other_target.clone(),
+                refreshed_sources,
+            )
+            .await
+            .is_ok_and(|(_, versions)| !versions.is_empty())
+        {
+            other_targets.push(other_target.to_string());
+        }
+    }
+
+    if other_targets.is_empty() {
+        "no versions are published".to_string()
+    } else {
+        format!(
+            "no versions published for target {target}, only for: {}",
+            other_targets.join(", ")
+        )
+    }
+}
+
 impl Project {
+    /// The source a specifier is resolved from, along with the name of the index it's looked up
+    /// in. Top-level specifiers refer to the manifest's indices by name, while specifiers of
+    /// published packages store their index's URL
+    fn specifier_source(
+        &self,
+        manifest: &Manifest,
+        specifier: &DependencySpecifiers,
+        top_level: bool,
+    ) -> Result<(PackageSources, Option<String>), Box<errors::DependencyGraphError>> {
+        Ok(match specifier {
+            DependencySpecifiers::Pesde(specifier) => {
+                let (index_url, index_name) = if top_level {
+                    let name = specifier
+                        .index
+                        .as_deref()
+                        .unwrap_or(manifest.default_index_name());
+
+                    let index_url = self.index_url(&manifest.indices, name).ok_or(
+                        errors::DependencyGraphError::IndexNotFound(name.to_string()),
+                    )?;
+
+                    (index_url, name.to_string())
+                } else {
+                    let index_url = specifier.index.clone().unwrap();
+
+                    (
+                        index_url
+                            .as_str()
+                            .try_into()
+                            // specifiers in indices store the index url in this field
+                            .unwrap(),
+                        index_url,
+                    )
+                };
+
+                (
+                    PackageSources::Pesde(PesdePackageSource::new(index_url)),
+                    Some(index_name),
+                )
+            }
+            #[cfg(feature = "wally-compat")]
+            DependencySpecifiers::Wally(specifier) => {
+                let (index_url, index_name) = if top_level {
+                    let name = specifier
+                        .index
+                        .as_deref()
+                        .unwrap_or(manifest.default_index_name());
+
+                    let index_url = manifest
+                        .wally_indices
+                        .get(name)
+                        .ok_or(errors::DependencyGraphError::WallyIndexNotFound(
+                            name.to_string(),
+                        ))?
+                        .clone();
+
+                    (index_url, name.to_string())
+                } else {
+                    let index_url = specifier.index.clone().unwrap();
+
+                    (
+                        index_url
+                            .as_str()
+                            .try_into()
+                            // specifiers in indices store the index url in this field
+                            .unwrap(),
+                        index_url,
+                    )
+                };
+
+                (
+                    PackageSources::Wally(crate::source::wally::WallyPackageSource::new(index_url)),
+                    Some(index_name),
+                )
+            }
+            DependencySpecifiers::Git(specifier) => (
+                PackageSources::Git(crate::source::git::GitPackageSource::new(
+                    specifier.repo.clone(),
+                )),
+                None,
+            ),
+            DependencySpecifiers::Workspace(_) => (
+                PackageSources::Workspace(crate::source::workspace::WorkspacePackageSource),
+                None,
+            ),
+            // registries of transitive dependencies are made relative to the project when resolved
+            DependencySpecifiers::Local(specifier) => (
+                PackageSources::Local(crate::source::local::LocalPackageSource::new(
+                    self.package_dir().join(&specifier.registry),
+                )),
+                None,
+            ),
+            DependencySpecifiers::Custom(specifier) => (
+                PackageSources::Custom(crate::source::custom::CustomPackageSource::new(
+                    specifier.source.clone(),
+                )),
+                None,
+            ),
+        })
+    }
+
+    /// The sources of the manifest's dependencies and overrides, which can be refreshed up front
+    /// before building the dependency graph
+    pub fn manifest_sources(
+        &self,
+        manifest: &Manifest,
+    ) -> Result<HashSet<PackageSources>, Box<errors::DependencyGraphError>> {
+        manifest
+            .all_dependencies()
+            .map_err(|e| Box::new(e.into()))?
+            .into_values()
+            .map(|(spec, _)| spec)
+            .chain(manifest.overrides.values().cloned())
+            .map(|spec| {
+                self.specifier_source(manifest, &spec, true)
+                    .map(|(source, _)| source)
+            })
+            .collect()
+    }
+
     /// Create a dependency graph from the project's manifest
     #[instrument(
-        skip(self, previous_graph, refreshed_sources),
+        skip(self, previous_graph, refreshed_sources, reqwest),
         ret(level = "trace"),
         level = "debug"
     )]
@@ -64,9 +683,15 @@ impl Project {
         &self,
         previous_graph: Option<&DependencyGraph>,
         refreshed_sources: &mut HashSet<PackageSources>,
-        // used by `x` command - if true, specifier indices are expected to be URLs. will not do peer dependency checks
-        is_published_package: bool,
+        reqwest: &reqwest::Client,
+        options: ResolveOptions,
     ) -> Result<DependencyGraph, Box<errors::DependencyGraphError>> {
+        let ResolveOptions {
+            is_published_package,
+            install_peers,
+            strict_peers,
+        } = options;
+
         let manifest = self
             .deser_manifest()
             .await
@@ -117,55 +742,12 @@ impl Project {
                         true,
                     );
 
-                    let mut queue = node
-                        .dependencies
-                        .iter()
-                        .map(|(name, (version, dep_alias))| {
-                            (
-                                name,
-                                version,
-                                vec![alias.to_string(), dep_alias.to_string()],
-                            )
-                        })
-                        .collect::<VecDeque<_>>();
-
-                    while let Some((dep_name, dep_version, path)) = queue.pop_front() {
-                        let inner_span =
-                            tracing::info_span!("resolve dependency", path = path.join(">"));
-                        let _inner_guard = inner_span.enter();
-                        if let Some(dep_node) = previous_graph
-                            .get(dep_name)
-                            .and_then(|v| v.get(dep_version))
-                        {
-                            tracing::debug!("resolved sub-dependency {dep_name}@{dep_version}");
-                            insert_node(
-                                &mut graph,
-                                dep_name.clone(),
-                                dep_version.clone(),
-                                dep_node.clone(),
-                                false,
-                            );
-
-                            dep_node
-                                .dependencies
-                                .iter()
-                                .map(|(name, (version, alias))| {
-                                    (
-                                        name,
-                                        version,
-                                        path.iter()
-                                            .cloned()
-                                            .chain(std::iter::once(alias.to_string()))
-                                            .collect(),
-                                    )
-                                })
-                                .for_each(|dep| queue.push_back(dep));
-                        } else {
-                            tracing::warn!(
-                                "dependency {dep_name}@{dep_version} not found in previous graph"
-                            );
-                        }
-                    }
+                    insert_locked_dependencies(
+                        &mut graph,
+                        previous_graph,
+                        node,
+                        vec![alias.to_string()],
+                    );
                 }
             }
         }
@@ -184,76 +766,118 @@ impl Project {
             })
             .collect::<VecDeque<_>>();
 
+        // refresh the sources of the top-level specifiers concurrently, instead of one by one
+        // as they're resolved
+        let sources = queue
+            .iter()
+            .map(|(specifier, ..)| {
+                self.specifier_source(&manifest, specifier, !is_published_package)
+                    .map(|(source, _)| source)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        refresh_sources(self, reqwest, sources.into_iter(), refreshed_sources)
+            .await
+            .map_err(|e| Box::new((*e).into()))?;
+
         while let Some((specifier, ty, dependant, path, overridden, target)) = queue.pop_front() {
             async {
                 let alias = path.last().unwrap().clone();
                 let depth = path.len() - 1;
 
-                tracing::debug!("resolving {specifier} ({ty:?})");
-                let source = match &specifier {
-                    DependencySpecifiers::Pesde(specifier) => {
-                        let index_url = if !is_published_package && (depth == 0 || overridden) {
-                            let index_name = specifier.index.as_deref().unwrap_or(DEFAULT_INDEX_NAME);
-
-                            manifest
-                                .indices
-                                .get(index_name)
-                                .ok_or(errors::DependencyGraphError::IndexNotFound(
-                                    index_name.to_string(),
-                                ))?
-                                .clone()
-                        } else {
-                            let index_url = specifier.index.clone().unwrap();
-
-                            index_url
-                                .clone()
-                                .try_into()
-                                // specifiers in indices store the index url in this field
-                                .unwrap()
-                        };
-
-                        PackageSources::Pesde(PesdePackageSource::new(index_url))
-                    }
-                    #[cfg(feature = "wally-compat")]
-                    DependencySpecifiers::Wally(specifier) => {
-                        let index_url = if !is_published_package && (depth == 0 || overridden) {
-                            let index_name = specifier.index.as_deref().unwrap_or(DEFAULT_INDEX_NAME);
-
-                            manifest
-                                .wally_indices
-                                .get(index_name)
-                                .ok_or(errors::DependencyGraphError::WallyIndexNotFound(
-                                    index_name.to_string(),
-                                ))?
-                                .clone()
-                        } else {
-                            let index_url = specifier.index.clone().unwrap();
-
-                            index_url
-                                .clone()
-                                .try_into()
-                                // specifiers in indices store the index url in this field
-                                .unwrap()
-                        };
-
-                        PackageSources::Wally(crate::source::wally::WallyPackageSource::new(index_url))
+                // packages resolved to the version they were locked at keep the dependencies
+                // they were locked with, so that only the subtrees of changed specifiers are
+                // resolved again
+                if let Some((locked_name, locked_version_id, locked_node)) = dependant
+                    .as_ref()
+                    .filter(|_| {
+                        !overridden && !matches!(specifier, DependencySpecifiers::Workspace(_))
+                    })
+                    .zip(previous_graph)
+                    .and_then(|((dependant_name, dependant_version_id), previous_graph)| {
+                        let locked_dependant =
+                            previous_graph.get(dependant_name)?.get(dependant_version_id)?;
+                        let (locked_name, (locked_version_id, _)) = locked_dependant
+                            .dependencies
+                            .iter()
+                            .find(|(_, (_, dep_alias))| *dep_alias == alias)?;
+                        let (locked_specifier, _) =
+                            locked_dependant.pkg_ref.dependencies().get(&alias)?;
+                        if !can_reuse_locked(
+                            &specifier,
+                            locked_specifier,
+                            &target,
+                            locked_name,
+                            locked_version_id,
+                        ) {
+                            return None;
+                        }
+                        let locked_node =
+                            previous_graph.get(locked_name)?.get(locked_version_id)?;
+
+                        Some((locked_name, locked_version_id, locked_node))
+                    })
+                {
+                    let (dependant_name, dependant_version_id) = dependant.as_ref().unwrap();
+                    tracing::debug!(
+                        "resolved {locked_name}@{locked_version_id} from old dependency graph"
+                    );
+
+                    if let Some(node) = graph
+                        .get_mut(dependant_name)
+                        .and_then(|versions| versions.get_mut(dependant_version_id))
+                    {
+                        node.dependencies.insert(
+                            locked_name.clone(),
+                            (locked_version_id.clone(), alias.clone()),
+                        );
                     }
-                    DependencySpecifiers::Git(specifier) => PackageSources::Git(
-                        crate::source::git::GitPackageSource::new(specifier.repo.clone()),
-                    ),
-                    DependencySpecifiers::Workspace(_) => {
-                        PackageSources::Workspace(crate::source::workspace::WorkspacePackageSource)
+
+                    let is_new = graph
+                        .get(locked_name)
+                        .is_none_or(|versions| !versions.contains_key(locked_version_id));
+                    insert_node(
+                        &mut graph,
+                        locked_name.clone(),
+                        locked_version_id.clone(),
+                        locked_node.clone(),
+                        false,
+                    );
+                    if is_new {
+                        insert_locked_dependencies(
+                            &mut graph,
+                            previous_graph.unwrap(),
+                            locked_node,
+                            path.clone(),
+                        );
                     }
-                };
+
+                    return Ok(());
+                }
+
+                tracing::debug!("resolving {specifier} ({ty:?})");
+                // the index the package is looked up in, for error messages
+                let (source, index_name) = self.specifier_source(
+                    &manifest,
+                    &specifier,
+                    !is_published_package && (depth == 0 || overridden),
+                )?;
 
                 if refreshed_sources.insert(source.clone()) {
-                    source.refresh(self).await.map_err(|e| Box::new(e.into()))?;
+                    source.refresh(self, reqwest).await.map_err(|e| Box::new(e.into()))?;
                 }
 
                 let (name, resolved) = source
-                    .resolve(&specifier, self, target, refreshed_sources)
+                    .resolve(&specifier, self, reqwest, target.clone(), refreshed_sources)
                     .await
-                    .map_err(|e| Box::new(e.into()))?;
+                    .map_err(|e| {
+                        Box::new(match &index_name {
+                            Some(index_name) => errors::DependencyGraphError::ResolveInIndex(
+                                index_name.clone(),
+                                e,
+                            ),
+                            None => e.into(),
+                        })
+                    })?;
 
                 let Some(target_version_id) = graph
                     .get(&name)
@@ -264,11 +888,28 @@ impl Project {
                             .filter(|ver| resolved.contains_key(ver))
                             .max()
                     })
+                    // prefer versions from the previous graph, so that only changed specifiers
+                    // have their subtrees moved to newer versions
+                    .or_else(|| {
+                        previous_graph
+                            .and_then(|previous_graph| previous_graph.get(&name))
+                            .and_then(|versions| {
+                                versions
+                                    .keys()
+                                    .filter(|ver| resolved.contains_key(ver))
+                                    .max()
+                            })
+                    })
                     .or_else(|| resolved.last_key_value().map(|(ver, _)| ver))
                     .cloned()
                 else {
+                    let suggestions =
+                        version_suggestions(self, reqwest, &source, &specifier, target.clone(), refreshed_sources)
+                            .await;
+
                     return Err(Box::new(errors::DependencyGraphError::NoMatchingVersion(
                         format!("{specifier} ({target})"),
+                        suggestions,
                     )));
                 };
 
@@ -393,7 +1034,7 @@ impl Project {
                             .chain(std::iter::once(dependency_alias))
                             .collect(),
                         overridden.is_some(),
-                        *target_version_id.target(),
+                        target_version_id.target().clone(),
                     ));
                 }
 
@@ -403,6 +1044,22 @@ impl Project {
                 .await?;
         }
 
+        apply_duplicate_versions_policy(&mut graph, manifest.duplicate_versions)?;
+
+        if let Some(cycle) = find_cycle(&graph) {
+            return Err(Box::new(errors::DependencyGraphError::Cycle(
+                cycle
+                    .into_iter()
+                    .map(|(name, version_id)| format!("{name}@{version_id}"))
+                    .collect::<Vec<_>>()
+                    .join(" > "),
+            )));
+        }
+
+        let install_peers = install_peers || manifest.install_peers;
+        let strict_peers = !is_published_package && (strict_peers || manifest.strict_peers);
+        let mut unresolved_peers = vec![];
+
         for (name, versions) in &mut graph {
             for (version_id, node) in versions {
                 if node.is_peer && node.direct.is_none() {
@@ -410,17 +1067,100 @@ impl Project {
                 }
 
                 if node.resolved_ty == DependencyType::Peer {
-                    tracing::warn!("peer dependency {name}@{version_id} was not resolved");
+                    unresolved_peers.push((name.clone(), version_id.clone()));
                 }
             }
         }
 
+        if install_peers {
+            let mut direct_aliases = graph
+                .values()
+                .flat_map(BTreeMap::values)
+                .filter_map(|node| node.direct.as_ref())
+                .map(|(alias, _, _)| alias.clone())
+                .collect::<HashSet<_>>();
+
+            unresolved_peers.retain(|(name, version_id)| {
+                // the peer is installed as a direct dependency, using the alias and specifier of the first package depending on it
+                let Some((alias, specifier)) =
+                    graph
+                        .values()
+                        .flat_map(BTreeMap::values)
+                        .find_map(|dependant| {
+                            let (dep_version_id, alias) = dependant.dependencies.get(name)?;
+                            if dep_version_id != version_id {
+                                return None;
+                            }
+
+                            let (specifier, _) = dependant.pkg_ref.dependencies().get(alias)?;
+                            Some((alias.clone(), specifier.clone()))
+                        })
+                else {
+                    return true;
+                };
+
+                if !direct_aliases.insert(alias.clone()) {
+                    tracing::warn!(
+                        "cannot install peer dependency {name}@{version_id}, alias {alias} is already used by a direct dependency"
+                    );
+                    return true;
+                }
+
+                tracing::info!("installing unresolved peer dependency {name}@{version_id} as {alias}");
+                let node = graph
+                    .get_mut(name)
+                    .and_then(|versions| versions.get_mut(version_id))
+                    .unwrap();
+                node.direct = Some((alias, specifier, DependencyType::Standard));
+                node.resolved_ty = DependencyType::Standard;
+
+                false
+            });
+        }
+
+        if !strict_peers {
+            for (name, version_id) in unresolved_peers.drain(..) {
+                tracing::warn!("peer dependency {name}@{version_id} was not resolved");
+            }
+        }
+
+        if !unresolved_peers.is_empty() {
+            return Err(Box::new(errors::DependencyGraphError::UnresolvedPeers(
+                unresolved_peers
+                    .into_iter()
+                    .map(|(name, version_id)| {
+                        let dependants = graph
+                            .iter()
+                            .flat_map(|(dependant_name, versions)| {
+                                versions.iter().map(move |(dependant_version_id, node)| {
+                                    (dependant_name, dependant_version_id, node)
+                                })
+                            })
+                            .filter(|(_, _, node)| {
+                                node.dependencies
+                                    .get(&name)
+                                    .is_some_and(|(v, _)| *v == version_id)
+                            })
+                            .map(|(dependant_name, dependant_version_id, _)| {
+                                format!("{dependant_name}@{dependant_version_id}")
+                            })
+                            .collect::<Vec<_>>()
+                            .join(", ");
+
+                        format!("{name}@{version_id} (required by {dependants})")
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )));
+        }
+
         Ok(graph)
     }
 }
 
 /// Errors that can occur when resolving dependencies
 pub mod errors {
+    use crate::manifest::target::TargetKind;
     use thiserror::Error;
 
     /// Errors that can occur when creating a dependency graph
@@ -452,8 +1192,516 @@ pub mod errors {
         #[error("error resolving package")]
         Resolve(#[from] crate::source::errors::ResolveError),
 
+        /// An error occurred while resolving a package in an index
+        #[error("error resolving package in index `{0}`")]
+        ResolveInIndex(String, #[source] crate::source::errors::ResolveError),
+
         /// No matching version was found for a specifier
-        #[error("no matching version found for {0}")]
-        NoMatchingVersion(String),
+        #[error("no matching version found for {0}; {1}")]
+        NoMatchingVersion(String, String),
+
+        /// Multiple versions of a package were resolved for one target while this is disallowed
+        #[error("multiple versions of {0} resolved for target {1}: {2}")]
+        DuplicateVersions(String, TargetKind, String),
+
+        /// Multiple versions of a package were resolved for one target, and none of them is accepted by all of its dependants
+        #[error("multiple versions of {0} resolved for target {1} ({2}), none of which satisfies every dependant")]
+        CannotUnify(String, TargetKind, String),
+
+        /// Multiple versions of a package were resolved for one target as direct dependencies of different aliases, which can't be unified
+        #[error("multiple versions of {0} resolved for target {1} as direct dependencies ({2}), which can't be unified")]
+        CannotUnifyDirect(String, TargetKind, String),
+
+        /// A dependency cycle was found
+        #[error("dependency cycle found: {0}")]
+        Cycle(String),
+
+        /// Peer dependencies were not resolved while in strict mode
+        #[error("unresolved peer dependencies: {0}")]
+        UnresolvedPeers(String),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::test_graph::{graph, name, node, version_id};
+
+    fn cycle_names(graph: &DependencyGraph) -> Option<Vec<String>> {
+        find_cycle(graph).map(|cycle| {
+            cycle
+                .into_iter()
+                .map(|(name, _)| name.as_str().1.to_string())
+                .collect()
+        })
+    }
+
+    #[test]
+    fn direct_cycle() {
+        let graph = graph([
+            node("acme/a", "1.0.0", false, &[("acme/b", "1.0.0")]),
+            node("acme/b", "1.0.0", false, &[("acme/a", "1.0.0")]),
+        ]);
+
+        assert_eq!(cycle_names(&graph).unwrap(), ["a", "b", "a"]);
+    }
+
+    #[test]
+    fn self_cycle() {
+        let graph = graph([node("acme/a", "1.0.0", false, &[("acme/a", "1.0.0")])]);
+
+        assert_eq!(cycle_names(&graph).unwrap(), ["a", "a"]);
+    }
+
+    #[test]
+    fn indirect_cycle() {
+        let graph = graph([
+            node("acme/a", "1.0.0", false, &[("acme/b", "1.0.0")]),
+            node("acme/b", "1.0.0", false, &[("acme/c", "1.0.0")]),
+            node("acme/c", "1.0.0", false, &[("acme/d", "1.0.0")]),
+            node("acme/d", "1.0.0", false, &[("acme/b", "1.0.0")]),
+        ]);
+
+        assert_eq!(cycle_names(&graph).unwrap(), ["b", "c", "d", "b"]);
+    }
+
+    #[test]
+    fn acyclic() {
+        // a diamond, in which `d` is reached twice without forming a cycle
+        let graph = graph([
+            node(
+                "acme/a",
+                "1.0.0",
+                false,
+                &[("acme/b", "1.0.0"), ("acme/c", "1.0.0")],
+            ),
+            node("acme/b", "1.0.0", false, &[("acme/d", "1.0.0")]),
+            node("acme/c", "1.0.0", false, &[("acme/d", "1.0.0")]),
+            node("acme/d", "1.0.0", false, &[]),
+        ]);
+
+        assert_eq!(cycle_names(&graph), None);
+    }
+
+    fn versions(graph: &DependencyGraph, package: &str) -> Vec<String> {
+        graph[&PackageNames::Pesde(name(package))]
+            .keys()
+            .map(|version_id| version_id.version().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn unify_carries_direct_dependency_over() {
+        let mut graph = graph([
+            node("acme/app_dep", "1.0.0", true, &[("acme/foo", "1.1.0")]),
+            node("acme/foo", "1.0.0", true, &[]),
+            node("acme/foo", "1.1.0", false, &[]),
+        ]);
+
+        apply_duplicate_versions_policy(&mut graph, DuplicateVersionsPolicy::Unify).unwrap();
+        assert_eq!(versions(&graph, "acme/foo"), ["1.1.0"]);
+
+        // the removed version's alias is carried over to the version it was unified into
+        let foo = &graph[&PackageNames::Pesde(name("acme/foo"))][&version_id("1.1.0")];
+        assert_eq!(foo.direct.as_ref().unwrap().0, "foo");
+    }
+
+    #[test]
+    fn unify_refuses_direct_dependencies_of_different_aliases() {
+        let mut foo_next = node("acme/foo", "1.1.0", true, &[]);
+        foo_next.direct.as_mut().unwrap().0 = "foo_next".to_string();
+        let mut graph = graph([node("acme/foo", "1.0.0", true, &[]), foo_next]);
+
+        let err = apply_duplicate_versions_policy(&mut graph, DuplicateVersionsPolicy::Unify)
+            .unwrap_err();
+        assert!(matches!(
+            *err,
+            errors::DependencyGraphError::CannotUnifyDirect(_, _, ref aliases) if aliases == "foo, foo_next"
+        ));
+        // neither version was dropped
+        assert_eq!(versions(&graph, "acme/foo"), ["1.0.0", "1.1.0"]);
+    }
+
+    /// A project whose manifest has the dependencies, and a local registry next to it with the
+    /// index files, given by the names of their packages. Dependencies in the index files refer
+    /// to the registry as `.`
+    async fn local_project(
+        dependencies: &str,
+        index_files: &[(&str, &str)],
+    ) -> (tempfile::TempDir, Project) {
+        let root = tempfile::tempdir().unwrap();
+        let package_dir = root.path().join("project");
+        fs_err::tokio::create_dir_all(&package_dir).await.unwrap();
+        fs_err::tokio::write(
+            package_dir.join(crate::MANIFEST_FILE_NAME),
+            format!(
+                "name = \"acme/app\"\nversion = \"0.1.0\"\ntarget = {{ environment = \"luau\" }}\n\n{dependencies}"
+            ),
+        )
+        .await
+        .unwrap();
+
+        for (package, index_file) in index_files {
+            write_index_file(root.path(), package, index_file).await;
+        }
+
+        let project = Project::new(
+            &package_dir,
+            None::<std::path::PathBuf>,
+            root.path().join("data"),
+            root.path().join("cas"),
+            crate::AuthConfig::new(),
+        );
+
+        (root, project)
+    }
+
+    async fn write_index_file(root: &std::path::Path, package: &str, index_file: &str) {
+        let path = root
+            .join("registry")
+            .join(crate::source::local::INDEX_DIR)
+            .join(package);
+        fs_err::tokio::create_dir_all(path.parent().unwrap())
+            .await
+            .unwrap();
+        fs_err::tokio::write(path, index_file).await.unwrap();
+    }
+
+    async fn resolve(
+        project: &Project,
+        previous_graph: Option<&DependencyGraph>,
+        options: ResolveOptions,
+    ) -> Result<DependencyGraph, Box<errors::DependencyGraphError>> {
+        project
+            .dependency_graph(
+                previous_graph,
+                &mut HashSet::new(),
+                &reqwest::Client::new(),
+                options,
+            )
+            .await
+    }
+
+    async fn suggestions(dependencies: &str, index_file: &str) -> String {
+        let (_root, project) = local_project(dependencies, &[("acme/foo", index_file)]).await;
+
+        match *resolve(&project, None, ResolveOptions::default())
+            .await
+            .unwrap_err()
+        {
+            errors::DependencyGraphError::NoMatchingVersion(_, suggestions) => suggestions,
+            e => panic!("unexpected error: {e}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn suggests_releases_and_prereleases() {
+        let suggestions = suggestions(
+            r#"[dependencies]
+foo = { local = "acme/foo", version = "^2.0.0", registry = "../registry" }
+"#,
+            r#"
+["1.4.2 luau"]
+target = { environment = "luau" }
+
+["1.5.0-beta.1 luau"]
+target = { environment = "luau" }
+
+["2.1.0-rc.1 luau"]
+target = { environment = "luau" }
+
+["3.0.0-rc.1 luau"]
+target = { environment = "luau" }
+"#,
+        )
+        .await;
+
+        // the prerelease of 1.x isn't suggested over its release
+        assert_eq!(suggestions, "available: 1.4.2, 2.1.0-rc.1, 3.0.0-rc.1");
+    }
+
+    #[tokio::test]
+    async fn suggests_other_targets() {
+        let suggestions = suggestions(
+            r#"[dependencies]
+foo = { local = "acme/foo", version = "^1.0.0", registry = "../registry" }
+"#,
+            r#"
+["1.0.0 roblox"]
+target = { environment = "roblox" }
+"#,
+        )
+        .await;
+
+        assert_eq!(
+            suggestions,
+            "no versions published for target luau, only for: roblox"
+        );
+    }
+
+    fn dependency_of(graph: &DependencyGraph, package: &str, dependency: &str) -> String {
+        let (_, node) = graph[&PackageNames::Pesde(name(package))]
+            .iter()
+            .next()
+            .unwrap();
+        node.dependencies[&PackageNames::Pesde(name(dependency))]
+            .0
+            .version()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn re_resolves_only_changed_subtrees() {
+        let (root, project) = local_project(
+            r#"[dependencies]
+a = { local = "acme/a", version = "^1.0.0", registry = "../registry" }
+b = { local = "acme/b", version = "^1.0.0", registry = "../registry" }
+"#,
+            &[
+                (
+                    "acme/a",
+                    r#"
+["1.0.0 luau"]
+target = { environment = "luau" }
+
+["1.0.0 luau".dependencies]
+c = [{ local = "acme/c", version = "^1.0.0", registry = "." }, "standard"]
+"#,
+                ),
+                (
+                    "acme/b",
+                    r#"
+["1.0.0 luau"]
+target = { environment = "luau" }
+
+["1.0.0 luau".dependencies]
+d = [{ local = "acme/d", version = "^1.0.0", registry = "." }, "standard"]
+"#,
+                ),
+                (
+                    "acme/c",
+                    r#"
+["1.0.0 luau"]
+target = { environment = "luau" }
+"#,
+                ),
+                (
+                    "acme/d",
+                    r#"
+["1.0.0 luau"]
+target = { environment = "luau" }
+"#,
+                ),
+            ],
+        )
+        .await;
+
+        let locked = resolve(&project, None, ResolveOptions::default())
+            .await
+            .unwrap();
+
+        // newer versions are published, and `b`'s specifier is changed while still matching
+        // the version it was locked at. `d` can't be resolved anymore, so it can only be taken
+        // from the previous graph
+        write_index_file(
+            root.path(),
+            "acme/c",
+            r#"
+["1.0.0 luau"]
+target = { environment = "luau" }
+
+["1.1.0 luau"]
+target = { environment = "luau" }
+"#,
+        )
+        .await;
+        fs_err::tokio::remove_file(
+            root.path()
+                .join("registry")
+                .join(crate::source::local::INDEX_DIR)
+                .join("acme/d"),
+        )
+        .await
+        .unwrap();
+        fs_err::tokio::write(
+            project.package_dir().join(crate::MANIFEST_FILE_NAME),
+            r#"name = "acme/app"
+version = "0.1.0"
+target = { environment = "luau" }
+
+[dependencies]
+a = { local = "acme/a", version = "^1.0.0", registry = "../registry" }
+b = { local = "acme/b", version = "=1.0.0", registry = "../registry" }
+"#,
+        )
+        .await
+        .unwrap();
+
+        let graph = resolve(&project, Some(&locked), ResolveOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(dependency_of(&graph, "acme/a", "acme/c"), "1.0.0");
+        assert_eq!(dependency_of(&graph, "acme/b", "acme/d"), "1.0.0");
+        assert_eq!(versions(&graph, "acme/c"), ["1.0.0"]);
+        assert_eq!(
+            graph[&PackageNames::Pesde(name("acme/b"))]
+                .values()
+                .next()
+                .unwrap()
+                .direct
+                .as_ref()
+                .unwrap()
+                .1
+                .to_string(),
+            "acme/b@local:=1.0.0"
+        );
+    }
+
+    #[tokio::test]
+    async fn re_resolves_dependencies_whose_package_changed() {
+        let (root, project) = local_project(
+            r#"[dependencies]
+a = { local = "acme/a", version = "^1.0.0", registry = "../registry" }
+"#,
+            &[
+                (
+                    "acme/a",
+                    r#"
+["1.0.0 luau"]
+target = { environment = "luau" }
+
+["1.0.0 luau".dependencies]
+c = [{ local = "acme/c", version = "^1.0.0", registry = "." }, "standard"]
+"#,
+                ),
+                (
+                    "acme/c",
+                    r#"
+["1.0.0 luau"]
+target = { environment = "luau" }
+"#,
+                ),
+                (
+                    "acme/e",
+                    r#"
+["1.0.0 luau"]
+target = { environment = "luau" }
+"#,
+                ),
+            ],
+        )
+        .await;
+
+        let locked = resolve(&project, None, ResolveOptions::default())
+            .await
+            .unwrap();
+
+        // the alias now points to another package, whose locked version would still match
+        write_index_file(
+            root.path(),
+            "acme/a",
+            r#"
+["1.0.0 luau"]
+target = { environment = "luau" }
+
+["1.0.0 luau".dependencies]
+c = [{ local = "acme/e", version = "^1.0.0", registry = "." }, "standard"]
+"#,
+        )
+        .await;
+        // changed so that `a` is resolved again rather than copied from the previous graph
+        fs_err::tokio::write(
+            project.package_dir().join(crate::MANIFEST_FILE_NAME),
+            r#"name = "acme/app"
+version = "0.1.0"
+target = { environment = "luau" }
+
+[dependencies]
+a = { local = "acme/a", version = "=1.0.0", registry = "../registry" }
+"#,
+        )
+        .await
+        .unwrap();
+
+        let graph = resolve(&project, Some(&locked), ResolveOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(dependency_of(&graph, "acme/a", "acme/e"), "1.0.0");
+        assert!(!graph.contains_key(&PackageNames::Pesde(name("acme/c"))));
+    }
+
+    const PEER_INDEX_FILES: [(&str, &str); 3] = [
+        (
+            "acme/a",
+            r#"
+["1.0.0 luau"]
+target = { environment = "luau" }
+
+["1.0.0 luau".dependencies]
+p = [{ local = "acme/p", version = "^1.0.0", registry = "." }, "peer"]
+"#,
+        ),
+        (
+            "acme/p",
+            r#"
+["1.0.0 luau"]
+target = { environment = "luau" }
+"#,
+        ),
+        (
+            "acme/other",
+            r#"
+["1.0.0 luau"]
+target = { environment = "luau" }
+"#,
+        ),
+    ];
+
+    const STRICT_INSTALL_PEERS: ResolveOptions = ResolveOptions {
+        is_published_package: false,
+        install_peers: true,
+        strict_peers: true,
+    };
+
+    #[tokio::test]
+    async fn strict_peers_accepts_installed_peers() {
+        let (_root, project) = local_project(
+            r#"[dependencies]
+a = { local = "acme/a", version = "^1.0.0", registry = "../registry" }
+"#,
+            &PEER_INDEX_FILES,
+        )
+        .await;
+
+        let graph = resolve(&project, None, STRICT_INSTALL_PEERS).await.unwrap();
+        let (_, p) = graph[&PackageNames::Pesde(name("acme/p"))]
+            .iter()
+            .next()
+            .unwrap();
+        assert_eq!(p.direct.as_ref().unwrap().0, "p");
+        assert_eq!(p.resolved_ty, DependencyType::Standard);
+    }
+
+    #[tokio::test]
+    async fn strict_peers_rejects_peers_which_cant_be_installed() {
+        // the peer's alias is already used, so it can't be installed
+        let (_root, project) = local_project(
+            r#"[dependencies]
+a = { local = "acme/a", version = "^1.0.0", registry = "../registry" }
+p = { local = "acme/other", version = "^1.0.0", registry = "../registry" }
+"#,
+            &PEER_INDEX_FILES,
+        )
+        .await;
+
+        match *resolve(&project, None, STRICT_INSTALL_PEERS)
+            .await
+            .unwrap_err()
+        {
+            errors::DependencyGraphError::UnresolvedPeers(peers) => {
+                assert_eq!(peers, "acme/p@1.0.0 luau (required by acme/a@1.0.0 luau)")
+            }
+            e => panic!("unexpected error: {e}"),
+        }
     }
 }
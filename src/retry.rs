@@ -0,0 +1,111 @@
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::{fmt::Display, future::Future, time::Duration};
+
+/// How network operations are retried when they fail transiently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum amount of times an operation is retried after its first attempt
+    pub max_retries: u32,
+    /// The delay before the first retry, which doubles with every following retry
+    pub initial_delay: Duration,
+    /// The maximum delay between two attempts
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy which never retries operations
+    pub fn never() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// The delay before the given retry (starting at zero). The delay grows exponentially, and a
+    /// random part of it is used so that clients failing at the same time don't retry in lockstep
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.initial_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay)
+            .mul_f64(fastrand::f64())
+    }
+
+    /// Runs the operation, retrying it for as long as it fails with errors which are transient
+    pub async fn retry<T, E: Display, F: FnMut() -> Fut, Fut: Future<Output = Result<T, E>>>(
+        &self,
+        mut operation: F,
+        is_transient: impl Fn(&E) -> bool,
+    ) -> Result<T, E> {
+        let mut retry = 0;
+
+        loop {
+            match operation().await {
+                Err(e) if retry < self.max_retries && is_transient(&e) => {
+                    let delay = self.delay(retry);
+                    tracing::warn!("{e}, retrying in {}ms", delay.as_millis());
+                    tokio::time::sleep(delay).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Sends the request, retrying it if it fails transiently. Responses with a transient error
+    /// status are retried too, the last one being returned once the retries run out. Requests
+    /// whose bodies are streams can't be retried, and are only sent once
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, reqwest::Error> {
+        let mut retry = 0;
+
+        loop {
+            let Some(attempt) = request.try_clone() else {
+                return request.send().await;
+            };
+
+            let result = attempt.send().await;
+            let error = match &result {
+                Ok(response) if is_transient_status(response.status()) => {
+                    format!("server responded with {}", response.status())
+                }
+                Err(e) if is_transient(e) => e.to_string(),
+                _ => return result,
+            };
+
+            if retry >= self.max_retries {
+                return result;
+            }
+
+            let delay = self.delay(retry);
+            tracing::warn!("{error}, retrying in {}ms", delay.as_millis());
+            tokio::time::sleep(delay).await;
+            retry += 1;
+        }
+    }
+}
+
+/// Whether a response status indicates a failure which may not occur again
+pub fn is_transient_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Whether a request failed in a way which may not occur again, such as timeouts, dropped
+/// connections, or transient error statuses. Failures reading the response body are considered
+/// transient too, since reqwest reports connections dropped mid-body as decoding errors
+pub fn is_transient(e: &reqwest::Error) -> bool {
+    e.is_timeout()
+        || e.is_connect()
+        || e.is_request()
+        || e.is_body()
+        || e.is_decode()
+        || e.status().is_some_and(is_transient_status)
+}
@@ -0,0 +1,306 @@
+use crate::{
+    lockfile::Lockfile,
+    source::{
+        fs::{cas_path, store_in_cas, FSEntry, PackageFS},
+        git_index::GitBasedSource,
+        refs::PackageRefs,
+        traits::PackageRef,
+        PackageSources,
+    },
+    Project,
+};
+use async_compression::Level;
+use fs_err::tokio as fs;
+use futures::StreamExt;
+use std::{
+    collections::HashSet,
+    path::{Component, Path, PathBuf},
+};
+use tokio::io::AsyncWriteExt;
+use tracing::instrument;
+
+/// The directory of a snapshot containing files of the data directory
+const DATA_DIR: &str = "data";
+/// The directory of a snapshot containing files of the CAS
+const CAS_DIR: &str = "cas";
+
+/// Exports the cached indices of the sources, along with the downloaded packages of the lockfile
+/// (and the sources they come from) if given, into a gzipped tarball which can be imported on
+/// another machine to install without network access
+#[instrument(skip(project, sources, lockfile), level = "debug")]
+pub async fn export_snapshot<I: IntoIterator<Item = PackageSources>>(
+    project: &Project,
+    sources: I,
+    lockfile: Option<&Lockfile>,
+) -> Result<Vec<u8>, errors::ExportError> {
+    let mut sources = sources.into_iter().collect::<HashSet<_>>();
+    let mut cas_files = HashSet::new();
+
+    let mut archive = tokio_tar::Builder::new(
+        async_compression::tokio::write::GzipEncoder::with_quality(vec![], Level::Best),
+    );
+
+    for (name, versions) in lockfile.iter().flat_map(|lockfile| &lockfile.graph) {
+        for (version_id, node) in versions {
+            let pkg_ref = &node.node.pkg_ref;
+            let index_file = match pkg_ref {
+                PackageRefs::Pesde(pkg_ref) => pkg_ref.index_file(project),
+                #[cfg(feature = "wally-compat")]
+                PackageRefs::Wally(pkg_ref) => pkg_ref.index_file(project),
+                PackageRefs::Git(pkg_ref) => pkg_ref.index_file(project),
+                PackageRefs::Workspace(_) | PackageRefs::Local(_) | PackageRefs::Custom(_) => {
+                    continue
+                }
+            };
+
+            let contents = match fs::read_to_string(&index_file).await {
+                Ok(contents) => contents,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    return Err(errors::ExportError::NotDownloaded(format!(
+                        "{name}@{version_id}"
+                    )))
+                }
+                Err(e) => return Err(errors::ExportError::Io(e)),
+            };
+
+            if let PackageFS::CAS(entries) = toml::from_str::<PackageFS>(&contents)? {
+                cas_files.extend(entries.into_values().filter_map(|entry| match entry {
+                    FSEntry::File(hash) => Some(hash),
+                    FSEntry::Directory => None,
+                }));
+            }
+
+            archive
+                .append_path_with_name(&index_file, snapshot_path(project, &index_file))
+                .await?;
+            sources.insert(pkg_ref.source());
+        }
+    }
+
+    for hash in cas_files {
+        let path = cas_path(&hash, project.cas_dir());
+        archive
+            .append_path_with_name(&path, snapshot_path(project, &path))
+            .await?;
+    }
+
+    for source in sources {
+        let (path, url) = match &source {
+            PackageSources::Pesde(source) => (source.path(project), source.repo_url().clone()),
+            #[cfg(feature = "wally-compat")]
+            PackageSources::Wally(source) => (source.path(project), source.repo_url().clone()),
+            PackageSources::Git(source) => (source.path(project), source.repo_url().clone()),
+            PackageSources::Workspace(_) | PackageSources::Local(_) | PackageSources::Custom(_) => {
+                continue
+            }
+        };
+
+        if !path.exists() {
+            return Err(errors::ExportError::NotCached(Box::new(url)));
+        }
+
+        archive
+            .append_dir_all(snapshot_path(project, &path), &path)
+            .await?;
+    }
+
+    let mut encoder = archive.into_inner().await?;
+    encoder.shutdown().await?;
+
+    Ok(encoder.into_inner())
+}
+
+/// The path of a file of the data directory or the CAS inside of a snapshot
+fn snapshot_path(project: &Project, path: &Path) -> PathBuf {
+    match path.strip_prefix(project.cas_dir()) {
+        Ok(relative) => Path::new(CAS_DIR).join(relative),
+        Err(_) => Path::new(DATA_DIR).join(
+            path.strip_prefix(project.data_dir())
+                .expect("snapshot files must be in the data directory or the CAS"),
+        ),
+    }
+}
+
+/// Imports a snapshot created by [`export_snapshot`] into the project's data directory and CAS
+#[instrument(skip(project, bytes), level = "debug")]
+pub async fn import_snapshot(project: &Project, bytes: &[u8]) -> Result<(), errors::ImportError> {
+    let mut decoder = async_compression::tokio::bufread::GzipDecoder::new(bytes);
+    let mut archive = tokio_tar::Archive::new(&mut decoder);
+    let mut entries = archive.entries()?;
+
+    while let Some(mut entry) = entries.next().await.transpose()? {
+        let path = entry.path()?.to_path_buf();
+
+        let mut components = path.components();
+        let base = match components.next() {
+            Some(Component::Normal(dir)) if dir == DATA_DIR => project.data_dir(),
+            Some(Component::Normal(dir)) if dir == CAS_DIR => project.cas_dir(),
+            _ => return Err(errors::ImportError::InvalidPath(path)),
+        };
+
+        if components
+            .clone()
+            .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            return Err(errors::ImportError::InvalidPath(path));
+        }
+
+        let is_cas = base == project.cas_dir();
+        let destination = base.join(components.as_path());
+        let entry_type = entry.header().entry_type();
+
+        if is_cas {
+            if !entry_type.is_file() {
+                continue;
+            }
+
+            // entries are stored by the hash of their contents, so ones which don't match the
+            // hash their path is derived from could be used to tamper with other packages
+            let expected = components
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<String>();
+            let hash = store_in_cas(project.cas_dir(), &mut entry, |_| async { Ok(()) }).await?;
+            if hash != expected {
+                return Err(errors::ImportError::HashMismatch(path));
+            }
+
+            continue;
+        }
+
+        if entry_type.is_dir() {
+            fs::create_dir_all(&destination).await?;
+            continue;
+        }
+
+        if !entry_type.is_file() {
+            continue;
+        }
+
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        match fs::metadata(&destination).await {
+            // repositories contain read-only files, which can't be overwritten in place
+            Ok(_) => fs::remove_file(&destination).await?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        entry.unpack(&destination).await?;
+    }
+
+    Ok(())
+}
+
+/// Errors that can occur when exporting or importing snapshots
+pub mod errors {
+    use std::path::PathBuf;
+    use thiserror::Error;
+
+    /// Errors that can occur when exporting a snapshot
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum ExportError {
+        /// An error occurred interacting with the file system
+        #[error("error interacting with the file system")]
+        Io(#[from] std::io::Error),
+
+        /// A package of the lockfile hasn't been downloaded yet
+        #[error("package {0} hasn't been downloaded, install the project first")]
+        NotDownloaded(String),
+
+        /// An error occurred deserializing a downloaded package's cached file system
+        #[error("error deserializing cached package file system")]
+        Deserialize(#[from] toml::de::Error),
+
+        /// A source hasn't been cached yet
+        #[error("source {0} hasn't been cached, refresh it first")]
+        NotCached(Box<gix::Url>),
+    }
+
+    /// Errors that can occur when importing a snapshot
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum ImportError {
+        /// An error occurred interacting with the file system
+        #[error("error interacting with the file system")]
+        Io(#[from] std::io::Error),
+
+        /// The snapshot contains a path outside of the data directory and the CAS
+        #[error("snapshot contains invalid path {0}")]
+        InvalidPath(PathBuf),
+
+        /// The snapshot contains a CAS file whose contents don't match the hash of its path
+        #[error("snapshot contains CAS file {0} whose contents don't match its hash")]
+        HashMismatch(PathBuf),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(root: &Path) -> Project {
+        Project::new(
+            root.join("project"),
+            None::<PathBuf>,
+            root.join("data"),
+            root.join("cas"),
+            crate::AuthConfig::new(),
+        )
+    }
+
+    async fn snapshot(path: &str, contents: &[u8]) -> Vec<u8> {
+        let mut archive =
+            tokio_tar::Builder::new(async_compression::tokio::write::GzipEncoder::new(vec![]));
+        let mut header = tokio_tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        archive
+            .append_data(&mut header, path, contents)
+            .await
+            .unwrap();
+
+        let mut encoder = archive.into_inner().await.unwrap();
+        encoder.shutdown().await.unwrap();
+        encoder.into_inner()
+    }
+
+    #[tokio::test]
+    async fn imports_cas_files_matching_their_hash() {
+        let root = tempfile::tempdir().unwrap();
+        let project = project(root.path());
+        let hash = crate::util::hash("hello");
+
+        let bytes = snapshot(
+            &format!("{CAS_DIR}/{}/{}", &hash[..2], &hash[2..]),
+            b"hello",
+        )
+        .await;
+        import_snapshot(&project, &bytes).await.unwrap();
+
+        let path = cas_path(&hash, project.cas_dir());
+        assert_eq!(fs::read(&path).await.unwrap(), b"hello");
+        assert!(fs::metadata(&path).await.unwrap().permissions().readonly());
+    }
+
+    #[tokio::test]
+    async fn rejects_cas_files_not_matching_their_hash() {
+        let root = tempfile::tempdir().unwrap();
+        let project = project(root.path());
+        let hash = crate::util::hash("hello");
+
+        let bytes = snapshot(
+            &format!("{CAS_DIR}/{}/{}", &hash[..2], &hash[2..]),
+            b"tampered",
+        )
+        .await;
+        assert!(matches!(
+            import_snapshot(&project, &bytes).await,
+            Err(errors::ImportError::HashMismatch(_))
+        ));
+        assert!(!cas_path(&hash, project.cas_dir()).exists());
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{
+    manifest::target::{Target, TargetKind},
+    reporters::DownloadProgressReporter,
+    source::{
+        custom::{pkg_ref::CustomPackageRef, specifier::CustomDependencySpecifier},
+        fs::PackageFS,
+        PackageSource, PackageSources, ResolveResult,
+    },
+    Project,
+};
+use futures::future::BoxFuture;
+use std::{collections::HashSet, fmt::Debug};
+use tracing::instrument;
+
+/// The custom package reference
+pub mod pkg_ref;
+/// The custom dependency specifier
+pub mod specifier;
+
+/// The error type of custom sources
+pub type CustomSourceError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A package source implemented outside of pesde, registered on a project with
+/// [`Project::with_custom_source`]. Dependencies using it are declared with
+/// `{ custom = "<name>", data = { ... } }`, where the data is interpreted by the source
+pub trait CustomSource: Debug + Send + Sync {
+    /// Refreshes the source
+    fn refresh<'a>(
+        &'a self,
+        _project: &'a Project,
+        _reqwest: &'a reqwest::Client,
+    ) -> BoxFuture<'a, Result<(), CustomSourceError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Resolves a specifier to the versions of the package matching it
+    fn resolve<'a>(
+        &'a self,
+        specifier: &'a CustomDependencySpecifier,
+        project: &'a Project,
+        reqwest: &'a reqwest::Client,
+        project_target: TargetKind,
+    ) -> BoxFuture<'a, Result<ResolveResult<CustomPackageRef>, CustomSourceError>>;
+
+    /// Downloads a package
+    fn download<'a>(
+        &'a self,
+        pkg_ref: &'a CustomPackageRef,
+        project: &'a Project,
+        reqwest: &'a reqwest::Client,
+        reporter: &'a dyn DownloadProgressReporter,
+    ) -> BoxFuture<'a, Result<(PackageFS, Target), CustomSourceError>>;
+}
+
+/// A package source dispatching to the custom source registered on the project under its name
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
+pub struct CustomPackageSource {
+    name: String,
+}
+
+impl CustomPackageSource {
+    /// Creates a new custom package source from the name the source was registered under
+    pub fn new(name: String) -> Self {
+        Self { name }
+    }
+
+    /// The name the source was registered under
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn source<'a>(
+        &self,
+        project: &'a Project,
+    ) -> Result<&'a dyn CustomSource, errors::UnknownSourceError> {
+        project
+            .custom_source(&self.name)
+            .ok_or_else(|| errors::UnknownSourceError(self.name.clone()))
+    }
+}
+
+impl PackageSource for CustomPackageSource {
+    type Specifier = CustomDependencySpecifier;
+    type Ref = CustomPackageRef;
+    type RefreshError = errors::RefreshError;
+    type ResolveError = errors::ResolveError;
+    type DownloadError = errors::DownloadError;
+
+    #[instrument(skip_all, level = "debug")]
+    async fn refresh(
+        &self,
+        project: &Project,
+        reqwest: &reqwest::Client,
+    ) -> Result<(), Self::RefreshError> {
+        self.source(project)?
+            .refresh(project, reqwest)
+            .await
+            .map_err(|e| errors::RefreshError::Source(self.name.clone(), e))
+    }
+
+    #[instrument(skip_all, level = "debug")]
+    async fn resolve(
+        &self,
+        specifier: &Self::Specifier,
+        project: &Project,
+        reqwest: &reqwest::Client,
+        project_target: TargetKind,
+        _refreshed_sources: &mut HashSet<PackageSources>,
+    ) -> Result<ResolveResult<Self::Ref>, Self::ResolveError> {
+        self.source(project)?
+            .resolve(specifier, project, reqwest, project_target)
+            .await
+            .map_err(|e| errors::ResolveError::Source(self.name.clone(), e))
+    }
+
+    #[instrument(skip_all, level = "debug")]
+    async fn download(
+        &self,
+        pkg_ref: &Self::Ref,
+        project: &Project,
+        reqwest: &reqwest::Client,
+        reporter: &dyn DownloadProgressReporter,
+    ) -> Result<(PackageFS, Target), Self::DownloadError> {
+        self.source(project)?
+            .download(pkg_ref, project, reqwest, reporter)
+            .await
+            .map_err(|e| errors::DownloadError::Source(self.name.clone(), e))
+    }
+}
+
+/// Errors that can occur when interacting with a custom package source
+pub mod errors {
+    use crate::source::custom::CustomSourceError;
+    use thiserror::Error;
+
+    /// No custom source was registered under the name
+    #[derive(Debug, Error)]
+    #[error("no custom source registered under the name {0}")]
+    pub struct UnknownSourceError(pub String);
+
+    /// Errors that can occur when refreshing a custom package source
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum RefreshError {
+        /// The source isn't registered
+        #[error("custom source not registered")]
+        Unknown(#[from] UnknownSourceError),
+
+        /// The source failed to refresh
+        #[error("error refreshing custom source {0}")]
+        Source(String, #[source] CustomSourceError),
+    }
+
+    /// Errors that can occur when resolving a package from a custom package source
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum ResolveError {
+        /// The source isn't registered
+        #[error("custom source not registered")]
+        Unknown(#[from] UnknownSourceError),
+
+        /// The source failed to resolve the package
+        #[error("error resolving package from custom source {0}")]
+        Source(String, #[source] CustomSourceError),
+    }
+
+    /// Errors that can occur when downloading a package from a custom package source
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum DownloadError {
+        /// The source isn't registered
+        #[error("custom source not registered")]
+        Unknown(#[from] UnknownSourceError),
+
+        /// The source failed to download the package
+        #[error("error downloading package from custom source {0}")]
+        Source(String, #[source] CustomSourceError),
+    }
+}
@@ -0,0 +1,32 @@
+use crate::{
+    manifest::DependencyType,
+    source::{custom::CustomPackageSource, DependencySpecifiers, PackageRef, PackageSources},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A reference to a package from a custom source
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct CustomPackageRef {
+    /// The name the source was registered under
+    pub source: String,
+    /// The source-specific data identifying the package
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub data: BTreeMap<String, String>,
+    /// The dependencies of the package
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependencies: BTreeMap<String, (DependencySpecifiers, DependencyType)>,
+}
+impl PackageRef for CustomPackageRef {
+    fn dependencies(&self) -> &BTreeMap<String, (DependencySpecifiers, DependencyType)> {
+        &self.dependencies
+    }
+
+    fn use_new_structure(&self) -> bool {
+        true
+    }
+
+    fn source(&self) -> PackageSources {
+        PackageSources::Custom(CustomPackageSource::new(self.source.clone()))
+    }
+}
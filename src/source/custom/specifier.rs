@@ -0,0 +1,33 @@
+use crate::{manifest::platform::PlatformCondition, source::DependencySpecifier};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt::Display};
+
+/// The specifier for a dependency from a custom source
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct CustomDependencySpecifier {
+    /// The name the source was registered under
+    #[serde(rename = "custom")]
+    pub source: String,
+    /// The source-specific data describing the dependency
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub data: BTreeMap<String, String>,
+    /// The platform this dependency is restricted to
+    #[serde(flatten)]
+    pub platform: PlatformCondition,
+}
+impl DependencySpecifier for CustomDependencySpecifier {}
+
+impl Display for CustomDependencySpecifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}",
+            self.source,
+            self.data
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+    }
+}
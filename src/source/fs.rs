@@ -38,8 +38,9 @@ pub enum FSEntry {
 pub enum PackageFS {
     /// A package stored in the CAS
     CAS(BTreeMap<RelativePathBuf, FSEntry>),
-    /// A package that's to be copied
-    Copy(PathBuf, TargetKind),
+    /// A package that's to be copied, with its target and all targets its packages folders may be
+    /// for, which aren't copied
+    Copy(PathBuf, TargetKind, Vec<TargetKind>),
 }
 
 async fn set_readonly(path: &Path, readonly: bool) -> std::io::Result<()> {
@@ -74,6 +75,27 @@ pub(crate) fn cas_path(hash: &str, cas_dir: &Path) -> PathBuf {
     cas_dir.join(prefix).join(rest)
 }
 
+/// Links a file from the CAS into the destination, so that identical files across
+/// packages, targets and projects share the same storage. Falls back to copying if hard linking
+/// is not possible (e.g. the destination is on a different device), which the standard library
+/// performs as a reflink on filesystems supporting it (e.g. APFS, Btrfs and XFS)
+pub(crate) async fn link_from_cas(cas_file_path: &Path, destination: &Path) -> std::io::Result<()> {
+    match fs::hard_link(cas_file_path, destination).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Err(e),
+        Err(e) => {
+            tracing::debug!(
+                "failed to hard link {} to {}, copying instead: {e}",
+                cas_file_path.display(),
+                destination.display()
+            );
+
+            fs::copy(cas_file_path, destination).await?;
+            set_readonly(destination, false).await
+        }
+    }
+}
+
 pub(crate) async fn store_in_cas<
     R: tokio::io::AsyncRead + Unpin,
     P: AsRef<Path>,
@@ -153,7 +175,7 @@ impl PackageFS {
                                 let cas_file_path = cas_path.join(prefix).join(rest);
 
                                 if link {
-                                    fs::hard_link(cas_file_path, path).await?;
+                                    link_from_cas(&cas_file_path, &path).await?;
                                 } else {
                                     fs::copy(cas_file_path, &path).await?;
                                     set_readonly(&path, false).await?;
@@ -169,7 +191,7 @@ impl PackageFS {
                 }))
                 .await?;
             }
-            PackageFS::Copy(src, target) => {
+            PackageFS::Copy(src, target, target_kinds) => {
                 fs::create_dir_all(destination.as_ref()).await?;
 
                 let mut read_dir = fs::read_dir(src).await?;
@@ -185,7 +207,7 @@ impl PackageFS {
                             continue;
                         }
 
-                        for other_target in TargetKind::VARIANTS {
+                        for other_target in target_kinds {
                             if target.packages_folder(other_target) == file_name {
                                 continue 'entry;
                             }
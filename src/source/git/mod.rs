@@ -4,6 +4,7 @@ use crate::{
         Manifest,
     },
     names::PackageNames,
+    reporters::DownloadProgressReporter,
     source::{
         fs::{store_in_cas, FSEntry, PackageFS},
         git::{pkg_ref::GitPackageRef, specifier::GitDependencySpecifier},
@@ -13,20 +14,19 @@ use crate::{
         PackageSource, PackageSources, ResolveResult, VersionId, IGNORED_DIRS, IGNORED_FILES,
     },
     util::hash,
-    Project, DEFAULT_INDEX_NAME, LOCKFILE_FILE_NAME, MANIFEST_FILE_NAME,
+    OldBehaviourGlobs, Project, LOCKFILE_FILE_NAME, MANIFEST_FILE_NAME,
 };
 use fs_err::tokio as fs;
 use futures::future::try_join_all;
 use gix::{bstr::BStr, traverse::tree::Recorder, ObjectId, Url};
-use relative_path::RelativePathBuf;
+use relative_path::{RelativePath, RelativePathBuf};
 use std::{
     collections::{BTreeMap, HashSet},
     fmt::Debug,
     hash::Hash,
     path::PathBuf,
-    sync::Arc,
 };
-use tokio::{sync::Mutex, task::spawn_blocking};
+use tokio::task::spawn_blocking;
 use tracing::instrument;
 
 /// The Git package reference
@@ -51,6 +51,16 @@ impl GitBasedSource for GitPackageSource {
     fn repo_url(&self) -> &Url {
         &self.repo_url
     }
+
+    fn shallow(&self) -> bool {
+        // dependencies may point to any revision in the history
+        false
+    }
+
+    fn follows_refresh_policy(&self) -> bool {
+        // dependencies may point to revisions newer than the last refresh
+        false
+    }
 }
 
 impl GitPackageSource {
@@ -62,6 +72,84 @@ impl GitPackageSource {
     fn as_bytes(&self) -> Vec<u8> {
         self.repo_url.to_bstring().to_vec()
     }
+
+    /// Fetches a submodule and reads the files of its commit, prefixed with the submodule's path
+    #[allow(clippy::result_large_err)]
+    async fn submodule_records(
+        &self,
+        project: &Project,
+        reqwest: &reqwest::Client,
+        path: &RelativePath,
+        url: &str,
+        commit_id: ObjectId,
+    ) -> Result<Vec<(RelativePathBuf, Option<Vec<u8>>)>, errors::DownloadError> {
+        let submodule_error = |e| {
+            errors::DownloadError::Submodule(
+                path.to_relative_path_buf(),
+                Box::new(self.repo_url.clone()),
+                Box::new(e),
+            )
+        };
+
+        let url = Url::try_from(url).map_err(|e| submodule_error(e.into()))?;
+        let source = GitPackageSource::new(url.clone());
+        GitBasedSource::refresh(&source, project, reqwest)
+            .await
+            .map_err(|e| submodule_error(e.into()))?;
+
+        let repo = gix::open(source.path(project))
+            .map_err(|e| {
+                submodule_error(errors::DownloadError::OpenRepo(Box::new(url.clone()), e))
+            })?
+            .into_sync();
+        let path = path.to_relative_path_buf();
+
+        spawn_blocking(move || {
+            let repo = repo.to_thread_local();
+            let mut recorder = Recorder::default();
+
+            repo.find_object(commit_id)
+                .map_err(|e| {
+                    errors::DownloadError::ParseOidToObject(commit_id, Box::new(url.clone()), e)
+                })?
+                .peel_to_tree()
+                .map_err(|e| errors::DownloadError::ParseObjectToTree(Box::new(url.clone()), e))?
+                .traverse()
+                .breadthfirst(&mut recorder)
+                .map_err(|e| errors::DownloadError::TraverseTree(Box::new(url.clone()), e))?;
+
+            recorder
+                .records
+                .into_iter()
+                .map(|entry| {
+                    let entry_path = path.join(entry.filepath.to_string());
+
+                    if entry.mode.is_commit() {
+                        return Err(errors::DownloadError::NestedSubmodule(
+                            entry_path,
+                            Box::new(url.clone()),
+                        ));
+                    }
+
+                    let object = repo.find_object(entry.oid).map_err(|e| {
+                        errors::DownloadError::ParseOidToObject(entry.oid, Box::new(url.clone()), e)
+                    })?;
+
+                    Ok((
+                        entry_path,
+                        if matches!(object.kind, gix::object::Kind::Tree) {
+                            None
+                        } else {
+                            Some(object.data.clone())
+                        },
+                    ))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .await
+        .unwrap()
+        .map_err(submodule_error)
+    }
 }
 
 impl PackageSource for GitPackageSource {
@@ -72,15 +160,21 @@ impl PackageSource for GitPackageSource {
     type DownloadError = errors::DownloadError;
 
     #[instrument(skip_all, level = "debug")]
-    async fn refresh(&self, project: &Project) -> Result<(), Self::RefreshError> {
-        GitBasedSource::refresh(self, project).await
+    async fn refresh(
+        &self,
+        project: &Project,
+        reqwest: &reqwest::Client,
+    ) -> Result<(), Self::RefreshError> {
+        GitBasedSource::refresh(self, project, reqwest).await
     }
 
     #[instrument(skip_all, level = "debug")]
+    #[allow(clippy::result_large_err)]
     async fn resolve(
         &self,
         specifier: &Self::Specifier,
         project: &Project,
+        _reqwest: &reqwest::Client,
         _project_target: TargetKind,
         _refreshed_sources: &mut HashSet<PackageSources>,
     ) -> Result<ResolveResult<Self::Ref>, Self::ResolveError> {
@@ -136,6 +230,17 @@ impl PackageSource for GitPackageSource {
             root_tree.clone()
         };
 
+        let submodules = match read_file(&root_tree, [SUBMODULES_FILE_NAME])
+            .map_err(|e| errors::ResolveError::ReadSubmodules(Box::new(self.repo_url.clone()), e))?
+        {
+            Some(contents) => {
+                package_submodules(&self.repo_url, &contents, specifier.path.as_deref()).map_err(
+                    |e| errors::ResolveError::Submodules(Box::new(self.repo_url.clone()), e),
+                )?
+            }
+            None => BTreeMap::new(),
+        };
+
         let manifest = match read_file(&tree, [MANIFEST_FILE_NAME])
             .map_err(|e| errors::ResolveError::ReadManifest(Box::new(self.repo_url.clone()), e))?
         {
@@ -168,12 +273,11 @@ impl PackageSource for GitPackageSource {
                                 let index_name = specifier
                                     .index
                                     .as_deref()
-                                    .unwrap_or(DEFAULT_INDEX_NAME)
+                                    .unwrap_or(manifest.default_index_name())
                                     .to_string();
                                 specifier.index = Some(
-                                    manifest
-                                        .indices
-                                        .get(&index_name)
+                                    project
+                                        .index_url(&manifest.indices, &index_name)
                                         .ok_or_else(|| {
                                             errors::ResolveError::PesdeIndexNotFound(
                                                 index_name.clone(),
@@ -188,7 +292,7 @@ impl PackageSource for GitPackageSource {
                                 let index_name = specifier
                                     .index
                                     .as_deref()
-                                    .unwrap_or(DEFAULT_INDEX_NAME)
+                                    .unwrap_or(manifest.default_index_name())
                                     .to_string();
                                 specifier.index = Some(
                                     manifest
@@ -230,7 +334,8 @@ impl PackageSource for GitPackageSource {
                                     }
                                 };
 
-                                let target = specifier.target.unwrap_or(manifest.target.kind());
+                                let target =
+                                    specifier.target.clone().unwrap_or(manifest.target.kind());
 
                                 let path = lockfile
                                     .workspace
@@ -249,8 +354,16 @@ impl PackageSource for GitPackageSource {
                                     repo: self.repo_url.clone(),
                                     rev: rev.to_string(),
                                     path: Some(path),
+                                    platform: specifier.platform.clone(),
                                 })
                             }
+                            DependencySpecifiers::Local(specifier) => {
+                                return Err(errors::ResolveError::LocalDependency(
+                                    specifier.name.to_string(),
+                                    Box::new(self.repo_url.clone()),
+                                ));
+                            }
+                            DependencySpecifiers::Custom(_) => {}
                         }
 
                         Ok((alias, (spec, ty)))
@@ -280,16 +393,14 @@ impl PackageSource for GitPackageSource {
                                         e,
                                     )
                                 })?;
-                                let name = PackageNames::Wally(manifest.package.name);
                                 let version_id = VersionId(
                                     manifest.package.version,
-                                    match manifest.package.realm {
-                                        crate::source::wally::manifest::Realm::Server => {
-                                            TargetKind::RobloxServer
-                                        }
-                                        _ => TargetKind::Roblox,
-                                    },
+                                    project.wally_realms().target_kind(
+                                        &manifest.package.name,
+                                        manifest.package.realm,
+                                    ),
                                 );
+                                let name = PackageNames::Wally(manifest.package.name);
 
                                 (name, version_id, dependencies)
                             }
@@ -327,23 +438,23 @@ impl PackageSource for GitPackageSource {
                     tree_id: tree.id.to_string(),
                     new_structure,
                     dependencies,
+                    submodules,
+                    path: specifier.path.clone(),
                 },
             )]),
         ))
     }
 
     #[instrument(skip_all, level = "debug")]
+    #[allow(clippy::result_large_err)]
     async fn download(
         &self,
         pkg_ref: &Self::Ref,
         project: &Project,
-        _reqwest: &reqwest::Client,
+        reqwest: &reqwest::Client,
+        reporter: &dyn DownloadProgressReporter,
     ) -> Result<(PackageFS, Target), Self::DownloadError> {
-        let index_file = project
-            .cas_dir
-            .join("git_index")
-            .join(hash(self.as_bytes()))
-            .join(&pkg_ref.tree_id);
+        let index_file = pkg_ref.index_file(project);
 
         match fs::read_to_string(&index_file).await {
             Ok(s) => {
@@ -403,6 +514,8 @@ impl PackageSource for GitPackageSource {
             Err(e) => return Err(errors::DownloadError::Io(e)),
         }
 
+        reporter.report_extract();
+
         let repo = gix::open(self.path(project))
             .map_err(|e| errors::DownloadError::OpenRepo(Box::new(self.repo_url.clone()), e))?
             .into_sync();
@@ -454,7 +567,11 @@ impl PackageSource for GitPackageSource {
 
         let repo = repo.to_thread_local();
 
-        let records = records
+        let (submodule_entries, entries) = records
+            .into_iter()
+            .partition::<Vec<_>, _>(|entry| entry.mode.is_commit());
+
+        let mut records = entries
             .into_iter()
             .map(|entry| {
                 let object = repo.find_object(entry.oid).map_err(|e| {
@@ -476,11 +593,62 @@ impl PackageSource for GitPackageSource {
             })
             .collect::<Result<Vec<_>, _>>()?;
 
-        let manifest = Arc::new(Mutex::new(None::<Vec<u8>>));
+        for entry in submodule_entries {
+            let path = RelativePathBuf::from(entry.filepath.to_string());
+            let Some(url) = pkg_ref.submodules.get(&path) else {
+                return Err(errors::DownloadError::UnknownSubmodule(
+                    path,
+                    Box::new(self.repo_url.clone()),
+                ));
+            };
+
+            records.extend(
+                self.submodule_records(project, reqwest, &path, url, entry.oid)
+                    .await?,
+            );
+            records.push((path, None));
+        }
+
+        let manifest = match records
+            .iter()
+            .find(|(path, _)| path == MANIFEST_FILE_NAME)
+            .and_then(|(_, contents)| contents.clone())
+        {
+            Some(data) => match String::from_utf8(data) {
+                Ok(s) => match toml::from_str::<Manifest>(&s) {
+                    Ok(m) => Some(m),
+                    Err(e) => {
+                        return Err(errors::DownloadError::DeserializeFile(
+                            Box::new(self.repo_url.clone()),
+                            e,
+                        ))
+                    }
+                },
+                Err(e) => return Err(errors::DownloadError::ParseManifest(e)),
+            },
+            None => None,
+        };
+
+        // only keep the files a package in a subdirectory declares, like a published package would,
+        // as the rest of the repository may be outside of what it's meant to contain
+        let included = match manifest
+            .as_ref()
+            .filter(|m| pkg_ref.path.is_some() && !m.includes.is_empty())
+        {
+            Some(manifest) => Some(included_paths(&records, &manifest.includes).map_err(|e| {
+                errors::DownloadError::Includes(Box::new(self.repo_url.clone()), e)
+            })?),
+            None => None,
+        };
+
         let entries = try_join_all(
             records
                 .into_iter()
                 .filter(|(path, contents)| {
+                    if included.as_ref().is_some_and(|included| !included.contains(path)) {
+                        return false;
+                    }
+
                     let name = path.file_name().unwrap_or("");
                     if contents.is_none() {
                         return !IGNORED_DIRS.contains(&name);
@@ -501,44 +669,21 @@ impl PackageSource for GitPackageSource {
 
                     true
                 })
-                .map(|(path, contents)| {
-                    let manifest = manifest.clone();
-                    async move {
-                        let Some(contents) = contents else {
-                            return Ok::<_, errors::DownloadError>((path, FSEntry::Directory));
-                        };
-
-                        let hash =
-                            store_in_cas(project.cas_dir(), contents.as_slice(), |_| async { Ok(()) })
-                                .await?;
-
-                        if path == MANIFEST_FILE_NAME {
-                            manifest.lock().await.replace(contents);
-                        }
+                .map(|(path, contents)| async move {
+                    let Some(contents) = contents else {
+                        return Ok::<_, errors::DownloadError>((path, FSEntry::Directory));
+                    };
 
-                        Ok((path, FSEntry::File(hash)))
-                    }
+                    let hash =
+                        store_in_cas(project.cas_dir(), contents.as_slice(), |_| async { Ok(()) })
+                            .await?;
+
+                    Ok((path, FSEntry::File(hash)))
                 }),
         )
-            .await?
-            .into_iter()
-            .collect::<BTreeMap<_, _>>();
-
-        let manifest = match Arc::into_inner(manifest).unwrap().into_inner() {
-            Some(data) => match String::from_utf8(data.to_vec()) {
-                Ok(s) => match toml::from_str::<Manifest>(&s) {
-                    Ok(m) => Some(m),
-                    Err(e) => {
-                        return Err(errors::DownloadError::DeserializeFile(
-                            Box::new(self.repo_url.clone()),
-                            e,
-                        ))
-                    }
-                },
-                Err(e) => return Err(errors::DownloadError::ParseManifest(e)),
-            },
-            None => None,
-        };
+        .await?
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
 
         let fs = PackageFS::CAS(entries);
 
@@ -576,6 +721,100 @@ impl PackageSource for GitPackageSource {
     }
 }
 
+/// The name of the file declaring a repository's submodules
+const SUBMODULES_FILE_NAME: &str = ".gitmodules";
+
+/// Gets the URLs of the submodules inside of a package's tree from the repository's
+/// `.gitmodules` file, by their path relative to the package
+fn package_submodules(
+    repo_url: &Url,
+    contents: &str,
+    package_path: Option<&RelativePath>,
+) -> Result<BTreeMap<RelativePathBuf, String>, errors::SubmodulesError> {
+    let file =
+        gix::submodule::File::from_bytes(contents.as_bytes(), None, &gix::config::File::default())?;
+
+    let mut submodules = BTreeMap::new();
+
+    for name in file.names() {
+        let path = RelativePathBuf::from(file.path(name)?.to_string());
+        let path = match package_path {
+            Some(package_path) => match path.strip_prefix(package_path) {
+                Ok(path) => path.to_relative_path_buf(),
+                Err(_) => continue,
+            },
+            None => path,
+        };
+
+        let url = file.url(name)?;
+        let raw_path = url.path.to_string();
+        // relative URLs are relative to the superproject's URL
+        let url = if url.scheme == gix::url::Scheme::File
+            && (raw_path.starts_with("./") || raw_path.starts_with("../"))
+        {
+            let mut url_path = repo_url.path.to_string().trim_end_matches('/').to_string();
+
+            for component in raw_path.split('/') {
+                match component {
+                    "." | "" => {}
+                    ".." => {
+                        let end = url_path.rfind('/').unwrap_or(0);
+                        url_path.truncate(end);
+                    }
+                    component => {
+                        url_path.push('/');
+                        url_path.push_str(component);
+                    }
+                }
+            }
+
+            let mut url = repo_url.clone();
+            url.path = url_path.into();
+            url
+        } else {
+            url
+        };
+
+        submodules.insert(path, url.to_bstring().to_string());
+    }
+
+    Ok(submodules)
+}
+
+/// Gets the paths of a package's tree which are matched by its manifest's includes, following
+/// the same rules as when publishing (plain names include the whole root entry)
+fn included_paths(
+    records: &[(RelativePathBuf, Option<Vec<u8>>)],
+    includes: &[String],
+) -> Result<HashSet<RelativePathBuf>, wax::BuildError> {
+    let globs = OldBehaviourGlobs::new(includes.iter().map(|glob| glob.as_str()))?;
+
+    let mut included = records
+        .iter()
+        .map(|(path, _)| path)
+        .filter(|path| {
+            path == &MANIFEST_FILE_NAME
+                || path
+                    .components()
+                    .next()
+                    .is_some_and(|root| globs.is_root_name_match(root.as_str()))
+                || globs.is_glob_match(path.as_str())
+        })
+        .cloned()
+        .collect::<HashSet<_>>();
+
+    // keep the directories leading up to included files
+    let parents = included
+        .iter()
+        .flat_map(|path| std::iter::successors(path.parent(), |path| path.parent()))
+        .filter(|path| !path.as_str().is_empty())
+        .map(|path| path.to_relative_path_buf())
+        .collect::<Vec<_>>();
+    included.extend(parents);
+
+    Ok(included)
+}
+
 /// Errors that can occur when interacting with the Git package source
 pub mod errors {
     use crate::manifest::target::TargetKind;
@@ -671,6 +910,38 @@ pub mod errors {
         /// No path for a workspace member was found in the lockfile
         #[error("no path found for workspace member {0} {1} in lockfile for repository {2}")]
         NoPathForWorkspaceMember(String, TargetKind, Box<gix::Url>),
+
+        /// A local registry dependency was found, which can't be resolved outside of the local filesystem
+        #[error("local registry dependency {0} can't be used by repository {1}")]
+        LocalDependency(String, Box<gix::Url>),
+
+        /// An error occurred reading the submodules file
+        #[error("error reading submodules file of repository {0}")]
+        ReadSubmodules(
+            Box<gix::Url>,
+            #[source] crate::source::git_index::errors::ReadFile,
+        ),
+
+        /// An error occurred getting the submodules of the repository
+        #[error("error getting submodules of repository {0}")]
+        Submodules(Box<gix::Url>, #[source] SubmodulesError),
+    }
+
+    /// Errors that can occur when getting the submodules of a repository
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum SubmodulesError {
+        /// An error occurred parsing the submodules file
+        #[error("error parsing submodules file")]
+        Parse(#[from] gix::config::parse::Error),
+
+        /// A submodule has an invalid path
+        #[error("submodule has an invalid path")]
+        Path(#[from] gix::submodule::config::path::Error),
+
+        /// A submodule has an invalid URL
+        #[error("submodule has an invalid URL")]
+        Url(#[from] gix::submodule::config::url::Error),
     }
 
     /// Errors that can occur when downloading a package from a Git package source
@@ -728,5 +999,29 @@ pub mod errors {
         /// An error occurred while parsing tree_id to ObjectId
         #[error("error parsing tree_id to ObjectId for repository {0}")]
         ParseTreeId(Box<gix::Url>, #[source] gix::hash::decode::Error),
+
+        /// A submodule without an entry in the repository's submodules file was found
+        #[error("submodule at {0} of repository {1} is not declared in .gitmodules")]
+        UnknownSubmodule(RelativePathBuf, Box<gix::Url>),
+
+        /// An error occurred reading a submodule
+        #[error("error reading submodule at {0} of repository {1}")]
+        Submodule(RelativePathBuf, Box<gix::Url>, #[source] Box<DownloadError>),
+
+        /// A submodule nested inside of another submodule was found, which isn't supported
+        #[error("nested submodule at {0} of repository {1} is not supported")]
+        NestedSubmodule(RelativePathBuf, Box<gix::Url>),
+
+        /// An error occurred parsing a submodule's URL
+        #[error("error parsing submodule URL")]
+        ParseUrl(#[from] gix::url::parse::Error),
+
+        /// An error occurred refreshing a submodule's repository
+        #[error("error refreshing submodule repository")]
+        Refresh(#[from] crate::source::git_index::errors::RefreshError),
+
+        /// An error occurred building the globs of the manifest's includes
+        #[error("error building the includes globs for repository {0}")]
+        Includes(Box<gix::Url>, #[source] wax::BuildError),
     }
 }
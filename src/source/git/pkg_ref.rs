@@ -1,9 +1,12 @@
+use relative_path::RelativePathBuf;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, path::PathBuf};
 
 use crate::{
     manifest::DependencyType,
     source::{git::GitPackageSource, DependencySpecifiers, PackageRef, PackageSources},
+    util::hash,
+    Project,
 };
 
 /// A Git package reference
@@ -22,7 +25,24 @@ pub struct GitPackageRef {
     pub dependencies: BTreeMap<String, (DependencySpecifiers, DependencyType)>,
     /// Whether this package uses the new structure
     pub new_structure: bool,
+    /// The URLs of the submodules inside of the package's tree, by their path
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub submodules: BTreeMap<RelativePathBuf, String>,
+    /// The path of the package in the repository, if it's in a subdirectory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<RelativePathBuf>,
+}
+impl GitPackageRef {
+    /// The path of the cached file system of the package, written once it's been downloaded
+    pub(crate) fn index_file(&self, project: &Project) -> PathBuf {
+        project
+            .cas_dir
+            .join("git_index")
+            .join(hash(self.repo.to_bstring()))
+            .join(&self.tree_id)
+    }
 }
+
 impl PackageRef for GitPackageRef {
     fn dependencies(&self) -> &BTreeMap<String, (DependencySpecifiers, DependencyType)> {
         &self.dependencies
@@ -2,7 +2,7 @@ use relative_path::RelativePathBuf;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
-use crate::source::DependencySpecifier;
+use crate::{manifest::platform::PlatformCondition, source::DependencySpecifier};
 
 /// The specifier for a Git dependency
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
@@ -18,6 +18,9 @@ pub struct GitDependencySpecifier {
     /// The path of the package in the repository
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub path: Option<RelativePathBuf>,
+    /// The platform this dependency is restricted to
+    #[serde(flatten)]
+    pub platform: PlatformCondition,
 }
 impl DependencySpecifier for GitDependencySpecifier {}
 
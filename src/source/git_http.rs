@@ -0,0 +1,343 @@
+use gix::{
+    protocol::transport::client::{
+        http::{self, GetResponse, PostBodyDataKind, PostResponse},
+        Transport,
+    },
+    remote::{Connection, Direction},
+};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::{
+    io::{BufRead, Cursor, ErrorKind, Read, Write},
+    sync::{Arc, Mutex},
+};
+use tokio::runtime::Handle;
+
+/// Makes the connection send its HTTP requests with the given client instead of one gix builds
+/// itself, so that Git repositories are fetched with the project's proxy and root certificates.
+/// Must be called from a blocking task of the Tokio runtime the client runs on
+pub(crate) fn use_reqwest(
+    conn: &mut Connection<'_, '_, Box<dyn Transport + Send>>,
+    reqwest: &reqwest::Client,
+) {
+    let Ok((url, version)) = conn.remote().sanitized_url_and_version(Direction::Fetch) else {
+        // the error will be reported when the connection is used
+        return;
+    };
+    if !matches!(url.scheme, gix::url::Scheme::Http | gix::url::Scheme::Https) {
+        return;
+    }
+
+    *conn.transport_mut() = Box::new(http::connect_http(
+        ReqwestHttp {
+            client: reqwest.clone(),
+            runtime: Handle::current(),
+            redirected_base_url: Default::default(),
+        },
+        url,
+        version,
+        false,
+    ));
+}
+
+/// An implementation of gix's HTTP transport backed by a [`reqwest::Client`]
+struct ReqwestHttp {
+    client: reqwest::Client,
+    runtime: Handle,
+    /// The base URL the first request was redirected to, which later requests are sent to
+    /// directly like git does
+    redirected_base_url: Arc<Mutex<Option<String>>>,
+}
+
+impl ReqwestHttp {
+    fn request(
+        &self,
+        url: &str,
+        base_url: &str,
+        headers: impl IntoIterator<Item = impl AsRef<str>>,
+        body: Option<Vec<u8>>,
+    ) -> Arc<Mutex<Exchange>> {
+        let headers = headers
+            .into_iter()
+            .filter_map(|line| {
+                let (name, value) = line.as_ref().split_once(':')?;
+                Some((
+                    HeaderName::try_from(name).ok()?,
+                    HeaderValue::try_from(value.trim()).ok()?,
+                ))
+            })
+            .collect();
+
+        Arc::new(Mutex::new(Exchange::Pending(PendingRequest {
+            client: self.client.clone(),
+            runtime: self.runtime.clone(),
+            redirected_base_url: self.redirected_base_url.clone(),
+            tail: url.strip_prefix(base_url).unwrap_or_default().to_string(),
+            url: url.to_string(),
+            headers,
+            body,
+        })))
+    }
+}
+
+impl http::Http for ReqwestHttp {
+    type Headers = ResponseHeaders;
+    type ResponseBody = ResponseBody;
+    type PostBody = RequestBody;
+
+    fn get(
+        &mut self,
+        url: &str,
+        base_url: &str,
+        headers: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<GetResponse<Self::Headers, Self::ResponseBody>, http::Error> {
+        let exchange = self.request(url, base_url, headers, None);
+
+        Ok(GetResponse {
+            headers: ResponseHeaders::new(exchange.clone()),
+            body: ResponseBody::new(exchange, self.runtime.clone()),
+        })
+    }
+
+    fn post(
+        &mut self,
+        url: &str,
+        base_url: &str,
+        headers: impl IntoIterator<Item = impl AsRef<str>>,
+        _body: PostBodyDataKind,
+    ) -> Result<PostResponse<Self::Headers, Self::ResponseBody, Self::PostBody>, http::Error> {
+        let exchange = self.request(url, base_url, headers, Some(vec![]));
+
+        Ok(PostResponse {
+            post_body: RequestBody(exchange.clone()),
+            headers: ResponseHeaders::new(exchange.clone()),
+            body: ResponseBody::new(exchange, self.runtime.clone()),
+        })
+    }
+
+    fn configure(
+        &mut self,
+        _config: &dyn std::any::Any,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        // the client is configured by the project instead
+        Ok(())
+    }
+}
+
+struct PendingRequest {
+    client: reqwest::Client,
+    runtime: Handle,
+    redirected_base_url: Arc<Mutex<Option<String>>>,
+    url: String,
+    /// The part of the URL after the repository's base URL
+    tail: String,
+    headers: HeaderMap,
+    body: Option<Vec<u8>>,
+}
+
+impl PendingRequest {
+    /// Sends the request, turning statuses into the errors gix expects: 401 must be a
+    /// [`ErrorKind::PermissionDenied`] for credentials to be asked for
+    fn send(self) -> std::io::Result<reqwest::Response> {
+        let url = match &*self.redirected_base_url.lock().unwrap() {
+            Some(base_url) => format!("{base_url}{}", self.tail),
+            None => self.url,
+        };
+
+        let request = match self.body {
+            Some(body) => self.client.post(&url).body(body),
+            None => self.client.get(&url),
+        }
+        .headers(self.headers);
+
+        let response = self
+            .runtime
+            .block_on(request.send())
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| {
+                let kind = match e.status() {
+                    Some(reqwest::StatusCode::UNAUTHORIZED) => ErrorKind::PermissionDenied,
+                    Some(status) if status.is_server_error() => ErrorKind::ConnectionAborted,
+                    Some(_) => ErrorKind::Other,
+                    None if e.is_timeout() => ErrorKind::TimedOut,
+                    None if e.is_connect() => ErrorKind::ConnectionRefused,
+                    None => ErrorKind::Other,
+                };
+
+                std::io::Error::new(kind, e)
+            })?;
+
+        if response.url().as_str() != url {
+            if let Some(base_url) = response.url().as_str().strip_suffix(&self.tail) {
+                *self.redirected_base_url.lock().unwrap() = Some(base_url.to_string());
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+/// A request which is sent once its response is first read, after its body has been written
+enum Exchange {
+    Pending(PendingRequest),
+    Sent {
+        headers: Option<Vec<u8>>,
+        response: Option<reqwest::Response>,
+    },
+    Failed(ErrorKind, String),
+}
+
+impl Exchange {
+    fn send(&mut self) -> std::io::Result<&mut Self> {
+        if let Exchange::Pending(_) = self {
+            let Exchange::Pending(request) =
+                std::mem::replace(self, Exchange::Failed(ErrorKind::Other, String::new()))
+            else {
+                unreachable!()
+            };
+
+            *self = match request.send() {
+                Ok(response) => Exchange::Sent {
+                    headers: Some(
+                        response
+                            .headers()
+                            .iter()
+                            .flat_map(|(name, value)| {
+                                [name.as_str().as_bytes(), b":", value.as_bytes(), b"\n"].concat()
+                            })
+                            .collect(),
+                    ),
+                    response: Some(response),
+                },
+                Err(e) => Exchange::Failed(e.kind(), e.to_string()),
+            };
+        }
+
+        match self {
+            Exchange::Failed(kind, message) => Err(std::io::Error::new(*kind, message.clone())),
+            exchange => Ok(exchange),
+        }
+    }
+}
+
+/// The body of a POST request, sent once it's been written
+struct RequestBody(Arc<Mutex<Exchange>>);
+
+impl Write for RequestBody {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut *self.0.lock().unwrap() {
+            Exchange::Pending(PendingRequest {
+                body: Some(body), ..
+            }) => {
+                body.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            _ => Err(std::io::Error::other("request was already sent")),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The headers of a response, as `name:value` lines
+struct ResponseHeaders {
+    exchange: Arc<Mutex<Exchange>>,
+    headers: Option<Cursor<Vec<u8>>>,
+}
+
+impl ResponseHeaders {
+    fn new(exchange: Arc<Mutex<Exchange>>) -> Self {
+        Self {
+            exchange,
+            headers: None,
+        }
+    }
+
+    fn headers(&mut self) -> std::io::Result<&mut Cursor<Vec<u8>>> {
+        if self.headers.is_none() {
+            let headers = match self.exchange.lock().unwrap().send()? {
+                Exchange::Sent { headers, .. } => headers.take().unwrap_or_default(),
+                _ => unreachable!(),
+            };
+            self.headers = Some(Cursor::new(headers));
+        }
+
+        Ok(self.headers.as_mut().unwrap())
+    }
+}
+
+impl Read for ResponseHeaders {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.headers()?.read(buf)
+    }
+}
+
+impl BufRead for ResponseHeaders {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.headers()?.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Some(headers) = &mut self.headers {
+            headers.consume(amt);
+        }
+    }
+}
+
+/// The body of a response, read chunk by chunk
+struct ResponseBody {
+    exchange: Arc<Mutex<Exchange>>,
+    runtime: Handle,
+    response: Option<reqwest::Response>,
+    chunk: Cursor<Vec<u8>>,
+}
+
+impl ResponseBody {
+    fn new(exchange: Arc<Mutex<Exchange>>, runtime: Handle) -> Self {
+        Self {
+            exchange,
+            runtime,
+            response: None,
+            chunk: Default::default(),
+        }
+    }
+}
+
+impl Read for ResponseBody {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.fill_buf()?.read(buf)?;
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl BufRead for ResponseBody {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.response.is_none() {
+            self.response = match self.exchange.lock().unwrap().send()? {
+                Exchange::Sent { response, .. } => response.take(),
+                _ => unreachable!(),
+            };
+        }
+
+        while self.chunk.position() as usize >= self.chunk.get_ref().len() {
+            let Some(response) = &mut self.response else {
+                break;
+            };
+            match self.runtime.block_on(response.chunk()) {
+                Ok(Some(chunk)) => self.chunk = Cursor::new(chunk.to_vec()),
+                Ok(None) => {
+                    self.response = None;
+                }
+                Err(e) => return Err(std::io::Error::other(e)),
+            }
+        }
+
+        self.chunk.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.chunk.consume(amt);
+    }
+}
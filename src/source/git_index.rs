@@ -1,12 +1,26 @@
 #![allow(async_fn_in_trait)]
 
-use crate::{util::authenticate_conn, Project};
+use crate::{
+    source::{git_http::use_reqwest, is_stale, RefreshPolicy},
+    util::authenticate_conn,
+    AuthConfig, Project,
+};
 use fs_err::tokio as fs;
-use gix::remote::Direction;
-use std::fmt::Debug;
+use gix::{
+    remote::{fetch::Shallow, Direction},
+    sec::identity::Account,
+};
+use std::{
+    fmt::Debug,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+};
 use tokio::task::spawn_blocking;
 use tracing::instrument;
 
+/// The file in a repository whose modification time marks when it was last refreshed
+const REFRESHED_FILE: &str = "pesde_refreshed";
+
 /// A trait for sources that are based on Git repositories
 pub trait GitBasedSource {
     /// The path to the index
@@ -15,81 +29,250 @@ pub trait GitBasedSource {
     /// The URL of the repository
     fn repo_url(&self) -> &gix::Url;
 
-    /// Refreshes the repository
-    async fn refresh(&self, project: &Project) -> Result<(), errors::RefreshError> {
+    /// The credentials to fetch the repository with
+    fn git_credentials(&self, auth_config: &AuthConfig) -> Option<Account> {
+        auth_config.git_credentials_for(self.repo_url())
+    }
+
+    /// Whether only the latest commit of the repository is needed, allowing shallow fetches
+    fn shallow(&self) -> bool {
+        true
+    }
+
+    /// Whether refreshes of the repository follow the project's refresh policy. Cached
+    /// repositories are never refreshed under [`RefreshPolicy::Never`] regardless, so that
+    /// projects can be installed offline
+    fn follows_refresh_policy(&self) -> bool {
+        true
+    }
+
+    /// The mirrors the repository is fetched from if it can't be reached
+    async fn git_mirrors(&self, project: &Project) -> Vec<gix::Url> {
+        project.mirrors(self.repo_url()).to_vec()
+    }
+
+    /// Refreshes the repository, falling back to its mirrors if it can't be reached
+    async fn refresh(
+        &self,
+        project: &Project,
+        reqwest: &reqwest::Client,
+    ) -> Result<(), errors::RefreshError> {
         let path = self.path(project);
         let repo_url = self.repo_url().clone();
-        let auth_config = project.auth_config.clone();
-
-        if path.exists() {
-            spawn_blocking(move || {
-                let repo = match gix::open(&path) {
-                    Ok(repo) => repo,
-                    Err(e) => return Err(errors::RefreshError::Open(path, Box::new(e))),
-                };
-                let remote = match repo.find_default_remote(Direction::Fetch) {
-                    Some(Ok(remote)) => remote,
-                    Some(Err(e)) => {
-                        return Err(errors::RefreshError::GetDefaultRemote(path, Box::new(e)))
-                    }
-                    None => {
-                        return Err(errors::RefreshError::NoDefaultRemote(path));
-                    }
-                };
-
-                let mut connection = match remote.connect(Direction::Fetch) {
-                    Ok(connection) => connection,
-                    Err(e) => {
-                        return Err(errors::RefreshError::Connect(
-                            repo_url.to_string(),
-                            Box::new(e),
-                        ))
-                    }
-                };
-
-                authenticate_conn(&mut connection, &auth_config);
-
-                let fetch =
-                    match connection.prepare_fetch(gix::progress::Discard, Default::default()) {
-                        Ok(fetch) => fetch,
-                        Err(e) => {
-                            return Err(errors::RefreshError::PrepareFetch(
-                                repo_url.to_string(),
-                                Box::new(e),
-                            ))
-                        }
-                    };
-
-                match fetch.receive(gix::progress::Discard, &false.into()) {
-                    Ok(_) => Ok(()),
-                    Err(e) => Err(errors::RefreshError::Read(
-                        repo_url.to_string(),
-                        Box::new(e),
-                    )),
-                }
-            })
-            .await
-            .unwrap()?;
+        let refreshed_file = path.join(REFRESHED_FILE);
 
+        if (self.follows_refresh_policy() || project.refresh_policy() == RefreshPolicy::Never)
+            && path.exists()
+            && !is_stale(project, &refreshed_file).await
+        {
+            tracing::debug!("{repo_url} was refreshed recently, skipping refresh");
             return Ok(());
         }
 
-        fs::create_dir_all(&path).await?;
-
-        spawn_blocking(move || {
-            gix::prepare_clone_bare(repo_url.clone(), &path)
-                .map_err(|e| errors::RefreshError::Clone(repo_url.to_string(), Box::new(e)))?
-                .configure_connection(move |c| {
-                    authenticate_conn(c, &auth_config);
-                    Ok(())
-                })
-                .fetch_only(gix::progress::Discard, &false.into())
-                .map_err(|e| errors::RefreshError::Fetch(repo_url.to_string(), Box::new(e)))
-        })
+        let shallow = if self.shallow() {
+            Shallow::DepthAtRemote(NonZeroU32::MIN)
+        } else {
+            Shallow::NoChange
+        };
+
+        let err = match refresh_from(
+            project,
+            reqwest,
+            &path,
+            &repo_url,
+            self.git_credentials(&project.auth_config),
+            shallow.clone(),
+        )
         .await
-        .unwrap()
-        .map(|_| ())
+        {
+            Err(e) if e.is_unreachable() => e,
+            res => {
+                res?;
+                fs::write(&refreshed_file, "").await?;
+                return Ok(());
+            }
+        };
+
+        for mirror in self.git_mirrors(project).await {
+            tracing::warn!("failed to reach {repo_url}, trying mirror {mirror}");
+
+            let credentials = project.auth_config.git_credentials_for(&mirror);
+            match refresh_from(
+                project,
+                reqwest,
+                &path,
+                &mirror,
+                credentials,
+                shallow.clone(),
+            )
+            .await
+            {
+                Err(e) if e.is_unreachable() => {
+                    tracing::warn!("failed to reach mirror {mirror}: {e}");
+                }
+                res => {
+                    res?;
+                    fs::write(&refreshed_file, "").await?;
+                    return Ok(());
+                }
+            }
+        }
+
+        Err(err)
+    }
+}
+
+/// Fetches the repository from the URL into the path, or clones it if it isn't cached yet
+async fn refresh_from(
+    project: &Project,
+    reqwest: &reqwest::Client,
+    path: &Path,
+    repo_url: &gix::Url,
+    credentials: Option<Account>,
+    shallow: Shallow,
+) -> Result<(), errors::RefreshError> {
+    if path.exists() {
+        return project
+            .retry_policy()
+            .retry(
+                || {
+                    let path = path.to_path_buf();
+                    let repo_url = repo_url.clone();
+                    let credentials = credentials.clone();
+                    let shallow = shallow.clone();
+                    let reqwest = reqwest.clone();
+
+                    async move {
+                        spawn_blocking(move || {
+                            fetch(path, &repo_url, credentials, shallow, &reqwest)
+                        })
+                        .await
+                        .unwrap()
+                    }
+                },
+                errors::RefreshError::is_spurious,
+            )
+            .await;
     }
+
+    project
+        .retry_policy()
+        .retry(
+            || {
+                let path = path.to_path_buf();
+                let repo_url = repo_url.clone();
+                let credentials = credentials.clone();
+                let shallow = shallow.clone();
+                let reqwest = reqwest.clone();
+
+                async move {
+                    // a failed clone removes the directory it was cloning into
+                    fs::create_dir_all(&path).await?;
+
+                    spawn_blocking(move || clone(&path, repo_url, credentials, shallow, reqwest))
+                        .await
+                        .unwrap()
+                }
+            },
+            errors::RefreshError::is_spurious,
+        )
+        .await
+}
+
+/// Fetches the remote of a cached repository
+fn fetch(
+    path: PathBuf,
+    repo_url: &gix::Url,
+    credentials: Option<Account>,
+    shallow: Shallow,
+    reqwest: &reqwest::Client,
+) -> Result<(), errors::RefreshError> {
+    let repo = match gix::open(&path) {
+        Ok(repo) => repo,
+        Err(e) => return Err(errors::RefreshError::Open(path, Box::new(e))),
+    };
+    let default_remote = match repo.find_default_remote(Direction::Fetch) {
+        Some(Ok(remote)) => remote,
+        Some(Err(e)) => return Err(errors::RefreshError::GetDefaultRemote(path, Box::new(e))),
+        None => {
+            return Err(errors::RefreshError::NoDefaultRemote(path));
+        }
+    };
+    // the repository may have been cloned from a mirror, or be fetched from one now
+    let remote = if default_remote.url(Direction::Fetch) == Some(repo_url) {
+        default_remote
+    } else {
+        let refspecs = default_remote
+            .refspecs(Direction::Fetch)
+            .iter()
+            .map(|spec| spec.to_ref().to_bstring())
+            .collect::<Vec<_>>();
+
+        repo.remote_at(repo_url.clone())
+            .map_err(|e| errors::RefreshError::RemoteAt(repo_url.to_string(), Box::new(e)))?
+            .with_refspecs(refspecs, Direction::Fetch)
+            .map_err(|e| errors::RefreshError::RefSpecs(repo_url.to_string(), Box::new(e)))?
+    };
+
+    // the repository may have been cloned shallowly before its full history was needed
+    let shallow = if matches!(shallow, Shallow::NoChange) && repo.is_shallow() {
+        Shallow::undo()
+    } else {
+        shallow
+    };
+
+    let mut connection = match remote.connect(Direction::Fetch) {
+        Ok(connection) => connection,
+        Err(e) => {
+            return Err(errors::RefreshError::Connect(
+                repo_url.to_string(),
+                Box::new(e),
+            ))
+        }
+    };
+
+    use_reqwest(&mut connection, reqwest);
+    authenticate_conn(&mut connection, credentials);
+
+    let fetch = match connection.prepare_fetch(gix::progress::Discard, Default::default()) {
+        Ok(fetch) => fetch.with_shallow(shallow),
+        Err(e) => {
+            return Err(errors::RefreshError::PrepareFetch(
+                repo_url.to_string(),
+                Box::new(e),
+            ))
+        }
+    };
+
+    match fetch.receive(gix::progress::Discard, &false.into()) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(errors::RefreshError::Read(
+            repo_url.to_string(),
+            Box::new(e),
+        )),
+    }
+}
+
+/// Clones a repository as a bare repository
+fn clone(
+    path: &Path,
+    repo_url: gix::Url,
+    credentials: Option<Account>,
+    shallow: Shallow,
+    reqwest: reqwest::Client,
+) -> Result<(), errors::RefreshError> {
+    gix::prepare_clone_bare(repo_url.clone(), path)
+        .map_err(|e| errors::RefreshError::Clone(repo_url.to_string(), Box::new(e)))?
+        .with_shallow(shallow)
+        .configure_connection(move |c| {
+            use_reqwest(c, &reqwest);
+            authenticate_conn(c, credentials.clone());
+            Ok(())
+        })
+        .fetch_only(gix::progress::Discard, &false.into())
+        .map_err(|e| errors::RefreshError::Fetch(repo_url.to_string(), Box::new(e)))?;
+
+    Ok(())
 }
 
 /// Reads a file from a tree
@@ -126,9 +309,9 @@ pub fn read_file<
     Ok(Some(string))
 }
 
-/// Gets the root tree of a repository
+/// Gets the commit the root tree of a repository belongs to
 #[instrument(skip(repo), level = "trace")]
-pub fn root_tree(repo: &gix::Repository) -> Result<gix::Tree, errors::TreeError> {
+pub fn root_commit(repo: &gix::Repository) -> Result<gix::Commit<'_>, errors::TreeError> {
     // this is a bare repo, so this is the actual path
     let path = repo.path().to_path_buf();
 
@@ -170,7 +353,19 @@ pub fn root_tree(repo: &gix::Repository) -> Result<gix::Tree, errors::TreeError>
         Err(e) => return Err(errors::TreeError::CannotConvertToObject(id_str, e)),
     };
 
-    match object.peel_to_tree() {
+    match object.peel_to_commit() {
+        Ok(commit) => Ok(commit),
+        Err(e) => Err(errors::TreeError::CannotPeelToCommit(id_str, e)),
+    }
+}
+
+/// Gets the root tree of a repository
+#[instrument(skip(repo), level = "trace")]
+pub fn root_tree(repo: &gix::Repository) -> Result<gix::Tree<'_>, errors::TreeError> {
+    let commit = root_commit(repo)?;
+
+    let id_str = commit.id.to_string();
+    match gix::Object::from(commit).peel_to_tree() {
         Ok(tree) => Ok(tree),
         Err(e) => Err(errors::TreeError::CannotPeelToTree(id_str, e)),
     }
@@ -202,6 +397,14 @@ pub mod errors {
         #[error("error getting default remote from repository at {0}")]
         GetDefaultRemote(PathBuf, #[source] Box<gix::remote::find::existing::Error>),
 
+        /// Error creating a remote for a URL
+        #[error("error creating remote for {0}")]
+        RemoteAt(String, #[source] Box<gix::remote::init::Error>),
+
+        /// Error applying the default remote's refspecs to a remote
+        #[error("error applying refspecs to remote {0}")]
+        RefSpecs(String, #[source] Box<gix::refspec::parse::Error>),
+
         /// Error connecting to remote repository
         #[error("error connecting to remote repository at {0}")]
         Connect(String, #[source] Box<gix::remote::connect::Error>),
@@ -223,6 +426,46 @@ pub mod errors {
         Fetch(String, #[source] Box<gix::clone::fetch::Error>),
     }
 
+    impl RefreshError {
+        /// Whether the error may not occur again when retrying, such as a dropped connection
+        pub fn is_spurious(&self) -> bool {
+            use gix::protocol::transport::IsSpuriousError;
+
+            let spurious = match self {
+                RefreshError::Connect(_, e) => e.is_spurious(),
+                RefreshError::PrepareFetch(_, e) => e.is_spurious(),
+                RefreshError::Read(_, e) => e.is_spurious(),
+                RefreshError::Fetch(_, e) => match e.as_ref() {
+                    gix::clone::fetch::Error::Connect(e) => e.is_spurious(),
+                    gix::clone::fetch::Error::PrepareFetch(e) => e.is_spurious(),
+                    gix::clone::fetch::Error::Fetch(e) => e.is_spurious(),
+                    _ => false,
+                },
+                _ => false,
+            };
+
+            // gix's HTTP transport turns failed requests into IO errors only carrying the message
+            // of the underlying error, which aren't considered spurious by gix itself
+            spurious
+                || std::iter::successors(Some(self as &dyn std::error::Error), |e| e.source())
+                    .filter_map(|e| e.downcast_ref::<std::io::Error>())
+                    .any(|e| e.is_spurious() || e.to_string().starts_with("error sending request"))
+        }
+
+        /// Whether the repository couldn't be reached, so that it may be fetched from a mirror
+        pub fn is_unreachable(&self) -> bool {
+            self.is_spurious()
+                || matches!(
+                    self,
+                    RefreshError::Connect(..)
+                        | RefreshError::PrepareFetch(..)
+                        | RefreshError::Read(..)
+                        | RefreshError::Fetch(..)
+                        | RefreshError::Clone(..)
+                )
+        }
+    }
+
     /// Errors that can occur when reading a git-based package source's tree
     #[derive(Debug, Error)]
     #[non_exhaustive]
@@ -259,6 +502,10 @@ pub mod errors {
         #[error("error converting id {0} to object")]
         CannotConvertToObject(String, #[source] gix::object::find::existing::Error),
 
+        /// Error peeling object to commit in repository
+        #[error("error peeling object {0} to commit")]
+        CannotPeelToCommit(String, #[source] gix::object::peel::to_kind::Error),
+
         /// Error peeling object to tree in repository
         #[error("error peeling object {0} to tree")]
         CannotPeelToTree(String, #[source] gix::object::peel::to_kind::Error),
@@ -0,0 +1,363 @@
+use crate::{
+    manifest::target::{Target, TargetKind},
+    names::{PackageName, PackageNames},
+    reporters::DownloadProgressReporter,
+    source::{
+        fs::PackageFS,
+        local::{pkg_ref::LocalPackageRef, specifier::LocalDependencySpecifier},
+        pesde::{unpack_archive, IndexFile},
+        DependencySpecifiers, PackageSource, PackageSources, ResolveResult, VersionId,
+    },
+    Project,
+};
+use fs_err::tokio as fs;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+use tokio::io::BufReader;
+use tracing::instrument;
+
+/// The local registry package reference
+pub mod pkg_ref;
+/// The local registry dependency specifier
+pub mod specifier;
+
+/// The directory of a local registry containing the index files, laid out like a pesde index
+pub const INDEX_DIR: &str = "index";
+/// The directory of a local registry containing the package archives, laid out as
+/// `<scope>/<name>/<version>/<target>.tar.gz`
+pub const ARCHIVES_DIR: &str = "archives";
+
+/// A package source backed by a plain directory, for use without a network or git server
+/// (e.g. in tests or offline demos)
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
+pub struct LocalPackageSource {
+    path: PathBuf,
+}
+
+impl LocalPackageSource {
+    /// Creates a new local package source from the path of the registry directory
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The path of the registry directory. It's relative to the project's directory when the
+    /// source comes from a lockfile
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The path of the registry directory, resolved against the project's directory
+    fn registry_dir(&self, project: &Project) -> PathBuf {
+        project.package_dir().join(&self.path)
+    }
+
+    /// Reads the index file of a package, with every version published of it
+    pub async fn read_index_file(
+        &self,
+        name: &PackageName,
+        project: &Project,
+    ) -> Result<IndexFile, errors::ResolveError> {
+        let (scope, name_part) = name.as_str();
+        let path = self
+            .registry_dir(project)
+            .join(INDEX_DIR)
+            .join(scope)
+            .join(name_part);
+
+        let string = match fs::read_to_string(path).await {
+            Ok(s) => s,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(errors::ResolveError::NotFound(name.to_string()));
+            }
+            Err(e) => return Err(errors::ResolveError::Read(name.to_string(), e)),
+        };
+
+        toml::from_str(&string).map_err(|e| errors::ResolveError::Parse(name.to_string(), e))
+    }
+}
+
+/// The path of a registry directory relative to the project's directory, as it's stored in
+/// lockfiles so that they don't depend on where the project is located
+pub(crate) fn relative_registry_path(project: &Project, path: &Path) -> PathBuf {
+    pathdiff::diff_paths(path, project.package_dir()).unwrap_or_else(|| path.to_path_buf())
+}
+
+impl PackageSource for LocalPackageSource {
+    type Specifier = LocalDependencySpecifier;
+    type Ref = LocalPackageRef;
+    type RefreshError = errors::RefreshError;
+    type ResolveError = errors::ResolveError;
+    type DownloadError = errors::DownloadError;
+
+    async fn refresh(
+        &self,
+        _project: &Project,
+        _reqwest: &reqwest::Client,
+    ) -> Result<(), Self::RefreshError> {
+        // no-op
+        Ok(())
+    }
+
+    #[instrument(skip_all, level = "debug")]
+    async fn resolve(
+        &self,
+        specifier: &Self::Specifier,
+        project: &Project,
+        _reqwest: &reqwest::Client,
+        project_target: TargetKind,
+        _refreshed_sources: &mut HashSet<PackageSources>,
+    ) -> Result<ResolveResult<Self::Ref>, Self::ResolveError> {
+        let registry_dir = self.registry_dir(project);
+        let entries = self.read_index_file(&specifier.name, project).await?;
+
+        tracing::debug!("{} has {} possible entries", specifier.name, entries.len());
+
+        let mut entries = entries
+            .into_iter()
+            .filter(|(VersionId(version, target), entry)| {
+                !entry.yanked
+                    && specifier.version.matches(version)
+                    && match &specifier.target {
+                        Some(specifier_target) => specifier_target == target,
+                        None => project.is_target_compatible(&project_target, target),
+                    }
+            })
+            .collect::<IndexFile>();
+
+        // prefer the project's own target over compatible ones when a version has both
+        let exact_versions = entries
+            .keys()
+            .filter(|v_id| *v_id.target() == project_target)
+            .map(|v_id| v_id.version().clone())
+            .collect::<HashSet<_>>();
+        entries.retain(|v_id, _| {
+            *v_id.target() == project_target
+                || specifier.target.is_some()
+                || !exact_versions.contains(v_id.version())
+        });
+
+        Ok((
+            PackageNames::Pesde(specifier.name.clone()),
+            entries
+                .into_iter()
+                .map(|(id, entry)| {
+                    let version = id.version().clone();
+
+                    (
+                        id,
+                        LocalPackageRef {
+                            name: specifier.name.clone(),
+                            version,
+                            registry: relative_registry_path(project, &registry_dir),
+                            dependencies: entry
+                                .dependencies
+                                .into_iter()
+                                .map(|(alias, (mut spec, ty))| {
+                                    // registries in index files are relative to this registry
+                                    if let DependencySpecifiers::Local(spec) = &mut spec {
+                                        spec.registry = relative_registry_path(
+                                            project,
+                                            &registry_dir.join(&spec.registry),
+                                        );
+                                    }
+
+                                    (alias, (spec, ty))
+                                })
+                                .collect(),
+                            target: entry.target,
+                        },
+                    )
+                })
+                .collect(),
+        ))
+    }
+
+    #[instrument(skip_all, level = "debug")]
+    async fn download(
+        &self,
+        pkg_ref: &Self::Ref,
+        project: &Project,
+        _reqwest: &reqwest::Client,
+        reporter: &dyn DownloadProgressReporter,
+    ) -> Result<(PackageFS, Target), Self::DownloadError> {
+        let (scope, name) = pkg_ref.name.as_str();
+        let archive_path = self
+            .registry_dir(project)
+            .join(ARCHIVES_DIR)
+            .join(scope)
+            .join(name)
+            .join(pkg_ref.version.to_string())
+            .join(format!("{}.tar.gz", pkg_ref.target.kind()));
+
+        let file = fs::File::open(&archive_path)
+            .await
+            .map_err(errors::DownloadError::Read)?;
+
+        reporter.report_extract();
+
+        let entries = unpack_archive(project, BufReader::new(file))
+            .await
+            .map_err(|e| errors::DownloadError::Unpack(Box::new(e)))?;
+
+        Ok((PackageFS::CAS(entries), pkg_ref.target.clone()))
+    }
+}
+
+/// Errors that can occur when interacting with a local package source
+pub mod errors {
+    use thiserror::Error;
+
+    /// Errors that can occur when refreshing a local package source
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum RefreshError {}
+
+    /// Errors that can occur when resolving a package from a local package source
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum ResolveError {
+        /// Package not found in the registry
+        #[error("package {0} not found")]
+        NotFound(String),
+
+        /// Error reading the index file for the package
+        #[error("error reading index file for {0}")]
+        Read(String, #[source] std::io::Error),
+
+        /// Error parsing the index file for the package
+        #[error("error parsing index file for {0}")]
+        Parse(String, #[source] toml::de::Error),
+    }
+
+    /// Errors that can occur when downloading a package from a local package source
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum DownloadError {
+        /// Error reading the package's archive
+        #[error("error reading package archive")]
+        Read(#[source] std::io::Error),
+
+        /// Error unpacking the package's archive
+        #[error("error unpacking package archive")]
+        Unpack(#[source] Box<crate::source::pesde::errors::DownloadError>),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        names::PackageName,
+        source::{traits::PackageRef, version_id::VersionId},
+        AuthConfig,
+    };
+    use async_compression::tokio::write::GzipEncoder;
+    use tokio::io::AsyncWriteExt;
+
+    const INDEX_FILE: &str = r#"
+["1.0.0 luau"]
+target = { environment = "luau", lib = "init.luau" }
+
+["1.0.0 luau".dependencies]
+bar = [{ local = "acme/bar", version = "^1.0.0", registry = "../other" }, "standard"]
+"#;
+
+    async fn write_archive(path: &Path, files: &[(&str, &str)]) {
+        fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+
+        let mut archive = tokio_tar::Builder::new(GzipEncoder::new(Vec::new()));
+        for (name, contents) in files {
+            let mut header = tokio_tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            archive
+                .append_data(&mut header, name, contents.as_bytes())
+                .await
+                .unwrap();
+        }
+
+        let mut encoder = archive.into_inner().await.unwrap();
+        encoder.shutdown().await.unwrap();
+        fs::write(path, encoder.into_inner()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn lockfile_registry_paths_are_relative() {
+        let root = tempfile::tempdir().unwrap();
+        let package_dir = root.path().join("project");
+        let registry_dir = root.path().join("registry");
+        fs::create_dir_all(&package_dir).await.unwrap();
+
+        fs::create_dir_all(registry_dir.join(INDEX_DIR).join("acme"))
+            .await
+            .unwrap();
+        fs::write(
+            registry_dir.join(INDEX_DIR).join("acme").join("foo"),
+            INDEX_FILE,
+        )
+        .await
+        .unwrap();
+        write_archive(
+            &registry_dir
+                .join(ARCHIVES_DIR)
+                .join("acme")
+                .join("foo")
+                .join("1.0.0")
+                .join("luau.tar.gz"),
+            &[("init.luau", "return {}\n")],
+        )
+        .await;
+
+        let project = Project::new(
+            &package_dir,
+            None::<PathBuf>,
+            root.path().join("data"),
+            root.path().join("cas"),
+            AuthConfig::new(),
+        );
+        let reqwest = reqwest::Client::new();
+
+        let specifier = LocalDependencySpecifier {
+            name: "acme/foo".parse::<PackageName>().unwrap(),
+            version: "^1.0.0".parse().unwrap(),
+            registry: "../registry".into(),
+            target: None,
+            platform: Default::default(),
+        };
+        let (_, mut versions) = LocalPackageSource::new(package_dir.join(&specifier.registry))
+            .resolve(
+                &specifier,
+                &project,
+                &reqwest,
+                TargetKind::Luau,
+                &mut HashSet::new(),
+            )
+            .await
+            .unwrap();
+        let (version_id, pkg_ref) = versions.pop_last().unwrap();
+        assert_eq!(version_id, "1.0.0 luau".parse::<VersionId>().unwrap());
+
+        assert_eq!(pkg_ref.registry, Path::new("../registry"));
+        let Some((DependencySpecifiers::Local(dependency), _)) = pkg_ref.dependencies.get("bar")
+        else {
+            panic!("expected a local dependency");
+        };
+        assert_eq!(dependency.registry, Path::new("../registry/../other"));
+
+        // the source of a locked package is resolved against the project again to download it
+        let PackageSources::Local(source) = pkg_ref.source() else {
+            panic!("expected a local source");
+        };
+        let (PackageFS::CAS(entries), _) = source
+            .download(&pkg_ref, &project, &reqwest, &())
+            .await
+            .unwrap()
+        else {
+            panic!("expected the package to be stored in the CAS");
+        };
+        assert!(entries.contains_key(relative_path::RelativePath::new("init.luau")));
+    }
+}
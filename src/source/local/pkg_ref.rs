@@ -0,0 +1,39 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    manifest::{target::Target, DependencyType},
+    names::PackageName,
+    source::{local::LocalPackageSource, DependencySpecifiers, PackageRef, PackageSources},
+};
+
+/// A local registry package reference
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct LocalPackageRef {
+    /// The name of the package
+    pub name: PackageName,
+    /// The version of the package
+    pub version: Version,
+    /// The path of the registry directory, relative to the project's directory
+    pub registry: PathBuf,
+    /// The dependencies of the package
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependencies: BTreeMap<String, (DependencySpecifiers, DependencyType)>,
+    /// The target of the package
+    pub target: Target,
+}
+impl PackageRef for LocalPackageRef {
+    fn dependencies(&self) -> &BTreeMap<String, (DependencySpecifiers, DependencyType)> {
+        &self.dependencies
+    }
+
+    fn use_new_structure(&self) -> bool {
+        true
+    }
+
+    fn source(&self) -> PackageSources {
+        PackageSources::Local(LocalPackageSource::new(self.registry.clone()))
+    }
+}
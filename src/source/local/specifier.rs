@@ -0,0 +1,34 @@
+use crate::{
+    manifest::{platform::PlatformCondition, target::TargetKind},
+    names::PackageName,
+    source::DependencySpecifier,
+};
+use semver::VersionReq;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, path::PathBuf};
+
+/// The specifier for a local registry dependency
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct LocalDependencySpecifier {
+    /// The name of the package
+    #[serde(rename = "local")]
+    pub name: PackageName,
+    /// The version requirement for the package
+    pub version: VersionReq,
+    /// The path of the registry directory. In manifests and lockfiles this is relative to the
+    /// package's directory, and in the registry's index files relative to the registry itself
+    pub registry: PathBuf,
+    /// The target to use for the package
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<TargetKind>,
+    /// The platform this dependency is restricted to
+    #[serde(flatten)]
+    pub platform: PlatformCondition,
+}
+impl DependencySpecifier for LocalDependencySpecifier {}
+
+impl Display for LocalDependencySpecifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@local:{}", self.name, self.version)
+    }
+}
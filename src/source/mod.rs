@@ -1,6 +1,7 @@
 use crate::{
     manifest::target::{Target, TargetKind},
     names::PackageNames,
+    reporters::DownloadProgressReporter,
     source::{
         fs::PackageFS, refs::PackageRefs, specifiers::DependencySpecifiers, traits::*,
         version_id::VersionId,
@@ -9,15 +10,23 @@ use crate::{
 };
 use std::{
     collections::{BTreeMap, HashSet},
-    fmt::Debug,
+    fmt::{Debug, Display, Formatter},
+    path::Path,
+    time::{Duration, SystemTime},
 };
 
+/// Custom package sources implemented outside of pesde
+pub mod custom;
 /// Packages' filesystems
 pub mod fs;
 /// The Git package source
 pub mod git;
+/// An HTTP backend for fetching Git repositories with the project's HTTP client
+pub(crate) mod git_http;
 /// Git index-based package source utilities
 pub mod git_index;
+/// The local registry package source
+pub mod local;
 /// The pesde package source
 pub mod pesde;
 /// Package references
@@ -43,6 +52,60 @@ pub const IGNORED_DIRS: &[&str] = &[".git"];
 /// The result of resolving a package
 pub type ResolveResult<Ref> = (PackageNames, BTreeMap<VersionId, Ref>);
 
+/// The file in the data directory whose modification time marks when all cached indices were invalidated
+const INDICES_INVALIDATED_FILE: &str = "indices_invalidated";
+
+/// How cached indices are refreshed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RefreshPolicy {
+    /// Indices are always refreshed
+    #[default]
+    Always,
+    /// Indices are only refreshed if they haven't been within the given duration
+    IfOlderThan(Duration),
+    /// Indices are never refreshed, only fetched if they aren't cached yet
+    Never,
+}
+
+async fn modified_at(path: &Path) -> Option<SystemTime> {
+    fs_err::tokio::metadata(path)
+        .await
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Whether cached index data needs to be refreshed according to the project's refresh policy,
+/// judging by when the file at the path was last written
+pub(crate) async fn is_stale(project: &Project, path: &Path) -> bool {
+    let max_age = match project.refresh_policy() {
+        RefreshPolicy::Always => return true,
+        RefreshPolicy::IfOlderThan(max_age) => Some(max_age),
+        RefreshPolicy::Never => None,
+    };
+
+    let Some(refreshed_at) = modified_at(path).await else {
+        return true;
+    };
+
+    let Some(max_age) = max_age else {
+        return false;
+    };
+
+    if refreshed_at.elapsed().map_or(true, |age| age > max_age) {
+        return true;
+    }
+
+    modified_at(&project.data_dir().join(INDICES_INVALIDATED_FILE))
+        .await
+        .is_some_and(|invalidated_at| invalidated_at >= refreshed_at)
+}
+
+/// Marks all cached indices as stale, so that they are refreshed the next time they're used
+/// unless the refresh policy is [`RefreshPolicy::Never`]
+pub async fn invalidate_indices(project: &Project) -> std::io::Result<()> {
+    fs_err::tokio::write(project.data_dir().join(INDICES_INVALIDATED_FILE), "").await
+}
+
 /// All possible package sources
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub enum PackageSources {
@@ -55,6 +118,30 @@ pub enum PackageSources {
     Git(git::GitPackageSource),
     /// A workspace package source
     Workspace(workspace::WorkspacePackageSource),
+    /// A local registry package source
+    Local(local::LocalPackageSource),
+    /// A custom package source
+    Custom(custom::CustomPackageSource),
+}
+
+impl Display for PackageSources {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackageSources::Pesde(source) => {
+                write!(f, "{}", git_index::GitBasedSource::repo_url(source))
+            }
+            #[cfg(feature = "wally-compat")]
+            PackageSources::Wally(source) => {
+                write!(f, "{}", git_index::GitBasedSource::repo_url(source))
+            }
+            PackageSources::Git(source) => {
+                write!(f, "{}", git_index::GitBasedSource::repo_url(source))
+            }
+            PackageSources::Workspace(_) => write!(f, "workspace"),
+            PackageSources::Local(source) => write!(f, "{}", source.path().display()),
+            PackageSources::Custom(source) => write!(f, "custom source {}", source.name()),
+        }
+    }
 }
 
 impl PackageSource for PackageSources {
@@ -64,13 +151,31 @@ impl PackageSource for PackageSources {
     type ResolveError = errors::ResolveError;
     type DownloadError = errors::DownloadError;
 
-    async fn refresh(&self, project: &Project) -> Result<(), Self::RefreshError> {
+    async fn refresh(
+        &self,
+        project: &Project,
+        reqwest: &reqwest::Client,
+    ) -> Result<(), Self::RefreshError> {
         match self {
-            PackageSources::Pesde(source) => source.refresh(project).await.map_err(Into::into),
+            PackageSources::Pesde(source) => {
+                source.refresh(project, reqwest).await.map_err(Into::into)
+            }
             #[cfg(feature = "wally-compat")]
-            PackageSources::Wally(source) => source.refresh(project).await.map_err(Into::into),
-            PackageSources::Git(source) => source.refresh(project).await.map_err(Into::into),
-            PackageSources::Workspace(source) => source.refresh(project).await.map_err(Into::into),
+            PackageSources::Wally(source) => {
+                source.refresh(project, reqwest).await.map_err(Into::into)
+            }
+            PackageSources::Git(source) => {
+                source.refresh(project, reqwest).await.map_err(Into::into)
+            }
+            PackageSources::Workspace(source) => {
+                source.refresh(project, reqwest).await.map_err(Into::into)
+            }
+            PackageSources::Local(source) => {
+                source.refresh(project, reqwest).await.map_err(Into::into)
+            }
+            PackageSources::Custom(source) => {
+                source.refresh(project, reqwest).await.map_err(Into::into)
+            }
         }
     }
 
@@ -78,12 +183,19 @@ impl PackageSource for PackageSources {
         &self,
         specifier: &Self::Specifier,
         project: &Project,
+        reqwest: &reqwest::Client,
         project_target: TargetKind,
         refreshed_sources: &mut HashSet<PackageSources>,
     ) -> Result<ResolveResult<Self::Ref>, Self::ResolveError> {
         match (self, specifier) {
             (PackageSources::Pesde(source), DependencySpecifiers::Pesde(specifier)) => source
-                .resolve(specifier, project, project_target, refreshed_sources)
+                .resolve(
+                    specifier,
+                    project,
+                    reqwest,
+                    project_target,
+                    refreshed_sources,
+                )
                 .await
                 .map(|(name, results)| {
                     (
@@ -98,7 +210,13 @@ impl PackageSource for PackageSources {
 
             #[cfg(feature = "wally-compat")]
             (PackageSources::Wally(source), DependencySpecifiers::Wally(specifier)) => source
-                .resolve(specifier, project, project_target, refreshed_sources)
+                .resolve(
+                    specifier,
+                    project,
+                    reqwest,
+                    project_target,
+                    refreshed_sources,
+                )
                 .await
                 .map(|(name, results)| {
                     (
@@ -112,7 +230,13 @@ impl PackageSource for PackageSources {
                 .map_err(Into::into),
 
             (PackageSources::Git(source), DependencySpecifiers::Git(specifier)) => source
-                .resolve(specifier, project, project_target, refreshed_sources)
+                .resolve(
+                    specifier,
+                    project,
+                    reqwest,
+                    project_target,
+                    refreshed_sources,
+                )
                 .await
                 .map(|(name, results)| {
                     (
@@ -127,7 +251,13 @@ impl PackageSource for PackageSources {
 
             (PackageSources::Workspace(source), DependencySpecifiers::Workspace(specifier)) => {
                 source
-                    .resolve(specifier, project, project_target, refreshed_sources)
+                    .resolve(
+                        specifier,
+                        project,
+                        reqwest,
+                        project_target,
+                        refreshed_sources,
+                    )
                     .await
                     .map(|(name, results)| {
                         (
@@ -143,6 +273,46 @@ impl PackageSource for PackageSources {
                     .map_err(Into::into)
             }
 
+            (PackageSources::Local(source), DependencySpecifiers::Local(specifier)) => source
+                .resolve(
+                    specifier,
+                    project,
+                    reqwest,
+                    project_target,
+                    refreshed_sources,
+                )
+                .await
+                .map(|(name, results)| {
+                    (
+                        name,
+                        results
+                            .into_iter()
+                            .map(|(version, pkg_ref)| (version, PackageRefs::Local(pkg_ref)))
+                            .collect(),
+                    )
+                })
+                .map_err(Into::into),
+
+            (PackageSources::Custom(source), DependencySpecifiers::Custom(specifier)) => source
+                .resolve(
+                    specifier,
+                    project,
+                    reqwest,
+                    project_target,
+                    refreshed_sources,
+                )
+                .await
+                .map(|(name, results)| {
+                    (
+                        name,
+                        results
+                            .into_iter()
+                            .map(|(version, pkg_ref)| (version, PackageRefs::Custom(pkg_ref)))
+                            .collect(),
+                    )
+                })
+                .map_err(Into::into),
+
             _ => Err(errors::ResolveError::Mismatch),
         }
     }
@@ -152,26 +322,37 @@ impl PackageSource for PackageSources {
         pkg_ref: &Self::Ref,
         project: &Project,
         reqwest: &reqwest::Client,
+        reporter: &dyn DownloadProgressReporter,
     ) -> Result<(PackageFS, Target), Self::DownloadError> {
         match (self, pkg_ref) {
             (PackageSources::Pesde(source), PackageRefs::Pesde(pkg_ref)) => source
-                .download(pkg_ref, project, reqwest)
+                .download(pkg_ref, project, reqwest, reporter)
                 .await
                 .map_err(Into::into),
 
             #[cfg(feature = "wally-compat")]
             (PackageSources::Wally(source), PackageRefs::Wally(pkg_ref)) => source
-                .download(pkg_ref, project, reqwest)
+                .download(pkg_ref, project, reqwest, reporter)
                 .await
                 .map_err(Into::into),
 
             (PackageSources::Git(source), PackageRefs::Git(pkg_ref)) => source
-                .download(pkg_ref, project, reqwest)
+                .download(pkg_ref, project, reqwest, reporter)
                 .await
                 .map_err(Into::into),
 
             (PackageSources::Workspace(source), PackageRefs::Workspace(pkg_ref)) => source
-                .download(pkg_ref, project, reqwest)
+                .download(pkg_ref, project, reqwest, reporter)
+                .await
+                .map_err(Into::into),
+
+            (PackageSources::Local(source), PackageRefs::Local(pkg_ref)) => source
+                .download(pkg_ref, project, reqwest, reporter)
+                .await
+                .map_err(Into::into),
+
+            (PackageSources::Custom(source), PackageRefs::Custom(pkg_ref)) => source
+                .download(pkg_ref, project, reqwest, reporter)
                 .await
                 .map_err(Into::into),
 
@@ -192,9 +373,21 @@ pub mod errors {
         #[error("error refreshing pesde package source")]
         GitBased(#[from] crate::source::git_index::errors::RefreshError),
 
+        /// A pesde package source failed to refresh
+        #[error("error refreshing pesde package source")]
+        Pesde(#[from] crate::source::pesde::errors::RefreshError),
+
         /// A workspace package source failed to refresh
         #[error("error refreshing workspace package source")]
         Workspace(#[from] crate::source::workspace::errors::RefreshError),
+
+        /// A local package source failed to refresh
+        #[error("error refreshing local package source")]
+        Local(#[from] crate::source::local::errors::RefreshError),
+
+        /// A custom package source failed to refresh
+        #[error("error refreshing custom package source")]
+        Custom(#[from] crate::source::custom::errors::RefreshError),
     }
 
     /// Errors that can occur when resolving a package
@@ -221,6 +414,14 @@ pub mod errors {
         /// A workspace package source failed to resolve
         #[error("error resolving workspace package")]
         Workspace(#[from] crate::source::workspace::errors::ResolveError),
+
+        /// A local package source failed to resolve
+        #[error("error resolving local package")]
+        Local(#[from] crate::source::local::errors::ResolveError),
+
+        /// A custom package source failed to resolve
+        #[error("error resolving custom package")]
+        Custom(#[from] crate::source::custom::errors::ResolveError),
     }
 
     /// Errors that can occur when downloading a package
@@ -247,5 +448,13 @@ pub mod errors {
         /// A workspace package source failed to download
         #[error("error downloading workspace package")]
         Workspace(#[from] crate::source::workspace::errors::DownloadError),
+
+        /// A local package source failed to download
+        #[error("error downloading local package")]
+        Local(#[from] crate::source::local::errors::DownloadError),
+
+        /// A custom package source failed to download
+        #[error("error downloading custom package")]
+        Custom(#[from] crate::source::custom::errors::DownloadError),
     }
 }
@@ -1,12 +1,17 @@
 use gix::Url;
 use relative_path::RelativePathBuf;
-use reqwest::header::{ACCEPT, AUTHORIZATION};
+use reqwest::{
+    header::{ACCEPT, AUTHORIZATION, CONTENT_RANGE, ETAG, IF_NONE_MATCH, RANGE},
+    RequestBuilder, StatusCode,
+};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{BTreeMap, BTreeSet, HashSet},
-    fmt::Debug,
+    fmt::{Debug, Display},
     hash::Hash,
-    path::PathBuf,
+    io::SeekFrom,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
 };
 
 use pkg_ref::PesdePackageRef;
@@ -15,25 +20,32 @@ use specifier::PesdeDependencySpecifier;
 use crate::{
     manifest::{
         target::{Target, TargetKind},
-        DependencyType,
+        DependencyType, Maintainer, Metadata, SignaturePolicy,
     },
     names::{PackageName, PackageNames},
+    reporters::DownloadProgressReporter,
     source::{
         fs::{store_in_cas, FSEntry, PackageFS},
         git_index::{read_file, root_tree, GitBasedSource},
-        DependencySpecifiers, PackageSource, PackageSources, ResolveResult, VersionId,
+        is_stale, DependencySpecifiers, PackageSource, PackageSources, ResolveResult, VersionId,
         IGNORED_DIRS, IGNORED_FILES,
     },
-    util::hash,
+    util::{hash, hash_file},
     Project,
 };
+use async_compression::Level;
 use fs_err::tokio as fs;
 use futures::StreamExt;
-use tokio::task::spawn_blocking;
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncSeekExt, AsyncWriteExt, BufReader},
+    task::spawn_blocking,
+};
 use tracing::instrument;
 
 /// The pesde package reference
 pub mod pkg_ref;
+/// Signing and verifying packages' archives
+pub mod signing;
 /// The pesde dependency specifier
 pub mod specifier;
 
@@ -41,16 +53,66 @@ pub mod specifier;
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub struct PesdePackageSource {
     repo_url: Url,
+    full_history: bool,
 }
 
 /// The file containing scope information
 pub const SCOPE_INFO_FILE: &str = "scope.toml";
 
+/// The prefix of index URLs which use the sparse HTTP protocol instead of git
+pub const SPARSE_INDEX_PREFIX: &str = "sparse+";
+
+/// The directory of the CAS containing partially downloaded archives, named by their checksums
+const PARTIAL_DOWNLOADS_DIR: &str = ".partial";
+
 /// Information about a scope
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ScopeInfo {
     /// The people authorized to publish packages to this scope
     pub owners: BTreeSet<u64>,
+    /// The GitHub teams, as `org/team`, whose members are authorized to publish packages to this
+    /// scope
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub teams: BTreeSet<String>,
+    /// The people authorized to publish packages to this scope, without managing them
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub publishers: BTreeSet<u64>,
+    /// The GitHub teams, as `org/team`, whose members are authorized to publish packages to this
+    /// scope, without managing them
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub publisher_teams: BTreeSet<String>,
+    /// The owners of specific packages of this scope, by the packages' names
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub packages: BTreeMap<String, PackageOwners>,
+}
+
+/// The owners of a package, who are authorized to publish it in addition to the owners of its
+/// scope
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackageOwners {
+    /// The people authorized to publish this package
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub owners: BTreeSet<u64>,
+    /// The GitHub teams, as `org/team`, whose members are authorized to publish this package
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub teams: BTreeSet<String>,
+    /// The people authorized to publish this package, without managing it
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub publishers: BTreeSet<u64>,
+    /// The GitHub teams, as `org/team`, whose members are authorized to publish this package,
+    /// without managing it
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub publisher_teams: BTreeSet<String>,
+}
+
+impl PackageOwners {
+    /// Whether the package has no owners or publishers
+    pub fn is_empty(&self) -> bool {
+        self.owners.is_empty()
+            && self.teams.is_empty()
+            && self.publishers.is_empty()
+            && self.publisher_teams.is_empty()
+    }
 }
 
 impl GitBasedSource for PesdePackageSource {
@@ -61,24 +123,208 @@ impl GitBasedSource for PesdePackageSource {
     fn repo_url(&self) -> &Url {
         &self.repo_url
     }
+
+    fn shallow(&self) -> bool {
+        !self.full_history
+    }
+
+    async fn git_mirrors(&self, project: &Project) -> Vec<Url> {
+        // the index's own mirrors are known from the copy cached by a previous refresh
+        self.mirrors(project)
+            .await
+            .into_iter()
+            .filter(|mirror| sparse_base_url(mirror).is_none())
+            .collect()
+    }
 }
 
 impl PesdePackageSource {
     /// Creates a new pesde package source
     pub fn new(repo_url: Url) -> Self {
-        Self { repo_url }
+        Self {
+            repo_url,
+            full_history: false,
+        }
+    }
+
+    /// Keeps the full history of the index when refreshing it, instead of only its latest commit.
+    /// Commits can't be pushed from shallow clones, so this is needed to modify the index
+    pub fn with_full_history(mut self) -> Self {
+        self.full_history = true;
+        self
     }
 
     fn as_bytes(&self) -> Vec<u8> {
         self.repo_url.to_bstring().to_vec()
     }
 
+    /// Whether this source uses the sparse HTTP protocol instead of cloning the git index
+    pub fn is_sparse(&self) -> bool {
+        self.sparse_base_url().is_some()
+    }
+
+    fn sparse_base_url(&self) -> Option<String> {
+        sparse_base_url(&self.repo_url)
+    }
+
+    /// The mirrors of this index, both declared by the index and configured by the user
+    async fn mirrors(&self, project: &Project) -> Vec<Url> {
+        let mut mirrors = self
+            .config(project)
+            .await
+            .map(|config| config.mirrors)
+            .unwrap_or_default();
+        mirrors.extend(project.mirrors(&self.repo_url).iter().cloned());
+        mirrors
+    }
+
+    /// Fetches a file from a sparse index, falling back to the index's sparse mirrors if it can't be reached
+    async fn fetch_sparse_file(
+        &self,
+        project: &Project,
+        reqwest: &reqwest::Client,
+        base_url: &str,
+        file_path: &[&str],
+    ) -> Result<Option<String>, errors::SparseError> {
+        let err = match self
+            .fetch_sparse_file_from(project, reqwest, &self.repo_url, base_url, file_path)
+            .await
+        {
+            Err(errors::SparseError::Request(e)) if is_network_error(&e) => e,
+            res => return res,
+        };
+
+        for mirror in self.mirrors(project).await {
+            let Some(mirror_base_url) = sparse_base_url(&mirror) else {
+                continue;
+            };
+
+            tracing::warn!("failed to reach {}, trying mirror {mirror}", self.repo_url);
+
+            match self
+                .fetch_sparse_file_from(project, reqwest, &mirror, &mirror_base_url, file_path)
+                .await
+            {
+                Err(errors::SparseError::Request(e)) if is_network_error(&e) => {
+                    tracing::warn!("failed to reach mirror {mirror}: {e}");
+                }
+                res => return res,
+            }
+        }
+
+        Err(err.into())
+    }
+
+    /// Fetches a file from a sparse index, reusing the cached copy if the server reports it as unchanged
+    #[instrument(skip(self, project, reqwest, base_url), level = "debug")]
+    async fn fetch_sparse_file_from(
+        &self,
+        project: &Project,
+        reqwest: &reqwest::Client,
+        index_url: &Url,
+        base_url: &str,
+        file_path: &[&str],
+    ) -> Result<Option<String>, errors::SparseError> {
+        let cache_path = file_path
+            .iter()
+            .fold(self.path(project), |path, part| path.join(part));
+        let etag_path = cache_path.with_file_name(format!(
+            "{}.etag",
+            file_path.last().copied().unwrap_or_default()
+        ));
+
+        let cached = match fs::read_to_string(&cache_path).await {
+            Ok(s) => Some(s),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(errors::SparseError::Io(e)),
+        };
+
+        let url = format!("{base_url}/{}", file_path.join("/"));
+
+        if cached.is_some() && !is_stale(project, &cache_path).await {
+            tracing::debug!("using recently fetched copy of {url}");
+            return Ok(cached);
+        }
+        let mut request = reqwest.get(&url);
+
+        if cached.is_some() {
+            if let Ok(etag) = fs::read_to_string(&etag_path).await {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+        }
+
+        if let Some(token) = project.auth_config.tokens().get(index_url) {
+            tracing::debug!("using token for {index_url}");
+            request = request.header(AUTHORIZATION, token);
+        }
+
+        let response = project.retry_policy().send(request).await?;
+
+        match response.status() {
+            StatusCode::NOT_MODIFIED => {
+                if let Some(cached) = cached {
+                    tracing::debug!("using cached copy of {url}");
+                    // rewritten to mark the copy as fresh
+                    fs::write(&cache_path, &cached).await?;
+                    return Ok(Some(cached));
+                }
+            }
+            StatusCode::NOT_FOUND => {
+                for path in [&cache_path, &etag_path] {
+                    match fs::remove_file(path).await {
+                        Ok(_) => {}
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                        Err(e) => return Err(errors::SparseError::Io(e)),
+                    }
+                }
+
+                return Ok(None);
+            }
+            _ => {}
+        }
+
+        let response = response.error_for_status()?;
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_string);
+        let contents = response.text().await?;
+
+        if let Some(parent) = cache_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&cache_path, &contents).await?;
+
+        match etag {
+            Some(etag) => fs::write(&etag_path, etag).await?,
+            None => match fs::remove_file(&etag_path).await {
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(errors::SparseError::Io(e)),
+            },
+        }
+
+        Ok(Some(contents))
+    }
+
     /// Reads the config file
     #[instrument(skip_all, ret(level = "trace"), level = "debug")]
     pub async fn config(&self, project: &Project) -> Result<IndexConfig, errors::ConfigError> {
         let repo_url = self.repo_url.clone();
         let path = self.path(project);
 
+        if self.is_sparse() {
+            // the config file is fetched when the source is refreshed
+            return match fs::read_to_string(path.join("config.toml")).await {
+                Ok(s) => toml::from_str(&s).map_err(Into::into),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    Err(errors::ConfigError::Missing(Box::new(repo_url)))
+                }
+                Err(e) => Err(errors::ConfigError::ReadCache(e)),
+            };
+        }
+
         spawn_blocking(move || {
             let repo = gix::open(&path).map_err(Box::new)?;
             let tree = root_tree(&repo).map_err(Box::new)?;
@@ -92,18 +338,68 @@ impl PesdePackageSource {
         .await
         .unwrap()
     }
+
+    /// Reads the index file of a package, with every version published of it
+    pub async fn read_index_file(
+        &self,
+        name: &PackageName,
+        project: &Project,
+        reqwest: &reqwest::Client,
+    ) -> Result<IndexFile, errors::ResolveError> {
+        let (scope, name_part) = name.as_str();
+        let string = match self.sparse_base_url() {
+            Some(base_url) => self
+                .fetch_sparse_file(project, reqwest, &base_url, &[scope, name_part])
+                .await
+                .map_err(|e| errors::ResolveError::Sparse(name.to_string(), Box::new(e)))?,
+            None => {
+                let path = self.path(project);
+                let package_name = name.clone();
+
+                // reading from the repository is blocking, so keep it off the async runtime
+                spawn_blocking(move || {
+                    let (scope, name) = package_name.as_str();
+                    let repo = gix::open(&path).map_err(Box::new)?;
+                    let tree = root_tree(&repo).map_err(Box::new)?;
+                    read_file(&tree, [scope, name]).map_err(|e| {
+                        errors::ResolveError::Read(package_name.to_string(), Box::new(e))
+                    })
+                })
+                .await
+                .unwrap()?
+            }
+        };
+        let Some(string) = string else {
+            return Err(errors::ResolveError::NotFound(name.to_string()));
+        };
+
+        toml::from_str(&string).map_err(|e| errors::ResolveError::Parse(name.to_string(), e))
+    }
 }
 
 impl PackageSource for PesdePackageSource {
     type Specifier = PesdeDependencySpecifier;
     type Ref = PesdePackageRef;
-    type RefreshError = crate::source::git_index::errors::RefreshError;
+    type RefreshError = errors::RefreshError;
     type ResolveError = errors::ResolveError;
     type DownloadError = errors::DownloadError;
 
     #[instrument(skip_all, level = "debug")]
-    async fn refresh(&self, project: &Project) -> Result<(), Self::RefreshError> {
-        GitBasedSource::refresh(self, project).await
+    async fn refresh(
+        &self,
+        project: &Project,
+        reqwest: &reqwest::Client,
+    ) -> Result<(), Self::RefreshError> {
+        match self.sparse_base_url() {
+            Some(base_url) => self
+                .fetch_sparse_file(project, reqwest, &base_url, &["config.toml"])
+                .await
+                .map(|_| ())
+                .map_err(Into::into),
+            None => GitBasedSource::refresh(self, project, reqwest)
+                .await
+                .map_err(Into::into),
+        }
     }
 
     #[instrument(skip_all, level = "debug")]
@@ -111,36 +407,46 @@ impl PackageSource for PesdePackageSource {
         &self,
         specifier: &Self::Specifier,
         project: &Project,
+        reqwest: &reqwest::Client,
         project_target: TargetKind,
         _refreshed_sources: &mut HashSet<PackageSources>,
     ) -> Result<ResolveResult<Self::Ref>, Self::ResolveError> {
-        let (scope, name) = specifier.name.as_str();
-        let repo = gix::open(self.path(project)).map_err(Box::new)?;
-        let tree = root_tree(&repo).map_err(Box::new)?;
-        let string = match read_file(&tree, [scope, name]) {
-            Ok(Some(s)) => s,
-            Ok(None) => return Err(Self::ResolveError::NotFound(specifier.name.to_string())),
-            Err(e) => {
-                return Err(Self::ResolveError::Read(
-                    specifier.name.to_string(),
-                    Box::new(e),
-                ))
-            }
-        };
-
-        let entries: IndexFile = toml::from_str(&string)
-            .map_err(|e| Self::ResolveError::Parse(specifier.name.to_string(), e))?;
+        let entries = self
+            .read_index_file(&specifier.name, project, reqwest)
+            .await?;
 
         tracing::debug!("{} has {} possible entries", specifier.name, entries.len());
 
+        let mut entries = entries
+            .into_iter()
+            // yanked versions are kept by projects which already locked them, as those don't
+            // resolve their dependencies again, but are never picked for new resolutions
+            .filter(|(VersionId(version, target), entry)| {
+                !entry.yanked
+                    && specifier.version.matches(version)
+                    && match &specifier.target {
+                        Some(specifier_target) => specifier_target == target,
+                        None => project.is_target_compatible(&project_target, target),
+                    }
+            })
+            .collect::<IndexFile>();
+
+        // prefer the project's own target over compatible ones when a version has both
+        let exact_versions = entries
+            .keys()
+            .filter(|v_id| *v_id.target() == project_target)
+            .map(|v_id| v_id.version().clone())
+            .collect::<HashSet<_>>();
+        entries.retain(|v_id, _| {
+            *v_id.target() == project_target
+                || specifier.target.is_some()
+                || !exact_versions.contains(v_id.version())
+        });
+
         Ok((
             PackageNames::Pesde(specifier.name.clone()),
             entries
                 .into_iter()
-                .filter(|(VersionId(version, target), _)| {
-                    specifier.version.matches(version)
-                        && specifier.target.unwrap_or(project_target) == *target
-                })
                 .map(|(id, entry)| {
                     let version = id.version().clone();
 
@@ -152,6 +458,8 @@ impl PackageSource for PesdePackageSource {
                             index_url: self.repo_url.clone(),
                             dependencies: entry.dependencies,
                             target: entry.target,
+                            checksum: entry.checksum,
+                            signature: entry.signature,
                         },
                     )
                 })
@@ -165,14 +473,10 @@ impl PackageSource for PesdePackageSource {
         pkg_ref: &Self::Ref,
         project: &Project,
         reqwest: &reqwest::Client,
+        reporter: &dyn DownloadProgressReporter,
     ) -> Result<(PackageFS, Target), Self::DownloadError> {
         let config = self.config(project).await.map_err(Box::new)?;
-        let index_file = project
-            .cas_dir
-            .join("index")
-            .join(pkg_ref.name.escaped())
-            .join(pkg_ref.version.to_string())
-            .join(pkg_ref.target.to_string());
+        let index_file = pkg_ref.index_file(project);
 
         match fs::read_to_string(&index_file).await {
             Ok(s) => {
@@ -188,74 +492,586 @@ impl PackageSource for PesdePackageSource {
             Err(e) => return Err(errors::DownloadError::ReadIndex(e)),
         }
 
-        let url = config
-            .download()
-            .replace("{PACKAGE}", &pkg_ref.name.to_string().replace("/", "%2F"))
-            .replace("{PACKAGE_VERSION}", &pkg_ref.version.to_string())
-            .replace("{PACKAGE_TARGET}", &pkg_ref.target.to_string());
+        let archive =
+            match download_archive(&config, &self.repo_url, pkg_ref, project, reqwest, reporter)
+                .await
+            {
+                Ok(archive) => archive,
+                Err(errors::ArchiveError::Request(e)) if is_network_error(&e) => {
+                    let mut mirrored = None;
+
+                    for mirror in config.mirrors.iter().chain(project.mirrors(&self.repo_url)) {
+                        tracing::warn!(
+                            "failed to download {}@{} {} from {}, trying mirror {mirror}",
+                            pkg_ref.name,
+                            pkg_ref.version,
+                            pkg_ref.target,
+                            self.repo_url
+                        );
+
+                        match download_from_mirror(mirror, pkg_ref, project, reqwest, reporter)
+                            .await
+                        {
+                            Ok(archive) => {
+                                mirrored = Some(archive);
+                                break;
+                            }
+                            Err(e) => {
+                                tracing::warn!("failed to download from mirror {mirror}: {e}")
+                            }
+                        }
+                    }
+
+                    mirrored.ok_or(errors::ArchiveError::Request(e))?
+                }
+                Err(e) => return Err(e.into()),
+            };
 
-        let mut request = reqwest.get(&url).header(ACCEPT, "application/octet-stream");
+        let entries = extract_archive(&config, pkg_ref, project, archive.path(), reporter).await;
+        // the archive is removed even if it's invalid, so that it isn't resumed next time
+        archive
+            .remove()
+            .await
+            .map_err(|e| errors::DownloadError::Download(errors::ArchiveError::File(e)))?;
+        let fs = PackageFS::CAS(entries?);
 
-        if let Some(token) = project.auth_config.tokens().get(&self.repo_url) {
-            tracing::debug!("using token for {}", self.repo_url);
-            request = request.header(AUTHORIZATION, token);
+        if let Some(parent) = index_file.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(errors::DownloadError::WriteIndex)?;
+        }
+
+        fs::write(&index_file, toml::to_string(&fs)?)
+            .await
+            .map_err(errors::DownloadError::WriteIndex)?;
+
+        Ok((fs, pkg_ref.target.clone()))
+    }
+}
+
+/// The compression format of a package's archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ArchiveCompression {
+    /// Gzip, which all versions of pesde can extract
+    #[default]
+    Gzip,
+    /// Zstandard, which produces smaller archives that are faster to extract
+    Zstd,
+}
+
+impl ArchiveCompression {
+    /// Detects the compression of an archive from its magic bytes
+    pub fn detect(archive: &[u8]) -> Option<Self> {
+        if archive.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::Gzip)
+        } else if archive.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// The file extension of archives compressed with this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Gzip => "tar.gz",
+            Self::Zstd => "tar.zst",
+        }
+    }
+
+    /// The MIME type of archives compressed with this format
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Gzip => "application/gzip",
+            Self::Zstd => "application/zstd",
+        }
+    }
+
+    /// The HTTP content encoding of archives compressed with this format
+    pub fn content_encoding(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
         }
+    }
+
+    /// Compresses a tarball with this format
+    pub async fn compress(&self, tarball: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Self::Gzip => {
+                let mut encoder =
+                    async_compression::tokio::write::GzipEncoder::with_quality(vec![], Level::Best);
+                encoder.write_all(tarball).await?;
+                encoder.shutdown().await?;
+                Ok(encoder.into_inner())
+            }
+            Self::Zstd => {
+                let mut encoder = async_compression::tokio::write::ZstdEncoder::with_quality(
+                    vec![],
+                    Level::Precise(19),
+                );
+                encoder.write_all(tarball).await?;
+                encoder.shutdown().await?;
+                Ok(encoder.into_inner())
+            }
+        }
+    }
 
-        let response = request.send().await?.error_for_status()?;
-        let bytes = response.bytes().await?;
+    /// A reader decompressing an archive compressed with this format
+    pub fn decoder<'a, R: AsyncBufRead + Unpin + Send + 'a>(
+        &self,
+        archive: R,
+    ) -> Box<dyn AsyncRead + Unpin + Send + 'a> {
+        match self {
+            Self::Gzip => Box::new(async_compression::tokio::bufread::GzipDecoder::new(archive)),
+            Self::Zstd => Box::new(async_compression::tokio::bufread::ZstdDecoder::new(archive)),
+        }
+    }
+}
 
-        let mut decoder = async_compression::tokio::bufread::GzipDecoder::new(bytes.as_ref());
-        let mut archive = tokio_tar::Archive::new(&mut decoder);
+impl Display for ArchiveCompression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Gzip => write!(f, "gzip"),
+            Self::Zstd => write!(f, "zstd"),
+        }
+    }
+}
 
-        let mut entries = BTreeMap::new();
+impl FromStr for ArchiveCompression {
+    type Err = errors::ArchiveCompressionFromStr;
 
-        let mut archive_entries = archive.entries().map_err(errors::DownloadError::Unpack)?;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gzip" => Ok(Self::Gzip),
+            "zstd" => Ok(Self::Zstd),
+            _ => Err(errors::ArchiveCompressionFromStr(s.to_string())),
+        }
+    }
+}
 
-        while let Some(entry) = archive_entries
-            .next()
+/// Unpacks a package's compressed tarball into the CAS, streaming it from the reader
+pub(crate) async fn unpack_archive<R: AsyncBufRead + Unpin + Send>(
+    project: &Project,
+    mut reader: R,
+) -> Result<BTreeMap<RelativePathBuf, FSEntry>, errors::DownloadError> {
+    // archives in an unknown format fail to decode as gzip, the format of older archives
+    let compression = ArchiveCompression::detect(
+        reader
+            .fill_buf()
             .await
-            .transpose()
-            .map_err(errors::DownloadError::Unpack)?
+            .map_err(errors::DownloadError::Unpack)?,
+    )
+    .unwrap_or_default();
+    let mut decoder = compression.decoder(reader);
+    let mut archive = tokio_tar::Archive::new(&mut decoder);
+
+    let mut entries = BTreeMap::new();
+
+    let mut archive_entries = archive.entries().map_err(errors::DownloadError::Unpack)?;
+
+    while let Some(entry) = archive_entries
+        .next()
+        .await
+        .transpose()
+        .map_err(errors::DownloadError::Unpack)?
+    {
+        let path = entry.path().map_err(errors::DownloadError::Unpack)?;
+        // the entries are later written relative to the package's folder, which they mustn't escape
+        if path
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
         {
-            let path =
-                RelativePathBuf::from_path(entry.path().map_err(errors::DownloadError::Unpack)?)
-                    .unwrap();
-            let name = path.file_name().unwrap_or("");
-
-            if entry.header().entry_type().is_dir() {
-                if IGNORED_DIRS.contains(&name) {
-                    continue;
-                }
+            return Err(errors::DownloadError::InvalidPath(path.into_owned()));
+        }
 
-                entries.insert(path, FSEntry::Directory);
+        let path = RelativePathBuf::from_path(path).unwrap();
+        let name = path.file_name().unwrap_or("");
 
+        if entry.header().entry_type().is_dir() {
+            if IGNORED_DIRS.contains(&name) {
                 continue;
             }
 
-            if IGNORED_FILES.contains(&name) {
-                continue;
+            entries.insert(path, FSEntry::Directory);
+
+            continue;
+        }
+
+        if IGNORED_FILES.contains(&name) {
+            continue;
+        }
+
+        let hash = store_in_cas(project.cas_dir(), entry, |_| async { Ok(()) })
+            .await
+            .map_err(errors::DownloadError::Store)?;
+        entries.insert(path, FSEntry::File(hash));
+    }
+
+    Ok(entries)
+}
+
+fn sparse_base_url(url: &Url) -> Option<String> {
+    url.to_bstring()
+        .to_string()
+        .strip_prefix(SPARSE_INDEX_PREFIX)
+        .map(|url| url.trim_end_matches('/').to_string())
+}
+
+/// Whether the error is a failure to reach the server, as opposed to a rejected request
+fn is_network_error(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout() || e.status().is_some_and(|status| status.is_server_error())
+}
+
+/// An archive downloaded to disk, which is removed once it has been extracted
+enum DownloadedArchive {
+    /// A file named by the archive's checksum, which is kept if the download is interrupted so
+    /// that it can be resumed. It's locked until it's removed, so that other processes
+    /// downloading the same archive wait instead of writing to it at the same time
+    Partial(PathBuf, std::fs::File),
+    /// A temporary file, for archives whose checksum isn't known
+    Temporary(tempfile::TempPath),
+}
+
+impl DownloadedArchive {
+    fn path(&self) -> &Path {
+        match self {
+            DownloadedArchive::Partial(path, _) => path,
+            DownloadedArchive::Temporary(path) => path,
+        }
+    }
+
+    /// Opens the archive for writing
+    async fn open(&self) -> std::io::Result<fs::File> {
+        match self {
+            // the lock is shared with the duplicated handle
+            DownloadedArchive::Partial(path, file) => Ok(fs::File::from_std(
+                fs_err::File::from_parts(file.try_clone()?, path),
+            )),
+            DownloadedArchive::Temporary(path) => {
+                fs::OpenOptions::new().write(true).open(path).await
             }
+        }
+    }
 
-            let hash = store_in_cas(project.cas_dir(), entry, |_| async { Ok(()) })
-                .await
-                .map_err(errors::DownloadError::Store)?;
-            entries.insert(path, FSEntry::File(hash));
+    async fn remove(self) -> std::io::Result<()> {
+        match self {
+            // removed before the lock is released, so that waiting processes don't resume it
+            DownloadedArchive::Partial(path, _file) => fs::remove_file(path).await,
+            DownloadedArchive::Temporary(path) => path.close(),
+        }
+    }
+}
+
+/// Opens and locks the partial download at the path, waiting for other processes downloading the
+/// same archive to finish. As they remove the file once they've extracted it, the file is opened
+/// again if it's no longer the one at the path once the lock is acquired
+async fn lock_partial_download(path: PathBuf) -> std::io::Result<std::fs::File> {
+    spawn_blocking(move || loop {
+        if let Some(parent) = path.parent() {
+            fs_err::create_dir_all(parent)?;
         }
 
-        let fs = PackageFS::CAS(entries);
+        let file = fs_err::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?
+            .into_parts()
+            .0;
+        fs4::FileExt::lock_exclusive(&file)?;
+
+        match same_file::Handle::from_path(&path) {
+            Ok(handle) if handle == same_file::Handle::from_file(file.try_clone()?)? => {
+                return Ok(file)
+            }
+            Ok(_) => {}
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::NotFound | std::io::ErrorKind::PermissionDenied
+                ) => {}
+            Err(e) => return Err(e),
+        }
+    })
+    .await
+    .unwrap()
+}
 
-        if let Some(parent) = index_file.parent() {
-            fs::create_dir_all(parent)
+/// Downloads a package's archive to disk, as archives can be too large to be kept in memory
+async fn download_archive(
+    config: &IndexConfig,
+    index_url: &Url,
+    pkg_ref: &PesdePackageRef,
+    project: &Project,
+    reqwest: &reqwest::Client,
+    reporter: &dyn DownloadProgressReporter,
+) -> Result<DownloadedArchive, errors::ArchiveError> {
+    let url = config
+        .download()
+        .replace("{PACKAGE}", &pkg_ref.name.to_string().replace("/", "%2F"))
+        .replace("{PACKAGE_VERSION}", &pkg_ref.version.to_string())
+        .replace("{PACKAGE_TARGET}", &pkg_ref.target.to_string());
+
+    let mut request = reqwest.get(&url).header(ACCEPT, "application/octet-stream");
+
+    if let Some(token) = project.auth_config.tokens().get(index_url) {
+        tracing::debug!("using token for {index_url}");
+        request = request.header(AUTHORIZATION, token);
+    }
+
+    // the partial file is named by the checksum, so archives without one can't be resumed
+    let archive =
+        match pkg_ref.checksum.as_deref().filter(|checksum| {
+            !checksum.is_empty() && checksum.chars().all(|c| c.is_ascii_hexdigit())
+        }) {
+            Some(checksum) => {
+                let path = project.cas_dir().join(PARTIAL_DOWNLOADS_DIR).join(checksum);
+                let file = lock_partial_download(path.clone())
+                    .await
+                    .map_err(errors::ArchiveError::File)?;
+
+                DownloadedArchive::Partial(path, file)
+            }
+            None => {
+                let tmp_dir = project.cas_dir().join(".tmp");
+                fs::create_dir_all(&tmp_dir)
+                    .await
+                    .map_err(errors::ArchiveError::File)?;
+
+                DownloadedArchive::Temporary(
+                    tempfile::NamedTempFile::new_in(tmp_dir)
+                        .map_err(errors::ArchiveError::File)?
+                        .into_temp_path(),
+                )
+            }
+        };
+    let resume = matches!(archive, DownloadedArchive::Partial(..));
+
+    let result = project
+        .retry_policy()
+        .retry(
+            || async {
+                let request = request.try_clone().expect("GET requests can be cloned");
+                let mut file = archive.open().await.map_err(errors::ArchiveError::File)?;
+                download_to_file(
+                    request,
+                    &mut file,
+                    resume,
+                    config.max_archive_size as u64,
+                    reporter,
+                )
                 .await
-                .map_err(errors::DownloadError::WriteIndex)?;
+            },
+            errors::ArchiveError::is_transient,
+        )
+        .await;
+
+    match result {
+        Ok(()) => Ok(archive),
+        // an archive which is too large is never resumed
+        Err(e @ errors::ArchiveError::TooLarge(_)) => {
+            archive.remove().await.map_err(errors::ArchiveError::File)?;
+            Err(e)
         }
+        Err(e) => Err(e),
+    }
+}
 
-        fs::write(&index_file, toml::to_string(&fs)?)
+/// The position the body of a partial response starts at in the whole file, from its
+/// `Content-Range` header
+fn content_range_start(response: &reqwest::Response) -> Option<u64> {
+    response
+        .headers()
+        .get(CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes ")?
+        .split_once('-')?
+        .0
+        .parse()
+        .ok()
+}
+
+/// Downloads an archive into the file, aborting if it's larger than the limit. If resuming, the
+/// download continues from the bytes the file already contains, provided the server supports
+/// range requests
+async fn download_to_file(
+    request: RequestBuilder,
+    file: &mut fs::File,
+    resume: bool,
+    limit: u64,
+    reporter: &dyn DownloadProgressReporter,
+) -> Result<(), errors::ArchiveError> {
+    let mut downloaded = if resume {
+        file.metadata()
             .await
-            .map_err(errors::DownloadError::WriteIndex)?;
+            .map_err(errors::ArchiveError::File)?
+            .len()
+    } else {
+        0
+    };
+
+    let mut response = loop {
+        let request = request.try_clone().expect("GET requests can be cloned");
+        let request = if downloaded > 0 {
+            request.header(RANGE, format!("bytes={downloaded}-"))
+        } else {
+            request
+        };
+        let response = request.send().await?;
 
-        Ok((fs, pkg_ref.target.clone()))
+        match response.status() {
+            StatusCode::PARTIAL_CONTENT if content_range_start(&response) == Some(downloaded) => {
+                tracing::debug!(
+                    "resuming download of {} from byte {downloaded}",
+                    file.path().display()
+                );
+                break response;
+            }
+            // appending the body wouldn't produce the archive, so it's downloaded again
+            StatusCode::PARTIAL_CONTENT => {
+                tracing::debug!(
+                    "range of partial response doesn't start at byte {downloaded}, restarting download of {}",
+                    file.path().display()
+                );
+                downloaded = 0;
+            }
+            // the previous download was interrupted after receiving the whole archive
+            StatusCode::RANGE_NOT_SATISFIABLE if downloaded > 0 => return Ok(()),
+            _ => {
+                downloaded = 0;
+                break response.error_for_status()?;
+            }
+        }
+    };
+
+    if downloaded == 0 {
+        file.set_len(0).await.map_err(errors::ArchiveError::File)?;
+    }
+    file.seek(SeekFrom::Start(downloaded))
+        .await
+        .map_err(errors::ArchiveError::File)?;
+
+    let total = response.content_length().map(|len| downloaded + len);
+    reporter.report_progress(downloaded, total);
+
+    if total.is_some_and(|total| total > limit) {
+        return Err(errors::ArchiveError::TooLarge(limit));
+    }
+
+    while let Some(chunk) = response.chunk().await? {
+        downloaded += chunk.len() as u64;
+        if downloaded > limit {
+            return Err(errors::ArchiveError::TooLarge(limit));
+        }
+
+        file.write_all(&chunk)
+            .await
+            .map_err(errors::ArchiveError::File)?;
+        reporter.report_progress(downloaded, total);
+    }
+
+    file.flush().await.map_err(errors::ArchiveError::File)
+}
+
+/// Verifies a downloaded archive against its checksum and signature, and unpacks it into the CAS
+async fn extract_archive(
+    config: &IndexConfig,
+    pkg_ref: &PesdePackageRef,
+    project: &Project,
+    archive: &Path,
+    reporter: &dyn DownloadProgressReporter,
+) -> Result<BTreeMap<RelativePathBuf, FSEntry>, errors::DownloadError> {
+    if let Some(checksum) = &pkg_ref.checksum {
+        let actual = hash_file(archive)
+            .await
+            .map_err(|e| errors::DownloadError::Download(errors::ArchiveError::File(e)))?;
+        if actual != *checksum {
+            return Err(errors::DownloadError::ChecksumMismatch {
+                package: format!("{}@{} {}", pkg_ref.name, pkg_ref.version, pkg_ref.target),
+                expected: checksum.clone(),
+                actual,
+            });
+        }
+    }
+
+    verify_signature(config, pkg_ref, project, archive).await?;
+
+    reporter.report_extract();
+
+    let file = fs::File::open(archive)
+        .await
+        .map_err(|e| errors::DownloadError::Download(errors::ArchiveError::File(e)))?;
+    unpack_archive(project, BufReader::new(file)).await
+}
+
+/// Verifies a package's archive against its signature according to the project's signature policy
+async fn verify_signature(
+    config: &IndexConfig,
+    pkg_ref: &PesdePackageRef,
+    project: &Project,
+    archive: &Path,
+) -> Result<(), errors::DownloadError> {
+    let policy = project.signature_policy();
+    if policy == SignaturePolicy::Ignore {
+        return Ok(());
+    }
+
+    let package = format!("{}@{} {}", pkg_ref.name, pkg_ref.version, pkg_ref.target);
+
+    match &pkg_ref.signature {
+        Some(signature) if !config.trusted_keys.is_empty() => {
+            // signatures can only be verified against the whole archive
+            let bytes = fs::read(archive)
+                .await
+                .map_err(|e| errors::DownloadError::Download(errors::ArchiveError::File(e)))?;
+
+            signing::verify(&bytes, signature, &config.trusted_keys)
+                .map_err(|e| errors::DownloadError::Signature(package, e))
+        }
+        _ if policy == SignaturePolicy::Require => Err(errors::DownloadError::Unsigned(package)),
+        None if !config.trusted_keys.is_empty() => {
+            tracing::warn!("{package} is not signed by any of its index's trusted keys");
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Downloads a package's archive from a mirror, verifying it against the checksum recorded by the index
+async fn download_from_mirror(
+    mirror: &Url,
+    pkg_ref: &PesdePackageRef,
+    project: &Project,
+    reqwest: &reqwest::Client,
+    reporter: &dyn DownloadProgressReporter,
+) -> Result<DownloadedArchive, errors::MirrorError> {
+    let Some(checksum) = &pkg_ref.checksum else {
+        return Err(errors::MirrorError::MissingChecksum);
+    };
+
+    let source = PesdePackageSource::new(mirror.clone());
+    PackageSource::refresh(&source, project, reqwest)
+        .await
+        .map_err(Box::new)?;
+    let config = source.config(project).await.map_err(Box::new)?;
+
+    let archive = download_archive(&config, mirror, pkg_ref, project, reqwest, reporter).await?;
+    let actual = hash_file(archive.path())
+        .await
+        .map_err(errors::ArchiveError::File)?;
+    if actual != *checksum {
+        archive.remove().await.map_err(errors::ArchiveError::File)?;
+
+        return Err(errors::MirrorError::ChecksumMismatch {
+            expected: checksum.clone(),
+            actual,
+        });
     }
+
+    Ok(archive)
 }
 
 fn default_archive_size() -> usize {
@@ -331,6 +1147,12 @@ pub struct IndexConfig {
     /// The packages to display in the CLI for default script implementations
     #[serde(default)]
     pub scripts_packages: Vec<PackageName>,
+    /// Other indices serving the same packages, used when this one can't be reached
+    #[serde(default, deserialize_with = "crate::util::deserialize_gix_url_vec")]
+    pub mirrors: Vec<Url>,
+    /// The base64-encoded Ed25519 public keys trusted to sign packages' archives
+    #[serde(default)]
+    pub trusted_keys: Vec<String>,
 }
 
 impl IndexConfig {
@@ -356,7 +1178,7 @@ pub enum DocEntryKind {
     Page {
         /// The name of the page
         name: String,
-        /// The hash of the page's content
+        /// The SHA-256 checksum of the page's content, which it's stored under by the registry
         hash: String,
     },
     /// A category in the documentation
@@ -422,14 +1244,36 @@ pub struct IndexFileEntry {
     /// The repository of this package
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub repository: Option<url::Url>,
+    /// The maintainers of this package
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub maintainers: Vec<Maintainer>,
+    /// The URLs where this package can be funded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub funding: Vec<url::Url>,
 
     /// The documentation for this package
     #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
     pub docs: BTreeSet<DocEntry>,
+    /// The arbitrary metadata of this package
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    pub metadata: Metadata,
 
     /// The dependencies of this package
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub dependencies: BTreeMap<String, (DependencySpecifiers, DependencyType)>,
+
+    /// The SHA-256 checksum of this package's archive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    /// The base64-encoded signature of this package's archive, made by one of the index's trusted keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Whether this version has been yanked, which excludes it from new resolutions
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub yanked: bool,
+    /// The message this version has been deprecated with, if it is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
 }
 
 /// The index file for a package
@@ -437,10 +1281,99 @@ pub type IndexFile = BTreeMap<VersionId, IndexFileEntry>;
 
 /// Errors that can occur when interacting with the pesde package source
 pub mod errors {
+    use std::path::PathBuf;
     use thiserror::Error;
 
     use crate::source::git_index::errors::{ReadFile, TreeError};
 
+    /// Errors that can occur when refreshing a pesde package source
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum RefreshError {
+        /// Error refreshing the git index
+        #[error("error refreshing git index")]
+        Git(#[from] crate::source::git_index::errors::RefreshError),
+
+        /// Error fetching the config file from a sparse index
+        #[error("error fetching config file from sparse index")]
+        Sparse(#[from] SparseError),
+    }
+
+    /// Errors that can occur when downloading a package from a mirror
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum MirrorError {
+        /// The index didn't record a checksum to verify the mirrored archive against
+        #[error("no checksum recorded for the package to verify the mirrored archive against")]
+        MissingChecksum,
+
+        /// Error refreshing the mirror
+        #[error("error refreshing mirror")]
+        Refresh(#[from] Box<RefreshError>),
+
+        /// Error reading the mirror's config file
+        #[error("error reading mirror config file")]
+        Config(#[from] Box<ConfigError>),
+
+        /// Error downloading the archive from the mirror
+        #[error("error downloading package from mirror")]
+        Download(#[from] ArchiveError),
+
+        /// The mirrored archive doesn't match the recorded checksum
+        #[error("mirrored archive checksum {actual} doesn't match recorded checksum {expected}")]
+        ChecksumMismatch {
+            /// The recorded checksum
+            expected: String,
+            /// The checksum of the mirrored archive
+            actual: String,
+        },
+    }
+
+    /// An unknown archive compression format was parsed
+    #[derive(Debug, Error)]
+    #[error("unknown archive compression {0}, expected gzip or zstd")]
+    pub struct ArchiveCompressionFromStr(pub String);
+
+    /// Errors that can occur when downloading a package's archive
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum ArchiveError {
+        /// Error requesting the archive
+        #[error("error requesting archive")]
+        Request(#[from] reqwest::Error),
+
+        /// Error interacting with the downloaded archive on disk
+        #[error("error interacting with downloaded archive")]
+        File(#[source] std::io::Error),
+
+        /// The archive is larger than the index allows
+        #[error("archive is larger than the maximum size of {0} bytes")]
+        TooLarge(u64),
+    }
+
+    impl ArchiveError {
+        /// Whether the download failed in a way which may not occur again
+        pub fn is_transient(&self) -> bool {
+            match self {
+                ArchiveError::Request(e) => crate::retry::is_transient(e),
+                ArchiveError::File(_) | ArchiveError::TooLarge(_) => false,
+            }
+        }
+    }
+
+    /// Errors that can occur when fetching a file from a sparse index
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum SparseError {
+        /// Error interacting with the cached copy of the file
+        #[error("error interacting with the index cache")]
+        Io(#[from] std::io::Error),
+
+        /// Error requesting the file from the index
+        #[error("error requesting file from index")]
+        Request(#[from] reqwest::Error),
+    }
+
     /// Errors that can occur when resolving a package from a pesde package source
     #[derive(Debug, Error)]
     #[non_exhaustive]
@@ -464,6 +1397,10 @@ pub mod errors {
         /// Error parsing file for package
         #[error("error parsing file for {0}")]
         Parse(String, #[source] toml::de::Error),
+
+        /// Error fetching file for package from a sparse index
+        #[error("error fetching file for {0} from sparse index")]
+        Sparse(String, #[source] Box<SparseError>),
     }
 
     /// Errors that can occur when reading the config file for a pesde package source
@@ -489,6 +1426,10 @@ pub mod errors {
         /// The config file is missing
         #[error("missing config file for index at {0}")]
         Missing(Box<gix::Url>),
+
+        /// Error reading the cached config file of a sparse index
+        #[error("error reading cached config file")]
+        ReadCache(#[source] std::io::Error),
     }
 
     /// Errors that can occur when downloading a package from a pesde package source
@@ -501,12 +1442,16 @@ pub mod errors {
 
         /// Error downloading package
         #[error("error downloading package")]
-        Download(#[from] reqwest::Error),
+        Download(#[from] ArchiveError),
 
         /// Error unpacking package
         #[error("error unpacking package")]
         Unpack(#[source] std::io::Error),
 
+        /// The package's archive contains a path outside of the package
+        #[error("archive contains invalid path {0}")]
+        InvalidPath(PathBuf),
+
         /// Error storing file in CAS
         #[error("error storing file in CAS")]
         Store(#[source] std::io::Error),
@@ -526,5 +1471,29 @@ pub mod errors {
         /// Error writing index file
         #[error("error reading index file")]
         ReadIndex(#[source] std::io::Error),
+
+        /// The package's signature couldn't be verified
+        #[error("error verifying signature of {0}")]
+        Signature(
+            String,
+            #[source] crate::source::pesde::signing::errors::VerifyError,
+        ),
+
+        /// The package isn't signed, but the project requires signatures
+        #[error("{0} is not signed by a key trusted by its index, which the project requires")]
+        Unsigned(String),
+
+        /// The downloaded archive doesn't match the checksum recorded by the index
+        #[error(
+            "archive checksum {actual} of {package} doesn't match recorded checksum {expected}"
+        )]
+        ChecksumMismatch {
+            /// The package whose archive was downloaded
+            package: String,
+            /// The recorded checksum
+            expected: String,
+            /// The checksum of the downloaded archive
+            actual: String,
+        },
     }
 }
@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, path::PathBuf};
 
 use semver::Version;
 use serde::{Deserialize, Serialize};
@@ -7,6 +7,7 @@ use crate::{
     manifest::{target::Target, DependencyType},
     names::PackageName,
     source::{pesde::PesdePackageSource, DependencySpecifiers, PackageRef, PackageSources},
+    Project,
 };
 
 /// A pesde package reference
@@ -27,7 +28,25 @@ pub struct PesdePackageRef {
     pub dependencies: BTreeMap<String, (DependencySpecifiers, DependencyType)>,
     /// The target of the package
     pub target: Target,
+    /// The SHA-256 checksum of the package's archive, if recorded by the index
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    /// The signature of the package's archive, if signed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
 }
+impl PesdePackageRef {
+    /// The path of the cached file system of the package, written once it's been downloaded
+    pub(crate) fn index_file(&self, project: &Project) -> PathBuf {
+        project
+            .cas_dir
+            .join("index")
+            .join(self.name.escaped())
+            .join(self.version.to_string())
+            .join(self.target.to_string())
+    }
+}
+
 impl PackageRef for PesdePackageRef {
     fn dependencies(&self) -> &BTreeMap<String, (DependencySpecifiers, DependencyType)> {
         &self.dependencies
@@ -0,0 +1,83 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+
+/// The HTTP header carrying the signature of a published archive
+pub const SIGNATURE_HEADER: &str = "X-Pesde-Signature";
+
+/// An Ed25519 key used to sign packages' archives
+#[derive(Debug)]
+pub struct SigningKey(Ed25519KeyPair);
+
+impl SigningKey {
+    /// Parses a key from a base64-encoded PKCS#8 document
+    pub fn from_base64(key: &str) -> Result<Self, errors::SigningKeyError> {
+        let document = STANDARD.decode(key.trim())?;
+
+        Ed25519KeyPair::from_pkcs8_maybe_unchecked(&document)
+            .map(Self)
+            .map_err(|e| errors::SigningKeyError::Invalid(e.to_string()))
+    }
+
+    /// The base64-encoded public key, as declared in an index's trusted keys
+    pub fn public_key(&self) -> String {
+        STANDARD.encode(self.0.public_key().as_ref())
+    }
+
+    /// Signs an archive, returning the base64-encoded signature
+    pub fn sign(&self, archive: &[u8]) -> String {
+        STANDARD.encode(self.0.sign(archive).as_ref())
+    }
+}
+
+/// Verifies that an archive was signed by one of the trusted keys, all being base64-encoded
+pub fn verify<S: AsRef<str>>(
+    archive: &[u8],
+    signature: &str,
+    trusted_keys: &[S],
+) -> Result<(), errors::VerifyError> {
+    let signature = STANDARD.decode(signature)?;
+
+    for key in trusted_keys {
+        let key = STANDARD.decode(key.as_ref())?;
+
+        if UnparsedPublicKey::new(&ED25519, key)
+            .verify(archive, &signature)
+            .is_ok()
+        {
+            return Ok(());
+        }
+    }
+
+    Err(errors::VerifyError::Untrusted)
+}
+
+/// Errors that can occur when signing or verifying archives
+pub mod errors {
+    use thiserror::Error;
+
+    /// Errors that can occur when reading a signing key
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum SigningKeyError {
+        /// The key is not valid base64
+        #[error("signing key is not valid base64")]
+        Decode(#[from] base64::DecodeError),
+
+        /// The key is not a valid Ed25519 PKCS#8 document
+        #[error("signing key is not a valid Ed25519 PKCS#8 document: {0}")]
+        Invalid(String),
+    }
+
+    /// Errors that can occur when verifying an archive's signature
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum VerifyError {
+        /// The signature or a trusted key is not valid base64
+        #[error("signature or trusted key is not valid base64")]
+        Decode(#[from] base64::DecodeError),
+
+        /// The signature was not made by any of the trusted keys
+        #[error("signature was not made by any of the trusted keys")]
+        Untrusted,
+    }
+}
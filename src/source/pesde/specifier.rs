@@ -1,4 +1,8 @@
-use crate::{manifest::target::TargetKind, names::PackageName, source::DependencySpecifier};
+use crate::{
+    manifest::{platform::PlatformCondition, target::TargetKind},
+    names::PackageName,
+    source::DependencySpecifier,
+};
 use semver::VersionReq;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
@@ -16,6 +20,9 @@ pub struct PesdeDependencySpecifier {
     /// The target to use for the package
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub target: Option<TargetKind>,
+    /// The platform this dependency is restricted to
+    #[serde(flatten)]
+    pub platform: PlatformCondition,
 }
 impl DependencySpecifier for PesdeDependencySpecifier {}
 
@@ -18,6 +18,10 @@ pub enum PackageRefs {
     Git(crate::source::git::pkg_ref::GitPackageRef),
     /// A workspace package reference
     Workspace(crate::source::workspace::pkg_ref::WorkspacePackageRef),
+    /// A local registry package reference
+    Local(crate::source::local::pkg_ref::LocalPackageRef),
+    /// A custom package reference
+    Custom(crate::source::custom::pkg_ref::CustomPackageRef),
 }
 
 impl PackageRefs {
@@ -40,6 +44,8 @@ impl PackageRef for PackageRefs {
             PackageRefs::Wally(pkg_ref) => pkg_ref.dependencies(),
             PackageRefs::Git(pkg_ref) => pkg_ref.dependencies(),
             PackageRefs::Workspace(pkg_ref) => pkg_ref.dependencies(),
+            PackageRefs::Local(pkg_ref) => pkg_ref.dependencies(),
+            PackageRefs::Custom(pkg_ref) => pkg_ref.dependencies(),
         }
     }
 
@@ -50,6 +56,8 @@ impl PackageRef for PackageRefs {
             PackageRefs::Wally(pkg_ref) => pkg_ref.use_new_structure(),
             PackageRefs::Git(pkg_ref) => pkg_ref.use_new_structure(),
             PackageRefs::Workspace(pkg_ref) => pkg_ref.use_new_structure(),
+            PackageRefs::Local(pkg_ref) => pkg_ref.use_new_structure(),
+            PackageRefs::Custom(pkg_ref) => pkg_ref.use_new_structure(),
         }
     }
 
@@ -60,6 +68,8 @@ impl PackageRef for PackageRefs {
             PackageRefs::Wally(pkg_ref) => pkg_ref.source(),
             PackageRefs::Git(pkg_ref) => pkg_ref.source(),
             PackageRefs::Workspace(pkg_ref) => pkg_ref.source(),
+            PackageRefs::Local(pkg_ref) => pkg_ref.source(),
+            PackageRefs::Custom(pkg_ref) => pkg_ref.source(),
         }
     }
 }
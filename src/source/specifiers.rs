@@ -1,4 +1,7 @@
-use crate::source::{pesde, traits::DependencySpecifier};
+use crate::{
+    manifest::{platform::PlatformCondition, target::TargetKind},
+    source::{pesde, traits::DependencySpecifier},
+};
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
@@ -15,9 +18,53 @@ pub enum DependencySpecifiers {
     Git(crate::source::git::specifier::GitDependencySpecifier),
     /// A workspace dependency specifier
     Workspace(crate::source::workspace::specifier::WorkspaceDependencySpecifier),
+    /// A local registry dependency specifier
+    Local(crate::source::local::specifier::LocalDependencySpecifier),
+    /// A custom source dependency specifier
+    Custom(crate::source::custom::specifier::CustomDependencySpecifier),
 }
 impl DependencySpecifier for DependencySpecifiers {}
 
+impl DependencySpecifiers {
+    /// Returns the platform this dependency is restricted to
+    pub fn platform(&self) -> &PlatformCondition {
+        match self {
+            DependencySpecifiers::Pesde(specifier) => &specifier.platform,
+            #[cfg(feature = "wally-compat")]
+            DependencySpecifiers::Wally(specifier) => &specifier.platform,
+            DependencySpecifiers::Git(specifier) => &specifier.platform,
+            DependencySpecifiers::Workspace(specifier) => &specifier.platform,
+            DependencySpecifiers::Local(specifier) => &specifier.platform,
+            DependencySpecifiers::Custom(specifier) => &specifier.platform,
+        }
+    }
+
+    /// Returns the target this specifier explicitly requests, if any
+    pub fn target(&self) -> Option<&TargetKind> {
+        match self {
+            DependencySpecifiers::Pesde(specifier) => specifier.target.as_ref(),
+            DependencySpecifiers::Workspace(specifier) => specifier.target.as_ref(),
+            DependencySpecifiers::Local(specifier) => specifier.target.as_ref(),
+            #[cfg(feature = "wally-compat")]
+            DependencySpecifiers::Wally(_) => None,
+            DependencySpecifiers::Git(_) | DependencySpecifiers::Custom(_) => None,
+        }
+    }
+
+    /// Returns the range of versions this specifier accepts, if it specifies one
+    pub fn version_req(&self) -> Option<&semver::VersionReq> {
+        match self {
+            DependencySpecifiers::Pesde(specifier) => Some(&specifier.version),
+            #[cfg(feature = "wally-compat")]
+            DependencySpecifiers::Wally(specifier) => Some(&specifier.version),
+            DependencySpecifiers::Local(specifier) => Some(&specifier.version),
+            DependencySpecifiers::Git(_)
+            | DependencySpecifiers::Workspace(_)
+            | DependencySpecifiers::Custom(_) => None,
+        }
+    }
+}
+
 impl Display for DependencySpecifiers {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -26,6 +73,8 @@ impl Display for DependencySpecifiers {
             DependencySpecifiers::Wally(specifier) => write!(f, "{specifier}"),
             DependencySpecifiers::Git(specifier) => write!(f, "{specifier}"),
             DependencySpecifiers::Workspace(specifier) => write!(f, "{specifier}"),
+            DependencySpecifiers::Local(specifier) => write!(f, "{specifier}"),
+            DependencySpecifiers::Custom(specifier) => write!(f, "{specifier}"),
         }
     }
 }
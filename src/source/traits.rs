@@ -4,6 +4,7 @@ use crate::{
         target::{Target, TargetKind},
         DependencyType,
     },
+    reporters::DownloadProgressReporter,
     source::{DependencySpecifiers, PackageFS, PackageSources, ResolveResult},
     Project,
 };
@@ -39,7 +40,11 @@ pub trait PackageSource: Debug {
     type DownloadError: std::error::Error;
 
     /// Refreshes the source
-    async fn refresh(&self, _project: &Project) -> Result<(), Self::RefreshError> {
+    async fn refresh(
+        &self,
+        _project: &Project,
+        _reqwest: &reqwest::Client,
+    ) -> Result<(), Self::RefreshError> {
         Ok(())
     }
 
@@ -48,6 +53,7 @@ pub trait PackageSource: Debug {
         &self,
         specifier: &Self::Specifier,
         project: &Project,
+        reqwest: &reqwest::Client,
         project_target: TargetKind,
         refreshed_sources: &mut HashSet<PackageSources>,
     ) -> Result<ResolveResult<Self::Ref>, Self::ResolveError>;
@@ -58,5 +64,6 @@ pub trait PackageSource: Debug {
         pkg_ref: &Self::Ref,
         project: &Project,
         reqwest: &reqwest::Client,
+        reporter: &dyn DownloadProgressReporter,
     ) -> Result<(PackageFS, Target), Self::DownloadError>;
 }
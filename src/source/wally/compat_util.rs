@@ -5,9 +5,9 @@ use serde::Deserialize;
 use tempfile::TempDir;
 
 use crate::{
-    manifest::target::Target,
+    manifest::target::{Target, TargetKind},
     scripts::{execute_script, ScriptName},
-    source::wally::manifest::{Realm, WallyManifest},
+    source::wally::manifest::WallyManifest,
     Project, LINK_LIB_NO_FILE_FOUND,
 };
 use fs_err::tokio as fs;
@@ -71,10 +71,22 @@ pub(crate) async fn get_target(
     let manifest = fs::read_to_string(&manifest).await?;
     let manifest: WallyManifest = toml::from_str(&manifest)?;
 
-    Ok(if matches!(manifest.package.realm, Realm::Shared) {
-        Target::Roblox { lib, build_files }
+    let target_kind = project
+        .wally_realms()
+        .target_kind(&manifest.package.name, manifest.package.realm);
+
+    Ok(if target_kind == TargetKind::Roblox {
+        Target::Roblox {
+            lib,
+            types: None,
+            build_files,
+        }
     } else {
-        Target::RobloxServer { lib, build_files }
+        Target::RobloxServer {
+            lib,
+            types: None,
+            build_files,
+        }
     })
 }
 
@@ -1,22 +1,35 @@
 use std::collections::BTreeMap;
 
 use crate::{
-    manifest::{errors, DependencyType},
+    manifest::{errors, target::TargetKind, DependencyType},
     names::wally::WallyPackageName,
     source::{specifiers::DependencySpecifiers, wally::specifier::WallyDependencySpecifier},
 };
 use semver::{Version, VersionReq};
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize};
 use tracing::instrument;
 
-#[derive(Deserialize, Clone, Debug)]
+/// The realm of a Wally package
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum Realm {
+    /// The package can be used by both the client and the server
     #[serde(alias = "dev")]
     Shared,
+    /// The package can only be used by the server
     Server,
 }
 
+impl Realm {
+    /// The target packages of this realm resolve to
+    pub fn target_kind(self) -> TargetKind {
+        match self {
+            Realm::Shared => TargetKind::Roblox,
+            Realm::Server => TargetKind::RobloxServer,
+        }
+    }
+}
+
 #[derive(Deserialize, Clone, Debug)]
 #[serde(rename_all = "kebab-case")]
 pub struct WallyPackage {
@@ -43,6 +56,7 @@ pub fn deserialize_specifiers<'de, D: Deserializer<'de>>(
                     name: name.parse().map_err(serde::de::Error::custom)?,
                     version: VersionReq::parse(version).map_err(serde::de::Error::custom)?,
                     index: None,
+                    platform: Default::default(),
                 },
             ))
         })
@@ -1,27 +1,24 @@
 use crate::{
     manifest::target::{Target, TargetKind},
-    names::PackageNames,
+    names::{wally::WallyPackageName, PackageNames},
+    reporters::{read_response, DownloadProgressReporter},
     source::{
         fs::{store_in_cas, FSEntry, PackageFS},
         git_index::{read_file, root_tree, GitBasedSource},
         traits::PackageSource,
         version_id::VersionId,
-        wally::{
-            compat_util::get_target,
-            manifest::{Realm, WallyManifest},
-            pkg_ref::WallyPackageRef,
-        },
+        wally::{compat_util::get_target, manifest::WallyManifest, pkg_ref::WallyPackageRef},
         PackageSources, ResolveResult, IGNORED_DIRS, IGNORED_FILES,
     },
     util::hash,
-    Project,
+    AuthConfig, Project,
 };
 use fs_err::tokio as fs;
 use futures::future::try_join_all;
-use gix::Url;
+use gix::{sec::identity::Account, Url};
 use relative_path::RelativePathBuf;
 use reqwest::header::AUTHORIZATION;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::{BTreeMap, HashSet},
     path::PathBuf,
@@ -34,11 +31,22 @@ use tracing::instrument;
 
 pub(crate) mod compat_util;
 pub(crate) mod manifest;
+pub use manifest::Realm;
+
 /// The Wally package reference
 pub mod pkg_ref;
 /// The Wally dependency specifier
 pub mod specifier;
 
+/// The Wally version sent to Wally registries in the `Wally-Version` header, unless overridden
+/// with the `PESDE_WALLY_VERSION` environment variable
+pub const WALLY_VERSION: &str = "0.3.2";
+
+/// The Wally version to send to Wally registries in the `Wally-Version` header
+pub fn wally_version() -> String {
+    std::env::var("PESDE_WALLY_VERSION").unwrap_or_else(|_| WALLY_VERSION.to_string())
+}
+
 /// The Wally package source
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub struct WallyPackageSource {
@@ -56,6 +64,10 @@ impl GitBasedSource for WallyPackageSource {
     fn repo_url(&self) -> &Url {
         &self.repo_url
     }
+
+    fn git_credentials(&self, auth_config: &AuthConfig) -> Option<Account> {
+        auth_config.wally_git_credentials_for(&self.repo_url)
+    }
 }
 
 impl WallyPackageSource {
@@ -97,8 +109,12 @@ impl PackageSource for WallyPackageSource {
     type DownloadError = errors::DownloadError;
 
     #[instrument(skip_all, level = "debug")]
-    async fn refresh(&self, project: &Project) -> Result<(), Self::RefreshError> {
-        GitBasedSource::refresh(self, project).await
+    async fn refresh(
+        &self,
+        project: &Project,
+        reqwest: &reqwest::Client,
+    ) -> Result<(), Self::RefreshError> {
+        GitBasedSource::refresh(self, project, reqwest).await
     }
 
     #[instrument(skip_all, level = "debug")]
@@ -106,6 +122,7 @@ impl PackageSource for WallyPackageSource {
         &self,
         specifier: &Self::Specifier,
         project: &Project,
+        reqwest: &reqwest::Client,
         project_target: TargetKind,
         refreshed_sources: &mut HashSet<PackageSources>,
     ) -> Result<ResolveResult<Self::Ref>, Self::ResolveError> {
@@ -124,7 +141,7 @@ impl PackageSource for WallyPackageSource {
                 for registry in config.fallback_registries {
                     let source = WallyPackageSource::new(registry.clone());
                     if refreshed_sources.insert(PackageSources::Wally(source.clone())) {
-                        GitBasedSource::refresh(&source, project)
+                        GitBasedSource::refresh(&source, project, reqwest)
                             .await
                             .map_err(Box::new)?;
                     }
@@ -132,7 +149,8 @@ impl PackageSource for WallyPackageSource {
                     match Box::pin(source.resolve(
                         specifier,
                         project,
-                        project_target,
+                        reqwest,
+                        project_target.clone(),
                         refreshed_sources,
                     ))
                     .await
@@ -177,10 +195,9 @@ impl PackageSource for WallyPackageSource {
                     Ok((
                         VersionId(
                             manifest.package.version.clone(),
-                            match manifest.package.realm {
-                                Realm::Server => TargetKind::RobloxServer,
-                                _ => TargetKind::Roblox,
-                            },
+                            project
+                                .wally_realms()
+                                .target_kind(&manifest.package.name, manifest.package.realm),
                         ),
                         WallyPackageRef {
                             name: specifier.name.clone(),
@@ -202,13 +219,10 @@ impl PackageSource for WallyPackageSource {
         pkg_ref: &Self::Ref,
         project: &Project,
         reqwest: &reqwest::Client,
+        reporter: &dyn DownloadProgressReporter,
     ) -> Result<(PackageFS, Target), Self::DownloadError> {
         let config = self.config(project).await.map_err(Box::new)?;
-        let index_file = project
-            .cas_dir
-            .join("wally_index")
-            .join(pkg_ref.name.escaped())
-            .join(pkg_ref.version.to_string());
+        let index_file = pkg_ref.index_file(project);
 
         let tempdir = match fs::read_to_string(&index_file).await {
             Ok(s) => {
@@ -237,20 +251,26 @@ impl PackageSource for WallyPackageSource {
                 config.api.as_str().trim_end_matches('/'),
                 pkg_ref.version
             ))
-            .header(
-                "Wally-Version",
-                std::env::var("PESDE_WALLY_VERSION")
-                    .as_deref()
-                    .unwrap_or("0.3.2"),
-            );
+            .header("Wally-Version", wally_version());
 
         if let Some(token) = project.auth_config.tokens().get(&self.repo_url) {
             tracing::debug!("using token for {}", self.repo_url);
             request = request.header(AUTHORIZATION, token);
         }
 
-        let response = request.send().await?.error_for_status()?;
-        let mut bytes = response.bytes().await?;
+        let mut bytes = project
+            .retry_policy()
+            .retry(
+                || async {
+                    let request = request.try_clone().expect("GET requests can be cloned");
+                    let response = request.send().await?.error_for_status()?;
+                    read_response(response, reporter).await
+                },
+                crate::retry::is_transient,
+            )
+            .await?;
+
+        reporter.report_extract();
 
         let archive = async_zip::tokio::read::seek::ZipFileReader::with_tokio(
             std::io::Cursor::new(&mut bytes),
@@ -332,6 +352,36 @@ impl PackageSource for WallyPackageSource {
     }
 }
 
+/// How the realms of Wally packages translate into pesde targets, and so which packages folder
+/// they're installed into
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WallyRealmsConfig {
+    /// The realm packages published to the shared realm are treated as
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared: Option<Realm>,
+    /// The realm packages published to the server realm are treated as
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<Realm>,
+    /// The realms specific packages are treated as, regardless of the realm they're published to
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub packages: BTreeMap<WallyPackageName, Realm>,
+}
+
+impl WallyRealmsConfig {
+    /// The realm a package published to the given realm is treated as
+    pub fn realm(&self, name: &WallyPackageName, realm: Realm) -> Realm {
+        self.packages.get(name).copied().unwrap_or(match realm {
+            Realm::Shared => self.shared.unwrap_or(Realm::Shared),
+            Realm::Server => self.server.unwrap_or(Realm::Server),
+        })
+    }
+
+    /// The target a package published to the given realm resolves to
+    pub fn target_kind(&self, name: &WallyPackageName, realm: Realm) -> TargetKind {
+        self.realm(name, realm).target_kind()
+    }
+}
+
 /// A Wally index config
 #[derive(Debug, Clone, Deserialize)]
 pub struct WallyIndexConfig {
@@ -340,6 +390,13 @@ pub struct WallyIndexConfig {
     fallback_registries: Vec<gix::Url>,
 }
 
+impl WallyIndexConfig {
+    /// The URL of the API
+    pub fn api(&self) -> &str {
+        self.api.as_str().trim_end_matches('/')
+    }
+}
+
 /// Errors that can occur when interacting with a Wally package source
 pub mod errors {
     use thiserror::Error;
@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, path::PathBuf};
 
 use semver::Version;
 use serde::{Deserialize, Serialize};
@@ -7,6 +7,7 @@ use crate::{
     manifest::DependencyType,
     names::wally::WallyPackageName,
     source::{wally::WallyPackageSource, DependencySpecifiers, PackageRef, PackageSources},
+    Project,
 };
 
 /// A Wally package reference
@@ -27,6 +28,17 @@ pub struct WallyPackageRef {
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub dependencies: BTreeMap<String, (DependencySpecifiers, DependencyType)>,
 }
+impl WallyPackageRef {
+    /// The path of the cached file system of the package, written once it's been downloaded
+    pub(crate) fn index_file(&self, project: &Project) -> PathBuf {
+        project
+            .cas_dir
+            .join("wally_index")
+            .join(self.name.escaped())
+            .join(self.version.to_string())
+    }
+}
+
 impl PackageRef for WallyPackageRef {
     fn dependencies(&self) -> &BTreeMap<String, (DependencySpecifiers, DependencyType)> {
         &self.dependencies
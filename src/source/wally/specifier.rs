@@ -3,7 +3,10 @@ use std::fmt::Display;
 use semver::VersionReq;
 use serde::{Deserialize, Serialize};
 
-use crate::{names::wally::WallyPackageName, source::DependencySpecifier};
+use crate::{
+    manifest::platform::PlatformCondition, names::wally::WallyPackageName,
+    source::DependencySpecifier,
+};
 
 /// The specifier for a Wally dependency
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
@@ -16,6 +19,9 @@ pub struct WallyDependencySpecifier {
     /// The index to use for the package
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub index: Option<String>,
+    /// The platform this dependency is restricted to
+    #[serde(flatten)]
+    pub platform: PlatformCondition,
 }
 impl DependencySpecifier for WallyDependencySpecifier {}
 
@@ -1,13 +1,19 @@
 use crate::{
-    manifest::target::{Target, TargetKind},
+    errors::ManifestReadError,
+    manifest::{
+        target::{Target, TargetKind},
+        Manifest,
+    },
     names::PackageNames,
+    reporters::DownloadProgressReporter,
     source::{
-        fs::PackageFS, specifiers::DependencySpecifiers, traits::PackageSource,
-        version_id::VersionId, workspace::pkg_ref::WorkspacePackageRef, PackageSources,
-        ResolveResult,
+        fs::PackageFS, local::relative_registry_path, specifiers::DependencySpecifiers,
+        traits::PackageSource, version_id::VersionId, workspace::pkg_ref::WorkspacePackageRef,
+        PackageSources, ResolveResult,
     },
-    Project, DEFAULT_INDEX_NAME,
+    Project, MANIFEST_FILE_NAME,
 };
+use fs_err::tokio as fs;
 use futures::StreamExt;
 use relative_path::RelativePathBuf;
 use reqwest::Client;
@@ -31,7 +37,11 @@ impl PackageSource for WorkspacePackageSource {
     type ResolveError = errors::ResolveError;
     type DownloadError = errors::DownloadError;
 
-    async fn refresh(&self, _project: &Project) -> Result<(), Self::RefreshError> {
+    async fn refresh(
+        &self,
+        _project: &Project,
+        _reqwest: &reqwest::Client,
+    ) -> Result<(), Self::RefreshError> {
         // no-op
         Ok(())
     }
@@ -41,6 +51,7 @@ impl PackageSource for WorkspacePackageSource {
         &self,
         specifier: &Self::Specifier,
         project: &Project,
+        _reqwest: &reqwest::Client,
         project_target: TargetKind,
         _refreshed_sources: &mut HashSet<PackageSources>,
     ) -> Result<ResolveResult<Self::Ref>, Self::ResolveError> {
@@ -49,15 +60,33 @@ impl PackageSource for WorkspacePackageSource {
                 .workspace_dir
                 .as_ref()
                 .unwrap_or(&project.package_dir);
-            let target = specifier.target.unwrap_or(project_target);
+            let target = specifier.target.clone().unwrap_or(project_target.clone());
 
             let members = project.workspace_members(workspace_dir, true).await?;
             pin!(members);
 
+            // a member of a compatible target is only used if none has the exact target
+            let mut compatible = None;
+
             while let Some((path, manifest)) = members.next().await.transpose()? {
-                if manifest.name == specifier.name && manifest.target.kind() == target {
+                if manifest.name != specifier.name {
+                    continue;
+                }
+
+                if manifest.target.kind() == target {
                     break 'finder (path, manifest);
                 }
+
+                if specifier.target.is_none()
+                    && compatible.is_none()
+                    && project.is_target_compatible(&project_target, &manifest.target.kind())
+                {
+                    compatible = Some((path, manifest));
+                }
+            }
+
+            if let Some(compatible) = compatible {
+                break 'finder compatible;
             }
 
             return Err(errors::ResolveError::NoWorkspaceMember(
@@ -85,13 +114,14 @@ impl PackageSource for WorkspacePackageSource {
                         .map(|(alias, (mut spec, ty))| {
                             match &mut spec {
                                 DependencySpecifiers::Pesde(spec) => {
-                                    let index_name =
-                                        spec.index.as_deref().unwrap_or(DEFAULT_INDEX_NAME);
+                                    let index_name = spec
+                                        .index
+                                        .as_deref()
+                                        .unwrap_or(manifest.default_index_name());
 
                                     spec.index = Some(
-                                        manifest
-                                            .indices
-                                            .get(index_name)
+                                        project
+                                            .index_url(&manifest.indices, index_name)
                                             .ok_or(errors::ResolveError::IndexNotFound(
                                                 index_name.to_string(),
                                                 manifest.name.to_string(),
@@ -101,8 +131,10 @@ impl PackageSource for WorkspacePackageSource {
                                 }
                                 #[cfg(feature = "wally-compat")]
                                 DependencySpecifiers::Wally(spec) => {
-                                    let index_name =
-                                        spec.index.as_deref().unwrap_or(DEFAULT_INDEX_NAME);
+                                    let index_name = spec
+                                        .index
+                                        .as_deref()
+                                        .unwrap_or(manifest.default_index_name());
 
                                     spec.index = Some(
                                         manifest
@@ -117,6 +149,11 @@ impl PackageSource for WorkspacePackageSource {
                                 }
                                 DependencySpecifiers::Git(_) => {}
                                 DependencySpecifiers::Workspace(_) => {}
+                                DependencySpecifiers::Local(spec) => {
+                                    spec.registry =
+                                        relative_registry_path(project, &path.join(&spec.registry));
+                                }
+                                DependencySpecifiers::Custom(_) => {}
                             }
 
                             Ok((alias, (spec, ty)))
@@ -134,11 +171,24 @@ impl PackageSource for WorkspacePackageSource {
         pkg_ref: &Self::Ref,
         project: &Project,
         _reqwest: &Client,
+        _reporter: &dyn DownloadProgressReporter,
     ) -> Result<(PackageFS, Target), Self::DownloadError> {
         let path = pkg_ref.path.to_path(project.workspace_dir.clone().unwrap());
+        // the member's packages folders may be for the custom targets it declares
+        let manifest = toml::from_str::<Manifest>(
+            &fs::read_to_string(path.join(MANIFEST_FILE_NAME))
+                .await
+                .map_err(ManifestReadError::Io)?,
+        )
+        .map_err(ManifestReadError::Serde)?;
+        let target_kinds = TargetKind::VARIANTS
+            .iter()
+            .cloned()
+            .chain(manifest.custom_targets.into_keys().map(TargetKind::Custom))
+            .collect();
 
         Ok((
-            PackageFS::Copy(path, pkg_ref.target.kind()),
+            PackageFS::Copy(path, pkg_ref.target.kind(), target_kinds),
             pkg_ref.target.clone(),
         ))
     }
@@ -182,5 +232,9 @@ pub mod errors {
         /// An error occurred reading the workspace members
         #[error("failed to read workspace members")]
         ReadWorkspaceMembers(#[from] std::io::Error),
+
+        /// An error occurred reading the member's manifest
+        #[error("failed to read the member's manifest")]
+        ManifestRead(#[from] crate::errors::ManifestReadError),
     }
 }
@@ -1,4 +1,8 @@
-use crate::{manifest::target::TargetKind, names::PackageName, source::DependencySpecifier};
+use crate::{
+    manifest::{platform::PlatformCondition, target::TargetKind},
+    names::PackageName,
+    source::DependencySpecifier,
+};
 use serde::{Deserialize, Serialize};
 use serde_with::{DeserializeFromStr, SerializeDisplay};
 use std::{fmt::Display, str::FromStr};
@@ -14,6 +18,9 @@ pub struct WorkspaceDependencySpecifier {
     pub version: VersionTypeOrReq,
     /// The target of the workspace package
     pub target: Option<TargetKind>,
+    /// The platform this dependency is restricted to
+    #[serde(flatten)]
+    pub platform: PlatformCondition,
 }
 impl DependencySpecifier for WorkspaceDependencySpecifier {}
 
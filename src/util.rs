@@ -1,18 +1,19 @@
-use crate::AuthConfig;
-use gix::bstr::BStr;
+use gix::{bstr::BStr, sec::identity::Account};
 use serde::{Deserialize, Deserializer, Serializer};
 use sha2::{Digest, Sha256};
 use std::collections::{BTreeMap, HashSet};
 
+// the error type is defined by gix
+#[allow(clippy::result_large_err)]
 pub fn authenticate_conn(
     conn: &mut gix::remote::Connection<
         '_,
         '_,
         Box<dyn gix::protocol::transport::client::Transport + Send>,
     >,
-    auth_config: &AuthConfig,
+    credentials: Option<Account>,
 ) {
-    if let Some(iden) = auth_config.git_credentials().cloned() {
+    if let Some(iden) = credentials {
         conn.set_credentials(move |action| match action {
             gix::credentials::helper::Action::Get(ctx) => {
                 Ok(Some(gix::credentials::protocol::Outcome {
@@ -83,3 +84,22 @@ pub fn deserialize_git_like_url<'de, D: Deserializer<'de>>(
 pub fn hash<S: AsRef<[u8]>>(struc: S) -> String {
     format!("{:x}", Sha256::digest(struc.as_ref()))
 }
+
+pub async fn hash_file(path: &std::path::Path) -> std::io::Result<String> {
+    use tokio::io::AsyncReadExt;
+
+    let mut file = fs_err::tokio::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buf[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}